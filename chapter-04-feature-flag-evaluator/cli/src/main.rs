@@ -0,0 +1,242 @@
+//! Native command-line front end for the feature flag evaluator.
+//!
+//! `ff-eval` runs the same evaluation as the WASI module without a wasm
+//! runtime, so targeting rules can be tried out locally:
+//!
+//! * `ff-eval eval flags.json --context '{"userId":"u1"}'` evaluates a flag
+//!   set document for a context; `--key` picks a single flag.
+//! * `ff-eval explain ...` does the same and adds the per-rule trace.
+//! * `ff-eval validate flags.json` lints a flag set document.
+//! * `ff-eval bucket paywall u1` prints the rollout bucket of a user.
+//!
+//! Evaluation builds an input document and hands it to
+//! [`ff_eval_wasi_app::evaluate_document`], so the output is byte-for-byte
+//! what the WASI module prints for the same flags and context, and failures
+//! are the same error documents with the same exit statuses.
+
+use clap::{Args, Parser, Subcommand};
+use ff_eval_core::{bucket_with, Flag, HashAlgorithm};
+use ff_eval_wasi_app::{evaluate_document, load_flags_file, DocumentError};
+use serde::de::Error as _;
+use std::io::Read;
+use std::path::PathBuf;
+
+/// Exit status when a flag set document fails validation, shared with the
+/// WASI module's `FLAGS_FILE`.
+const EXIT_INVALID: i32 = 6;
+/// Exit status for command-line usage errors, shared with the WASI module.
+const EXIT_USAGE: i32 = 7;
+
+#[derive(Debug, Parser)]
+#[command(name = "ff-eval", version, about = "Evaluate feature flags locally")]
+struct Cli {
+    /// Pretty-print the output document.
+    #[arg(long, global = true)]
+    pretty: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Evaluate the flags of a flag set document for a context.
+    Eval(EvalArgs),
+    /// Like `eval`, adding the per-rule evaluation trace to each result.
+    Explain(EvalArgs),
+    /// Lint a flag set document without evaluating it.
+    Validate {
+        /// Flag set document, `{"flags": [...]}`.
+        flags: PathBuf,
+    },
+    /// Print the deterministic rollout bucket, in [0, 1), of a user for a flag.
+    Bucket(BucketArgs),
+}
+
+#[derive(Debug, Args)]
+struct EvalArgs {
+    /// Flag set document, `{"flags": [...]}`.
+    flags: PathBuf,
+    /// Context as JSON, `@path` to read it from a file, or `-` for standard
+    /// input.
+    #[arg(short, long, default_value = "-")]
+    context: String,
+    /// Evaluate only the flag with this key and print its result instead of
+    /// the payload keyed by flag key.
+    #[arg(short, long)]
+    key: Option<String>,
+    /// Value of the `now` builtin, as an RFC 3339 timestamp.
+    #[arg(long)]
+    now: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct BucketArgs {
+    /// Flag key.
+    key: String,
+    /// Value being bucketed, normally the context's `userId`.
+    user_id: String,
+    /// The flag's `salt`, if it has one.
+    #[arg(long)]
+    salt: Option<String>,
+    /// The flag's `hash`: `fnv1a` or `murmur3`.
+    #[arg(long, default_value = "fnv1a", value_parser = parse_hash)]
+    hash: HashAlgorithm,
+}
+
+fn main() {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(err) => {
+            // `--help` and `--version` also arrive here, on stdout.
+            let code = if err.use_stderr() { EXIT_USAGE } else { 0 };
+            let _ = err.print();
+            std::process::exit(code);
+        }
+    };
+    match run(cli.command) {
+        Ok((output, code)) => {
+            if cli.pretty {
+                println!("{output:#}");
+            } else {
+                println!("{output}");
+            }
+            std::process::exit(code);
+        }
+        Err(err) => {
+            eprintln!("{}", err.to_json());
+            std::process::exit(err.exit_code());
+        }
+    }
+}
+
+/// The output document and the exit status to report it with.
+fn run(command: Command) -> Result<(serde_json::Value, i32), DocumentError> {
+    match command {
+        Command::Eval(args) => Ok((evaluate_document(&eval_input(&args, false)?)?, 0)),
+        Command::Explain(args) => Ok((evaluate_document(&eval_input(&args, true)?)?, 0)),
+        Command::Validate { flags } => {
+            let flags = load_flags_file(&flags)?;
+            let input = serde_json::json!({ "flags": flags_json(&flags)?, "validate": true });
+            let output = evaluate_document(&input.to_string())?;
+            let code = if output["valid"] == true {
+                0
+            } else {
+                EXIT_INVALID
+            };
+            Ok((output, code))
+        }
+        Command::Bucket(args) => Ok((bucket_json(&args), 0)),
+    }
+}
+
+/// The input document `ff-eval eval` and `ff-eval explain` evaluate.
+fn eval_input(args: &EvalArgs, explain: bool) -> Result<String, DocumentError> {
+    let flags = load_flags_file(&args.flags)?;
+    let mut input =
+        serde_json::json!({ "context": read_context(&args.context)?, "explain": explain });
+    match &args.key {
+        Some(key) => {
+            let flag = flags.iter().find(|flag| &flag.key == key).ok_or_else(|| {
+                parse_error(format!("no flag `{key}` in {}", args.flags.display()))
+            })?;
+            input["flag"] = serde_json::to_value(flag).map_err(DocumentError::Parse)?;
+        }
+        None => input["flags"] = flags_json(&flags)?,
+    }
+    if let Some(now) = &args.now {
+        input["now"] = now.as_str().into();
+    }
+    Ok(input.to_string())
+}
+
+fn flags_json(flags: &[Flag]) -> Result<serde_json::Value, DocumentError> {
+    serde_json::to_value(flags).map_err(DocumentError::Parse)
+}
+
+/// Parse the `--context` argument: inline JSON, `@path` or `-`.
+fn read_context(arg: &str) -> Result<serde_json::Value, DocumentError> {
+    let text = match arg {
+        "-" => {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|err| DocumentError::Parse(serde_json::Error::io(err)))?;
+            text
+        }
+        _ => match arg.strip_prefix('@') {
+            Some(path) => std::fs::read_to_string(path)
+                .map_err(|err| parse_error(format!("cannot read context file {path}: {err}")))?,
+            None => arg.to_owned(),
+        },
+    };
+    serde_json::from_str(&text).map_err(DocumentError::Parse)
+}
+
+fn bucket_json(args: &BucketArgs) -> serde_json::Value {
+    // Seed like the evaluator does, so the bucket matches `rollout(p)`.
+    let flag = Flag {
+        key: args.key.clone(),
+        salt: args.salt.clone(),
+        ..Flag::default()
+    };
+    let bucket = bucket_with(args.hash.hasher(), &flag.bucket_seed(), &args.user_id);
+    serde_json::json!({ "key": args.key, "userId": args.user_id, "bucket": bucket })
+}
+
+fn parse_hash(name: &str) -> Result<HashAlgorithm, String> {
+    HashAlgorithm::parse(name).ok_or_else(|| format!("unknown hash `{name}`"))
+}
+
+fn parse_error(message: String) -> DocumentError {
+    DocumentError::Parse(serde_json::Error::custom(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_definition_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn eval_matches_the_wasi_module() {
+        let vector: serde_json::Value =
+            serde_json::from_str(include_str!("../../tests/vectors/t1.json")).unwrap();
+        let flags = std::env::temp_dir().join(format!("ff-eval-cli-{}.json", std::process::id()));
+        let document = serde_json::json!({ "flags": [vector["flag"]] });
+        std::fs::write(&flags, document.to_string()).unwrap();
+        let args = |key: Option<&str>| EvalArgs {
+            flags: flags.clone(),
+            context: vector["context"].to_string(),
+            key: key.map(str::to_owned),
+            now: None,
+        };
+        let (single, code) = run(Command::Eval(args(Some("paywall")))).unwrap();
+        let expected = evaluate_document(&vector.to_string()).unwrap();
+        assert_eq!((single, code), (expected.clone(), 0));
+        let (payload, _) = run(Command::Eval(args(None))).unwrap();
+        assert_eq!(payload["paywall"]["enabled"], expected["enabled"]);
+        let (explained, _) = run(Command::Explain(args(Some("paywall")))).unwrap();
+        assert!(explained["trace"].is_array());
+        let missing = run(Command::Eval(args(Some("nope")))).unwrap_err();
+        assert_eq!(missing.code(), "PARSE");
+        std::fs::remove_file(&flags).unwrap();
+    }
+
+    #[test]
+    fn bucket_matches_rollout() {
+        let args = BucketArgs {
+            key: "paywall".to_owned(),
+            user_id: "u123".to_owned(),
+            salt: None,
+            hash: HashAlgorithm::Fnv1a,
+        };
+        let output = bucket_json(&args);
+        let bucket = output["bucket"].as_f64().unwrap();
+        assert_eq!(bucket, ff_eval_core::bucket("paywall", "u123"));
+        assert!(ff_eval_core::rollout("paywall", "u123", bucket + 1e-9));
+    }
+}
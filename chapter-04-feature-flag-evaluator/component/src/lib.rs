@@ -0,0 +1,22 @@
+//! Component-model build of the feature flag evaluator.
+//!
+//! Exports the `evaluator` world from `wit/ff-eval.wit`.  The export is a
+//! thin wrapper over [`ff_eval_wasi_app::evaluate_parts`], so a component
+//! host gets the same output document as the stdin/stdout protocol.
+
+wit_bindgen::generate!({
+    world: "evaluator",
+    path: "../wit",
+});
+
+struct Evaluator;
+
+impl Guest for Evaluator {
+    fn evaluate(flag: String, context: String) -> Result<String, String> {
+        ff_eval_wasi_app::evaluate_parts(&flag, &context)
+            .map(|output| output.to_string())
+            .map_err(|err| err.to_string())
+    }
+}
+
+export!(Evaluator);
@@ -0,0 +1,41 @@
+//! Feeds arbitrary rule text to everything that parses rules.  None of it
+//! may panic or overflow the stack, and expressions over the limits must be
+//! refused in linear time.  Run with `cargo fuzz run eval_rule_expr` from
+//! `core/`.
+#![no_main]
+
+use ff_eval_core::{
+    check_rule, eval_flag_with_trace, eval_rule_expr, validate_flag, Context, Flag, Rule, Value,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(expr) = std::str::from_utf8(data) else {
+        return;
+    };
+    let ctx: Context = [
+        ("userId", Value::Str("u1".into())),
+        ("country", Value::Str("CA".into())),
+        ("age", Value::Num(30.0)),
+        ("roles", Value::List(vec![Value::Str("admin".into())])),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect();
+
+    let _ = check_rule(expr);
+    let _ = eval_rule_expr("fuzz", expr, &ctx);
+
+    let flag = Flag {
+        key: "fuzz".to_string(),
+        rules: vec![Rule {
+            cond: expr.to_string(),
+            then_value: true,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let _ = validate_flag(&flag);
+    let _ = flag.referenced_attributes();
+    let _ = eval_flag_with_trace(&flag, &ctx);
+});
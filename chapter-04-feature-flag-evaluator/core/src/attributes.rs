@@ -0,0 +1,160 @@
+//! Which context attributes a flag reads.
+//!
+//! Hosts that load user attributes from a profile store can ask a flag what
+//! it needs with [`Flag::referenced_attributes`] and fetch only those keys
+//! before evaluating.  The walk follows the grammar of
+//! [`crate::eval_rule_expr`] without evaluating anything.
+
+use crate::functions::call_parts;
+use crate::{
+    check_limits, is_identifier, is_literal, parse_bucketing_call, split_args, split_top_level,
+    Flag, COMPARISON_OPERATORS,
+};
+use std::collections::BTreeSet;
+
+impl Flag {
+    /// The context keys the flag's rules can read: compared and tested
+    /// attributes, the lists passed to `any`/`all`, and the `by` and `per`
+    /// attributes of bucketing calls (`userId` unless `by` names another),
+    /// and the arguments of custom builtins.
+    /// A context holding these keys evaluates exactly like the full context.
+    ///
+    /// The flag's state is not consulted, and malformed rules, which are
+    /// always skipped, contribute nothing.
+    pub fn referenced_attributes(&self) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        for rule in &self.rules {
+            if check_limits(rule.cond.trim()).is_ok() {
+                collect_expr(rule.cond.trim(), &mut names);
+            }
+        }
+        names
+    }
+}
+
+fn collect_expr(s: &str, out: &mut BTreeSet<String>) {
+    for sep in ["||", "&&"] {
+        if let Some(idx) = split_top_level(s, sep) {
+            collect_expr(s[..idx].trim(), out);
+            collect_expr(s[idx + 2..].trim(), out);
+            return;
+        }
+    }
+    for op in COMPARISON_OPERATORS {
+        if let Some(idx) = split_top_level(s, op) {
+            let (lhs, rhs) = (s[..idx].trim(), s[idx + op.len()..].trim());
+            collect_term(lhs, out);
+            // A regex pattern and an `in (...)` list are always literals.
+            let literal_rhs = op == "~=" || (op.trim() == "in" && rhs.starts_with('('));
+            if !literal_rhs {
+                collect_term(rhs, out);
+            }
+            return;
+        }
+    }
+    collect_term(s, out);
+}
+
+fn collect_term(t: &str, out: &mut BTreeSet<String>) {
+    for (name, positional) in [
+        ("variant(", 1),
+        ("rollout(", 1),
+        ("ramp(", 4),
+        ("bucket(", 1),
+    ] {
+        if let Some(inner) = t.strip_prefix(name).and_then(|rest| rest.strip_suffix(')')) {
+            if let Ok((_, bucketing)) = parse_bucketing_call(inner, positional) {
+                out.insert(bucketing.by.to_string());
+                out.extend(bucketing.per.map(str::to_string));
+            }
+            return;
+        }
+    }
+    for name in ["exists(", "missing("] {
+        if let Some(inner) = t.strip_prefix(name).and_then(|rest| rest.strip_suffix(')')) {
+            if is_identifier(inner.trim()) {
+                out.insert(inner.trim().to_string());
+            }
+            return;
+        }
+    }
+    for name in ["any(", "all("] {
+        if let Some(inner) = t.strip_prefix(name).and_then(|rest| rest.strip_suffix(')')) {
+            if let Some(list) = split_args(inner).first() {
+                collect_term(list, out);
+            }
+            return;
+        }
+    }
+    // A custom builtin sees only its arguments.
+    if let Some((_, inner)) = call_parts(t) {
+        for arg in split_args(inner) {
+            collect_term(arg, out);
+        }
+        return;
+    }
+    if t != "now" && !is_literal(t) && is_identifier(t) {
+        out.insert(t.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Flag, Rule};
+
+    fn flag(conds: &[&str]) -> Flag {
+        Flag {
+            key: "beta".to_string(),
+            rules: conds
+                .iter()
+                .map(|cond| Rule {
+                    cond: cond.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn names(flag: &Flag) -> Vec<String> {
+        flag.referenced_attributes().into_iter().collect()
+    }
+
+    #[test]
+    fn collects_the_attributes_rules_read() {
+        let flag = flag(&[
+            "country in ('CA', 'US') && plan != 'free'",
+            "'admin' in roles || any(groups, 'beta', 'staff')",
+            "email ~= '^[a-z]+@corp\\.' && exists(accountId)",
+            "rollout(0.2, by='orgId', per='region') || bucket(10) < 3",
+            "ramp('2025-03-01T00:00:00Z', '2025-03-15T00:00:00Z', 0.1, 1, by='teamId')",
+            "variant({'a': 0.5, 'b': 0.5}, by='deviceId') == 'b'",
+            "now >= '2025-03-01T00:00:00Z' && missing(trialEnd) && true",
+            "geo_distance(lat, lon, 45.5) < 50",
+        ]);
+        assert_eq!(
+            names(&flag),
+            [
+                "accountId",
+                "country",
+                "deviceId",
+                "email",
+                "groups",
+                "lat",
+                "lon",
+                "orgId",
+                "plan",
+                "region",
+                "roles",
+                "teamId",
+                "trialEnd",
+                "userId"
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_rules_read_nothing() {
+        assert!(names(&flag(&["rollout(0.2, by=orgId)", "exists('x')", "true"])).is_empty());
+    }
+}
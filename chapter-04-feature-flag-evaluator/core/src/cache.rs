@@ -0,0 +1,237 @@
+//! Memoized evaluation for hot paths.
+//!
+//! A host that evaluates the same flag for the same user on every request
+//! can keep an [`EvalCache`].  Results are keyed by the flag and the values
+//! of its [`crate::Flag::referenced_attributes`], so attributes a flag never
+//! reads do not split its entries.  Evaluation has no clock here: the
+//! cache only serves clockless evaluation, which is a pure function of the
+//! flag and those attributes.
+//!
+//! The cache does not see flag definitions change.  Call
+//! [`EvalCache::invalidate`] after changing a flag evaluated through
+//! [`EvalCache::eval`]; [`EvalCache::eval_in`] tracks the [`FlagSet`]
+//! fingerprint and drops everything when a reload changes it.
+
+use crate::{CompiledFlag, Context, EvalResult, Fingerprint, FlagSet, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Cached results keyed by flag and context attributes.
+#[derive(Debug, Clone)]
+pub struct EvalCache {
+    max_entries: usize,
+    /// Attributes each cached flag reads, in sorted order.
+    attributes: HashMap<String, Vec<String>>,
+    entries: HashMap<(String, u64), Entry>,
+    /// Fingerprint of the set the entries came from, for [`EvalCache::eval_in`].
+    fingerprint: Option<Fingerprint>,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    /// The attribute values the result was computed from, compared on
+    /// lookup so a hash collision is a miss rather than a wrong answer.
+    values: Vec<Option<Value>>,
+    result: EvalResult,
+}
+
+impl EvalCache {
+    /// A cache holding at most `max_entries` results.  When it is full the
+    /// next miss empties it, which keeps memory bounded without tracking
+    /// recency on the hot path.
+    pub fn new(max_entries: usize) -> Self {
+        EvalCache {
+            max_entries: max_entries.max(1),
+            attributes: HashMap::new(),
+            entries: HashMap::new(),
+            fingerprint: None,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// [`CompiledFlag::eval`], served from the cache when the flag was
+    /// already evaluated with the same values for the attributes it reads.
+    pub fn eval(&mut self, flag: &CompiledFlag, ctx: &Context) -> EvalResult {
+        self.lookup(flag, ctx, || flag.eval(ctx))
+    }
+
+    /// [`FlagSet::eval`] through the cache, or `None` if the set has no flag
+    /// named `key`.  The cache is emptied whenever the set's fingerprint
+    /// differs from the one its entries were computed under.
+    pub fn eval_in(&mut self, set: &FlagSet, key: &str, ctx: &Context) -> Option<EvalResult> {
+        if self.fingerprint != Some(set.fingerprint()) {
+            self.clear();
+            self.fingerprint = Some(set.fingerprint());
+        }
+        let flag = set.compiled(key)?;
+        Some(self.lookup(flag, ctx, || {
+            set.eval(key, ctx).expect("the set has the flag")
+        }))
+    }
+
+    /// Drop the cached results of the flag named `key`, e.g. after its
+    /// definition changed.
+    pub fn invalidate(&mut self, key: &str) {
+        self.attributes.remove(key);
+        self.entries.retain(|(flag, _), _| flag != key);
+    }
+
+    /// Drop every cached result.
+    pub fn clear(&mut self) {
+        self.attributes.clear();
+        self.entries.clear();
+        self.fingerprint = None;
+    }
+
+    /// Number of cached results.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Lookups answered from the cache and lookups that evaluated, since the
+    /// cache was created.
+    pub fn hits_and_misses(&self) -> (u64, u64) {
+        (self.hits, self.misses)
+    }
+
+    fn lookup(
+        &mut self,
+        flag: &CompiledFlag,
+        ctx: &Context,
+        eval: impl FnOnce() -> EvalResult,
+    ) -> EvalResult {
+        let key = &flag.flag().key;
+        let attributes = self
+            .attributes
+            .entry(key.clone())
+            .or_insert_with(|| flag.flag().referenced_attributes().into_iter().collect());
+        let values: Vec<Option<Value>> = attributes.iter().map(|a| ctx.get(a).cloned()).collect();
+        let mut hasher = DefaultHasher::new();
+        for value in &values {
+            hash_value(value.as_ref(), &mut hasher);
+        }
+        let slot = (key.clone(), hasher.finish());
+        if let Some(entry) = self.entries.get(&slot) {
+            if entry.values == values {
+                self.hits += 1;
+                return entry.result.clone();
+            }
+        }
+        self.misses += 1;
+        let result = eval();
+        if self.entries.len() >= self.max_entries {
+            self.entries.clear();
+        }
+        self.entries.insert(
+            slot,
+            Entry {
+                values,
+                result: result.clone(),
+            },
+        );
+        result
+    }
+}
+
+fn hash_value(value: Option<&Value>, state: &mut impl Hasher) {
+    match value {
+        None => 0u8.hash(state),
+        Some(Value::Str(s)) => (1u8, s).hash(state),
+        Some(Value::Num(n)) => (2u8, n.to_bits()).hash(state),
+        Some(Value::Bool(b)) => (3u8, b).hash(state),
+        Some(Value::List(items)) => {
+            (4u8, items.len()).hash(state);
+            for item in items {
+                hash_value(Some(item), state);
+            }
+        }
+        Some(Value::Time(t)) => (5u8, t).hash(state),
+        Some(Value::Null) => 6u8.hash(state),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Flag, Rule};
+
+    fn flag(key: &str, cond: &str) -> Flag {
+        Flag {
+            key: key.to_string(),
+            rules: vec![Rule {
+                cond: cond.to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn ctx(pairs: &[(&str, &str)]) -> Context {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), Value::Str(v.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn keys_on_the_attributes_the_flag_reads() {
+        let compiled = CompiledFlag::compile(flag(
+            "beta",
+            "country == 'CA' && rollout(0.5, by='accountId')",
+        ))
+        .unwrap();
+        let mut cache = EvalCache::new(16);
+        let a = ctx(&[("country", "CA"), ("accountId", "acct-1"), ("page", "home")]);
+        let b = ctx(&[("country", "CA"), ("accountId", "acct-1"), ("page", "cart")]);
+        let c = ctx(&[("country", "CA"), ("accountId", "acct-2")]);
+        for ctx in [&a, &b, &c, &a] {
+            assert_eq!(
+                cache.eval(&compiled, ctx).enabled,
+                compiled.eval(ctx).enabled
+            );
+        }
+        // `page` is never read, so `b` shares `a`'s entry.
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.hits_and_misses(), (2, 2));
+
+        let changed = CompiledFlag::compile(flag("beta", "country == 'US'")).unwrap();
+        cache.invalidate("beta");
+        assert!(cache.is_empty());
+        assert!(!cache.eval(&changed, &a).enabled);
+    }
+
+    #[test]
+    fn flag_set_reloads_empty_the_cache() {
+        let mut set = FlagSet::load(vec![flag("beta", "country == 'CA'")]).unwrap();
+        let mut cache = EvalCache::new(16);
+        let ca = ctx(&[("country", "CA")]);
+        assert!(cache.eval_in(&set, "beta", &ca).unwrap().enabled);
+        assert!(cache.eval_in(&set, "beta", &ca).unwrap().enabled);
+        assert!(cache.eval_in(&set, "missing", &ca).is_none());
+
+        set.reload(vec![flag("beta", "country == 'US'")]).unwrap();
+        let result = cache.eval_in(&set, "beta", &ca).unwrap();
+        assert!(!result.enabled);
+        assert_eq!(result.flagset_fingerprint, Some(set.fingerprint()));
+        assert_eq!(cache.hits_and_misses(), (1, 2));
+    }
+
+    #[test]
+    fn a_full_cache_starts_over() {
+        let compiled = CompiledFlag::compile(flag("beta", "country == 'CA'")).unwrap();
+        let mut cache = EvalCache::new(2);
+        for country in ["CA", "US", "FR"] {
+            cache.eval(&compiled, &ctx(&[("country", country)]));
+        }
+        assert_eq!(cache.len(), 1);
+    }
+}
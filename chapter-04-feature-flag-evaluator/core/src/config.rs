@@ -0,0 +1,467 @@
+//! Loading a whole flag set from a configuration document.
+//!
+//! A document is `{"flags": [...]}`, where each flag has the shape of the
+//! `flag` object in the evaluator input, as published in
+//! `contracts/flagset.schema.json` ([`SCHEMA`]).  Loading checks the document
+//! against that schema and every rule against the expression grammar, and
+//! reports each problem with its path and, where it can be found in the
+//! source, its line and column, so a bad config fails at deploy time rather
+//! than as a silently skipped rule.
+//!
+//! Overrides live in a separate document, `{"environments": {...}}`, mapping
+//! each environment name to the flag values it forces
+//! (`contracts/overrides.schema.json`, [`OVERRIDES_SCHEMA`]).  Loading one
+//! picks out a single environment's [`Overrides`].
+
+use crate::{
+    check_rule_with, CompileErrorKind, CompiledFlag, Flag, FlagSet, FunctionRegistry, Overrides,
+};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// The JSON Schema a configuration document must satisfy.
+pub const SCHEMA: &str = include_str!("../../contracts/flagset.schema.json");
+
+/// The JSON Schema an overrides document must satisfy.
+pub const OVERRIDES_SCHEMA: &str = include_str!("../../contracts/overrides.schema.json");
+
+/// Most rules a flag may carry, matching `maxItems` in the schema.
+pub const MAX_RULES: usize = 64;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Document {
+    #[serde(rename = "$schema", default)]
+    _schema: Option<String>,
+    flags: Vec<Flag>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OverridesDocument {
+    #[serde(rename = "$schema", default)]
+    _schema: Option<String>,
+    environments: BTreeMap<String, BTreeMap<String, bool>>,
+}
+
+/// A 1-based line and column in the source document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// One problem found while loading a configuration document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Where the problem is, e.g. `flags[0].rules[2].if`; empty for
+    /// document-level syntax errors.
+    pub path: String,
+    pub position: Option<Position>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(Position { line, column }) = self.position {
+            write!(f, "{line}:{column}: ")?;
+        }
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Every problem found in a configuration document, in document order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{diagnostic}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Parse and validate a JSON configuration document.
+pub fn parse_json(text: &str) -> Result<Vec<Flag>, ConfigError> {
+    parse_json_with(text, None)
+}
+
+/// Parse and validate a YAML configuration document.
+#[cfg(feature = "yaml")]
+pub fn parse_yaml(text: &str) -> Result<Vec<Flag>, ConfigError> {
+    parse_yaml_with(text, None)
+}
+
+fn parse_json_with(
+    text: &str,
+    functions: Option<&FunctionRegistry>,
+) -> Result<Vec<Flag>, ConfigError> {
+    let document: Document = from_json(text)?;
+    validate(document.flags, text, functions, |cond| {
+        serde_json::to_string(cond).ok()
+    })
+}
+
+#[cfg(feature = "yaml")]
+fn parse_yaml_with(
+    text: &str,
+    functions: Option<&FunctionRegistry>,
+) -> Result<Vec<Flag>, ConfigError> {
+    let document: Document = from_yaml(text)?;
+    validate(document.flags, text, functions, |cond| {
+        Some(cond.to_string())
+    })
+}
+
+/// Parse a JSON overrides document and return the overrides for
+/// `environment`.  An environment the document does not list has none.
+pub fn parse_overrides_json(text: &str, environment: &str) -> Result<Overrides, ConfigError> {
+    select_environment(from_json(text)?, environment)
+}
+
+/// Parse a YAML overrides document and return the overrides for
+/// `environment`.  An environment the document does not list has none.
+#[cfg(feature = "yaml")]
+pub fn parse_overrides_yaml(text: &str, environment: &str) -> Result<Overrides, ConfigError> {
+    select_environment(from_yaml(text)?, environment)
+}
+
+fn from_json<T: DeserializeOwned>(text: &str) -> Result<T, ConfigError> {
+    serde_json::from_str(text).map_err(|err| {
+        let position = (err.line() > 0).then(|| Position {
+            line: err.line(),
+            column: err.column(),
+        });
+        single(position, strip_location(&err.to_string()))
+    })
+}
+
+#[cfg(feature = "yaml")]
+fn from_yaml<T: DeserializeOwned>(text: &str) -> Result<T, ConfigError> {
+    serde_yaml::from_str(text).map_err(|err| {
+        let position = err.location().map(|at| Position {
+            line: at.line(),
+            column: at.column(),
+        });
+        single(position, strip_location(&err.to_string()))
+    })
+}
+
+/// Check every environment, not only the selected one, so a mistake in
+/// any environment fails wherever the document is loaded.
+fn select_environment(
+    document: OverridesDocument,
+    environment: &str,
+) -> Result<Overrides, ConfigError> {
+    let mut diagnostics = Vec::new();
+    for (name, values) in &document.environments {
+        if name.is_empty() {
+            diagnostics.push(Diagnostic {
+                path: "environments".to_string(),
+                position: None,
+                message: "environment name must not be empty".to_string(),
+            });
+        }
+        if values.contains_key("") {
+            diagnostics.push(Diagnostic {
+                path: format!("environments.{name}"),
+                position: None,
+                message: "flag key must not be empty".to_string(),
+            });
+        }
+    }
+    if !diagnostics.is_empty() {
+        return Err(ConfigError { diagnostics });
+    }
+    let mut environments = document.environments;
+    Ok(environments
+        .remove(environment)
+        .unwrap_or_default()
+        .into_iter()
+        .collect())
+}
+
+/// [`parse_json`] and load the flags into a [`FlagSet`].
+pub fn load_json(text: &str) -> Result<FlagSet, ConfigError> {
+    into_flagset(parse_json(text)?, None)
+}
+
+/// [`parse_yaml`] and load the flags into a [`FlagSet`].
+#[cfg(feature = "yaml")]
+pub fn load_yaml(text: &str) -> Result<FlagSet, ConfigError> {
+    into_flagset(parse_yaml(text)?, None)
+}
+
+/// [`load_json`] for flags evaluated with `functions`, so rules calling its
+/// custom builtins are not rejected as malformed.
+pub fn load_json_with_functions(
+    text: &str,
+    functions: &FunctionRegistry,
+) -> Result<FlagSet, ConfigError> {
+    into_flagset(parse_json_with(text, Some(functions))?, Some(functions))
+}
+
+/// [`load_yaml`] for flags evaluated with `functions`.  See
+/// [`load_json_with_functions`].
+#[cfg(feature = "yaml")]
+pub fn load_yaml_with_functions(
+    text: &str,
+    functions: &FunctionRegistry,
+) -> Result<FlagSet, ConfigError> {
+    into_flagset(parse_yaml_with(text, Some(functions))?, Some(functions))
+}
+
+fn into_flagset(
+    flags: Vec<Flag>,
+    functions: Option<&FunctionRegistry>,
+) -> Result<FlagSet, ConfigError> {
+    // `validate` already compiled every flag, so this cannot fail.
+    FlagSet::load_with(flags, functions).map_err(|err| single(None, err.to_string()))
+}
+
+fn single(position: Option<Position>, message: String) -> ConfigError {
+    ConfigError {
+        diagnostics: vec![Diagnostic {
+            path: String::new(),
+            position,
+            message,
+        }],
+    }
+}
+
+/// serde appends " at line L column C"; the position is reported separately.
+fn strip_location(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(idx) => message[..idx].to_string(),
+        None => message.to_string(),
+    }
+}
+
+/// Check what serde cannot: unique non-empty keys, the rule limit, window
+/// order and rule syntax.  `needle` renders a condition as it appears in the
+/// source so the diagnostic can point at it.
+fn validate(
+    flags: Vec<Flag>,
+    text: &str,
+    functions: Option<&FunctionRegistry>,
+    needle: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<Flag>, ConfigError> {
+    let mut diagnostics = Vec::new();
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    let mut cursor = 0;
+    for (i, flag) in flags.iter().enumerate() {
+        let mut report = |path: String, position: Option<Position>, message: String| {
+            diagnostics.push(Diagnostic {
+                path,
+                position,
+                message,
+            })
+        };
+        if flag.key.is_empty() {
+            report(
+                format!("flags[{i}].key"),
+                None,
+                "key must not be empty".to_string(),
+            );
+        } else if let Some(first) = seen.insert(&flag.key, i) {
+            report(
+                format!("flags[{i}].key"),
+                None,
+                format!(
+                    "duplicate key `{}`, first defined by flags[{first}]",
+                    flag.key
+                ),
+            );
+        }
+        if flag.rules.len() > MAX_RULES {
+            report(
+                format!("flags[{i}].rules"),
+                None,
+                format!("{} rules exceed the limit of {MAX_RULES}", flag.rules.len()),
+            );
+        }
+        for (j, rule) in flag.rules.iter().enumerate() {
+            // Conditions appear in document order, so search forward only.
+            let found =
+                needle(&rule.cond).and_then(|n| text[cursor..].find(&n).map(|at| cursor + at));
+            if let Some(at) = found {
+                cursor = at + 1;
+            }
+            let position = found.map(|at| position_of(text, at));
+            if let Err(message) = check_rule_with(&rule.cond, functions) {
+                report(format!("flags[{i}].rules[{j}].if"), position, message);
+            }
+            if let (Some(from), Some(until)) = (rule.active_from, rule.active_until) {
+                if from >= until {
+                    report(
+                        format!("flags[{i}].rules[{j}]"),
+                        position,
+                        "activeFrom must be before activeUntil".to_string(),
+                    );
+                }
+            }
+        }
+        // Unknown builtins were already reported as malformed rules.
+        if let Err(err) = CompiledFlag::compile_with(flag.clone(), functions) {
+            if err.kind == CompileErrorKind::InvalidPattern {
+                report(
+                    format!("flags[{i}].rules[{}].if", err.rule),
+                    None,
+                    format!("invalid pattern '{}': {}", err.pattern, err.message),
+                );
+            }
+        }
+    }
+    if diagnostics.is_empty() {
+        Ok(flags)
+    } else {
+        Err(ConfigError { diagnostics })
+    }
+}
+
+/// 1-based line and column (in characters) of a byte offset.
+fn position_of(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    Position {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_a_valid_document() {
+        let text = r#"{
+  "$schema": "./flagset.schema.json",
+  "flags": [
+    { "key": "paywall", "default": false, "rules": [{ "if": "country == 'CA'", "then": true }] },
+    { "key": "beta", "default": false, "rules": [{ "if": "rollout(0.1)", "then": true }],
+      "metadata": { "owner": "growth", "expires": "2025-06-01T00:00:00Z", "tags": ["experiment"] } }
+  ]
+}"#;
+        let set = load_json(text).unwrap();
+        assert_eq!(set.keys().collect::<Vec<_>>(), ["beta", "paywall"]);
+        let beta = &set.get("beta").unwrap().metadata;
+        assert_eq!(beta.owner.as_deref(), Some("growth"));
+        assert_eq!(beta.tags, ["experiment"]);
+        assert!(set.get("paywall").unwrap().metadata.is_empty());
+    }
+
+    #[test]
+    fn accepts_registered_functions_only_when_given() {
+        let text = r#"{"flags": [
+  {"key": "nearby", "rules": [{"if": "geo_distance(lat, lon) < 50", "then": true}]}
+]}"#;
+        let err = load_json(text).unwrap_err();
+        assert_eq!(err.diagnostics[0].path, "flags[0].rules[0].if");
+
+        let mut functions = FunctionRegistry::new();
+        functions
+            .register("geo_distance", |_| Ok(crate::Value::Num(0.0)))
+            .unwrap();
+        let set = load_json_with_functions(text, &functions).unwrap();
+        let result = set
+            .eval_with(
+                "nearby",
+                &crate::Context::new(),
+                &mut crate::EvalOptions {
+                    functions: Some(&functions),
+                    ..crate::EvalOptions::default()
+                },
+            )
+            .unwrap();
+        assert!(result.enabled);
+    }
+
+    #[test]
+    fn reports_malformed_rules_with_positions() {
+        let text = r#"{"flags": [
+  {"key": "a", "default": false, "rules": [
+    {"if": "country == 'CA'", "then": true},
+    {"if": "rollout(high)", "then": true}
+  ]},
+  {"key": "a", "default": false, "rules": [
+    {"if": "plan in ('pro', gold)", "then": true,
+     "activeFrom": "2025-02-01T00:00:00Z", "activeUntil": "2025-01-01T00:00:00Z"}
+  ]}
+]}"#;
+        let err = parse_json(text).unwrap_err();
+        let rendered: Vec<String> = err.diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            rendered,
+            [
+                "4:12: flags[0].rules[1].if: `rollout(...)` needs a numeric share, found `high`",
+                "flags[1].key: duplicate key `a`, first defined by flags[0]",
+                "7:12: flags[1].rules[0].if: `in` list item `gold` is not a quoted string",
+                "7:12: flags[1].rules[0]: activeFrom must be before activeUntil",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_schema_violations_with_serde_positions() {
+        let err = parse_json("{\"flags\": [\n  {\"key\": \"a\", \"rules\": [], \"extra\": 1}\n]}")
+            .unwrap_err();
+        assert_eq!(err.diagnostics.len(), 1);
+        let diagnostic = &err.diagnostics[0];
+        assert_eq!(diagnostic.position.map(|p| p.line), Some(2));
+        assert!(diagnostic.message.starts_with("unknown field `extra`"));
+        assert!(parse_json(r#"{"flags": [{"key": "a", "rules": [], "hash": "md5"}]}"#).is_err());
+        assert!(parse_json(r#"{"flag": []}"#).is_err());
+    }
+
+    #[test]
+    fn loads_the_overrides_of_one_environment() {
+        let text = r#"{"environments": {
+  "dev": {"paywall": true, "beta": true},
+  "staging": {"paywall": false}
+}}"#;
+        let dev = parse_overrides_json(text, "dev").unwrap();
+        assert_eq!(dev.get("paywall"), Some(&true));
+        assert_eq!(dev.len(), 2);
+        let staging = parse_overrides_json(text, "staging").unwrap();
+        assert_eq!(staging.get("paywall"), Some(&false));
+        assert!(parse_overrides_json(text, "prod").unwrap().is_empty());
+
+        let err =
+            parse_overrides_json(r#"{"environments": {"dev": {"": true}}}"#, "prod").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "environments.dev: flag key must not be empty"
+        );
+        let err = parse_overrides_json(r#"{"environments": {"dev": {"paywall": "on"}}}"#, "dev")
+            .unwrap_err();
+        assert!(err.diagnostics[0].message.starts_with("invalid type"));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn loads_yaml_with_the_same_checks() {
+        let text = "flags:\n  - key: paywall\n    rules:\n      - if: country == 'CA'\n        then: true\n      - if: exists(1plan)\n        then: true\n";
+        let err = parse_yaml(text).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "6:13: flags[0].rules[1].if: `exists(...)` needs an attribute name"
+        );
+        let set = load_yaml(&text.replace("exists(1plan)", "exists(plan)")).unwrap();
+        assert!(set.get("paywall").is_some());
+    }
+}
@@ -0,0 +1,448 @@
+//! A hot-reloadable set of flags.
+//!
+//! Hosts that cache decisions need to know when the flags behind them change.
+//! A [`FlagSet`] fingerprints its contents, stamps every [`EvalResult`] it
+//! produces with that fingerprint, and notifies subscribers whenever a reload
+//! changes it.  Reloading identical flags keeps the fingerprint, so caches are
+//! only invalidated by real changes.
+
+use crate::{
+    CompileError, CompiledFlag, Context, EvalOptions, EvalResult, Flag, FunctionRegistry, Timestamp,
+};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Content hash of a flag set.  Independent of the order flags were loaded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint(pub(crate) u64);
+
+impl Fingerprint {
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Passed to subscribers when a reload changes the fingerprint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FingerprintChange {
+    pub previous: Fingerprint,
+    pub current: Fingerprint,
+}
+
+/// Handle returned by [`FlagSet::subscribe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SubscriptionId(u64);
+
+type Subscriber = Box<dyn FnMut(&FingerprintChange)>;
+
+/// Compiled flags keyed by flag key.
+pub struct FlagSet {
+    flags: BTreeMap<String, CompiledFlag>,
+    fingerprint: Fingerprint,
+    subscribers: BTreeMap<SubscriptionId, Subscriber>,
+    next_subscription: u64,
+}
+
+impl FlagSet {
+    /// Compile `flags` into a new set.  A later flag with the same key
+    /// replaces an earlier one.
+    pub fn load(flags: Vec<Flag>) -> Result<Self, CompileError> {
+        Self::load_with(flags, None)
+    }
+
+    /// [`FlagSet::load`] for flags evaluated with `functions`, so rules
+    /// calling its custom builtins compile.
+    pub fn load_with_functions(
+        flags: Vec<Flag>,
+        functions: &FunctionRegistry,
+    ) -> Result<Self, CompileError> {
+        Self::load_with(flags, Some(functions))
+    }
+
+    pub(crate) fn load_with(
+        flags: Vec<Flag>,
+        functions: Option<&FunctionRegistry>,
+    ) -> Result<Self, CompileError> {
+        let flags = compile_all(flags, functions)?;
+        Ok(FlagSet {
+            fingerprint: fingerprint(&flags),
+            flags,
+            subscribers: BTreeMap::new(),
+            next_subscription: 0,
+        })
+    }
+
+    /// Replace every flag in the set.  Subscribers are notified, in the
+    /// order they subscribed, only if the fingerprint changed; the return
+    /// value says whether it did.  On a compile error the set is unchanged.
+    pub fn reload(&mut self, flags: Vec<Flag>) -> Result<bool, CompileError> {
+        self.reload_with(flags, None)
+    }
+
+    /// [`FlagSet::reload`] for flags evaluated with `functions`.
+    pub fn reload_with_functions(
+        &mut self,
+        flags: Vec<Flag>,
+        functions: &FunctionRegistry,
+    ) -> Result<bool, CompileError> {
+        self.reload_with(flags, Some(functions))
+    }
+
+    fn reload_with(
+        &mut self,
+        flags: Vec<Flag>,
+        functions: Option<&FunctionRegistry>,
+    ) -> Result<bool, CompileError> {
+        let flags = compile_all(flags, functions)?;
+        let change = FingerprintChange {
+            previous: self.fingerprint,
+            current: fingerprint(&flags),
+        };
+        self.flags = flags;
+        if change.previous == change.current {
+            return Ok(false);
+        }
+        self.fingerprint = change.current;
+        for subscriber in self.subscribers.values_mut() {
+            subscriber(&change);
+        }
+        Ok(true)
+    }
+
+    /// Call `subscriber` after every reload that changes the fingerprint.
+    pub fn subscribe(
+        &mut self,
+        subscriber: impl FnMut(&FingerprintChange) + 'static,
+    ) -> SubscriptionId {
+        let id = SubscriptionId(self.next_subscription);
+        self.next_subscription += 1;
+        self.subscribers.insert(id, Box::new(subscriber));
+        id
+    }
+
+    /// Stop notifying a subscriber.  Returns `false` if it was not subscribed.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        self.subscribers.remove(&id).is_some()
+    }
+
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Flag> {
+        self.flags.get(key).map(CompiledFlag::flag)
+    }
+
+    pub(crate) fn compiled(&self, key: &str) -> Option<&CompiledFlag> {
+        self.flags.get(key)
+    }
+
+    /// Flag keys in sorted order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.flags.keys().map(String::as_str)
+    }
+
+    /// Flags whose `metadata.expires` is at or before `now`, soonest expiry
+    /// first, so cleanup tooling can list stale flags.  Pass a later instant
+    /// to see what will go stale by then.
+    pub fn report_expiring(&self, now: Timestamp) -> Vec<&Flag> {
+        let mut expiring: Vec<&Flag> = self
+            .flags
+            .values()
+            .map(CompiledFlag::flag)
+            .filter(|flag| flag.metadata.expires.is_some_and(|expires| expires <= now))
+            .collect();
+        expiring.sort_by_key(|flag| flag.metadata.expires);
+        expiring
+    }
+
+    /// Flags carrying `tag` in `metadata.tags`, in key order.
+    pub fn by_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a Flag> + 'a {
+        self.flags
+            .values()
+            .map(CompiledFlag::flag)
+            .filter(move |flag| flag.metadata.tags.iter().any(|t| t == tag))
+    }
+
+    /// Evaluate the flag named `key`, or `None` if the set has no such flag.
+    pub fn eval(&self, key: &str, ctx: &Context) -> Option<EvalResult> {
+        let result = self.flags.get(key)?.eval(ctx);
+        Some(self.stamp(result))
+    }
+
+    /// [`FlagSet::eval`] with the clock, custom builtins, statistics and
+    /// overrides in `opts`; see [`crate::eval_flag_with`].  Keys the set
+    /// does not have are not counted.
+    pub fn eval_with(
+        &self,
+        key: &str,
+        ctx: &Context,
+        opts: &mut EvalOptions<'_>,
+    ) -> Option<EvalResult> {
+        let result = self.flags.get(key)?.eval_with(ctx, opts);
+        Some(self.stamp(result))
+    }
+
+    fn stamp(&self, result: EvalResult) -> EvalResult {
+        EvalResult {
+            flagset_fingerprint: Some(self.fingerprint),
+            ..result
+        }
+    }
+}
+
+/// Evaluate every flag in `set` for one context, in key order, so a host can
+/// build a user's whole flag payload in one call.  Each flag is evaluated
+/// through its compiled form, so `~=` patterns are never re-parsed, and every
+/// result carries the set's fingerprint.
+pub fn eval_all(set: &FlagSet, ctx: &Context) -> Vec<EvalResult> {
+    set.flags
+        .values()
+        .map(|flag| set.stamp(flag.eval(ctx)))
+        .collect()
+}
+
+/// [`eval_all`] with the clock, custom builtins, statistics and overrides
+/// in `opts`, applied to every flag.
+pub fn eval_all_with(set: &FlagSet, ctx: &Context, opts: &mut EvalOptions<'_>) -> Vec<EvalResult> {
+    set.flags
+        .values()
+        .map(|flag| set.stamp(flag.eval_with(ctx, opts)))
+        .collect()
+}
+
+impl fmt::Debug for FlagSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FlagSet")
+            .field("flags", &self.flags.keys().collect::<Vec<_>>())
+            .field("fingerprint", &self.fingerprint)
+            .field("subscribers", &self.subscribers.len())
+            .finish()
+    }
+}
+
+fn compile_all(
+    flags: Vec<Flag>,
+    functions: Option<&FunctionRegistry>,
+) -> Result<BTreeMap<String, CompiledFlag>, CompileError> {
+    let mut compiled = BTreeMap::new();
+    for flag in flags {
+        compiled.insert(
+            flag.key.clone(),
+            CompiledFlag::compile_with(flag, functions)?,
+        );
+    }
+    Ok(compiled)
+}
+
+/// 64-bit FNV-1a over every field that affects evaluation, flags in key
+/// order.  Strings are length-prefixed so field boundaries cannot shift.
+/// Metadata is left out: editing an owner or tag does not invalidate caches.
+fn fingerprint(flags: &BTreeMap<String, CompiledFlag>) -> Fingerprint {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        for byte in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    for flag in flags.values().map(CompiledFlag::flag) {
+        feed(flag.key.as_bytes());
+        feed(&[flag.default as u8]);
+        feed(&[flag.salt.is_some() as u8]);
+        feed(flag.salt.as_deref().unwrap_or_default().as_bytes());
+        feed(flag.hash.as_str().as_bytes());
+        feed(flag.state.as_str().as_bytes());
+        feed(&(flag.rules.len() as u64).to_le_bytes());
+        for rule in &flag.rules {
+            feed(rule.cond.as_bytes());
+            feed(&[rule.then_value as u8]);
+            for bound in [rule.active_from, rule.active_until] {
+                feed(&[bound.is_some() as u8]);
+                feed(&bound.map_or(0, |t| t.unix_millis()).to_le_bytes());
+            }
+        }
+    }
+    Fingerprint(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FlagMetadata, Rule};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn flag(key: &str, cond: &str) -> Flag {
+        Flag {
+            key: key.to_string(),
+            rules: vec![Rule {
+                cond: cond.to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fingerprint_ignores_load_order_and_tracks_content() {
+        let a = FlagSet::load(vec![flag("a", "true"), flag("b", "false")]).unwrap();
+        let b = FlagSet::load(vec![flag("b", "false"), flag("a", "true")]).unwrap();
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        let salted = Flag {
+            salt: Some("2025".to_string()),
+            ..flag("a", "true")
+        };
+        let c = FlagSet::load(vec![salted, flag("b", "false")]).unwrap();
+        assert_ne!(a.fingerprint(), c.fingerprint());
+        assert_eq!(a.fingerprint().to_string().len(), 16);
+    }
+
+    #[test]
+    fn results_carry_the_fingerprint() {
+        let set = FlagSet::load(vec![flag("a", "country == 'CA'")]).unwrap();
+        let mut ctx = Context::new();
+        ctx.insert("country".to_string(), crate::Value::Str("CA".to_string()));
+        let result = set.eval("a", &ctx).unwrap();
+        assert!(result.enabled);
+        assert_eq!(result.flagset_fingerprint, Some(set.fingerprint()));
+        assert!(set.eval("missing", &ctx).is_none());
+        assert_eq!(
+            crate::eval_flag(set.get("a").unwrap(), &ctx).flagset_fingerprint,
+            None
+        );
+    }
+
+    #[test]
+    fn eval_all_covers_every_flag_in_key_order() {
+        let set = FlagSet::load(vec![
+            flag("paywall", "country == 'CA'"),
+            flag("beta", "now >= '2025-03-01T00:00:00Z'"),
+            flag("archive", "false"),
+        ])
+        .unwrap();
+        let mut ctx = Context::new();
+        ctx.insert("country".to_string(), crate::Value::Str("CA".to_string()));
+
+        let summary = |results: Vec<EvalResult>| -> Vec<(String, bool)> {
+            results.into_iter().map(|r| (r.key, r.enabled)).collect()
+        };
+        let results = eval_all(&set, &ctx);
+        assert!(results
+            .iter()
+            .all(|r| r.flagset_fingerprint == Some(set.fingerprint())));
+        assert_eq!(
+            summary(results),
+            [
+                ("archive".into(), false),
+                ("beta".into(), false),
+                ("paywall".into(), true)
+            ]
+        );
+        let now = Timestamp::parse_rfc3339("2025-03-02T00:00:00Z").unwrap();
+        assert_eq!(
+            summary(eval_all_with(
+                &set,
+                &ctx,
+                &mut EvalOptions {
+                    now: Some(now),
+                    ..EvalOptions::default()
+                }
+            )),
+            [
+                ("archive".into(), false),
+                ("beta".into(), true),
+                ("paywall".into(), true)
+            ]
+        );
+        assert!(eval_all(&FlagSet::load(Vec::new()).unwrap(), &ctx).is_empty());
+    }
+
+    #[test]
+    fn metadata_queries_ignore_evaluation() {
+        let at = |s: &str| Timestamp::parse_rfc3339(s).unwrap();
+        let with = |key: &str, expires: Option<&str>, tags: &[&str]| Flag {
+            metadata: FlagMetadata {
+                owner: Some("growth".to_string()),
+                expires: expires.map(at),
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+                ..Default::default()
+            },
+            ..flag(key, "true")
+        };
+        let set = FlagSet::load(vec![
+            with(
+                "paywall",
+                Some("2025-06-01T00:00:00Z"),
+                &["billing", "experiment"],
+            ),
+            with("banner", Some("2025-03-01T00:00:00Z"), &["experiment"]),
+            with("checkout", None, &["billing"]),
+        ])
+        .unwrap();
+        let keys = |flags: Vec<&Flag>| -> Vec<String> {
+            flags.into_iter().map(|f| f.key.clone()).collect()
+        };
+
+        assert!(set.report_expiring(at("2025-01-01T00:00:00Z")).is_empty());
+        assert_eq!(
+            keys(set.report_expiring(at("2025-03-01T00:00:00Z"))),
+            ["banner"]
+        );
+        assert_eq!(
+            keys(set.report_expiring(at("2026-01-01T00:00:00Z"))),
+            ["banner", "paywall"]
+        );
+        assert_eq!(
+            keys(set.by_tag("billing").collect()),
+            ["checkout", "paywall"]
+        );
+        assert!(set.by_tag("missing").next().is_none());
+
+        // Expired flags still evaluate, and metadata leaves the fingerprint alone.
+        assert!(set.eval("banner", &Context::new()).unwrap().enabled);
+        let bare = FlagSet::load(vec![
+            flag("paywall", "true"),
+            flag("banner", "true"),
+            flag("checkout", "true"),
+        ])
+        .unwrap();
+        assert_eq!(set.fingerprint(), bare.fingerprint());
+    }
+
+    #[test]
+    fn subscribers_hear_only_real_changes() {
+        let mut set = FlagSet::load(vec![flag("a", "true")]).unwrap();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&seen);
+        let id = set.subscribe(move |change| sink.borrow_mut().push(*change));
+        let original = set.fingerprint();
+
+        assert!(!set.reload(vec![flag("a", "true")]).unwrap());
+        assert!(seen.borrow().is_empty());
+
+        assert!(set.reload(vec![flag("a", "false")]).unwrap());
+        assert_eq!(
+            *seen.borrow(),
+            vec![FingerprintChange {
+                previous: original,
+                current: set.fingerprint()
+            }]
+        );
+        let stale = set.fingerprint();
+
+        assert!(set.unsubscribe(id));
+        assert!(!set.unsubscribe(id));
+        assert!(set.reload(vec![flag("a", "true")]).unwrap());
+        assert_eq!(seen.borrow().len(), 1);
+        assert_ne!(set.fingerprint(), stale);
+        assert_eq!(set.fingerprint(), original);
+    }
+}
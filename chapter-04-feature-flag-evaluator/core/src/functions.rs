@@ -0,0 +1,253 @@
+//! Caller-defined builtins.
+//!
+//! A host can extend the rule vocabulary without forking the grammar by
+//! registering functions in a [`FunctionRegistry`] and evaluating with it in
+//! [`crate::EvalOptions::functions`], e.g. `geo_distance(lat, lon) < 50`.
+//! A call to a registered name evaluates each argument as a term and passes
+//! the values to the function.  Without the registry, or for a name
+//! that is not registered, the call is not a builtin: the rule fails to
+//! evaluate, and [`crate::CompiledFlag::compile`] rejects the flag unless it
+//! is compiled with the registry.
+//!
+//! Evaluation stays a pure function of the flag, the context and the
+//! injected clock, and the rest of the crate relies on that: bucketing must
+//! not drift between requests and [`crate::EvalCache`] memoizes results.  A
+//! builtin is therefore a plain `fn` rather than a closure, so it cannot
+//! capture a clock, a random source or mutable state, and it sees only its
+//! arguments, never the context.  It must return the same value for the
+//! same arguments; reading the environment, the time or randomness from
+//! inside it breaks that contract.
+
+use crate::lint::BUILTINS;
+use crate::{is_identifier, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A deterministic builtin.  Returning `Err(())`, e.g. for arguments of the
+/// wrong type, makes the calling rule fail to evaluate, like any other
+/// malformed term.
+pub type Builtin = fn(&[Value]) -> Result<Value, ()>;
+
+/// Custom builtins by name.
+#[derive(Debug, Clone, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, Builtin>,
+}
+
+/// Why [`FunctionRegistry::register`] refused a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegisterError {
+    /// The name is not an identifier, so rules could not call it.
+    InvalidName(String),
+    /// The name belongs to a core builtin such as `rollout`.
+    Reserved(String),
+    /// A function is already registered under the name.
+    Duplicate(String),
+}
+
+impl fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegisterError::InvalidName(name) => write!(f, "`{name}` is not a valid function name"),
+            RegisterError::Reserved(name) => write!(f, "`{name}` is a core builtin"),
+            RegisterError::Duplicate(name) => write!(f, "`{name}` is already registered"),
+        }
+    }
+}
+
+impl std::error::Error for RegisterError {}
+
+impl FunctionRegistry {
+    pub fn new() -> Self {
+        FunctionRegistry::default()
+    }
+
+    /// Make `function` callable from rules as `name(...)`.  Core builtins
+    /// cannot be replaced, and each name can be registered once.
+    pub fn register(&mut self, name: &str, function: Builtin) -> Result<(), RegisterError> {
+        if !is_identifier(name) {
+            return Err(RegisterError::InvalidName(name.to_string()));
+        }
+        if BUILTINS.contains(&name) {
+            return Err(RegisterError::Reserved(name.to_string()));
+        }
+        if self.functions.contains_key(name) {
+            return Err(RegisterError::Duplicate(name.to_string()));
+        }
+        self.functions.insert(name.to_string(), function);
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+
+    /// Registered names, in sorted order.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.functions.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// The registered function called by term `t` and its argument list,
+    /// if `t` is a call to one.
+    pub(crate) fn lookup<'t>(&self, t: &'t str) -> Option<(Builtin, &'t str)> {
+        let (name, inner) = call_parts(t)?;
+        Some((*self.functions.get(name)?, inner))
+    }
+}
+
+/// Split `name(args)` into the name and the text between the parentheses.
+pub(crate) fn call_parts(t: &str) -> Option<(&str, &str)> {
+    let (name, rest) = t.split_once('(')?;
+    let name = name.trim();
+    let inner = rest.strip_suffix(')')?;
+    is_identifier(name).then_some((name, inner))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        check_rule, check_rule_with_functions, eval_flag, eval_flag_with, eval_flag_with_trace,
+        trace_flag_with, validate_flag, validate_flag_with_functions, CompileErrorKind,
+        CompiledFlag, Context, EvalOptions, Flag, LintKind, Rule, RuleOutcome,
+    };
+
+    /// Planar distance from the point given by the two arguments to a fixed
+    /// office, rounded to whole units.
+    fn geo_distance(args: &[Value]) -> Result<Value, ()> {
+        let [Value::Num(lat), Value::Num(lon)] = args else {
+            return Err(());
+        };
+        Ok(Value::Num(
+            ((lat - 45.5).powi(2) + (lon + 73.5).powi(2)).sqrt().round(),
+        ))
+    }
+
+    fn registry() -> FunctionRegistry {
+        let mut functions = FunctionRegistry::new();
+        functions.register("geo_distance", geo_distance).unwrap();
+        functions
+    }
+
+    fn with(functions: &FunctionRegistry) -> EvalOptions<'_> {
+        EvalOptions {
+            functions: Some(functions),
+            ..EvalOptions::default()
+        }
+    }
+
+    fn flag(cond: &str) -> Flag {
+        Flag {
+            key: "nearby".to_string(),
+            rules: vec![Rule {
+                cond: cond.to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn at(lat: f64, lon: f64) -> Context {
+        [("lat", lat), ("lon", lon)]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), Value::Num(v)))
+            .collect()
+    }
+
+    #[test]
+    fn rules_call_registered_functions() {
+        let functions = registry();
+        let flag = flag("geo_distance(lat, lon) < 50");
+        let near = eval_flag_with(&flag, &at(45.0, -73.0), &mut with(&functions));
+        assert!(near.enabled);
+        assert_eq!(near.matched_rule, Some(0));
+        assert!(!eval_flag_with(&flag, &at(10.0, 10.0), &mut with(&functions)).enabled);
+        // A failing call skips the rule, as does evaluating without the registry.
+        let mut missing = at(45.0, -73.0);
+        missing.remove("lon");
+        assert_eq!(
+            eval_flag_with(&flag, &missing, &mut with(&functions)).matched_rule,
+            None
+        );
+        assert_eq!(eval_flag(&flag, &at(45.0, -73.0)).matched_rule, None);
+    }
+
+    #[test]
+    fn traces_call_registered_functions() {
+        let functions = registry();
+        let flag = flag("geo_distance(lat, lon) < 50");
+        let ctx = at(45.0, -73.0);
+        let trace = trace_flag_with(&flag, &ctx, &mut with(&functions));
+        assert!(trace.result.enabled);
+        assert_eq!(trace.result.matched_rule, Some(0));
+        assert_eq!(trace.rules[0].outcome, RuleOutcome::Matched);
+        assert_eq!(trace.rules[0].steps[0].value, Some(true));
+        // Without the registry the rule fails to evaluate, as in eval_flag.
+        let unregistered = eval_flag_with_trace(&flag, &ctx);
+        assert_eq!(unregistered.rules[0].outcome, RuleOutcome::Errored);
+        assert_eq!(unregistered.result.matched_rule, None);
+    }
+
+    #[test]
+    fn unknown_calls_fail_closed() {
+        let functions = registry();
+        let ctx = at(45.0, -73.0);
+        // An unregistered call is not a missing attribute: neither `== null`
+        // nor `!= null` can match on it.
+        for cond in ["typo_fn(lat) == null", "typo_fn(lat) != null"] {
+            let flag = flag(cond);
+            assert_eq!(eval_flag(&flag, &ctx).matched_rule, None, "{cond}");
+            assert_eq!(
+                eval_flag_with(&flag, &ctx, &mut with(&functions)).matched_rule,
+                None,
+                "{cond}"
+            );
+            let trace = eval_flag_with_trace(&flag, &ctx);
+            assert_eq!(trace.rules[0].outcome, RuleOutcome::Errored, "{cond}");
+
+            let err = CompiledFlag::compile(flag.clone()).unwrap_err();
+            assert_eq!((err.rule, err.kind), (0, CompileErrorKind::UnknownBuiltin));
+            assert_eq!(err.pattern, "typo_fn(lat)");
+            assert!(CompiledFlag::compile_with_functions(flag, &functions).is_err());
+        }
+
+        let known = flag("geo_distance(lat, lon) < 50");
+        assert!(CompiledFlag::compile(known.clone()).is_err());
+        let compiled = CompiledFlag::compile_with_functions(known, &functions).unwrap();
+        assert!(compiled.eval_with(&ctx, &mut with(&functions)).enabled);
+    }
+
+    #[test]
+    fn registration_protects_the_grammar() {
+        let mut functions = registry();
+        assert_eq!(
+            functions.register("rollout", geo_distance),
+            Err(RegisterError::Reserved("rollout".to_string()))
+        );
+        assert_eq!(
+            functions.register("geo_distance", geo_distance),
+            Err(RegisterError::Duplicate("geo_distance".to_string()))
+        );
+        assert_eq!(
+            functions.register("geo distance", geo_distance),
+            Err(RegisterError::InvalidName("geo distance".to_string()))
+        );
+        assert_eq!(functions.names(), ["geo_distance"]);
+    }
+
+    #[test]
+    fn checks_accept_registered_calls() {
+        let functions = registry();
+        let cond = "geo_distance(lat, lon) < 50";
+        assert!(check_rule(cond).is_err());
+        assert_eq!(check_rule_with_functions(cond, &functions), Ok(()));
+        assert!(check_rule_with_functions("geo_distance(lat, 'x) < 50", &functions).is_err());
+
+        let flag = flag(cond);
+        assert_eq!(validate_flag(&flag)[0].kind, LintKind::UnknownBuiltin);
+        assert!(validate_flag_with_functions(&flag, &functions).is_empty());
+    }
+}
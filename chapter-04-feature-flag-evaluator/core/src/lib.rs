@@ -5,11 +5,40 @@
 //! compiled to the `wasm32-wasip1` target without modification.  The core supports a simple
 //! expression language for flag rules and can be extended easily to support additional
 //! operators.
+//!
+//! The optional `regex` feature adds the `~=` operator and pulls in the `regex` crate.  The
+//! optional `serde` feature derives `Serialize`/`Deserialize` for the flag, context and result
+//! types using the JSON field names of the evaluator contract (`if`/`then`, `matchedRule`, ...).
+//! The `config` feature adds [`config`], which loads a whole flag set from a JSON document
+//! (or YAML, with the `yaml` feature) and reports malformed rules with their line and column.
+
+mod attributes;
+mod cache;
+#[cfg(feature = "config")]
+pub mod config;
+mod flagset;
+mod functions;
+mod lint;
+#[cfg(feature = "serde")]
+mod serde_support;
+mod stats;
 
+pub use cache::EvalCache;
+pub use flagset::{
+    eval_all, eval_all_with, Fingerprint, FingerprintChange, FlagSet, SubscriptionId,
+};
+pub use functions::{Builtin, FunctionRegistry, RegisterError};
+pub use lint::{validate_flag, validate_flag_with_functions, LintKind, LintWarning};
+pub use stats::{EvalStats, FlagStats};
+
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt;
 
 /// A flag definition containing a unique key, a list of rules and a default value.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Flag {
     /// Unique identifier for the flag (e.g. "paywall").
     pub key: String,
@@ -17,42 +46,473 @@ pub struct Flag {
     /// determines the value returned by the evaluator.
     pub rules: Vec<Rule>,
     /// Default value returned when no rule matches or an error occurs.
+    #[cfg_attr(feature = "serde", serde(default))]
     pub default: bool,
+    /// Optional salt mixed into rollout and variant bucketing.  Rotating the
+    /// salt re-shuffles cohorts without renaming the flag.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub salt: Option<String>,
+    /// Hash algorithm used for rollout and variant bucketing.  Defaults to
+    /// FNV-1a; choose murmur3 to match SDKs that bucket with it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hash: HashAlgorithm,
+    /// Lifecycle state.  Only an enabled flag evaluates its rules; the rules
+    /// of a disabled or archived flag are kept but ignored.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "FlagState::is_enabled")
+    )]
+    pub state: FlagState,
+    /// Ownership and lifetime details for cleanup tooling.  Evaluation never
+    /// reads them; see [`FlagSet::report_expiring`] and [`FlagSet::by_tag`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "FlagMetadata::is_empty")
+    )]
+    pub metadata: FlagMetadata,
+}
+
+impl Flag {
+    /// The string that seeds bucketing for this flag: the key, followed by
+    /// `:salt` when a salt is set.
+    pub fn bucket_seed(&self) -> Cow<'_, str> {
+        match &self.salt {
+            Some(salt) => Cow::Owned(format!("{}:{}", self.key, salt)),
+            None => Cow::Borrowed(&self.key),
+        }
+    }
+}
+
+/// Lifecycle states selectable per flag via [`Flag::state`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FlagState {
+    /// Rules are evaluated as usual.
+    #[default]
+    Enabled,
+    /// Targeting is switched off: every context receives the flag's default.
+    Disabled,
+    /// The flag is retired.  Evaluation returns the default, and the result
+    /// says `Archived` so callers still reading the flag can be found.
+    Archived,
+}
+
+impl FlagState {
+    /// Parse a state name (`"enabled"`, `"disabled"` or `"archived"`).
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "enabled" => Some(FlagState::Enabled),
+            "disabled" => Some(FlagState::Disabled),
+            "archived" => Some(FlagState::Archived),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FlagState::Enabled => "enabled",
+            FlagState::Disabled => "disabled",
+            FlagState::Archived => "archived",
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        *self == FlagState::Enabled
+    }
+}
+
+/// Who owns a flag, when it was created, when it should be removed and how
+/// it is grouped.  Every field is optional and none affects evaluation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct FlagMetadata {
+    /// Team or person responsible for the flag, e.g. `"checkout-team"`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub owner: Option<String>,
+    /// Instant the flag was introduced, RFC 3339 in UTC in JSON.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub created: Option<Timestamp>,
+    /// Instant after which the flag is stale and due for removal.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub expires: Option<Timestamp>,
+    /// Free-form labels for grouping flags, e.g. `["experiment"]`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub tags: Vec<String>,
+}
+
+impl FlagMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == FlagMetadata::default()
+    }
+}
+
+/// A 32-bit hash function used to place users into buckets.
+pub trait Hasher {
+    fn hash32(&self, input: &[u8]) -> u32;
+}
+
+/// 32-bit FNV-1a, the evaluator's original bucketing hash.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fnv1a;
+
+impl Hasher for Fnv1a {
+    fn hash32(&self, input: &[u8]) -> u32 {
+        let mut hash: u32 = 0x811c9dc5;
+        for byte in input {
+            hash ^= *byte as u32;
+            hash = hash.wrapping_mul(0x0100_0193);
+        }
+        hash
+    }
+}
+
+/// MurmurHash3 x86 32-bit with a zero seed, bit-compatible with the
+/// reference implementation used by other SDKs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Murmur3;
+
+impl Hasher for Murmur3 {
+    fn hash32(&self, input: &[u8]) -> u32 {
+        const C1: u32 = 0xcc9e_2d51;
+        const C2: u32 = 0x1b87_3593;
+        let mut hash: u32 = 0;
+        let mut blocks = input.chunks_exact(4);
+        for block in &mut blocks {
+            let mut k = u32::from_le_bytes([block[0], block[1], block[2], block[3]]);
+            k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+            hash ^= k;
+            hash = hash
+                .rotate_left(13)
+                .wrapping_mul(5)
+                .wrapping_add(0xe654_6b64);
+        }
+        let tail = blocks.remainder();
+        if !tail.is_empty() {
+            let mut k: u32 = 0;
+            for (i, byte) in tail.iter().enumerate() {
+                k |= (*byte as u32) << (8 * i);
+            }
+            hash ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        }
+        hash ^= input.len() as u32;
+        hash ^= hash >> 16;
+        hash = hash.wrapping_mul(0x85eb_ca6b);
+        hash ^= hash >> 13;
+        hash = hash.wrapping_mul(0xc2b2_ae35);
+        hash ^ (hash >> 16)
+    }
+}
+
+/// Hash algorithms selectable per flag via [`Flag::hash`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    #[default]
+    Fnv1a,
+    Murmur3,
+}
+
+impl HashAlgorithm {
+    /// Parse an algorithm name (`"fnv1a"` or `"murmur3"`).
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "fnv1a" => Some(HashAlgorithm::Fnv1a),
+            "murmur3" => Some(HashAlgorithm::Murmur3),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HashAlgorithm::Fnv1a => "fnv1a",
+            HashAlgorithm::Murmur3 => "murmur3",
+        }
+    }
+
+    /// The [`Hasher`] implementing this algorithm.
+    pub fn hasher(self) -> &'static dyn Hasher {
+        match self {
+            HashAlgorithm::Fnv1a => &Fnv1a,
+            HashAlgorithm::Murmur3 => &Murmur3,
+        }
+    }
 }
 
 /// A single rule consisting of a condition and a resulting value.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Rule {
     /// Expression to evaluate.  The core supports the following forms:
     ///
     /// * `rollout(p)` – returns `true` if the deterministic rollout bucket for this flag
-    ///   and the current `userId` is less than `p` where `0 <= p <= 1`.
+    ///   and the current `userId` is less than `p` where `0 <= p <= 1`.  An optional
+    ///   `by='attr'` argument buckets by another context attribute instead, e.g.
+    ///   `rollout(0.2, by='accountId')`, and `per='attr'` rolls out to a share
+    ///   `p` of each value of that attribute, e.g. `rollout(0.1, per='country')`.
+    /// * `ramp(start, end, from, to)` – a rollout whose share follows a schedule:
+    ///   none before `start`, then growing linearly from `from` at `start` to
+    ///   `to` at `end`, e.g. `ramp('2025-03-01T00:00:00Z', '2025-03-15T00:00:00Z', 0.1, 1)`.
+    ///   The share is computed from the injected `now`, so the rule is skipped
+    ///   without a clock.  It takes the same named arguments as `rollout`.
     /// * Comparisons `==`, `!=`, `<`, `<=`, `>`, `>=` between context values and
-    ///   literals (strings, numbers or booleans).
+    ///   literals (strings, numbers or booleans).  Strings order only when both
+    ///   sides are RFC 3339 timestamps, which compare as instants.
+    /// * `now` – the evaluation instant in [`EvalOptions::now`], e.g.
+    ///   `now >= '2025-03-01T00:00:00Z'`.  Rules using `now` are skipped when
+    ///   no instant is supplied; the system clock is never read.
+    /// * String operators `contains`, `startsWith`, `endsWith` and glob-style
+    ///   `matches` (`*` and `?` wildcards), e.g. `email endsWith '@example.com'`.
+    /// * Regex match `~=` against a quoted pattern, e.g. `email ~= '^[a-z]+@corp\.'`.
+    ///   Requires the `regex` feature; without it the rule is skipped.
     /// * Membership `in`, for example `country in ('CA','US','MX')`.  Only string
     ///   membership is supported; numeric or boolean membership returns `false`.
+    ///   The right-hand side may also be a list-valued context attribute, as in
+    ///   `'admin' in roles`.
+    /// * `any(roles, 'admin', 'owner')` / `all(roles, 'admin', 'owner')` – true
+    ///   when the list attribute contains any / all of the given literals.
+    /// * `exists(attr)` / `missing(attr)` – whether the context holds a non-null
+    ///   value for `attr`, so an absent attribute can be told apart from `false`
+    ///   or `''`.  `attr != null` is equivalent to `exists(attr)`.
     /// * Logical AND (`&&`) and OR (`||`) operators with short‑circuit evaluation.
+    /// * `variant({'a':0.5,'b':0.5})` – assigns a weighted variant using the same
+    ///   bucket as `rollout`.  As a condition it is true when a variant was
+    ///   assigned; it can also be compared, e.g. `variant({...}) == 'b'`.
+    /// * Calls to custom builtins registered in a [`FunctionRegistry`], e.g.
+    ///   `geo_distance(lat, lon) < 50`, when evaluating with the registry in
+    ///   [`EvalOptions::functions`].
     ///
     /// Unknown or malformed expressions cause the rule to be skipped.
+    #[cfg_attr(feature = "serde", serde(rename = "if"))]
     pub cond: String,
     /// The value to return when the condition evaluates to true.
+    #[cfg_attr(feature = "serde", serde(rename = "then"))]
     pub then_value: bool,
+    /// First instant at which the rule applies (inclusive).  Checked against
+    /// the injected clock before the condition; see [`Rule::window_skip`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "activeFrom",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )
+    )]
+    pub active_from: Option<Timestamp>,
+    /// Instant from which the rule no longer applies (exclusive), so a
+    /// temporary promotion expires without redeploying the flag.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "activeUntil",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )
+    )]
+    pub active_until: Option<Timestamp>,
 }
 
-/// A value within a context.  Context values may be strings, numbers or booleans.
-#[derive(Debug, Clone)]
+impl Rule {
+    /// Why the rule's scheduling window keeps it from being evaluated at
+    /// `now`, or `None` when it is active.  A windowed rule without a clock
+    /// cannot be placed in time and is skipped.
+    pub fn window_skip(&self, now: Option<Timestamp>) -> Option<SkipReason> {
+        if self.active_from.is_none() && self.active_until.is_none() {
+            return None;
+        }
+        let Some(now) = now else {
+            return Some(SkipReason::NoClock);
+        };
+        if self.active_from.is_some_and(|from| now < from) {
+            return Some(SkipReason::NotYetActive);
+        }
+        if self.active_until.is_some_and(|until| now >= until) {
+            return Some(SkipReason::Expired);
+        }
+        None
+    }
+}
+
+/// A value within a context.  Context values may be strings, numbers, booleans
+/// or lists of those.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Str(String),
     Num(f64),
     Bool(bool),
+    /// A list-valued attribute such as `roles`.  Usable with `in`, `any`
+    /// and `all`; comparisons against a list are always false.
+    List(Vec<Value>),
+    /// An instant, as produced by the `now` builtin.  Compares with other
+    /// instants and with RFC 3339 strings.
+    Time(Timestamp),
     Null,
 }
 
 /// A simple alias for the context map.  Keys are ASCII strings; values are s.
 pub type Context = HashMap<String, Value>;
 
+/// Flag values forced by key, e.g. for one environment or a local debugging
+/// session.  See [`EvalOptions::overrides`].
+pub type Overrides = HashMap<String, bool>;
+
+/// An instant in UTC with millisecond precision.  The evaluator never reads
+/// the system clock: callers pass the evaluation instant in
+/// [`EvalOptions::now`] so time-based rules stay deterministic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    millis: i64,
+}
+
+impl Timestamp {
+    pub fn from_unix_millis(millis: i64) -> Self {
+        Timestamp { millis }
+    }
+
+    pub fn unix_millis(self) -> i64 {
+        self.millis
+    }
+
+    /// Parse an RFC 3339 timestamp such as `2025-03-01T00:00:00Z` or
+    /// `2025-03-01T09:30:00.250+05:30`.  Fractional seconds beyond
+    /// milliseconds are truncated and a leap second reads as `:59`.
+    pub fn parse_rfc3339(text: &str) -> Option<Self> {
+        let b = text.as_bytes();
+        if b.len() < 20 || b[4] != b'-' || b[7] != b'-' || b[13] != b':' || b[16] != b':' {
+            return None;
+        }
+        if !matches!(b[10], b'T' | b't' | b' ') {
+            return None;
+        }
+        let year = digits(&b[0..4])?;
+        let month = digits(&b[5..7])?;
+        let day = digits(&b[8..10])?;
+        let hour = digits(&b[11..13])?;
+        let minute = digits(&b[14..16])?;
+        let second = digits(&b[17..19])?;
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        if hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+        let second = second.min(59);
+        let mut rest = &b[19..];
+        let mut millis = 0;
+        if let Some(frac) = rest.strip_prefix(b".") {
+            let len = frac.iter().take_while(|c| c.is_ascii_digit()).count();
+            if len == 0 {
+                return None;
+            }
+            for (i, c) in frac[..len.min(3)].iter().enumerate() {
+                millis += i64::from(c - b'0') * [100, 10, 1][i];
+            }
+            rest = &frac[len..];
+        }
+        let offset_minutes = match rest {
+            [b'Z' | b'z'] => 0,
+            [sign @ (b'+' | b'-'), h1, h2, b':', m1, m2] => {
+                let (h, m) = (digits(&[*h1, *h2])?, digits(&[*m1, *m2])?);
+                if h > 23 || m > 59 {
+                    return None;
+                }
+                let minutes = h * 60 + m;
+                if *sign == b'-' {
+                    -minutes
+                } else {
+                    minutes
+                }
+            }
+            _ => return None,
+        };
+        let days = days_from_civil(year, month, day);
+        let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second - offset_minutes * 60;
+        Some(Timestamp::from_unix_millis(seconds * 1_000 + millis))
+    }
+}
+
+/// RFC 3339 in UTC, e.g. `2025-03-01T00:00:00Z`; milliseconds are shown only
+/// when non-zero.  Round-trips through [`Timestamp::parse_rfc3339`].
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let days = self.millis.div_euclid(86_400_000);
+        let rest = self.millis.rem_euclid(86_400_000);
+        let (year, month, day) = civil_from_days(days);
+        let (hour, minute) = (rest / 3_600_000, rest / 60_000 % 60);
+        let (second, millis) = (rest / 1_000 % 60, rest % 1_000);
+        write!(
+            f,
+            "{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}"
+        )?;
+        if millis != 0 {
+            write!(f, ".{millis:03}")?;
+        }
+        f.write_str("Z")
+    }
+}
+
+/// Parse a run of ASCII digits.
+fn digits(bytes: &[u8]) -> Option<i64> {
+    bytes.iter().try_fold(0i64, |acc, c| {
+        c.is_ascii_digit().then(|| acc * 10 + i64::from(c - b'0'))
+    })
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's
+/// `days_from_civil`).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Inverse of [`days_from_civil`] (Howard Hinnant's `civil_from_days`).
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
 /// The result of flag evaluation.
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
 pub struct EvalResult {
     /// The flag key that was evaluated.
     pub key: String,
@@ -60,6 +520,151 @@ pub struct EvalResult {
     pub enabled: bool,
     /// Index of the matched rule, if any.   if no rule matched.
     pub matched_rule: Option<usize>,
+    /// Variant assigned by a `variant(...)` call in the matched rule, if any.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub variant: Option<String>,
+    /// Stratum a `per='attr'` rollout or variant in the matched rule bucketed
+    /// within, so analytics can check rollout rates per stratum.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub stratum: Option<Stratum>,
+    /// Fingerprint of the [`FlagSet`] the flag was evaluated from, or `None`
+    /// for a standalone flag.  A decision cached under an older fingerprint
+    /// is stale.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub flagset_fingerprint: Option<Fingerprint>,
+    /// State of the flag when it was evaluated.  Anything but
+    /// [`FlagState::Enabled`] means no rule was evaluated and `enabled` is
+    /// the flag's default.  Omitted from JSON when enabled.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "FlagState::is_enabled")
+    )]
+    pub state: FlagState,
+    /// Whether `enabled` was forced by an override rather than evaluated.
+    /// No rule is evaluated for an overridden flag.  Omitted from JSON when
+    /// false.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    pub overridden: bool,
+    /// Why `enabled` has its value, in OpenFeature's vocabulary.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub reason: Reason,
+}
+
+impl EvalResult {
+    /// The result of forcing `flag` to `enabled`.
+    pub fn overridden(flag: &Flag, enabled: bool) -> Self {
+        EvalResult {
+            key: flag.key.clone(),
+            enabled,
+            matched_rule: None,
+            variant: None,
+            stratum: None,
+            flagset_fingerprint: None,
+            state: flag.state,
+            overridden: true,
+            reason: Reason::Static,
+        }
+    }
+}
+
+/// Why an evaluation returned its value.  The names and their
+/// `SCREAMING_SNAKE_CASE` JSON form follow the OpenFeature resolution
+/// reasons, so hosts can hand them to an OpenFeature SDK unchanged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Reason {
+    /// A rule matched; [`EvalResult::matched_rule`] says which.
+    TargetingMatch,
+    /// No rule matched and none failed to evaluate, so the flag returned
+    /// its default.
+    #[default]
+    Default,
+    /// No rule matched and at least one failed to evaluate, e.g. it
+    /// compared values of different types or used `now` without a clock.
+    /// The flag returned its default.
+    Error,
+    /// The flag is disabled or archived, so no rule was evaluated.
+    Disabled,
+    /// An override forced the value.
+    Static,
+    /// A flag this one depends on did not pass.  Reserved for OpenFeature
+    /// compatibility: the core has no prerequisites and never returns this,
+    /// but hosts that gate flags on other flags can report it.
+    PrerequisiteFailed,
+}
+
+impl Reason {
+    /// Parse a reason name such as `"TARGETING_MATCH"`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "TARGETING_MATCH" => Some(Reason::TargetingMatch),
+            "DEFAULT" => Some(Reason::Default),
+            "ERROR" => Some(Reason::Error),
+            "DISABLED" => Some(Reason::Disabled),
+            "STATIC" => Some(Reason::Static),
+            "PREREQUISITE_FAILED" => Some(Reason::PrerequisiteFailed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Reason::TargetingMatch => "TARGETING_MATCH",
+            Reason::Default => "DEFAULT",
+            Reason::Error => "ERROR",
+            Reason::Disabled => "DISABLED",
+            Reason::Static => "STATIC",
+            Reason::PrerequisiteFailed => "PREREQUISITE_FAILED",
+        }
+    }
+
+    /// The reason for returning the default after evaluating the rules.
+    fn fallthrough(state: FlagState, errored: bool) -> Self {
+        if !state.is_enabled() {
+            Reason::Disabled
+        } else if errored {
+            Reason::Error
+        } else {
+            Reason::Default
+        }
+    }
+}
+
+/// The attribute and value a stratified rollout bucketed within, e.g.
+/// `country` = `CA` for `rollout(0.1, per='country')`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stratum {
+    pub attribute: String,
+    pub value: String,
+}
+
+/// How to evaluate a flag beyond its context: the clock, custom builtins,
+/// statistics and overrides, in any combination.  The default evaluates like
+/// [`eval_flag`].
+#[derive(Debug, Default)]
+pub struct EvalOptions<'a> {
+    /// Value of the `now` builtin.  Without a clock, rules that use `now`
+    /// or have a scheduling window are skipped.
+    pub now: Option<Timestamp>,
+    /// Custom builtins the rules may call; see [`FunctionRegistry`].
+    pub functions: Option<&'a FunctionRegistry>,
+    /// Counters the evaluation is added to; see [`EvalStats`].
+    pub stats: Option<&'a mut EvalStats>,
+    /// Forced flag values.  An overridden flag evaluates no rules, whatever
+    /// its state, and the result is marked [`EvalResult::overridden`].
+    pub overrides: Option<&'a Overrides>,
 }
 
 /// Evaluate a flag against a context.
@@ -69,25 +674,183 @@ pub struct EvalResult {
 /// matches.  The first matching rule determines the return value.  If no
 /// rule matches, the flag's default is used.  The function never panics.
 pub fn eval_flag(flag: &Flag, ctx: &Context) -> EvalResult {
-    if let Some((idx, val)) = eval_rules(flag, ctx) {
-        return EvalResult {
-            key: flag.key.clone(),
-            enabled: val,
-            matched_rule: Some(idx),
+    eval_flag_with(flag, ctx, &mut EvalOptions::default())
+}
+
+/// Evaluate a flag like [`eval_flag`] with the clock, custom builtins,
+/// statistics and overrides in `opts`.
+pub fn eval_flag_with(flag: &Flag, ctx: &Context, opts: &mut EvalOptions<'_>) -> EvalResult {
+    evaluate(flag, ctx, None, opts)
+}
+
+/// The overridden value of `flag`, if `opts` forces one.  Counts the
+/// evaluation in `opts.stats` when it does.
+fn overridden(flag: &Flag, opts: &mut EvalOptions<'_>) -> Option<bool> {
+    let enabled = *opts.overrides?.get(&flag.key)?;
+    if let Some(stats) = opts.stats.as_deref_mut() {
+        stats.entry(flag).evaluations += 1;
+    }
+    Some(enabled)
+}
+
+fn evaluate(
+    flag: &Flag,
+    ctx: &Context,
+    patterns: Option<&Patterns>,
+    opts: &mut EvalOptions<'_>,
+) -> EvalResult {
+    if let Some(enabled) = overridden(flag, opts) {
+        return EvalResult::overridden(flag, enabled);
+    }
+    let now = opts.now;
+    let mut stats = opts.stats.as_deref_mut().map(|stats| stats.entry(flag));
+    if let Some(stats) = stats.as_deref_mut() {
+        stats.evaluations += 1;
+    }
+    let seed = flag.bucket_seed();
+    // A disabled or archived flag falls straight through to its default.
+    let rules = if flag.state.is_enabled() {
+        &flag.rules[..]
+    } else {
+        &[]
+    };
+    let mut errored = false;
+    for (i, rule) in rules.iter().enumerate() {
+        if rule.window_skip(now).is_some() {
+            continue;
+        }
+        let mut scratch = Scratch {
+            patterns,
+            functions: opts.functions,
+            now,
+            ..Scratch::for_flag(flag)
         };
+        match eval_cond(&seed, &rule.cond, ctx, &mut scratch) {
+            Ok(true) => {
+                if let Some(stats) = stats {
+                    stats.matches[i] += 1;
+                }
+                return EvalResult {
+                    key: flag.key.clone(),
+                    enabled: rule.then_value,
+                    matched_rule: Some(i),
+                    variant: scratch.variant,
+                    stratum: scratch.stratum,
+                    flagset_fingerprint: None,
+                    state: flag.state,
+                    overridden: false,
+                    reason: Reason::TargetingMatch,
+                };
+            }
+            Ok(false) => {}
+            Err(()) => {
+                errored = true;
+                if let Some(stats) = stats.as_deref_mut() {
+                    stats.errors[i] += 1;
+                }
+            }
+        }
+    }
+    if let Some(stats) = stats {
+        stats.defaults += 1;
     }
     EvalResult {
         key: flag.key.clone(),
         enabled: flag.default,
         matched_rule: None,
+        variant: None,
+        stratum: None,
+        flagset_fingerprint: None,
+        state: flag.state,
+        overridden: false,
+        reason: Reason::fallthrough(flag.state, errored),
+    }
+}
+
+/// Aggregate outcome of [`eval_flag_bulk`] over a population of contexts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BulkCounts {
+    /// Number of contexts evaluated.
+    pub total: u64,
+    /// Contexts for which the flag was enabled.
+    pub enabled: u64,
+    /// Contexts decided by each rule, indexed like [`Flag::rules`].
+    pub by_rule: Vec<u64>,
+    /// Contexts no rule matched, which received the flag's default.
+    pub unmatched: u64,
+}
+
+impl BulkCounts {
+    /// Share of contexts for which the flag was enabled, in [0, 1].  Zero
+    /// for an empty population.
+    pub fn enabled_share(&self) -> f64 {
+        match self.total {
+            0 => 0.0,
+            total => self.enabled as f64 / total as f64,
+        }
+    }
+}
+
+/// Evaluate one flag against many contexts and count the outcomes, e.g. to
+/// estimate what share of users a rollout would reach.  `~=` patterns are
+/// compiled once for the whole population; invalid ones are skipped just as
+/// [`eval_flag`] skips them.  Rules with a scheduling window are skipped, as
+/// there is no clock; see [`eval_flag_bulk_with`].
+pub fn eval_flag_bulk<'c>(
+    flag: &Flag,
+    contexts: impl IntoIterator<Item = &'c Context>,
+) -> BulkCounts {
+    eval_flag_bulk_with(flag, contexts, &mut EvalOptions::default())
+}
+
+/// [`eval_flag_bulk`] with the clock, custom builtins, statistics and
+/// overrides in `opts`, applied to every context.
+pub fn eval_flag_bulk_with<'c>(
+    flag: &Flag,
+    contexts: impl IntoIterator<Item = &'c Context>,
+    opts: &mut EvalOptions<'_>,
+) -> BulkCounts {
+    let mut patterns = Patterns::default();
+    for rule in &flag.rules {
+        let mut found = Vec::new();
+        // A rule over the limits is skipped at evaluation; don't walk it.
+        if check_limits(rule.cond.trim()).is_ok() {
+            collect_regex_patterns(rule.cond.trim(), &mut found);
+        }
+        for pattern in found {
+            // Left uncached on error, so evaluation skips the rule.
+            let _ = patterns.insert(pattern);
+        }
+    }
+    let mut counts = BulkCounts {
+        by_rule: vec![0; flag.rules.len()],
+        ..BulkCounts::default()
+    };
+    for ctx in contexts {
+        let result = evaluate(flag, ctx, Some(&patterns), opts);
+        counts.total += 1;
+        counts.enabled += u64::from(result.enabled);
+        match result.matched_rule {
+            Some(i) => counts.by_rule[i] += 1,
+            None => counts.unmatched += 1,
+        }
     }
+    counts
 }
 
 /// Evaluate the rules of a flag.  Returns the index and value of the first
-/// matching rule, or  if no rule matches.
+/// matching rule, or  if no rule matches.  There is no clock here, so rules
+/// with a scheduling window are skipped.
 pub fn eval_rules(flag: &Flag, ctx: &Context) -> Option<(usize, bool)> {
+    if !flag.state.is_enabled() {
+        return None;
+    }
+    let seed = flag.bucket_seed();
     for (i, rule) in flag.rules.iter().enumerate() {
-        match eval_rule_expr(&flag.key, &rule.cond, ctx) {
+        if rule.window_skip(None).is_some() {
+            continue;
+        }
+        match eval_cond(&seed, &rule.cond, ctx, &mut Scratch::for_flag(flag)) {
             Ok(true) => return Some((i, rule.then_value)),
             Ok(false) => continue,
             Err(_) => continue, // malformed rule, skip
@@ -96,116 +859,882 @@ pub fn eval_rules(flag: &Flag, ctx: &Context) -> Option<(usize, bool)> {
     None
 }
 
-/// Evaluate a single rule expression against the given context.
-///
-/// This function parses and evaluates boolean expressions consisting of
-/// `rollout(p)`, comparisons (`==`, `!=`, `<`, `<=`, `>`, `>=`), membership
-/// checks (`in`) and logical conjunction (`&&`) and disjunction (`||`).
-/// Strings may be quoted with single or double quotes.  Lists for `in`
-/// must be comma separated and enclosed in parentheses.  Unknown or
-/// malformed expressions cause the rule to be skipped (the function
-/// returns an `Err(())`).
-pub fn eval_rule_expr(flag_key: &str, expr: &str, ctx: &Context) -> Result<bool, ()> {
-    eval_expr(flag_key, expr.trim(), ctx)
+/// Outcome of a single rule in a traced evaluation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleOutcome {
+    /// The condition evaluated to `true` and decided the flag value.
+    Matched,
+    /// The condition evaluated to `false`.
+    NotMatched,
+    /// The condition was malformed, so the rule was skipped by the evaluator.
+    Errored,
+    /// The rule was never evaluated; [`RuleTrace::skip_reason`] says why.
+    Skipped,
 }
 
-/// Deterministic rollout function.  Given a flag key, user ID and probability
-/// , returns  if the hash bucket falls below .  The implementation
-/// uses a 32‑bit FNV‑1a hash to compute a value in [0, 1).
-pub fn rollout(flag_key: &str, user_id: &str, p: f64) -> bool {
-    let concatenated = format!("{}:{}", flag_key, user_id);
-    // 32‑bit FNV‑1a parameters
-    let mut hash: u32 = 0x811c9dc5;
-    for byte in concatenated.as_bytes() {
-        hash ^= *byte as u32;
-        hash = hash.wrapping_mul(0x0100_0193);
-    }
-    // Map to [0,1) by dividing by 2^32
-    let bucket = (hash as f64) / 4_294_967_296.0;
-    bucket < p
+/// Why a rule was [`RuleOutcome::Skipped`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// An earlier rule matched first.
+    EarlierMatch,
+    /// The clock is before the rule's `active_from`.
+    NotYetActive,
+    /// The clock is at or past the rule's `active_until`.
+    Expired,
+    /// The rule has a scheduling window but no clock was injected.
+    NoClock,
+    /// The flag is disabled or archived, so none of its rules are evaluated.
+    FlagInactive,
+    /// An override forced the flag's value, so none of its rules are
+    /// evaluated.
+    Overridden,
 }
 
-/// Evaluate a boolean expression with logical operators, comparisons and built‑ins.
-///
-/// The grammar supported by this evaluator is a subset of the contract described in
-/// the README:
-///
-/// ```text
-/// expr      := or_expr
-/// or_expr   := and_expr { "||" and_expr }
-/// and_expr  := cmp_expr { "&&" cmp_expr }
-/// cmp_expr  := term { comp_op term }
-/// comp_op   := "==" | "!=" | "<" | "<=" | ">" | ">=" | " in "
-/// term      := ident | literal | rollout
-/// ident     := unquoted identifier that resolves to a context value
-/// literal   := string | number | boolean
-/// rollout   := "rollout(" number ")"
-/// ```
-///
-/// Strings may be quoted with single or double quotes.  Lists for the `in` operator
-/// must be enclosed in parentheses and separated by commas, for example
-/// `region in ('us','ca','eu')`.  Expressions that do not conform to this grammar
-/// return an `Err(())` and cause their rule to be skipped.
-fn eval_expr(flag_key: &str, s: &str, ctx: &Context) -> Result<bool, ()> {
-    // Try OR level splitting
-    if let Some(idx) = split_top_level(s, "||") {
-        let left = &s[..idx];
-        let right = &s[idx + 2..];
-        let lval = eval_expr(flag_key, left.trim(), ctx)?;
-        if lval {
-            return Ok(true);
+impl SkipReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SkipReason::EarlierMatch => "earlier_match",
+            SkipReason::NotYetActive => "not_yet_active",
+            SkipReason::Expired => "expired",
+            SkipReason::NoClock => "no_clock",
+            SkipReason::FlagInactive => "flag_inactive",
+            SkipReason::Overridden => "overridden",
         }
-        let rval = eval_expr(flag_key, right.trim(), ctx)?;
-        return Ok(rval);
     }
-    // Try AND level splitting
-    if let Some(idx) = split_top_level(s, "&&") {
-        let left = &s[..idx];
-        let right = &s[idx + 2..];
-        let lval = eval_expr(flag_key, left.trim(), ctx)?;
-        if !lval {
-            return Ok(false);
+}
+
+/// A sub-expression visited during evaluation together with the boolean it
+/// produced, for example `country == 'CA'` → `false`.  `value` is `None` when
+/// the sub-expression was malformed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    pub expr: String,
+    pub value: Option<bool>,
+}
+
+/// Per-rule explanation produced by [`eval_flag_with_trace`].
+#[derive(Debug, Clone)]
+pub struct RuleTrace {
+    /// Index of the rule within the flag.
+    pub index: usize,
+    /// The rule condition as written.
+    pub cond: String,
+    pub outcome: RuleOutcome,
+    /// Set exactly when `outcome` is [`RuleOutcome::Skipped`].
+    pub skip_reason: Option<SkipReason>,
+    /// Sub-expressions in evaluation order (innermost first).  Operands
+    /// skipped by short-circuiting do not appear.  Empty for skipped rules.
+    pub steps: Vec<TraceStep>,
+}
+
+/// The result of a traced evaluation: the usual [`EvalResult`] plus one
+/// [`RuleTrace`] for every rule of the flag.
+#[derive(Debug, Clone)]
+pub struct EvalTrace {
+    pub result: EvalResult,
+    pub rules: Vec<RuleTrace>,
+}
+
+impl EvalTrace {
+    /// The trace of forcing `flag` to `enabled`: every rule is skipped with
+    /// [`SkipReason::Overridden`].
+    pub fn overridden(flag: &Flag, enabled: bool) -> Self {
+        let rules = flag
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| RuleTrace {
+                index: i,
+                cond: rule.cond.clone(),
+                outcome: RuleOutcome::Skipped,
+                skip_reason: Some(SkipReason::Overridden),
+                steps: Vec::new(),
+            })
+            .collect();
+        EvalTrace {
+            result: EvalResult::overridden(flag, enabled),
+            rules,
         }
-        let rval = eval_expr(flag_key, right.trim(), ctx)?;
-        return Ok(rval);
     }
-    // Handle comparisons.  Check for the longest operators first to avoid partial matches.
-    for op in [" in ", "<=", ">=", "==", "!=", "<", ">"] {
-        if let Some(idx) = split_top_level(s, op) {
-            let lhs = s[..idx].trim();
-            let rhs = s[idx + op.len()..].trim();
-            let left_value = parse_term_as_value(lhs, ctx, flag_key)?;
-            // Special case for `in` where rhs should be a list of strings
-            if op.trim() == "in" {
-                return eval_in(left_value, rhs);
+}
+
+/// Evaluate a flag like [`eval_flag`] while recording why each rule matched,
+/// did not match, errored or was skipped.  Intended for debugging targeting
+/// rules; the returned `result` is identical to `eval_flag(flag, ctx)`.
+pub fn eval_flag_with_trace(flag: &Flag, ctx: &Context) -> EvalTrace {
+    trace_flag_with(flag, ctx, &mut EvalOptions::default())
+}
+
+/// [`eval_flag_with_trace`] with the clock, custom builtins, statistics and
+/// overrides in `opts`; the returned `result` is identical to
+/// `eval_flag_with(flag, ctx, opts)`, and the stats count the evaluation
+/// the same way.
+pub fn trace_flag_with(flag: &Flag, ctx: &Context, opts: &mut EvalOptions<'_>) -> EvalTrace {
+    evaluate_traced(flag, ctx, None, opts)
+}
+
+fn evaluate_traced(
+    flag: &Flag,
+    ctx: &Context,
+    patterns: Option<&Patterns>,
+    opts: &mut EvalOptions<'_>,
+) -> EvalTrace {
+    if let Some(enabled) = overridden(flag, opts) {
+        return EvalTrace::overridden(flag, enabled);
+    }
+    let now = opts.now;
+    let mut stats = opts.stats.as_deref_mut().map(|stats| stats.entry(flag));
+    if let Some(stats) = stats.as_deref_mut() {
+        stats.evaluations += 1;
+    }
+    let seed = flag.bucket_seed();
+    let mut rules = Vec::with_capacity(flag.rules.len());
+    let mut result = EvalResult {
+        key: flag.key.clone(),
+        enabled: flag.default,
+        matched_rule: None,
+        variant: None,
+        stratum: None,
+        flagset_fingerprint: None,
+        state: flag.state,
+        overridden: false,
+        reason: Reason::fallthrough(flag.state, false),
+    };
+    for (i, rule) in flag.rules.iter().enumerate() {
+        let mut scratch = Scratch {
+            steps: Some(Vec::new()),
+            patterns,
+            functions: opts.functions,
+            now,
+            ..Scratch::for_flag(flag)
+        };
+        let skip_reason = if !flag.state.is_enabled() {
+            Some(SkipReason::FlagInactive)
+        } else if result.matched_rule.is_some() {
+            Some(SkipReason::EarlierMatch)
+        } else {
+            rule.window_skip(now)
+        };
+        let outcome = if skip_reason.is_some() {
+            RuleOutcome::Skipped
+        } else {
+            match eval_cond(&seed, &rule.cond, ctx, &mut scratch) {
+                Ok(true) => {
+                    if let Some(stats) = stats.as_deref_mut() {
+                        stats.matches[i] += 1;
+                    }
+                    result.enabled = rule.then_value;
+                    result.matched_rule = Some(i);
+                    result.variant = scratch.variant;
+                    result.stratum = scratch.stratum;
+                    result.reason = Reason::TargetingMatch;
+                    RuleOutcome::Matched
+                }
+                Ok(false) => RuleOutcome::NotMatched,
+                Err(_) => {
+                    if let Some(stats) = stats.as_deref_mut() {
+                        stats.errors[i] += 1;
+                    }
+                    if result.reason == Reason::Default {
+                        result.reason = Reason::Error;
+                    }
+                    RuleOutcome::Errored
+                }
             }
-            let right_value = parse_term_as_value(rhs, ctx, flag_key)?;
-            return eval_comparison(left_value, op, right_value);
-        }
+        };
+        rules.push(RuleTrace {
+            index: i,
+            cond: rule.cond.clone(),
+            outcome,
+            skip_reason,
+            steps: scratch.steps.unwrap_or_default(),
+        });
     }
-    // Otherwise parse single term as boolean
-    match parse_term_as_value(s, ctx, flag_key)? {
-        Value::Bool(b) => Ok(b),
-        _ => Err(()),
+    if let (Some(stats), None) = (stats, result.matched_rule) {
+        stats.defaults += 1;
     }
+    EvalTrace { result, rules }
 }
 
-/// Split an expression at the first occurrence of `sep` that is not inside
-/// quotes or parentheses.  Returns the index where `sep` starts.
-fn split_top_level(s: &str, sep: &str) -> Option<usize> {
-    let bytes = s.as_bytes();
-    let sep_bytes = sep.as_bytes();
-    let mut single_quote = false;
-    let mut double_quote = false;
-    let mut paren_depth: i32 = 0;
-    let mut i = 0;
-    while i + sep_bytes.len() <= bytes.len() {
-        let c = bytes[i] as char;
-        // Toggle quote states
-        if c == '\'' && !double_quote {
-            single_quote = !single_quote;
-            i += 1;
-            continue;
+/// A flag prepared for repeated evaluation.  Compiling validates every regex
+/// pattern used with `~=` up front and caches the compiled patterns, so
+/// evaluation never recompiles them and invalid patterns are reported with
+/// their rule instead of silently skipping it.  Calls to unknown builtins are
+/// rejected the same way.
+#[derive(Debug, Clone)]
+pub struct CompiledFlag {
+    flag: Flag,
+    patterns: Patterns,
+}
+
+/// Why a flag failed to compile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    /// Index of the offending rule.
+    pub rule: usize,
+    pub kind: CompileErrorKind,
+    /// The pattern, or the call, as written in the rule.
+    pub pattern: String,
+    pub message: String,
+}
+
+/// What in a rule failed to compile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompileErrorKind {
+    /// A `~=` pattern is not a valid regex.
+    InvalidPattern,
+    /// A `name(...)` call to neither a builtin nor a registered function.
+    UnknownBuiltin,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            CompileErrorKind::InvalidPattern => write!(
+                f,
+                "rule {}: invalid pattern '{}': {}",
+                self.rule, self.pattern, self.message
+            ),
+            CompileErrorKind::UnknownBuiltin => write!(f, "rule {}: {}", self.rule, self.message),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+impl CompiledFlag {
+    /// Compile a flag, failing on the first invalid `~=` pattern or call to
+    /// an unknown builtin.  Without the `regex` feature any use of `~=` is
+    /// an error.
+    pub fn compile(flag: Flag) -> Result<Self, CompileError> {
+        Self::compile_with(flag, None)
+    }
+
+    /// [`CompiledFlag::compile`] for a flag evaluated with `functions`, so
+    /// calls to the custom builtins it holds are accepted.
+    pub fn compile_with_functions(
+        flag: Flag,
+        functions: &FunctionRegistry,
+    ) -> Result<Self, CompileError> {
+        Self::compile_with(flag, Some(functions))
+    }
+
+    pub(crate) fn compile_with(
+        flag: Flag,
+        functions: Option<&FunctionRegistry>,
+    ) -> Result<Self, CompileError> {
+        let mut patterns = Patterns::default();
+        for (i, rule) in flag.rules.iter().enumerate() {
+            let cond = rule.cond.trim();
+            // A rule over the limits is skipped at evaluation; don't walk it.
+            if check_limits(cond).is_err() {
+                continue;
+            }
+            if let Some(call) = lint::first_unknown_call(cond, functions) {
+                return Err(CompileError {
+                    rule: i,
+                    kind: CompileErrorKind::UnknownBuiltin,
+                    pattern: call.to_string(),
+                    message: format!("unknown builtin `{call}`"),
+                });
+            }
+            let mut found = Vec::new();
+            collect_regex_patterns(cond, &mut found);
+            for pattern in found {
+                patterns.insert(pattern).map_err(|message| CompileError {
+                    rule: i,
+                    kind: CompileErrorKind::InvalidPattern,
+                    pattern: pattern.to_string(),
+                    message,
+                })?;
+            }
+        }
+        Ok(CompiledFlag { flag, patterns })
+    }
+
+    pub fn flag(&self) -> &Flag {
+        &self.flag
+    }
+
+    /// Same as [`eval_flag`] on the underlying flag, using cached patterns.
+    pub fn eval(&self, ctx: &Context) -> EvalResult {
+        self.eval_with(ctx, &mut EvalOptions::default())
+    }
+
+    /// Same as [`eval_flag_with`] on the underlying flag, using cached
+    /// patterns.
+    pub fn eval_with(&self, ctx: &Context, opts: &mut EvalOptions<'_>) -> EvalResult {
+        evaluate(&self.flag, ctx, Some(&self.patterns), opts)
+    }
+
+    /// Same as [`eval_flag_with_trace`] on the underlying flag, using cached
+    /// patterns.
+    pub fn eval_with_trace(&self, ctx: &Context) -> EvalTrace {
+        self.trace_with(ctx, &mut EvalOptions::default())
+    }
+
+    /// Same as [`trace_flag_with`] on the underlying flag, using cached
+    /// patterns.
+    pub fn trace_with(&self, ctx: &Context, opts: &mut EvalOptions<'_>) -> EvalTrace {
+        evaluate_traced(&self.flag, ctx, Some(&self.patterns), opts)
+    }
+}
+
+/// Compiled `~=` patterns keyed by their source text.
+#[derive(Debug, Clone, Default)]
+struct Patterns {
+    #[cfg(feature = "regex")]
+    compiled: HashMap<String, regex::Regex>,
+}
+
+impl Patterns {
+    #[cfg(feature = "regex")]
+    fn insert(&mut self, pattern: &str) -> Result<(), String> {
+        if !self.compiled.contains_key(pattern) {
+            let re = regex::Regex::new(pattern).map_err(|e| e.to_string())?;
+            self.compiled.insert(pattern.to_string(), re);
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "regex"))]
+    fn insert(&mut self, _pattern: &str) -> Result<(), String> {
+        Err("the `~=` operator requires the `regex` feature".to_string())
+    }
+}
+
+/// Collect the quoted right-hand sides of `~=` comparisons in an expression,
+/// following the same `||` / `&&` splitting as the evaluator.
+fn collect_regex_patterns<'a>(expr: &'a str, out: &mut Vec<&'a str>) {
+    for sep in ["||", "&&"] {
+        if let Some(idx) = split_top_level(expr, sep) {
+            collect_regex_patterns(expr[..idx].trim(), out);
+            collect_regex_patterns(expr[idx + 2..].trim(), out);
+            return;
+        }
+    }
+    if let Some(idx) = split_top_level(expr, "~=") {
+        if let Some(pattern) = unquote(expr[idx + 2..].trim()) {
+            out.push(pattern);
+        }
+    }
+}
+
+/// Evaluate a `~=` comparison, preferring a pattern cached by
+/// [`CompiledFlag`].  Invalid patterns are errors.
+#[cfg(feature = "regex")]
+fn regex_is_match(pattern: &str, text: &str, scratch: &Scratch) -> Result<bool, ()> {
+    if let Some(re) = scratch.patterns.and_then(|p| p.compiled.get(pattern)) {
+        return Ok(re.is_match(text));
+    }
+    regex::Regex::new(pattern)
+        .map(|re| re.is_match(text))
+        .map_err(|_| ())
+}
+
+#[cfg(not(feature = "regex"))]
+fn regex_is_match(_pattern: &str, _text: &str, _scratch: &Scratch) -> Result<bool, ()> {
+    Err(())
+}
+
+/// Evaluate a single rule expression against the given context.
+///
+/// `flag_key` seeds rollout and variant bucketing; pass
+/// [`Flag::bucket_seed`] to honour a flag's salt.  Bucketing always uses
+/// FNV-1a here; evaluate through [`eval_flag`] to honour [`Flag::hash`].
+///
+/// This function parses and evaluates boolean expressions consisting of
+/// `rollout(p)`, comparisons (`==`, `!=`, `<`, `<=`, `>`, `>=`), membership
+/// checks (`in`) and logical conjunction (`&&`) and disjunction (`||`).
+/// Strings may be quoted with single or double quotes.  Lists for `in`
+/// must be comma separated and enclosed in parentheses.  Unknown or
+/// malformed expressions cause the rule to be skipped (the function
+/// returns an `Err(())`), and so do expressions over the [`MAX_EXPR_LEN`],
+/// [`MAX_NESTING_DEPTH`] or [`MAX_LIST_ITEMS`] limits.
+#[allow(clippy::result_unit_err)]
+pub fn eval_rule_expr(flag_key: &str, expr: &str, ctx: &Context) -> Result<bool, ()> {
+    eval_cond(flag_key, expr, ctx, &mut Scratch::default())
+}
+
+/// Evaluate a rule condition, refusing it before any recursion when it is
+/// over the limits.
+fn eval_cond(flag_key: &str, cond: &str, ctx: &Context, scratch: &mut Scratch) -> Result<bool, ()> {
+    let cond = cond.trim();
+    check_limits(cond).map_err(|_| ())?;
+    eval_expr(flag_key, cond, ctx, scratch)
+}
+
+/// Check a rule expression against the grammar of [`eval_rule_expr`]
+/// without evaluating it, so malformed rules can be reported before they are
+/// silently skipped at runtime.  The error describes the first problem found.
+pub fn check_rule(expr: &str) -> Result<(), String> {
+    check_rule_with(expr, None)
+}
+
+/// [`check_rule`] for rules evaluated with `functions`, so calls to the
+/// custom builtins it holds are accepted.
+pub fn check_rule_with_functions(expr: &str, functions: &FunctionRegistry) -> Result<(), String> {
+    check_rule_with(expr, Some(functions))
+}
+
+fn check_rule_with(expr: &str, functions: Option<&FunctionRegistry>) -> Result<(), String> {
+    let expr = expr.trim();
+    check_limits(expr)?;
+    check_expr(expr, functions)
+}
+
+/// Longest rule expression the evaluator accepts, in bytes.
+pub const MAX_EXPR_LEN: usize = 4096;
+
+/// Deepest nesting the evaluator accepts.  Every `&&` and `||` counts as a
+/// level, as does every level of parentheses or braces, because the
+/// evaluator recurses on each of them.
+pub const MAX_NESTING_DEPTH: usize = 64;
+
+/// Most items in one list: an `in (...)` list, a `variant` weight map or the
+/// arguments of a call.
+pub const MAX_LIST_ITEMS: usize = 256;
+
+/// Check an expression against the limits in one pass, before anything
+/// recursive looks at it, so an adversarial flag definition costs linear
+/// time to reject instead of deep recursion or repeated rescans.
+fn check_limits(s: &str) -> Result<(), String> {
+    if s.len() > MAX_EXPR_LEN {
+        return Err(format!(
+            "expression is {} bytes, over the limit of {MAX_EXPR_LEN}",
+            s.len()
+        ));
+    }
+    let bytes = s.as_bytes();
+    let mut quote = None;
+    let mut operators = 0;
+    // Items seen so far in each open bracket, innermost last.
+    let mut items: Vec<usize> = Vec::new();
+    let mut deepest = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                b'\'' | b'"' => quote = Some(c),
+                b'(' | b'{' => {
+                    items.push(1);
+                    deepest = deepest.max(items.len());
+                }
+                b')' | b'}' => {
+                    items.pop();
+                }
+                b',' => {
+                    if let Some(count) = items.last_mut() {
+                        *count += 1;
+                        if *count > MAX_LIST_ITEMS {
+                            return Err(format!("a list has more than {MAX_LIST_ITEMS} items"));
+                        }
+                    }
+                }
+                b'&' | b'|' if bytes.get(i + 1) == Some(&c) => {
+                    operators += 1;
+                    i += 1;
+                }
+                _ => {}
+            },
+        }
+        i += 1;
+    }
+    if operators + deepest > MAX_NESTING_DEPTH {
+        return Err(format!(
+            "expression nests {} levels deep, over the limit of {MAX_NESTING_DEPTH}",
+            operators + deepest
+        ));
+    }
+    Ok(())
+}
+
+fn check_expr(s: &str, functions: Option<&FunctionRegistry>) -> Result<(), String> {
+    if s.is_empty() {
+        return Err("empty expression".to_string());
+    }
+    for sep in ["||", "&&"] {
+        if let Some(idx) = split_top_level(s, sep) {
+            check_expr(s[..idx].trim(), functions)?;
+            return check_expr(s[idx + 2..].trim(), functions);
+        }
+    }
+    for op in COMPARISON_OPERATORS {
+        if let Some(idx) = split_top_level(s, op) {
+            let (lhs, rhs) = (s[..idx].trim(), s[idx + op.len()..].trim());
+            if lhs.is_empty() || rhs.is_empty() {
+                return Err(format!("`{}` is missing an operand", op.trim()));
+            }
+            check_term(lhs, functions)?;
+            return match op.trim() {
+                "~=" => unquote(rhs)
+                    .map(|_| ())
+                    .ok_or_else(|| "`~=` needs a quoted pattern".to_string()),
+                "in" if rhs.starts_with('(') => check_in_list(rhs),
+                _ => check_term(rhs, functions),
+            };
+        }
+    }
+    check_term(s, functions)?;
+    if s.starts_with("bucket(") {
+        return Err(format!(
+            "`{s}` is a number, not a condition; compare it, e.g. `bucket(10) < 3`"
+        ));
+    }
+    let literal = s == "null" || s.parse::<f64>().is_ok() || unquote(s).is_some();
+    if literal {
+        return Err(format!("`{s}` is a literal, not a condition"));
+    }
+    Ok(())
+}
+
+fn check_term(t: &str, functions: Option<&FunctionRegistry>) -> Result<(), String> {
+    if is_variant_call(t) {
+        let (weights, _) = parse_bucketing_args(&t["variant(".len()..t.len() - 1])
+            .map_err(|_| "malformed `variant(...)` arguments".to_string())?;
+        return parse_variant_weights(weights).map(|_| ()).map_err(|_| {
+            "variant weights must map quoted names to non-negative numbers summing to at most 1"
+                .to_string()
+        });
+    }
+    if let Some(inner) = t
+        .strip_prefix("rollout(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let (p, _) = parse_bucketing_args(inner)
+            .map_err(|_| "malformed `rollout(...)` arguments".to_string())?;
+        return p
+            .parse::<f64>()
+            .map(|_| ())
+            .map_err(|_| format!("`rollout(...)` needs a numeric share, found `{p}`"));
+    }
+    if let Some(inner) = t
+        .strip_prefix("ramp(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let (args, _) = parse_bucketing_call(inner, 4)
+            .map_err(|_| "malformed `ramp(...)` arguments".to_string())?;
+        return Ramp::parse(&args).map(|_| ()).map_err(|_| {
+            "`ramp(...)` needs quoted start and end instants, start first, then two numeric shares"
+                .to_string()
+        });
+    }
+    if let Some(inner) = t
+        .strip_prefix("bucket(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let (n, _) = parse_bucketing_args(inner)
+            .map_err(|_| "malformed `bucket(...)` arguments".to_string())?;
+        return parse_bucket_count(n).map(|_| ()).map_err(|_| {
+            format!("`bucket(...)` needs a positive whole number of buckets, found `{n}`")
+        });
+    }
+    for name in ["exists(", "missing("] {
+        if let Some(inner) = t.strip_prefix(name).and_then(|rest| rest.strip_suffix(')')) {
+            return match is_identifier(inner.trim()) {
+                true => Ok(()),
+                false => Err(format!("`{name}...)` needs an attribute name")),
+            };
+        }
+    }
+    for name in ["any(", "all("] {
+        if let Some(inner) = t.strip_prefix(name).and_then(|rest| rest.strip_suffix(')')) {
+            let args = split_args(inner);
+            let Some((list, wanted)) = args.split_first().filter(|(_, w)| !w.is_empty()) else {
+                return Err(format!("`{name}...)` needs a list and at least one value"));
+            };
+            check_term(list, functions)?;
+            for literal in wanted {
+                if !is_literal(literal) {
+                    return Err(format!("`{literal}` in `{name}...)` is not a literal"));
+                }
+            }
+            return Ok(());
+        }
+    }
+    if let Some((_, inner)) = functions.and_then(|f| f.lookup(t)) {
+        return split_args(inner)
+            .into_iter()
+            .try_for_each(|arg| check_term(arg, functions));
+    }
+    if t == "now" || is_literal(t) || is_identifier(t) {
+        return Ok(());
+    }
+    Err(format!("unrecognised term `{t}`"))
+}
+
+fn check_in_list(rhs: &str) -> Result<(), String> {
+    let inner = rhs
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+        .ok_or_else(|| "unclosed `in` list".to_string())?;
+    for part in inner.split(',') {
+        if unquote(part.trim()).is_none() {
+            return Err(format!(
+                "`in` list item `{}` is not a quoted string",
+                part.trim()
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn is_literal(t: &str) -> bool {
+    t.eq_ignore_ascii_case("true")
+        || t.eq_ignore_ascii_case("false")
+        || t == "null"
+        || t.parse::<f64>().is_ok()
+        || unquote(t).is_some()
+}
+
+/// Deterministic rollout function.  Given a flag key, user ID and probability
+/// , returns  if the hash bucket falls below .  The implementation
+/// uses a 32‑bit FNV‑1a hash to compute a value in [0, 1).
+pub fn rollout(flag_key: &str, user_id: &str, p: f64) -> bool {
+    bucket(flag_key, user_id) < p
+}
+
+/// Deterministic bucket in [0, 1) for a flag key and user ID.  Shared by
+/// [`rollout`] and [`assign_variant`] so both are sticky per user.
+pub fn bucket(flag_key: &str, user_id: &str) -> f64 {
+    bucket_with(&Fnv1a, flag_key, user_id)
+}
+
+/// Like [`bucket`] but hashing `flag_key:user_id` with the given [`Hasher`].
+pub fn bucket_with(hasher: &dyn Hasher, flag_key: &str, user_id: &str) -> f64 {
+    let concatenated = format!("{}:{}", flag_key, user_id);
+    // Map to [0,1) by dividing by 2^32
+    (hasher.hash32(concatenated.as_bytes()) as f64) / 4_294_967_296.0
+}
+
+/// Deterministic bucket index in `0..n` for a flag key and user ID: the
+/// [`bucket`] scaled to `n` equal slices, so `bucket_index(k, u, 10) < 3`
+/// exactly when `rollout(k, u, 0.3)`.  `n` must be positive.
+pub fn bucket_index(flag_key: &str, user_id: &str, n: u32) -> u32 {
+    index_in_bucket(bucket(flag_key, user_id), n)
+}
+
+/// The slice of `n` containing bucket `b`.  See [`bucket_index`].
+fn index_in_bucket(b: f64, n: u32) -> u32 {
+    ((b * f64::from(n)) as u32).min(n.saturating_sub(1))
+}
+
+/// The bucket count of a `bucket(n)` call: a positive whole number.
+fn parse_bucket_count(n: &str) -> Result<u32, ()> {
+    n.trim().parse::<u32>().ok().filter(|n| *n > 0).ok_or(())
+}
+
+/// Assign a weighted variant to a user.  Weights are consumed in order as
+/// consecutive slices of [0, 1); the user's [`bucket`] selects the slice.
+/// Returns `None` when the bucket falls past the sum of the weights, so
+/// weights summing to less than one leave the remaining users unassigned.
+pub fn assign_variant<'a>(
+    flag_key: &str,
+    user_id: &str,
+    weights: &'a [(String, f64)],
+) -> Option<&'a str> {
+    variant_in_bucket(bucket(flag_key, user_id), weights)
+}
+
+/// Select the weight slice containing bucket `b`.  See [`assign_variant`].
+fn variant_in_bucket(b: f64, weights: &[(String, f64)]) -> Option<&str> {
+    let mut upper = 0.0;
+    for (name, weight) in weights {
+        upper += weight;
+        if b < upper {
+            return Some(name);
+        }
+    }
+    None
+}
+
+/// Per-rule state threaded through the expression evaluator.
+#[derive(Debug, Default)]
+struct Scratch<'a> {
+    /// Visited sub-expressions, recorded only for traced evaluations.
+    steps: Option<Vec<TraceStep>>,
+    /// Variant assigned by a `variant(...)` call while evaluating the rule.
+    variant: Option<String>,
+    /// Stratum of the last `per='attr'` bucketing evaluated in the rule.
+    stratum: Option<Stratum>,
+    /// Hash algorithm used by `rollout(...)` and `variant(...)`.
+    hash: HashAlgorithm,
+    /// Patterns precompiled by [`CompiledFlag`], if evaluating through one.
+    #[cfg_attr(not(feature = "regex"), allow(dead_code))]
+    patterns: Option<&'a Patterns>,
+    /// Custom builtins the caller evaluates with, if any.
+    functions: Option<&'a FunctionRegistry>,
+    /// Value of the `now` builtin, injected by the caller.
+    now: Option<Timestamp>,
+}
+
+impl Scratch<'_> {
+    fn for_flag(flag: &Flag) -> Self {
+        Scratch {
+            hash: flag.hash,
+            ..Scratch::default()
+        }
+    }
+}
+
+/// Comparison operators in the order the evaluator tries them, longest
+/// first so `<=` is not split as `<`.
+const COMPARISON_OPERATORS: [&str; 12] = [
+    " in ",
+    "~=",
+    " contains ",
+    " startsWith ",
+    " endsWith ",
+    " matches ",
+    "<=",
+    ">=",
+    "==",
+    "!=",
+    "<",
+    ">",
+];
+
+/// Evaluate a boolean expression with logical operators, comparisons and built‑ins.
+///
+/// The grammar supported by this evaluator is a subset of the contract described in
+/// the README:
+///
+/// ```text
+/// expr      := or_expr
+/// or_expr   := and_expr { "||" and_expr }
+/// and_expr  := cmp_expr { "&&" cmp_expr }
+/// cmp_expr  := term { comp_op term }
+/// comp_op   := "==" | "!=" | "<" | "<=" | ">" | ">=" | " in "
+///            | " contains " | " startsWith " | " endsWith " | " matches " | "~="
+/// term      := ident | literal | "now" | rollout | ramp | bucket | variant | any | all | exists
+///            | missing | call
+/// ident     := unquoted identifier that resolves to a context value
+/// literal   := string | number | boolean | "null"
+/// rollout   := "rollout(" number { "," named } ")"
+/// ramp      := "ramp(" string "," string "," number "," number { "," named } ")"
+/// bucket    := "bucket(" integer { "," named } ")"
+/// variant   := "variant({" string ":" number { "," string ":" number } "}" { "," named } ")"
+/// named     := "by=" string | "per=" string | "layer=" string
+/// any       := "any(" ident { "," literal } ")"
+/// all       := "all(" ident { "," literal } ")"
+/// exists    := "exists(" ident ")"
+/// missing   := "missing(" ident ")"
+/// call      := name "(" [ term { "," term } ] ")"   a custom builtin, see FunctionRegistry
+/// ```
+///
+/// Strings may be quoted with single or double quotes.  Inline lists for the `in`
+/// operator must be enclosed in parentheses and separated by commas, for example
+/// `region in ('us','ca','eu')`; otherwise the right-hand side is resolved as a term
+/// and must be a list-valued context attribute (a missing attribute is an empty
+/// list).  Expressions that do not conform to this grammar
+/// return an `Err(())` and cause their rule to be skipped.
+///
+/// When `scratch` carries a step list, every visited sub-expression is
+/// appended to it together with its result.
+fn eval_expr(flag_key: &str, s: &str, ctx: &Context, scratch: &mut Scratch) -> Result<bool, ()> {
+    let result = eval_expr_node(flag_key, s, ctx, scratch);
+    if let Some(steps) = scratch.steps.as_mut() {
+        steps.push(TraceStep {
+            expr: s.to_string(),
+            value: result.ok(),
+        });
+    }
+    result
+}
+
+/// Evaluate one node of the expression grammar.  See [`eval_expr`].
+fn eval_expr_node(
+    flag_key: &str,
+    s: &str,
+    ctx: &Context,
+    scratch: &mut Scratch,
+) -> Result<bool, ()> {
+    // Try OR level splitting
+    if let Some(idx) = split_top_level(s, "||") {
+        let left = &s[..idx];
+        let right = &s[idx + 2..];
+        let lval = eval_expr(flag_key, left.trim(), ctx, scratch)?;
+        if lval {
+            return Ok(true);
+        }
+        let rval = eval_expr(flag_key, right.trim(), ctx, scratch)?;
+        return Ok(rval);
+    }
+    // Try AND level splitting
+    if let Some(idx) = split_top_level(s, "&&") {
+        let left = &s[..idx];
+        let right = &s[idx + 2..];
+        let lval = eval_expr(flag_key, left.trim(), ctx, scratch)?;
+        if !lval {
+            return Ok(false);
+        }
+        let rval = eval_expr(flag_key, right.trim(), ctx, scratch)?;
+        return Ok(rval);
+    }
+    // Handle comparisons.  Check for the longest operators first to avoid partial matches.
+    for op in COMPARISON_OPERATORS {
+        if let Some(idx) = split_top_level(s, op) {
+            let lhs = s[..idx].trim();
+            let rhs = s[idx + op.len()..].trim();
+            let left_value = parse_term_as_value(lhs, ctx, flag_key, scratch)?;
+            // `~=` takes a quoted pattern, never a context value
+            if op == "~=" {
+                let pattern = unquote(rhs).ok_or(())?;
+                return match left_value {
+                    Value::Str(text) => regex_is_match(pattern, &text, scratch),
+                    _ => Ok(false),
+                };
+            }
+            // Special case for `in` where rhs should be a list of strings
+            if op.trim() == "in" {
+                if rhs.starts_with('(') {
+                    return eval_in(left_value, rhs);
+                }
+                // A missing attribute contains nothing; a scalar is not a list.
+                return match parse_term_as_value(rhs, ctx, flag_key, scratch)? {
+                    list @ Value::List(_) => Ok(list_contains(&list, &left_value)),
+                    Value::Null => Ok(false),
+                    _ => Err(()),
+                };
+            }
+            let right_value = parse_term_as_value(rhs, ctx, flag_key, scratch)?;
+            // Only the `null` literal compares by presence; a missing
+            // attribute against anything else is a type mismatch.
+            if lhs == "null" || rhs == "null" {
+                return Ok(eval_null_comparison(&left_value, op.trim(), &right_value));
+            }
+            return eval_comparison(left_value, op.trim(), right_value);
+        }
+    }
+    // Otherwise parse single term as boolean.  A bare `variant(...)` call is
+    // true when the user was assigned one of its variants.
+    match parse_term_as_value(s, ctx, flag_key, scratch)? {
+        Value::Bool(b) => Ok(b),
+        Value::Str(_) if is_variant_call(s) => Ok(true),
+        Value::Null if is_variant_call(s) => Ok(false),
+        _ => Err(()),
+    }
+}
+
+/// Split an expression at the first occurrence of `sep` that is not inside
+/// quotes, parentheses or braces.  Returns the index where `sep` starts.
+fn split_top_level(s: &str, sep: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let sep_bytes = sep.as_bytes();
+    let mut single_quote = false;
+    let mut double_quote = false;
+    let mut paren_depth: i32 = 0;
+    let mut i = 0;
+    while i + sep_bytes.len() <= bytes.len() {
+        let c = bytes[i] as char;
+        // Toggle quote states
+        if c == '\'' && !double_quote {
+            single_quote = !single_quote;
+            i += 1;
+            continue;
         }
         if c == '"' && !single_quote {
             double_quote = !double_quote;
@@ -214,16 +1743,14 @@ fn split_top_level(s: &str, sep: &str) -> Option<usize> {
         }
         // Track parentheses depth
         if !single_quote && !double_quote {
-            if c == '(' {
+            if c == '(' || c == '{' {
                 paren_depth += 1;
-            } else if c == ')' {
+            } else if c == ')' || c == '}' {
                 paren_depth -= 1;
             }
             // Check for separator at top level
-            if paren_depth == 0 && !single_quote && !double_quote {
-                if bytes[i..].starts_with(sep_bytes) {
-                    return Some(i);
-                }
+            if paren_depth == 0 && bytes[i..].starts_with(sep_bytes) {
+                return Some(i);
             }
         }
         i += 1;
@@ -231,20 +1758,108 @@ fn split_top_level(s: &str, sep: &str) -> Option<usize> {
     None
 }
 
-/// Parse a term into a `Value`.  A term can be a literal, identifier or rollout call.
-fn parse_term_as_value(term: &str, ctx: &Context, flag_key: &str) -> Result<Value, ()> {
+/// Parse a term into a `Value`.  A term can be a literal, identifier, rollout or
+/// variant call.
+fn parse_term_as_value(
+    term: &str,
+    ctx: &Context,
+    flag_key: &str,
+    scratch: &mut Scratch,
+) -> Result<Value, ()> {
     let t = term.trim();
+    // variant({'a': 0.5, 'b': 0.5}) yields the assigned variant name or Null
+    if is_variant_call(t) {
+        let (weights, bucketing) = parse_bucketing_args(&t["variant(".len()..t.len() - 1])?;
+        let weights = parse_variant_weights(weights)?;
+        let b = bucketing.bucket(ctx, flag_key, scratch);
+        return Ok(match variant_in_bucket(b, &weights) {
+            Some(name) => {
+                scratch.variant = Some(name.to_string());
+                Value::Str(name.to_string())
+            }
+            None => Value::Null,
+        });
+    }
     // rollout(p)
     if let Some(rest) = t.strip_prefix("rollout(") {
         if let Some(inner) = rest.strip_suffix(')') {
-            let p: f64 = inner.trim().parse().map_err(|_| ())?;
-            // Extract userId from context, default to empty string if missing or not a string
-            let user_id = match ctx.get("userId") {
-                Some(Value::Str(s)) => s.as_str(),
-                _ => "",
+            let (p, bucketing) = parse_bucketing_args(inner)?;
+            let p: f64 = p.parse().map_err(|_| ())?;
+            let b = bucketing.bucket(ctx, flag_key, scratch);
+            return Ok(Value::Bool(b < p));
+        }
+    }
+    // ramp(start, end, from, to): a rollout whose share moves with `now`
+    if let Some(inner) = t
+        .strip_prefix("ramp(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let (args, bucketing) = parse_bucketing_call(inner, 4)?;
+        let ramp = Ramp::parse(&args)?;
+        let now = scratch.now.ok_or(())?;
+        let b = bucketing.bucket(ctx, flag_key, scratch);
+        return Ok(Value::Bool(b < ramp.share(now)));
+    }
+    // bucket(n): the user's bucket as a whole number in 0..n
+    if let Some(inner) = t
+        .strip_prefix("bucket(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let (n, bucketing) = parse_bucketing_args(inner)?;
+        let n = parse_bucket_count(n)?;
+        let b = bucketing.bucket(ctx, flag_key, scratch);
+        return Ok(Value::Num(f64::from(index_in_bucket(b, n))));
+    }
+    // now: the caller-supplied evaluation instant.  Without one the rule
+    // cannot be decided and is skipped.
+    if t == "now" {
+        return scratch.now.map(Value::Time).ok_or(());
+    }
+    // exists(attr) / missing(attr): presence of a non-null context value
+    for (name, want_present) in [("exists(", true), ("missing(", false)] {
+        if let Some(inner) = t.strip_prefix(name).and_then(|rest| rest.strip_suffix(')')) {
+            let attr = inner.trim();
+            if !is_identifier(attr) {
+                return Err(());
+            }
+            let present = !matches!(ctx.get(attr), None | Some(Value::Null));
+            return Ok(Value::Bool(present == want_present));
+        }
+    }
+    // any(list, ...) / all(list, ...)
+    for (name, require_all) in [("any(", false), ("all(", true)] {
+        if let Some(inner) = t.strip_prefix(name).and_then(|rest| rest.strip_suffix(')')) {
+            let args = split_args(inner);
+            let (list, wanted) = args.split_first().ok_or(())?;
+            if wanted.is_empty() {
+                return Err(());
+            }
+            let list = parse_term_as_value(list, ctx, flag_key, scratch)?;
+            let mut found = Vec::with_capacity(wanted.len());
+            for literal in wanted {
+                let value = parse_term_as_value(literal, &Context::new(), flag_key, scratch)?;
+                found.push(list_contains(&list, &value));
+            }
+            let result = if require_all {
+                found.iter().all(|f| *f)
+            } else {
+                found.iter().any(|f| *f)
             };
-            return Ok(Value::Bool(rollout(flag_key, user_id, p)));
+            return Ok(Value::Bool(result));
+        }
+    }
+    // name(args...): a custom builtin from the caller's registry
+    if let Some((function, inner)) = scratch.functions.and_then(|f| f.lookup(t)) {
+        let mut args = Vec::new();
+        for arg in split_args(inner) {
+            args.push(parse_term_as_value(arg, ctx, flag_key, scratch)?);
         }
+        return function(&args);
+    }
+    // Any other call names nothing the evaluator knows; failing here keeps a
+    // typo from reading as a missing attribute and matching `== null`.
+    if functions::call_parts(t).is_some() {
+        return Err(());
     }
     // boolean literal
     if t.eq_ignore_ascii_case("true") {
@@ -253,13 +1868,16 @@ fn parse_term_as_value(term: &str, ctx: &Context, flag_key: &str) -> Result<Valu
     if t.eq_ignore_ascii_case("false") {
         return Ok(Value::Bool(false));
     }
+    // null literal, for `attr == null` / `attr != null`
+    if t == "null" {
+        return Ok(Value::Null);
+    }
     // numeric literal
     if let Ok(n) = t.parse::<f64>() {
         return Ok(Value::Num(n));
     }
     // string literal in single or double quotes
-    if (t.starts_with('"') && t.ends_with('"')) || (t.starts_with('\'') && t.ends_with('\'')) {
-        let content = &t[1..t.len() - 1];
+    if let Some(content) = unquote(t) {
         return Ok(Value::Str(content.to_string()));
     }
     // identifier resolves from context
@@ -270,32 +1888,316 @@ fn parse_term_as_value(term: &str, ctx: &Context, flag_key: &str) -> Result<Valu
     Ok(Value::Null)
 }
 
-/// Evaluate a comparison between two values using the given operator.
-fn eval_comparison(left: Value, op: &str, right: Value) -> Result<bool, ()> {
-    match (left, right) {
-        (Value::Str(a), Value::Str(b)) => match op {
-            "==" => Ok(a == b),
-            "!=" => Ok(a != b),
-            _ => Err(()),
-        },
-        (Value::Num(a), Value::Num(b)) => match op {
-            "==" => Ok((a - b).abs() < std::f64::EPSILON),
-            "!=" => Ok((a - b).abs() >= std::f64::EPSILON),
-            "<" => Ok(a < b),
-            "<=" => Ok(a <= b),
-            ">" => Ok(a > b),
-            ">=" => Ok(a >= b),
-            _ => Err(()),
-        },
-        (Value::Bool(a), Value::Bool(b)) => match op {
-            "==" => Ok(a == b),
-            "!=" => Ok(a != b),
-            _ => Err(()),
-        },
-        _ => Ok(false), // mismatched types always false for comparisons
+/// Named arguments of `rollout(...)`, `bucket(...)` and `variant(...)`.
+struct Bucketing<'a> {
+    /// Attribute identifying the unit being bucketed; `by='attr'`,
+    /// defaulting to `userId`.
+    by: &'a str,
+    /// Attribute whose value partitions users into strata; `per='attr'`.
+    per: Option<&'a str>,
+    /// Experiment layer; `layer='name'`.  Calls in the same layer share
+    /// buckets across flags.
+    layer: Option<&'a str>,
+}
+
+impl Bucketing<'_> {
+    /// The bucket for this call.  A stratified call hashes
+    /// `flag:stratum:id`, so each stratum is bucketed independently and a
+    /// rollout of `p` reaches a share `p` of every stratum.  A layered call
+    /// hashes `layer:name` in place of the flag key and salt, so flags that
+    /// split one layer into disjoint ranges reach disjoint users.
+    fn bucket(&self, ctx: &Context, flag_key: &str, scratch: &mut Scratch) -> f64 {
+        let id = bucketing_id(ctx, self.by);
+        let layer_seed;
+        let flag_key = match self.layer {
+            Some(name) => {
+                layer_seed = format!("layer:{name}");
+                layer_seed.as_str()
+            }
+            None => flag_key,
+        };
+        let Some(attr) = self.per else {
+            return bucket_with(scratch.hash.hasher(), flag_key, id);
+        };
+        let value = bucketing_id(ctx, attr);
+        scratch.stratum = Some(Stratum {
+            attribute: attr.to_string(),
+            value: value.to_string(),
+        });
+        bucket_with(scratch.hash.hasher(), &format!("{flag_key}:{value}"), id)
+    }
+}
+
+/// Split the arguments of `rollout(...)`, `bucket(...)` or `variant(...)`
+/// into the leading argument and the named `by='attr'`, `per='attr'` and
+/// `layer='name'` arguments, in any order.
+fn parse_bucketing_args(inner: &str) -> Result<(&str, Bucketing<'_>), ()> {
+    let (lead, bucketing) = parse_bucketing_call(inner, 1)?;
+    Ok((lead[0], bucketing))
+}
+
+/// Like [`parse_bucketing_args`] for a call with `positional` leading
+/// arguments, all of which must be present.
+fn parse_bucketing_call(inner: &str, positional: usize) -> Result<(Vec<&str>, Bucketing<'_>), ()> {
+    if inner.trim_end().ends_with(',') {
+        return Err(());
+    }
+    let mut args = split_args(inner);
+    if args.len() < positional {
+        return Err(());
+    }
+    let named = args.split_off(positional);
+    let lead = args;
+    let mut bucketing = Bucketing {
+        by: "userId",
+        per: None,
+        layer: None,
+    };
+    for arg in named {
+        let (name, attr) = arg.split_once('=').ok_or(())?;
+        let attr = unquote(attr.trim()).ok_or(())?;
+        match name.trim() {
+            "by" => bucketing.by = attr,
+            "per" => bucketing.per = Some(attr),
+            "layer" => bucketing.layer = Some(attr),
+            _ => return Err(()),
+        }
+    }
+    Ok((lead, bucketing))
+}
+
+/// The schedule of a `ramp(start, end, from, to)` call: a rollout share that
+/// grows (or shrinks) linearly from `from` at `start` to `to` at `end`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Ramp {
+    start: Timestamp,
+    end: Timestamp,
+    from: f64,
+    to: f64,
+}
+
+impl Ramp {
+    /// Parse the four leading arguments: quoted RFC 3339 instants, `start`
+    /// strictly before `end`, then two numeric shares.
+    fn parse(args: &[&str]) -> Result<Self, ()> {
+        let [start, end, from, to] = args else {
+            return Err(());
+        };
+        let start = parse_time(unquote(start).ok_or(())?)?;
+        let end = parse_time(unquote(end).ok_or(())?)?;
+        let from: f64 = from.parse().map_err(|_| ())?;
+        let to: f64 = to.parse().map_err(|_| ())?;
+        if start >= end || from.is_nan() || to.is_nan() {
+            return Err(());
+        }
+        Ok(Ramp {
+            start,
+            end,
+            from,
+            to,
+        })
+    }
+
+    /// The share at `now`: nobody before `start`, `from` at `start`, `to`
+    /// from `end` on, and linear in between.  Buckets are fixed, so while the
+    /// share grows every user reached earlier stays reached.
+    fn share(&self, now: Timestamp) -> f64 {
+        if now < self.start {
+            return 0.0;
+        }
+        if now >= self.end {
+            return self.to;
+        }
+        let elapsed = (now.unix_millis() - self.start.unix_millis()) as f64;
+        let span = (self.end.unix_millis() - self.start.unix_millis()) as f64;
+        self.from + (self.to - self.from) * elapsed / span
+    }
+}
+
+/// Split a comma separated argument list at the top level.
+fn split_args(inner: &str) -> Vec<&str> {
+    let mut args = Vec::new();
+    let mut rest = inner;
+    while let Some(idx) = split_top_level(rest, ",") {
+        args.push(rest[..idx].trim());
+        rest = &rest[idx + 1..];
+    }
+    if !rest.trim().is_empty() {
+        args.push(rest.trim());
+    }
+    args
+}
+
+/// Whether `list` is a [`Value::List`] holding an element equal to `item`.
+/// Anything else, including a missing attribute, contains nothing.
+fn list_contains(list: &Value, item: &Value) -> bool {
+    let Value::List(items) = list else {
+        return false;
+    };
+    items.iter().any(|candidate| match (candidate, item) {
+        (Value::Str(a), Value::Str(b)) => a == b,
+        (Value::Num(a), Value::Num(b)) => (a - b).abs() < f64::EPSILON,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Time(a), Value::Time(b)) => a == b,
+        _ => false,
+    })
+}
+
+/// Resolve the identifier used for bucketing.  Missing or non-string
+/// attributes bucket as the empty string.
+fn bucketing_id<'a>(ctx: &'a Context, attr: &str) -> &'a str {
+    match ctx.get(attr) {
+        Some(Value::Str(s)) => s.as_str(),
+        _ => "",
+    }
+}
+
+/// Whether a term is a bare attribute name: ASCII letters, digits, `_`, `-`
+/// and `.`, not starting with a digit.
+fn is_identifier(t: &str) -> bool {
+    !t.is_empty()
+        && !t.starts_with(|c: char| c.is_ascii_digit())
+        && t.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Whether a term is a `variant(...)` call.
+fn is_variant_call(t: &str) -> bool {
+    t.starts_with("variant(") && t.ends_with(')')
+}
+
+/// Parse the weight map of a `variant(...)` call, for example
+/// `{'a': 0.5, 'b': 0.3}`.  Names must be quoted, weights must be
+/// non-negative and the total may not exceed one.
+fn parse_variant_weights(inner: &str) -> Result<Vec<(String, f64)>, ()> {
+    let inner = inner.trim();
+    let body = inner
+        .strip_prefix('{')
+        .and_then(|rest| rest.strip_suffix('}'))
+        .ok_or(())?;
+    let mut weights = Vec::new();
+    let mut total = 0.0;
+    for entry in body.split(',') {
+        let (name, weight) = entry.rsplit_once(':').ok_or(())?;
+        let name = unquote(name.trim()).ok_or(())?;
+        let weight: f64 = weight.trim().parse().map_err(|_| ())?;
+        if weight.is_nan() || weight < 0.0 {
+            return Err(());
+        }
+        total += weight;
+        weights.push((name.to_string(), weight));
+    }
+    if total > 1.0 + 1e-9 {
+        return Err(());
+    }
+    Ok(weights)
+}
+
+/// Strip matching single or double quotes from a string literal.
+fn unquote(token: &str) -> Option<&str> {
+    if token.len() >= 2
+        && ((token.starts_with('"') && token.ends_with('"'))
+            || (token.starts_with('\'') && token.ends_with('\'')))
+    {
+        Some(&token[1..token.len() - 1])
+    } else {
+        None
     }
 }
 
+/// Evaluate a comparison between two values using the given operator.
+fn eval_comparison(left: Value, op: &str, right: Value) -> Result<bool, ()> {
+    match (left, right) {
+        (Value::Str(a), Value::Str(b)) => match op {
+            "==" => Ok(a == b),
+            "!=" => Ok(a != b),
+            "contains" => Ok(a.contains(&b)),
+            "startsWith" => Ok(a.starts_with(&b)),
+            "endsWith" => Ok(a.ends_with(&b)),
+            "matches" => Ok(glob_match(&b, &a)),
+            // Strings order only as RFC 3339 timestamps.
+            "<" | "<=" | ">" | ">=" => compare_times(parse_time(&a)?, op, parse_time(&b)?),
+            _ => Err(()),
+        },
+        // An instant compares with another instant or an RFC 3339 string; a
+        // string that is not a timestamp makes the rule malformed.
+        (Value::Time(a), Value::Time(b)) => compare_times(a, op, b),
+        (Value::Time(a), Value::Str(b)) => compare_times(a, op, parse_time(&b)?),
+        (Value::Str(a), Value::Time(b)) => compare_times(parse_time(&a)?, op, b),
+        (Value::Num(a), Value::Num(b)) => match op {
+            "==" => Ok((a - b).abs() < f64::EPSILON),
+            "!=" => Ok((a - b).abs() >= f64::EPSILON),
+            "<" => Ok(a < b),
+            "<=" => Ok(a <= b),
+            ">" => Ok(a > b),
+            ">=" => Ok(a >= b),
+            _ => Err(()),
+        },
+        (Value::Bool(a), Value::Bool(b)) => match op {
+            "==" => Ok(a == b),
+            "!=" => Ok(a != b),
+            _ => Err(()),
+        },
+        _ => Ok(false), // mismatched types always false for comparisons
+    }
+}
+
+/// A comparison with the `null` literal on one side.  Only null equals
+/// null, so `attr != null` is true for any present value, including `false`
+/// and the empty string; other operators never match.
+fn eval_null_comparison(left: &Value, op: &str, right: &Value) -> bool {
+    let both_null = matches!((left, right), (Value::Null, Value::Null));
+    match op {
+        "==" => both_null,
+        "!=" => !both_null,
+        _ => false,
+    }
+}
+
+fn parse_time(text: &str) -> Result<Timestamp, ()> {
+    Timestamp::parse_rfc3339(text).ok_or(())
+}
+
+fn compare_times(a: Timestamp, op: &str, b: Timestamp) -> Result<bool, ()> {
+    match op {
+        "==" => Ok(a == b),
+        "!=" => Ok(a != b),
+        "<" => Ok(a < b),
+        "<=" => Ok(a <= b),
+        ">" => Ok(a > b),
+        ">=" => Ok(a >= b),
+        _ => Err(()),
+    }
+}
+
+/// Glob matching for the `matches` operator: `*` matches any run of
+/// characters (including none) and `?` matches exactly one character.
+/// Everything else matches literally and the whole string must match.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` seen and the text index it is matched up to.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last `*` swallow one more character and retry.
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
 /// Evaluate the `in` operator: left must be a string and right must be a list of strings.
 fn eval_in(left: Value, rhs: &str) -> Result<bool, ()> {
     match left {
@@ -308,11 +2210,8 @@ fn eval_in(left: Value, rhs: &str) -> Result<bool, ()> {
             let inner = &trimmed[1..trimmed.len() - 1];
             for part in inner.split(',') {
                 let token = part.trim();
-                if (token.starts_with('"') && token.ends_with('"')) || (token.starts_with('\'') && token.ends_with('\'')) {
-                    let content = &token[1..token.len() - 1];
-                    if content == s {
-                        return Ok(true);
-                    }
+                if unquote(token) == Some(s.as_str()) {
+                    return Ok(true);
                 }
             }
             Ok(false)
@@ -325,6 +2224,14 @@ fn eval_in(left: Value, rhs: &str) -> Result<bool, ()> {
 mod tests {
     use super::*;
 
+    /// Options with `now` as the clock.
+    fn clock(now: Timestamp) -> EvalOptions<'static> {
+        EvalOptions {
+            now: Some(now),
+            ..EvalOptions::default()
+        }
+    }
+
     /// Helper to build a context from a list of key/value pairs.
     fn ctx(pairs: &[(&str, &str)]) -> Context {
         let mut m = Context::new();
@@ -335,274 +2242,1674 @@ mod tests {
     }
 
     #[test]
-    fn test_eval_flag_ca_rollout() {
+    fn test_eval_flag_ca_rollout() {
+        let flag = Flag {
+            key: "paywall".to_string(),
+            rules: vec![
+                Rule {
+                    cond: "country == 'CA'".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "rollout(0.20)".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+            ],
+            default: false,
+            ..Default::default()
+        };
+        let ctx_map = ctx(&[("userId", "u123"), ("country", "CA")]);
+        let res = eval_flag(&flag, &ctx_map);
+        assert!(res.enabled);
+        assert_eq!(res.matched_rule, Some(0));
+    }
+
+    #[test]
+    fn test_eval_flag_rollout() {
+        let flag = Flag {
+            key: "paywall".to_string(),
+            rules: vec![
+                Rule {
+                    cond: "country == 'CA'".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "rollout(0.20)".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+            ],
+            default: false,
+            ..Default::default()
+        };
+        // Use a userId whose bucket is less than 0.20; "u20" yields about 0.0838
+        let ctx_map = ctx(&[("userId", "u20"), ("country", "US")]);
+        let res = eval_flag(&flag, &ctx_map);
+        assert!(res.enabled);
+        assert_eq!(res.matched_rule, Some(1));
+    }
+
+    #[test]
+    fn test_eval_flag_default() {
+        let flag = Flag {
+            key: "paywall".to_string(),
+            rules: vec![
+                Rule {
+                    cond: "country == 'CA'".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "rollout(0.00)".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+            ],
+            default: false,
+            ..Default::default()
+        };
+        let ctx_map = ctx(&[("userId", "u999"), ("country", "US")]);
+        let res = eval_flag(&flag, &ctx_map);
+        assert!(!res.enabled);
+        assert_eq!(res.matched_rule, None);
+    }
+
+    #[test]
+    fn test_string_in_operator() {
+        let flag = Flag {
+            key: "region_test".to_string(),
+            rules: vec![Rule {
+                cond: "region in ('EU','APAC')".to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            default: false,
+            ..Default::default()
+        };
+        let ctx_map = ctx(&[("userId", "u1"), ("region", "EU")]);
+        let res = eval_flag(&flag, &ctx_map);
+        assert!(res.enabled);
+        assert_eq!(res.matched_rule, Some(0));
+        // region not in list should return default
+        let ctx_map2 = ctx(&[("userId", "u2"), ("region", "NA")]);
+        let res2 = eval_flag(&flag, &ctx_map2);
+        assert!(!res2.enabled);
+        assert_eq!(res2.matched_rule, None);
+    }
+
+    #[test]
+    fn test_numeric_comparisons() {
+        let flag = Flag {
+            key: "version_test".to_string(),
+            rules: vec![
+                Rule {
+                    cond: "ver >= 2".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "ver < 2".to_string(),
+                    then_value: false,
+                    ..Default::default()
+                },
+            ],
+            default: false,
+            ..Default::default()
+        };
+        // context numeric values must be inserted as Value::Num to test numeric comparisons
+        let mut ctx_map: Context = Context::new();
+        ctx_map.insert("userId".to_string(), Value::Str("u3".to_string()));
+        ctx_map.insert("ver".to_string(), Value::Num(3.0));
+        let res = eval_flag(&flag, &ctx_map);
+        assert!(res.enabled);
+        assert_eq!(res.matched_rule, Some(0));
+        // version less than 2
+        let mut ctx_map2: Context = Context::new();
+        ctx_map2.insert("userId".to_string(), Value::Str("u4".to_string()));
+        ctx_map2.insert("ver".to_string(), Value::Num(1.0));
+        let res2 = eval_flag(&flag, &ctx_map2);
+        assert!(!res2.enabled);
+        assert_eq!(res2.matched_rule, Some(1));
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        let flag = Flag {
+            key: "logic_test".to_string(),
+            rules: vec![
+                Rule {
+                    cond: "country == 'CA' && ver >= 2".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "country == 'US' || country == 'MX'".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+            ],
+            default: false,
+            ..Default::default()
+        };
+        // CA and ver >= 2 should match first rule
+        let mut ctx_map: Context = Context::new();
+        ctx_map.insert("userId".to_string(), Value::Str("u5".to_string()));
+        ctx_map.insert("country".to_string(), Value::Str("CA".to_string()));
+        ctx_map.insert("ver".to_string(), Value::Num(2.0));
+        let res = eval_flag(&flag, &ctx_map);
+        assert!(res.enabled);
+        assert_eq!(res.matched_rule, Some(0));
+        // US should match second rule via OR
+        let mut ctx_map2: Context = Context::new();
+        ctx_map2.insert("userId".to_string(), Value::Str("u6".to_string()));
+        ctx_map2.insert("country".to_string(), Value::Str("US".to_string()));
+        let res2 = eval_flag(&flag, &ctx_map2);
+        assert!(res2.enabled);
+        assert_eq!(res2.matched_rule, Some(1));
+        // unknown country should not match any rule
+        let mut ctx_map3: Context = Context::new();
+        ctx_map3.insert("userId".to_string(), Value::Str("u7".to_string()));
+        ctx_map3.insert("country".to_string(), Value::Str("BR".to_string()));
+        ctx_map3.insert("ver".to_string(), Value::Num(5.0));
+        let res3 = eval_flag(&flag, &ctx_map3);
+        assert!(!res3.enabled);
+        assert_eq!(res3.matched_rule, None);
+    }
+
+    #[test]
+    fn test_malformed_rule_is_skipped() {
+        let flag = Flag {
+            key: "malformed_rule".to_string(),
+            rules: vec![
+                Rule {
+                    cond: "missingField".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "country == 'CA'".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+            ],
+            default: false,
+            ..Default::default()
+        };
+        let ctx_map = ctx(&[("userId", "u8"), ("country", "CA")]);
+        let res = eval_flag(&flag, &ctx_map);
+        assert!(res.enabled);
+        assert_eq!(res.matched_rule, Some(1));
+    }
+
+    #[test]
+    fn test_lone_quote_is_not_a_string_literal() {
+        let ctx_map = ctx(&[("userId", "u8"), ("country", "'")]);
+        for cond in [
+            "'",
+            "\"",
+            "country == '",
+            "country in (')",
+            "country in (\", 'CA')",
+        ] {
+            assert!(check_rule(cond).is_err(), "{cond}");
+            let _ = eval_rule_expr("lone_quote", cond, &ctx_map);
+        }
+        assert_eq!(
+            eval_rule_expr("lone_quote", "country in (')", &ctx_map),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn test_double_quotes_and_boolean_literals() {
+        let flag = Flag {
+            key: "literal_test".to_string(),
+            rules: vec![
+                Rule {
+                    cond: "country == \"CA\"".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "true".to_string(),
+                    then_value: false,
+                    ..Default::default()
+                },
+            ],
+            default: true,
+            ..Default::default()
+        };
+        let ctx_map = ctx(&[("userId", "u9"), ("country", "CA")]);
+        let res = eval_flag(&flag, &ctx_map);
+        assert!(res.enabled);
+        assert_eq!(res.matched_rule, Some(0));
+
+        let ctx_map2 = ctx(&[("userId", "u10"), ("country", "US")]);
+        let res2 = eval_flag(&flag, &ctx_map2);
+        assert!(!res2.enabled);
+        assert_eq!(res2.matched_rule, Some(1));
+    }
+
+    #[test]
+    fn test_false_literal_and_unknown_identifier_handling() {
+        let flag = Flag {
+            key: "bool_false_test".to_string(),
+            rules: vec![
+                Rule {
+                    cond: "false".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "unknownField".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+            ],
+            default: false,
+            ..Default::default()
+        };
+        let ctx_map = ctx(&[("userId", "u11"), ("country", "US")]);
+        let res = eval_flag(&flag, &ctx_map);
+        assert!(!res.enabled);
+        assert_eq!(res.matched_rule, None);
+    }
+
+    #[test]
+    fn test_rollout_missing_user_id_and_invalid_rollout_expression() {
+        let flag = Flag {
+            key: "rollout_edges".to_string(),
+            rules: vec![
+                Rule {
+                    cond: "rollout(1.0)".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "rollout(bad)".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+            ],
+            default: false,
+            ..Default::default()
+        };
+        let mut ctx_map: Context = Context::new();
+        ctx_map.insert("country".to_string(), Value::Str("US".to_string()));
+        let res = eval_flag(&flag, &ctx_map);
+        assert!(res.enabled);
+        assert_eq!(res.matched_rule, Some(0));
+
+        assert!(eval_rule_expr("rollout_edges", "rollout(bad)", &ctx_map).is_err());
+        assert!(eval_rule_expr("rollout_edges", "rollout(0.5", &ctx_map).is_err());
+    }
+
+    #[test]
+    fn test_string_and_boolean_comparison_edges() {
+        let mut ctx_map: Context = Context::new();
+        ctx_map.insert("userId".to_string(), Value::Str("u12".to_string()));
+        ctx_map.insert("country".to_string(), Value::Str("CA".to_string()));
+        ctx_map.insert("enabled".to_string(), Value::Bool(true));
+
+        assert_eq!(eval_rule_expr("cmp_edges", "country != 'US'", &ctx_map), Ok(true));
+        assert_eq!(eval_rule_expr("cmp_edges", "enabled == true", &ctx_map), Ok(true));
+        assert_eq!(eval_rule_expr("cmp_edges", "enabled != false", &ctx_map), Ok(true));
+    }
+
+    #[test]
+    fn test_numeric_bool_and_mismatched_comparison_edges() {
+        let mut ctx_map: Context = Context::new();
+        ctx_map.insert("userId".to_string(), Value::Str("u13".to_string()));
+        ctx_map.insert("ver".to_string(), Value::Num(2.0));
+        ctx_map.insert("enabled".to_string(), Value::Bool(true));
+
+        assert_eq!(eval_rule_expr("cmp_edges", "ver == 2", &ctx_map), Ok(true));
+        assert_eq!(eval_rule_expr("cmp_edges", "ver != 3", &ctx_map), Ok(true));
+        assert_eq!(eval_rule_expr("cmp_edges", "ver <= 2", &ctx_map), Ok(true));
+        assert_eq!(eval_rule_expr("cmp_edges", "ver > 1", &ctx_map), Ok(true));
+        assert_eq!(eval_rule_expr("cmp_edges", "ver ~~ 2", &ctx_map), Err(()));
+        assert_eq!(eval_rule_expr("cmp_edges", "ver == '2'", &ctx_map), Ok(false));
+        assert_eq!(eval_rule_expr("cmp_edges", "enabled >= true", &ctx_map), Err(()));
+    }
+
+    #[test]
+    fn test_in_operator_error_and_false_paths() {
+        let mut ctx_map: Context = Context::new();
+        ctx_map.insert("userId".to_string(), Value::Str("u14".to_string()));
+        ctx_map.insert("country".to_string(), Value::Str("CA".to_string()));
+        ctx_map.insert("ver".to_string(), Value::Num(2.0));
+
+        assert_eq!(eval_rule_expr("in_edges", "country in ('US','MX')", &ctx_map), Ok(false));
+        assert_eq!(eval_rule_expr("in_edges", "country in (\"CA\",\"US\")", &ctx_map), Ok(true));
+        assert_eq!(eval_rule_expr("in_edges", "country in 'CA'", &ctx_map), Err(()));
+        assert_eq!(eval_rule_expr("in_edges", "country in (CA,'US')", &ctx_map), Ok(false));
+        assert_eq!(eval_rule_expr("in_edges", "ver in ('1','2')", &ctx_map), Ok(false));
+    }
+
+    #[test]
+    fn test_internal_invalid_operators() {
+        assert_eq!(
+            eval_comparison(Value::Str("CA".to_string()), "<", Value::Str("US".to_string())),
+            Err(())
+        );
+        assert_eq!(eval_comparison(Value::Num(1.0), "contains", Value::Num(2.0)), Err(()));
+    }
+
+    #[test]
+    fn test_error_propagation_in_logical_and_comparison_expressions() {
+        let ctx_map = ctx(&[("userId", "u15"), ("country", "CA")]);
+
+        assert_eq!(eval_rule_expr("propagation", "rollout(bad) || true", &ctx_map), Err(()));
+        assert_eq!(eval_rule_expr("propagation", "false || rollout(bad)", &ctx_map), Err(()));
+        assert_eq!(eval_rule_expr("propagation", "rollout(bad) && true", &ctx_map), Err(()));
+        assert_eq!(eval_rule_expr("propagation", "true && rollout(bad)", &ctx_map), Err(()));
+        assert_eq!(
+            eval_rule_expr("propagation", "rollout(bad) == true", &ctx_map),
+            Err(())
+        );
+        assert_eq!(
+            eval_rule_expr("propagation", "true == rollout(bad)", &ctx_map),
+            Err(())
+        );
+    }
+
+    #[test]
+    fn test_eval_flag_with_trace_reports_rule_outcomes() {
+        let flag = Flag {
+            key: "trace_test".to_string(),
+            rules: vec![
+                Rule {
+                    cond: "country == 'CA'".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "rollout(bad)".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "country == 'US' && ver >= 2".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "true".to_string(),
+                    then_value: false,
+                    ..Default::default()
+                },
+            ],
+            default: false,
+            ..Default::default()
+        };
+        let mut ctx_map = ctx(&[("userId", "u16"), ("country", "US")]);
+        ctx_map.insert("ver".to_string(), Value::Num(3.0));
+
+        let trace = eval_flag_with_trace(&flag, &ctx_map);
+        let plain = eval_flag(&flag, &ctx_map);
+        assert_eq!(trace.result.enabled, plain.enabled);
+        assert_eq!(trace.result.matched_rule, Some(2));
+
+        let outcomes: Vec<RuleOutcome> = trace.rules.iter().map(|r| r.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                RuleOutcome::NotMatched,
+                RuleOutcome::Errored,
+                RuleOutcome::Matched,
+                RuleOutcome::Skipped,
+            ]
+        );
+        assert_eq!(
+            trace.rules[0].steps,
+            vec![TraceStep {
+                expr: "country == 'CA'".to_string(),
+                value: Some(false)
+            }]
+        );
+        assert_eq!(trace.rules[1].steps[0].value, None);
+        assert_eq!(
+            trace.rules[2].steps,
+            vec![
+                TraceStep {
+                    expr: "country == 'US'".to_string(),
+                    value: Some(true)
+                },
+                TraceStep {
+                    expr: "ver >= 2".to_string(),
+                    value: Some(true)
+                },
+                TraceStep {
+                    expr: "country == 'US' && ver >= 2".to_string(),
+                    value: Some(true)
+                },
+            ]
+        );
+        assert!(trace.rules[3].steps.is_empty());
+    }
+
+    #[test]
+    fn test_eval_flag_with_trace_omits_short_circuited_operands() {
+        let flag = Flag {
+            key: "trace_short_circuit".to_string(),
+            rules: vec![Rule {
+                cond: "country == 'CA' || rollout(bad)".to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            default: false,
+            ..Default::default()
+        };
+        let ctx_map = ctx(&[("userId", "u17"), ("country", "CA")]);
+        let trace = eval_flag_with_trace(&flag, &ctx_map);
+        assert!(trace.result.enabled);
+        let exprs: Vec<&str> = trace.rules[0]
+            .steps
+            .iter()
+            .map(|step| step.expr.as_str())
+            .collect();
+        assert_eq!(
+            exprs,
+            vec!["country == 'CA'", "country == 'CA' || rollout(bad)"]
+        );
+
+        let default_trace = eval_flag_with_trace(&flag, &ctx(&[("country", "US")]));
+        assert!(!default_trace.result.enabled);
+        assert_eq!(default_trace.result.matched_rule, None);
+        assert_eq!(default_trace.rules[0].outcome, RuleOutcome::Errored);
+    }
+
+    #[test]
+    fn test_variant_assignment_is_sticky_and_reported() {
+        let flag = Flag {
+            key: "experiment".to_string(),
+            rules: vec![Rule {
+                cond: "variant({'a':0.5,'b':0.3,'c':0.2})".to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            default: false,
+            ..Default::default()
+        };
+        for i in 0..50 {
+            let user = format!("user-{}", i);
+            let ctx_map = ctx(&[("userId", user.as_str())]);
+            let res = eval_flag(&flag, &ctx_map);
+            assert!(res.enabled);
+            assert_eq!(res.matched_rule, Some(0));
+            let b = bucket("experiment", &user);
+            let expected = if b < 0.5 {
+                "a"
+            } else if b < 0.8 {
+                "b"
+            } else {
+                "c"
+            };
+            assert_eq!(res.variant.as_deref(), Some(expected));
+            assert_eq!(eval_flag(&flag, &ctx_map).variant, res.variant);
+        }
+    }
+
+    #[test]
+    fn test_variant_matches_bucket_slices() {
+        let weights = vec![("a".to_string(), 0.5), ("b".to_string(), 0.5)];
+        let b = bucket("exp", "u20");
+        let expected = if b < 0.5 { "a" } else { "b" };
+        assert_eq!(assign_variant("exp", "u20", &weights), Some(expected));
+        assert_eq!(assign_variant("exp", "u20", &[]), None);
+        assert_eq!(rollout("exp", "u20", 0.5), expected == "a");
+    }
+
+    #[test]
+    fn test_variant_comparison_and_partial_weights() {
+        let ctx_map = ctx(&[("userId", "u21")]);
+        let assigned = assign_variant(
+            "exp",
+            "u21",
+            &[("a".to_string(), 0.5), ("b".to_string(), 0.5)],
+        )
+        .unwrap()
+        .to_string();
+        let expr = format!("variant({{'a':0.5,\"b\":0.5}}) == '{}'", assigned);
+        assert_eq!(eval_rule_expr("exp", &expr, &ctx_map), Ok(true));
+
+        // A zero total weight never assigns a variant, so the bare call is false
+        // and the flag falls back to its default without a variant.
+        assert_eq!(
+            eval_rule_expr("exp", "variant({'a':0})", &ctx_map),
+            Ok(false)
+        );
+        let flag = Flag {
+            key: "exp".to_string(),
+            rules: vec![Rule {
+                cond: "variant({'a':0})".to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            default: false,
+            ..Default::default()
+        };
+        let res = eval_flag(&flag, &ctx_map);
+        assert_eq!(res.matched_rule, None);
+        assert_eq!(res.variant, None);
+
+        let traced = eval_flag_with_trace(
+            &Flag {
+                key: "exp".to_string(),
+                rules: vec![Rule {
+                    cond: "variant({'only':1})".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                }],
+                default: false,
+                ..Default::default()
+            },
+            &ctx_map,
+        );
+        assert_eq!(traced.result.variant.as_deref(), Some("only"));
+    }
+
+    #[test]
+    fn test_malformed_variant_calls_are_errors() {
+        let ctx_map = ctx(&[("userId", "u22")]);
+        for expr in [
+            "variant('a')",
+            "variant({'a' 0.5})",
+            "variant({a:0.5})",
+            "variant({'a':x})",
+            "variant({'a':-0.1})",
+            "variant({'a':0.7,'b':0.7})",
+        ] {
+            assert_eq!(eval_rule_expr("exp", expr, &ctx_map), Err(()), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_rollout_by_attribute() {
+        let mut ctx_map = ctx(&[("userId", "u23"), ("accountId", "acct-7")]);
+        ctx_map.insert("seats".to_string(), Value::Num(5.0));
+        let by_account = rollout("bucketed", "acct-7", 0.5);
+        assert_eq!(
+            eval_rule_expr("bucketed", "rollout(0.5, by='accountId')", &ctx_map),
+            Ok(by_account)
+        );
+        assert_eq!(
+            eval_rule_expr("bucketed", "rollout(0.5, by = \"seats\")", &ctx_map),
+            Ok(rollout("bucketed", "", 0.5))
+        );
+        let weights = vec![("a".to_string(), 0.5), ("b".to_string(), 0.5)];
+        let expected = assign_variant("bucketed", "acct-7", &weights).unwrap();
+        let expr = format!(
+            "variant({{'a':0.5,'b':0.5}}, by='accountId') == '{}'",
+            expected
+        );
+        assert_eq!(eval_rule_expr("bucketed", &expr, &ctx_map), Ok(true));
+
+        for malformed in [
+            "rollout(0.5, accountId)",
+            "rollout(0.5, on='accountId')",
+            "rollout(0.5, by=accountId)",
+            "variant({'a':1}, by='x', extra)",
+        ] {
+            assert_eq!(
+                eval_rule_expr("bucketed", malformed, &ctx_map),
+                Err(()),
+                "{}",
+                malformed
+            );
+        }
+    }
+
+    #[test]
+    fn test_bucket_index_builtin() {
+        for i in 0..200 {
+            let user = format!("user-{i}");
+            let ctx_map = ctx(&[("userId", &user)]);
+            let index = bucket_index("layered", &user, 10);
+            assert!(index < 10);
+            assert_eq!(
+                eval_rule_expr("layered", "bucket(10) < 3", &ctx_map),
+                Ok(rollout("layered", &user, 0.3)),
+                "{user}"
+            );
+            assert_eq!(
+                eval_rule_expr("layered", &format!("bucket(10) == {index}"), &ctx_map),
+                Ok(true)
+            );
+            // Flags in one layer split the same buckets, so disjoint ranges
+            // never reach the same user.
+            let layer_a = "bucket(4, layer='checkout') < 2";
+            let layer_b = "bucket(4, layer = 'checkout') >= 2";
+            assert_ne!(
+                eval_rule_expr("checkout-a", layer_a, &ctx_map),
+                eval_rule_expr("checkout-b", layer_b, &ctx_map)
+            );
+        }
+        let ctx_map = ctx(&[("userId", "u1")]);
+        for malformed in [
+            "bucket(0) < 1",
+            "bucket(2.5) < 1",
+            "bucket(-3) < 1",
+            "bucket() < 1",
+            "bucket(10, on='x') < 1",
+            "bucket(10)",
+        ] {
+            assert_eq!(
+                eval_rule_expr("layered", malformed, &ctx_map),
+                Err(()),
+                "{malformed}"
+            );
+            assert!(check_rule(malformed).is_err(), "{malformed}");
+        }
+        assert_eq!(
+            check_rule("bucket(x) < 3").unwrap_err(),
+            "`bucket(...)` needs a positive whole number of buckets, found `x`"
+        );
+        assert!(check_rule("bucket(10, by='accountId', per='country') < 3").is_ok());
+    }
+
+    #[test]
+    fn test_flag_salt_reshuffles_buckets() {
+        let unsalted = Flag {
+            key: "salted".to_string(),
+            rules: vec![Rule {
+                cond: "rollout(0.5)".to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            default: false,
+            ..Default::default()
+        };
+        let salted = Flag {
+            salt: Some("2025-q1".to_string()),
+            ..unsalted.clone()
+        };
+        assert_eq!(unsalted.bucket_seed(), "salted");
+        assert_eq!(salted.bucket_seed(), "salted:2025-q1");
+
+        let ctx_map = ctx(&[("userId", "u24")]);
+        assert_eq!(
+            eval_flag(&salted, &ctx_map).enabled,
+            rollout("salted:2025-q1", "u24", 0.5)
+        );
+        assert_eq!(
+            eval_flag_with_trace(&salted, &ctx_map).result.enabled,
+            rollout("salted:2025-q1", "u24", 0.5)
+        );
+        assert_eq!(
+            eval_rules(&salted, &ctx_map).is_some(),
+            rollout("salted:2025-q1", "u24", 0.5)
+        );
+        assert_eq!(
+            eval_flag(&unsalted, &ctx_map).enabled,
+            rollout("salted", "u24", 0.5)
+        );
+    }
+
+    #[test]
+    fn test_hashers_match_reference_vectors() {
+        assert_eq!(Fnv1a.hash32(b""), 0x811c_9dc5);
+        assert_eq!(Fnv1a.hash32(b"a"), 0xe40c_292c);
+        assert_eq!(Murmur3.hash32(b""), 0);
+        assert_eq!(Murmur3.hash32(b"test"), 0xba6b_d213);
+        assert_eq!(Murmur3.hash32(b"hello"), 0x248b_fa47);
+        assert_eq!(Murmur3.hash32(b"Hello, world!"), 0xc036_3e43);
+        assert_eq!(
+            HashAlgorithm::parse("murmur3"),
+            Some(HashAlgorithm::Murmur3)
+        );
+        assert_eq!(HashAlgorithm::parse("sha1"), None);
+    }
+
+    #[test]
+    fn test_flag_state_skips_rules_and_is_reported() {
+        let enabled = Flag {
+            key: "paywall".to_string(),
+            rules: vec![Rule {
+                cond: "country == 'CA'".to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let ctx_map = ctx(&[("country", "CA")]);
+        let result = eval_flag(&enabled, &ctx_map);
+        assert_eq!(
+            (result.enabled, result.matched_rule, result.state),
+            (true, Some(0), FlagState::Enabled)
+        );
+
+        for state in [FlagState::Disabled, FlagState::Archived] {
+            let flag = Flag {
+                state,
+                ..enabled.clone()
+            };
+            let result = eval_flag(&flag, &ctx_map);
+            assert_eq!(
+                (result.enabled, result.matched_rule, result.state),
+                (false, None, state)
+            );
+            assert_eq!(eval_rules(&flag, &ctx_map), None);
+            let trace = eval_flag_with_trace(&flag, &ctx_map);
+            assert_eq!(trace.rules[0].skip_reason, Some(SkipReason::FlagInactive));
+            assert!(trace.rules[0].steps.is_empty());
+        }
+        let set = |state| {
+            FlagSet::load(vec![Flag {
+                state,
+                ..enabled.clone()
+            }])
+            .unwrap()
+            .fingerprint()
+        };
+        assert_ne!(set(FlagState::Enabled), set(FlagState::Archived));
+        assert_eq!(FlagState::parse("archived"), Some(FlagState::Archived));
+        assert_eq!(FlagState::parse("paused"), None);
+    }
+
+    #[test]
+    fn test_reason_says_why_the_value_was_returned() {
+        let flag = Flag {
+            key: "paywall".to_string(),
+            rules: vec![
+                Rule {
+                    cond: "signup < '2025-01-01T00:00:00Z'".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "country == 'CA'".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let reason = |flag: &Flag, ctx: &Context| {
+            let result = eval_flag(flag, ctx);
+            assert_eq!(eval_flag_with_trace(flag, ctx).result.reason, result.reason);
+            result.reason
+        };
+        // A `signup` that is not a timestamp makes the first rule fail.
+        let signup = |signup, country| ctx(&[("signup", signup), ("country", country)]);
+        assert_eq!(reason(&flag, &signup("soon", "CA")), Reason::TargetingMatch);
+        assert_eq!(reason(&flag, &signup("soon", "US")), Reason::Error);
+        assert_eq!(
+            reason(&flag, &signup("2025-06-01T00:00:00Z", "US")),
+            Reason::Default
+        );
+        let disabled = Flag {
+            state: FlagState::Disabled,
+            ..flag.clone()
+        };
+        assert_eq!(
+            reason(&disabled, &ctx(&[("country", "CA")])),
+            Reason::Disabled
+        );
+        assert_eq!(
+            EvalResult::overridden(&disabled, true).reason,
+            Reason::Static
+        );
+        assert_eq!(
+            Reason::parse(Reason::PrerequisiteFailed.as_str()),
+            Some(Reason::PrerequisiteFailed)
+        );
+    }
+
+    #[test]
+    fn test_overrides_force_the_value_and_are_reported() {
+        let flag = Flag {
+            key: "paywall".to_string(),
+            rules: vec![Rule {
+                cond: "country == 'CA'".to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let ctx_map = ctx(&[("country", "CA")]);
+        let mut overrides = Overrides::new();
+        overrides.insert("other".to_string(), false);
+        let mut opts = EvalOptions {
+            overrides: Some(&overrides),
+            ..EvalOptions::default()
+        };
+        let result = eval_flag_with(&flag, &ctx_map, &mut opts);
+        assert_eq!(
+            (result.enabled, result.matched_rule, result.overridden),
+            (true, Some(0), false)
+        );
+
+        overrides.insert("paywall".to_string(), false);
+        let mut opts = EvalOptions {
+            overrides: Some(&overrides),
+            ..EvalOptions::default()
+        };
+        let result = eval_flag_with(&flag, &ctx_map, &mut opts);
+        assert_eq!(
+            (result.enabled, result.matched_rule, result.overridden),
+            (false, None, true)
+        );
+        // An override applies whatever the flag's state, and ignores the clock.
+        let archived = Flag {
+            state: FlagState::Archived,
+            ..flag.clone()
+        };
+        overrides.insert("paywall".to_string(), true);
+        let now = Timestamp::parse_rfc3339("2025-03-01T00:00:00Z").unwrap();
+        let mut opts = EvalOptions {
+            now: Some(now),
+            overrides: Some(&overrides),
+            ..EvalOptions::default()
+        };
+        let result = eval_flag_with(&archived, &ctx_map, &mut opts);
+        assert_eq!(
+            (result.enabled, result.state, result.overridden),
+            (true, FlagState::Archived, true)
+        );
+
+        let trace = EvalTrace::overridden(&flag, true);
+        assert!(trace.result.overridden && trace.result.enabled);
+        assert_eq!(trace.rules[0].skip_reason, Some(SkipReason::Overridden));
+        assert!(!eval_flag(&flag, &ctx_map).overridden);
+    }
+
+    #[test]
+    fn test_flag_hash_algorithm_selects_bucketing() {
+        let fnv = Flag {
+            key: "hashed".to_string(),
+            rules: vec![Rule {
+                cond: "variant({'a':0.5,'b':0.5})".to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let murmur = Flag {
+            hash: HashAlgorithm::Murmur3,
+            ..fnv.clone()
+        };
+        let expected = |hasher: &dyn Hasher, id: &str| {
+            if bucket_with(hasher, "hashed", id) < 0.5 {
+                "a"
+            } else {
+                "b"
+            }
+        };
+        let mut differs = false;
+        for i in 0..32 {
+            let id = format!("user-{i}");
+            let ctx_map = ctx(&[("userId", id.as_str())]);
+            let a = eval_flag(&fnv, &ctx_map).variant.unwrap();
+            let b = eval_flag(&murmur, &ctx_map).variant.unwrap();
+            assert_eq!(a, expected(&Fnv1a, &id));
+            assert_eq!(b, expected(&Murmur3, &id));
+            differs |= a != b;
+        }
+        assert!(differs);
+    }
+
+    #[test]
+    fn test_membership_against_context_lists() {
+        let mut ctx_map = ctx(&[("userId", "u1")]);
+        ctx_map.insert(
+            "roles".to_string(),
+            Value::List(vec![
+                Value::Str("admin".into()),
+                Value::Str("billing".into()),
+            ]),
+        );
+        ctx_map.insert("tiers".to_string(), Value::List(vec![Value::Num(2.0)]));
+        let eval = |expr: &str| eval_rule_expr("k", expr, &ctx_map);
+
+        assert_eq!(eval("'admin' in roles"), Ok(true));
+        assert_eq!(eval("'owner' in roles"), Ok(false));
+        assert_eq!(eval("2 in tiers"), Ok(true));
+        assert_eq!(eval("'admin' in missing"), Ok(false));
+        assert_eq!(eval("'u1' in userId"), Err(()));
+        assert_eq!(eval("'admin' in ('admin')"), Ok(true));
+
+        assert_eq!(eval("any(roles, 'owner', 'billing')"), Ok(true));
+        assert_eq!(eval("any(roles, 'owner')"), Ok(false));
+        assert_eq!(eval("all(roles, 'admin', 'billing')"), Ok(true));
+        assert_eq!(eval("all(roles, 'admin', 'owner')"), Ok(false));
+        assert_eq!(eval("all(missing, 'admin')"), Ok(false));
+        assert_eq!(eval("any(roles, 'owner') || userId == 'u1'"), Ok(true));
+        assert_eq!(eval("any(roles)"), Err(()));
+        assert_eq!(eval("roles == 'admin'"), Ok(false));
+        assert_eq!(eval("roles"), Err(()));
+    }
+
+    #[test]
+    fn test_string_operators() {
+        let ctx_map = ctx(&[
+            ("email", "ana@example.com"),
+            ("path", "/docs/guide/intro.md"),
+        ]);
+        let eval = |expr: &str| eval_rule_expr("k", expr, &ctx_map);
+
+        assert_eq!(eval("email endsWith '@example.com'"), Ok(true));
+        assert_eq!(eval("email startsWith 'ana@'"), Ok(true));
+        assert_eq!(eval("email contains 'example'"), Ok(true));
+        assert_eq!(eval("email contains 'Example'"), Ok(false));
+        assert_eq!(eval("path matches '/docs/*.md'"), Ok(true));
+        assert_eq!(eval("path matches '/docs/guide/intro.?d'"), Ok(true));
+        assert_eq!(eval("path matches '/docs/*.txt'"), Ok(false));
+        assert_eq!(eval("path matches '/blog/*'"), Ok(false));
+        assert_eq!(
+            eval("email endsWith '@example.com' && path startsWith '/docs'"),
+            Ok(true)
+        );
+        // Quoted operator words are literals, not operators.
+        assert_eq!(eval("email == 'a contains b'"), Ok(false));
+        // Non-string operands never match and numbers reject string operators.
+        assert_eq!(eval("missing contains 'x'"), Ok(false));
+        assert_eq!(
+            eval_comparison(Value::Num(12.0), "startsWith", Value::Num(1.0)),
+            Err(())
+        );
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(glob_match("a*b", "abab"));
+        assert!(!glob_match("a?c", "ac"));
+        assert!(!glob_match("abc", "abcd"));
+        assert!(glob_match("**.md", "x.md"));
+    }
+
+    #[test]
+    fn test_regex_operator_requires_feature() {
+        let ctx_map = ctx(&[("email", "ana@corp.example")]);
         let flag = Flag {
-            key: "paywall".to_string(),
-            rules: vec![
-                Rule { cond: "country == 'CA'".to_string(), then_value: true },
-                Rule { cond: "rollout(0.20)".to_string(), then_value: true },
-            ],
-            default: false,
+            key: "re".to_string(),
+            rules: vec![Rule {
+                cond: "email ~= '^[a-z]+@corp\\.'".to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            ..Default::default()
         };
-        let ctx_map = ctx(&[("userId", "u123"), ("country", "CA")]);
-        let res = eval_flag(&flag, &ctx_map);
-        assert!(res.enabled);
-        assert_eq!(res.matched_rule, Some(0));
+        let compiled = CompiledFlag::compile(flag.clone());
+        if cfg!(feature = "regex") {
+            let compiled = compiled.unwrap();
+            assert_eq!(compiled.eval(&ctx_map).matched_rule, Some(0));
+            assert_eq!(eval_flag(&flag, &ctx_map).matched_rule, Some(0));
+            assert_eq!(
+                eval_rule_expr("re", "email ~= 'corp$'", &ctx_map),
+                Ok(false)
+            );
+            assert_eq!(eval_rule_expr("re", "missing ~= '.*'", &ctx_map), Ok(false));
+        } else {
+            assert!(compiled.unwrap_err().message.contains("`regex` feature"));
+            assert_eq!(eval_flag(&flag, &ctx_map).matched_rule, None);
+        }
+        // The pattern must be a quoted literal either way.
+        assert_eq!(eval_rule_expr("re", "email ~= pattern", &ctx_map), Err(()));
     }
 
+    #[cfg(feature = "regex")]
     #[test]
-    fn test_eval_flag_rollout() {
+    fn test_compiled_flag_reports_invalid_patterns() {
         let flag = Flag {
-            key: "paywall".to_string(),
+            key: "re".to_string(),
             rules: vec![
-                Rule { cond: "country == 'CA'".to_string(), then_value: true },
-                Rule { cond: "rollout(0.20)".to_string(), then_value: true },
+                Rule {
+                    cond: "country == 'CA'".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "country == 'US' && email ~= '(unclosed'".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
             ],
-            default: false,
+            ..Default::default()
         };
-        // Use a userId whose bucket is less than 0.20; "u20" yields about 0.0838
-        let ctx_map = ctx(&[("userId", "u20"), ("country", "US")]);
-        let res = eval_flag(&flag, &ctx_map);
-        assert!(res.enabled);
-        assert_eq!(res.matched_rule, Some(1));
+        let err = CompiledFlag::compile(flag.clone()).unwrap_err();
+        assert_eq!(err.rule, 1);
+        assert_eq!(err.pattern, "(unclosed");
+        assert!(err
+            .to_string()
+            .starts_with("rule 1: invalid pattern '(unclosed'"));
+        // Uncompiled evaluation skips the rule instead.
+        let ctx_map = ctx(&[("country", "US"), ("email", "a@b")]);
+        assert_eq!(eval_flag(&flag, &ctx_map).matched_rule, None);
     }
 
     #[test]
-    fn test_eval_flag_default() {
+    fn test_rfc3339_parsing() {
+        let t = |s: &str| Timestamp::parse_rfc3339(s).map(Timestamp::unix_millis);
+        assert_eq!(t("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(t("2025-03-01T00:00:00Z"), Some(1_740_787_200_000));
+        assert_eq!(t("2025-03-01T05:30:00.250+05:30"), Some(1_740_787_200_250));
+        assert_eq!(t("2024-02-29T23:59:60Z"), t("2024-02-29T23:59:59Z"));
+        assert_eq!(t("1969-12-31T23:59:59.9999z"), Some(-1));
+        for bad in [
+            "2025-02-29T00:00:00Z",
+            "2025-03-01T24:00:00Z",
+            "2025-03-01T00:00:00",
+            "2025-03-01",
+            "2025-03-01T00:00:00.Z",
+        ] {
+            assert_eq!(t(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_now_builtin_uses_injected_clock() {
         let flag = Flag {
-            key: "paywall".to_string(),
-            rules: vec![
-                Rule { cond: "country == 'CA'".to_string(), then_value: true },
-                Rule { cond: "rollout(0.00)".to_string(), then_value: true },
-            ],
-            default: false,
+            key: "launch".to_string(),
+            rules: vec![Rule {
+                cond: "now >= '2025-03-01T00:00:00Z'".to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            ..Default::default()
         };
-        let ctx_map = ctx(&[("userId", "u999"), ("country", "US")]);
-        let res = eval_flag(&flag, &ctx_map);
-        assert!(!res.enabled);
-        assert_eq!(res.matched_rule, None);
+        let before = Timestamp::parse_rfc3339("2025-02-28T23:59:59.999Z").unwrap();
+        let after = Timestamp::parse_rfc3339("2025-03-01T00:00:00Z").unwrap();
+        let empty = Context::new();
+        assert!(!eval_flag_with(&flag, &empty, &mut clock(before)).enabled);
+        assert!(eval_flag_with(&flag, &empty, &mut clock(after)).enabled);
+        // Without a clock the rule cannot be decided and is skipped.
+        assert_eq!(eval_flag(&flag, &empty).matched_rule, None);
+        let trace = eval_flag_with_trace(&flag, &empty);
+        assert_eq!(trace.rules[0].outcome, RuleOutcome::Errored);
+        let compiled = CompiledFlag::compile(flag).unwrap();
+        assert_eq!(
+            compiled.eval_with(&empty, &mut clock(after)).matched_rule,
+            Some(0)
+        );
+        assert_eq!(
+            compiled.trace_with(&empty, &mut clock(before)).rules[0].outcome,
+            RuleOutcome::NotMatched
+        );
     }
 
     #[test]
-    fn test_string_in_operator() {
-        let flag = Flag {
-            key: "region_test".to_string(),
-            rules: vec![
-                Rule { cond: "region in ('EU','APAC')".to_string(), then_value: true },
-            ],
-            default: false,
+    fn test_timestamp_comparisons() {
+        let flag = |cond: &str| Flag {
+            key: "t".to_string(),
+            rules: vec![Rule {
+                cond: cond.to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            ..Default::default()
         };
-        let ctx_map = ctx(&[("userId", "u1"), ("region", "EU")]);
-        let res = eval_flag(&flag, &ctx_map);
-        assert!(res.enabled);
-        assert_eq!(res.matched_rule, Some(0));
-        // region not in list should return default
-        let ctx_map2 = ctx(&[("userId", "u2"), ("region", "NA")]);
-        let res2 = eval_flag(&flag, &ctx_map2);
-        assert!(!res2.enabled);
-        assert_eq!(res2.matched_rule, None);
+        let now = Timestamp::parse_rfc3339("2025-06-01T12:00:00Z").unwrap();
+        let ctx_map = ctx(&[("signupAt", "2025-05-31T23:00:00-02:00"), ("name", "x")]);
+        let matched = |cond: &str| {
+            eval_flag_with(&flag(cond), &ctx_map, &mut clock(now))
+                .matched_rule
+                .is_some()
+        };
+        // Offsets are normalised before comparing.
+        assert!(matched("signupAt < now"));
+        assert!(matched("now == '2025-06-01T14:00:00+02:00'"));
+        assert!(matched("signupAt > '2025-06-01T00:59:59Z'"));
+        assert!(!matched("signupAt <= '2025-06-01T00:59:59Z'"));
+        // Strings that are not timestamps cannot be ordered.
+        assert!(!matched("name < now"));
+        assert!(!matched("name < 'y'"));
+        assert!(!matched("now < 5"));
     }
 
     #[test]
-    fn test_numeric_comparisons() {
+    fn test_exists_missing_and_null() {
+        let mut ctx_map = ctx(&[("nickname", "")]);
+        ctx_map.insert("beta".to_string(), Value::Bool(false));
+        ctx_map.insert("deleted".to_string(), Value::Null);
+        let check = |expr: &str| eval_rule_expr("f", expr, &ctx_map);
+        // Present but false or empty still exists.
+        assert_eq!(check("exists(beta)"), Ok(true));
+        assert_eq!(check("exists(nickname)"), Ok(true));
+        assert_eq!(check("exists(plan)"), Ok(false));
+        assert_eq!(check("missing(plan)"), Ok(true));
+        assert_eq!(check("missing(deleted)"), Ok(true));
+        assert_eq!(check("beta != null"), Ok(true));
+        assert_eq!(check("beta == null"), Ok(false));
+        assert_eq!(check("plan == null"), Ok(true));
+        assert_eq!(check("plan != null"), Ok(false));
+        assert_eq!(check("null == deleted"), Ok(true));
+        // A missing attribute only compares by presence against `null`.
+        assert_eq!(check("plan != 'x'"), Ok(false));
+        assert_eq!(check("plan == 'x'"), Ok(false));
+        assert_eq!(check("plan == tier"), Ok(false));
+        assert_eq!(check("plan != tier"), Ok(false));
+        assert_eq!(check("missing(plan) && beta == false"), Ok(true));
+        // The argument must be a bare attribute name.
+        assert_eq!(check("exists('beta')"), Err(()));
+        assert_eq!(check("exists()"), Err(()));
+    }
+
+    #[test]
+    fn test_rule_scheduling_windows() {
+        let at = |s: &str| Timestamp::parse_rfc3339(s).unwrap();
         let flag = Flag {
-            key: "version_test".to_string(),
+            key: "promo".to_string(),
             rules: vec![
-                Rule { cond: "ver >= 2".to_string(), then_value: true },
-                Rule { cond: "ver < 2".to_string(), then_value: false },
+                Rule {
+                    cond: "true".to_string(),
+                    then_value: true,
+                    active_from: Some(at("2025-11-28T00:00:00Z")),
+                    active_until: Some(at("2025-12-02T00:00:00Z")),
+                },
+                Rule {
+                    cond: "country == 'CA'".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
             ],
-            default: false,
+            ..Default::default()
         };
-        // context numeric values must be inserted as Value::Num to test numeric comparisons
-        let mut ctx_map: Context = Context::new();
-        ctx_map.insert("userId".to_string(), Value::Str("u3".to_string()));
-        ctx_map.insert("ver".to_string(), Value::Num(3.0));
-        let res = eval_flag(&flag, &ctx_map);
-        assert!(res.enabled);
-        assert_eq!(res.matched_rule, Some(0));
-        // version less than 2
-        let mut ctx_map2: Context = Context::new();
-        ctx_map2.insert("userId".to_string(), Value::Str("u4".to_string()));
-        ctx_map2.insert("ver".to_string(), Value::Num(1.0));
-        let res2 = eval_flag(&flag, &ctx_map2);
-        assert!(!res2.enabled);
-        assert_eq!(res2.matched_rule, Some(1));
+        let empty = Context::new();
+        assert_eq!(
+            eval_flag_with(&flag, &empty, &mut clock(at("2025-11-28T00:00:00Z"))).matched_rule,
+            Some(0)
+        );
+        assert_eq!(
+            eval_flag_with(&flag, &empty, &mut clock(at("2025-12-01T23:59:59Z"))).matched_rule,
+            Some(0)
+        );
+        // `active_until` is exclusive; an expired promotion falls through.
+        assert_eq!(
+            eval_flag_with(&flag, &empty, &mut clock(at("2025-12-02T00:00:00Z"))).matched_rule,
+            None
+        );
+        assert_eq!(eval_flag(&flag, &empty).matched_rule, None);
+        assert_eq!(
+            eval_rules(&flag, &ctx(&[("country", "CA")])),
+            Some((1, true))
+        );
+
+        let reasons = |trace: EvalTrace| -> Vec<_> {
+            trace
+                .rules
+                .iter()
+                .map(|r| (r.outcome, r.skip_reason))
+                .collect()
+        };
+        assert_eq!(
+            reasons(trace_flag_with(
+                &flag,
+                &empty,
+                &mut clock(at("2025-11-01T00:00:00Z"))
+            )),
+            vec![
+                (RuleOutcome::Skipped, Some(SkipReason::NotYetActive)),
+                (RuleOutcome::NotMatched, None)
+            ]
+        );
+        assert_eq!(
+            reasons(trace_flag_with(
+                &flag,
+                &empty,
+                &mut clock(at("2026-01-01T00:00:00Z"))
+            ))[0],
+            (RuleOutcome::Skipped, Some(SkipReason::Expired))
+        );
+        assert_eq!(
+            reasons(eval_flag_with_trace(&flag, &empty))[0],
+            (RuleOutcome::Skipped, Some(SkipReason::NoClock))
+        );
+        assert_eq!(
+            reasons(trace_flag_with(
+                &flag,
+                &empty,
+                &mut clock(at("2025-11-30T00:00:00Z"))
+            ))[1],
+            (RuleOutcome::Skipped, Some(SkipReason::EarlierMatch))
+        );
     }
 
     #[test]
-    fn test_logical_operators() {
+    fn test_stratified_rollout() {
         let flag = Flag {
-            key: "logic_test".to_string(),
-            rules: vec![
-                Rule { cond: "country == 'CA' && ver >= 2".to_string(), then_value: true },
-                Rule { cond: "country == 'US' || country == 'MX'".to_string(), then_value: true },
-            ],
-            default: false,
+            key: "checkout".to_string(),
+            rules: vec![Rule {
+                cond: "rollout(0.1, per='country')".to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            ..Default::default()
         };
-        // CA and ver >= 2 should match first rule
-        let mut ctx_map: Context = Context::new();
-        ctx_map.insert("userId".to_string(), Value::Str("u5".to_string()));
-        ctx_map.insert("country".to_string(), Value::Str("CA".to_string()));
-        ctx_map.insert("ver".to_string(), Value::Num(2.0));
-        let res = eval_flag(&flag, &ctx_map);
-        assert!(res.enabled);
-        assert_eq!(res.matched_rule, Some(0));
-        // US should match second rule via OR
-        let mut ctx_map2: Context = Context::new();
-        ctx_map2.insert("userId".to_string(), Value::Str("u6".to_string()));
-        ctx_map2.insert("country".to_string(), Value::Str("US".to_string()));
-        let res2 = eval_flag(&flag, &ctx_map2);
-        assert!(res2.enabled);
-        assert_eq!(res2.matched_rule, Some(1));
-        // unknown country should not match any rule
-        let mut ctx_map3: Context = Context::new();
-        ctx_map3.insert("userId".to_string(), Value::Str("u7".to_string()));
-        ctx_map3.insert("country".to_string(), Value::Str("BR".to_string()));
-        ctx_map3.insert("ver".to_string(), Value::Num(5.0));
-        let res3 = eval_flag(&flag, &ctx_map3);
-        assert!(!res3.enabled);
-        assert_eq!(res3.matched_rule, None);
+        for country in ["CA", "US", "MX"] {
+            let mut enabled = 0;
+            for i in 0..2000 {
+                let user = format!("user-{i}");
+                let result = eval_flag(&flag, &ctx(&[("userId", &user), ("country", country)]));
+                if result.enabled {
+                    enabled += 1;
+                    let stratum = result.stratum.unwrap();
+                    assert_eq!(
+                        (stratum.attribute.as_str(), stratum.value.as_str()),
+                        ("country", country)
+                    );
+                }
+            }
+            // Each stratum gets close to its own 10%.
+            assert!((140..=260).contains(&enabled), "{country}: {enabled}");
+        }
+        // The stratum value is part of the hashed key.
+        let user = ctx(&[("userId", "u-7"), ("country", "CA")]);
+        let expected = bucket("checkout:CA", "u-7") < 0.1;
+        assert_eq!(eval_flag(&flag, &user).enabled, expected);
+        // Unstratified rollouts carry no stratum, and `by`/`per` combine in any order.
+        assert_eq!(
+            eval_rule_expr("f", "rollout(1, per='plan', by='accountId')", &user),
+            Ok(true)
+        );
+        assert_eq!(
+            eval_rule_expr("f", "rollout(1, region='x')", &user),
+            Err(())
+        );
+        assert_eq!(eval_rule_expr("f", "rollout(1,)", &user), Err(()));
+        let plain = Flag {
+            rules: vec![Rule {
+                cond: "rollout(1)".to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            ..flag
+        };
+        assert_eq!(eval_flag(&plain, &user).stratum, None);
     }
 
     #[test]
-    fn test_malformed_rule_is_skipped() {
+    fn test_ramp_follows_its_schedule() {
         let flag = Flag {
-            key: "malformed_rule".to_string(),
-            rules: vec![
-                Rule { cond: "missingField".to_string(), then_value: true },
-                Rule { cond: "country == 'CA'".to_string(), then_value: true },
-            ],
-            default: false,
+            key: "checkout".to_string(),
+            rules: vec![Rule {
+                cond: "ramp('2025-03-01T00:00:00Z', '2025-03-11T00:00:00Z', 0.1, 0.6)".to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            ..Default::default()
         };
-        let ctx_map = ctx(&[("userId", "u8"), ("country", "CA")]);
-        let res = eval_flag(&flag, &ctx_map);
-        assert!(res.enabled);
-        assert_eq!(res.matched_rule, Some(1));
+        let at = |text: &str| Timestamp::parse_rfc3339(text).unwrap();
+        let reached = |now: Timestamp| {
+            (0..2000)
+                .filter(|i| {
+                    eval_flag_with(
+                        &flag,
+                        &ctx(&[("userId", &format!("user-{i}"))]),
+                        &mut clock(now),
+                    )
+                    .enabled
+                })
+                .map(|i| format!("user-{i}"))
+                .collect::<Vec<_>>()
+        };
+        assert!(reached(at("2025-02-28T23:59:59Z")).is_empty());
+        let start = reached(at("2025-03-01T00:00:00Z"));
+        let middle = reached(at("2025-03-06T00:00:00Z"));
+        let end = reached(at("2025-03-11T00:00:00Z"));
+        assert!((140..=260).contains(&start.len()), "{}", start.len());
+        assert!((600..=800).contains(&middle.len()), "{}", middle.len());
+        assert!((1100..=1300).contains(&end.len()), "{}", end.len());
+        assert_eq!(reached(at("2026-01-01T00:00:00Z")), end);
+        // The same buckets as `rollout`, so a growing ramp never drops a user.
+        assert!(start.iter().all(|user| middle.contains(user)));
+        assert!(middle.iter().all(|user| end.contains(user)));
+        let user = ctx(&[("userId", "u-7")]);
+        assert_eq!(
+            eval_flag_with(&flag, &user, &mut clock(at("2025-03-06T00:00:00Z"))).enabled,
+            bucket("checkout", "u-7") < 0.35
+        );
+        // Without a clock the rule cannot be decided and is skipped.
+        assert_eq!(eval_flag(&flag, &user).matched_rule, None);
+
+        for malformed in [
+            "ramp('2025-03-11T00:00:00Z', '2025-03-01T00:00:00Z', 0, 1)",
+            "ramp('2025-03-01T00:00:00Z', '2025-03-11T00:00:00Z', 0)",
+            "ramp(start, '2025-03-11T00:00:00Z', 0, 1)",
+            "ramp('2025-03-01T00:00:00Z', '2025-03-11T00:00:00Z', 0, high)",
+        ] {
+            assert!(check_rule(malformed).is_err(), "{malformed}");
+        }
+        assert_eq!(
+            check_rule(
+                "ramp('2025-03-01T00:00:00Z', '2025-03-11T00:00:00Z', 0, 1, by='accountId')"
+            ),
+            Ok(())
+        );
+        let wide = Flag {
+            rules: vec![Rule {
+                cond: "ramp('2025-03-01T00:00:00Z', '2025-03-11T00:00:00Z', 0, 50)".to_string(),
+                ..Default::default()
+            }],
+            ..flag
+        };
+        assert_eq!(validate_flag(&wide)[0].kind, LintKind::RolloutOutOfRange);
     }
 
     #[test]
-    fn test_double_quotes_and_boolean_literals() {
+    fn test_bulk_evaluation_counts_outcomes_per_rule() {
         let flag = Flag {
-            key: "literal_test".to_string(),
+            key: "paywall".to_string(),
             rules: vec![
-                Rule { cond: "country == \"CA\"".to_string(), then_value: true },
-                Rule { cond: "true".to_string(), then_value: false },
+                Rule {
+                    cond: "country == 'CA'".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "rollout(0.5)".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "plan == 'free'".to_string(),
+                    then_value: false,
+                    ..Default::default()
+                },
             ],
-            default: true,
+            ..Default::default()
         };
-        let ctx_map = ctx(&[("userId", "u9"), ("country", "CA")]);
-        let res = eval_flag(&flag, &ctx_map);
-        assert!(res.enabled);
-        assert_eq!(res.matched_rule, Some(0));
+        let contexts: Vec<Context> = (0..1000)
+            .map(|i| {
+                let mut ctx = Context::new();
+                ctx.insert("userId".to_string(), Value::Str(format!("user-{i}")));
+                let country = if i % 10 == 0 { "CA" } else { "US" };
+                ctx.insert("country".to_string(), Value::Str(country.to_string()));
+                ctx.insert("plan".to_string(), Value::Str("free".to_string()));
+                ctx
+            })
+            .collect();
 
-        let ctx_map2 = ctx(&[("userId", "u10"), ("country", "US")]);
-        let res2 = eval_flag(&flag, &ctx_map2);
-        assert!(!res2.enabled);
-        assert_eq!(res2.matched_rule, Some(1));
-    }
+        let counts = eval_flag_bulk(&flag, &contexts);
+        assert_eq!(counts.total, 1000);
+        assert_eq!(counts.by_rule[0], 100);
+        assert_eq!(counts.by_rule.iter().sum::<u64>() + counts.unmatched, 1000);
+        assert_eq!(counts.unmatched, 0);
+        assert_eq!(counts.enabled, counts.by_rule[0] + counts.by_rule[1]);
+        // Each context is decided exactly as eval_flag decides it.
+        let enabled = contexts.iter().filter(|ctx| eval_flag(&flag, ctx).enabled);
+        assert_eq!(counts.enabled, enabled.count() as u64);
+        assert!((counts.enabled_share() - 0.55).abs() < 0.05);
 
-    #[test]
-    fn test_false_literal_and_unknown_identifier_handling() {
-        let flag = Flag {
-            key: "bool_false_test".to_string(),
+        let empty = eval_flag_bulk(&flag, &[]);
+        assert_eq!((empty.total, empty.by_rule.len()), (0, 3));
+        assert_eq!(empty.enabled_share(), 0.0);
+
+        // A rule far past the limits is skipped without being walked, so
+        // it costs a linear scan rather than deep recursion and rescans.
+        let deep = Flag {
+            key: "deep".to_string(),
             rules: vec![
-                Rule { cond: "false".to_string(), then_value: true },
-                Rule { cond: "unknownField".to_string(), then_value: true },
+                Rule {
+                    cond: vec!["email ~= 'a'"; 20_000].join(" && "),
+                    then_value: true,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "true".to_string(),
+                    then_value: false,
+                    ..Default::default()
+                },
             ],
-            default: false,
+            ..Default::default()
         };
-        let ctx_map = ctx(&[("userId", "u11"), ("country", "US")]);
-        let res = eval_flag(&flag, &ctx_map);
-        assert!(!res.enabled);
-        assert_eq!(res.matched_rule, None);
+        let counts = eval_flag_bulk(&deep, &contexts);
+        assert_eq!(counts.by_rule, vec![0, 1000]);
+        assert_eq!(counts.enabled, 0);
     }
 
     #[test]
-    fn test_rollout_missing_user_id_and_invalid_rollout_expression() {
+    fn test_stats_count_evaluations_matches_and_errors() {
         let flag = Flag {
-            key: "rollout_edges".to_string(),
+            key: "paywall".to_string(),
             rules: vec![
-                Rule { cond: "rollout(1.0)".to_string(), then_value: true },
-                Rule { cond: "rollout(bad)".to_string(), then_value: true },
+                Rule {
+                    // Not a boolean, so the rule always errors.
+                    cond: "country".to_string(),
+                    then_value: false,
+                    ..Default::default()
+                },
+                Rule {
+                    cond: "country == 'CA'".to_string(),
+                    then_value: true,
+                    ..Default::default()
+                },
             ],
-            default: false,
+            ..Default::default()
         };
-        let mut ctx_map: Context = Context::new();
-        ctx_map.insert("country".to_string(), Value::Str("US".to_string()));
-        let res = eval_flag(&flag, &ctx_map);
-        assert!(res.enabled);
-        assert_eq!(res.matched_rule, Some(0));
+        let mut stats = EvalStats::new();
+        for country in ["CA", "CA", "US"] {
+            let mut ctx = Context::new();
+            ctx.insert("country".to_string(), Value::Str(country.to_string()));
+            let mut opts = EvalOptions {
+                stats: Some(&mut stats),
+                ..EvalOptions::default()
+            };
+            let counted = eval_flag_with(&flag, &ctx, &mut opts);
+            assert_eq!(counted.matched_rule, eval_flag(&flag, &ctx).matched_rule);
+        }
+        let expected = FlagStats {
+            evaluations: 3,
+            matches: vec![0, 2],
+            errors: vec![3, 0],
+            defaults: 1,
+        };
+        assert_eq!(stats.get("paywall"), Some(&expected));
+        assert_eq!(stats.get("other"), None);
 
-        assert!(eval_rule_expr("rollout_edges", "rollout(bad)", &ctx_map).is_err());
-        assert!(eval_rule_expr("rollout_edges", "rollout(0.5", &ctx_map).is_err());
+        let compiled = CompiledFlag::compile(flag).unwrap();
+        let mut opts = EvalOptions {
+            stats: Some(&mut stats),
+            ..EvalOptions::default()
+        };
+        compiled.eval_with(&Context::new(), &mut opts);
+        assert_eq!(stats.get("paywall").unwrap().evaluations, 4);
+
+        let mut merged = stats.clone();
+        merged.merge(&stats);
+        assert_eq!(merged.get("paywall").unwrap().errors, vec![8, 0]);
     }
 
     #[test]
-    fn test_string_and_boolean_comparison_edges() {
-        let mut ctx_map: Context = Context::new();
-        ctx_map.insert("userId".to_string(), Value::Str("u12".to_string()));
-        ctx_map.insert("country".to_string(), Value::Str("CA".to_string()));
-        ctx_map.insert("enabled".to_string(), Value::Bool(true));
+    fn test_options_combine_trace_stats_and_overrides() {
+        let flag = Flag {
+            key: "paywall".to_string(),
+            rules: vec![Rule {
+                cond: "country == 'CA'".to_string(),
+                then_value: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let ctx_map = ctx(&[("country", "CA")]);
+        let mut stats = EvalStats::new();
+        let mut overrides = Overrides::new();
+        overrides.insert("other".to_string(), false);
+        let mut opts = EvalOptions {
+            stats: Some(&mut stats),
+            overrides: Some(&overrides),
+            ..EvalOptions::default()
+        };
+        let trace = trace_flag_with(&flag, &ctx_map, &mut opts);
+        assert_eq!(trace.rules[0].outcome, RuleOutcome::Matched);
+        assert!(!trace.result.overridden);
 
-        assert_eq!(eval_rule_expr("cmp_edges", "country != 'US'", &ctx_map), Ok(true));
-        assert_eq!(eval_rule_expr("cmp_edges", "enabled == true", &ctx_map), Ok(true));
-        assert_eq!(eval_rule_expr("cmp_edges", "enabled != false", &ctx_map), Ok(true));
+        overrides.insert("paywall".to_string(), false);
+        let mut opts = EvalOptions {
+            stats: Some(&mut stats),
+            overrides: Some(&overrides),
+            ..EvalOptions::default()
+        };
+        let trace = trace_flag_with(&flag, &ctx_map, &mut opts);
+        let result = eval_flag_with(&flag, &ctx_map, &mut opts);
+        assert_eq!(
+            (trace.result.enabled, trace.result.overridden),
+            (result.enabled, result.overridden)
+        );
+        assert!(result.overridden && !result.enabled);
+        assert_eq!(trace.rules[0].skip_reason, Some(SkipReason::Overridden));
+        // Overridden evaluations are counted, but decided by no rule.
+        let expected = FlagStats {
+            evaluations: 3,
+            matches: vec![1],
+            errors: vec![0],
+            defaults: 0,
+        };
+        assert_eq!(stats.get("paywall"), Some(&expected));
     }
 
     #[test]
-    fn test_numeric_bool_and_mismatched_comparison_edges() {
-        let mut ctx_map: Context = Context::new();
-        ctx_map.insert("userId".to_string(), Value::Str("u13".to_string()));
-        ctx_map.insert("ver".to_string(), Value::Num(2.0));
-        ctx_map.insert("enabled".to_string(), Value::Bool(true));
-
-        assert_eq!(eval_rule_expr("cmp_edges", "ver == 2", &ctx_map), Ok(true));
-        assert_eq!(eval_rule_expr("cmp_edges", "ver != 3", &ctx_map), Ok(true));
-        assert_eq!(eval_rule_expr("cmp_edges", "ver <= 2", &ctx_map), Ok(true));
-        assert_eq!(eval_rule_expr("cmp_edges", "ver > 1", &ctx_map), Ok(true));
-        assert_eq!(eval_rule_expr("cmp_edges", "ver ~~ 2", &ctx_map), Err(()));
-        assert_eq!(eval_rule_expr("cmp_edges", "ver == '2'", &ctx_map), Ok(false));
-        assert_eq!(eval_rule_expr("cmp_edges", "enabled >= true", &ctx_map), Err(()));
+    fn test_check_rule_accepts_the_documented_forms() {
+        for expr in [
+            "country == 'CA'",
+            "rollout(0.20)",
+            "rollout(0.2, by='accountId')",
+            "rollout(0.1, per='country')",
+            "region in ('EU','APAC') && ver >= 2",
+            "country == 'US' || country == 'MX'",
+            "email endsWith '@example.com'",
+            "path matches '/docs/*.md'",
+            "email ~= '^[a-z]+@corp\\.'",
+            "'admin' in roles",
+            "any(roles, 'admin', 'owner')",
+            "exists(nickname) && attr != null",
+            "now >= '2025-03-01T00:00:00Z'",
+            "variant({'a':0.5,'b':0.5}) == 'b'",
+            "beta",
+        ] {
+            assert_eq!(check_rule(expr), Ok(()), "{expr}");
+        }
+        for expr in [
+            "",
+            "country ==",
+            "'CA'",
+            "rollout(half)",
+            "country in ('CA', US)",
+            "variant({'a':0.8,'b':0.8})",
+            "email ~= pattern",
+            "all(roles)",
+            "country = 'CA'",
+        ] {
+            assert!(check_rule(expr).is_err(), "{expr}");
+        }
     }
 
     #[test]
-    fn test_in_operator_error_and_false_paths() {
-        let mut ctx_map: Context = Context::new();
-        ctx_map.insert("userId".to_string(), Value::Str("u14".to_string()));
-        ctx_map.insert("country".to_string(), Value::Str("CA".to_string()));
-        ctx_map.insert("ver".to_string(), Value::Num(2.0));
+    fn test_rules_over_the_limits_are_refused_before_recursing() {
+        let ctx_map = ctx(&[("userId", "u1"), ("country", "CA")]);
+        let chain = |n: usize| vec!["country == 'CA'"; n].join(" && ");
+        assert_eq!(
+            eval_rule_expr("k", &chain(MAX_NESTING_DEPTH + 1), &ctx_map),
+            Ok(true)
+        );
+        let long_chain = chain(MAX_NESTING_DEPTH + 2);
+        assert_eq!(eval_rule_expr("k", &long_chain, &ctx_map), Err(()));
+        assert!(check_rule(&long_chain).unwrap_err().contains("levels deep"));
 
-        assert_eq!(eval_rule_expr("in_edges", "country in ('US','MX')", &ctx_map), Ok(false));
-        assert_eq!(eval_rule_expr("in_edges", "country in (\"CA\",\"US\")", &ctx_map), Ok(true));
-        assert_eq!(eval_rule_expr("in_edges", "country in 'CA'", &ctx_map), Err(()));
-        assert_eq!(eval_rule_expr("in_edges", "country in (CA,'US')", &ctx_map), Ok(false));
-        assert_eq!(eval_rule_expr("in_edges", "ver in ('1','2')", &ctx_map), Ok(false));
+        // Deep enough to overflow the stack if anything recursed on it.
+        let deep = format!("{}x{}", "any(".repeat(100_000), ")".repeat(100_000));
+        assert_eq!(eval_rule_expr("k", &deep, &ctx_map), Err(()));
+        let nested = format!(
+            "{}true{}",
+            "any(".repeat(MAX_NESTING_DEPTH + 1),
+            ")".repeat(MAX_NESTING_DEPTH + 1)
+        );
+        assert!(check_rule(&nested).unwrap_err().contains("levels deep"));
+
+        let items = |n: usize| vec!["'CA'"; n].join(",");
+        let at_limit = format!("country in ({})", items(MAX_LIST_ITEMS));
+        assert_eq!(eval_rule_expr("k", &at_limit, &ctx_map), Ok(true));
+        let over = format!("country in ({})", items(MAX_LIST_ITEMS + 1));
+        assert!(check_rule(&over).unwrap_err().contains("more than"));
+        // Commas inside quotes are not items.
+        let quoted = format!("any(roles, '{}')", ",".repeat(MAX_LIST_ITEMS * 2));
+        assert_eq!(check_rule(&quoted), Ok(()));
+
+        let padded = format!("country == '{}'", "x".repeat(MAX_EXPR_LEN));
+        assert_eq!(eval_rule_expr("k", &padded, &ctx_map), Err(()));
+        let flag = Flag {
+            key: "k".to_string(),
+            rules: vec![Rule {
+                cond: padded,
+                then_value: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert_eq!(eval_flag(&flag, &ctx_map).matched_rule, None);
+        assert_eq!(validate_flag(&flag)[0].kind, LintKind::Malformed);
+        assert!(flag.referenced_attributes().is_empty());
     }
 
     #[test]
-    fn test_internal_invalid_operators() {
-        assert_eq!(
-            eval_comparison(Value::Str("CA".to_string()), "<", Value::Str("US".to_string())),
-            Err(())
-        );
-        assert_eq!(eval_comparison(Value::Num(1.0), "contains", Value::Num(2.0)), Err(()));
+    fn timestamps_display_as_rfc3339_utc() {
+        for text in [
+            "1970-01-01T00:00:00Z",
+            "2024-02-29T23:59:59.250Z",
+            "1969-12-31T23:59:59.999Z",
+        ] {
+            let t = Timestamp::parse_rfc3339(text).unwrap();
+            assert_eq!(t.to_string(), text);
+        }
+        let offset = Timestamp::parse_rfc3339("2025-03-01T09:30:00+05:30").unwrap();
+        assert_eq!(offset.to_string(), "2025-03-01T04:00:00Z");
     }
 
+    #[cfg(feature = "serde")]
     #[test]
-    fn test_error_propagation_in_logical_and_comparison_expressions() {
-        let ctx_map = ctx(&[("userId", "u15"), ("country", "CA")]);
+    fn serde_uses_contract_field_names() {
+        let json = r#"{"key":"paywall","default":false,"hash":"murmur3","rules":[
+            {"if":"country == 'CA'","then":true,"activeFrom":"2025-03-01T00:00:00Z"}]}"#;
+        let flag: Flag = serde_json::from_str(json).unwrap();
+        assert_eq!(flag.hash, HashAlgorithm::Murmur3);
+        assert_eq!(flag.rules[0].cond, "country == 'CA'");
+        assert_eq!(
+            flag.rules[0].active_from.unwrap().to_string(),
+            "2025-03-01T00:00:00Z"
+        );
+        let back = serde_json::to_value(&flag).unwrap();
+        assert_eq!(
+            back,
+            serde_json::from_str::<serde_json::Value>(json).unwrap()
+        );
+        assert!(serde_json::from_str::<Flag>(r#"{"key":"k","rules":[],"hash":"sha1"}"#).is_err());
+        assert!(
+            serde_json::from_str::<Flag>(r#"{"key":"k","rules":[],"state":"paused"}"#).is_err()
+        );
 
-        assert_eq!(eval_rule_expr("propagation", "rollout(bad) || true", &ctx_map), Err(()));
-        assert_eq!(eval_rule_expr("propagation", "false || rollout(bad)", &ctx_map), Err(()));
-        assert_eq!(eval_rule_expr("propagation", "rollout(bad) && true", &ctx_map), Err(()));
-        assert_eq!(eval_rule_expr("propagation", "true && rollout(bad)", &ctx_map), Err(()));
+        let ctx: Context = serde_json::from_str(
+            r#"{"country":"CA","age":30,"roles":["admin"],"meta":{"a":1},"gone":null}"#,
+        )
+        .unwrap();
+        let now = Timestamp::parse_rfc3339("2025-06-01T00:00:00Z").unwrap();
+        let result = eval_flag_with(&flag, &ctx, &mut clock(now));
+        assert!(matches!(ctx["meta"], Value::Null) && matches!(ctx["roles"], Value::List(_)));
         assert_eq!(
-            eval_rule_expr("propagation", "rollout(bad) == true", &ctx_map),
-            Err(())
+            serde_json::to_value(&result).unwrap(),
+            serde_json::json!({ "key": "paywall", "enabled": true, "matchedRule": 0, "reason": "TARGETING_MATCH" })
         );
+        let set = FlagSet::load(vec![flag]).unwrap();
+        let stamped = serde_json::to_value(set.eval("paywall", &Context::new()).unwrap()).unwrap();
+        assert_eq!(stamped["flagsetFingerprint"], set.fingerprint().to_string());
+        let decoded: EvalResult = serde_json::from_value(stamped).unwrap();
+        assert_eq!(decoded.flagset_fingerprint, Some(set.fingerprint()));
+
+        let mut stats = EvalStats::new();
+        let mut opts = EvalOptions {
+            stats: Some(&mut stats),
+            ..EvalOptions::default()
+        };
+        set.eval_with("paywall", &Context::new(), &mut opts)
+            .unwrap();
         assert_eq!(
-            eval_rule_expr("propagation", "true == rollout(bad)", &ctx_map),
-            Err(())
+            serde_json::to_value(&stats).unwrap(),
+            serde_json::json!({ "flags": { "paywall":
+                { "evaluations": 1, "matches": [0], "errors": [0], "defaults": 1 } } })
         );
     }
 }
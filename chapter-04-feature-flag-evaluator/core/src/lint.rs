@@ -0,0 +1,360 @@
+//! Static checks over a flag's rules.
+//!
+//! [`crate::check_rule`] only answers whether a rule parses.  [`validate_flag`]
+//! also looks for rules that parse but cannot do what their author meant: a
+//! rule that can never be reached, a call to a builtin the evaluator does not
+//! have, a comparison whose operand types rule out a match, or a rollout share
+//! outside `[0, 1]`.  Nothing is evaluated, so no context or clock is needed.
+
+use crate::{
+    check_limits, check_rule_with, is_identifier, is_literal, parse_bucketing_args,
+    parse_bucketing_call, split_top_level, unquote, Flag, FunctionRegistry, Ramp, Timestamp,
+    COMPARISON_OPERATORS,
+};
+use std::fmt;
+
+/// Builtins the evaluator understands as `name(...)` calls.
+pub(crate) const BUILTINS: [&str; 8] = [
+    "rollout", "ramp", "bucket", "variant", "exists", "missing", "any", "all",
+];
+
+/// What a [`LintWarning`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintKind {
+    /// The rule does not parse and is always skipped.
+    Malformed,
+    /// An earlier rule always matches, so this one is never evaluated.
+    Unreachable,
+    /// A `name(...)` call to something that is not a builtin.
+    UnknownBuiltin,
+    /// A comparison whose operand types mean it cannot hold as written.
+    SuspiciousComparison,
+    /// `rollout(p)`, or a `ramp(...)` share, outside `[0, 1]`.
+    RolloutOutOfRange,
+}
+
+impl LintKind {
+    /// Stable name for logs and reports.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LintKind::Malformed => "malformed",
+            LintKind::Unreachable => "unreachable",
+            LintKind::UnknownBuiltin => "unknown_builtin",
+            LintKind::SuspiciousComparison => "suspicious_comparison",
+            LintKind::RolloutOutOfRange => "rollout_out_of_range",
+        }
+    }
+}
+
+/// One problem found in a flag's rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintWarning {
+    /// Index of the offending rule in [`Flag::rules`].
+    pub rule: usize,
+    pub kind: LintKind,
+    pub message: String,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rule {}: {}", self.rule, self.message)
+    }
+}
+
+/// Lint every rule of `flag`, returning warnings in rule order.  An empty
+/// result means every rule parses and none of the checks above fired; it
+/// does not mean the flag behaves as intended.
+pub fn validate_flag(flag: &Flag) -> Vec<LintWarning> {
+    validate_with(flag, None)
+}
+
+/// [`validate_flag`] for a flag evaluated with `functions`, so calls to the
+/// custom builtins it holds are not reported as unknown.
+pub fn validate_flag_with_functions(flag: &Flag, functions: &FunctionRegistry) -> Vec<LintWarning> {
+    validate_with(flag, Some(functions))
+}
+
+fn validate_with(flag: &Flag, functions: Option<&FunctionRegistry>) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+    let mut shadowed_by = None;
+    for (i, rule) in flag.rules.iter().enumerate() {
+        let mut found = Vec::new();
+        // A rule over the limits is only reported as malformed.
+        if check_limits(rule.cond.trim()).is_ok() {
+            lint_expr(rule.cond.trim(), functions, &mut found);
+        }
+        let unknown_builtin = found
+            .iter()
+            .any(|(kind, _)| *kind == LintKind::UnknownBuiltin);
+        if let Err(message) = check_rule_with(&rule.cond, functions) {
+            // An unknown builtin is also a parse error; report it once.
+            if !unknown_builtin {
+                found.insert(0, (LintKind::Malformed, message));
+            }
+        }
+        if let Some(earlier) = shadowed_by {
+            found.push((
+                LintKind::Unreachable,
+                format!("never evaluated: rule {earlier} always matches"),
+            ));
+        } else if always_matches(rule.cond.trim())
+            && rule.active_from.is_none()
+            && rule.active_until.is_none()
+        {
+            shadowed_by = Some(i);
+        }
+        warnings.extend(found.into_iter().map(|(kind, message)| LintWarning {
+            rule: i,
+            kind,
+            message,
+        }));
+    }
+    warnings
+}
+
+/// Whether a condition holds for every context: the literal `true`, or a
+/// rollout to everyone, since every bucket is below 1.
+fn always_matches(cond: &str) -> bool {
+    cond.eq_ignore_ascii_case("true") || rollout_share(cond).is_some_and(|p| p >= 1.0)
+}
+
+/// The schedule of a well-formed `ramp(...)` term.
+fn ramp_schedule(t: &str) -> Option<Ramp> {
+    let inner = t.strip_prefix("ramp(")?.strip_suffix(')')?;
+    let (args, _) = parse_bucketing_call(inner, 4).ok()?;
+    Ramp::parse(&args).ok()
+}
+
+/// The share of a well-formed `rollout(...)` term.
+fn rollout_share(t: &str) -> Option<f64> {
+    let inner = t.strip_prefix("rollout(")?.strip_suffix(')')?;
+    let (p, _) = parse_bucketing_args(inner).ok()?;
+    p.parse().ok()
+}
+
+fn lint_expr(s: &str, functions: Option<&FunctionRegistry>, out: &mut Vec<(LintKind, String)>) {
+    for sep in ["||", "&&"] {
+        if let Some(idx) = split_top_level(s, sep) {
+            lint_expr(s[..idx].trim(), functions, out);
+            lint_expr(s[idx + 2..].trim(), functions, out);
+            return;
+        }
+    }
+    for op in COMPARISON_OPERATORS {
+        if let Some(idx) = split_top_level(s, op) {
+            let (lhs, op, rhs) = (s[..idx].trim(), op.trim(), s[idx + op.len()..].trim());
+            lint_term(lhs, functions, out);
+            // The right side of `~=` is a pattern and of `in` may be a list.
+            if op != "~=" && !(op == "in" && rhs.starts_with('(')) {
+                lint_term(rhs, functions, out);
+            }
+            if let Some(message) = suspicious_comparison(lhs, op, rhs) {
+                out.push((LintKind::SuspiciousComparison, message));
+            }
+            return;
+        }
+    }
+    lint_term(s, functions, out);
+}
+
+fn lint_term(t: &str, functions: Option<&FunctionRegistry>, out: &mut Vec<(LintKind, String)>) {
+    if let Some(p) = rollout_share(t) {
+        if !(0.0..=1.0).contains(&p) {
+            out.push((
+                LintKind::RolloutOutOfRange,
+                format!("`rollout({p})` share is outside [0, 1]"),
+            ));
+        }
+        return;
+    }
+    if let Some(ramp) = ramp_schedule(t) {
+        for p in [ramp.from, ramp.to] {
+            if !(0.0..=1.0).contains(&p) {
+                out.push((
+                    LintKind::RolloutOutOfRange,
+                    format!("`ramp(...)` share {p} is outside [0, 1]"),
+                ));
+            }
+        }
+        return;
+    }
+    if let Some(name) = unknown_call(t, functions) {
+        out.push((
+            LintKind::UnknownBuiltin,
+            format!("unknown builtin `{name}(...)`"),
+        ));
+    }
+}
+
+/// The name called by term `t` when it is a `name(...)` call to neither a
+/// builtin nor a function in `functions`.
+fn unknown_call<'t>(t: &'t str, functions: Option<&FunctionRegistry>) -> Option<&'t str> {
+    let (name, _) = t.split_once('(')?;
+    let name = name.trim();
+    let registered = functions.is_some_and(|f| f.contains(name));
+    (t.ends_with(')') && is_identifier(name) && !BUILTINS.contains(&name) && !registered)
+        .then_some(name)
+}
+
+/// The first term of `s`, as written, that calls an unknown builtin.  Such
+/// a call fails every evaluation of its rule.
+pub(crate) fn first_unknown_call<'s>(
+    s: &'s str,
+    functions: Option<&FunctionRegistry>,
+) -> Option<&'s str> {
+    for sep in ["||", "&&"] {
+        if let Some(idx) = split_top_level(s, sep) {
+            return first_unknown_call(s[..idx].trim(), functions)
+                .or_else(|| first_unknown_call(s[idx + 2..].trim(), functions));
+        }
+    }
+    for op in COMPARISON_OPERATORS {
+        if let Some(idx) = split_top_level(s, op) {
+            let (lhs, op, rhs) = (s[..idx].trim(), op.trim(), s[idx + op.len()..].trim());
+            let mut terms = vec![lhs];
+            if op != "~=" && !(op == "in" && rhs.starts_with('(')) {
+                terms.push(rhs);
+            }
+            return terms
+                .into_iter()
+                .find(|t| unknown_call(t, functions).is_some());
+        }
+    }
+    unknown_call(s, functions).map(|_| s)
+}
+
+/// Explain why `lhs op rhs` cannot hold as written, if it cannot.
+fn suspicious_comparison(lhs: &str, op: &str, rhs: &str) -> Option<String> {
+    if is_literal(lhs) && is_literal(rhs) {
+        return Some(format!(
+            "`{lhs} {op} {rhs}` compares two literals and ignores the context"
+        ));
+    }
+    let is_bool = |t: &str| t.eq_ignore_ascii_case("true") || t.eq_ignore_ascii_case("false");
+    match op {
+        "<" | "<=" | ">" | ">=" => {
+            for side in [lhs, rhs] {
+                if is_bool(side) {
+                    return Some(format!("`{op}` never holds for a boolean such as `{side}`"));
+                }
+                if let Some(message) = not_a_timestamp(op, side) {
+                    return Some(message);
+                }
+            }
+            None
+        }
+        "==" | "!=" if lhs == "now" || rhs == "now" => {
+            let other = if lhs == "now" { rhs } else { lhs };
+            not_a_timestamp(op, other)
+        }
+        "contains" | "startsWith" | "endsWith" | "matches" => {
+            let non_string = |t: &str| is_literal(t) && unquote(t).is_none();
+            [lhs, rhs]
+                .into_iter()
+                .find(|side| non_string(side))
+                .map(|side| format!("`{op}` compares strings, but `{side}` is not one"))
+        }
+        _ => None,
+    }
+}
+
+/// Strings order, and compare with `now`, only as RFC 3339 timestamps.
+fn not_a_timestamp(op: &str, side: &str) -> Option<String> {
+    let text = unquote(side)?;
+    match Timestamp::parse_rfc3339(text) {
+        Some(_) => None,
+        None => Some(format!(
+            "`{op}` on strings needs RFC 3339 timestamps, but `{side}` is not one"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rule;
+
+    fn flag(conds: &[&str]) -> Flag {
+        Flag {
+            key: "f".to_string(),
+            rules: conds
+                .iter()
+                .map(|cond| Rule {
+                    cond: cond.to_string(),
+                    then_value: true,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn kinds(flag: &Flag) -> Vec<(usize, &'static str)> {
+        validate_flag(flag)
+            .iter()
+            .map(|w| (w.rule, w.kind.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn clean_flags_have_no_warnings() {
+        let flag = flag(&[
+            "country in ('CA','US') && rollout(0.25, by='accountId')",
+            "now >= '2025-03-01T00:00:00Z' || plan == 'pro'",
+            "email endsWith '@example.com' && age >= 18",
+            "any(roles, 'admin') && exists(plan)",
+            "bucket(10, layer='checkout') < 3",
+        ]);
+        assert_eq!(validate_flag(&flag), []);
+    }
+
+    #[test]
+    fn reports_each_kind_with_its_rule() {
+        let flag = flag(&[
+            "rollout(1.5)",
+            "geo(country) && plan == 'pro'",
+            "plan contains 5 || age < 'ten'",
+            "beta > true",
+            "'a' == 'a'",
+            "plan in ('pro', gold)",
+        ]);
+        assert_eq!(
+            kinds(&flag),
+            [
+                (0, "rollout_out_of_range"),
+                (1, "unknown_builtin"),
+                (1, "unreachable"),
+                (2, "suspicious_comparison"),
+                (2, "suspicious_comparison"),
+                (2, "unreachable"),
+                (3, "suspicious_comparison"),
+                (3, "unreachable"),
+                (4, "suspicious_comparison"),
+                (4, "unreachable"),
+                (5, "malformed"),
+                (5, "unreachable"),
+            ]
+        );
+        let warnings = validate_flag(&flag);
+        assert_eq!(
+            warnings[1].to_string(),
+            "rule 1: unknown builtin `geo(...)`"
+        );
+        assert_eq!(
+            warnings[2].to_string(),
+            "rule 1: never evaluated: rule 0 always matches"
+        );
+    }
+
+    #[test]
+    fn windowed_catch_alls_do_not_shadow() {
+        let mut windowed = flag(&["TRUE", "plan == 'pro'"]);
+        windowed.rules[0].active_until = Timestamp::parse_rfc3339("2025-03-01T00:00:00Z");
+        assert_eq!(validate_flag(&windowed), []);
+        assert_eq!(
+            kinds(&flag(&["TRUE", "plan == 'pro'"])),
+            [(1, "unreachable")]
+        );
+        assert_eq!(kinds(&flag(&["rollout(0.999)", "true"])), []);
+    }
+}
@@ -0,0 +1,81 @@
+//! Evaluation counters.
+//!
+//! Edge deployments rarely keep decision logs, but they still need to know
+//! which flags are used before removing any.  An [`EvalStats`] passed in
+//! [`crate::EvalOptions::stats`] counts evaluations per flag, matches
+//! per rule and rules skipped because they failed to evaluate.  It serializes
+//! with the `serde` feature, so a host can report it as is.
+
+use crate::Flag;
+use std::collections::BTreeMap;
+
+/// Counters for every flag evaluated through it, keyed by flag key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EvalStats {
+    pub flags: BTreeMap<String, FlagStats>,
+}
+
+/// Counters for one flag.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct FlagStats {
+    /// Number of evaluations, including those an override decided.
+    pub evaluations: u64,
+    /// Evaluations decided by each rule, indexed like [`Flag::rules`].
+    pub matches: Vec<u64>,
+    /// Times each rule was skipped because it failed to evaluate, e.g. a
+    /// comparison between mismatched types.
+    pub errors: Vec<u64>,
+    /// Evaluations no rule matched, which received the flag's default.  An
+    /// overridden evaluation counts neither here nor in `matches`.
+    pub defaults: u64,
+}
+
+impl EvalStats {
+    pub fn new() -> Self {
+        EvalStats::default()
+    }
+
+    /// Counters for the flag named `key`, or `None` if it was never evaluated
+    /// through these stats.
+    pub fn get(&self, key: &str) -> Option<&FlagStats> {
+        self.flags.get(key)
+    }
+
+    /// Add the counters of `other`, e.g. to combine reports from several
+    /// workers.
+    pub fn merge(&mut self, other: &EvalStats) {
+        for (key, theirs) in &other.flags {
+            let ours = self.flags.entry(key.clone()).or_default();
+            ours.evaluations += theirs.evaluations;
+            ours.defaults += theirs.defaults;
+            add_counts(&mut ours.matches, &theirs.matches);
+            add_counts(&mut ours.errors, &theirs.errors);
+        }
+    }
+
+    /// The counters of `flag`, sized for its rules.
+    pub(crate) fn entry(&mut self, flag: &Flag) -> &mut FlagStats {
+        let stats = self.flags.entry(flag.key.clone()).or_default();
+        // A reload may have added rules since the last evaluation.
+        if stats.matches.len() < flag.rules.len() {
+            stats.matches.resize(flag.rules.len(), 0);
+            stats.errors.resize(flag.rules.len(), 0);
+        }
+        stats
+    }
+}
+
+fn add_counts(ours: &mut Vec<u64>, theirs: &[u64]) {
+    if ours.len() < theirs.len() {
+        ours.resize(theirs.len(), 0);
+    }
+    for (ours, theirs) in ours.iter_mut().zip(theirs) {
+        *ours += theirs;
+    }
+}
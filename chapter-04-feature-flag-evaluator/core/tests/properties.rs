@@ -0,0 +1,182 @@
+//! Property-based tests for rule expressions.
+//!
+//! Expressions are generated from the grammar's own pieces (attributes,
+//! literals, comparison operators, `in` lists, `&&` and `||`)
+//! and contexts from random attribute values, so the properties hold for
+//! far more shapes than the hand-written cases in `src/lib.rs`.  The fuzz
+//! target in `core/fuzz` covers arbitrary bytes; these cover well-formed
+//! input, where a wrong answer is more likely than a crash.
+
+use ff_eval_core::{
+    check_rule, eval_flag_with_trace, eval_rule_expr, validate_flag, Context, Flag, Rule, Value,
+};
+use proptest::prelude::*;
+
+/// Attributes a generated context may carry.
+const KNOWN: [&str; 5] = ["country", "plan", "age", "beta", "roles"];
+
+fn value() -> impl Strategy<Value = Value> {
+    let scalar = prop_oneof![
+        "[A-Z]{2}".prop_map(Value::Str),
+        (-100i32..100).prop_map(|n| Value::Num(f64::from(n))),
+        any::<bool>().prop_map(Value::Bool),
+        Just(Value::Null),
+    ];
+    prop_oneof![
+        4 => scalar.clone(),
+        1 => prop::collection::vec(scalar, 0..3).prop_map(Value::List),
+    ]
+}
+
+fn context() -> impl Strategy<Value = Context> {
+    prop::collection::hash_map(prop::sample::select(&KNOWN[..]), value(), 0..=KNOWN.len()).prop_map(
+        |attrs| {
+            let mut ctx: Context = attrs.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+            ctx.insert("userId".to_string(), Value::Str("u1".to_string()));
+            ctx
+        },
+    )
+}
+
+fn quoted() -> impl Strategy<Value = String> {
+    "[A-Z]{2}".prop_map(|s| format!("'{s}'"))
+}
+
+/// A literal as written in a rule: a quoted string, a number or a boolean.
+fn literal() -> impl Strategy<Value = String> {
+    prop_oneof![
+        quoted(),
+        (-100i32..100).prop_map(|n| n.to_string()),
+        any::<bool>().prop_map(|b| b.to_string()),
+    ]
+}
+
+fn operator() -> impl Strategy<Value = &'static str> {
+    prop::sample::select(&["==", "!=", "<", "<=", ">", ">="][..])
+}
+
+/// An attribute that no generated context carries.
+fn unknown_attribute() -> impl Strategy<Value = String> {
+    "zz_[a-z]{1,6}"
+}
+
+/// A comparison or `in` test over a context attribute: pure, in that its
+/// value depends only on the context.
+fn comparison() -> impl Strategy<Value = String> {
+    let attribute = prop_oneof![
+        4 => prop::sample::select(&KNOWN[..]).prop_map(str::to_string),
+        1 => unknown_attribute(),
+    ];
+    prop_oneof![
+        (attribute.clone(), operator(), literal()).prop_map(|(a, op, l)| format!("{a} {op} {l}")),
+        (attribute, prop::collection::vec(quoted(), 1..4))
+            .prop_map(|(a, items)| format!("{a} in ({})", items.join(", "))),
+    ]
+}
+
+/// Comparisons combined with `&&` and `||`.  The grammar has no grouping
+/// parentheses, so precedence alone decides the shape.
+fn expression() -> impl Strategy<Value = String> {
+    comparison().prop_recursive(4, 24, 2, |inner| {
+        prop_oneof![
+            (inner.clone(), inner.clone()).prop_map(|(a, b)| format!("{a} && {b}")),
+            (inner.clone(), inner).prop_map(|(a, b)| format!("{a} || {b}")),
+        ]
+    })
+}
+
+/// Pieces of the grammar for [`token_soup`].
+const TOKENS: [&str; 28] = [
+    "country",
+    "==",
+    "!=",
+    "<",
+    ">=",
+    "in",
+    "&&",
+    "||",
+    "(",
+    ")",
+    "'CA'",
+    "\"",
+    "'",
+    "1.5",
+    "-",
+    "true",
+    "null",
+    "rollout(",
+    "0.2",
+    ",",
+    "by=",
+    "variant({",
+    "}",
+    "bucket(",
+    "now",
+    "exists(",
+    "matches",
+    " ",
+];
+
+/// Text built from grammar tokens in any order, most of it malformed.
+fn token_soup() -> impl Strategy<Value = String> {
+    let token = prop_oneof![
+        prop::sample::select(&TOKENS[..]).prop_map(str::to_string),
+        "\\PC{0,3}",
+    ];
+    prop::collection::vec(token, 0..24).prop_map(|tokens| tokens.concat())
+}
+
+fn flag_with(cond: &str) -> Flag {
+    Flag {
+        key: "prop".to_string(),
+        rules: vec![Rule {
+            cond: cond.to_string(),
+            then_value: true,
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+proptest! {
+    #[test]
+    fn evaluation_never_panics(expr in prop_oneof![expression(), token_soup()], ctx in context()) {
+        let _ = check_rule(&expr);
+        let _ = eval_rule_expr("prop", &expr, &ctx);
+        let flag = flag_with(&expr);
+        let _ = validate_flag(&flag);
+        let _ = eval_flag_with_trace(&flag, &ctx);
+    }
+
+    #[test]
+    fn generated_expressions_are_well_formed(expr in expression()) {
+        prop_assert_eq!(check_rule(&expr), Ok(()));
+    }
+
+    /// Only for comparisons that evaluate on their own: one that is skipped,
+    /// such as `<` between two plain strings, may be short-circuited past
+    /// in one order and not the other.
+    #[test]
+    fn conjunction_of_comparisons_commutes(a in comparison(), b in comparison(), ctx in context()) {
+        prop_assume!(eval_rule_expr("prop", &a, &ctx).is_ok());
+        prop_assume!(eval_rule_expr("prop", &b, &ctx).is_ok());
+        prop_assert_eq!(
+            eval_rule_expr("prop", &format!("{a} && {b}"), &ctx),
+            eval_rule_expr("prop", &format!("{b} && {a}"), &ctx)
+        );
+        prop_assert_eq!(
+            eval_rule_expr("prop", &format!("{a} || {b}"), &ctx),
+            eval_rule_expr("prop", &format!("{b} || {a}"), &ctx)
+        );
+    }
+
+    #[test]
+    fn unknown_attributes_never_equal_a_literal(
+        attr in unknown_attribute(),
+        lit in literal(),
+        ctx in context(),
+    ) {
+        prop_assert_eq!(eval_rule_expr("prop", &format!("{attr} == {lit}"), &ctx), Ok(false));
+        prop_assert_eq!(eval_rule_expr("prop", &format!("{lit} == {attr}"), &ctx), Ok(false));
+    }
+}
@@ -0,0 +1,213 @@
+// @generated by scripts/generate_schema_tests.py from scripts/schema_tests.json.
+// Do not edit: change the schema or the type, then rerun the script.
+
+#![cfg(feature = "serde")]
+
+use jsonschema::{Draft, JSONSchema};
+use serde_json::Value;
+
+/// The schema at `pointer` inside `source`, with the root's definitions in
+/// scope.  Draft 7 ignores keywords next to `$ref`, so the root's own
+/// constraints do not apply.
+fn compile(source: &str, pointer: &str) -> JSONSchema {
+    let mut schema: Value = serde_json::from_str(source).expect("schema is JSON");
+    if !pointer.is_empty() {
+        schema["$ref"] = Value::String(format!("#{pointer}"));
+    }
+    JSONSchema::options()
+        .with_draft(Draft::Draft7)
+        .compile(&schema)
+        .expect("schema compiles")
+}
+
+fn assert_valid(schema: &JSONSchema, document: &Value, what: &str) {
+    if let Err(errors) = schema.validate(document) {
+        let errors: Vec<String> = errors
+            .map(|e| format!("{}: {e}", e.instance_path))
+            .collect();
+        panic!("{what} does not match the schema: {errors:?}\n{document:#}");
+    }
+}
+
+#[test]
+fn input_flag_minimal_sample_round_trips() {
+    let schema = compile(
+        include_str!("../../contracts/input.schema.json"),
+        "/properties/flag",
+    );
+    let sample: Value = serde_json::from_str(
+        r#"{
+  "default": true,
+  "key": "example",
+  "rules": []
+}"#,
+    )
+    .unwrap();
+    assert_valid(&schema, &sample, "the minimal sample");
+    let typed: ff_eval_core::Flag = serde_json::from_value(sample.clone())
+        .expect("the minimal sample deserializes into ff_eval_core::Flag");
+    let back = serde_json::to_value(&typed).unwrap();
+    assert_valid(&schema, &back, "ff_eval_core::Flag serialized");
+}
+
+#[test]
+fn input_flag_full_sample_round_trips() {
+    let schema = compile(
+        include_str!("../../contracts/input.schema.json"),
+        "/properties/flag",
+    );
+    let sample: Value = serde_json::from_str(
+        r#"{
+  "default": true,
+  "hash": "murmur3",
+  "key": "example",
+  "metadata": {
+    "created": "2025-01-01T00:00:00Z",
+    "expires": "2025-01-01T00:00:00Z",
+    "owner": "example",
+    "tags": [
+      "example"
+    ]
+  },
+  "rules": [
+    {
+      "activeFrom": "2025-01-01T00:00:00Z",
+      "activeUntil": "2025-01-01T00:00:00Z",
+      "if": "example",
+      "then": true
+    }
+  ],
+  "salt": "example",
+  "state": "archived"
+}"#,
+    )
+    .unwrap();
+    assert_valid(&schema, &sample, "the full sample");
+    let typed: ff_eval_core::Flag = serde_json::from_value(sample.clone())
+        .expect("the full sample deserializes into ff_eval_core::Flag");
+    let back = serde_json::to_value(&typed).unwrap();
+    assert_valid(&schema, &back, "ff_eval_core::Flag serialized");
+    assert_eq!(
+        back, sample,
+        "fields were lost or changed in the round trip"
+    );
+}
+
+#[test]
+fn flagset_flag_minimal_sample_round_trips() {
+    let schema = compile(
+        include_str!("../../contracts/flagset.schema.json"),
+        "/definitions/flag",
+    );
+    let sample: Value = serde_json::from_str(
+        r#"{
+  "key": "example",
+  "rules": []
+}"#,
+    )
+    .unwrap();
+    assert_valid(&schema, &sample, "the minimal sample");
+    let typed: ff_eval_core::Flag = serde_json::from_value(sample.clone())
+        .expect("the minimal sample deserializes into ff_eval_core::Flag");
+    let back = serde_json::to_value(&typed).unwrap();
+    assert_valid(&schema, &back, "ff_eval_core::Flag serialized");
+}
+
+#[test]
+fn flagset_flag_full_sample_round_trips() {
+    let schema = compile(
+        include_str!("../../contracts/flagset.schema.json"),
+        "/definitions/flag",
+    );
+    let sample: Value = serde_json::from_str(
+        r#"{
+  "default": true,
+  "hash": "murmur3",
+  "key": "example",
+  "metadata": {
+    "created": "2025-01-01T00:00:00Z",
+    "expires": "2025-01-01T00:00:00Z",
+    "owner": "example",
+    "tags": [
+      "example"
+    ]
+  },
+  "rules": [
+    {
+      "activeFrom": "2025-01-01T00:00:00Z",
+      "activeUntil": "2025-01-01T00:00:00Z",
+      "if": "example",
+      "then": true
+    }
+  ],
+  "salt": "example",
+  "state": "archived"
+}"#,
+    )
+    .unwrap();
+    assert_valid(&schema, &sample, "the full sample");
+    let typed: ff_eval_core::Flag = serde_json::from_value(sample.clone())
+        .expect("the full sample deserializes into ff_eval_core::Flag");
+    let back = serde_json::to_value(&typed).unwrap();
+    assert_valid(&schema, &back, "ff_eval_core::Flag serialized");
+    assert_eq!(
+        back, sample,
+        "fields were lost or changed in the round trip"
+    );
+}
+
+#[test]
+fn output_minimal_sample_round_trips() {
+    let schema = compile(
+        include_str!("../../contracts/output.schema.json"),
+        "/definitions/result",
+    );
+    let sample: Value = serde_json::from_str(
+        r#"{
+  "enabled": true,
+  "key": "example",
+  "matchedRule": 0,
+  "reason": "TARGETING_MATCH"
+}"#,
+    )
+    .unwrap();
+    assert_valid(&schema, &sample, "the minimal sample");
+    let typed: ff_eval_core::EvalResult = serde_json::from_value(sample.clone())
+        .expect("the minimal sample deserializes into ff_eval_core::EvalResult");
+    let back = serde_json::to_value(&typed).unwrap();
+    assert_valid(&schema, &back, "ff_eval_core::EvalResult serialized");
+}
+
+#[test]
+fn output_full_sample_round_trips() {
+    let schema = compile(
+        include_str!("../../contracts/output.schema.json"),
+        "/definitions/result",
+    );
+    let sample: Value = serde_json::from_str(
+        r#"{
+  "enabled": true,
+  "flagsetFingerprint": "0123456789abcdef",
+  "key": "example",
+  "matchedRule": 0,
+  "overridden": true,
+  "reason": "PREREQUISITE_FAILED",
+  "state": "archived",
+  "stratum": {
+    "attribute": "example",
+    "value": "example"
+  },
+  "variant": "example"
+}"#,
+    )
+    .unwrap();
+    assert_valid(&schema, &sample, "the full sample");
+    let typed: ff_eval_core::EvalResult = serde_json::from_value(sample.clone())
+        .expect("the full sample deserializes into ff_eval_core::EvalResult");
+    let back = serde_json::to_value(&typed).unwrap();
+    assert_valid(&schema, &back, "ff_eval_core::EvalResult serialized");
+    assert_eq!(
+        back, sample,
+        "fields were lost or changed in the round trip"
+    );
+}
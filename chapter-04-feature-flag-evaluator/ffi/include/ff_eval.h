@@ -0,0 +1,26 @@
+/* C ABI of the feature flag evaluator.  Build with
+ * `cargo build --release -p ff_eval_ffi`, then link libff_eval_ffi. */
+#ifndef FF_EVAL_H
+#define FF_EVAL_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Evaluate the UTF-8 input document of `len` bytes at `input` (the same JSON
+ * the WASI app reads) and return the output document.  Failures come back as
+ * {"error": "<message>"}; the result is never NULL.  Release it with
+ * ff_eval_string_free, not free(). */
+char *ff_eval_flag_json(const uint8_t *input, size_t len);
+
+/* Release a string returned by ff_eval_flag_json.  NULL is ignored. */
+void ff_eval_string_free(char *ptr);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FF_EVAL_H */
@@ -0,0 +1,112 @@
+//! C ABI of the feature flag evaluator.
+//!
+//! Mobile SDKs (Swift, Kotlin through JNI) link this library instead of
+//! embedding a WASI runtime.  The boundary is the same JSON document the
+//! WASI executable reads and prints, evaluated by
+//! [`ff_eval_wasi_app::evaluate_document`], so every host gets identical
+//! answers.  See `include/ff_eval.h` for the C declarations.
+//!
+//! Ownership: every string returned by this library is allocated by Rust and
+//! must be released with [`ff_eval_string_free`], never with the host's
+//! `free`.  No panic crosses the boundary; one is reported as an error
+//! document instead.  This relies on the default `panic = "unwind"`.
+
+use std::ffi::{c_char, CString};
+use std::panic::{self, AssertUnwindSafe};
+
+/// Evaluate the UTF-8 input document of `len` bytes at `input` and return
+/// the output document as a NUL-terminated string.
+///
+/// Failures are returned as a document too, `{"error": "<message>"}`, so the
+/// result is never null: invalid UTF-8, invalid JSON, a rule that does not
+/// compile, or a panic inside the evaluator.
+///
+/// # Safety
+///
+/// `input` must point to `len` readable bytes, or be null when `len` is 0.
+/// The bytes are only read during the call.
+#[no_mangle]
+pub unsafe extern "C" fn ff_eval_flag_json(input: *const u8, len: usize) -> *mut c_char {
+    let bytes = if len == 0 {
+        &[][..]
+    } else if input.is_null() {
+        return error_document("input pointer is null");
+    } else {
+        // SAFETY: the caller guarantees `input` points to `len` readable bytes.
+        unsafe { std::slice::from_raw_parts(input, len) }
+    };
+    let Ok(text) = std::str::from_utf8(bytes) else {
+        return error_document("input is not valid UTF-8");
+    };
+    match panic::catch_unwind(AssertUnwindSafe(|| {
+        ff_eval_wasi_app::evaluate_document(text)
+    })) {
+        Ok(Ok(output)) => into_c_string(output.to_string()),
+        Ok(Err(err)) => error_document(&err.to_string()),
+        Err(_) => error_document("evaluator panicked"),
+    }
+}
+
+/// Release a string returned by [`ff_eval_flag_json`].  Null is ignored.
+///
+/// # Safety
+///
+/// `ptr` must be null or a pointer returned by this library that has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn ff_eval_string_free(ptr: *mut c_char) {
+    if !ptr.is_null() {
+        // SAFETY: the caller guarantees `ptr` came from `CString::into_raw`
+        // in this library and is freed only once.
+        drop(unsafe { CString::from_raw(ptr) });
+    }
+}
+
+fn error_document(message: &str) -> *mut c_char {
+    into_c_string(serde_json::json!({ "error": message }).to_string())
+}
+
+/// Serialized JSON escapes control characters, so it never holds a NUL.
+fn into_c_string(json: String) -> *mut c_char {
+    CString::new(json)
+        .expect("serialized JSON contains no NUL byte")
+        .into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    fn call(input: &[u8]) -> serde_json::Value {
+        unsafe {
+            let ptr = ff_eval_flag_json(input.as_ptr(), input.len());
+            assert!(!ptr.is_null());
+            let text = CStr::from_ptr(ptr).to_str().unwrap().to_owned();
+            ff_eval_string_free(ptr);
+            serde_json::from_str(&text).unwrap()
+        }
+    }
+
+    #[test]
+    fn evaluates_like_the_wasi_app() {
+        let input = r#"{"flag":{"key":"paywall","rules":[{"if":"country == 'CA'","then":true}],"default":false},"context":{"userId":"u1","country":"CA"}}"#;
+        assert_eq!(
+            call(input.as_bytes()),
+            ff_eval_wasi_app::evaluate_document(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn failures_come_back_as_error_documents() {
+        let error = |value: serde_json::Value| value["error"].as_str().unwrap().to_owned();
+        assert!(error(call(b"{")).starts_with("invalid input"));
+        assert_eq!(error(call(&[0xff, 0xfe])), "input is not valid UTF-8");
+        assert!(error(call(b"")).starts_with("invalid input"));
+        let null = unsafe { ff_eval_flag_json(std::ptr::null(), 4) };
+        let text = unsafe { CStr::from_ptr(null) }.to_str().unwrap().to_owned();
+        unsafe { ff_eval_string_free(null) };
+        assert_eq!(text, r#"{"error":"input pointer is null"}"#);
+        unsafe { ff_eval_string_free(std::ptr::null_mut()) };
+    }
+}
@@ -0,0 +1,213 @@
+//! OpenFeature provider adapter for the feature flag evaluator.
+//!
+//! [`FlagSetProvider`] resolves boolean flags from a [`FlagSet`] with the
+//! shape an OpenFeature provider has: an evaluation context made of a
+//! targeting key and attributes, and resolution details carrying the value,
+//! the variant and the [`Reason`].  The reasons already use OpenFeature's
+//! names, so nothing is translated on the way out.
+//!
+//! The crate does not depend on an OpenFeature SDK, so it neither pins an
+//! SDK version nor pulls an async runtime into hosts that only want the
+//! mapping.  A host implements the SDK's `FeatureProvider` trait by copying
+//! the SDK's context fields into an [`EvaluationContext`] and the returned
+//! [`ResolutionDetails`] back; each field has a counterpart of the same
+//! name.
+
+use ff_eval_core::{Context, EvalOptions, EvalResult, FlagSet, Reason, Timestamp, Value};
+use std::fmt;
+
+/// The OpenFeature evaluation context: the targeting key identifies the
+/// subject and is what bucketing builtins read as `userId`.
+#[derive(Debug, Clone, Default)]
+pub struct EvaluationContext {
+    pub targeting_key: Option<String>,
+    pub attributes: Context,
+}
+
+impl EvaluationContext {
+    pub fn new(targeting_key: &str) -> Self {
+        EvaluationContext {
+            targeting_key: Some(targeting_key.to_string()),
+            attributes: Context::new(),
+        }
+    }
+
+    pub fn with_attribute(mut self, name: &str, value: Value) -> Self {
+        self.attributes.insert(name.to_string(), value);
+        self
+    }
+
+    /// The rule context.  An explicit `userId` attribute wins over the
+    /// targeting key.
+    fn to_context(&self) -> Context {
+        let mut ctx = self.attributes.clone();
+        if let Some(key) = &self.targeting_key {
+            ctx.entry("userId".to_string())
+                .or_insert_with(|| Value::Str(key.clone()));
+        }
+        ctx
+    }
+}
+
+/// What a resolution returned and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolutionDetails<T> {
+    pub value: T,
+    /// Variant assigned by a `variant(...)` call in the matched rule.
+    pub variant: Option<String>,
+    pub reason: Reason,
+    /// Index of the matched rule, for the SDK's flag metadata.
+    pub matched_rule: Option<usize>,
+}
+
+/// OpenFeature error codes the adapter can return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    FlagNotFound,
+}
+
+/// A resolution that produced no value; the SDK falls back to the caller's
+/// default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvaluationError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl fmt::Display for EvaluationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for EvaluationError {}
+
+/// Resolves flags from a [`FlagSet`].
+pub struct FlagSetProvider {
+    set: FlagSet,
+}
+
+impl FlagSetProvider {
+    /// The provider name reported in the SDK's provider metadata.
+    pub const NAME: &'static str = "ff-eval";
+
+    pub fn new(set: FlagSet) -> Self {
+        FlagSetProvider { set }
+    }
+
+    pub fn flag_set(&self) -> &FlagSet {
+        &self.set
+    }
+
+    /// Swap in a reloaded set, e.g. after a config change.
+    pub fn replace(&mut self, set: FlagSet) {
+        self.set = set;
+    }
+
+    /// Resolve `flag_key`.  Rules that use `now` are skipped; see
+    /// [`FlagSetProvider::resolve_boolean_value_at`].
+    pub fn resolve_boolean_value(
+        &self,
+        flag_key: &str,
+        ctx: &EvaluationContext,
+    ) -> Result<ResolutionDetails<bool>, EvaluationError> {
+        details(flag_key, self.set.eval(flag_key, &ctx.to_context()))
+    }
+
+    /// [`FlagSetProvider::resolve_boolean_value`] with an injected `now`.
+    pub fn resolve_boolean_value_at(
+        &self,
+        flag_key: &str,
+        ctx: &EvaluationContext,
+        now: Timestamp,
+    ) -> Result<ResolutionDetails<bool>, EvaluationError> {
+        let mut opts = EvalOptions {
+            now: Some(now),
+            ..EvalOptions::default()
+        };
+        details(
+            flag_key,
+            self.set.eval_with(flag_key, &ctx.to_context(), &mut opts),
+        )
+    }
+}
+
+fn details(
+    flag_key: &str,
+    result: Option<EvalResult>,
+) -> Result<ResolutionDetails<bool>, EvaluationError> {
+    let result = result.ok_or_else(|| EvaluationError {
+        code: ErrorCode::FlagNotFound,
+        message: format!("flag `{flag_key}` is not in the flag set"),
+    })?;
+    Ok(ResolutionDetails {
+        value: result.enabled,
+        variant: result.variant,
+        reason: result.reason,
+        matched_rule: result.matched_rule,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ff_eval_core::{Flag, FlagState, Rule};
+
+    fn provider() -> FlagSetProvider {
+        let rule = |cond: &str| Rule {
+            cond: cond.to_string(),
+            then_value: true,
+            ..Default::default()
+        };
+        let paywall = Flag {
+            key: "paywall".to_string(),
+            rules: vec![rule("country == 'CA'"), rule("rollout(1.0)")],
+            ..Default::default()
+        };
+        let retired = Flag {
+            key: "retired".to_string(),
+            state: FlagState::Archived,
+            ..paywall.clone()
+        };
+        FlagSetProvider::new(FlagSet::load(vec![paywall, retired]).unwrap())
+    }
+
+    #[test]
+    fn resolves_with_openfeature_reasons() {
+        let provider = provider();
+        let canadian =
+            EvaluationContext::new("u1").with_attribute("country", Value::Str("CA".into()));
+        let details = provider
+            .resolve_boolean_value("paywall", &canadian)
+            .unwrap();
+        assert_eq!(
+            (details.value, details.reason, details.matched_rule),
+            (true, Reason::TargetingMatch, Some(0))
+        );
+        // The targeting key buckets like `userId`.
+        let details = provider
+            .resolve_boolean_value("paywall", &EvaluationContext::new("u2"))
+            .unwrap();
+        assert_eq!(details.matched_rule, Some(1));
+        let anonymous = EvaluationContext::default();
+        assert_eq!(
+            provider
+                .resolve_boolean_value("paywall", &anonymous)
+                .unwrap()
+                .matched_rule,
+            Some(1)
+        );
+        let details = provider
+            .resolve_boolean_value("retired", &canadian)
+            .unwrap();
+        assert_eq!((details.value, details.reason), (false, Reason::Disabled));
+    }
+
+    #[test]
+    fn unknown_flags_are_not_found() {
+        let err = provider()
+            .resolve_boolean_value("missing", &EvaluationContext::new("u1"))
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::FlagNotFound);
+    }
+}
@@ -1,6 +1,9 @@
 #!/bin/bash
 #
-# Run all test vectors through the compiled evaluator using wasmtime.
+# Run all test vectors through the compiled evaluator using wasmtime and
+# compare each output with its golden file in tests/vectors/expected/.
+# `cargo test -p ff_eval_wasi_app --test conformance` checks the native
+# build against the same files.
 #
 # Usage:
 #   ./scripts/run_vectors.sh
@@ -25,11 +28,18 @@ fi
 
 VECTORS_DIR="$(dirname "$0")/../tests/vectors"
 for vector in "$VECTORS_DIR"/*.json; do
-  echo "=== Running $(basename "$vector") ==="
-  if ! wasmtime "$WASM" < "$vector"; then
+  name="$(basename "$vector")"
+  echo "=== Running $name ==="
+  if ! output="$(wasmtime "$WASM" < "$vector")"; then
     echo "error: evaluator failed for $vector"
     exit 1
   fi
-  printf '\n'
+  printf '%s\n' "$output"
+  # Compare as JSON so key order and whitespace do not matter.
+  if ! python3 -c 'import json, sys; sys.exit(json.loads(sys.argv[1]) != json.load(open(sys.argv[2])))' \
+    "$output" "$VECTORS_DIR/expected/$name"; then
+    echo "error: $name does not match tests/vectors/expected/$name"
+    exit 1
+  fi
 done
-echo "All vectors executed successfully."
+echo "All vectors match their golden files."
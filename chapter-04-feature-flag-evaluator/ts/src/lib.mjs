@@ -28,14 +28,22 @@ function splitTopLevel(source, separator) {
   return -1;
 }
 
-export function rollout(flagKey, userId, probability) {
+function bucket(flagKey, userId) {
   const input = `${flagKey}:${userId}`;
   let hash = 0x811c9dc5;
   for (const byte of Buffer.from(input, 'utf8')) {
     hash ^= byte;
     hash = Math.imul(hash, 0x01000193) >>> 0;
   }
-  return hash / 4294967296 < probability;
+  return hash / 4294967296;
+}
+
+export function rollout(flagKey, userId, probability) {
+  return bucket(flagKey, userId) < probability;
+}
+
+export function bucketIndex(flagKey, userId, count) {
+  return Math.min(Math.floor(bucket(flagKey, userId) * count), count - 1);
 }
 
 function parseTerm(term, context, flagKey) {
@@ -47,6 +55,13 @@ function parseTerm(term, context, flagKey) {
     }
     return rollout(flagKey, typeof context.userId === 'string' ? context.userId : '', probability);
   }
+  if (trimmed.startsWith('bucket(') && trimmed.endsWith(')')) {
+    const count = Number(trimmed.slice('bucket('.length, -1).trim());
+    if (!Number.isInteger(count) || count <= 0) {
+      throw new Error('invalid bucket');
+    }
+    return bucketIndex(flagKey, typeof context.userId === 'string' ? context.userId : '', count);
+  }
   if (trimmed === 'true') return true;
   if (trimmed === 'false') return false;
   if ((trimmed.startsWith("'") && trimmed.endsWith("'")) || (trimmed.startsWith('"') && trimmed.endsWith('"'))) {
@@ -135,7 +150,30 @@ function evalExpr(flagKey, source, context) {
 }
 
 export function evalFlag(input) {
-  const { flag, context } = input;
+  const { flag, context, overrides = {} } = input;
+  const state = flag.state ?? 'enabled';
+  if (Object.hasOwn(overrides, flag.key)) {
+    // An override forces the value without evaluating any rule.
+    return {
+      key: flag.key,
+      enabled: overrides[flag.key],
+      matchedRule: null,
+      ...(state !== 'enabled' && { state }),
+      overridden: true,
+      reason: 'STATIC',
+    };
+  }
+  if (state !== 'enabled') {
+    // Disabled and archived flags keep their rules but never evaluate them.
+    return {
+      key: flag.key,
+      enabled: flag.default ?? false,
+      matchedRule: null,
+      state,
+      reason: 'DISABLED',
+    };
+  }
+  let errored = false;
   for (let index = 0; index < flag.rules.length; index += 1) {
     const rule = flag.rules[index];
     try {
@@ -144,15 +182,25 @@ export function evalFlag(input) {
           key: flag.key,
           enabled: rule.then,
           matchedRule: index,
+          reason: 'TARGETING_MATCH',
         };
       }
     } catch {
-      continue;
+      errored = true;
     }
   }
   return {
     key: flag.key,
     enabled: flag.default ?? false,
     matchedRule: null,
+    reason: errored ? 'ERROR' : 'DEFAULT',
   };
 }
+
+export function evalInput(input) {
+  // A `contexts` batch evaluates the flag once per context, in order.
+  if (Array.isArray(input.contexts)) {
+    return input.contexts.map((context) => evalFlag({ ...input, context }));
+  }
+  return evalFlag(input);
+}
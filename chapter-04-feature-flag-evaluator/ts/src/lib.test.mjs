@@ -2,7 +2,7 @@ import test from 'node:test';
 import assert from 'node:assert/strict';
 import { readFileSync } from 'node:fs';
 import { join } from 'node:path';
-import { evalFlag, rollout } from './lib.mjs';
+import { bucketIndex, evalFlag, evalInput, evalRuleExpr, rollout } from './lib.mjs';
 
 const root = join(import.meta.dirname, '..', '..');
 
@@ -12,21 +12,59 @@ function loadLab(name) {
 
 test('country rule wins before rollout', () => {
   const result = evalFlag(loadLab('lab1-country-match'));
-  assert.deepEqual(result, { key: 'paywall', enabled: true, matchedRule: 0 });
+  assert.deepEqual(result, { key: 'paywall', enabled: true, matchedRule: 0, reason: 'TARGETING_MATCH' });
 });
 
 test('rollout is deterministic for the sticky cohort', () => {
   assert.equal(rollout('paywall', 'u20', 0.2), true);
   const result = evalFlag(loadLab('lab2-rollout-match'));
-  assert.deepEqual(result, { key: 'paywall', enabled: true, matchedRule: 1 });
+  assert.deepEqual(result, { key: 'paywall', enabled: true, matchedRule: 1, reason: 'TARGETING_MATCH' });
+});
+
+test('bucket(n) returns the index of the user bucket', () => {
+  for (let i = 0; i < 200; i += 1) {
+    const userId = `user-${i}`;
+    const index = bucketIndex('layered', userId, 10);
+    assert.ok(index >= 0 && index < 10);
+    assert.equal(evalRuleExpr('layered', `bucket(10) == ${index}`, { userId }), true);
+  }
+  assert.throws(() => evalRuleExpr('layered', 'bucket(0) < 1', { userId: 'u1' }));
 });
 
 test('default fallback returns null matchedRule', () => {
   const result = evalFlag(loadLab('lab3-default-fallback'));
-  assert.deepEqual(result, { key: 'paywall', enabled: false, matchedRule: null });
+  assert.deepEqual(result, { key: 'paywall', enabled: false, matchedRule: null, reason: 'DEFAULT' });
 });
 
 test('rule language lab exercises in numeric and logical operators', () => {
   const result = evalFlag(loadLab('lab4-rule-language'));
-  assert.deepEqual(result, { key: 'checkout-rollout', enabled: true, matchedRule: 0 });
+  assert.deepEqual(result, { key: 'checkout-rollout', enabled: true, matchedRule: 0, reason: 'TARGETING_MATCH' });
+});
+
+test('disabled and archived flags return the default and report their state', () => {
+  const input = loadLab('lab1-country-match');
+  for (const state of ['disabled', 'archived']) {
+    const result = evalFlag({ ...input, flag: { ...input.flag, state } });
+    assert.deepEqual(result, { key: 'paywall', enabled: input.flag.default, matchedRule: null, state, reason: 'DISABLED' });
+  }
+});
+
+test('overrides force the value and are reported', () => {
+  const input = loadLab('lab1-country-match');
+  const result = evalFlag({ ...input, overrides: { paywall: false, other: true } });
+  assert.deepEqual(result, { key: 'paywall', enabled: false, matchedRule: null, overridden: true, reason: 'STATIC' });
+  assert.equal(evalFlag({ ...input, overrides: { other: false } }).overridden, undefined);
+});
+
+test('a rule that fails to evaluate is reported when no rule matches', () => {
+  const input = loadLab('lab3-default-fallback');
+  const flag = { ...input.flag, rules: [{ if: 'bucket(0) < 1', then: true }, ...input.flag.rules] };
+  assert.equal(evalFlag({ ...input, flag }).reason, 'ERROR');
+});
+
+test('contexts are evaluated as a batch in order', () => {
+  const { flag, context } = loadLab('lab1-country-match');
+  const fallback = loadLab('lab3-default-fallback').context;
+  const results = evalInput({ flag, contexts: [context, fallback] });
+  assert.deepEqual(results, [evalFlag({ flag, context }), evalFlag({ flag, context: fallback })]);
 });
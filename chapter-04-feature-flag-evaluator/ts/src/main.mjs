@@ -1,5 +1,5 @@
 import { stdin } from 'node:process';
-import { evalFlag } from './lib.mjs';
+import { evalInput } from './lib.mjs';
 
 async function readStdin() {
   const chunks = [];
@@ -10,4 +10,4 @@ async function readStdin() {
 }
 
 const input = JSON.parse(await readStdin());
-process.stdout.write(JSON.stringify(evalFlag(input)));
+process.stdout.write(JSON.stringify(evalInput(input)));
@@ -0,0 +1,761 @@
+//! JSON boundary of the feature flag evaluator.
+//!
+//! Deserializes the input document read by the WASI executable straight into
+//! core types (via the core's `serde` feature), evaluates it, and renders the
+//! output document.  The executable and the
+//! `uma` CLI both go through [`evaluate_document`] so they accept and produce
+//! exactly the same JSON.
+//!
+//! An input carries either one `context` or a `contexts` array.  A batch
+//! compiles the flag once and returns an array with one result per context,
+//! in input order, so a cohort simulation costs a single instantiation.
+//!
+//! Likewise it carries one `flag` or a `flags` array.  A flag set is
+//! evaluated with [`ff_eval_core::eval_all`] into a payload object mapping
+//! each flag key to its result, the document an SDK bootstraps a client
+//! from.
+//!
+//! With `"events": true` each result also carries the evaluation as a
+//! deterministic event list stamped with a logical clock, in the shape the
+//! Chapter 5 runtime records, so an orchestrator can persist lifecycle
+//! metadata for flag evaluations too.
+//!
+//! The flags may instead come from a flag set document on disk
+//! ([`load_flags_file`], the executable's `--flags` option), so slowly
+//! changing configuration is read from a WASI preopened directory while each
+//! request on stdin carries only the context.  [`evaluate_reader_with_flags`]
+//! then treats the loaded flags as the input's `flags`.
+//!
+//! With `"validate": true` nothing is evaluated: the flags are linted with
+//! [`ff_eval_core::validate_flag`] and compiled, and the output lists the
+//! problems found, so CI can gate a flag change without a context.
+
+use ff_eval_core::config::{self, ConfigError};
+use ff_eval_core::{
+    eval_all_with, trace_flag_with, validate_flag, CompileError, CompileErrorKind, CompiledFlag,
+    Context, EvalOptions, EvalResult, EvalTrace, Flag, FlagSet, Overrides, RuleOutcome, Timestamp,
+};
+use serde::de::Error as _;
+use serde::Deserialize;
+use std::fmt;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Input cap used when `FF_EVAL_MAX_INPUT_BYTES` is unset: 1 MiB.
+pub const DEFAULT_MAX_INPUT_BYTES: u64 = 1024 * 1024;
+
+/// Representation of the input JSON for serde deserialization.
+#[derive(Debug, Deserialize)]
+struct Input {
+    /// Exactly one of `flag` and `flags` must be present.
+    #[serde(default)]
+    flag: Option<Flag>,
+    #[serde(default)]
+    flags: Option<Vec<Flag>>,
+    /// Exactly one of `context` and `contexts` must be present.
+    #[serde(default)]
+    context: Option<Context>,
+    #[serde(default)]
+    contexts: Option<Vec<Context>>,
+    #[serde(default)]
+    explain: bool,
+    /// Add the evaluation's event list to each result.
+    #[serde(default)]
+    events: bool,
+    /// Lint the flags instead of evaluating them; the context is not needed.
+    #[serde(default)]
+    validate: bool,
+    /// Value of the `now` builtin, as an RFC 3339 timestamp.
+    #[serde(default)]
+    now: Option<Timestamp>,
+    /// Flag values forced by key; see `ff_eval_core::EvalOptions::overrides`.
+    #[serde(default)]
+    overrides: Overrides,
+}
+
+/// Why an input document could not be evaluated.
+#[derive(Debug)]
+pub enum DocumentError {
+    /// The input is not valid JSON or does not match the input schema,
+    /// including an unknown `flag.hash` or a malformed timestamp.
+    Parse(serde_json::Error),
+    /// The input is longer than the configured cap.  Reading stops at the
+    /// first byte past the cap, so nothing larger is ever buffered.
+    TooLarge { limit: u64 },
+    /// A rule could not be compiled, e.g. an invalid `~=` pattern or a call
+    /// to an unknown builtin.
+    Compile(CompileError),
+    /// The flag set file could not be read.
+    FlagsUnreadable { path: String, error: io::Error },
+    /// The flag set file is not a valid flag set document.
+    FlagsInvalid { path: String, error: ConfigError },
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentError::Parse(err) => write!(f, "invalid input: {err}"),
+            DocumentError::TooLarge { limit } => {
+                write!(f, "input exceeds the {limit}-byte limit")
+            }
+            DocumentError::Compile(err) => write!(f, "{err}"),
+            DocumentError::FlagsUnreadable { path, error } => {
+                write!(f, "cannot read flags file {path}: {error}")
+            }
+            DocumentError::FlagsInvalid { path, error } => {
+                write!(f, "invalid flags file {path}: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DocumentError {}
+
+impl DocumentError {
+    /// Stable name of the error class: `PARSE`, `INPUT_TOO_LARGE`,
+    /// `COMPILE` or `FLAGS_FILE`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            DocumentError::Parse(_) => "PARSE",
+            DocumentError::TooLarge { .. } => "INPUT_TOO_LARGE",
+            DocumentError::Compile(_) => "COMPILE",
+            DocumentError::FlagsUnreadable { .. } | DocumentError::FlagsInvalid { .. } => {
+                "FLAGS_FILE"
+            }
+        }
+    }
+
+    /// Exit status of the WASI executable for this class.  1 is left to
+    /// failures outside the evaluator, such as a trap in the runtime.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            DocumentError::Parse(_) => 2,
+            DocumentError::TooLarge { .. } => 3,
+            DocumentError::Compile(_) => 4,
+            DocumentError::FlagsUnreadable { .. } | DocumentError::FlagsInvalid { .. } => 6,
+        }
+    }
+
+    /// The error document, `{"error": {"code": ..., "message": ...}}`, with
+    /// the position of a parse error, the cap that was exceeded, the rule
+    /// that failed to compile or the flag set file at fault.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut error = serde_json::json!({ "code": self.code(), "message": self.to_string() });
+        match self {
+            // Errors raised after parsing, e.g. a missing `context`, have no
+            // position.
+            DocumentError::Parse(err) if err.line() > 0 => {
+                error["line"] = err.line().into();
+                error["column"] = err.column().into();
+            }
+            DocumentError::Parse(_) => {}
+            DocumentError::TooLarge { limit } => error["limitBytes"] = (*limit).into(),
+            DocumentError::Compile(err) => error["rule"] = err.rule.into(),
+            DocumentError::FlagsUnreadable { path, .. } => error["path"] = path.as_str().into(),
+            DocumentError::FlagsInvalid {
+                path,
+                error: config,
+            } => {
+                error["path"] = path.as_str().into();
+                error["diagnostics"] = config
+                    .diagnostics
+                    .iter()
+                    .map(|d| serde_json::Value::from(d.to_string()))
+                    .collect();
+            }
+        }
+        serde_json::json!({ "error": error })
+    }
+}
+
+/// Evaluate one input document and return the output document.
+pub fn evaluate_document(input: &str) -> Result<serde_json::Value, DocumentError> {
+    let parsed: Input = serde_json::from_str(input).map_err(DocumentError::Parse)?;
+    evaluate_input(parsed)
+}
+
+/// Evaluate a flag object for a context object, each passed as its own JSON
+/// text, and return the output document.  This backs the component's
+/// `evaluate` export (`wit/ff-eval.wit`), which has no room for `explain`,
+/// `now` or `overrides`.
+pub fn evaluate_parts(flag: &str, context: &str) -> Result<serde_json::Value, DocumentError> {
+    evaluate_input(Input {
+        flag: Some(serde_json::from_str(flag).map_err(DocumentError::Parse)?),
+        flags: None,
+        context: Some(serde_json::from_str(context).map_err(DocumentError::Parse)?),
+        contexts: None,
+        explain: false,
+        events: false,
+        validate: false,
+        now: None,
+        overrides: Overrides::default(),
+    })
+}
+
+/// Like [`evaluate_document`], parsing incrementally from `reader` and
+/// failing with [`DocumentError::TooLarge`] as soon as more than `limit`
+/// bytes have been read.
+pub fn evaluate_reader(reader: impl Read, limit: u64) -> Result<serde_json::Value, DocumentError> {
+    evaluate_input(read_input(reader, limit)?)
+}
+
+/// Like [`evaluate_reader`] for an input that carries no `flag` or `flags`
+/// of its own: `flags`, typically from [`load_flags_file`], are evaluated
+/// as its flag set.
+pub fn evaluate_reader_with_flags(
+    reader: impl Read,
+    limit: u64,
+    flags: Vec<Flag>,
+) -> Result<serde_json::Value, DocumentError> {
+    let mut parsed = read_input(reader, limit)?;
+    if parsed.flag.is_some() || parsed.flags.is_some() {
+        return Err(DocumentError::Parse(serde_json::Error::custom(
+            "`flag` and `flags` are not allowed when the flags come from a file",
+        )));
+    }
+    parsed.flags = Some(flags);
+    evaluate_input(parsed)
+}
+
+/// Read a flag set document, `{"flags": [...]}` as described by
+/// `contracts/flagset.schema.json`, with the core's config loader.
+pub fn load_flags_file(path: &Path) -> Result<Vec<Flag>, DocumentError> {
+    let display = path.display().to_string();
+    let text = std::fs::read_to_string(path).map_err(|error| DocumentError::FlagsUnreadable {
+        path: display.clone(),
+        error,
+    })?;
+    config::parse_json(&text).map_err(|error| DocumentError::FlagsInvalid {
+        path: display,
+        error,
+    })
+}
+
+fn read_input(reader: impl Read, limit: u64) -> Result<Input, DocumentError> {
+    let mut reader = LimitedReader {
+        inner: reader,
+        remaining: limit,
+        exceeded: false,
+    };
+    let parsed: Result<Input, _> = serde_json::from_reader(&mut reader);
+    if reader.exceeded {
+        return Err(DocumentError::TooLarge { limit });
+    }
+    parsed.map_err(DocumentError::Parse)
+}
+
+/// The input cap from `FF_EVAL_MAX_INPUT_BYTES`, falling back to
+/// [`DEFAULT_MAX_INPUT_BYTES`] when it is unset or not a number.
+pub fn max_input_bytes() -> u64 {
+    std::env::var("FF_EVAL_MAX_INPUT_BYTES")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_MAX_INPUT_BYTES)
+}
+
+/// A reader that errors, and remembers it did, once `remaining` runs out.
+struct LimitedReader<R> {
+    inner: R,
+    remaining: u64,
+    exceeded: bool,
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Allow one byte past the cap so an input of exactly `limit` bytes
+        // still reaches end of file.
+        let max = buf
+            .len()
+            .min(usize::try_from(self.remaining + 1).unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..max])?;
+        if n as u64 > self.remaining {
+            self.exceeded = true;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "input too large",
+            ));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// What an input asks to evaluate.
+enum Target {
+    Flag(CompiledFlag),
+    Set(FlagSet),
+}
+
+/// The input fields that apply to every flag and context.
+struct Options {
+    explain: bool,
+    events: bool,
+    now: Option<Timestamp>,
+    overrides: Overrides,
+}
+
+fn evaluate_input(parsed: Input) -> Result<serde_json::Value, DocumentError> {
+    if parsed.validate {
+        return match (&parsed.flag, &parsed.flags) {
+            (Some(flag), None) => Ok(validate_json(std::slice::from_ref(flag))),
+            (None, Some(flags)) => Ok(validate_json(flags)),
+            _ => Err(exactly_one_of("flag", "flags")),
+        };
+    }
+    // Compile the flags so invalid `~=` patterns are reported instead of
+    // silently skipping their rule.
+    let target = match (parsed.flag, parsed.flags) {
+        (Some(flag), None) => {
+            Target::Flag(CompiledFlag::compile(flag).map_err(DocumentError::Compile)?)
+        }
+        (None, Some(flags)) => Target::Set(FlagSet::load(flags).map_err(DocumentError::Compile)?),
+        _ => return Err(exactly_one_of("flag", "flags")),
+    };
+    let options = Options {
+        explain: parsed.explain,
+        events: parsed.events,
+        now: parsed.now,
+        overrides: parsed.overrides,
+    };
+    let evaluate = |ctx| match &target {
+        Target::Flag(flag) => evaluate_one(flag, ctx, &options),
+        Target::Set(set) => evaluate_payload(set, ctx, &options),
+    };
+    match (parsed.context, parsed.contexts) {
+        (Some(ctx), None) => Ok(evaluate(ctx)),
+        (None, Some(contexts)) => Ok(contexts.into_iter().map(evaluate).collect()),
+        _ => Err(exactly_one_of("context", "contexts")),
+    }
+}
+
+fn exactly_one_of(a: &str, b: &str) -> DocumentError {
+    DocumentError::Parse(serde_json::Error::custom(format!(
+        "expected exactly one of `{a}` and `{b}`"
+    )))
+}
+
+/// The output document for one flag and one context.
+fn evaluate_one(flag: &CompiledFlag, ctx: Context, options: &Options) -> serde_json::Value {
+    let mut opts = options.eval_options();
+    // Evaluate the flag, recording a trace when the output needs one.
+    let (result, trace): (EvalResult, Option<EvalTrace>) = if options.traced() {
+        let trace = flag.trace_with(&ctx, &mut opts);
+        (trace.result.clone(), Some(trace))
+    } else {
+        (flag.eval_with(&ctx, &mut opts), None)
+    };
+    result_json(&result, trace.as_ref(), options)
+}
+
+/// The payload for one context: every flag in the set, keyed by flag key.
+fn evaluate_payload(set: &FlagSet, ctx: Context, options: &Options) -> serde_json::Value {
+    let results = eval_all_with(set, &ctx, &mut options.eval_options());
+    let payload = results.into_iter().map(|result| {
+        let flag = set
+            .get(&result.key)
+            .expect("eval_all returns the set's flags");
+        // The set evaluates without traces, so a trace re-runs the flag.
+        let trace = options
+            .traced()
+            .then(|| trace_flag_with(flag, &ctx, &mut options.eval_options()));
+        (
+            result.key.clone(),
+            result_json(&result, trace.as_ref(), options),
+        )
+    });
+    serde_json::Value::Object(payload.collect())
+}
+
+/// The validation report: every lint warning, plus the pattern that stops
+/// a flag from compiling, flag by flag.
+fn validate_json(flags: &[Flag]) -> serde_json::Value {
+    let mut diagnostics = Vec::new();
+    for flag in flags {
+        let mut found: Vec<(usize, &str, String)> = validate_flag(flag)
+            .into_iter()
+            .map(|warning| (warning.rule, warning.kind.as_str(), warning.message))
+            .collect();
+        // An unknown builtin is already among the lint warnings.
+        match CompiledFlag::compile(flag.clone()) {
+            Err(err) if err.kind == CompileErrorKind::InvalidPattern => {
+                let message = format!("invalid pattern '{}': {}", err.pattern, err.message);
+                found.push((err.rule, "invalid_pattern", message));
+            }
+            _ => {}
+        }
+        diagnostics.extend(found.into_iter().map(|(rule, kind, message)| {
+            serde_json::json!({ "flag": flag.key, "rule": rule, "kind": kind, "message": message })
+        }));
+    }
+    serde_json::json!({ "valid": diagnostics.is_empty(), "diagnostics": diagnostics })
+}
+
+/// The result serializes to the output document; only the trace and the
+/// events are added, both rendered from the trace.
+fn result_json(
+    result: &EvalResult,
+    trace: Option<&EvalTrace>,
+    options: &Options,
+) -> serde_json::Value {
+    let mut output = serde_json::to_value(result).expect("EvalResult should serialize");
+    if let Some(trace) = trace {
+        if options.explain {
+            output["trace"] = trace_json(trace);
+        }
+        if options.events {
+            output["events"] = events_json(trace);
+        }
+    }
+    output
+}
+
+impl Options {
+    /// Whether results need an [`EvalTrace`].
+    fn traced(&self) -> bool {
+        self.explain || self.events
+    }
+
+    /// The clock and overrides to evaluate with.
+    fn eval_options(&self) -> EvalOptions<'_> {
+        EvalOptions {
+            now: self.now,
+            overrides: Some(&self.overrides),
+            ..EvalOptions::default()
+        }
+    }
+}
+
+/// Render an evaluation as the `events` array of the output document:
+/// `parse`, one `rule_evaluated` per rule that was evaluated, then `matched`,
+/// `default` or `overridden`.  Event `t` is the logical clock, counting
+/// events from 0, so the list depends only on the input.
+fn events_json(trace: &EvalTrace) -> serde_json::Value {
+    let result = &trace.result;
+    let mut events = vec![(
+        "parse",
+        serde_json::json!({ "flag": result.key, "rules": trace.rules.len() }),
+    )];
+    for rule in &trace.rules {
+        let outcome = match rule.outcome {
+            RuleOutcome::Matched => "matched",
+            RuleOutcome::NotMatched => "not_matched",
+            RuleOutcome::Errored => "error",
+            RuleOutcome::Skipped => continue,
+        };
+        events.push((
+            "rule_evaluated",
+            serde_json::json!({ "rule": rule.index, "outcome": outcome }),
+        ));
+    }
+    events.push(match result.matched_rule {
+        Some(rule) => (
+            "matched",
+            serde_json::json!({ "rule": rule, "enabled": result.enabled }),
+        ),
+        None if result.overridden => (
+            "overridden",
+            serde_json::json!({ "enabled": result.enabled }),
+        ),
+        None => (
+            "default",
+            serde_json::json!({ "enabled": result.enabled, "reason": result.reason.as_str() }),
+        ),
+    });
+    events
+        .into_iter()
+        .enumerate()
+        .map(|(t, (type_, data))| serde_json::json!({ "t": t.to_string(), "type": type_, "data": data }))
+        .collect()
+}
+
+/// Render an evaluation trace as the `trace` array of the output document.
+fn trace_json(trace: &EvalTrace) -> serde_json::Value {
+    let rules: Vec<serde_json::Value> = trace
+        .rules
+        .iter()
+        .map(|rule| {
+            let outcome = match rule.outcome {
+                RuleOutcome::Matched => "matched",
+                RuleOutcome::NotMatched => "not_matched",
+                RuleOutcome::Errored => "error",
+                RuleOutcome::Skipped => "skipped",
+            };
+            let steps: Vec<serde_json::Value> = rule
+                .steps
+                .iter()
+                .map(|step| serde_json::json!({ "expr": step.expr, "value": step.value }))
+                .collect();
+            let mut entry = serde_json::json!({
+                "rule": rule.index,
+                "if": rule.cond,
+                "outcome": outcome,
+                "steps": steps,
+            });
+            if let Some(reason) = rule.skip_reason {
+                entry["reason"] = serde_json::Value::String(reason.as_str().to_string());
+            }
+            entry
+        })
+        .collect();
+    serde_json::Value::Array(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reader_input_is_capped() {
+        let input = include_str!("../../tests/vectors/t1.json");
+        let len = input.len() as u64;
+        let output = evaluate_reader(input.as_bytes(), len).unwrap();
+        assert_eq!(output, evaluate_document(input).unwrap());
+        assert!(matches!(
+            evaluate_reader(input.as_bytes(), len - 1),
+            Err(DocumentError::TooLarge { limit }) if limit == len - 1
+        ));
+        assert!(matches!(
+            evaluate_reader(&b"{"[..], len),
+            Err(DocumentError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn parts_evaluate_like_the_document() {
+        let input = include_str!("../../tests/vectors/t1.json");
+        let document: serde_json::Value = serde_json::from_str(input).unwrap();
+        let output = evaluate_parts(
+            &document["flag"].to_string(),
+            &document["context"].to_string(),
+        )
+        .unwrap();
+        assert_eq!(output, evaluate_document(input).unwrap());
+        assert!(matches!(
+            evaluate_parts("{}", "{}"),
+            Err(DocumentError::Parse(_))
+        ));
+        assert!(matches!(
+            evaluate_parts(&document["flag"].to_string(), "[]"),
+            Err(DocumentError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn contexts_evaluate_in_one_batch() {
+        let input = include_str!("../../tests/vectors/t1.json");
+        let mut document: serde_json::Value = serde_json::from_str(input).unwrap();
+        let ctx = document.as_object_mut().unwrap().remove("context").unwrap();
+        let mut us = ctx.clone();
+        us["country"] = serde_json::json!("US");
+        document["contexts"] = serde_json::json!([ctx, us, ctx]);
+        let output = evaluate_document(&document.to_string()).unwrap();
+        let single = evaluate_document(input).unwrap();
+        let results = output.as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!((&results[0], &results[2]), (&single, &single));
+        assert_ne!(results[1]["matchedRule"], 0);
+        let evaluate = |document: &serde_json::Value| evaluate_document(&document.to_string());
+        document["contexts"] = serde_json::json!([]);
+        assert_eq!(evaluate(&document).unwrap(), serde_json::json!([]));
+        // A document must carry exactly one of the two.
+        document["context"] = ctx;
+        assert!(matches!(evaluate(&document), Err(DocumentError::Parse(_))));
+        document = serde_json::json!({ "flag": document["flag"] });
+        assert!(matches!(evaluate(&document), Err(DocumentError::Parse(_))));
+    }
+
+    #[test]
+    fn flags_evaluate_to_a_payload() {
+        let input = include_str!("../../tests/vectors/t1.json");
+        let mut document: serde_json::Value = serde_json::from_str(input).unwrap();
+        let flag = document.as_object_mut().unwrap().remove("flag").unwrap();
+        let mut beta = flag.clone();
+        beta["key"] = serde_json::json!("beta");
+        beta["rules"] = serde_json::json!([{ "if": "country == 'US'", "then": true }]);
+        document["flags"] = serde_json::json!([flag, beta]);
+        document["overrides"] = serde_json::json!({ "beta": true });
+        let payload = evaluate_document(&document.to_string()).unwrap();
+        let payload = payload.as_object().unwrap();
+        assert_eq!(payload.keys().collect::<Vec<_>>(), ["beta", "paywall"]);
+        assert_eq!(payload["paywall"]["matchedRule"], 0);
+        assert_eq!(payload["beta"]["overridden"], true);
+        // Every result carries the fingerprint of the set.
+        let fingerprint = &payload["paywall"]["flagsetFingerprint"];
+        assert!(fingerprint.is_string());
+        assert_eq!(&payload["beta"]["flagsetFingerprint"], fingerprint);
+
+        document["explain"] = serde_json::json!(true);
+        let payload = evaluate_document(&document.to_string()).unwrap();
+        assert_eq!(payload["paywall"]["trace"][0]["outcome"], "matched");
+        assert_eq!(payload["beta"]["trace"][0]["reason"], "overridden");
+        document["flag"] = flag;
+        assert!(matches!(
+            evaluate_document(&document.to_string()),
+            Err(DocumentError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn errors_render_as_documents() {
+        let error = evaluate_document("{\n  \"flag\": 1\n}").unwrap_err();
+        assert_eq!(error.exit_code(), 2);
+        let document = error.to_json();
+        assert_eq!(document["error"]["code"], "PARSE");
+        assert_eq!(document["error"]["line"], 2);
+        assert!(document["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid input"));
+
+        let too_large = DocumentError::TooLarge { limit: 16 }.to_json();
+        assert_eq!(
+            too_large,
+            serde_json::json!({ "error": {
+                "code": "INPUT_TOO_LARGE",
+                "message": "input exceeds the 16-byte limit",
+                "limitBytes": 16,
+            }})
+        );
+
+        let flag = r#"{"key":"k","rules":[{"if":"true","then":true},{"if":"x ~= '('","then":true}],"default":false}"#;
+        let error = evaluate_parts(flag, "{}").unwrap_err();
+        assert_eq!((error.code(), error.exit_code()), ("COMPILE", 4));
+        assert_eq!(error.to_json()["error"]["rule"], 1);
+    }
+
+    #[test]
+    fn validate_reports_diagnostics_without_a_context() {
+        let input = include_str!("../../tests/vectors/t1.json");
+        let mut document: serde_json::Value = serde_json::from_str(input).unwrap();
+        let flag = document["flag"].clone();
+        let mut broken = flag.clone();
+        broken["key"] = serde_json::json!("broken");
+        broken["rules"] = serde_json::json!([
+            { "if": "true", "then": true },
+            { "if": "geo(lat) < 5", "then": true },
+        ]);
+        let validate = |flags| {
+            let document = serde_json::json!({ "flags": flags, "validate": true });
+            evaluate_document(&document.to_string()).unwrap()
+        };
+        assert_eq!(
+            validate(serde_json::json!([flag])),
+            serde_json::json!({ "valid": true, "diagnostics": [] })
+        );
+        let report = validate(serde_json::json!([flag, broken]));
+        assert_eq!(report["valid"], false);
+        let kinds: Vec<_> = report["diagnostics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| {
+                (
+                    d["flag"].as_str().unwrap(),
+                    d["rule"].as_u64().unwrap(),
+                    d["kind"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("broken", 1, "unknown_builtin"),
+                ("broken", 1, "unreachable")
+            ]
+        );
+
+        // A context is allowed but ignored.
+        document["validate"] = serde_json::json!(true);
+        let report = evaluate_document(&document.to_string()).unwrap();
+        assert_eq!(report["valid"], true);
+        assert!(report.get("enabled").is_none());
+    }
+
+    #[test]
+    fn events_record_the_evaluation() {
+        let input = include_str!("../../tests/vectors/t2.json");
+        let mut document: serde_json::Value = serde_json::from_str(input).unwrap();
+        document["events"] = serde_json::json!(true);
+        let output = evaluate_document(&document.to_string()).unwrap();
+        let plain = evaluate_document(input).unwrap();
+        assert_eq!(output["enabled"], plain["enabled"]);
+        assert!(output.get("trace").is_none());
+        let events = output["events"].as_array().unwrap();
+        let types: Vec<_> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+        assert_eq!(
+            types,
+            ["parse", "rule_evaluated", "rule_evaluated", "matched"]
+        );
+        let clock: Vec<_> = events.iter().map(|e| e["t"].as_str().unwrap()).collect();
+        assert_eq!(clock, ["0", "1", "2", "3"]);
+        assert_eq!(
+            events[1]["data"],
+            serde_json::json!({ "rule": 0, "outcome": "not_matched" })
+        );
+        // The same input always yields the same events.
+        assert_eq!(evaluate_document(&document.to_string()).unwrap(), output);
+
+        document["overrides"] = serde_json::json!({ "paywall": false });
+        let output = evaluate_document(&document.to_string()).unwrap();
+        assert_eq!(output["events"][1]["type"], "overridden");
+    }
+
+    #[test]
+    fn overrides_force_the_value() {
+        let input = include_str!("../../tests/vectors/t1.json");
+        let mut document: serde_json::Value = serde_json::from_str(input).unwrap();
+        document["overrides"] = serde_json::json!({ "paywall": false, "other": true });
+        document["explain"] = serde_json::json!(true);
+        let output = evaluate_document(&document.to_string()).unwrap();
+        assert_eq!(output["enabled"], false);
+        assert_eq!(output["overridden"], true);
+        assert!(output["matchedRule"].is_null());
+        assert_eq!(output["reason"], "STATIC");
+        assert_eq!(output["trace"][0]["reason"], "overridden");
+        let plain = evaluate_document(input).unwrap();
+        assert!(plain.get("overridden").is_none());
+    }
+
+    #[test]
+    fn flags_can_come_from_a_file() {
+        let input = include_str!("../../tests/vectors/t1.json");
+        let mut document: serde_json::Value = serde_json::from_str(input).unwrap();
+        let flag = document.as_object_mut().unwrap().remove("flag").unwrap();
+        let path = std::env::temp_dir().join(format!("ff-eval-flags-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            serde_json::json!({ "flags": [flag.clone()] }).to_string(),
+        )
+        .unwrap();
+        let flags = load_flags_file(&path).unwrap();
+        let output = evaluate_reader_with_flags(
+            document.to_string().as_bytes(),
+            DEFAULT_MAX_INPUT_BYTES,
+            flags.clone(),
+        );
+        document["flags"] = serde_json::json!([flag]);
+        assert_eq!(
+            output.unwrap(),
+            evaluate_document(&document.to_string()).unwrap()
+        );
+        let err = evaluate_reader_with_flags(
+            document.to_string().as_bytes(),
+            DEFAULT_MAX_INPUT_BYTES,
+            flags,
+        );
+        assert!(matches!(err, Err(DocumentError::Parse(_))));
+
+        std::fs::write(
+            &path,
+            r#"{"flags": [{"key": "a", "rules": [], "default": 1}]}"#,
+        )
+        .unwrap();
+        let err = load_flags_file(&path).unwrap_err();
+        assert_eq!((err.code(), err.exit_code()), ("FLAGS_FILE", 6));
+        assert!(err.to_json()["error"]["diagnostics"][0].is_string());
+        std::fs::remove_file(&path).unwrap();
+        let err = load_flags_file(&path).unwrap_err();
+        assert!(matches!(err, DocumentError::FlagsUnreadable { .. }));
+        assert_eq!(err.to_json()["error"]["path"], path.display().to_string());
+    }
+}
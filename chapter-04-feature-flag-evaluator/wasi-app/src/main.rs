@@ -2,88 +2,85 @@
 //!
 //! This binary reads a single UTF‑8 JSON document from standard input, evaluates the
 //! contained flag against the provided context using the core library, and writes a
-//! single UTF‑8 JSON document to standard output.  On parse error it exits with
-//! code 1; on success it exits with code 0.
-
-use ff_eval_core::{Context, EvalResult, Flag, Rule, Value};
-use serde::Deserialize;
-use std::collections::HashMap;
-use std::io::{Read, Write};
-
-/// Representation of the input JSON for serde deserialization.
-#[derive(Debug, Deserialize)]
-struct Input {
-    flag: FlagJson,
-    context: HashMap<String, serde_json::Value>,
-}
+//! single UTF‑8 JSON document to standard output.  On success it exits with
+//! code 0.  On failure it writes an error document such as
+//! `{"error": {"code": "PARSE", "message": "..."}}` to standard error and
+//! exits with a code per error class: 2 for `PARSE`, 3 for
+//! `INPUT_TOO_LARGE`, 4 for `COMPILE`, 5 for `OUTPUT` when standard
+//! output cannot be written, 6 for `FLAGS_FILE` and 7 for `USAGE`.
+//!
+//! Setting `"explain": true` in the input adds a per-rule `trace` array to the
+//! output so targeting rules can be debugged.
+//!
+//! With `--flags <path>` the flags are read from a flag set document at
+//! `path`, typically inside a directory the runtime preopened (e.g.
+//! `wasmtime run --dir ./config::/ ... --flags /flags.json`), and the input
+//! on standard input carries only the context and options.
+//!
+//! Input is parsed as it is read and capped at `FF_EVAL_MAX_INPUT_BYTES`
+//! (1 MiB by default); a larger input fails fast with `INPUT_TOO_LARGE`.
 
-#[derive(Debug, Deserialize)]
-struct FlagJson {
-    key: String,
-    rules: Vec<RuleJson>,
-    #[serde(default)]
-    default: bool,
-}
+use ff_eval_wasi_app::{
+    evaluate_reader, evaluate_reader_with_flags, load_flags_file, max_input_bytes,
+};
+use std::io::Write;
+use std::path::PathBuf;
 
-#[derive(Debug, Deserialize)]
-struct RuleJson {
-    #[serde(rename = "if")]
-    cond: String,
-    #[serde(rename = "then")]
-    then_value: bool,
-}
+/// Exit status when the output document cannot be written.
+const EXIT_OUTPUT: i32 = 5;
+/// Exit status for unrecognised command-line arguments.
+const EXIT_USAGE: i32 = 7;
 
 fn main() {
-    // Read all input from stdin into a string.
-    let mut input = String::new();
-    if let Err(_) = std::io::stdin().read_to_string(&mut input) {
-        std::process::exit(1);
-    }
-    // Deserialize the JSON input.  On failure, exit with status 1.
-    let parsed: Input = match serde_json::from_str(&input) {
-        Ok(val) => val,
-        Err(_) => {
-            std::process::exit(1);
-        }
+    let flags_path = match parse_args(std::env::args().skip(1)) {
+        Ok(path) => path,
+        Err(message) => fail("USAGE", message, EXIT_USAGE),
     };
-    // Convert to core types.
-    let flag = Flag {
-        key: parsed.flag.key,
-        rules: parsed
-            .flag
-            .rules
-            .into_iter()
-            .map(|r| Rule {
-                cond: r.cond,
-                then_value: r.then_value,
-            })
-            .collect(),
-        default: parsed.flag.default,
+    // Parse stdin incrementally and report any failure as an error document.
+    let stdin = std::io::stdin().lock();
+    let result = match flags_path {
+        Some(path) => load_flags_file(&path)
+            .and_then(|flags| evaluate_reader_with_flags(stdin, max_input_bytes(), flags)),
+        None => evaluate_reader(stdin, max_input_bytes()),
+    };
+    let output = match result {
+        Ok(output) => output,
+        Err(err) => {
+            eprintln!("{}", err.to_json());
+            std::process::exit(err.exit_code());
+        }
     };
-    let mut ctx: Context = Context::new();
-    for (k, v) in parsed.context.into_iter() {
-        let value = match v {
-            serde_json::Value::String(s) => Value::Str(s),
-            serde_json::Value::Number(n) => {
-                // Convert numbers to f64; JSON numbers may not always fit in f64 but this is sufficient for this example.
-                Value::Num(n.as_f64().unwrap_or(0.0))
-            }
-            serde_json::Value::Bool(b) => Value::Bool(b),
-            _ => Value::Null,
-        };
-        ctx.insert(k, value);
-    }
-    // Evaluate the flag.
-    let result: EvalResult = ff_eval_core::eval_flag(&flag, &ctx);
-    // Construct the output JSON.
-    let output = serde_json::json!({
-        "key": result.key,
-        "enabled": result.enabled,
-        "matchedRule": result.matched_rule.map(|i| i as i64),
-    });
     // Write the JSON to stdout.
-    if let Err(_) = std::io::stdout().write_all(output.to_string().as_bytes()) {
-        std::process::exit(1);
+    if let Err(err) = std::io::stdout().write_all(output.to_string().as_bytes()) {
+        fail("OUTPUT", err.to_string(), EXIT_OUTPUT);
     }
     std::process::exit(0);
-}
\ No newline at end of file
+}
+
+/// The `--flags` path, if given; either `--flags <path>` or `--flags=<path>`.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<PathBuf>, String> {
+    let mut flags = None;
+    while let Some(arg) = args.next() {
+        let path = match arg.strip_prefix("--flags=") {
+            Some(path) => path.to_owned(),
+            None if arg == "--flags" => args.next().ok_or("--flags needs a path")?,
+            None => {
+                return Err(format!(
+                    "unexpected argument `{arg}`; usage: [--flags <path>]"
+                ))
+            }
+        };
+        if flags.replace(PathBuf::from(path)).is_some() {
+            return Err("--flags given more than once".to_owned());
+        }
+    }
+    Ok(flags)
+}
+
+fn fail(code: &str, message: String, exit_code: i32) -> ! {
+    eprintln!(
+        "{}",
+        serde_json::json!({ "error": { "code": code, "message": message } })
+    );
+    std::process::exit(exit_code);
+}
@@ -0,0 +1,98 @@
+//! Golden-file conformance suite.
+//!
+//! Every `tests/vectors/<name>.json` input has its expected output document
+//! in `tests/vectors/expected/<name>.json`.  The native build is checked by
+//! calling [`evaluate_document`] directly; the WASI build is checked by
+//! running the compiled module under `wasmtime`, when both are available, so
+//! the two paths cannot drift apart unnoticed.  `scripts/run_vectors.sh`
+//! runs the same comparison for the WASI build alone.
+//!
+//! After an intended output change, rewrite the expected files with
+//! `FF_EVAL_BLESS=1 cargo test -p ff_eval_wasi_app --test conformance`.
+
+use ff_eval_wasi_app::evaluate_document;
+use serde_json::Value;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+fn chapter_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("..")
+}
+
+/// Every vector as `(name, input, expected output path)`, sorted by name.
+fn vectors() -> Vec<(String, String, PathBuf)> {
+    let dir = chapter_root().join("tests/vectors");
+    let mut vectors: Vec<_> = std::fs::read_dir(&dir)
+        .expect("tests/vectors exists")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            let input = std::fs::read_to_string(&path).unwrap();
+            let expected = dir.join("expected").join(format!("{name}.json"));
+            (name, input, expected)
+        })
+        .collect();
+    vectors.sort();
+    assert!(!vectors.is_empty(), "no vectors in {}", dir.display());
+    vectors
+}
+
+fn expected(path: &Path) -> Value {
+    let text = std::fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("{}: {err}; bless to create it", path.display()));
+    serde_json::from_str(&text).unwrap()
+}
+
+#[test]
+fn native_build_matches_golden_files() {
+    let bless = std::env::var_os("FF_EVAL_BLESS").is_some();
+    for (name, input, path) in vectors() {
+        let output = evaluate_document(&input).unwrap_or_else(|err| panic!("{name}: {err}"));
+        if bless {
+            let text = serde_json::to_string_pretty(&output).unwrap() + "\n";
+            std::fs::write(&path, text).unwrap();
+            continue;
+        }
+        assert_eq!(
+            output,
+            expected(&path),
+            "{name} diverges from its golden file"
+        );
+    }
+}
+
+#[test]
+fn wasm_build_matches_golden_files() {
+    let module = chapter_root().join("target/wasm32-wasip1/release/ff_eval_wasi_app.wasm");
+    if !module.exists() {
+        eprintln!("skipped: build {} to check the WASI path", module.display());
+        return;
+    }
+    for (name, input, path) in vectors() {
+        let child = Command::new("wasmtime")
+            .arg(&module)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn();
+        let Ok(mut child) = child else {
+            eprintln!("skipped: wasmtime is not on PATH");
+            return;
+        };
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        let run = child.wait_with_output().unwrap();
+        assert!(
+            run.status.success(),
+            "{name}: wasmtime exited with {}",
+            run.status
+        );
+        let output: Value = serde_json::from_slice(&run.stdout).unwrap();
+        assert_eq!(output, expected(&path), "{name} diverges under wasmtime");
+    }
+}
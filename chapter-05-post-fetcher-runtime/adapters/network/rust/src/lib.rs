@@ -1,35 +1,129 @@
 //! Host network adapter implementation.  This crate provides a
 //! `HostFetch` struct implementing the `NetworkAdapter` trait defined in the
 //! `service` crate.  It uses `reqwest::blocking` to perform synchronous
-//! HTTP GET requests on native targets.  When compiled to WebAssembly,
+//! HTTP GET, POST, PUT and DELETE requests on native targets, undoing any
+//! `Content-Encoding` of the response, and can stream bodies that have
+//! none.  A [`HostFetchConfig`] sets the proxy, extra CA certificates,
+//! minimum TLS version and connect timeout.  When compiled to WebAssembly,
 //! callers must supply an alternative implementation (this crate will not
 //! compile to wasm32 by default).
 
 use anyhow::Result;
-use service::api::{NetworkAdapter, NetworkResponse};
+use service::api::{AdapterCapabilities, NetworkAdapter, NetworkResponse, StreamingResponse};
+use service::content_encoding::decode_response;
+use service::fetch_config::{HostFetchConfig, TlsVersion};
+use service::model::Request;
 use std::collections::HashMap;
+use std::time::Duration;
 
-pub struct HostFetch;
+#[derive(Default)]
+pub struct HostFetch {
+    pub config: HostFetchConfig,
+}
 
-impl NetworkAdapter for HostFetch {
-    fn fetch(&self, url: &str, headers: &HashMap<String, String>) -> Result<NetworkResponse> {
-        let client = reqwest::blocking::Client::new();
-        let mut req = client.get(url);
-        for (k, v) in headers {
+impl HostFetch {
+    /// A host fetch adapter connecting as `config` says.
+    pub fn with_config(config: HostFetchConfig) -> Self {
+        Self { config }
+    }
+
+    /// The client for this adapter's config.  Without a proxy configured,
+    /// reqwest's own discovery from the environment applies.
+    fn client(&self) -> Result<reqwest::blocking::Client> {
+        let config = &self.config;
+        let mut client = reqwest::blocking::Client::builder();
+        if let Some(proxy) = &config.proxy {
+            client = client.no_proxy().proxy(reqwest::Proxy::all(proxy)?);
+        }
+        if let Some(pem) = config.ca_certificates()? {
+            for certificate in reqwest::Certificate::from_pem_bundle(&pem)? {
+                client = client.add_root_certificate(certificate);
+            }
+        }
+        if let Some(version) = config.tls_min_version {
+            client = client.min_tls_version(match version {
+                TlsVersion::Tls1_0 => reqwest::tls::Version::TLS_1_0,
+                TlsVersion::Tls1_1 => reqwest::tls::Version::TLS_1_1,
+                TlsVersion::Tls1_2 => reqwest::tls::Version::TLS_1_2,
+                TlsVersion::Tls1_3 => reqwest::tls::Version::TLS_1_3,
+            });
+        }
+        if let Some(millis) = config.connect_timeout_millis {
+            client = client.connect_timeout(Duration::from_millis(millis));
+        }
+        Ok(client.build()?)
+    }
+
+    /// Send `request`, returning the response with its body unread.
+    fn send(&self, request: &Request) -> Result<reqwest::blocking::Response> {
+        let client = self.client()?;
+        let method = reqwest::Method::from_bytes(request.method().as_bytes())?;
+        let mut req = client.request(method, &request.url);
+        for (k, v) in &request.headers {
             req = req.header(k.as_str(), v.as_str());
         }
-        let resp = req.send()?;
-        let status = resp.status().as_u16();
-        let mut resp_headers = HashMap::new();
-        for (k, v) in resp.headers().iter() {
-            let val = v.to_str().unwrap_or("").to_string();
-            resp_headers.insert(k.to_string(), val);
+        if let Some(body) = &request.body {
+            req = req.body(body.clone());
         }
-        let body = resp.text()?;
+        Ok(req.send()?)
+    }
+}
+
+impl NetworkAdapter for HostFetch {
+    fn fetch(&self, request: &Request) -> Result<NetworkResponse> {
+        let resp = self.send(request)?;
+        let status = resp.status().as_u16();
+        let mut resp_headers = response_headers(&resp);
+        let (body, decoded) = decode_response(&mut resp_headers, resp.bytes()?.to_vec(), None)?;
         Ok(NetworkResponse {
             status,
             headers: resp_headers,
             body,
+            decoded,
+        })
+    }
+
+    /// Reads the body from the connection as it arrives, unless it has a
+    /// `Content-Encoding` to undo first.
+    fn fetch_stream(&self, request: &Request) -> Result<StreamingResponse> {
+        let resp = self.send(request)?;
+        let headers = response_headers(&resp);
+        if headers
+            .keys()
+            .any(|key| key.eq_ignore_ascii_case("content-encoding"))
+        {
+            let mut headers = headers;
+            let status = resp.status().as_u16();
+            let (body, decoded) = decode_response(&mut headers, resp.bytes()?.to_vec(), None)?;
+            let response = NetworkResponse {
+                status,
+                headers,
+                body,
+                decoded,
+            };
+            return Ok(response.into());
+        }
+        Ok(StreamingResponse {
+            status: resp.status().as_u16(),
+            headers,
+            body: Box::new(resp),
+            decoded: None,
         })
     }
+
+    fn capabilities(&self) -> AdapterCapabilities {
+        AdapterCapabilities {
+            methods: ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec(),
+            compression: true,
+            streaming: true,
+            ..AdapterCapabilities::default()
+        }
+    }
+}
+
+fn response_headers(resp: &reqwest::blocking::Response) -> HashMap<String, String> {
+    resp.headers()
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+        .collect()
 }
@@ -0,0 +1,57 @@
+//! Which URLs a run may fetch.  The contract's `adapter` section declares
+//! the allowed schemes and hosts; the runtime checks the request URL against
+//! them before the adapter is called, so a denied request never leaves the
+//! host.
+
+use crate::contract::AdapterPolicy;
+use crate::thread_manager::host_of;
+use serde_json::{json, Value};
+
+/// Why a URL was denied, naming the allowlist it falls outside.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyDenial {
+    /// The lowercased scheme is not in `allowedSchemes`.
+    Scheme(String),
+    /// The lowercased host, without port, is not in `allowedHosts`.
+    Host(String),
+}
+
+impl PolicyDenial {
+    /// Data of the `policy_denied` event for a request to `url`.
+    pub(crate) fn event_data(&self, url: &str) -> Value {
+        let (rule, value) = match self {
+            PolicyDenial::Scheme(scheme) => ("allowedSchemes", scheme),
+            PolicyDenial::Host(host) => ("allowedHosts", host),
+        };
+        json!({ "url": url, "rule": rule, "value": value })
+    }
+}
+
+/// Check `url` against the policy's scheme allowlist, then its host
+/// allowlist.  Both compare case-insensitively, hosts ignore the port, and
+/// an empty list allows anything.
+pub fn check_url(policy: &AdapterPolicy, url: &str) -> Result<(), PolicyDenial> {
+    let allowed = |list: &[String], value: &str| {
+        list.is_empty() || list.iter().any(|entry| entry.eq_ignore_ascii_case(value))
+    };
+    let scheme = scheme_of(url);
+    if !allowed(&policy.allowed_schemes, &scheme) {
+        return Err(PolicyDenial::Scheme(scheme));
+    }
+    let host = host_of(url);
+    let host = host
+        .rsplit_once(':')
+        .filter(|(_, port)| port.bytes().all(|b| b.is_ascii_digit()))
+        .map_or(host.as_str(), |(host, _)| host);
+    if !allowed(&policy.allowed_hosts, host) {
+        return Err(PolicyDenial::Host(host.to_string()));
+    }
+    Ok(())
+}
+
+/// The lowercased scheme of `url`, empty when it has none.
+pub(crate) fn scheme_of(url: &str) -> String {
+    url.split_once("://")
+        .map_or("", |(scheme, _)| scheme)
+        .to_ascii_lowercase()
+}
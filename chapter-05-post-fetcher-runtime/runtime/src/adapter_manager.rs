@@ -1,19 +1,37 @@
-use service::api::{NetworkAdapter, NetworkResponse};
+use service::api::{
+    AdapterCapabilities, AsyncNetworkAdapter, DelayProvider, NetworkResponse, StreamingResponse,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use service::api::{FetchFuture, NetworkAdapter};
+use service::model::{Request, TraceContext};
 
-#[cfg(target_arch = "wasm32")]
-use crate::cache_adapter::CacheAdapter;
+use crate::auth_adapter::{AuthAdapter, TokenProvider};
+use crate::cache_adapter::{CacheAdapter, CacheLookup, ResponseCache};
+use crate::circuit_breaker::{CircuitBreakerAdapter, CircuitBreakers, CircuitTransition};
+use crate::contract::AdapterPolicy;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::contract::ExecutionLimits;
+use crate::executor::PooledAdapter;
+use crate::middleware::{Middleware, MiddlewareAdapter};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::replay_adapter::ReplayAdapter;
+use crate::retry_adapter::{RetryAdapter, RetryAttempt};
+use crate::thread_manager::ThreadManager;
+use crate::trace_adapter::TraceAdapter;
+use anyhow::Result;
 #[cfg(not(target_arch = "wasm32"))]
-use crate::cache_adapter::CacheAdapter;
-#[cfg(target_arch = "wasm32")]
-use crate::retry_adapter::RetryAdapter;
+use service::api::BudgetExceeded;
 #[cfg(not(target_arch = "wasm32"))]
-use crate::retry_adapter::RetryAdapter;
-#[cfg(target_arch = "wasm32")]
-use crate::wasi_http_adapter::WasiHttpAdapter;
-use anyhow::{Context, Result};
+use service::content_encoding::decode_response;
+#[cfg(not(target_arch = "wasm32"))]
+use service::fetch_config::{HostFetchConfig, TlsVersion};
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
-use std::fs;
-use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::Read;
+use std::rc::Rc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
 
 /// Metadata persisted about the adapter selection.  Records which
 /// implementation was chosen and the host environment.
@@ -23,149 +41,527 @@ pub struct AdapterBinding {
     pub host: String,
 }
 
-/// A simple adapter manager that selects a concrete network adapter at
-/// runtime.  For non‑wasm targets this uses a host fetch implementation
-/// (`reqwest`).  For wasm targets, it expects the host to provide an
-/// externally‑defined implementation via imports; in that case this struct
-/// merely holds a reference.
+/// Counters shared between the adapter manager and its wrappers.  The run
+/// summary reads the cache hit and retry counts here.  Cache lookups,
+/// retries, circuit state changes and token refreshes, by URL, are also
+/// logged until the runtime emits them as `cache_hit`/`cache_miss`,
+/// `fetch_retry`, `circuit_state` and `auth_refresh` events.  The tokens
+/// sent are kept for the runtime to redact.
+#[derive(Debug, Default)]
+pub struct AdapterStats {
+    pub cache_hits: Cell<u64>,
+    pub cache_log: RefCell<Vec<CacheLookup>>,
+    pub retries: Cell<u64>,
+    pub retry_log: RefCell<Vec<RetryAttempt>>,
+    pub circuit_log: RefCell<Vec<CircuitTransition>>,
+    pub auth_log: RefCell<Vec<String>>,
+    pub tokens: RefCell<Vec<String>>,
+}
+
+/// What the wrappers of a run share with the runtime: the run's trace, if
+/// it has one, the token provider, if requests are authorized, the
+/// middleware stack, the delay provider for retry backoff, the circuit
+/// breaker and response cache state that outlast the run, the runtime
+/// clock's time at its start, if it has a clock, and the worker threads of
+/// a batch run, if it has them.
+#[derive(Clone)]
+pub struct AdapterContext {
+    pub trace: Option<TraceContext>,
+    pub auth: Option<Rc<dyn TokenProvider>>,
+    pub middleware: Vec<Rc<dyn Middleware>>,
+    pub delay: Rc<dyn DelayProvider>,
+    pub circuits: Rc<CircuitBreakers>,
+    pub responses: Rc<ResponseCache>,
+    pub now_millis: Option<u64>,
+    pub threads: Option<Rc<ThreadManager>>,
+}
+
+impl Default for AdapterContext {
+    /// No trace, no authorization, no middleware, no delay, fresh state, no
+    /// clock and no worker threads.
+    fn default() -> Self {
+        Self {
+            trace: None,
+            auth: None,
+            middleware: Vec::new(),
+            delay: Rc::new(crate::NoDelay),
+            circuits: Rc::default(),
+            responses: Rc::default(),
+            now_millis: None,
+            threads: None,
+        }
+    }
+}
+
+/// Wraps the network adapter bound for a run, chosen by name from the
+/// [`crate::AdapterRegistry`] or given by the host, as the adapter policy
+/// asks, and records the binding.
 pub struct AdapterManager {
-    adapter: Box<dyn NetworkAdapter>,
+    adapter: Box<dyn AsyncNetworkAdapter>,
     pub binding: AdapterBinding,
+    pub stats: Rc<AdapterStats>,
 }
 
 impl AdapterManager {
-    /// Create a new adapter manager by selecting the appropriate adapter.
-    /// On non‑wasm targets this constructs a `HostFetchAdapter`.  On wasm
-    /// targets, the caller must supply a host‑provided implementation.
-    pub fn new(adapter: Option<Box<dyn NetworkAdapter>>) -> Self {
+    /// Create a new adapter manager wrapping `adapter`, bound under
+    /// `impl_name`, as `policy` asks.  The binding name gains a prefix for
+    /// each wrapper.  The wrappers keep their state in `context`, and a
+    /// thread-safe blocking adapter is called from its worker threads.
+    pub fn new(
+        mut adapter: Box<dyn AsyncNetworkAdapter>,
+        mut impl_name: String,
+        policy: &AdapterPolicy,
+        context: AdapterContext,
+    ) -> Self {
+        let stats = Rc::new(AdapterStats::default());
         #[cfg(target_arch = "wasm32")]
-        {
-            // On wasm targets we attempt to use a WASI HTTP adapter if none was
-            // explicitly provided.  This requires a host runtime that
-            // implements the `wasi:http` proposal.  Failing that, a
-            // host-provided adapter must be supplied.
-            if let Some(adapter) = adapter {
-                let binding = AdapterBinding {
-                    impl_name: "custom".to_string(),
-                    host: "wasm32".to_string(),
-                };
-                return Self { adapter, binding };
-            }
-            // Attempt to select a WasiHttpAdapter.  Note that this adapter
-            // currently returns an error because the WASI HTTP API is not
-            // implemented in this example.  If you enable a working
-            // implementation, set the impl_name accordingly.
-            let adapter =
-                Box::new(crate::wasi_http_adapter::WasiHttpAdapter {}) as Box<dyn NetworkAdapter>;
-            let binding = AdapterBinding {
-                impl_name: "wasi-http".to_string(),
-                host: "wasm32".to_string(),
-            };
-            return Self { adapter, binding };
-        }
-
+        let host = "wasm32";
         #[cfg(not(target_arch = "wasm32"))]
-        {
-            if let Some(adapter) = adapter {
-                // Respect externally provided adapter but apply optional retry/cache wrappers.
-                let mut adapter: Box<dyn NetworkAdapter> = adapter;
-                let mut impl_name = "custom".to_string();
-                let enable_retry = std::env::var("UMA_ENABLE_RETRY").is_ok();
-                let enable_cache = std::env::var("UMA_ENABLE_CACHE").is_ok();
-                if enable_retry {
-                    adapter = Box::new(RetryAdapter::new(adapter, 3));
-                    impl_name = format!("retry-{}", impl_name);
-                }
-                if enable_cache {
-                    adapter = Box::new(CacheAdapter::new(adapter));
-                    impl_name = format!("cache-{}", impl_name);
-                }
-                let binding = AdapterBinding {
-                    impl_name,
-                    host: "native".to_string(),
-                };
-                return Self { adapter, binding };
-            }
-            // Default host fetch adapter with optional wrappers.
-            let mut adapter: Box<dyn NetworkAdapter> = Box::new(HostFetchAdapter {});
-            let mut impl_name = "host-fetch".to_string();
-            let enable_retry = std::env::var("UMA_ENABLE_RETRY").is_ok();
-            let enable_cache = std::env::var("UMA_ENABLE_CACHE").is_ok();
-            if enable_retry {
-                adapter = Box::new(RetryAdapter::new(adapter, 3));
-                impl_name = format!("retry-{}", impl_name);
-            }
-            if enable_cache {
-                adapter = Box::new(CacheAdapter::new(adapter));
-                impl_name = format!("cache-{}", impl_name);
-            }
-            let binding = AdapterBinding {
-                impl_name,
-                host: "native".to_string(),
-            };
-            Self { adapter, binding }
+        let host = "native";
+        let pooled = context.threads.filter(|threads| threads.is_pooled());
+        if let (Some(threads), Some(shared)) = (pooled, adapter.thread_safe()) {
+            adapter = Box::new(PooledAdapter {
+                adapter: shared,
+                threads,
+            });
+        }
+        if let Some(trace) = &context.trace {
+            adapter = Box::new(TraceAdapter::new(adapter, trace));
+        }
+        if let Some(provider) = context.auth {
+            adapter = Box::new(AuthAdapter::new(adapter, provider, Rc::clone(&stats)));
+            impl_name = format!("auth-{}", impl_name);
+        }
+        if !context.middleware.is_empty() {
+            adapter = Box::new(MiddlewareAdapter::new(adapter, context.middleware));
+            impl_name = format!("middleware-{}", impl_name);
+        }
+        if policy.retries > 0 {
+            adapter = Box::new(RetryAdapter::new(
+                adapter,
+                policy.retry_policy(),
+                context.delay,
+                Rc::clone(&stats),
+            ));
+            impl_name = format!("retry-{}", impl_name);
+        }
+        if policy.circuit_breaker.failure_threshold > 0 {
+            adapter = Box::new(CircuitBreakerAdapter::new(
+                adapter,
+                policy.circuit_breaker,
+                context.circuits,
+                Rc::clone(&stats),
+            ));
+            impl_name = format!("circuit-{}", impl_name);
+        }
+        if policy.cache {
+            adapter = Box::new(CacheAdapter::new(
+                adapter,
+                policy.cache_limits,
+                context.responses,
+                context.now_millis,
+                Rc::clone(&stats),
+            ));
+            impl_name = format!("cache-{}", impl_name);
+        }
+        let binding = AdapterBinding {
+            impl_name,
+            host: host.to_string(),
+        };
+        Self {
+            adapter,
+            binding,
+            stats,
         }
     }
 
     /// Perform a network fetch.  Delegates to the underlying adapter.
-    pub fn fetch(&self, url: &str, headers: &HashMap<String, String>) -> Result<NetworkResponse> {
-        self.adapter.fetch(url, headers)
+    pub async fn fetch(&self, request: &Request) -> Result<NetworkResponse> {
+        self.adapter.fetch(request).await
     }
-}
 
-fn fixture_response(url: &str) -> Result<Option<NetworkResponse>> {
-    if url != "uma-fixture://sample-post" {
-        return Ok(None);
+    /// Perform a network fetch whose body is read as it arrives.  The cache
+    /// and middleware wrappers need the whole response, so with them, or an
+    /// adapter that does not stream, the body is read from memory.
+    pub async fn fetch_stream(&self, request: &Request) -> Result<StreamingResponse> {
+        self.adapter.fetch_stream(request).await
     }
 
-    let fixture_path =
-        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../tests/fixtures/sample_post.json");
-    let body = fs::read_to_string(&fixture_path)
-        .with_context(|| format!("failed to read fixture {}", fixture_path.display()))?;
-    let mut headers = HashMap::new();
-    headers.insert("content-type".to_string(), "application/json".to_string());
-    Ok(Some(NetworkResponse {
-        status: 200,
-        headers,
-        body,
-    }))
+    /// What the resolved adapter chain supports.  Middleware, retry and
+    /// cache wrappers add nothing, so this is what the innermost adapter declares.
+    pub fn capabilities(&self) -> AdapterCapabilities {
+        self.adapter.capabilities()
+    }
 }
 
 /// A simple host fetch adapter using `reqwest::blocking`.  Only available on
-/// non‑wasm targets.
+/// non‑wasm targets.  Requests over one of `limits` fail with
+/// [`BudgetExceeded`], and connections follow `config`.
 #[cfg(not(target_arch = "wasm32"))]
-pub struct HostFetchAdapter;
+#[derive(Default)]
+pub struct HostFetchAdapter {
+    pub limits: ExecutionLimits,
+    pub config: HostFetchConfig,
+}
 
 #[cfg(not(target_arch = "wasm32"))]
-impl NetworkAdapter for HostFetchAdapter {
-    fn fetch(&self, url: &str, headers: &HashMap<String, String>) -> Result<NetworkResponse> {
-        if let Some(response) = fixture_response(url)? {
-            return Ok(response);
+impl HostFetchAdapter {
+    /// The bundled fixture `request` asks for, if it is a GET of one.
+    fn fixture(&self, request: &Request) -> Result<Option<NetworkResponse>> {
+        if request.method() != "GET" {
+            return Ok(None);
+        }
+        let response = ReplayAdapter::bundled().response(&request.url);
+        if let Some(response) = &response {
+            check_body_len(&self.limits, response.body.len() as u64)?;
         }
+        Ok(response)
+    }
 
-        // Use reqwest::blocking to perform a GET request.
-        // Note: for demonstration purposes only; proper error handling and
-        // limits should be implemented in a real adapter.
+    /// Send `request`, returning the response with its body unread.
+    fn send(&self, request: &Request) -> Result<reqwest::blocking::Response> {
+        // Use reqwest::blocking to send the request.
         // Disable ambient proxy discovery so the sample behaves
         // deterministically on fresh reader machines, including macOS
         // hosts where system proxy APIs can fail in restricted contexts.
-        let client = reqwest::blocking::Client::builder().no_proxy().build()?;
-        let mut req = client.get(url);
-        for (k, v) in headers {
+        // Only the configured proxy, if any, is used.
+        let mut client = reqwest::blocking::Client::builder().no_proxy();
+        if let Some(max) = self.limits.max_redirects {
+            client = client.redirect(reqwest::redirect::Policy::limited(max as usize));
+        }
+        if let Some(millis) = self.limits.timeout_millis {
+            client = client.timeout(std::time::Duration::from_millis(millis));
+        }
+        let settings = ClientSettings::new(&self.config)?;
+        if let Some(proxy) = settings.proxy {
+            client = client.proxy(proxy);
+        }
+        for certificate in settings.certificates {
+            client = client.add_root_certificate(certificate);
+        }
+        if let Some(version) = settings.tls_min_version {
+            client = client.min_tls_version(version);
+        }
+        if let Some(timeout) = settings.connect_timeout {
+            client = client.connect_timeout(timeout);
+        }
+        let client = client.build()?;
+        let method = reqwest::Method::from_bytes(request.method().as_bytes())?;
+        let mut req = client.request(method, &request.url);
+        for (k, v) in &request.headers {
             req = req.header(k.as_str(), v.as_str());
         }
-        let resp = req.send()?;
+        if let Some(body) = &request.body {
+            req = req.body(body.clone());
+        }
+        req.send().map_err(|err| budget_error(&self.limits, err))
+    }
+
+    /// Read the body of `resp`, whose headers are `resp_headers`, whole and
+    /// decode it.
+    fn buffer(
+        &self,
+        mut resp: reqwest::blocking::Response,
+        mut resp_headers: HashMap<String, String>,
+    ) -> Result<NetworkResponse> {
         let status = resp.status().as_u16();
-        let mut resp_headers = HashMap::new();
-        for (k, v) in resp.headers().iter() {
-            let val = v.to_str().unwrap_or("").to_string();
-            resp_headers.insert(k.to_string(), val);
+        // Read at most one byte past the limit, which is enough to tell that
+        // the body is over it.
+        let mut body = Vec::new();
+        let read = match self.limits.max_body_bytes {
+            Some(max) => (&mut resp)
+                .take(max.saturating_add(1))
+                .read_to_end(&mut body),
+            None => resp.read_to_end(&mut body),
+        };
+        read.map_err(|err| read_error(&self.limits, err))?;
+        check_body_len(&self.limits, body.len() as u64)?;
+        // Undo any Content-Encoding, holding the expanded body to the same
+        // limit.
+        let (body, decoded) = decode_response(&mut resp_headers, body, self.limits.max_body_bytes)?;
+        check_body_len(&self.limits, body.len() as u64)?;
+        Ok(NetworkResponse {
+            status,
+            headers: resp_headers,
+            body,
+            decoded,
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl NetworkAdapter for HostFetchAdapter {
+    fn fetch(&self, request: &Request) -> Result<NetworkResponse> {
+        if let Some(response) = self.fixture(request)? {
+            return Ok(response);
+        }
+        let resp = self.send(request)?;
+        let headers = response_headers(resp.headers());
+        self.buffer(resp, headers)
+    }
+
+    /// Streams the body straight from the connection, held to the body
+    /// limit as it is read.  A body with a `Content-Encoding` is read and
+    /// decoded whole first, as [`NetworkAdapter::fetch`] does.
+    fn fetch_stream(&self, request: &Request) -> Result<StreamingResponse> {
+        if let Some(response) = self.fixture(request)? {
+            return Ok(response.into());
+        }
+        let resp = self.send(request)?;
+        let headers = response_headers(resp.headers());
+        if headers
+            .keys()
+            .any(|key| key.eq_ignore_ascii_case("content-encoding"))
+        {
+            return self.buffer(resp, headers).map(StreamingResponse::from);
+        }
+        Ok(StreamingResponse {
+            status: resp.status().as_u16(),
+            headers,
+            body: Box::new(LimitedBody {
+                inner: resp,
+                limits: self.limits,
+                read: 0,
+            }),
+            decoded: None,
+        })
+    }
+
+    /// GET, POST, PUT and DELETE over HTTP(S) with reqwest's default TLS,
+    /// plus GET on the bundled `uma-fixture` URLs.  Bodies are read within
+    /// the request timeout when the contract sets one, decoded by their
+    /// `Content-Encoding`, and can be streamed.
+    fn capabilities(&self) -> AdapterCapabilities {
+        AdapterCapabilities {
+            methods: ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec(),
+            schemes: ["http", "https", "uma-fixture"].map(String::from).to_vec(),
+            compression: true,
+            streaming: true,
+            timeouts: true,
+            ..AdapterCapabilities::default()
         }
-        let body = resp.text()?;
+    }
+
+    fn thread_safe(&self) -> Option<Arc<dyn NetworkAdapter + Send + Sync>> {
+        Some(Arc::new(HostFetchAdapter {
+            limits: self.limits,
+            config: self.config.clone(),
+        }))
+    }
+}
+
+/// The async counterpart of [`HostFetchAdapter`], using reqwest's async
+/// client.  It needs a tokio runtime with I/O and timers enabled, so it is
+/// what `host-fetch` binds to on async runs and not on blocking ones.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+pub struct AsyncHostFetchAdapter {
+    pub limits: ExecutionLimits,
+    pub config: HostFetchConfig,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl AsyncHostFetchAdapter {
+    async fn fetch_through(&self, request: &Request) -> Result<NetworkResponse> {
+        let method = request.method();
+        if method == "GET" {
+            if let Some(response) = ReplayAdapter::bundled().response(&request.url) {
+                check_body_len(&self.limits, response.body.len() as u64)?;
+                return Ok(response);
+            }
+        }
+
+        // Proxy discovery is off for the same reason as in HostFetchAdapter.
+        let mut client = reqwest::Client::builder().no_proxy();
+        if let Some(max) = self.limits.max_redirects {
+            client = client.redirect(reqwest::redirect::Policy::limited(max as usize));
+        }
+        if let Some(millis) = self.limits.timeout_millis {
+            client = client.timeout(std::time::Duration::from_millis(millis));
+        }
+        let settings = ClientSettings::new(&self.config)?;
+        if let Some(proxy) = settings.proxy {
+            client = client.proxy(proxy);
+        }
+        for certificate in settings.certificates {
+            client = client.add_root_certificate(certificate);
+        }
+        if let Some(version) = settings.tls_min_version {
+            client = client.min_tls_version(version);
+        }
+        if let Some(timeout) = settings.connect_timeout {
+            client = client.connect_timeout(timeout);
+        }
+        let client = client.build()?;
+        let method = reqwest::Method::from_bytes(method.as_bytes())?;
+        let mut req = client.request(method, &request.url);
+        for (k, v) in &request.headers {
+            req = req.header(k.as_str(), v.as_str());
+        }
+        if let Some(body) = &request.body {
+            req = req.body(body.clone());
+        }
+        let mut resp = req
+            .send()
+            .await
+            .map_err(|err| budget_error(&self.limits, err))?;
+        let status = resp.status().as_u16();
+        let mut resp_headers = response_headers(resp.headers());
+        // Stop reading as soon as the body is over the limit.
+        let mut body = Vec::new();
+        while let Some(chunk) = resp
+            .chunk()
+            .await
+            .map_err(|err| budget_error(&self.limits, err))?
+        {
+            body.extend_from_slice(&chunk);
+            check_body_len(&self.limits, body.len() as u64)?;
+        }
+        let (body, decoded) = decode_response(&mut resp_headers, body, self.limits.max_body_bytes)?;
+        check_body_len(&self.limits, body.len() as u64)?;
         Ok(NetworkResponse {
             status,
             headers: resp_headers,
             body,
+            decoded,
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl AsyncNetworkAdapter for AsyncHostFetchAdapter {
+    fn fetch<'a>(&'a self, request: &'a Request) -> FetchFuture<'a> {
+        Box::pin(self.fetch_through(request))
+    }
+
+    /// The same as [`HostFetchAdapter`], but bodies are read whole.
+    fn capabilities(&self) -> AdapterCapabilities {
+        AdapterCapabilities {
+            streaming: false,
+            ..HostFetchAdapter::default().capabilities()
+        }
+    }
+}
+
+/// A [`HostFetchConfig`] in the types reqwest's client builders take.
+#[cfg(not(target_arch = "wasm32"))]
+struct ClientSettings {
+    proxy: Option<reqwest::Proxy>,
+    certificates: Vec<reqwest::Certificate>,
+    tls_min_version: Option<reqwest::tls::Version>,
+    connect_timeout: Option<std::time::Duration>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ClientSettings {
+    /// Parse `config`, reading its CA certificate file.
+    fn new(config: &HostFetchConfig) -> Result<Self> {
+        let proxy = config
+            .proxy
+            .as_deref()
+            .map(reqwest::Proxy::all)
+            .transpose()?;
+        let certificates = match config.ca_certificates()? {
+            Some(pem) => reqwest::Certificate::from_pem_bundle(&pem)?,
+            None => Vec::new(),
+        };
+        let tls_min_version = config.tls_min_version.map(|version| match version {
+            TlsVersion::Tls1_0 => reqwest::tls::Version::TLS_1_0,
+            TlsVersion::Tls1_1 => reqwest::tls::Version::TLS_1_1,
+            TlsVersion::Tls1_2 => reqwest::tls::Version::TLS_1_2,
+            TlsVersion::Tls1_3 => reqwest::tls::Version::TLS_1_3,
+        });
+        Ok(Self {
+            proxy,
+            certificates,
+            tls_min_version,
+            connect_timeout: config
+                .connect_timeout_millis
+                .map(std::time::Duration::from_millis),
+        })
+    }
+}
+
+/// A streamed body that fails with [`BudgetExceeded`] once more than the
+/// body limit has been read or the timeout runs out.
+#[cfg(not(target_arch = "wasm32"))]
+struct LimitedBody {
+    inner: reqwest::blocking::Response,
+    limits: ExecutionLimits,
+    read: u64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Read for LimitedBody {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let into_io = |err: anyhow::Error| match err.downcast::<BudgetExceeded>() {
+            Ok(budget) => std::io::Error::other(budget),
+            Err(err) => std::io::Error::other(err.to_string()),
+        };
+        let read = self
+            .inner
+            .read(buf)
+            .map_err(|err| into_io(read_error(&self.limits, err)))?;
+        self.read += read as u64;
+        check_body_len(&self.limits, self.read).map_err(into_io)?;
+        Ok(read)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn response_headers(headers: &reqwest::header::HeaderMap) -> HashMap<String, String> {
+    headers
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+        .collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn check_body_len(limits: &ExecutionLimits, len: u64) -> Result<()> {
+    match limits.max_body_bytes {
+        Some(max) if len > max => Err(BudgetExceeded {
+            limit: "maxBodyBytes",
+            max,
+        }
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// A send failure, as [`BudgetExceeded`] when it was caused by one of
+/// `limits`.
+#[cfg(not(target_arch = "wasm32"))]
+fn budget_error(limits: &ExecutionLimits, err: reqwest::Error) -> anyhow::Error {
+    let budget = if err.is_timeout() {
+        limits.timeout_millis.map(|max| BudgetExceeded {
+            limit: "timeoutMillis",
+            max,
+        })
+    } else if err.is_redirect() {
+        limits.max_redirects.map(|max| BudgetExceeded {
+            limit: "maxRedirects",
+            max: u64::from(max),
         })
+    } else {
+        None
+    };
+    budget.map_or_else(|| err.into(), Into::into)
+}
+
+/// A body read failure, as [`BudgetExceeded`] when the timeout ran out.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_error(limits: &ExecutionLimits, err: std::io::Error) -> anyhow::Error {
+    let timed_out = err.kind() == std::io::ErrorKind::TimedOut
+        || err
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<reqwest::Error>())
+            .is_some_and(reqwest::Error::is_timeout);
+    match limits.timeout_millis {
+        Some(max) if timed_out => BudgetExceeded {
+            limit: "timeoutMillis",
+            max,
+        }
+        .into(),
+        _ => err.into(),
     }
 }
@@ -0,0 +1,188 @@
+//! A wrapper adapter that authorizes requests.  [`AuthAdapter`] sets the
+//! `Authorization` header of every request it sends from a
+//! [`TokenProvider`], replacing any the input set.  A `401 Unauthorized`
+//! response makes it ask the provider to refresh and send the request once
+//! more with the new token; the second response is returned whatever its
+//! status.  Each refresh is logged in the shared `AdapterStats`, so the
+//! runtime can emit it as an `auth_refresh` event.
+//!
+//! The wrapper sits directly around the adapter, inside the middleware, so
+//! middleware never see the token and every retry attempt and cache
+//! revalidation is sent with the current one.  Every token it sends is
+//! recorded too, and the runtime replaces each with [`REDACTED`] wherever
+//! it appears in the run's events, and so in the lifecycle record: a token
+//! an adapter echoes into an error never leaves the run.
+
+use crate::adapter_manager::AdapterStats;
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+use service::api::{
+    AdapterCapabilities, AsyncNetworkAdapter, FetchFuture, NetworkResponse, StreamFuture,
+    StreamingResponse,
+};
+use service::model::Request;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// What a token is replaced with in events.
+pub const REDACTED: &str = "[redacted]";
+
+/// Supplies the credentials an [`AuthAdapter`] sends.  Closures returning
+/// a token are providers that never refresh.
+pub trait TokenProvider {
+    /// The token to send now.
+    fn token(&self) -> Result<String>;
+
+    /// Obtain a new token after the server rejected the current one.  Does
+    /// nothing by default, for providers whose `token` is always current.
+    fn refresh(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// The authorization scheme the token is sent with.
+    fn scheme(&self) -> &str {
+        "Bearer"
+    }
+}
+
+impl<F: Fn() -> Result<String>> TokenProvider for F {
+    fn token(&self) -> Result<String> {
+        self()
+    }
+}
+
+/// A token that never changes.
+#[derive(Debug, Clone)]
+pub struct StaticToken(pub String);
+
+impl TokenProvider for StaticToken {
+    fn token(&self) -> Result<String> {
+        Ok(self.0.clone())
+    }
+}
+
+/// A token read, trimmed, from a file each time it is sent, so a token
+/// another process rotates on disk is picked up without a refresh.
+#[derive(Debug, Clone)]
+pub struct FileToken(pub PathBuf);
+
+impl TokenProvider for FileToken {
+    fn token(&self) -> Result<String> {
+        let path = self.0.display();
+        let token = std::fs::read_to_string(&self.0)
+            .map_err(|err| anyhow!("reading the token from {path}: {err}"))?;
+        let token = token.trim();
+        if token.is_empty() {
+            bail!("the token file {path} is empty");
+        }
+        Ok(token.to_string())
+    }
+}
+
+pub struct AuthAdapter {
+    inner: Box<dyn AsyncNetworkAdapter>,
+    provider: Rc<dyn TokenProvider>,
+    stats: Rc<AdapterStats>,
+}
+
+impl AuthAdapter {
+    pub fn new(
+        inner: Box<dyn AsyncNetworkAdapter>,
+        provider: Rc<dyn TokenProvider>,
+        stats: Rc<AdapterStats>,
+    ) -> Self {
+        Self {
+            inner,
+            provider,
+            stats,
+        }
+    }
+
+    /// `request` with the provider's current token.
+    fn authorize(&self, request: &Request) -> Result<Request> {
+        let token = self.provider.token()?;
+        let mut request = request.clone();
+        request
+            .headers
+            .retain(|key, _| !key.eq_ignore_ascii_case("authorization"));
+        request.headers.insert(
+            "Authorization".to_string(),
+            format!("{} {token}", self.provider.scheme()),
+        );
+        let mut tokens = self.stats.tokens.borrow_mut();
+        if !tokens.contains(&token) {
+            tokens.push(token);
+        }
+        Ok(request)
+    }
+
+    /// Refresh the token after `request` was rejected.
+    fn refresh(&self, request: &Request) -> Result<()> {
+        self.stats.auth_log.borrow_mut().push(request.url.clone());
+        self.provider.refresh()
+    }
+
+    async fn fetch_through(&self, request: &Request) -> Result<NetworkResponse> {
+        let response = self.inner.fetch(&self.authorize(request)?).await?;
+        if response.status != 401 {
+            return Ok(response);
+        }
+        self.refresh(request)?;
+        self.inner.fetch(&self.authorize(request)?).await
+    }
+
+    async fn stream_through(&self, request: &Request) -> Result<StreamingResponse> {
+        let response = self.inner.fetch_stream(&self.authorize(request)?).await?;
+        if response.status != 401 {
+            return Ok(response);
+        }
+        self.refresh(request)?;
+        self.inner.fetch_stream(&self.authorize(request)?).await
+    }
+}
+
+impl AsyncNetworkAdapter for AuthAdapter {
+    fn fetch<'a>(&'a self, request: &'a Request) -> FetchFuture<'a> {
+        Box::pin(self.fetch_through(request))
+    }
+
+    fn fetch_stream<'a>(&'a self, request: &'a Request) -> StreamFuture<'a> {
+        Box::pin(self.stream_through(request))
+    }
+
+    fn capabilities(&self) -> AdapterCapabilities {
+        self.inner.capabilities()
+    }
+}
+
+/// Replace every occurrence of `tokens` in the strings of an event's data
+/// with [`REDACTED`].
+pub(crate) fn redact_tokens(data: &mut Value, tokens: &[String]) {
+    let tokens: Vec<&String> = tokens.iter().filter(|token| !token.is_empty()).collect();
+    if !tokens.is_empty() {
+        redact_value(data, &tokens);
+    }
+}
+
+fn redact_value(value: &mut Value, tokens: &[&String]) {
+    match value {
+        Value::String(text) => {
+            for token in tokens {
+                if text.contains(token.as_str()) {
+                    *text = text.replace(token.as_str(), REDACTED);
+                }
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                redact_value(value, tokens);
+            }
+        }
+        Value::Object(fields) => {
+            for value in fields.values_mut() {
+                redact_value(value, tokens);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,556 @@
+//! Programmatic runtime configuration.  A [`Runtime`] holds everything a run
+//! needs besides its input: the service contract, the network adapter, the
+//! retry, circuit breaker and cache wrappers, the request header allowlist, the runtime policy, the
+//! optional clock and event decorator, and the sinks and subscribers that receive the event log.  The environment is
+//! one optional source of settings, read by [`RuntimeBuilder::with_env`].
+
+use crate::adapter_manager::AdapterContext;
+use crate::auth_adapter::TokenProvider;
+use crate::cache_adapter::{CacheLimits, ResponseCache};
+use crate::circuit_breaker::{CircuitBreakerPolicy, CircuitBreakers};
+use crate::contract::{ExecutionLimits, ServiceContract};
+use crate::event_bus::{EventDecorator, EventSink};
+use crate::event_schema::EventSchemas;
+use crate::executor::{block_on, BlockingAdapter, SharedAdapter};
+use crate::middleware::Middleware;
+use crate::policy::{ConcurrencyPolicy, RuntimePolicy};
+use crate::privacy::{random_salt, PrivacyPolicy};
+use crate::registry::AdapterRegistry;
+use crate::run::{RunError, RunResult};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use service::api::{AsyncNetworkAdapter, Clock, DelayProvider, NetworkAdapter, TimeSource};
+#[cfg(not(target_arch = "wasm32"))]
+use service::fetch_config::HostFetchConfig;
+use service::model::Input;
+use service::normalizer::{Normalizer, NormalizerRegistry};
+use std::rc::Rc;
+
+/// How failed requests are retried.  Each attempt after the first is
+/// recorded as a `fetch_retry` event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts per request, the first included; 1 never retries.
+    pub max_attempts: u32,
+    /// Response statuses worth another attempt; empty retries every non-2xx
+    /// status but `304 Not Modified`, the answer to a conditional request.
+    /// Transport failures are always retried.
+    pub retryable_statuses: Vec<u16>,
+    /// Retry only idempotent methods: a failed `POST` may still have been
+    /// applied.
+    pub idempotent_only: bool,
+    /// Wait between attempts; `None` keeps the contract's `backoff`.
+    pub backoff: Option<Backoff>,
+}
+
+/// Exponential backoff between retry attempts: `initial_millis` before the
+/// first retry, doubling before each one after it, up to `max_millis`.
+/// There is no jitter, so the delays of a run are deterministic.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Backoff {
+    #[serde(default)]
+    pub initial_millis: u64,
+    #[serde(default)]
+    pub max_millis: u64,
+}
+
+impl Backoff {
+    /// The delay before attempt number `attempt`, counting the first as 1.
+    pub fn before(&self, attempt: u32) -> u64 {
+        let doublings = attempt.saturating_sub(2).min(63);
+        self.initial_millis
+            .saturating_mul(1 << doublings)
+            .min(self.max_millis)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::never()
+    }
+}
+
+impl RetryPolicy {
+    /// Every request is sent once.
+    pub fn never() -> Self {
+        Self::up_to(0)
+    }
+
+    /// Up to `retries` further attempts of an idempotent request after a
+    /// failure or non-2xx status.
+    pub fn up_to(retries: u32) -> Self {
+        Self {
+            max_attempts: retries.saturating_add(1),
+            retryable_statuses: Vec::new(),
+            idempotent_only: true,
+            backoff: None,
+        }
+    }
+
+    /// Wait following `backoff` between attempts.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = Some(backoff);
+        self
+    }
+
+    /// Retry only responses with one of `statuses`.
+    pub fn retry_on(mut self, statuses: impl IntoIterator<Item = u16>) -> Self {
+        self.retryable_statuses = statuses.into_iter().collect();
+        self
+    }
+
+    /// Whether a response with `status` is worth another attempt.
+    pub fn retries_status(&self, status: u16) -> bool {
+        !(200..300).contains(&status)
+            && status != 304
+            && (self.retryable_statuses.is_empty() || self.retryable_statuses.contains(&status))
+    }
+}
+
+/// Whether `GET` responses are cached across the runs of a runtime.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CachePolicy {
+    #[default]
+    Disabled,
+    /// Repeated `GET`s of a URL with the same headers are served from
+    /// memory, within the contract's `cacheLimits`.
+    InMemory,
+}
+
+/// A configured post fetcher runtime.  Build one with [`Runtime::builder`];
+/// it can then perform any number of runs.
+pub struct Runtime<'a> {
+    pub(crate) contract: ServiceContract,
+    pub(crate) adapter: Option<Rc<dyn AsyncNetworkAdapter>>,
+    pub(crate) adapters: AdapterRegistry,
+    pub(crate) normalizers: NormalizerRegistry,
+    pub(crate) stream_chunk_bytes: Option<usize>,
+    pub(crate) retry: Option<RetryPolicy>,
+    pub(crate) circuit_breaker: Option<CircuitBreakerPolicy>,
+    pub(crate) circuits: Rc<CircuitBreakers>,
+    pub(crate) cache: Option<CachePolicy>,
+    pub(crate) cache_limits: Option<CacheLimits>,
+    pub(crate) responses: Rc<ResponseCache>,
+    pub(crate) allowed_headers: Option<Vec<String>>,
+    pub(crate) event_schemas: Option<EventSchemas>,
+    pub(crate) sensitive_keys: Option<Vec<String>>,
+    pub(crate) policy: RuntimePolicy,
+    pub(crate) clock: Option<&'a dyn Clock>,
+    pub(crate) time_source: Option<&'a dyn TimeSource>,
+    pub(crate) delay: Option<Rc<dyn DelayProvider>>,
+    pub(crate) auth: Option<Rc<dyn TokenProvider>>,
+    pub(crate) middleware: Vec<Rc<dyn Middleware>>,
+    pub(crate) decorator: Option<&'a dyn EventDecorator>,
+    pub(crate) sinks: Vec<&'a dyn EventSink>,
+    pub(crate) subscribers: Vec<&'a dyn EventSink>,
+}
+
+impl<'a> Runtime<'a> {
+    /// A builder starting from the defaults: the bundled service contract,
+    /// the built-in adapter implementations with the one the contract binds
+    /// to `network.fetch`, the built-in normalizers, buffered response
+    /// bodies, the retry, circuit breaker and cache
+    /// settings of the contract's `adapter` section, the header policy of
+    /// its `headers` section, the bundled runtime policy, the host's delay
+    /// provider, no middleware,
+    /// no clock, no time source, no decorator, no sinks and no subscribers.
+    pub fn builder() -> RuntimeBuilder<'a> {
+        RuntimeBuilder {
+            runtime: Runtime {
+                contract: ServiceContract::bundled(),
+                adapter: None,
+                adapters: AdapterRegistry::builtin(),
+                normalizers: NormalizerRegistry::builtin(),
+                stream_chunk_bytes: None,
+                retry: None,
+                circuit_breaker: None,
+                circuits: Rc::default(),
+                cache: None,
+                cache_limits: None,
+                responses: Rc::default(),
+                allowed_headers: None,
+                event_schemas: None,
+                sensitive_keys: None,
+                policy: RuntimePolicy::bundled(),
+                clock: None,
+                time_source: None,
+                delay: None,
+                auth: None,
+                middleware: Vec::new(),
+                decorator: None,
+                sinks: Vec::new(),
+                subscribers: Vec::new(),
+            },
+        }
+    }
+
+    /// Run the post fetcher with a typed input; see [`crate::run`].
+    pub fn run(&self, input: Input) -> Result<RunResult, RunError> {
+        block_on(crate::execute(self, input, None, false))
+    }
+
+    /// Run the post fetcher with a JSON input document and return the output
+    /// and lifecycle record as JSON; see [`crate::run_json`].
+    pub fn run_json(&self, input_json: &str) -> Result<(String, String)> {
+        let (input, raw) = parse_input(input_json)?;
+        let result = block_on(crate::execute(self, input, Some(&raw), false))?;
+        run_json_output(&result)
+    }
+
+    /// Like [`Runtime::run`], for async hosts.  The run awaits its fetch and
+    /// retry backoff instead of blocking the thread, and binds the async
+    /// implementation of the contract's adapter when there is one.
+    ///
+    /// The future is not `Send`: the runtime shares its state through `Rc`.
+    /// Await it on the task that owns the runtime, for instance in a tokio
+    /// `LocalSet` or on a current-thread runtime.
+    pub async fn run_async(&self, input: Input) -> Result<RunResult, RunError> {
+        crate::execute(self, input, None, true).await
+    }
+
+    /// Like [`Runtime::run_json`], for async hosts; see [`Runtime::run_async`].
+    pub async fn run_json_async(&self, input_json: &str) -> Result<(String, String)> {
+        let (input, raw) = parse_input(input_json)?;
+        let result = crate::execute(self, input, Some(&raw), true).await?;
+        run_json_output(&result)
+    }
+
+    /// The circuits of the hosts this runtime has called.  They carry over
+    /// from one run to the next.
+    pub fn circuits(&self) -> &CircuitBreakers {
+        &self.circuits
+    }
+
+    /// The responses this runtime has cached.  They carry over from one run
+    /// to the next.
+    pub fn responses(&self) -> &ResponseCache {
+        &self.responses
+    }
+
+    /// The state the adapter wrappers of one run share with this runtime.
+    /// `now_millis` is the run's start on the runtime clock.
+    pub(crate) fn adapter_context(&self, now_millis: Option<u64>) -> AdapterContext {
+        let delay: Rc<dyn DelayProvider> = match &self.delay {
+            Some(delay) => Rc::clone(delay),
+            #[cfg(not(target_arch = "wasm32"))]
+            None => Rc::new(crate::SleepDelay),
+            #[cfg(target_arch = "wasm32")]
+            None => Rc::new(crate::NoDelay),
+        };
+        AdapterContext {
+            trace: None,
+            auth: self.auth.clone(),
+            middleware: self.middleware.clone(),
+            delay,
+            circuits: Rc::clone(&self.circuits),
+            responses: Rc::clone(&self.responses),
+            now_millis,
+            threads: None,
+        }
+    }
+
+    /// The adapter for one run and its binding name: the adapter set on the
+    /// builder, shared with every other run and bound as `custom`, or a new
+    /// one of the implementation the contract binds, async on `asynchronous`
+    /// runs if it has one.  Fails with the bound name when no implementation
+    /// is registered under it.
+    pub(crate) fn bind_adapter(
+        &self,
+        asynchronous: bool,
+    ) -> Result<(Box<dyn AsyncNetworkAdapter>, String), String> {
+        if let Some(adapter) = &self.adapter {
+            let adapter = Box::new(SharedAdapter(Rc::clone(adapter)));
+            return Ok((adapter, "custom".to_string()));
+        }
+        let name = self.contract.bindings.network_fetch();
+        let limits = self.contract.execution;
+        match self.adapters.create_for_run(name, limits, asynchronous) {
+            Some(adapter) => Ok((adapter, name.to_string())),
+            None => Err(name.to_string()),
+        }
+    }
+}
+
+/// Builder for [`Runtime`].
+pub struct RuntimeBuilder<'a> {
+    runtime: Runtime<'a>,
+}
+
+impl<'a> RuntimeBuilder<'a> {
+    /// Run under `contract` instead of the bundled service contract.
+    pub fn contract(mut self, contract: ServiceContract) -> Self {
+        self.runtime.contract = contract;
+        self
+    }
+
+    /// Fetch through `adapter` instead of the adapter the contract binds.
+    pub fn adapter(mut self, adapter: Box<dyn NetworkAdapter>) -> Self {
+        self.runtime.adapter = Some(Rc::new(BlockingAdapter(adapter)));
+        self
+    }
+
+    /// Like [`RuntimeBuilder::adapter`], with an async adapter.  Blocking
+    /// runs drive it on the calling thread, so it must not need an async
+    /// runtime of its own there.
+    pub fn async_adapter(mut self, adapter: Box<dyn AsyncNetworkAdapter>) -> Self {
+        self.runtime.adapter = Some(Rc::from(adapter));
+        self
+    }
+
+    /// Make the adapters `factory` creates available to contracts under
+    /// `name`, replacing any blocking implementation registered under it.
+    pub fn register_adapter<F>(mut self, name: &str, factory: F) -> Self
+    where
+        F: Fn(ExecutionLimits) -> Box<dyn NetworkAdapter> + 'static,
+    {
+        self.runtime.adapters.register(name, factory);
+        self
+    }
+
+    /// Like [`RuntimeBuilder::register_adapter`], for an async
+    /// implementation; see [`AdapterRegistry::register_async`].
+    pub fn register_async_adapter<F>(mut self, name: &str, factory: F) -> Self
+    where
+        F: Fn(ExecutionLimits) -> Box<dyn AsyncNetworkAdapter> + 'static,
+    {
+        self.runtime.adapters.register_async(name, factory);
+        self
+    }
+
+    /// Connect the built-in `host-fetch` adapter as `config` says: through a
+    /// proxy, trusting extra CA certificates, with a minimum TLS version or
+    /// a connect timeout.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn host_fetch_config(mut self, config: HostFetchConfig) -> Self {
+        self.runtime.adapters.register_host_fetch(config);
+        self
+    }
+
+    /// Make `normalizer` available to inputs as the `resource` `name`,
+    /// replacing any normalizer registered under it.
+    pub fn register_normalizer(
+        mut self,
+        name: &str,
+        normalizer: impl Normalizer + 'static,
+    ) -> Self {
+        self.runtime.normalizers.register(name, normalizer);
+        self
+    }
+
+    /// Normalize responses of `content_type` with the normalizer registered
+    /// as `name` when the input names no resource.
+    pub fn normalize_content_type(mut self, content_type: &str, name: &str) -> Self {
+        self.runtime
+            .normalizers
+            .register_content_type(content_type, name);
+        self
+    }
+
+    /// Read response bodies as they arrive, `chunk_bytes` at a time, and
+    /// record a `fetch_progress` event per chunk.  Array bodies whose
+    /// normalizer [normalizes elements](Normalizer::normalizes_elements) are
+    /// normalized element by element, so only the element being read is
+    /// held; other bodies are still normalized whole once read.
+    pub fn stream_chunks(mut self, chunk_bytes: usize) -> Self {
+        self.runtime.stream_chunk_bytes = Some(chunk_bytes.max(1));
+        self
+    }
+
+    /// Retry following `policy` instead of the contract's `retries`,
+    /// `retryOn` and `retryNonIdempotent`.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.runtime.retry = Some(policy);
+        self
+    }
+
+    /// Break circuits following `policy` instead of the contract's
+    /// `circuitBreaker`.
+    pub fn circuit_breaker(mut self, policy: CircuitBreakerPolicy) -> Self {
+        self.runtime.circuit_breaker = Some(policy);
+        self
+    }
+
+    /// Cache following `policy` instead of the contract's `cache`.
+    pub fn cache(mut self, policy: CachePolicy) -> Self {
+        self.runtime.cache = Some(policy);
+        self
+    }
+
+    /// Bound the cache with `limits` instead of the contract's
+    /// `cacheLimits`.
+    pub fn cache_limits(mut self, limits: CacheLimits) -> Self {
+        self.runtime.cache_limits = Some(limits);
+        self
+    }
+
+    /// Accept only these request header names, compared case-insensitively,
+    /// instead of the contract's `headers.allowed`.
+    pub fn allowed_headers<I, S>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.runtime.allowed_headers = Some(
+            headers
+                .into_iter()
+                .map(|header| header.as_ref().to_ascii_lowercase())
+                .collect(),
+        );
+        self
+    }
+
+    /// Check event payloads against `schemas` instead of the contract's
+    /// `events` section.
+    pub fn event_schemas(mut self, schemas: EventSchemas) -> Self {
+        self.runtime.event_schemas = Some(schemas);
+        self
+    }
+
+    /// Mask the values of these names in events instead of the runtime
+    /// policy's `secrets.sensitiveKeys`; see [`crate::SecretsPolicy`].
+    pub fn sensitive_keys<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.runtime.sensitive_keys = Some(patterns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Enforce `policy` instead of the bundled runtime policy.  It replaces
+    /// the sections set by earlier calls, such as
+    /// [`RuntimeBuilder::concurrency`] and [`RuntimeBuilder::privacy`].
+    pub fn policy(mut self, policy: RuntimePolicy) -> Self {
+        self.runtime.policy = policy;
+        self
+    }
+
+    /// Schedule fetches following `policy` instead of the runtime policy's
+    /// `concurrency` section: its per-host cap and worker count.
+    pub fn concurrency(mut self, policy: ConcurrencyPolicy) -> Self {
+        self.runtime.policy.concurrency = policy;
+        self
+    }
+
+    /// Redact events following `policy` instead of the runtime policy's
+    /// `privacy` section.  This is where a host supplies its secret salt.
+    pub fn privacy(mut self, policy: PrivacyPolicy) -> Self {
+        self.runtime.policy.privacy = policy;
+        self
+    }
+
+    /// Give runs a wall clock, so the SLA summary also checks
+    /// `maxWallMillis`.
+    pub fn clock(mut self, clock: &'a dyn Clock) -> Self {
+        self.runtime.clock = Some(clock);
+        self
+    }
+
+    /// Stamp every event with a `wallTime` from `source` besides its
+    /// logical `t`.  [`crate::SystemClock`] is a source of the system time.
+    pub fn time_source(mut self, source: &'a dyn TimeSource) -> Self {
+        self.runtime.time_source = Some(source);
+        self
+    }
+
+    /// Wait out retry backoff with `delay` instead of the host's provider:
+    /// [`crate::SleepDelay`] on native targets, [`crate::NoDelay`] on wasm.
+    pub fn delay(mut self, delay: Rc<dyn DelayProvider>) -> Self {
+        self.runtime.delay = Some(delay);
+        self
+    }
+
+    /// Authorize every request with a token from `provider`; see
+    /// [`crate::AuthAdapter`].
+    pub fn auth(mut self, provider: Rc<dyn TokenProvider>) -> Self {
+        self.runtime.auth = Some(provider);
+        self
+    }
+
+    /// Add `middleware` to the end of the stack run around each request the
+    /// adapter sends; see [`Middleware`].
+    pub fn middleware(mut self, middleware: Rc<dyn Middleware>) -> Self {
+        self.runtime.middleware.push(middleware);
+        self
+    }
+
+    /// Tag every event with host attributes; see [`EventDecorator`].
+    pub fn decorator(mut self, decorator: &'a dyn EventDecorator) -> Self {
+        self.runtime.decorator = Some(decorator);
+        self
+    }
+
+    /// Add a sink that receives the event log of every run once the run
+    /// has finished, and only if it succeeded.
+    pub fn sink(mut self, sink: &'a dyn EventSink) -> Self {
+        self.runtime.sinks.push(sink);
+        self
+    }
+
+    /// Add a subscriber that receives each event of every run as it is
+    /// emitted, to stream the log while the run is in progress; see
+    /// [`EventBus::subscribe`](crate::EventBus::subscribe).
+    pub fn subscribe(mut self, listener: &'a dyn EventSink) -> Self {
+        self.runtime.subscribers.push(listener);
+        self
+    }
+
+    /// Read settings from the environment: `UMA_ENABLE_RETRY` retries up to
+    /// three times and `UMA_ENABLE_CACHE` enables the in-memory cache.
+    /// `UMA_PRIVACY_MODE` set to `1`, `true`, `yes` or `on` enables the
+    /// privacy rules, and `UMA_PRIVACY_SALT` is the salt they hash with.
+    /// Unset variables, and other values of `UMA_PRIVACY_MODE`, leave the
+    /// current settings alone.
+    pub fn with_env(mut self) -> Self {
+        if std::env::var("UMA_ENABLE_RETRY").is_ok() {
+            self.runtime.retry = Some(RetryPolicy::up_to(3));
+        }
+        if std::env::var("UMA_ENABLE_CACHE").is_ok() {
+            self.runtime.cache = Some(CachePolicy::InMemory);
+        }
+        if std::env::var("UMA_PRIVACY_MODE").is_ok_and(|mode| switched_on(&mode)) {
+            self.runtime.policy.privacy.enabled = true;
+        }
+        if let Ok(salt) = std::env::var("UMA_PRIVACY_SALT") {
+            self.runtime.policy.privacy.salt = salt;
+        }
+        self
+    }
+
+    /// The runtime.  Without a privacy salt it gets a random one, so the
+    /// salt is never a value anyone can read in the policy.
+    pub fn build(mut self) -> Runtime<'a> {
+        let privacy = &mut self.runtime.policy.privacy;
+        if privacy.salt.is_empty() {
+            privacy.salt = random_salt();
+        }
+        self.runtime
+    }
+}
+
+/// Whether an environment flag's value turns it on.
+fn switched_on(value: &str) -> bool {
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes" | "on"
+    )
+}
+
+/// The input of a JSON run and the raw document, for the strict-mode
+/// field check.
+fn parse_input(input_json: &str) -> Result<(Input, Value), RunError> {
+    let invalid = |err: serde_json::Error| RunError::InputInvalid(err.to_string());
+    let raw: Value = serde_json::from_str(input_json).map_err(invalid)?;
+    let input: Input = serde_json::from_value(raw.clone()).map_err(invalid)?;
+    Ok((input, raw))
+}
+
+/// The output and lifecycle record of a JSON run.
+fn run_json_output(result: &RunResult) -> Result<(String, String)> {
+    Ok((
+        serde_json::to_string(&result.output)?,
+        serde_json::to_string(&result.lifecycle.to_json())?,
+    ))
+}
@@ -1,46 +1,281 @@
-//! A simple in-memory caching adapter.  Wraps another network adapter and
-//! caches responses by URL.  Only the first request for a given URL hits
-//! the underlying adapter; subsequent requests return the cached
-//! response.  The cache persists for the lifetime of the adapter.
+//! An in-memory caching adapter.  Wraps another network adapter and serves
+//! repeated `GET`s from a [`ResponseCache`] that the [`crate::Runtime`]
+//! keeps across its runs.  Other methods change server state, so they always
+//! pass through.  Hits are counted in the shared `AdapterStats` so the run
+//! summary can report them, and every lookup is logged there so the runtime
+//! can emit it as a `cache_hit`, `cache_revalidated` or `cache_miss` event.
+//!
+//! Responses are keyed by URL and request headers, so a request asking for
+//! another representation (`Accept`) or made with other credentials
+//! (`Authorization`) is never served someone else's variant.  Credential
+//! values are hashed before they become part of a key.  [`CacheLimits`] bound
+//! how long entries live and how many are kept; when the cache is full the
+//! least recently used entry is evicted.
+//!
+//! Entry age is measured with the runtime's clock when it has one.  Without
+//! one the cache keeps a logical clock that advances one tick per lookup, so
+//! runs stay deterministic and `ttl_millis` counts lookups instead.
+//!
+//! An expired entry whose response carried an `ETag` or `Last-Modified` is
+//! not dropped right away.  The next lookup sends a conditional `GET` with
+//! `If-None-Match` or `If-Modified-Since`; a `304 Not Modified` answer
+//! renews the entry and serves its body, and any other answer replaces it.
 
+use crate::adapter_manager::AdapterStats;
 use anyhow::Result;
-use service::api::{NetworkAdapter, NetworkResponse};
-use std::cell::RefCell;
+use serde::{Deserialize, Serialize};
+use service::api::{AdapterCapabilities, AsyncNetworkAdapter, FetchFuture, NetworkResponse};
+use service::model::Request;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Request headers whose values are hashed in cache keys.
+const CREDENTIAL_HEADERS: [&str; 3] = ["authorization", "cookie", "proxy-authorization"];
+
+/// How long cached responses live and how many are kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheLimits {
+    /// Age after which an entry is fetched again; `None` keeps entries until
+    /// they are evicted.
+    #[serde(default)]
+    pub ttl_millis: Option<u64>,
+    /// Most entries kept; `None` is unbounded.
+    #[serde(default)]
+    pub max_entries: Option<u32>,
+}
+
+/// How a cache lookup was answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheOutcome {
+    /// A fresh entry was served.
+    Hit,
+    /// An expired entry was confirmed with a `304 Not Modified` and served.
+    Revalidated,
+    /// The response came from the adapter.
+    Miss,
+}
+
+impl CacheOutcome {
+    /// The type of the event recording the lookup.
+    pub fn event_type(self) -> &'static str {
+        match self {
+            CacheOutcome::Hit => "cache_hit",
+            CacheOutcome::Revalidated => "cache_revalidated",
+            CacheOutcome::Miss => "cache_miss",
+        }
+    }
+}
+
+/// One cache lookup: the URL looked up and how it was answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheLookup {
+    pub url: String,
+    pub outcome: CacheOutcome,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    response: NetworkResponse,
+    stored_at: u64,
+    last_used: u64,
+}
+
+/// Cached responses shared by every run of a runtime.
+#[derive(Debug, Default)]
+pub struct ResponseCache {
+    entries: RefCell<HashMap<String, CacheEntry>>,
+    /// Logical clock used when the runtime has no wall clock.
+    ticks: Cell<u64>,
+    /// Lookup counter ordering entries by recent use.
+    uses: Cell<u64>,
+}
+
+impl ResponseCache {
+    /// Entries currently cached, expired ones included until looked up.
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+}
+
+/// The cache key of `request`: its URL plus its headers, sorted by name,
+/// with credential values hashed.
+pub fn cache_key(request: &Request) -> String {
+    let mut headers: Vec<(String, String)> = request
+        .headers
+        .iter()
+        .map(|(name, value)| {
+            let name = name.to_ascii_lowercase();
+            let value = if CREDENTIAL_HEADERS.contains(&name.as_str()) {
+                format!("#{:016x}", fnv1a(value))
+            } else {
+                value.clone()
+            };
+            (name, value)
+        })
+        .collect();
+    headers.sort();
+    let mut key = request.url.clone();
+    for (name, value) in headers {
+        key.push('\n');
+        key.push_str(&name);
+        key.push_str(": ");
+        key.push_str(&value);
+    }
+    key
+}
+
+fn fnv1a(value: &str) -> u64 {
+    value.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
 
 pub struct CacheAdapter {
-    inner: Box<dyn NetworkAdapter>,
-    cache: RefCell<HashMap<String, NetworkResponse>>,
+    inner: Box<dyn AsyncNetworkAdapter>,
+    limits: CacheLimits,
+    cache: Rc<ResponseCache>,
+    /// The runtime clock's time at the start of the run, if it has one.
+    now_millis: Option<u64>,
+    stats: Rc<AdapterStats>,
 }
 
 impl CacheAdapter {
-    pub fn new(inner: Box<dyn NetworkAdapter>) -> Self {
+    pub fn new(
+        inner: Box<dyn AsyncNetworkAdapter>,
+        limits: CacheLimits,
+        cache: Rc<ResponseCache>,
+        now_millis: Option<u64>,
+        stats: Rc<AdapterStats>,
+    ) -> Self {
         Self {
             inner,
-            cache: RefCell::new(HashMap::new()),
+            limits,
+            cache,
+            now_millis,
+            stats,
         }
     }
-}
 
-impl NetworkAdapter for CacheAdapter {
-    fn fetch(&self, url: &str, headers: &HashMap<String, String>) -> Result<NetworkResponse> {
-        if let Some(resp) = self.cache.borrow().get(url) {
-            // Return a clone of the cached response.
-            return Ok(NetworkResponse {
-                status: resp.status,
-                headers: resp.headers.clone(),
-                body: resp.body.clone(),
-            });
+    /// The current time for entry ages: the wall clock, or the next tick.
+    fn now(&self) -> u64 {
+        self.now_millis.unwrap_or_else(|| {
+            let tick = self.cache.ticks.get() + 1;
+            self.cache.ticks.set(tick);
+            tick
+        })
+    }
+
+    fn log(&self, request: &Request, outcome: CacheOutcome) {
+        if outcome != CacheOutcome::Miss {
+            self.stats.cache_hits.set(self.stats.cache_hits.get() + 1);
+        }
+        self.stats.cache_log.borrow_mut().push(CacheLookup {
+            url: request.url.clone(),
+            outcome,
+        });
+    }
+
+    /// `request` made conditional on the validators of `cached`, if it has
+    /// any.
+    fn conditional(request: &Request, cached: &NetworkResponse) -> Option<Request> {
+        if let Some(etag) = cached.header("etag") {
+            return Some(request.with_header("If-None-Match", etag));
+        }
+        let modified = cached.header("last-modified")?;
+        Some(request.with_header("If-Modified-Since", modified))
+    }
+
+    fn next_use(&self) -> u64 {
+        let uses = self.cache.uses.get() + 1;
+        self.cache.uses.set(uses);
+        uses
+    }
+
+    async fn fetch_through(&self, request: &Request) -> Result<NetworkResponse> {
+        if request.method() != "GET" {
+            return self.inner.fetch(request).await;
+        }
+        let key = cache_key(request);
+        let now = self.now();
+        let mut conditional = None;
+        {
+            let mut entries = self.cache.entries.borrow_mut();
+            if let Some(entry) = entries.get_mut(&key) {
+                let fresh = match self.limits.ttl_millis {
+                    Some(ttl) => now.saturating_sub(entry.stored_at) < ttl,
+                    None => true,
+                };
+                if fresh {
+                    entry.last_used = self.next_use();
+                    self.log(request, CacheOutcome::Hit);
+                    return Ok(entry.response.clone());
+                }
+                conditional = Self::conditional(request, &entry.response);
+                if conditional.is_none() {
+                    entries.remove(&key);
+                }
+            }
+        }
+        let result = match &conditional {
+            Some(conditional) => self.inner.fetch(conditional).await,
+            None => self.inner.fetch(request).await,
+        };
+        let mut entries = self.cache.entries.borrow_mut();
+        if let (Some(_), Ok(resp)) = (&conditional, &result) {
+            if resp.status == 304 {
+                if let Some(entry) = entries.get_mut(&key) {
+                    entry.stored_at = now;
+                    entry.last_used = self.next_use();
+                    self.log(request, CacheOutcome::Revalidated);
+                    return Ok(entry.response.clone());
+                }
+            }
+        }
+        self.log(request, CacheOutcome::Miss);
+        // A failed revalidation keeps the expired entry for the next lookup.
+        let resp = result?;
+        entries.remove(&key);
+        if let Some(max) = self.limits.max_entries {
+            if max == 0 {
+                return Ok(resp);
+            }
+            while entries.len() >= max as usize {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| key.clone())
+                    .expect("a full cache has entries");
+                entries.remove(&oldest);
+            }
         }
-        let resp = self.inner.fetch(url, headers)?;
-        self.cache.borrow_mut().insert(
-            url.to_string(),
-            NetworkResponse {
-                status: resp.status,
-                headers: resp.headers.clone(),
-                body: resp.body.clone(),
+        // Entries are served as they were decoded, so hits decode nothing.
+        entries.insert(
+            key,
+            CacheEntry {
+                response: NetworkResponse {
+                    decoded: None,
+                    ..resp.clone()
+                },
+                stored_at: now,
+                last_used: self.next_use(),
             },
         );
         Ok(resp)
     }
 }
+
+impl AsyncNetworkAdapter for CacheAdapter {
+    fn fetch<'a>(&'a self, request: &'a Request) -> FetchFuture<'a> {
+        Box::pin(self.fetch_through(request))
+    }
+
+    fn capabilities(&self) -> AdapterCapabilities {
+        self.inner.capabilities()
+    }
+}
@@ -0,0 +1,187 @@
+//! Recorded HTTP exchanges for offline runs.  A [`RecordingAdapter`] wraps a
+//! real adapter and writes every request it answers to a cassette file; a
+//! [`crate::ReplayAdapter`] loaded from that file then serves the same
+//! responses without network access, so CI runs are reproducible.
+//!
+//! Exchanges are matched by method and URL.  Request headers and bodies are
+//! not recorded, so credentials never reach the cassette.  Transport errors
+//! are not recorded either: a request that failed while recording has no
+//! response to replay.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use service::api::{AdapterCapabilities, NetworkAdapter, NetworkResponse};
+use service::model::Request;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// A cassette file: the recorded exchanges, in the order they happened.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+/// One recorded request and the response it got.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Interaction {
+    pub method: String,
+    pub url: String,
+    pub response: RecordedResponse,
+}
+
+/// A response as stored in a cassette.  Headers are sorted so a cassette
+/// recorded twice from the same responses is the same file.  A UTF-8 body
+/// is stored as text in `body`, and any other as base64 in `bodyBase64`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: String,
+    #[serde(
+        default,
+        rename = "bodyBase64",
+        skip_serializing_if = "Option::is_none",
+        with = "base64_bytes"
+    )]
+    pub body_base64: Option<Vec<u8>>,
+}
+
+impl From<&NetworkResponse> for RecordedResponse {
+    fn from(response: &NetworkResponse) -> Self {
+        let (body, body_base64) = match response.text() {
+            Ok(text) => (text.to_string(), None),
+            Err(_) => (String::new(), Some(response.body.clone())),
+        };
+        Self {
+            status: response.status,
+            headers: response.headers.clone().into_iter().collect(),
+            body,
+            body_base64,
+        }
+    }
+}
+
+impl From<&RecordedResponse> for NetworkResponse {
+    fn from(response: &RecordedResponse) -> Self {
+        let body = match &response.body_base64 {
+            Some(bytes) => bytes.clone(),
+            None => response.body.clone().into_bytes(),
+        };
+        Self {
+            status: response.status,
+            headers: response.headers.clone().into_iter().collect(),
+            body,
+            decoded: None,
+        }
+    }
+}
+
+/// `bodyBase64` as standard, padded base64.  A cassette with a malformed
+/// value fails to load.
+mod base64_bytes {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        bytes: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => serializer.serialize_str(&STANDARD.encode(bytes)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        let Some(text) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        STANDARD
+            .decode(text)
+            .map(Some)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl Cassette {
+    /// Read the cassette at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading cassette {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("parsing cassette {}", path.display()))
+    }
+
+    /// Write the cassette to `path`, replacing the file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut text = serde_json::to_string_pretty(self)?;
+        text.push('\n');
+        std::fs::write(path, text).with_context(|| format!("writing cassette {}", path.display()))
+    }
+}
+
+/// Records what `inner` answers to a cassette file.  The file is rewritten
+/// after every response, so it is complete even if the host stops midway.
+pub struct RecordingAdapter {
+    inner: Box<dyn NetworkAdapter>,
+    path: PathBuf,
+    cassette: RefCell<Cassette>,
+}
+
+impl RecordingAdapter {
+    /// Record into a new cassette at `path`, replacing any file there on the
+    /// first response.
+    pub fn new(inner: Box<dyn NetworkAdapter>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            path: path.into(),
+            cassette: RefCell::default(),
+        }
+    }
+
+    /// Record after the interactions already in the cassette at `path`, or
+    /// into a new one if there is no file yet.
+    pub fn append(inner: Box<dyn NetworkAdapter>, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let cassette = if path.exists() {
+            Cassette::load(&path)?
+        } else {
+            Cassette::default()
+        };
+        Ok(Self {
+            inner,
+            path,
+            cassette: RefCell::new(cassette),
+        })
+    }
+
+    /// The interactions recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.cassette.borrow().clone()
+    }
+}
+
+impl NetworkAdapter for RecordingAdapter {
+    fn fetch(&self, request: &Request) -> Result<NetworkResponse> {
+        let response = self.inner.fetch(request)?;
+        let mut cassette = self.cassette.borrow_mut();
+        cassette.interactions.push(Interaction {
+            method: request.method(),
+            url: request.url.clone(),
+            response: RecordedResponse::from(&response),
+        });
+        cassette.save(&self.path)?;
+        Ok(response)
+    }
+
+    fn capabilities(&self) -> AdapterCapabilities {
+        self.inner.capabilities()
+    }
+}
@@ -0,0 +1,203 @@
+//! A wrapper adapter that stops calling a host after repeated failures.  Each
+//! host has its own circuit.  A circuit opens after `failure_threshold`
+//! consecutive failures.  While it is open, requests to that host fail at
+//! once with [`CircuitOpen`] and never reach the adapter.  The cooldown is
+//! counted in requests rather than time, so runs stay deterministic: after
+//! `cooldown_requests` requests have been failed fast, the next one is let
+//! through as a trial.  A trial that succeeds closes the circuit; one that
+//! fails opens it again.
+//!
+//! A failure is a transport error or a 5xx status; a 4xx is the caller's
+//! problem, not the host's.  The wrapper sits outside the retry wrapper, so
+//! a request that was retried counts once.  Circuits live in the
+//! [`crate::Runtime`] and outlast single runs; every state change is logged
+//! in the shared `AdapterStats` so the runtime can emit it as a
+//! `circuit_state` event.
+
+use crate::adapter_manager::AdapterStats;
+use crate::thread_manager::host_of;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use service::api::{
+    AdapterCapabilities, AsyncNetworkAdapter, FetchFuture, NetworkResponse, StreamFuture,
+    StreamingResponse,
+};
+use service::model::Request;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+
+/// When circuits open and how long they stay open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CircuitBreakerPolicy {
+    /// Consecutive failures that open a host's circuit; 0 leaves the
+    /// circuit breaker off.
+    #[serde(default)]
+    pub failure_threshold: u32,
+    /// Requests failed fast while open before a trial request is let
+    /// through.
+    #[serde(default)]
+    pub cooldown_requests: u32,
+}
+
+/// The state of one host's circuit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests reach the adapter.
+    #[default]
+    Closed,
+    /// Requests fail fast.
+    Open,
+    /// One trial request reaches the adapter.
+    HalfOpen,
+}
+
+impl CircuitState {
+    /// The name used in `circuit_state` events.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half_open",
+        }
+    }
+}
+
+/// A change in the state of a host's circuit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitTransition {
+    pub host: String,
+    pub from: CircuitState,
+    pub to: CircuitState,
+}
+
+/// A request failed fast because its host's circuit is open.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("circuit for {host} is open")]
+pub struct CircuitOpen {
+    pub host: String,
+}
+
+#[derive(Debug, Default)]
+struct HostCircuit {
+    state: CircuitState,
+    failures: u32,
+    rejected: u32,
+}
+
+/// The circuits of every host a runtime has called.
+#[derive(Debug, Default)]
+pub struct CircuitBreakers {
+    circuits: RefCell<HashMap<String, HostCircuit>>,
+}
+
+impl CircuitBreakers {
+    /// The state of `host`'s circuit; hosts never called are closed.
+    pub fn state(&self, host: &str) -> CircuitState {
+        self.circuits
+            .borrow()
+            .get(&host.to_ascii_lowercase())
+            .map_or(CircuitState::Closed, |circuit| circuit.state)
+    }
+}
+
+pub struct CircuitBreakerAdapter {
+    inner: Box<dyn AsyncNetworkAdapter>,
+    policy: CircuitBreakerPolicy,
+    circuits: Rc<CircuitBreakers>,
+    stats: Rc<AdapterStats>,
+}
+
+impl CircuitBreakerAdapter {
+    pub fn new(
+        inner: Box<dyn AsyncNetworkAdapter>,
+        policy: CircuitBreakerPolicy,
+        circuits: Rc<CircuitBreakers>,
+        stats: Rc<AdapterStats>,
+    ) -> Self {
+        Self {
+            inner,
+            policy,
+            circuits,
+            stats,
+        }
+    }
+
+    fn transition(&self, host: &str, circuit: &mut HostCircuit, to: CircuitState) {
+        self.stats.circuit_log.borrow_mut().push(CircuitTransition {
+            host: host.to_string(),
+            from: circuit.state,
+            to,
+        });
+        circuit.state = to;
+    }
+
+    /// Send `request` with `send` unless its host's circuit is open, and
+    /// count the response, whose status `status` reads, against the circuit.
+    async fn fetch_through<'a, T>(
+        &self,
+        request: &'a Request,
+        send: impl FnOnce(&'a Request) -> Pin<Box<dyn Future<Output = Result<T>> + 'a>>,
+        status: fn(&T) -> u16,
+    ) -> Result<T> {
+        let host = host_of(&request.url);
+        {
+            let mut circuits = self.circuits.circuits.borrow_mut();
+            let circuit = circuits.entry(host.clone()).or_default();
+            if circuit.state == CircuitState::Open {
+                if circuit.rejected < self.policy.cooldown_requests {
+                    circuit.rejected += 1;
+                    return Err(CircuitOpen { host }.into());
+                }
+                self.transition(&host, circuit, CircuitState::HalfOpen);
+            }
+        }
+        let result = send(request).await;
+        let failed = match &result {
+            Ok(resp) => status(resp) >= 500,
+            Err(_) => true,
+        };
+        let mut circuits = self.circuits.circuits.borrow_mut();
+        let circuit = circuits.entry(host.clone()).or_default();
+        if !failed {
+            circuit.failures = 0;
+            if circuit.state == CircuitState::HalfOpen {
+                self.transition(&host, circuit, CircuitState::Closed);
+            }
+            return result;
+        }
+        circuit.failures = circuit.failures.saturating_add(1);
+        if circuit.state == CircuitState::HalfOpen
+            || circuit.failures >= self.policy.failure_threshold
+        {
+            circuit.rejected = 0;
+            self.transition(&host, circuit, CircuitState::Open);
+        }
+        result
+    }
+}
+
+impl AsyncNetworkAdapter for CircuitBreakerAdapter {
+    fn fetch<'a>(&'a self, request: &'a Request) -> FetchFuture<'a> {
+        Box::pin(self.fetch_through(
+            request,
+            |request| self.inner.fetch(request),
+            |resp: &NetworkResponse| resp.status,
+        ))
+    }
+
+    fn fetch_stream<'a>(&'a self, request: &'a Request) -> StreamFuture<'a> {
+        Box::pin(self.fetch_through(
+            request,
+            |request| self.inner.fetch_stream(request),
+            |resp: &StreamingResponse| resp.status,
+        ))
+    }
+
+    fn capabilities(&self) -> AdapterCapabilities {
+        self.inner.capabilities()
+    }
+}
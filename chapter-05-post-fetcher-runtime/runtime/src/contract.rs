@@ -0,0 +1,371 @@
+//! Service contract loaded from `contracts/service.contract.json`.  Like the
+//! runtime policy, only the sections the runtime acts on are parsed, but the
+//! whole document is first checked against
+//! `contracts/service.contract.schema.json`.
+
+use crate::access_policy::check_url;
+use crate::builder::{Backoff, CachePolicy, RetryPolicy};
+use crate::cache_adapter::CacheLimits;
+use crate::circuit_breaker::CircuitBreakerPolicy;
+use crate::event_schema::EventSchemas;
+use crate::header_policy::HeaderPolicy;
+use crate::registry::DEFAULT_ADAPTER;
+use jsonschema::error::ValidationErrorKind;
+use jsonschema::{Draft, JSONSchema, ValidationError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use service::model::{InputMode, SlaTarget};
+use std::fmt;
+use std::sync::OnceLock;
+
+/// The service contract shipped with this example, embedded at build time.
+const SERVICE_CONTRACT_JSON: &str = include_str!("../../contracts/service.contract.json");
+
+/// The schema every service contract must match, embedded at build time.
+const SERVICE_CONTRACT_SCHEMA_JSON: &str =
+    include_str!("../../contracts/service.contract.schema.json");
+
+/// Why a service contract could not be loaded.
+#[derive(Debug, thiserror::Error)]
+pub enum ContractError {
+    /// The document is not JSON.
+    #[error("service contract could not be parsed: {0}")]
+    Parse(#[from] serde_json::Error),
+    /// The document does not match the contract schema, one diagnostic per
+    /// violation.
+    #[error("service contract does not match its schema:{}", list(.0))]
+    Schema(Vec<ContractDiagnostic>),
+}
+
+/// One schema violation in a service contract.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractDiagnostic {
+    /// JSON pointer to the offending value, e.g. `/adapter/retries`; empty
+    /// for the document itself.
+    pub pointer: String,
+    /// What the schema asks for, as the failing keyword and its schema value,
+    /// e.g. `minimum 0`.
+    pub expected: String,
+    /// The value at `pointer`, or `None` when a required one is missing.
+    pub found: Option<Value>,
+}
+
+impl fmt::Display for ContractDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() {
+            "(root)"
+        } else {
+            &self.pointer
+        };
+        match &self.found {
+            Some(found) => write!(f, "{pointer}: expected {}, found {found}", self.expected),
+            None => write!(f, "{pointer}: expected {}, found nothing", self.expected),
+        }
+    }
+}
+
+fn list(diagnostics: &[ContractDiagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| format!("\n  {diagnostic}"))
+        .collect()
+}
+
+/// Subset of the service contract interpreted by the runtime.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ServiceContract {
+    /// Completion target used to classify each run in the summary and the
+    /// lifecycle record.  `None` leaves runs unclassified.
+    #[serde(default)]
+    pub sla: Option<SlaTarget>,
+    /// `strict` rejects every run whose input carries undeclared fields;
+    /// `permissive`, the default, leaves the choice to the input.
+    #[serde(rename = "inputMode", default)]
+    pub input_mode: InputMode,
+    /// JSON Schema of the input document.  Strict mode checks field names
+    /// against its `properties`.
+    #[serde(default)]
+    pub input: Option<Value>,
+    /// How the network adapter is wrapped and which hosts it may reach.
+    #[serde(default)]
+    pub adapter: AdapterPolicy,
+    /// Budgets the host fetch adapter enforces on every request.
+    #[serde(default)]
+    pub execution: ExecutionLimits,
+    /// Which request headers runs accept.
+    #[serde(default)]
+    pub headers: HeaderPolicy,
+    /// The schemas event payloads must match, by event type.
+    #[serde(default)]
+    pub events: EventSchemas,
+    /// Which registered adapter implementation each binding uses.
+    #[serde(default)]
+    pub bindings: ContractBindings,
+}
+
+/// The contract's `bindings` section.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ContractBindings {
+    /// Name of the adapter bound to `network.fetch`; `None` binds
+    /// [`DEFAULT_ADAPTER`].
+    #[serde(rename = "network.fetch", default)]
+    pub network_fetch: Option<String>,
+}
+
+impl ContractBindings {
+    /// The name of the adapter `network.fetch` is bound to.
+    pub fn network_fetch(&self) -> &str {
+        self.network_fetch.as_deref().unwrap_or(DEFAULT_ADAPTER)
+    }
+}
+
+/// The contract's `execution` section.  A fetch over any limit fails with
+/// `network.budget_exceeded`; an absent limit is not enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionLimits {
+    /// Largest response body accepted, in bytes.
+    #[serde(default)]
+    pub max_body_bytes: Option<u64>,
+    /// Most redirects followed before giving up.
+    #[serde(default)]
+    pub max_redirects: Option<u32>,
+    /// Time allowed for the whole request, response body included.
+    #[serde(default)]
+    pub timeout_millis: Option<u64>,
+}
+
+/// The contract's `adapter` section.  The resolved policy of each run, after
+/// any settings made on the [`crate::RuntimeBuilder`], is recorded in its
+/// lifecycle record.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdapterPolicy {
+    /// Further attempts after a failed request; 0 leaves the retry wrapper
+    /// off.
+    #[serde(default)]
+    pub retries: u32,
+    /// Response statuses that are retried; empty retries every non-2xx
+    /// status but 304.
+    #[serde(default)]
+    pub retry_on: Vec<u16>,
+    /// Whether requests that are not idempotent, such as `POST`, are
+    /// retried too.
+    #[serde(default)]
+    pub retry_non_idempotent: bool,
+    /// Wait between retry attempts.
+    #[serde(default)]
+    pub backoff: Backoff,
+    /// When a host's circuit opens and how long it stays open.
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerPolicy,
+    /// Whether `GET` responses are cached across the runs of a runtime.
+    #[serde(default)]
+    pub cache: bool,
+    /// How long cached responses live and how many are kept.
+    #[serde(default)]
+    pub cache_limits: CacheLimits,
+    /// Hosts requests may go to, without port; empty allows any host.
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    /// URL schemes requests may use; empty allows any scheme.
+    #[serde(default)]
+    pub allowed_schemes: Vec<String>,
+}
+
+impl AdapterPolicy {
+    /// This policy with the runtime's explicit settings, if any, in place of
+    /// the contract's.
+    pub fn with_overrides(
+        mut self,
+        retry: Option<RetryPolicy>,
+        circuit_breaker: Option<CircuitBreakerPolicy>,
+        cache: Option<CachePolicy>,
+        cache_limits: Option<CacheLimits>,
+    ) -> Self {
+        if let Some(retry) = retry {
+            self.retries = retry.max_attempts.saturating_sub(1);
+            self.retry_on = retry.retryable_statuses;
+            self.retry_non_idempotent = !retry.idempotent_only;
+            if let Some(backoff) = retry.backoff {
+                self.backoff = backoff;
+            }
+        }
+        if let Some(circuit_breaker) = circuit_breaker {
+            self.circuit_breaker = circuit_breaker;
+        }
+        if let Some(cache) = cache {
+            self.cache = cache == CachePolicy::InMemory;
+        }
+        if let Some(cache_limits) = cache_limits {
+            self.cache_limits = cache_limits;
+        }
+        self
+    }
+
+    /// The retry settings of this policy.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            idempotent_only: !self.retry_non_idempotent,
+            backoff: Some(self.backoff),
+            ..RetryPolicy::up_to(self.retries).retry_on(self.retry_on.iter().copied())
+        }
+    }
+
+    /// Whether `url` passes the scheme and host allowlists; see
+    /// [`crate::check_url`].
+    pub fn allows(&self, url: &str) -> bool {
+        check_url(self, url).is_ok()
+    }
+}
+
+impl ServiceContract {
+    /// Parse a service contract document, after checking it against the
+    /// contract schema.  A document that does not match fails with every
+    /// violation listed, so a misconfigured deployment fails before any run.
+    pub fn from_json(json: &str) -> Result<Self, ContractError> {
+        let document: Value = serde_json::from_str(json)?;
+        let diagnostics = diagnose(&document);
+        if !diagnostics.is_empty() {
+            return Err(ContractError::Schema(diagnostics));
+        }
+        Ok(serde_json::from_value(document)?)
+    }
+
+    /// The contract bundled with the example.
+    pub fn bundled() -> Self {
+        Self::from_json(SERVICE_CONTRACT_JSON).expect("bundled service contract should parse")
+    }
+
+    /// Whether a run with `requested` input mode validates field names.
+    pub fn strict(&self, requested: InputMode) -> bool {
+        self.input_mode == InputMode::Strict || requested == InputMode::Strict
+    }
+
+    /// Dotted paths of the fields in `input` that the input schema does not
+    /// declare, sorted by key.  An object whose schema lists
+    /// `properties` accepts only those, unless `additionalProperties`
+    /// allows more; objects without `properties`, such as `headers`, are
+    /// free-form.  Array items are checked against `items` and named by
+    /// index, as in `requests.0.retries`.
+    pub fn unexpected_fields(&self, input: &Value) -> Vec<String> {
+        let mut fields = Vec::new();
+        if let Some(schema) = &self.input {
+            collect_unexpected(schema, schema, input, "", &mut fields);
+        }
+        fields
+    }
+}
+
+/// The schema violations in a contract document.
+fn diagnose(document: &Value) -> Vec<ContractDiagnostic> {
+    static SCHEMA: OnceLock<(Value, JSONSchema)> = OnceLock::new();
+    let (source, schema) = SCHEMA.get_or_init(|| {
+        let source: Value = serde_json::from_str(SERVICE_CONTRACT_SCHEMA_JSON)
+            .expect("contract schema should parse");
+        let schema = JSONSchema::options()
+            .with_draft(Draft::Draft7)
+            .compile(&source)
+            .expect("contract schema should compile");
+        (source, schema)
+    });
+    match schema.validate(document) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors
+            .flat_map(|error| diagnostics_of(source, &error))
+            .collect(),
+    }
+}
+
+/// Diagnostics for one validation error: one per unexpected or missing
+/// property, otherwise one naming the failing keyword.
+pub(crate) fn diagnostics_of(source: &Value, error: &ValidationError) -> Vec<ContractDiagnostic> {
+    let pointer = error.instance_path.to_string();
+    let schema_path = error.schema_path.to_string();
+    let child = |property: &str| {
+        let escaped = property.replace('~', "~0").replace('/', "~1");
+        format!("{pointer}/{escaped}")
+    };
+    match &error.kind {
+        ValidationErrorKind::AdditionalProperties { unexpected } => {
+            let parent = schema_path.trim_end_matches("/additionalProperties");
+            let declared: Vec<&str> = source
+                .pointer(&format!("{parent}/properties"))
+                .and_then(Value::as_object)
+                .map(|properties| properties.keys().map(String::as_str).collect())
+                .unwrap_or_default();
+            unexpected
+                .iter()
+                .map(|property| ContractDiagnostic {
+                    pointer: child(property),
+                    expected: format!("a declared property ({})", declared.join(", ")),
+                    found: error.instance.get(property).cloned(),
+                })
+                .collect()
+        }
+        ValidationErrorKind::Required { property } => vec![ContractDiagnostic {
+            pointer: child(property.as_str().unwrap_or_default()),
+            expected: "a value".to_string(),
+            found: None,
+        }],
+        _ => {
+            let keyword = schema_path.rsplit('/').next().unwrap_or_default();
+            let expected = match source.pointer(&schema_path) {
+                Some(value) => format!("{keyword} {value}"),
+                None => keyword.to_string(),
+            };
+            vec![ContractDiagnostic {
+                pointer,
+                expected,
+                found: Some(error.instance.clone().into_owned()),
+            }]
+        }
+    }
+}
+
+fn collect_unexpected(
+    root: &Value,
+    schema: &Value,
+    value: &Value,
+    path: &str,
+    fields: &mut Vec<String>,
+) {
+    // Follow a local `$ref` such as `#/properties/request` from the input
+    // schema's root.
+    let schema = match schema.get("$ref").and_then(Value::as_str) {
+        Some(reference) => match reference.strip_prefix('#').and_then(|at| root.pointer(at)) {
+            Some(target) => target,
+            None => return,
+        },
+        None => schema,
+    };
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (index, item) in array.iter().enumerate() {
+            collect_unexpected(root, items, item, &format!("{path}.{index}"), fields);
+        }
+        return;
+    }
+    let (Some(properties), Some(object)) = (
+        schema.get("properties").and_then(Value::as_object),
+        value.as_object(),
+    ) else {
+        return;
+    };
+    let open = schema
+        .get("additionalProperties")
+        .is_some_and(|extra| *extra != Value::Bool(false));
+    for (key, child) in object {
+        let child_path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{path}.{key}")
+        };
+        match properties.get(key) {
+            Some(child_schema) => {
+                collect_unexpected(root, child_schema, child, &child_path, fields)
+            }
+            None if !open => fields.push(child_path),
+            None => {}
+        }
+    }
+}
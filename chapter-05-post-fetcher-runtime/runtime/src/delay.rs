@@ -0,0 +1,102 @@
+//! Delay providers for the retry wrapper's backoff.  Production hosts wait
+//! for real with [`SleepDelay`]; tests and replays use [`NoDelay`] or
+//! [`LogicalDelay`], which return at once, so they stay fast and
+//! deterministic.  Either way the event log records the same delays.
+
+use service::api::DelayProvider;
+use std::cell::RefCell;
+#[cfg(not(target_arch = "wasm32"))]
+use std::future::Future;
+#[cfg(not(target_arch = "wasm32"))]
+use std::pin::Pin;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
+use std::task::{Context, Poll, Waker};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+
+/// Sleeps the current thread, or in async runs a timer thread, so the
+/// executor stays free.  The default on native targets.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct SleepDelay;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DelayProvider for SleepDelay {
+    fn delay(&self, millis: u64) {
+        std::thread::sleep(Duration::from_millis(millis));
+    }
+
+    fn delay_async(&self, millis: u64) -> Pin<Box<dyn Future<Output = ()> + '_>> {
+        Box::pin(Timer {
+            deadline: Instant::now() + Duration::from_millis(millis),
+            waker: None,
+        })
+    }
+}
+
+/// Resolves at `deadline`.  The first poll starts a thread that sleeps until
+/// then and wakes whichever task polled last, so it needs no particular
+/// executor.
+#[cfg(not(target_arch = "wasm32"))]
+struct Timer {
+    deadline: Instant,
+    waker: Option<Arc<Mutex<Waker>>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Future for Timer {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let now = Instant::now();
+        if now >= self.deadline {
+            return Poll::Ready(());
+        }
+        match &self.waker {
+            Some(waker) => cx.waker().clone_into(&mut waker.lock().unwrap()),
+            None => {
+                let waker = Arc::new(Mutex::new(cx.waker().clone()));
+                let wait = self.deadline - now;
+                let shared = Arc::clone(&waker);
+                std::thread::spawn(move || {
+                    std::thread::sleep(wait);
+                    shared.lock().unwrap().wake_by_ref();
+                });
+                self.waker = Some(waker);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+/// Returns at once.  The default on wasm targets, which cannot block.
+pub struct NoDelay;
+
+impl DelayProvider for NoDelay {
+    fn delay(&self, _millis: u64) {}
+}
+
+/// Returns at once, remembering every delay asked for in order.
+#[derive(Debug, Default)]
+pub struct LogicalDelay {
+    delays: RefCell<Vec<u64>>,
+}
+
+impl LogicalDelay {
+    /// The delays asked for so far, in milliseconds.
+    pub fn delays(&self) -> Vec<u64> {
+        self.delays.borrow().clone()
+    }
+
+    /// Their sum: the time a sleeping provider would have waited.
+    pub fn total_millis(&self) -> u64 {
+        self.delays.borrow().iter().sum()
+    }
+}
+
+impl DelayProvider for LogicalDelay {
+    fn delay(&self, millis: u64) {
+        self.delays.borrow_mut().push(millis);
+    }
+}
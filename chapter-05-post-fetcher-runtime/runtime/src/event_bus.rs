@@ -1,33 +1,266 @@
-use serde_json::Value;
-use service::model::Event;
+use crate::event_schema::EventSchemas;
+use crate::metadata::LifecycleRecord;
+use crate::privacy::PrivacyPolicy;
+use crate::secrets::SecretsPolicy;
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Map, Value};
+use service::api::{format_wall_time, TimeSource};
+use service::messages::{error_event_data, ErrorCode};
+use service::model::{Event, TraceContext};
+use std::cell::RefCell;
+use std::io::Write;
+use std::sync::mpsc::Sender;
+
+/// Largest size, in bytes of serialized JSON, of the attributes a decorator
+/// may attach to one event.
+pub const HOST_ATTRIBUTES_LIMIT: usize = 1024;
+
+/// Host middleware invoked before each event is stored.  The attributes it
+/// returns, such as a deployment id or region, are attached to the event
+/// under `host`; an empty map attaches nothing.  Values must be strings,
+/// numbers, booleans or null.
+pub trait EventDecorator {
+    fn decorate(&self, event: &Event) -> Map<String, Value>;
+}
+
+impl<F: Fn(&Event) -> Map<String, Value>> EventDecorator for F {
+    fn decorate(&self, event: &Event) -> Map<String, Value> {
+        self(event)
+    }
+}
+
+/// Receives events one at a time and in order: as they are emitted when
+/// subscribed to an [`EventBus`], or once the run has finished when added
+/// with [`crate::RuntimeBuilder::sink`].  Either way events arrive as they
+/// appear in the output: decorated, and redacted when privacy rules are
+/// active.
+pub trait EventSink {
+    fn record(&self, event: &Event);
+
+    /// Receives the lifecycle record of each run that returns one, after
+    /// the run's last event.  Ignored unless overridden.
+    fn record_lifecycle(&self, _lifecycle: &LifecycleRecord) {}
+}
+
+impl<F: Fn(&Event)> EventSink for F {
+    fn record(&self, event: &Event) {
+        self(event)
+    }
+}
+
+/// Sends each event down a channel, for a consumer on another thread.  A
+/// hung-up receiver is ignored: the run never fails because nobody listens.
+impl EventSink for Sender<Event> {
+    fn record(&self, event: &Event) {
+        let _ = self.send(event.clone());
+    }
+}
+
+/// Writes each event as one line of JSON, flushing after each, to stdout,
+/// a file or any other writer.  Write errors are ignored, as for a
+/// channel whose receiver hung up.
+pub struct JsonLines<W: Write>(RefCell<W>);
+
+impl<W: Write> JsonLines<W> {
+    pub fn new(writer: W) -> Self {
+        Self(RefCell::new(writer))
+    }
+
+    /// The writer, once the runs are done.
+    pub fn into_inner(self) -> W {
+        self.0.into_inner()
+    }
+}
+
+impl<W: Write> EventSink for JsonLines<W> {
+    fn record(&self, event: &Event) {
+        let mut writer = self.0.borrow_mut();
+        if let Ok(line) = serde_json::to_string(event) {
+            let _ = writeln!(writer, "{line}").and_then(|()| writer.flush());
+        }
+    }
+}
 
 /// Deterministic event bus.  Maintains a logical clock and appends events
 /// to an internal vector.  Each call to `emit` increments the clock and
-/// returns a reference to the stored event.
-pub struct EventBus {
+/// hands the stored event to every subscriber.
+pub struct EventBus<'a> {
     pub events: Vec<Event>,
     pub clock: u64,
+    decorator: Option<&'a dyn EventDecorator>,
+    decorator_error: Option<anyhow::Error>,
+    secrets: SecretsPolicy,
+    privacy: Option<PrivacyPolicy>,
+    listeners: Vec<&'a dyn EventSink>,
+    schemas: EventSchemas,
+    strict: bool,
+    time_source: Option<&'a dyn TimeSource>,
+    trace: Option<TraceContext>,
 }
 
-impl EventBus {
-    /// Create a new event bus with logical clock starting at zero.
-    pub fn new() -> Self {
+impl<'a> EventBus<'a> {
+    /// Create a new event bus with logical clock starting at zero that runs
+    /// `decorator`, if any, on every event it emits.
+    pub fn with_decorator(decorator: Option<&'a dyn EventDecorator>) -> Self {
         Self {
             events: Vec::new(),
             clock: 0,
+            decorator,
+            decorator_error: None,
+            secrets: SecretsPolicy::new(Vec::<String>::new()),
+            privacy: None,
+            listeners: Vec::new(),
+            schemas: EventSchemas::new(),
+            strict: false,
+            time_source: None,
+            trace: None,
         }
     }
 
+    /// Stamp every event with `trace`, if any.
+    pub fn with_trace(mut self, trace: Option<TraceContext>) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Stamp every event with its `wallTime` from `time_source`, if any,
+    /// next to its logical `t`.
+    pub fn with_time_source(mut self, time_source: Option<&'a dyn TimeSource>) -> Self {
+        self.time_source = time_source;
+        self
+    }
+
+    /// Check each event's data against its type's schema in `schemas` as
+    /// it is emitted, failing the run on a mismatch when `strict`; see
+    /// [`EventSchemas`].
+    pub fn with_schemas(mut self, schemas: EventSchemas, strict: bool) -> Self {
+        self.schemas = schemas;
+        self.strict = strict;
+        self
+    }
+
+    /// Apply `privacy` to the events subscribers receive.  The stored log
+    /// is redacted only once the run has been summarized, so the rules
+    /// never change the counters.
+    pub fn with_privacy(mut self, privacy: PrivacyPolicy) -> Self {
+        self.privacy = Some(privacy);
+        self
+    }
+
+    /// Hand every event emitted from now on to `listener`, as it is
+    /// stored.  The in-memory log is kept all the same.
+    pub fn subscribe(&mut self, listener: &'a dyn EventSink) {
+        self.listeners.push(listener);
+    }
+
+    /// Mask the values `secrets` names in every event's data and host
+    /// attributes as it is emitted, before the decorator sees it.
+    pub fn with_secrets(mut self, secrets: SecretsPolicy) -> Self {
+        self.secrets = secrets;
+        self
+    }
+
     /// Emit an event of the given type with arbitrary data.  The logical
-    /// timestamp is converted to a string.
+    /// timestamp is converted to a string.  Attributes that fail validation
+    /// are left off the event and reported by [`EventBus::finish`].  Data
+    /// that does not match the type's schema is followed by a
+    /// `schema_violation` event, tagged with the same batch request, and in
+    /// strict mode by the `error` event that fails the run.
     pub fn emit(&mut self, event_type: &str, data: Value) {
         let t = self.clock.to_string();
-        let event = Event {
+        self.store(event_type, data);
+        let data = &self.events[self.events.len() - 1].data;
+        let diagnostics = self.schemas.validate(event_type, data);
+        if diagnostics.is_empty() {
+            return;
+        }
+        let request = data.get("request").cloned();
+        let tag = |mut data: Value| {
+            if let Some(request) = &request {
+                data["request"] = request.clone();
+            }
+            data
+        };
+        let violations: Vec<Value> = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                json!({ "pointer": diagnostic.pointer, "expected": diagnostic.expected })
+            })
+            .collect();
+        self.store(
+            "schema_violation",
+            tag(json!({ "event": event_type, "t": t, "violations": violations })),
+        );
+        if self.strict {
+            let params = json!({ "event": event_type });
+            self.store(
+                "error",
+                tag(error_event_data(ErrorCode::SchemaViolation, params)),
+            );
+        }
+    }
+
+    /// Mask, decorate and store an event, then hand it to the subscribers.
+    fn store(&mut self, event_type: &str, mut data: Value) {
+        self.secrets.mask(&mut data);
+        let t = self.clock.to_string();
+        let mut event = Event {
             t,
+            wall_time: self
+                .time_source
+                .map(|source| format_wall_time(source.unix_millis())),
+            trace: self.trace.clone(),
             type_: event_type.to_string(),
             data,
+            host: None,
         };
+        if let Some(decorator) = self.decorator {
+            let mut attributes = decorator.decorate(&event);
+            self.secrets.mask_fields(&mut attributes);
+            match validate_host_attributes(&attributes) {
+                Ok(()) if attributes.is_empty() => {}
+                Ok(()) => event.host = Some(attributes),
+                Err(err) => {
+                    let err = err.context(format!(
+                        "host attributes for `{}` event {}",
+                        event.type_, event.t
+                    ));
+                    self.decorator_error.get_or_insert(err);
+                }
+            }
+        }
+        if !self.listeners.is_empty() {
+            let mut delivered = [event.clone()];
+            if let Some(privacy) = &self.privacy {
+                privacy.redact(&mut delivered);
+            }
+            for listener in &self.listeners {
+                listener.record(&delivered[0]);
+            }
+        }
         self.events.push(event);
         self.clock += 1;
     }
+
+    /// Fail with the first decorator error, if any.  A host that attaches
+    /// invalid attributes gets an error rather than a partially tagged log.
+    pub fn finish(&mut self) -> Result<()> {
+        self.decorator_error.take().map_or(Ok(()), Err)
+    }
+}
+
+fn validate_host_attributes(attributes: &Map<String, Value>) -> Result<()> {
+    if let Some((key, _)) = attributes
+        .iter()
+        .find(|(_, value)| value.is_array() || value.is_object())
+    {
+        bail!("`{key}` must be a string, number, boolean or null");
+    }
+    let size = serde_json::to_string(attributes)?.len();
+    if size > HOST_ATTRIBUTES_LIMIT {
+        return Err(anyhow!(
+            "{size} bytes exceeds the {HOST_ATTRIBUTES_LIMIT}-byte limit"
+        ));
+    }
+    Ok(())
 }
@@ -0,0 +1,86 @@
+//! Schemas for event payloads.  The contract's `events` section maps event
+//! types to the JSON Schema (draft 7) their `data` must match; the event
+//! bus checks every event of a listed type as it is emitted.  Types the
+//! section does not list carry any data.
+//!
+//! A payload that does not match is kept as emitted and followed by a
+//! `schema_violation` event naming it and listing where it differs from the
+//! schema.  In strict mode a localized `error` event follows as well, which
+//! fails the run.
+
+use crate::contract::{diagnostics_of, ContractDiagnostic};
+use anyhow::{anyhow, Result};
+use jsonschema::{Draft, JSONSchema};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A compiled schema per event type.
+#[derive(Clone, Default)]
+pub struct EventSchemas {
+    schemas: BTreeMap<String, (Value, Arc<JSONSchema>)>,
+}
+
+impl EventSchemas {
+    /// A registry that checks no event.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check the data of `event_type` events against `schema`, replacing
+    /// any schema registered for it.  Fails when `schema` does not compile.
+    pub fn schema(mut self, event_type: impl Into<String>, schema: Value) -> Result<Self> {
+        let event_type = event_type.into();
+        let compiled = JSONSchema::options()
+            .with_draft(Draft::Draft7)
+            .compile(&schema)
+            .map_err(|err| anyhow!("schema for `{event_type}` events: {err}"))?;
+        self.schemas
+            .insert(event_type, (schema, Arc::new(compiled)));
+        Ok(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_empty()
+    }
+
+    /// Where `data` differs from the schema of `event_type`; empty when it
+    /// matches or the type has no schema.
+    pub fn validate(&self, event_type: &str, data: &Value) -> Vec<ContractDiagnostic> {
+        let Some((source, schema)) = self.schemas.get(event_type) else {
+            return Vec::new();
+        };
+        match schema.validate(data) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .flat_map(|error| diagnostics_of(source, &error))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Debug for EventSchemas {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.schemas
+                    .iter()
+                    .map(|(name, (source, _))| (name, source)),
+            )
+            .finish()
+    }
+}
+
+impl<'de> Deserialize<'de> for EventSchemas {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let sources = BTreeMap::<String, Value>::deserialize(deserializer)?;
+        sources
+            .into_iter()
+            .try_fold(Self::new(), |schemas, (event_type, schema)| {
+                schemas.schema(event_type, schema)
+            })
+            .map_err(|err| serde::de::Error::custom(format!("{err:#}")))
+    }
+}
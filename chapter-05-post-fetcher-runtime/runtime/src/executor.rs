@@ -0,0 +1,140 @@
+//! The bridge between blocking and async adapters.  A run is an async
+//! computation over an [`AsyncNetworkAdapter`] chain.  [`crate::Runtime::run`]
+//! and the other blocking entry points drive it to completion on the calling
+//! thread with [`block_on`], and blocking adapters join the chain through
+//! [`BlockingAdapter`].  With blocking adapters the only future that waits
+//! is the retry backoff, during which `block_on` parks the thread much as a
+//! sleep would, so the blocking path behaves as it always has.
+//!
+//! Batch runs poll the fetches of a wave together with [`join_all`].  A
+//! thread-safe blocking adapter then serves them from the run's
+//! [`ThreadManager`] pool through [`PooledAdapter`], so they overlap.
+
+use crate::thread_manager::ThreadManager;
+use service::api::{
+    AdapterCapabilities, AsyncNetworkAdapter, FetchFuture, NetworkAdapter, StreamFuture,
+};
+use service::model::Request;
+use std::future::{poll_fn, Future};
+use std::pin::{pin, Pin};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::Thread;
+
+/// Wakes the thread blocked in [`block_on`].
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Run `future` to completion on the calling thread, parking the thread
+/// while it waits.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut context = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+/// Serves an async run with a blocking adapter.  Its fetch runs on the
+/// executor's thread when polled, so async hosts should keep to adapters that
+/// return at once, such as replay and mock adapters, and fetch over the
+/// network with an [`AsyncNetworkAdapter`].
+pub struct BlockingAdapter(pub Box<dyn NetworkAdapter>);
+
+impl AsyncNetworkAdapter for BlockingAdapter {
+    fn fetch<'a>(&'a self, request: &'a Request) -> FetchFuture<'a> {
+        Box::pin(async move { self.0.fetch(request) })
+    }
+
+    fn fetch_stream<'a>(&'a self, request: &'a Request) -> StreamFuture<'a> {
+        Box::pin(async move { self.0.fetch_stream(request) })
+    }
+
+    fn capabilities(&self) -> AdapterCapabilities {
+        self.0.capabilities()
+    }
+
+    fn thread_safe(&self) -> Option<Arc<dyn NetworkAdapter + Send + Sync>> {
+        self.0.thread_safe()
+    }
+}
+
+/// Lends an adapter to the adapter chain of one run, or of one request of a
+/// batch run.
+pub(crate) struct SharedAdapter(pub(crate) Rc<dyn AsyncNetworkAdapter>);
+
+impl AsyncNetworkAdapter for SharedAdapter {
+    fn fetch<'a>(&'a self, request: &'a Request) -> FetchFuture<'a> {
+        self.0.fetch(request)
+    }
+
+    fn fetch_stream<'a>(&'a self, request: &'a Request) -> StreamFuture<'a> {
+        self.0.fetch_stream(request)
+    }
+
+    fn thread_safe(&self) -> Option<Arc<dyn NetworkAdapter + Send + Sync>> {
+        self.0.thread_safe()
+    }
+
+    fn capabilities(&self) -> AdapterCapabilities {
+        self.0.capabilities()
+    }
+}
+
+/// Calls a thread-safe blocking adapter from the worker threads of a
+/// [`ThreadManager`], so the fetches of a batch wave run at the same time.
+pub(crate) struct PooledAdapter {
+    pub(crate) adapter: Arc<dyn NetworkAdapter + Send + Sync>,
+    pub(crate) threads: Rc<ThreadManager>,
+}
+
+impl AsyncNetworkAdapter for PooledAdapter {
+    fn fetch<'a>(&'a self, request: &'a Request) -> FetchFuture<'a> {
+        let adapter = Arc::clone(&self.adapter);
+        let request = request.clone();
+        Box::pin(self.threads.spawn(move || adapter.fetch(&request)))
+    }
+
+    fn capabilities(&self) -> AdapterCapabilities {
+        self.adapter.capabilities()
+    }
+}
+
+/// Poll `futures` together until every one is ready, returning their
+/// outputs in order.  Each pending future is polled again, in order, on
+/// every wake-up.
+pub(crate) async fn join_all<'a, T>(futures: Vec<Pin<Box<dyn Future<Output = T> + 'a>>>) -> Vec<T> {
+    let mut pending: Vec<_> = futures.into_iter().map(Some).collect();
+    let mut outputs: Vec<Option<T>> = pending.iter().map(|_| None).collect();
+    poll_fn(|cx| {
+        for (future, output) in pending.iter_mut().zip(&mut outputs) {
+            if let Some(ready) = future.as_mut() {
+                if let std::task::Poll::Ready(value) = ready.as_mut().poll(cx) {
+                    *output = Some(value);
+                    *future = None;
+                }
+            }
+        }
+        if pending.iter().all(Option::is_none) {
+            std::task::Poll::Ready(
+                outputs
+                    .iter_mut()
+                    .map(|output| output.take().unwrap())
+                    .collect(),
+            )
+        } else {
+            std::task::Poll::Pending
+        }
+    })
+    .await
+}
@@ -0,0 +1,182 @@
+//! Which request headers a run accepts.  The contract's `headers` section
+//! declares the allowed names, the longest value and the headers every
+//! request must carry; the runtime checks each request against them before
+//! anything is fetched, so a rejected request never leaves the host.
+//!
+//! Every violation is recorded as an event of its own type, naming the
+//! header, followed by the localized `error` event that fails the run.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use service::messages::ErrorCode;
+use std::collections::HashMap;
+
+/// Request headers accepted unless the contract or the builder sets
+/// another allowlist.
+pub const DEFAULT_ALLOWED_HEADERS: [&str; 3] = ["accept", "content-type", "authorization"];
+
+/// Longest header value accepted unless the contract sets another limit.
+pub const DEFAULT_MAX_HEADER_VALUE_LENGTH: usize = 1024;
+
+/// The contract's `headers` section.  Names compare case-insensitively.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeaderPolicy {
+    /// The headers a request may carry.  Required headers are allowed
+    /// whether or not they are listed.
+    #[serde(default = "default_allowed")]
+    pub allowed: Vec<String>,
+    /// Longest value accepted, in bytes; `None` accepts any length.
+    #[serde(default = "default_max_value_length")]
+    pub max_value_length: Option<usize>,
+    /// The headers every request must carry.
+    #[serde(default)]
+    pub required: Vec<String>,
+}
+
+fn default_allowed() -> Vec<String> {
+    DEFAULT_ALLOWED_HEADERS.map(String::from).to_vec()
+}
+
+fn default_max_value_length() -> Option<usize> {
+    Some(DEFAULT_MAX_HEADER_VALUE_LENGTH)
+}
+
+impl Default for HeaderPolicy {
+    fn default() -> Self {
+        Self {
+            allowed: default_allowed(),
+            max_value_length: default_max_value_length(),
+            required: Vec::new(),
+        }
+    }
+}
+
+/// One way a request's headers break the policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderViolation {
+    /// The header is neither allowed nor required.
+    Unexpected { header: String },
+    /// The header's value is `length` bytes, over the policy's `max`.
+    TooLong {
+        header: String,
+        length: usize,
+        max: usize,
+    },
+    /// A `content-type` the runtime does not submit.
+    UnsupportedContentType { header: String, value: String },
+    /// A required header the request does not carry.
+    Missing { header: String },
+}
+
+impl HeaderPolicy {
+    /// Whether a request may carry `name`.
+    pub fn allows(&self, name: &str) -> bool {
+        self.allowed
+            .iter()
+            .chain(&self.required)
+            .any(|allowed| allowed.eq_ignore_ascii_case(name))
+    }
+
+    /// Every violation in `headers`: those of each header, in name order,
+    /// then each missing required header, in policy order.
+    pub fn validate(&self, headers: &HashMap<String, String>) -> Vec<HeaderViolation> {
+        let mut names: Vec<&String> = headers.keys().collect();
+        names.sort_by_key(|name| (name.to_ascii_lowercase(), name.as_str()));
+        let mut violations = Vec::new();
+        for name in names {
+            let value = &headers[name];
+            let header = name.clone();
+            if !self.allows(name) {
+                violations.push(HeaderViolation::Unexpected {
+                    header: header.clone(),
+                });
+            }
+            if name.eq_ignore_ascii_case("content-type") && !submittable_content_type(value) {
+                violations.push(HeaderViolation::UnsupportedContentType {
+                    header: header.clone(),
+                    value: value.clone(),
+                });
+            }
+            if let Some(max) = self.max_value_length.filter(|max| value.len() > *max) {
+                violations.push(HeaderViolation::TooLong {
+                    header,
+                    length: value.len(),
+                    max,
+                });
+            }
+        }
+        for required in &self.required {
+            if !headers
+                .keys()
+                .any(|name| name.eq_ignore_ascii_case(required))
+            {
+                violations.push(HeaderViolation::Missing {
+                    header: required.clone(),
+                });
+            }
+        }
+        violations
+    }
+}
+
+impl HeaderViolation {
+    /// The type of the event recording the violation.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            HeaderViolation::Unexpected { .. } => "header_unexpected",
+            HeaderViolation::TooLong { .. } => "header_too_long",
+            HeaderViolation::UnsupportedContentType { .. } => "header_unsupported_content_type",
+            HeaderViolation::Missing { .. } => "header_missing",
+        }
+    }
+
+    /// The data of the event recording the violation.
+    pub fn event_data(&self) -> Value {
+        match self {
+            HeaderViolation::Unexpected { header } | HeaderViolation::Missing { header } => {
+                json!({ "header": header })
+            }
+            HeaderViolation::TooLong {
+                header,
+                length,
+                max,
+            } => json!({ "header": header, "length": length, "maxLength": max }),
+            HeaderViolation::UnsupportedContentType { header, value } => {
+                json!({ "header": header, "value": value })
+            }
+        }
+    }
+
+    /// The code and params of the `error` event that fails the run.
+    pub(crate) fn error(&self) -> (ErrorCode, Value) {
+        match self {
+            HeaderViolation::Unexpected { header } => {
+                (ErrorCode::UnexpectedHeader, json!({ "header": header }))
+            }
+            HeaderViolation::TooLong { header, .. } => {
+                (ErrorCode::HeaderTooLong, json!({ "header": header }))
+            }
+            HeaderViolation::UnsupportedContentType { header, value } => (
+                ErrorCode::UnsupportedContentType,
+                json!({ "header": header, "value": value }),
+            ),
+            HeaderViolation::Missing { header } => {
+                (ErrorCode::MissingHeader, json!({ "header": header }))
+            }
+        }
+    }
+}
+
+/// Whether a request `content-type` is one the runtime submits: JSON
+/// (`application/json` or a `+json` suffix type such as
+/// `application/merge-patch+json`) or `text/plain`, with any parameters.
+fn submittable_content_type(value: &str) -> bool {
+    let media_type = value.split(';').next().unwrap_or_default();
+    let media_type = media_type.trim().to_ascii_lowercase();
+    match media_type.split_once('/') {
+        Some(("application", subtype)) => subtype == "json" || subtype.ends_with("+json"),
+        Some(("text", subtype)) => subtype == "plain",
+        _ => false,
+    }
+}
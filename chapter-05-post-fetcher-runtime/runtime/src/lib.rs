@@ -2,130 +2,488 @@
 //! orchestrates contract loading, adapter binding, deterministic event
 //! emission, lifecycle metadata and service execution.
 
+mod access_policy;
 mod adapter_manager;
+mod auth_adapter;
+mod builder;
 mod cache_adapter;
+mod cassette;
+mod circuit_breaker;
+mod contract;
+mod delay;
 mod event_bus;
+mod event_schema;
+mod executor;
+mod header_policy;
+mod lifecycle_store;
 mod metadata;
+mod middleware;
+#[cfg(any(test, feature = "testing"))]
+mod mock_adapter;
+#[cfg(feature = "otel")]
+mod otel;
+mod policy;
+mod privacy;
+mod registry;
+mod replay_adapter;
 mod retry_adapter;
+mod run;
+mod secrets;
+mod signing;
+mod streaming;
 mod thread_manager;
+mod trace_adapter;
 mod wasi_http_adapter;
 
 use crate::adapter_manager::AdapterManager;
-use crate::event_bus::EventBus;
-use crate::metadata::LifecycleRecord;
-use crate::thread_manager::ThreadManager;
+use crate::executor::{join_all, SharedAdapter};
+use crate::registry::Unbound;
+
+pub use crate::access_policy::{check_url, PolicyDenial};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::adapter_manager::AsyncHostFetchAdapter;
+pub use crate::auth_adapter::{AuthAdapter, FileToken, StaticToken, TokenProvider, REDACTED};
+pub use crate::builder::{Backoff, CachePolicy, RetryPolicy, Runtime, RuntimeBuilder};
+pub use crate::cache_adapter::{cache_key, CacheLimits, CacheLookup, CacheOutcome, ResponseCache};
+pub use crate::cassette::{Cassette, Interaction, RecordedResponse, RecordingAdapter};
+pub use crate::circuit_breaker::{
+    CircuitBreakerPolicy, CircuitBreakers, CircuitOpen, CircuitState, CircuitTransition,
+};
+pub use crate::contract::{
+    AdapterPolicy, ContractBindings, ContractDiagnostic, ContractError, ExecutionLimits,
+    ServiceContract,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::delay::SleepDelay;
+pub use crate::delay::{LogicalDelay, NoDelay};
+pub use crate::event_bus::{EventBus, EventDecorator, EventSink, JsonLines, HOST_ATTRIBUTES_LIMIT};
+pub use crate::event_schema::EventSchemas;
+pub use crate::executor::BlockingAdapter;
+pub use crate::header_policy::{
+    HeaderPolicy, HeaderViolation, DEFAULT_ALLOWED_HEADERS, DEFAULT_MAX_HEADER_VALUE_LENGTH,
+};
+pub use crate::lifecycle_store::{Compression, IndexEntry, LifecycleStore};
+pub use crate::metadata::{BindingImpl, Bindings, LifecycleRecord, NormalizerBinding};
+pub use crate::middleware::Middleware;
+#[cfg(any(test, feature = "testing"))]
+pub use crate::mock_adapter::MockAdapter;
+#[cfg(feature = "otel")]
+pub use crate::otel::{NoLogs, OtelSink, RUN_SPAN_NAME};
+pub use crate::policy::{ConcurrencyPolicy, RuntimePolicy};
+pub use crate::privacy::{PrivacyAction, PrivacyPolicy, PrivacyRule};
+pub use crate::registry::{AdapterFactory, AdapterRegistry, AsyncAdapterFactory, DEFAULT_ADAPTER};
+pub use crate::replay_adapter::ReplayAdapter;
+pub use crate::run::{RunError, RunResult};
+pub use crate::secrets::{SecretsPolicy, DEFAULT_SENSITIVE_KEYS};
+pub use crate::signing::{
+    canonical_request, KeyProvider, SigningKey, SigningMiddleware, SIGNATURE_HEADER,
+};
+pub use crate::streaming::{ArrayStreamError, JsonArrayStream};
+pub use crate::thread_manager::{host_of, QueuedTask, Schedule, ThreadManager};
+pub use crate::trace_adapter::{TraceAdapter, TRACEPARENT_HEADER};
+pub use service::api::{AsyncNetworkAdapter, Clock, DelayProvider, FetchFuture, TimeSource};
+pub use service::fetch_config::{HostFetchConfig, TlsVersion};
 
 use anyhow::Result;
 use serde_json::{json, Value};
-use service::api::NetworkAdapter;
-use service::model::{Input, Output, Post};
-use service::{error_message, normalize_post};
-
-/// Run the UMA post fetcher with the given JSON input.  Returns a pair of
-/// strings: the service output JSON and the lifecycle metadata JSON.  The
-/// runtime is deterministic: given the same input and adapter implementation
-/// it will emit the same sequence of events and the same logical clock.
+use service::api::{BudgetExceeded, NetworkAdapter, StreamingResponse};
+use service::messages::{error_event_data, ErrorCode};
+use service::model::{Input, Output, Request};
+use service::normalizer::{
+    collection_details, NormalizeError, Normalized, Normalizer, NormalizerRegistry, RecordKind,
+    DEFAULT_NORMALIZER,
+};
+use service::{classify_sla, summarize_events};
+use std::future::Future;
+use std::io::Read;
+use std::pin::Pin;
+use std::rc::Rc;
+
+/// Run the UMA post fetcher with the given input.  Returns the typed service
+/// output and lifecycle record.  The runtime is deterministic: given the same
+/// input and adapter implementation it will emit the same sequence of events
+/// and the same logical clock.
+///
+/// A typed `Input` cannot carry fields the contract does not declare, so
+/// strict mode has nothing to report here; [`run_json`] checks the raw
+/// document.
+///
+/// This and the `run_json` family are shorthands for a [`Runtime`] built
+/// with [`RuntimeBuilder::with_env`]; build one directly to configure runs
+/// in code.
+pub fn run(input: Input, adapter: Option<Box<dyn NetworkAdapter>>) -> Result<RunResult, RunError> {
+    env_runtime(adapter).build().run(input)
+}
+
+/// Like [`run`], but reading the input from JSON and returning a pair of
+/// strings: the service output JSON and the lifecycle metadata JSON.
 pub fn run_json(
     input_json: &str,
     adapter: Option<Box<dyn NetworkAdapter>>,
 ) -> Result<(String, String)> {
-    // Parse the input according to the service contract.
-    let input: Input = serde_json::from_str(input_json)?;
+    run_json_with_clock(input_json, adapter, None)
+}
+
+/// Like [`run_json`], but with an optional wall-clock capability.  When a
+/// clock is present the SLA classification also checks the contract's
+/// `maxWallMillis`; nothing else in the run depends on it.
+pub fn run_json_with_clock(
+    input_json: &str,
+    adapter: Option<Box<dyn NetworkAdapter>>,
+    clock: Option<&dyn Clock>,
+) -> Result<(String, String)> {
+    run_json_with_decorator(input_json, adapter, clock, None)
+}
 
-    let thread_manager = ThreadManager::new();
-    let mut event_bus = EventBus::new();
+/// Like [`run_json_with_clock`], but with host middleware that tags every
+/// event before it is stored; see [`EventDecorator`].  Fails if the
+/// decorator returns attributes over [`HOST_ATTRIBUTES_LIMIT`] or with a
+/// nested value.
+pub fn run_json_with_decorator(
+    input_json: &str,
+    adapter: Option<Box<dyn NetworkAdapter>>,
+    clock: Option<&dyn Clock>,
+    decorator: Option<&dyn EventDecorator>,
+) -> Result<(String, String)> {
+    let mut builder = env_runtime(adapter);
+    if let Some(clock) = clock {
+        builder = builder.clock(clock);
+    }
+    if let Some(decorator) = decorator {
+        builder = builder.decorator(decorator);
+    }
+    builder.build().run_json(input_json)
+}
+
+/// Like [`run_json`], for async hosts: the fetch and retry backoff are
+/// awaited, and the default `host-fetch` binding uses reqwest's async
+/// client, so it needs a tokio runtime with I/O and timers enabled.  See
+/// [`Runtime::run_async`] for why the future is not `Send`.
+pub async fn run_json_async(
+    input_json: &str,
+    adapter: Option<Box<dyn AsyncNetworkAdapter>>,
+) -> Result<(String, String)> {
+    let builder = Runtime::builder().with_env();
+    let builder = match adapter {
+        Some(adapter) => builder.async_adapter(adapter),
+        None => builder,
+    };
+    builder.build().run_json_async(input_json).await
+}
+
+/// A runtime configured from the environment, fetching through `adapter`
+/// when one is given.
+fn env_runtime<'a>(adapter: Option<Box<dyn NetworkAdapter>>) -> RuntimeBuilder<'a> {
+    let builder = Runtime::builder().with_env();
+    match adapter {
+        Some(adapter) => builder.adapter(adapter),
+        None => builder,
+    }
+}
+
+/// The run behind [`Runtime::run`] and [`Runtime::run_json`] and their
+/// async forms.  `raw` is the input document, when there is one, for the
+/// strict-mode field check; `asynchronous` picks the kind of adapter bound.
+async fn execute(
+    runtime: &Runtime<'_>,
+    input: Input,
+    raw: Option<&Value>,
+    asynchronous: bool,
+) -> Result<RunResult, RunError> {
+    let clock = runtime.clock;
+    let started_at = clock.map(|clock| clock.now_millis());
+
+    let contract = &runtime.contract;
+    let policy = &runtime.policy;
+    let threads = Rc::new(
+        ThreadManager::with_max_per_host(policy.concurrency.max_per_host)
+            .with_workers(policy.concurrency.max_workers.unwrap_or(1)),
+    );
+    let secrets = runtime
+        .sensitive_keys
+        .clone()
+        .map_or(policy.secrets.clone(), SecretsPolicy::new);
+    let schemas = runtime
+        .event_schemas
+        .clone()
+        .unwrap_or_else(|| contract.events.clone());
+    let mut event_bus = EventBus::with_decorator(runtime.decorator)
+        .with_secrets(secrets)
+        .with_schemas(schemas, contract.strict(input.input_mode))
+        .with_time_source(runtime.time_source)
+        .with_trace(input.trace.clone());
+    if policy.privacy.enabled {
+        event_bus = event_bus.with_privacy(policy.privacy.clone());
+    }
+    for listener in &runtime.subscribers {
+        event_bus.subscribe(*listener);
+    }
     // Emit start event
     event_bus.emit("start", json!({ "runId": input.run_id.clone() }));
 
-    // Validate request headers before proceeding.  Only allow a small set of
-    // recognised header names and values under 1024 characters.  If
-    // validation fails, emit an error and skip the network fetch.
-    let allowed_headers = ["accept", "content-type", "authorization"];
-    let mut header_validation_failed = false;
-    for (key, value) in &input.request.headers {
-        let lower = key.to_ascii_lowercase();
-        if !allowed_headers.contains(&lower.as_str()) {
+    let mut validation_failed = false;
+    // In strict mode, list every field the contract's input schema does not
+    // declare.  Permissive mode ignores them, as serde does.
+    if let Some(raw) = raw.filter(|_| contract.strict(input.input_mode)) {
+        let fields = contract.unexpected_fields(raw);
+        if !fields.is_empty() {
             event_bus.emit(
                 "error",
-                json!({ "error": format!("unexpected header {}", key) }),
+                error_event_data(ErrorCode::ValidationError, json!({ "fields": fields })),
             );
-            header_validation_failed = true;
+            validation_failed = true;
         }
-        if value.len() > 1024 {
-            event_bus.emit(
-                "error",
-                json!({ "error": format!("header {} too long", key) }),
-            );
-            header_validation_failed = true;
+    }
+
+    // A batch run checks and fetches its requests in order, and every event
+    // about one of them names it by its index in `requests`.
+    let requests: Vec<&Request> = input.all_requests().collect();
+    let tag = |index: usize, mut data: Value| {
+        if input.is_batch() {
+            data["request"] = json!(index);
+        }
+        data
+    };
+
+    // Validate request headers against the contract's header policy before
+    // proceeding.  Each violation is recorded, then fails the run with its
+    // localized error, and the network fetch is skipped.
+    let mut header_policy = contract.headers.clone();
+    if let Some(allowed) = &runtime.allowed_headers {
+        header_policy.allowed = allowed.clone();
+    }
+    for (index, request) in requests.iter().enumerate() {
+        for violation in header_policy.validate(&request.headers) {
+            event_bus.emit(violation.event_type(), tag(index, violation.event_data()));
+            let (code, params) = violation.error();
+            event_bus.emit("error", tag(index, error_event_data(code, params)));
+            validation_failed = true;
         }
     }
 
-    // Prepare variables for the normalised post and final state.  The final
-    // state will be set to "failed" if any error events are emitted.
-    let mut normalized_post: Option<Post> = None;
+    // The final state will be set to "failed" if any error events are
+    // emitted.
     let mut final_state = "terminated".to_string();
 
-    let adapter_manager = AdapterManager::new(adapter);
-    if !header_validation_failed {
-        // Record fetch_request event only when the runtime will perform the fetch.
-        event_bus.emit("fetch_request", json!({ "url": input.request.url.clone() }));
-        // Perform network request.  Capture status and body.
-        let fetch_result = thread_manager
-            .run_sync(|| adapter_manager.fetch(&input.request.url, &input.request.headers));
-        match fetch_result {
-            Ok(resp) => {
-                // Emit fetch_response event
-                event_bus.emit("fetch_response", json!({ "status": resp.status }));
-                // Parse body into JSON
-                let body_str = resp.body;
-                let value: Result<Value, _> = serde_json::from_str(&body_str);
-                match value {
-                    Ok(json_val) => {
-                        // Normalise the post
-                        normalized_post = normalize_post(&json_val);
-                        if let Some(ref post) = normalized_post {
-                            event_bus.emit("normalized", json!({ "id": post.id }));
-                        } else {
-                            // Emit parse error event when fields missing
-                            let err_msg = error_message(Some(resp.status), None);
-                            event_bus.emit("error", json!({ "error": err_msg }));
-                        }
+    // Fail runs whose input names a normalizer that is not registered.  The
+    // normalizers of the run, with the kinds of their records, decide the
+    // output fields; with a resource named, that is its normalizer alone.
+    let resource = input.resource.as_deref();
+    let mut normalizers_used: Vec<(String, RecordKind)> = Vec::new();
+    if let Some(name) = resource {
+        match runtime.normalizers.get(name) {
+            Some(normalizer) => normalizers_used.push((name.to_string(), normalizer.kind())),
+            None => {
+                event_bus.emit(
+                    "error",
+                    error_event_data(ErrorCode::UnknownNormalizer, json!({ "normalizer": name })),
+                );
+                validation_failed = true;
+            }
+        }
+    }
+
+    // Fail runs bound to an adapter that is not registered, deny URLs
+    // outside the contract's allowlists, then reject requests the bound
+    // adapter chain cannot serve, all before fetching anything.
+    let adapter_policy = contract.adapter.clone().with_overrides(
+        runtime.retry.clone(),
+        runtime.circuit_breaker,
+        runtime.cache,
+        runtime.cache_limits,
+    );
+    let (adapter, impl_name, unbound) = match runtime.bind_adapter(asynchronous) {
+        Ok((adapter, name)) => (adapter, name, None),
+        Err(name) => (
+            Box::new(BlockingAdapter(Box::new(Unbound))) as Box<dyn AsyncNetworkAdapter>,
+            name.clone(),
+            Some(name),
+        ),
+    };
+    // Each request gets its own wrapper chain around the bound adapter, so
+    // the cache lookups and retries it logs are its own; the chains share
+    // the circuit breaker and cache state.  Batch runs fetch on the worker
+    // threads when the adapter allows it.
+    let bound: Rc<dyn AsyncNetworkAdapter> = Rc::from(adapter);
+    let mut context = runtime.adapter_context(started_at);
+    context.trace = input.trace.clone();
+    if input.is_batch() {
+        context.threads = Some(Rc::clone(&threads));
+    }
+    let managers: Vec<AdapterManager> = (0..requests.len().max(1))
+        .map(|_| {
+            AdapterManager::new(
+                Box::new(SharedAdapter(Rc::clone(&bound))),
+                impl_name.clone(),
+                &adapter_policy,
+                context.clone(),
+            )
+        })
+        .collect();
+    let adapter_manager = &managers[0];
+    if let Some(name) = unbound {
+        event_bus.emit(
+            "error",
+            error_event_data(ErrorCode::UnknownAdapter, json!({ "adapter": name })),
+        );
+        validation_failed = true;
+    } else {
+        for (index, request) in requests.iter().enumerate() {
+            if let Err(denial) = check_url(&adapter_policy, &request.url) {
+                // Record the decision, then fail the run with its localized error.
+                event_bus.emit("policy_denied", tag(index, denial.event_data(&request.url)));
+                let (code, params) = match denial {
+                    PolicyDenial::Scheme(scheme) => {
+                        (ErrorCode::SchemeNotAllowed, json!({ "scheme": scheme }))
                     }
-                    Err(parse_err) => {
-                        // Invalid JSON
-                        let err_msg = error_message(Some(resp.status), Some(&parse_err));
-                        event_bus.emit("error", json!({ "error": err_msg }));
-                        normalized_post = None;
+                    PolicyDenial::Host(host) => {
+                        (ErrorCode::HostNotAllowed, json!({ "host": host }))
                     }
-                }
+                };
+                event_bus.emit("error", tag(index, error_event_data(code, params)));
+                validation_failed = true;
+            } else if let Some(missing) = adapter_manager.capabilities().check(request) {
+                event_bus.emit(
+                    "error",
+                    tag(
+                        index,
+                        error_event_data(
+                            ErrorCode::UnsupportedCapability,
+                            json!({
+                                "adapter": adapter_manager.binding.impl_name,
+                                "capability": missing.capability,
+                                "requested": missing.requested,
+                            }),
+                        ),
+                    ),
+                );
+                validation_failed = true;
             }
-            Err(err) => {
-                // Network error
-                let err_msg = err.to_string();
-                event_bus.emit("fetch_response", json!({ "status": 0 }));
-                event_bus.emit("error", json!({ "error": err_msg }));
-                normalized_post = None;
+        }
+    }
+    let mut normalized = Normalized::default();
+    if !validation_failed {
+        // Fetch wave by wave, the requests of a wave at the same time, then
+        // log each request's events in request order, whatever order the
+        // fetches finished in, so the log stays reproducible.
+        let hosts: Vec<String> = requests
+            .iter()
+            .map(|request| host_of(&request.url))
+            .collect();
+        let schedule = threads.plan(&hosts);
+        for queued in &schedule.queued {
+            event_bus.emit(
+                "queued",
+                tag(
+                    queued.index,
+                    json!({ "host": queued.host, "wave": queued.wave }),
+                ),
+            );
+        }
+        let mut fetched: Vec<Option<Fetched>> = requests.iter().map(|_| None).collect();
+        for wave in &schedule.waves {
+            let fetches = wave
+                .iter()
+                .map(|&index| {
+                    let fetch = fetch_resource(
+                        &managers[index],
+                        requests[index],
+                        &runtime.normalizers,
+                        resource,
+                        runtime.stream_chunk_bytes,
+                        move |data| tag(index, data),
+                    );
+                    Box::pin(fetch) as Pin<Box<dyn Future<Output = _> + '_>>
+                })
+                .collect();
+            for (&index, result) in wave.iter().zip(join_all(fetches).await) {
+                fetched[index] = Some(result);
+            }
+        }
+        // No token sent for the run may appear in its events, so none
+        // reaches a subscriber or the lifecycle record.
+        let tokens: Vec<String> = managers
+            .iter()
+            .flat_map(|manager| manager.stats.tokens.take())
+            .collect();
+        for fetch in fetched.into_iter().flatten() {
+            for (event_type, mut data) in fetch.events.0 {
+                auth_adapter::redact_tokens(&mut data, &tokens);
+                event_bus.emit(event_type, data);
+            }
+            normalized.extend(fetch.records.unwrap_or_default());
+            if let Some(used) = fetch.normalizer {
+                if !normalizers_used.contains(&used) {
+                    normalizers_used.push(used);
+                }
             }
         }
     }
+    // A single post keeps its own field.  Batches list their posts, and
+    // every other normalizer lists its records in the field of their kind.
+    let single_post = !input.is_batch()
+        && normalizers_used
+            .iter()
+            .all(|(name, _)| name == DEFAULT_NORMALIZER);
+    let kinds: Vec<RecordKind> = normalizers_used.iter().map(|(_, kind)| *kind).collect();
+    let normalized_post = if single_post {
+        normalized.posts.pop()
+    } else {
+        None
+    };
+    let lists_posts = kinds.contains(&RecordKind::Post) || (input.is_batch() && kinds.is_empty());
+    let normalized_posts = (lists_posts && !single_post).then_some(normalized.posts);
+    let normalized_comments = kinds
+        .contains(&RecordKind::Comment)
+        .then_some(normalized.comments);
+    let normalized_users = kinds
+        .contains(&RecordKind::User)
+        .then_some(normalized.users);
 
-    // Determine final state based on whether any error events were emitted.
+    // End event
+    event_bus.emit("end", json!({}));
+
+    // Determine final state based on whether any error events were emitted,
+    // including one for an `end` event that broke its schema.
     if event_bus.events.iter().any(|e| e.type_ == "error") {
         final_state = "failed".to_string();
     }
+    event_bus
+        .finish()
+        .map_err(|err| RunError::ContractViolation(format!("{err:#}")))?;
 
-    // End event
-    event_bus.emit("end", json!({}));
-
-    // Build service output
+    // Build service output, completing the summary with the wrapper counters.
+    let mut summary = summarize_events(&event_bus.events);
+    summary.cache_hits = managers
+        .iter()
+        .map(|manager| manager.stats.cache_hits.get())
+        .sum();
+    summary.retries = managers
+        .iter()
+        .map(|manager| manager.stats.retries.get())
+        .sum();
+    if let Some(target) = contract.sla {
+        let wall_millis = clock
+            .zip(started_at)
+            .map(|(clock, start)| clock.now_millis().saturating_sub(start));
+        summary.sla = Some(classify_sla(target, summary.duration_ticks, wall_millis));
+    }
+    // Redact after summarizing so the counters never depend on the rules.
+    let redacted = policy.privacy.enabled;
+    if redacted {
+        policy.privacy.redact(&mut event_bus.events);
+    }
     let output = Output {
         normalized_post,
+        normalized_posts,
+        normalized_comments,
+        normalized_users,
         events: event_bus.events.clone(),
+        summary,
     };
-    let output_json = serde_json::to_string(&output)?;
 
     // Build lifecycle record
-    let lifecycle = LifecycleRecord::new(
+    let mut lifecycle = LifecycleRecord::new(
         "uma-post-fetcher.service",
         "1.0.0",
         "default.runtime.policy",
@@ -134,9 +492,313 @@ pub fn run_json(
         &final_state,
         event_bus.clock,
     );
-    let lifecycle_json = serde_json::to_string(&lifecycle.to_json())?;
+    lifecycle.redacted = redacted;
+    lifecycle.sla = output.summary.sla;
+    lifecycle.payload_bytes = requests
+        .iter()
+        .filter_map(|request| request.body.as_ref().map(Vec::len))
+        .reduce(|total, len| total + len);
+    lifecycle.adapter_policy = Some(adapter_policy);
+    lifecycle.trace = input.trace.clone();
+    lifecycle.bindings.normalizer = NormalizerBinding::new(
+        normalizers_used.iter().map(|(name, _)| name.as_str()),
+        resource.is_some(),
+    );
+
+    for sink in &runtime.sinks {
+        for event in &output.events {
+            sink.record(event);
+        }
+    }
+    for listener in runtime.subscribers.iter().chain(&runtime.sinks) {
+        listener.record_lifecycle(&lifecycle);
+    }
+    Ok(RunResult { output, lifecycle })
+}
+
+/// The events of one request's fetch, held until the run logs them in
+/// request order.
+#[derive(Default)]
+struct EventLog(Vec<(&'static str, Value)>);
+
+impl EventLog {
+    fn emit(&mut self, event_type: &'static str, data: Value) {
+        self.0.push((event_type, data));
+    }
+}
+
+/// What fetching one request of a run produced.
+struct Fetched {
+    records: Option<Normalized>,
+    /// The normalizer the response went through and the kind of its
+    /// records; `None` when the fetch failed.
+    normalizer: Option<(String, RecordKind)>,
+    events: EventLog,
+}
+
+/// Fetch `request` through `adapter_manager` and normalize the response
+/// with the normalizer named `resource`, or the one for its content type.
+/// With `stream_chunk_bytes` the body is streamed; see
+/// [`RuntimeBuilder::stream_chunks`].  The records are `None` after the
+/// error that prevented them, and the events of the fetch are each passed
+/// through `tag`.
+async fn fetch_resource(
+    adapter_manager: &AdapterManager,
+    request: &Request,
+    normalizers: &NormalizerRegistry,
+    resource: Option<&str>,
+    stream_chunk_bytes: Option<usize>,
+    tag: impl Fn(Value) -> Value,
+) -> Fetched {
+    let mut event_bus = EventLog::default();
+    let mut used = None;
+    // Record fetch_request event only when the runtime will perform the fetch.
+    event_bus.emit(
+        "fetch_request",
+        tag(json!({ "url": request.url.clone(), "method": request.method() })),
+    );
+    // Perform network request.  Capture status and body.
+    let fetch_result = match stream_chunk_bytes {
+        Some(_) => adapter_manager.fetch_stream(request).await,
+        None => adapter_manager
+            .fetch(request)
+            .await
+            .map(StreamingResponse::from),
+    };
+    // The cache lookup, then attempts the retry wrapper made after the
+    // first, in order.
+    for lookup in adapter_manager.stats.cache_log.take() {
+        event_bus.emit(
+            lookup.outcome.event_type(),
+            tag(json!({ "url": lookup.url })),
+        );
+    }
+    for url in adapter_manager.stats.auth_log.take() {
+        event_bus.emit("auth_refresh", tag(json!({ "url": url })));
+    }
+    for retry in adapter_manager.stats.retry_log.take() {
+        event_bus.emit(
+            "fetch_retry",
+            tag(json!({
+                "attempt": retry.attempt,
+                "previousStatus": retry.previous_status,
+                "delayMillis": retry.delay_millis,
+            })),
+        );
+    }
+    for transition in adapter_manager.stats.circuit_log.take() {
+        event_bus.emit(
+            "circuit_state",
+            tag(json!({
+                "host": transition.host,
+                "from": transition.from.as_str(),
+                "to": transition.to.as_str(),
+            })),
+        );
+    }
+    let records = match fetch_result {
+        Ok(resp) => {
+            // Emit fetch_response event
+            event_bus.emit("fetch_response", tag(json!({ "status": resp.status })));
+            // A body the adapter decompressed, with its size before and after
+            if let Some(decoded) = &resp.decoded {
+                event_bus.emit(
+                    "decompressed",
+                    tag(json!({
+                        "encoding": decoded.encoding,
+                        "encodedBytes": decoded.encoded_bytes,
+                        "decodedBytes": decoded.decoded_bytes,
+                    })),
+                );
+            }
+            // Pick the normalizer and normalise the body
+            let name = resource
+                .unwrap_or_else(|| normalizers.name_for_content_type(resp.header("content-type")));
+            let normalizer = normalizers.get(name);
+            if let Some(normalizer) = &normalizer {
+                used = Some((name.to_string(), normalizer.kind()));
+            }
+            let status = resp.status;
+            let normalized = match normalizer {
+                Some(normalizer) => {
+                    let read = read_body(resp, name, &*normalizer, stream_chunk_bytes, |data| {
+                        event_bus.emit("fetch_progress", tag(data))
+                    });
+                    match read {
+                        Ok(normalized) => Some(normalized),
+                        // The connection failed or a limit ran out mid-body
+                        Err(err) => {
+                            event_bus.emit("error", tag(fetch_error_data(&err)));
+                            return Fetched {
+                                records: None,
+                                normalizer: used,
+                                events: event_bus,
+                            };
+                        }
+                    }
+                }
+                None => None,
+            };
+            let data = match normalized {
+                Some(Ok(records)) => {
+                    event_bus.emit("normalized", tag(records.details.clone()));
+                    return Fetched {
+                        records: Some(records),
+                        normalizer: used,
+                        events: event_bus,
+                    };
+                }
+                // A content type mapped to a name that is not registered
+                None => {
+                    error_event_data(ErrorCode::UnknownNormalizer, json!({ "normalizer": name }))
+                }
+                // A body not in the normalizer's format, such as invalid JSON
+                Some(Err(NormalizeError::Parse(detail))) => error_event_data(
+                    ErrorCode::ParseError,
+                    json!({ "status": status, "detail": detail }),
+                ),
+                // A body without the fields of its records
+                Some(Err(NormalizeError::Shape)) if name == DEFAULT_NORMALIZER => {
+                    error_event_data(ErrorCode::InvalidPost, json!({ "status": status }))
+                }
+                Some(Err(NormalizeError::Shape)) => error_event_data(
+                    ErrorCode::InvalidResource,
+                    json!({ "status": status, "resource": name }),
+                ),
+            };
+            event_bus.emit("error", tag(data));
+            None
+        }
+        Err(err) => {
+            // Network error, or a fetch stopped by an execution limit
+            event_bus.emit("fetch_response", tag(json!({ "status": 0 })));
+            event_bus.emit("error", tag(fetch_error_data(&err)));
+            None
+        }
+    };
+    Fetched {
+        records,
+        normalizer: used,
+        events: event_bus,
+    }
+}
+
+/// The error event data of a fetch that failed.
+fn fetch_error_data(err: &anyhow::Error) -> Value {
+    if let Some(budget) = err.downcast_ref::<BudgetExceeded>() {
+        error_event_data(
+            ErrorCode::BudgetExceeded,
+            json!({ "limit": budget.limit, "max": budget.max }),
+        )
+    } else if let Some(open) = err.downcast_ref::<CircuitOpen>() {
+        error_event_data(ErrorCode::CircuitOpen, json!({ "host": open.host }))
+    } else {
+        error_event_data(
+            ErrorCode::NetworkFailure,
+            json!({ "detail": err.to_string() }),
+        )
+    }
+}
+
+/// Read the body of `resp` and normalize it with `normalizer`, registered
+/// as `name`.  Without `stream_chunk_bytes` the body is read whole.  With
+/// it, the body is read in chunks of that size, each reported to `progress`;
+/// an array body is then normalized element by element as the chunks
+/// arrive when the normalizer can, and anything else once it has been read.
+/// The error is a failure to read the body.
+fn read_body(
+    resp: StreamingResponse,
+    name: &str,
+    normalizer: &dyn Normalizer,
+    stream_chunk_bytes: Option<usize>,
+    mut progress: impl FnMut(Value),
+) -> Result<Result<Normalized, NormalizeError>> {
+    let Some(chunk_bytes) = stream_chunk_bytes else {
+        let mut body = Vec::new();
+        let mut reader = resp.body;
+        reader.read_to_end(&mut body).map_err(read_failure)?;
+        return Ok(normalizer.normalize(&body));
+    };
+    let mut parser = normalizer.normalizes_elements().then(JsonArrayStream::new);
+    // The body read so far, kept until it is known to be an array.
+    let mut body = Vec::new();
+    let mut records = Normalized::default();
+    let mut items = 0;
+    let mut bytes = 0;
+    for (index, chunk) in resp.chunks(chunk_bytes).enumerate() {
+        let chunk = chunk.map_err(read_failure)?;
+        bytes += chunk.len();
+        if let Some(stream) = parser.as_mut() {
+            match stream.push(&chunk) {
+                Ok(elements) => {
+                    items += elements.len();
+                    for element in &elements {
+                        records.extend(normalizer.normalize_element(element));
+                    }
+                }
+                Err(ArrayStreamError::NotAnArray) => parser = None,
+                Err(ArrayStreamError::Invalid(detail)) => {
+                    return Ok(Err(NormalizeError::Parse(detail)));
+                }
+            }
+        }
+        let streamed = parser.as_ref().is_some_and(JsonArrayStream::in_array);
+        if streamed {
+            body.clear();
+        } else {
+            body.extend_from_slice(&chunk);
+        }
+        let mut data = json!({ "chunk": index + 1, "bytes": bytes });
+        if streamed {
+            data["records"] = json!(records.len());
+        }
+        progress(data);
+    }
+    match parser {
+        Some(stream) if stream.in_array() => match stream.finish() {
+            Ok(()) => {
+                records.details = collection_details(name, records.len(), items);
+                Ok(Ok(records))
+            }
+            Err(ArrayStreamError::NotAnArray) => Ok(normalizer.normalize(&body)),
+            Err(ArrayStreamError::Invalid(detail)) => Ok(Err(NormalizeError::Parse(detail))),
+        },
+        _ => Ok(normalizer.normalize(&body)),
+    }
+}
 
-    Ok((output_json, lifecycle_json))
+/// A body read failure, as the [`BudgetExceeded`] it carries if a limit ran
+/// out.
+fn read_failure(err: std::io::Error) -> anyhow::Error {
+    match err
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<BudgetExceeded>())
+    {
+        Some(budget) => budget.clone().into(),
+        None => err.into(),
+    }
+}
+
+/// Wall-clock capability backed by the host's system time, which is also a
+/// time source for `wallTime`.  Native hosts opt into them with
+/// `UMA_ENABLE_CLOCK` and `UMA_WALL_TIME`; wasm builds have no system clock.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct SystemClock;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clock for SystemClock {
+    fn now_millis(&self) -> u64 {
+        self.unix_millis()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TimeSource for SystemClock {
+    fn unix_millis(&self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,195 @@
+//! File-backed store for lifecycle records.  Records are appended to a single
+//! data file, optionally compressed, and an index file maps each run ID to the
+//! byte range holding its record.  Reads consult the index and decompress
+//! transparently using the codec recorded for that entry, so a store can mix
+//! codecs if the configured compression changes between runs.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+
+const DATA_FILE: &str = "lifecycle.data";
+const INDEX_FILE: &str = "lifecycle.index";
+
+/// Compression applied to lifecycle records at rest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    /// Only available when the runtime is built with the `zstd` feature.
+    /// Entries written with zstd are still listed in the index without it,
+    /// but reading them returns an error.
+    Zstd,
+}
+
+impl Compression {
+    /// Parse a codec name as accepted by `UMA_LIFECYCLE_COMPRESSION`.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "none" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" => {
+                if cfg!(feature = "zstd") {
+                    Ok(Compression::Zstd)
+                } else {
+                    bail!("zstd compression requires the `zstd` feature")
+                }
+            }
+            other => bail!("unknown lifecycle compression `{other}`"),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    fn compress(self, bytes: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(bytes.to_vec()),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                Ok(encoder.finish()?)
+            }
+            Compression::Zstd => zstd_compress(bytes),
+        }
+    }
+
+    fn decompress(self, bytes: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(bytes.to_vec()),
+            Compression::Gzip => {
+                let mut out = Vec::new();
+                GzDecoder::new(bytes).read_to_end(&mut out)?;
+                Ok(out)
+            }
+            Compression::Zstd => zstd_decompress(bytes),
+        }
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_compress(bytes: &[u8]) -> Result<Vec<u8>> {
+    Ok(zstd::encode_all(bytes, 0)?)
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_decompress(bytes: &[u8]) -> Result<Vec<u8>> {
+    Ok(zstd::decode_all(bytes)?)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_compress(_bytes: &[u8]) -> Result<Vec<u8>> {
+    bail!("zstd compression requires the `zstd` feature")
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_decompress(_bytes: &[u8]) -> Result<Vec<u8>> {
+    bail!("zstd compression requires the `zstd` feature")
+}
+
+/// One line of the index file: where a run's record lives in the data file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    #[serde(rename = "runId")]
+    pub run_id: String,
+    pub offset: u64,
+    pub length: u64,
+    pub compression: Compression,
+}
+
+/// Append-only lifecycle store rooted at a directory.
+#[derive(Debug, Clone)]
+pub struct LifecycleStore {
+    dir: PathBuf,
+    compression: Compression,
+}
+
+impl LifecycleStore {
+    /// Open (creating if needed) a store in `dir`.  New records are written
+    /// with `compression`; existing records keep the codec they were written
+    /// with.
+    pub fn open(dir: impl AsRef<Path>, compression: Compression) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("creating lifecycle store at {}", dir.display()))?;
+        Ok(Self { dir, compression })
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Append a lifecycle record for `run_id` and index it.  A later append
+    /// for the same run ID shadows the earlier one on read.
+    pub fn append(&self, run_id: &str, lifecycle_json: &str) -> Result<IndexEntry> {
+        let bytes = self.compression.compress(lifecycle_json.as_bytes())?;
+        let mut data = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(DATA_FILE))?;
+        let offset = data.seek(SeekFrom::End(0))?;
+        data.write_all(&bytes)?;
+        data.flush()?;
+
+        let entry = IndexEntry {
+            run_id: run_id.to_string(),
+            offset,
+            length: bytes.len() as u64,
+            compression: self.compression,
+        };
+        let mut index = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(INDEX_FILE))?;
+        writeln!(index, "{}", serde_json::to_string(&entry)?)?;
+        Ok(entry)
+    }
+
+    /// All index entries in the order they were appended.
+    pub fn index(&self) -> Result<Vec<IndexEntry>> {
+        let path = self.dir.join(INDEX_FILE);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut entries = Vec::new();
+        for (line_no, line) in BufReader::new(File::open(&path)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str(&line)
+                .with_context(|| format!("{}:{}", path.display(), line_no + 1))?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    /// Read the most recent lifecycle record for `run_id`, decompressing it
+    /// as needed.  Returns `Ok(None)` when the run is not in the index.
+    pub fn read(&self, run_id: &str) -> Result<Option<String>> {
+        let Some(entry) = self.index()?.into_iter().rev().find(|e| e.run_id == run_id) else {
+            return Ok(None);
+        };
+        let mut data = File::open(self.dir.join(DATA_FILE))?;
+        data.seek(SeekFrom::Start(entry.offset))?;
+        let mut bytes = vec![0; entry.length as usize];
+        data.read_exact(&mut bytes)
+            .with_context(|| format!("lifecycle record for `{run_id}` is truncated"))?;
+        let raw = entry.compression.decompress(&bytes)?;
+        String::from_utf8(raw)
+            .map(Some)
+            .map_err(|_| anyhow!("lifecycle record for `{run_id}` is not valid UTF-8"))
+    }
+}
@@ -1,15 +1,79 @@
-use anyhow::Result;
-use serde_json::json;
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Map, Value};
+use service::model::Event;
 use std::io::Read;
+use uma_runtime::{
+    AdapterRegistry, Clock, Compression, JsonLines, LifecycleStore, RecordingAdapter,
+    ReplayAdapter, Runtime, ServiceContract, TimeSource,
+};
 
 fn main() -> Result<()> {
     let mut input = String::new();
     std::io::stdin().read_to_string(&mut input)?;
 
-    let (output_json, lifecycle_json) = uma_runtime::run_json(&input, None)?;
+    // The retry and cache wrappers follow `UMA_ENABLE_RETRY` and
+    // `UMA_ENABLE_CACHE`.
+    let mut runtime = Runtime::builder().with_env();
+    // `UMA_ADAPTER=replay` serves the bundled recordings, or the cassette at
+    // `UMA_CASSETTE`, instead of using the host's network, which is how a wasm
+    // build runs the sample.  `UMA_ADAPTER=record` fetches through the host's
+    // adapter and appends what it answers to the cassette.
+    let cassette = std::env::var("UMA_CASSETTE").ok();
+    match (std::env::var("UMA_ADAPTER").as_deref(), cassette) {
+        (Ok("replay"), None) => runtime = runtime.adapter(Box::new(ReplayAdapter::bundled())),
+        (Ok("replay"), Some(path)) => {
+            runtime = runtime.adapter(Box::new(ReplayAdapter::load(path)?))
+        }
+        (Ok("record"), Some(path)) => {
+            let contract = ServiceContract::bundled();
+            let inner = AdapterRegistry::builtin()
+                .create(contract.bindings.network_fetch(), contract.execution)
+                .context("the bundled contract binds an unregistered adapter")?;
+            runtime = runtime.adapter(Box::new(RecordingAdapter::append(inner, path)?));
+        }
+        (Ok("record"), None) => bail!("UMA_ADAPTER=record needs UMA_CASSETTE"),
+        (Ok(other), _) => bail!("unknown UMA_ADAPTER `{other}`, expected `replay` or `record`"),
+        (Err(_), _) => {}
+    }
+    let clock = system_clock();
+    // `UMA_HOST_ATTRIBUTES` is a JSON object, e.g. a deployment id and
+    // region, attached under `host` to every event of the run.
+    let host_attributes: Option<Map<String, Value>> = match std::env::var("UMA_HOST_ATTRIBUTES") {
+        Ok(text) => Some(serde_json::from_str(&text).context("parsing UMA_HOST_ATTRIBUTES")?),
+        Err(_) => None,
+    };
+    let decorator = host_attributes.map(|attributes| move |_: &Event| attributes.clone());
+    if let Some(clock) = clock.as_deref() {
+        runtime = runtime.clock(clock);
+    }
+    let time_source = wall_time_source();
+    if let Some(source) = time_source.as_deref() {
+        runtime = runtime.time_source(source);
+    }
+    if let Some(decorator) = &decorator {
+        runtime = runtime.decorator(decorator);
+    }
+    // `UMA_STREAM_EVENTS` writes each event to stderr as a JSON line while
+    // the run is in progress; stdout keeps the final report.
+    let stream = JsonLines::new(std::io::stderr());
+    if std::env::var("UMA_STREAM_EVENTS").is_ok() {
+        runtime = runtime.subscribe(&stream);
+    }
+    let (output_json, lifecycle_json) = runtime.build().run_json(&input)?;
     let output: serde_json::Value = serde_json::from_str(&output_json)?;
     let lifecycle: serde_json::Value = serde_json::from_str(&lifecycle_json)?;
 
+    // Optionally persist the lifecycle record so past runs can be inspected.
+    if let Ok(dir) = std::env::var("UMA_LIFECYCLE_DIR") {
+        let compression = match std::env::var("UMA_LIFECYCLE_COMPRESSION") {
+            Ok(name) => Compression::parse(&name)?,
+            Err(_) => Compression::None,
+        };
+        let input: serde_json::Value = serde_json::from_str(&input)?;
+        let run_id = input["runId"].as_str().unwrap_or_default();
+        LifecycleStore::open(dir, compression)?.append(run_id, &lifecycle_json)?;
+    }
+
     println!(
         "{}",
         serde_json::to_string_pretty(&json!({
@@ -19,3 +83,30 @@ fn main() -> Result<()> {
     );
     Ok(())
 }
+
+/// The wall clock is opt-in so default output stays byte-for-byte
+/// reproducible; with it the SLA summary also reports `wallMillis`.
+#[cfg(not(target_arch = "wasm32"))]
+fn system_clock() -> Option<Box<dyn Clock>> {
+    std::env::var("UMA_ENABLE_CLOCK")
+        .is_ok()
+        .then(|| Box::new(uma_runtime::SystemClock) as Box<dyn Clock>)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn system_clock() -> Option<Box<dyn Clock>> {
+    None
+}
+
+/// `wallTime` stamps are opt-in for the same reason.
+#[cfg(not(target_arch = "wasm32"))]
+fn wall_time_source() -> Option<Box<dyn TimeSource>> {
+    std::env::var("UMA_WALL_TIME")
+        .is_ok()
+        .then(|| Box::new(uma_runtime::SystemClock) as Box<dyn TimeSource>)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn wall_time_source() -> Option<Box<dyn TimeSource>> {
+    None
+}
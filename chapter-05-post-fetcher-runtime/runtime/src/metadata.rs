@@ -2,29 +2,50 @@ use serde::Serialize;
 use serde_json::Value;
 
 use crate::adapter_manager::AdapterBinding;
-use service::model::Event;
+use crate::contract::AdapterPolicy;
+use service::model::{Event, SlaReport, TraceContext};
+use service::normalizer::DEFAULT_NORMALIZER;
 
 /// Lifecycle record persisted after each run.  Matches the
 /// `metadata.schema.json` contract.
-#[derive(Serialize)]
+#[derive(Debug, Serialize)]
 pub struct LifecycleRecord {
     pub service: String,
+    #[serde(rename = "serviceVersion")]
     pub service_version: String,
+    #[serde(rename = "policyRef")]
     pub policy_ref: String,
     pub bindings: Bindings,
     pub events: Vec<Event>,
     pub state: String,
     #[serde(rename = "logicalClock")]
     pub logical_clock: u64,
+    /// Set when the events were rewritten by the policy's privacy rules.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub redacted: bool,
+    /// The run's SLA classification, copied from the output summary.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sla: Option<SlaReport>,
+    /// Size in bytes of the request body, when there was one.  The body
+    /// itself is never recorded.
+    #[serde(rename = "payloadBytes", skip_serializing_if = "Option::is_none")]
+    pub payload_bytes: Option<usize>,
+    /// The adapter policy the run resolved from the contract and runtime.
+    #[serde(rename = "adapterPolicy", skip_serializing_if = "Option::is_none")]
+    pub adapter_policy: Option<AdapterPolicy>,
+    /// The run's trace, as `traceId` and `spanId`, when the input gave one.
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<TraceContext>,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize)]
 pub struct Bindings {
     #[serde(rename = "network.fetch")]
     pub network_fetch: BindingImpl,
+    pub normalizer: NormalizerBinding,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize)]
 pub struct BindingImpl {
     /// The adapter implementation name.  Use a raw identifier rename to avoid
     /// clashing with the Rust keyword `impl`.
@@ -33,6 +54,41 @@ pub struct BindingImpl {
     pub host: String,
 }
 
+/// The normalizers a run's responses went through.
+#[derive(Debug, Serialize)]
+pub struct NormalizerBinding {
+    /// The normalizer names, comma-separated in the order the run first used
+    /// them.
+    #[serde(rename = "impl")]
+    pub impl_name: String,
+    /// `resource` when the input named the normalizer, `contentType` when
+    /// each response's content type picked it.
+    #[serde(rename = "selectedBy")]
+    pub selected_by: String,
+}
+
+impl NormalizerBinding {
+    /// The binding of a run that used the normalizers `names`, named by the
+    /// input's resource if `by_resource`.  A run that normalized nothing is
+    /// recorded with [`DEFAULT_NORMALIZER`].
+    pub fn new<'n>(names: impl Iterator<Item = &'n str>, by_resource: bool) -> Self {
+        let names: Vec<&str> = names.collect();
+        Self {
+            impl_name: if names.is_empty() {
+                DEFAULT_NORMALIZER.to_string()
+            } else {
+                names.join(",")
+            },
+            selected_by: if by_resource {
+                "resource"
+            } else {
+                "contentType"
+            }
+            .to_string(),
+        }
+    }
+}
+
 impl LifecycleRecord {
     /// Construct a new lifecycle record from the given parameters.  This helper
     /// adapts the `AdapterBinding` into the shape expected by the schema.
@@ -54,10 +110,16 @@ impl LifecycleRecord {
                     impl_name: adapter_binding.impl_name.clone(),
                     host: adapter_binding.host.clone(),
                 },
+                normalizer: NormalizerBinding::new(std::iter::empty(), false),
             },
             events,
             state: state.to_string(),
             logical_clock,
+            redacted: false,
+            sla: None,
+            payload_bytes: None,
+            adapter_policy: None,
+            trace: None,
         }
     }
 
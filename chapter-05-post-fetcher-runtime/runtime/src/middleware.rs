@@ -0,0 +1,79 @@
+//! Hooks around each request the adapter sends.  A [`Middleware`] sees the
+//! request before it is fetched and the response after, so hosts can add
+//! auth headers, logging or metrics without writing a wrapper adapter.
+//!
+//! The stack is ordered: `before_fetch` runs from the first middleware to the
+//! last and `after_fetch` from the last back to the first, so each
+//! middleware's hooks bracket the ones added after it.  A `before_fetch` that
+//! returns a response short-circuits the stack: neither the later middleware
+//! nor the adapter are called, and only the middleware that already ran see
+//! the response in `after_fetch`.
+//!
+//! The stack sits directly around the adapter, inside the retry, circuit
+//! breaker and cache wrappers.  Every attempt of a retried request and every
+//! conditional revalidation passes through it; cache hits do not.
+
+use anyhow::Result;
+use service::api::{AdapterCapabilities, AsyncNetworkAdapter, FetchFuture, NetworkResponse};
+use service::model::Request;
+use std::rc::Rc;
+
+/// A hook pair run around each request the adapter sends.  Both hooks do
+/// nothing by default, so a middleware implements only the ones it needs.
+pub trait Middleware {
+    /// Inspect or change `request` before it is sent.  Returning a response
+    /// answers the request without sending it; returning an error fails it.
+    fn before_fetch(&self, request: &mut Request) -> Result<Option<NetworkResponse>> {
+        let _ = request;
+        Ok(None)
+    }
+
+    /// Inspect or change `response` to `request` before it is returned.
+    /// Returning an error fails the request.
+    fn after_fetch(&self, request: &Request, response: &mut NetworkResponse) -> Result<()> {
+        let _ = (request, response);
+        Ok(())
+    }
+}
+
+pub struct MiddlewareAdapter {
+    inner: Box<dyn AsyncNetworkAdapter>,
+    stack: Vec<Rc<dyn Middleware>>,
+}
+
+impl MiddlewareAdapter {
+    pub fn new(inner: Box<dyn AsyncNetworkAdapter>, stack: Vec<Rc<dyn Middleware>>) -> Self {
+        Self { inner, stack }
+    }
+
+    async fn fetch_through(&self, request: &Request) -> Result<NetworkResponse> {
+        let mut request = request.clone();
+        let mut ran = 0;
+        let mut answer = None;
+        for middleware in &self.stack {
+            ran += 1;
+            answer = middleware.before_fetch(&mut request)?;
+            if answer.is_some() {
+                break;
+            }
+        }
+        let mut response = match answer {
+            Some(response) => response,
+            None => self.inner.fetch(&request).await?,
+        };
+        for middleware in self.stack[..ran].iter().rev() {
+            middleware.after_fetch(&request, &mut response)?;
+        }
+        Ok(response)
+    }
+}
+
+impl AsyncNetworkAdapter for MiddlewareAdapter {
+    fn fetch<'a>(&'a self, request: &'a Request) -> FetchFuture<'a> {
+        Box::pin(self.fetch_through(request))
+    }
+
+    fn capabilities(&self) -> AdapterCapabilities {
+        self.inner.capabilities()
+    }
+}
@@ -0,0 +1,217 @@
+//! A configurable network adapter for tests, built with the `testing`
+//! feature.  Routes map URL patterns to canned responses, so tests describe
+//! what the network answers instead of writing an adapter of their own.
+//!
+//! A pattern is a URL in which `*` matches any run of characters, optionally
+//! preceded by a method: `GET https://example.com/posts/*`.  Without a method
+//! it matches every method.  The first route whose pattern matches answers.
+//! Registering a pattern again adds to its sequence: its replies are served
+//! in order and the last one repeats, so `503` then `200` exercises a retry.
+//! A request no route matches fails like a transport error.
+//!
+//! Clones share their routes' progress and the request log, so keep one
+//! clone to inspect after handing another to the runtime.
+
+use anyhow::{anyhow, Result};
+use service::api::{AdapterCapabilities, NetworkAdapter, NetworkResponse};
+use service::model::Request;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+enum Reply {
+    Response(NetworkResponse),
+    Failure(String),
+}
+
+#[derive(Debug)]
+struct Route {
+    method: Option<String>,
+    pattern: String,
+    replies: Vec<Reply>,
+    served: usize,
+}
+
+impl Route {
+    fn matches(&self, request: &Request) -> bool {
+        self.method
+            .as_ref()
+            .is_none_or(|method| *method == request.method())
+            && glob_matches(&self.pattern, &request.url)
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    routes: Vec<Route>,
+    expected_headers: Vec<(String, String)>,
+    requests: Vec<Request>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MockAdapter {
+    state: Rc<RefCell<MockState>>,
+    capabilities: AdapterCapabilities,
+}
+
+impl Default for MockAdapter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockAdapter {
+    /// A mock with no routes that sends `GET`, `POST`, `PUT` and `DELETE`
+    /// to any scheme.
+    pub fn new() -> Self {
+        Self {
+            state: Rc::default(),
+            capabilities: AdapterCapabilities {
+                methods: ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec(),
+                ..AdapterCapabilities::default()
+            },
+        }
+    }
+
+    /// A response with `status` and a JSON `body`.
+    pub fn json(status: u16, body: &str) -> NetworkResponse {
+        NetworkResponse {
+            status,
+            headers: HashMap::from([("content-type".to_string(), "application/json".to_string())]),
+            body: body.into(),
+            decoded: None,
+        }
+    }
+
+    /// Answer requests matching `pattern` with `response`.
+    pub fn route(self, pattern: &str, response: NetworkResponse) -> Self {
+        self.reply(pattern, Reply::Response(response))
+    }
+
+    /// Fail requests matching `pattern` as a transport error with `detail`.
+    pub fn fail(self, pattern: &str, detail: &str) -> Self {
+        self.reply(pattern, Reply::Failure(detail.to_string()))
+    }
+
+    /// Panic on any request that does not carry header `name` with `value`.
+    /// Names compare case-insensitively.
+    pub fn expect_header(self, name: &str, value: &str) -> Self {
+        self.state
+            .borrow_mut()
+            .expected_headers
+            .push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Declare `capabilities` instead of the defaults.
+    pub fn with_capabilities(mut self, capabilities: AdapterCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Every request received, in order.
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.borrow().requests.clone()
+    }
+
+    /// How many requests received matched `pattern`.
+    pub fn calls(&self, pattern: &str) -> usize {
+        let probe = parse_pattern(pattern);
+        self.state
+            .borrow()
+            .requests
+            .iter()
+            .filter(|request| probe.matches(request))
+            .count()
+    }
+
+    fn reply(self, pattern: &str, reply: Reply) -> Self {
+        {
+            let mut state = self.state.borrow_mut();
+            let route = parse_pattern(pattern);
+            let existing = state.routes.iter_mut().find(|existing| {
+                existing.method == route.method && existing.pattern == route.pattern
+            });
+            match existing {
+                Some(existing) => existing.replies.push(reply),
+                None => state.routes.push(Route {
+                    replies: vec![reply],
+                    ..route
+                }),
+            }
+        }
+        self
+    }
+}
+
+impl NetworkAdapter for MockAdapter {
+    fn fetch(&self, request: &Request) -> Result<NetworkResponse> {
+        let mut state = self.state.borrow_mut();
+        state.requests.push(request.clone());
+        for (name, value) in &state.expected_headers {
+            let found = request
+                .headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, found)| found.as_str());
+            assert_eq!(
+                found,
+                Some(value.as_str()),
+                "mock request {} {} should carry header {name}",
+                request.method(),
+                request.url
+            );
+        }
+        let route = state
+            .routes
+            .iter_mut()
+            .find(|route| route.matches(request))
+            .ok_or_else(|| anyhow!("no mock route for {} {}", request.method(), request.url))?;
+        let reply = route.replies[route.served.min(route.replies.len() - 1)].clone();
+        route.served += 1;
+        match reply {
+            Reply::Response(response) => Ok(response),
+            Reply::Failure(detail) => Err(anyhow!(detail)),
+        }
+    }
+
+    fn capabilities(&self) -> AdapterCapabilities {
+        self.capabilities.clone()
+    }
+}
+
+/// A route without replies for `pattern`, split into method and URL pattern.
+fn parse_pattern(pattern: &str) -> Route {
+    let (method, pattern) = match pattern.split_once(' ') {
+        Some((method, url)) => (Some(method.to_ascii_uppercase()), url.trim()),
+        None => (None, pattern),
+    };
+    Route {
+        method,
+        pattern: pattern.to_string(),
+        replies: Vec::new(),
+        served: 0,
+    }
+}
+
+/// Whether `text` matches `pattern`, in which `*` matches any run of
+/// characters.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
@@ -0,0 +1,194 @@
+//! OpenTelemetry export of runs, behind the `otel` feature.  [`OtelSink`]
+//! is an [`EventSink`] that mirrors each run as one span through the
+//! OpenTelemetry API, so a host that has installed an SDK with an OTLP
+//! exporter sees its runs in the same observability stack as the rest of
+//! its services.
+//!
+//! - The `start` event opens a span named `uma.run`, a child of the input's
+//!   trace when it gave one, with the run id as `uma.run_id`.
+//! - Every event becomes a span event named after its type, carrying `t`,
+//!   `wallTime`, the data and the host attributes as JSON.  With a logger
+//!   each event is also emitted as a log record in the span's context,
+//!   `ERROR` for `error` events and `INFO` otherwise.
+//! - The lifecycle record becomes attributes of the span under
+//!   `uma.lifecycle.`, one per scalar field with nested objects flattened
+//!   by dotted name, arrays as JSON and the events left out.  A failed run
+//!   sets the span's status to an error, and the span ends.
+//!
+//! The sink holds one span at a time, so give each runtime its own sink
+//! when runs interleave.  Subscribed with [`crate::RuntimeBuilder::subscribe`]
+//! the span's events are timed as they happen; added with
+//! [`crate::RuntimeBuilder::sink`] they are all recorded once the run has
+//! finished.
+
+use crate::event_bus::EventSink;
+use crate::metadata::LifecycleRecord;
+use opentelemetry::logs::{
+    AnyValue, LogRecord, Logger, LoggerProvider, NoopLoggerProvider, Severity,
+};
+use opentelemetry::trace::{
+    Span, SpanContext, SpanId, Status, TraceContextExt, TraceFlags, TraceId, TraceState, Tracer,
+};
+use opentelemetry::{Context, KeyValue};
+use serde_json::Value;
+use service::model::{Event, TraceContext};
+use std::cell::RefCell;
+
+/// The name of the span each run is recorded as.
+pub const RUN_SPAN_NAME: &str = "uma.run";
+
+/// The logger of a sink that records no logs.
+pub type NoLogs = <NoopLoggerProvider as LoggerProvider>::Logger;
+
+/// Mirrors runs as spans of `tracer` and, with [`OtelSink::logger`], as
+/// log records of a logger.
+pub struct OtelSink<T: Tracer, L: Logger = NoLogs> {
+    tracer: T,
+    logger: L,
+    logs: bool,
+    span: RefCell<Option<T::Span>>,
+}
+
+impl<T: Tracer> OtelSink<T> {
+    /// A sink that records spans with `tracer`, such as
+    /// `opentelemetry::global::tracer("uma-runtime")`, and no logs.
+    pub fn new(tracer: T) -> Self {
+        Self {
+            tracer,
+            logger: NoopLoggerProvider::new().logger("uma-runtime"),
+            logs: false,
+            span: RefCell::new(None),
+        }
+    }
+}
+
+impl<T: Tracer, L: Logger> OtelSink<T, L> {
+    /// Also emit every event as a log record of `logger`.
+    pub fn logger<M: Logger>(self, logger: M) -> OtelSink<T, M> {
+        OtelSink {
+            tracer: self.tracer,
+            logger,
+            logs: true,
+            span: self.span,
+        }
+    }
+
+    fn start(&self, event: &Event) {
+        let parent = event
+            .trace
+            .as_ref()
+            .and_then(remote_context)
+            .map_or_else(Context::new, |remote| {
+                Context::new().with_remote_span_context(remote)
+            });
+        let mut builder = self.tracer.span_builder(RUN_SPAN_NAME);
+        if let Some(run_id) = event.data.get("runId").and_then(Value::as_str) {
+            builder = builder.with_attributes([KeyValue::new("uma.run_id", run_id.to_string())]);
+        }
+        let span = builder.start_with_context(&self.tracer, &parent);
+        // A run that returned an error never delivered its lifecycle record.
+        if let Some(mut stale) = self.span.replace(Some(span)) {
+            stale.set_status(Status::error("run ended without a lifecycle record"));
+            stale.end();
+        }
+    }
+
+    fn log(&self, event: &Event, span: Option<&SpanContext>) {
+        let mut record = self.logger.create_log_record();
+        let (severity, text) = if event.type_ == "error" {
+            (Severity::Error, "ERROR")
+        } else {
+            (Severity::Info, "INFO")
+        };
+        record.set_severity_number(severity);
+        record.set_severity_text(text);
+        record.set_body(AnyValue::from(
+            serde_json::to_string(&event.data).unwrap_or_default(),
+        ));
+        record.add_attribute("uma.event.type", event.type_.clone());
+        record.add_attribute("uma.event.t", event.t.clone());
+        if let Some(span) = span.filter(|span| span.is_valid()) {
+            record.set_trace_context(span.trace_id(), span.span_id(), Some(span.trace_flags()));
+        }
+        self.logger.emit(record);
+    }
+}
+
+impl<T: Tracer, L: Logger> EventSink for OtelSink<T, L> {
+    fn record(&self, event: &Event) {
+        if event.type_ == "start" {
+            self.start(event);
+        }
+        let mut span = self.span.borrow_mut();
+        if let Some(span) = span.as_mut() {
+            span.add_event(event.type_.clone(), event_attributes(event));
+        }
+        if self.logs {
+            self.log(event, span.as_ref().map(|span| span.span_context()));
+        }
+    }
+
+    fn record_lifecycle(&self, lifecycle: &LifecycleRecord) {
+        let Some(mut span) = self.span.borrow_mut().take() else {
+            return;
+        };
+        if let Ok(Value::Object(mut fields)) = serde_json::to_value(lifecycle) {
+            fields.remove("events");
+            let mut attributes = Vec::new();
+            flatten("uma.lifecycle", &Value::Object(fields), &mut attributes);
+            span.set_attributes(attributes);
+        }
+        if lifecycle.state == "failed" {
+            span.set_status(Status::error("run failed"));
+        }
+        span.end();
+    }
+}
+
+/// The span context of the caller's span, as the parent of a run's span.
+fn remote_context(trace: &TraceContext) -> Option<SpanContext> {
+    Some(SpanContext::new(
+        TraceId::from_hex(trace.trace_id()).ok()?,
+        SpanId::from_hex(trace.span_id()).ok()?,
+        TraceFlags::SAMPLED,
+        true,
+        TraceState::default(),
+    ))
+}
+
+fn event_attributes(event: &Event) -> Vec<KeyValue> {
+    let mut attributes = vec![
+        KeyValue::new("uma.event.t", event.t.clone()),
+        KeyValue::new("uma.event.data", event.data.to_string()),
+    ];
+    if let Some(wall_time) = &event.wall_time {
+        attributes.push(KeyValue::new("uma.event.wall_time", wall_time.clone()));
+    }
+    if let Some(host) = &event.host {
+        attributes.push(KeyValue::new(
+            "uma.event.host",
+            Value::Object(host.clone()).to_string(),
+        ));
+    }
+    attributes
+}
+
+/// Push an attribute for each scalar in `value`, named by its dotted path
+/// under `prefix`.  Arrays are recorded as JSON and nulls are left out.
+fn flatten(prefix: &str, value: &Value, attributes: &mut Vec<KeyValue>) {
+    match value {
+        Value::Object(fields) => {
+            for (name, value) in fields {
+                flatten(&format!("{prefix}.{name}"), value, attributes);
+            }
+        }
+        Value::Null => {}
+        Value::Bool(flag) => attributes.push(KeyValue::new(prefix.to_string(), *flag)),
+        Value::Number(number) => attributes.push(match number.as_i64() {
+            Some(integer) => KeyValue::new(prefix.to_string(), integer),
+            None => KeyValue::new(prefix.to_string(), number.as_f64().unwrap_or_default()),
+        }),
+        Value::String(text) => attributes.push(KeyValue::new(prefix.to_string(), text.clone())),
+        Value::Array(_) => attributes.push(KeyValue::new(prefix.to_string(), value.to_string())),
+    }
+}
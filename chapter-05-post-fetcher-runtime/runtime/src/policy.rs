@@ -0,0 +1,54 @@
+//! Runtime policy loaded from `contracts/policy.runtime.json`.  Only the
+//! sections the runtime actually enforces are parsed; the rest of the policy
+//! document remains declarative.
+
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+use crate::privacy::PrivacyPolicy;
+use crate::secrets::SecretsPolicy;
+
+/// The runtime policy shipped with this example, embedded at build time so
+/// native and wasm builds read the same document.
+const DEFAULT_POLICY_JSON: &str = include_str!("../../contracts/policy.runtime.json");
+
+/// Subset of the runtime policy interpreted by the runtime.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RuntimePolicy {
+    #[serde(default)]
+    pub concurrency: ConcurrencyPolicy,
+    #[serde(default)]
+    pub privacy: PrivacyPolicy,
+    #[serde(default)]
+    pub secrets: SecretsPolicy,
+}
+
+/// Scheduling limits applied by the `ThreadManager`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ConcurrencyPolicy {
+    /// Maximum number of in-flight fetches against a single host.  `None`
+    /// leaves the scheduler unbounded.
+    #[serde(rename = "maxPerHost", default)]
+    pub max_per_host: Option<usize>,
+    /// Worker threads a batch run fetches on.  `None` or 1 fetches one
+    /// request at a time on the run's thread.
+    #[serde(rename = "maxWorkers", default)]
+    pub max_workers: Option<usize>,
+}
+
+impl RuntimePolicy {
+    /// Parse a runtime policy document.
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// The policy bundled with the example, parsed once per process.
+    pub fn bundled() -> Self {
+        static BUNDLED: OnceLock<RuntimePolicy> = OnceLock::new();
+        BUNDLED
+            .get_or_init(|| {
+                Self::from_json(DEFAULT_POLICY_JSON).expect("bundled runtime policy should parse")
+            })
+            .clone()
+    }
+}
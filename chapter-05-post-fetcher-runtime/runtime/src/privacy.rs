@@ -0,0 +1,192 @@
+//! Privacy-safe event payloads.  When enabled, the runtime rewrites event data
+//! according to the `privacy` rules in the runtime policy before the events
+//! reach the service output or the lifecycle record, so records can be shipped
+//! to third-party storage without leaking request contents.
+
+use serde::Deserialize;
+use service::model::Event;
+
+/// Rules applied to the fields of every event's `data` object.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PrivacyPolicy {
+    /// Redact every run.  [`RuntimeBuilder::with_env`] also enables it when
+    /// `UMA_PRIVACY_MODE` is `1`, `true`, `yes` or `on`.
+    ///
+    /// [`RuntimeBuilder::with_env`]: crate::RuntimeBuilder::with_env
+    #[serde(default)]
+    pub enabled: bool,
+    /// Mixed into every hash so values from a small domain (numeric user ids)
+    /// cannot be recovered by hashing candidates without knowing the salt.
+    /// It must stay secret, so the bundled policy has none: set it with
+    /// `UMA_PRIVACY_SALT` or [`RuntimeBuilder::privacy`].  A runtime built
+    /// without one hashes with a random salt of its own.
+    ///
+    /// [`RuntimeBuilder::privacy`]: crate::RuntimeBuilder::privacy
+    #[serde(default)]
+    pub salt: String,
+    #[serde(default)]
+    pub rules: Vec<PrivacyRule>,
+}
+
+/// One field rewrite.  Fields that no rule names are left untouched.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PrivacyRule {
+    /// Dotted path into the event data, e.g. `params.detail`.
+    pub field: String,
+    #[serde(flatten)]
+    pub action: PrivacyAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum PrivacyAction {
+    /// Replace the whole value with its salted hash.
+    Hash,
+    /// Remove the field.  Used for values derived from others, such as the
+    /// localized error message, which can be rebuilt from `code` and `params`.
+    Drop,
+    /// Keep at most `max_chars` characters, marking cut values with `…`.
+    Truncate {
+        #[serde(rename = "maxChars")]
+        max_chars: usize,
+    },
+    /// Keep scheme, host and the shape of the path, hashing identifier-like
+    /// path segments and query values and dropping any fragment.
+    Url,
+}
+
+impl PrivacyPolicy {
+    /// Rewrite the data of every event in place.
+    pub fn redact(&self, events: &mut [Event]) {
+        for event in events {
+            for rule in &self.rules {
+                let (parent, name) = match rule.field.rsplit_once('.') {
+                    Some((parent, name)) => (Some(parent), name),
+                    None => (None, rule.field.as_str()),
+                };
+                let mut target = Some(&mut event.data);
+                for step in parent.into_iter().flat_map(|p| p.split('.')) {
+                    target = target.and_then(|value| value.get_mut(step));
+                }
+                let Some(object) = target.and_then(|value| value.as_object_mut()) else {
+                    continue;
+                };
+                if let Some(value) = object.remove(name) {
+                    if let Some(redacted) = self.apply(&rule.action, &value) {
+                        object.insert(name.to_string(), redacted);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The rewritten value, or `None` when the field should be removed.
+    fn apply(
+        &self,
+        action: &PrivacyAction,
+        value: &serde_json::Value,
+    ) -> Option<serde_json::Value> {
+        let text = match value {
+            serde_json::Value::String(s) => s.clone(),
+            serde_json::Value::Null => String::new(),
+            other => other.to_string(),
+        };
+        let redacted = match action {
+            PrivacyAction::Drop => return None,
+            _ if value.is_null() => return Some(serde_json::Value::Null),
+            PrivacyAction::Hash => self.hash(&text),
+            PrivacyAction::Truncate { max_chars } => truncate(&text, *max_chars),
+            PrivacyAction::Url => self.redact_url(&text),
+        };
+        Some(serde_json::Value::String(redacted))
+    }
+
+    /// Salted 64-bit FNV-1a, rendered as `h:` and 16 hex digits.
+    fn hash(&self, value: &str) -> String {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let len = self.salt.len().to_string();
+        for byte in len
+            .bytes()
+            .chain([b':'])
+            .chain(self.salt.bytes())
+            .chain(value.bytes())
+        {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        format!("h:{hash:016x}")
+    }
+
+    fn redact_url(&self, url: &str) -> String {
+        let url = url.split_once('#').map_or(url, |(before, _)| before);
+        let (path_part, query) = match url.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (url, None),
+        };
+        // Leave `scheme://authority` alone; only the path can carry identifiers.
+        let (prefix, path) = match path_part.split_once("://") {
+            Some((scheme, rest)) => match rest.find('/') {
+                Some(slash) => (&path_part[..scheme.len() + 3 + slash], &rest[slash..]),
+                None => (path_part, ""),
+            },
+            None => ("", path_part),
+        };
+        let path: Vec<String> = path
+            .split('/')
+            .map(|segment| {
+                if looks_like_identifier(segment) {
+                    self.hash(segment)
+                } else {
+                    segment.to_string()
+                }
+            })
+            .collect();
+        let mut redacted = format!("{prefix}{}", path.join("/"));
+        if let Some(query) = query {
+            let pairs: Vec<String> = query
+                .split('&')
+                .map(|pair| match pair.split_once('=') {
+                    Some((key, value)) => format!("{key}={}", self.hash(value)),
+                    None => pair.to_string(),
+                })
+                .collect();
+            redacted.push('?');
+            redacted.push_str(&pairs.join("&"));
+        }
+        redacted
+    }
+}
+
+/// A salt for a runtime built without one: 128 bits from the standard
+/// library's randomly keyed hasher, so hashes join across the runs of one
+/// runtime but not across processes.
+pub(crate) fn random_salt() -> String {
+    use std::hash::{BuildHasher, Hasher};
+    let half = || {
+        std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish()
+    };
+    format!("{:016x}{:016x}", half(), half())
+}
+
+/// Numeric ids, UUIDs and long hex tokens.  Words such as `posts` or `v1`
+/// describe the route rather than the user and are kept.
+fn looks_like_identifier(segment: &str) -> bool {
+    if segment.is_empty() {
+        return false;
+    }
+    if segment.bytes().all(|b| b.is_ascii_digit()) {
+        return true;
+    }
+    segment.len() >= 16
+        && segment.bytes().any(|b| b.is_ascii_digit())
+        && segment.bytes().all(|b| b.is_ascii_hexdigit() || b == b'-')
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    match value.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &value[..end]),
+        None => value.to_string(),
+    }
+}
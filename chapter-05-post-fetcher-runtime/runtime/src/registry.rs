@@ -0,0 +1,189 @@
+//! Named network adapter implementations.  The service contract binds
+//! `network.fetch` to one of these names in its `bindings` section, and the
+//! runtime creates that adapter for each run and records the name as the
+//! binding's `impl` in the lifecycle record.  An adapter given to the
+//! [`crate::RuntimeBuilder`] directly takes precedence and is recorded as
+//! `custom`.
+//!
+//! [`AdapterRegistry::builtin`] registers what the target can run:
+//! `host-fetch` and `replay` on native hosts, `wasi-http` and `replay` on
+//! wasm.  Hosts add their own implementations with
+//! [`crate::RuntimeBuilder::register_adapter`].
+//!
+//! A name can have a blocking and an async implementation.  Blocking runs
+//! use the blocking one and async runs the async one, each falling back to
+//! the other when it is the only one: native `host-fetch` has both, so it
+//! uses `reqwest::blocking` from [`crate::Runtime::run`] and reqwest's async
+//! client from [`crate::Runtime::run_async`].
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::adapter_manager::{AsyncHostFetchAdapter, HostFetchAdapter};
+use crate::contract::ExecutionLimits;
+use crate::executor::BlockingAdapter;
+use crate::replay_adapter::ReplayAdapter;
+#[cfg(target_arch = "wasm32")]
+use crate::wasi_http_adapter::WasiHttpAdapter;
+use anyhow::{anyhow, Result};
+use service::api::{AdapterCapabilities, AsyncNetworkAdapter, NetworkAdapter, NetworkResponse};
+#[cfg(not(target_arch = "wasm32"))]
+use service::fetch_config::HostFetchConfig;
+use service::model::Request;
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+
+/// The adapter bound when the contract does not name one: `host-fetch` on
+/// native hosts and `wasi-http` on wasm.
+#[cfg(not(target_arch = "wasm32"))]
+pub const DEFAULT_ADAPTER: &str = "host-fetch";
+#[cfg(target_arch = "wasm32")]
+pub const DEFAULT_ADAPTER: &str = "wasi-http";
+
+/// Creates an adapter for one run, enforcing the contract's execution
+/// limits if it can.
+pub type AdapterFactory = Rc<dyn Fn(ExecutionLimits) -> Box<dyn NetworkAdapter>>;
+
+/// Like [`AdapterFactory`], for an async implementation.
+pub type AsyncAdapterFactory = Rc<dyn Fn(ExecutionLimits) -> Box<dyn AsyncNetworkAdapter>>;
+
+/// Adapter implementations by name.
+#[derive(Clone, Default)]
+pub struct AdapterRegistry {
+    factories: BTreeMap<String, AdapterFactory>,
+    async_factories: BTreeMap<String, AsyncAdapterFactory>,
+}
+
+impl AdapterRegistry {
+    /// An empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The implementations shipped with the runtime for this target.
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        #[cfg(not(target_arch = "wasm32"))]
+        registry.register_host_fetch(HostFetchConfig::default());
+        // The wasi-http adapter is a placeholder that fails closed; keep the
+        // name if a working implementation replaces it.
+        #[cfg(target_arch = "wasm32")]
+        registry.register("wasi-http", |_| {
+            Box::new(WasiHttpAdapter) as Box<dyn NetworkAdapter>
+        });
+        registry.register("replay", |_| {
+            Box::new(ReplayAdapter::bundled()) as Box<dyn NetworkAdapter>
+        });
+        registry
+    }
+
+    /// Register both implementations of `host-fetch`, connecting as
+    /// `config` says.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn register_host_fetch(&mut self, config: HostFetchConfig) {
+        let blocking = config.clone();
+        self.register("host-fetch", move |limits| {
+            Box::new(HostFetchAdapter {
+                limits,
+                config: blocking.clone(),
+            }) as Box<dyn NetworkAdapter>
+        });
+        self.register_async("host-fetch", move |limits| {
+            Box::new(AsyncHostFetchAdapter {
+                limits,
+                config: config.clone(),
+            }) as Box<dyn AsyncNetworkAdapter>
+        });
+    }
+
+    /// Register `factory` under `name`, replacing any blocking
+    /// implementation registered under it before.
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(ExecutionLimits) -> Box<dyn NetworkAdapter> + 'static,
+    {
+        self.factories.insert(name.to_string(), Rc::new(factory));
+    }
+
+    /// Register `factory` as the async implementation of `name`, replacing
+    /// any async implementation registered under it before.
+    pub fn register_async<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(ExecutionLimits) -> Box<dyn AsyncNetworkAdapter> + 'static,
+    {
+        self.async_factories
+            .insert(name.to_string(), Rc::new(factory));
+    }
+
+    /// The registered names, in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        let names: BTreeSet<&str> = self
+            .factories
+            .keys()
+            .chain(self.async_factories.keys())
+            .map(String::as_str)
+            .collect();
+        names.into_iter()
+    }
+
+    /// A new adapter of the blocking implementation registered as `name`.
+    pub fn create(&self, name: &str, limits: ExecutionLimits) -> Option<Box<dyn NetworkAdapter>> {
+        self.factories.get(name).map(|factory| factory(limits))
+    }
+
+    /// A new adapter of the implementation registered as `name`, async if
+    /// there is one and blocking otherwise.
+    pub fn create_async(
+        &self,
+        name: &str,
+        limits: ExecutionLimits,
+    ) -> Option<Box<dyn AsyncNetworkAdapter>> {
+        match self.async_factories.get(name) {
+            Some(factory) => Some(factory(limits)),
+            None => self.create_blocking(name, limits),
+        }
+    }
+
+    /// The adapter for a run: blocking runs prefer the blocking
+    /// implementation of `name`, async runs the async one.
+    pub(crate) fn create_for_run(
+        &self,
+        name: &str,
+        limits: ExecutionLimits,
+        asynchronous: bool,
+    ) -> Option<Box<dyn AsyncNetworkAdapter>> {
+        if asynchronous {
+            return self.create_async(name, limits);
+        }
+        self.create_blocking(name, limits).or_else(|| {
+            self.async_factories
+                .get(name)
+                .map(|factory| factory(limits))
+        })
+    }
+
+    /// The blocking implementation of `name`, made to serve the async chain.
+    fn create_blocking(
+        &self,
+        name: &str,
+        limits: ExecutionLimits,
+    ) -> Option<Box<dyn AsyncNetworkAdapter>> {
+        self.create(name, limits)
+            .map(|adapter| Box::new(BlockingAdapter(adapter)) as Box<dyn AsyncNetworkAdapter>)
+    }
+}
+
+/// Stands in for an adapter name that is not registered.  The runtime fails
+/// such runs before fetching, so it is never called.
+pub(crate) struct Unbound;
+
+impl NetworkAdapter for Unbound {
+    fn fetch(&self, _request: &Request) -> Result<NetworkResponse> {
+        Err(anyhow!("no adapter is bound"))
+    }
+
+    fn capabilities(&self) -> AdapterCapabilities {
+        AdapterCapabilities {
+            methods: Vec::new(),
+            ..AdapterCapabilities::default()
+        }
+    }
+}
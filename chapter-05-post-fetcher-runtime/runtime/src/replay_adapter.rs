@@ -0,0 +1,116 @@
+//! Replay network adapter.  Serves recorded responses and never touches the
+//! network, so a run produces the same events on every host, including a
+//! WASI module without outbound HTTP.  The bundled recordings for
+//! `uma-fixture://` URLs are compiled in for the same reason: a sandboxed
+//! module cannot read them from the source tree.  Cassettes written by a
+//! [`crate::RecordingAdapter`] are loaded with [`ReplayAdapter::load`].
+//!
+//! Responses are looked up by method and URL.  When a request was recorded
+//! more than once, its responses are served in the order they were recorded
+//! and the last one is repeated after that, so a retried `503` replays as it
+//! happened.
+
+use crate::access_policy::scheme_of;
+use crate::cassette::Cassette;
+use anyhow::{anyhow, Result};
+use service::api::{AdapterCapabilities, NetworkAdapter, NetworkResponse};
+use service::model::Request;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+const SAMPLE_POST: &str = include_str!("../../tests/fixtures/sample_post.json");
+
+/// Method and URL of a recorded request.
+type Key = (String, String);
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplayAdapter {
+    recordings: HashMap<Key, Vec<NetworkResponse>>,
+    /// Responses served so far for each request.
+    played: RefCell<HashMap<Key, usize>>,
+}
+
+impl ReplayAdapter {
+    /// An adapter with no recordings; every fetch fails.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An adapter serving the recordings shipped with the sample, currently
+    /// `uma-fixture://sample-post`.
+    pub fn bundled() -> Self {
+        Self::new().record("uma-fixture://sample-post", SAMPLE_POST)
+    }
+
+    /// An adapter serving the interactions of `cassette`.
+    pub fn from_cassette(cassette: &Cassette) -> Self {
+        let mut adapter = Self::new();
+        for interaction in &cassette.interactions {
+            adapter
+                .recordings
+                .entry((
+                    interaction.method.to_ascii_uppercase(),
+                    interaction.url.clone(),
+                ))
+                .or_default()
+                .push(NetworkResponse::from(&interaction.response));
+        }
+        adapter
+    }
+
+    /// An adapter serving the cassette at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::from_cassette(&Cassette::load(path)?))
+    }
+
+    /// Serve `body` as a `200 application/json` response to `GET url`,
+    /// replacing what was recorded for it.
+    pub fn record(mut self, url: &str, body: &str) -> Self {
+        let mut headers = HashMap::new();
+        headers.insert("content-type".to_string(), "application/json".to_string());
+        let response = NetworkResponse {
+            status: 200,
+            headers,
+            body: body.into(),
+            decoded: None,
+        };
+        self.recordings
+            .insert(("GET".to_string(), url.to_string()), vec![response]);
+        self
+    }
+
+    /// The next recorded response to `GET url`, if there is one.
+    pub fn response(&self, url: &str) -> Option<NetworkResponse> {
+        self.next(("GET".to_string(), url.to_string()))
+    }
+
+    fn next(&self, key: Key) -> Option<NetworkResponse> {
+        let responses = self.recordings.get(&key)?;
+        let mut played = self.played.borrow_mut();
+        let count = played.entry(key).or_default();
+        let response = responses.get(*count).or(responses.last())?.clone();
+        *count += 1;
+        Some(response)
+    }
+}
+
+impl NetworkAdapter for ReplayAdapter {
+    fn fetch(&self, request: &Request) -> Result<NetworkResponse> {
+        self.next((request.method(), request.url.clone()))
+            .ok_or_else(|| anyhow!("no recorded response for {}", request.url))
+    }
+
+    /// The methods and schemes of the recorded requests, so anything else is
+    /// rejected before the fetch instead of failing as a missing recording.
+    /// The bundled recordings allow `GET` on `uma-fixture` URLs only.
+    fn capabilities(&self) -> AdapterCapabilities {
+        let methods: BTreeSet<&str> = self.recordings.keys().map(|(m, _)| m.as_str()).collect();
+        let schemes: BTreeSet<String> = self.recordings.keys().map(|(_, u)| scheme_of(u)).collect();
+        AdapterCapabilities {
+            methods: methods.into_iter().map(String::from).collect(),
+            schemes: schemes.into_iter().collect(),
+            ..AdapterCapabilities::default()
+        }
+    }
+}
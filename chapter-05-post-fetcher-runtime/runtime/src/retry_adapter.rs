@@ -1,45 +1,112 @@
 //! A wrapper adapter that retries failed network requests.  Retries are
-//! deterministic: the maximum number of retries and retry behaviour are
-//! fixed by configuration.  Backoff delays are not implemented in this
-//! example because the runtime must remain deterministic and avoid
-//! timers.
+//! deterministic: which responses are retried, how often and how long to
+//! wait before each is fixed by the [`RetryPolicy`].  The wait itself goes
+//! through a [`DelayProvider`], so tests and replays can skip it.  By
+//! default only idempotent requests are retried: a `POST` that failed may
+//! still have been applied.  Every attempt after the first is counted and
+//! logged in the shared `AdapterStats`, so the runtime can emit it as a
+//! `fetch_retry` event.  Streamed fetches are retried the same way, on the
+//! status in the response's head, before any of the body is read.
 
+use crate::adapter_manager::AdapterStats;
+use crate::builder::RetryPolicy;
 use anyhow::Result;
-use service::api::{NetworkAdapter, NetworkResponse};
-use std::collections::HashMap;
+use service::api::{
+    AdapterCapabilities, AsyncNetworkAdapter, DelayProvider, FetchFuture, NetworkResponse,
+    StreamFuture, StreamingResponse,
+};
+use service::model::Request;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+
+/// One attempt after the first: its number, counting the first as 1, the
+/// status that made it necessary, 0 for a transport failure, and the delay
+/// waited before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryAttempt {
+    pub attempt: u32,
+    pub previous_status: u16,
+    pub delay_millis: u64,
+}
 
 pub struct RetryAdapter {
-    inner: Box<dyn NetworkAdapter>,
-    max_retries: u32,
+    inner: Box<dyn AsyncNetworkAdapter>,
+    policy: RetryPolicy,
+    delay: Rc<dyn DelayProvider>,
+    stats: Rc<AdapterStats>,
 }
 
 impl RetryAdapter {
-    pub fn new(inner: Box<dyn NetworkAdapter>, max_retries: u32) -> Self {
-        Self { inner, max_retries }
+    pub fn new(
+        inner: Box<dyn AsyncNetworkAdapter>,
+        policy: RetryPolicy,
+        delay: Rc<dyn DelayProvider>,
+        stats: Rc<AdapterStats>,
+    ) -> Self {
+        Self {
+            inner,
+            policy,
+            delay,
+            stats,
+        }
     }
-}
 
-impl NetworkAdapter for RetryAdapter {
-    fn fetch(&self, url: &str, headers: &HashMap<String, String>) -> Result<NetworkResponse> {
-        let mut attempts = 0;
+    /// Send `request` with `send` until a response's status, read by
+    /// `status`, is not retried or the attempts run out.
+    async fn fetch_through<'a, T>(
+        &self,
+        request: &'a Request,
+        send: impl Fn(&'a Request) -> Pin<Box<dyn Future<Output = Result<T>> + 'a>>,
+        status: fn(&T) -> u16,
+    ) -> Result<T> {
+        if self.policy.idempotent_only && !request.is_idempotent() {
+            return send(request).await;
+        }
+        let mut attempt = 1;
         loop {
-            attempts += 1;
-            match self.inner.fetch(url, headers) {
-                Ok(resp) => {
-                    // Consider any 2xx status a success.
-                    if resp.status >= 200 && resp.status < 300 {
-                        return Ok(resp);
-                    }
-                    if attempts > self.max_retries {
-                        return Ok(resp);
-                    }
-                }
-                Err(err) => {
-                    if attempts > self.max_retries {
-                        return Err(err);
-                    }
-                }
+            let result = send(request).await;
+            let previous_status = match &result {
+                Ok(resp) if !self.policy.retries_status(status(resp)) => return result,
+                Ok(resp) => status(resp),
+                Err(_) => 0,
+            };
+            if attempt >= self.policy.max_attempts {
+                return result;
+            }
+            attempt += 1;
+            let delay_millis = self.policy.backoff.unwrap_or_default().before(attempt);
+            if delay_millis > 0 {
+                self.delay.delay_async(delay_millis).await;
             }
+            self.stats.retries.set(self.stats.retries.get() + 1);
+            self.stats.retry_log.borrow_mut().push(RetryAttempt {
+                attempt,
+                previous_status,
+                delay_millis,
+            });
         }
     }
 }
+
+impl AsyncNetworkAdapter for RetryAdapter {
+    fn fetch<'a>(&'a self, request: &'a Request) -> FetchFuture<'a> {
+        Box::pin(self.fetch_through(
+            request,
+            |request| self.inner.fetch(request),
+            |resp: &NetworkResponse| resp.status,
+        ))
+    }
+
+    fn fetch_stream<'a>(&'a self, request: &'a Request) -> StreamFuture<'a> {
+        Box::pin(self.fetch_through(
+            request,
+            |request| self.inner.fetch_stream(request),
+            |resp: &StreamingResponse| resp.status,
+        ))
+    }
+
+    fn capabilities(&self) -> AdapterCapabilities {
+        self.inner.capabilities()
+    }
+}
@@ -0,0 +1,72 @@
+//! Typed results of a run.  [`crate::run`] returns these directly;
+//! [`crate::run_json`] serializes them for hosts that speak JSON.
+
+use crate::metadata::LifecycleRecord;
+use service::messages::ErrorCode;
+use service::model::Output;
+
+/// The service output and lifecycle record of a completed run.  A run that
+/// failed still completes: its `lifecycle.state` is `failed` and
+/// [`RunResult::error`] says why.
+#[derive(Debug)]
+pub struct RunResult {
+    pub output: Output,
+    pub lifecycle: LifecycleRecord,
+}
+
+impl RunResult {
+    /// Why the run failed, classified from its first `error` event; `None`
+    /// when it succeeded.
+    pub fn error(&self) -> Option<RunError> {
+        let data = &self
+            .output
+            .events
+            .iter()
+            .find(|event| event.type_ == "error")?
+            .data;
+        let message = data["error"].as_str().unwrap_or_default().to_string();
+        let code = ErrorCode::ALL
+            .into_iter()
+            .find(|code| data["code"] == code.as_str());
+        Some(match code {
+            Some(
+                ErrorCode::NetworkFailure | ErrorCode::BudgetExceeded | ErrorCode::CircuitOpen,
+            ) => RunError::NetworkError(message),
+            Some(ErrorCode::ParseError) => RunError::ParseError(message),
+            Some(
+                ErrorCode::InvalidPost
+                | ErrorCode::InvalidResource
+                | ErrorCode::UnsupportedCapability
+                | ErrorCode::UnknownAdapter
+                | ErrorCode::SchemaViolation,
+            ) => RunError::ContractViolation(message),
+            _ => RunError::InputInvalid(message),
+        })
+    }
+}
+
+/// Why a run failed.  [`crate::run`] returns it when no run could be
+/// recorded at all; [`RunResult::error`] classifies runs that were recorded
+/// as `failed`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RunError {
+    /// The input is not a valid request: malformed JSON, a rejected header,
+    /// a URL outside the contract's `allowedSchemes` or `allowedHosts`, or
+    /// undeclared fields in strict mode.
+    #[error("invalid input: {0}")]
+    InputInvalid(String),
+    /// The adapter failed before producing a response, or went over one of
+    /// the contract's execution limits.
+    #[error("network error: {0}")]
+    NetworkError(String),
+    /// The response body was not valid JSON.
+    #[error("parse error: {0}")]
+    ParseError(String),
+    /// A party broke its contract: the response was not the requested
+    /// resource, the bound
+    /// adapter cannot serve the request, the contract binds an adapter that
+    /// is not registered, an event did not match its schema in strict mode,
+    /// or a host decorator returned attributes the runtime does not accept.
+    #[error("contract violation: {0}")]
+    ContractViolation(String),
+}
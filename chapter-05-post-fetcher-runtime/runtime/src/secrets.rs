@@ -0,0 +1,199 @@
+//! Masking of secret values in events.  Unlike the opt-in privacy rules,
+//! which rewrite named fields after the run, secrets are masked as each
+//! event is emitted, so no host decorator, event sink, output or lifecycle
+//! record ever holds one.
+//!
+//! A value is secret when its name matches one of the policy's sensitive
+//! key patterns: case-insensitive names in which `*` matches any run of
+//! characters and `?` any one, such as `authorization` or `*token*`.
+//! Names are found in three places:
+//!
+//! - object keys, anywhere in the event data or the host attributes;
+//! - the `header` field of an object, whose `value` field is then masked,
+//!   as in header validation errors;
+//! - `name=value` pairs and `Name: value` lines in text, such as a query
+//!   string in a URL or a header an adapter echoes into an error.
+//!
+//! Each secret value is replaced with [`REDACTED`].
+
+use crate::auth_adapter::REDACTED;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// The patterns used when the runtime policy has no `secrets` section.
+pub const DEFAULT_SENSITIVE_KEYS: [&str; 8] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "*token*",
+    "*secret*",
+    "*password*",
+    "*api*key*",
+];
+
+/// Which names hold secrets.  An empty list masks nothing.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SecretsPolicy {
+    #[serde(rename = "sensitiveKeys", default = "default_sensitive_keys")]
+    pub sensitive_keys: Vec<String>,
+}
+
+fn default_sensitive_keys() -> Vec<String> {
+    DEFAULT_SENSITIVE_KEYS.map(String::from).to_vec()
+}
+
+impl Default for SecretsPolicy {
+    fn default() -> Self {
+        Self {
+            sensitive_keys: default_sensitive_keys(),
+        }
+    }
+}
+
+impl SecretsPolicy {
+    pub fn new<I, S>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            sensitive_keys: patterns.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Whether `name` matches a sensitive key pattern.  `?` in a pattern
+    /// matches one character, so `api?key` covers `api-key` and `api_key`.
+    pub fn is_sensitive(&self, name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        self.sensitive_keys
+            .iter()
+            .any(|pattern| glob(pattern.to_ascii_lowercase().as_bytes(), name.as_bytes()))
+    }
+
+    /// Mask every secret in `value` in place.
+    pub fn mask(&self, value: &mut Value) {
+        if self.sensitive_keys.is_empty() {
+            return;
+        }
+        match value {
+            Value::String(text) => {
+                if let Some(masked) = self.mask_text(text) {
+                    *text = masked;
+                }
+            }
+            Value::Array(values) => {
+                for value in values {
+                    self.mask(value);
+                }
+            }
+            Value::Object(fields) => self.mask_fields(fields),
+            _ => {}
+        }
+    }
+
+    /// Mask every secret in the values of `fields` in place.
+    pub fn mask_fields(&self, fields: &mut Map<String, Value>) {
+        if self.sensitive_keys.is_empty() {
+            return;
+        }
+        let names_secret = fields
+            .get("header")
+            .and_then(Value::as_str)
+            .is_some_and(|header| self.is_sensitive(header));
+        for (key, value) in fields.iter_mut() {
+            if !value.is_null() && (self.is_sensitive(key) || (names_secret && key == "value")) {
+                *value = Value::String(REDACTED.to_string());
+            } else {
+                self.mask(value);
+            }
+        }
+    }
+
+    /// `text` with the values of its sensitive `name=value` pairs and
+    /// `Name: value` lines masked, or `None` when it has none.  A name must
+    /// start the text or follow whitespace or punctuation, so a path ending
+    /// in `token:` is not taken for a name.
+    fn mask_text(&self, text: &str) -> Option<String> {
+        let mut masked = String::new();
+        let mut copied = 0;
+        let mut index = 0;
+        while let Some(offset) = text[index..].find(['=', ':']) {
+            let separator = index + offset;
+            index = separator + 1;
+            let start = text[copied..separator]
+                .char_indices()
+                .rev()
+                .find(|(_, c)| !is_name_char(*c))
+                .map_or(copied, |(at, c)| copied + at + c.len_utf8());
+            let before = text[..start].chars().next_back();
+            let bounded = match before {
+                None => true,
+                Some(c) => c.is_whitespace() || "\"'{([,;".contains(c),
+            } || (text.as_bytes()[separator] == b'='
+                && matches!(before, Some('?' | '&')));
+            if start == separator || !bounded || !self.is_sensitive(&text[start..separator]) {
+                continue;
+            }
+            let value_start = if text.as_bytes()[separator] == b':' {
+                let after = &text[separator + 1..];
+                separator + 1 + (after.len() - after.trim_start_matches(' ').len())
+            } else {
+                separator + 1
+            };
+            let ends: &[char] = if text.as_bytes()[separator] == b'=' {
+                &['&', '#', ';', ',', '"', '\'', ' ', '\t', '\r', '\n']
+            } else {
+                &[';', ',', '"', '\'', '\r', '\n']
+            };
+            let value_end = text[value_start..]
+                .find(ends)
+                .map_or(text.len(), |end| value_start + end);
+            if value_end == value_start {
+                continue;
+            }
+            masked.push_str(&text[copied..value_start]);
+            masked.push_str(REDACTED);
+            copied = value_end;
+            index = value_end;
+        }
+        if copied == 0 {
+            return None;
+        }
+        masked.push_str(&text[copied..]);
+        Some(masked)
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')
+}
+
+/// Whether `name` matches `pattern`, in which `*` matches any run of bytes
+/// and `?` any one byte.
+fn glob(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was and the name position it is trying.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
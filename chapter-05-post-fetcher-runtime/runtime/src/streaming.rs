@@ -0,0 +1,191 @@
+//! Incremental parsing of JSON array bodies.  When a run streams its
+//! responses (see [`crate::RuntimeBuilder::stream_chunks`]), a list endpoint's
+//! body is fed to a [`JsonArrayStream`] chunk by chunk, and each element is
+//! normalized as soon as its closing byte arrives.  Only the element being
+//! read is buffered, so a body of many megabytes never has to be held whole.
+//!
+//! The parser only finds where each element ends; the element itself is
+//! parsed with `serde_json`, so what counts as valid JSON is the same as for
+//! a buffered body.
+
+use serde_json::Value;
+
+/// Why a body could not be read as a JSON array.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArrayStreamError {
+    /// The body does not start with `[`.  Nothing has been consumed but
+    /// whitespace, so the caller can read the body whole instead.
+    NotAnArray,
+    /// The body is not valid JSON.
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Before the opening `[`.
+    Start,
+    /// Where an element may start; `]` may close the array unless a comma
+    /// was just read.
+    Value { may_close: bool },
+    /// Inside an element.
+    Element,
+    /// After an element, where a comma or `]` must follow.
+    Separator,
+    /// After the closing `]`.
+    End,
+}
+
+/// A JSON array parser fed a body in chunks, returning each element once it
+/// is complete.
+#[derive(Debug)]
+pub struct JsonArrayStream {
+    state: State,
+    element: Vec<u8>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl Default for JsonArrayStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonArrayStream {
+    pub fn new() -> Self {
+        Self {
+            state: State::Start,
+            element: Vec::new(),
+            depth: 0,
+            in_string: false,
+            escaped: false,
+        }
+    }
+
+    /// Whether the opening `[` has been read.
+    pub fn in_array(&self) -> bool {
+        self.state != State::Start
+    }
+
+    /// Feed the next `chunk` of the body, returning the elements it
+    /// completed, in order.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<Value>, ArrayStreamError> {
+        let mut elements = Vec::new();
+        for &byte in chunk {
+            if self.state == State::Element {
+                if let Some(element) = self.element_byte(byte)? {
+                    elements.push(element);
+                    if self.state != State::Separator {
+                        // The byte that ended a number or literal still
+                        // needs reading as a separator.
+                        self.state = State::Separator;
+                        self.separator_byte(byte)?;
+                    }
+                }
+                continue;
+            }
+            if byte.is_ascii_whitespace() {
+                continue;
+            }
+            match self.state {
+                State::Start if byte == b'[' => self.state = State::Value { may_close: true },
+                State::Start => return Err(ArrayStreamError::NotAnArray),
+                State::Value { may_close: true } if byte == b']' => self.state = State::End,
+                State::Value { .. } if matches!(byte, b']' | b',') => {
+                    return Err(invalid(format!(
+                        "expected a value, found `{}`",
+                        byte as char
+                    )))
+                }
+                State::Value { .. } => self.start_element(byte),
+                State::Separator => self.separator_byte(byte)?,
+                State::End => return Err(invalid("trailing characters after the array")),
+                State::Element => unreachable!("handled above"),
+            }
+        }
+        Ok(elements)
+    }
+
+    /// Check the body ended with the array closed.  A body that held only
+    /// whitespace is [`ArrayStreamError::NotAnArray`].
+    pub fn finish(self) -> Result<(), ArrayStreamError> {
+        match self.state {
+            State::End => Ok(()),
+            State::Start => Err(ArrayStreamError::NotAnArray),
+            _ => Err(invalid("the body ended inside the array")),
+        }
+    }
+
+    fn start_element(&mut self, byte: u8) {
+        self.state = State::Element;
+        self.element.clear();
+        self.element.push(byte);
+        self.depth = usize::from(matches!(byte, b'{' | b'['));
+        self.in_string = byte == b'"';
+        self.escaped = false;
+    }
+
+    /// Read `byte` of the current element, returning the element if the
+    /// byte ended it.  A number or literal ends at the first byte that
+    /// cannot be part of it, which is not included; the state is then left
+    /// at [`State::Element`] for the caller to read the byte again.
+    fn element_byte(&mut self, byte: u8) -> Result<Option<Value>, ArrayStreamError> {
+        let scalar = self.depth == 0 && !self.in_string && !self.element.starts_with(b"\"");
+        if scalar {
+            if byte.is_ascii_whitespace() || matches!(byte, b',' | b']') {
+                return self.complete().map(Some);
+            }
+            self.element.push(byte);
+            return Ok(None);
+        }
+        self.element.push(byte);
+        if self.in_string {
+            if self.escaped {
+                self.escaped = false;
+            } else if byte == b'\\' {
+                self.escaped = true;
+            } else if byte == b'"' {
+                self.in_string = false;
+            }
+        } else {
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' => self.depth = self.depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        if self.depth == 0 && !self.in_string {
+            let element = self.complete()?;
+            self.state = State::Separator;
+            return Ok(Some(element));
+        }
+        Ok(None)
+    }
+
+    fn separator_byte(&mut self, byte: u8) -> Result<(), ArrayStreamError> {
+        match byte {
+            b',' => self.state = State::Value { may_close: false },
+            b']' => self.state = State::End,
+            byte if byte.is_ascii_whitespace() => {}
+            byte => {
+                return Err(invalid(format!(
+                    "expected `,` or `]`, found `{}`",
+                    byte as char
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse the buffered element.
+    fn complete(&mut self) -> Result<Value, ArrayStreamError> {
+        let element = std::mem::take(&mut self.element);
+        serde_json::from_slice(&element).map_err(|err| invalid(err.to_string()))
+    }
+}
+
+fn invalid(detail: impl Into<String>) -> ArrayStreamError {
+    ArrayStreamError::Invalid(detail.into())
+}
@@ -5,8 +5,10 @@
 use super::*;
 use serde_json::{json, Value};
 use service::api::{NetworkAdapter, NetworkResponse};
+use service::normalizer::Normalizer;
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::rc::Rc;
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
     Arc, Mutex, OnceLock,
@@ -21,16 +23,13 @@ fn env_lock() -> std::sync::MutexGuard<'static, ()> {
 struct DummyAdapter;
 
 impl NetworkAdapter for DummyAdapter {
-    fn fetch(
-        &self,
-        _url: &str,
-        _headers: &HashMap<String, String>,
-    ) -> anyhow::Result<NetworkResponse> {
+    fn fetch(&self, _request: &service::model::Request) -> anyhow::Result<NetworkResponse> {
         let body = r#"{"id":1,"userId":2,"title":"t","body":"b"}"#;
         Ok(NetworkResponse {
             status: 200,
             headers: HashMap::new(),
-            body: body.to_string(),
+            body: body.into(),
+            decoded: None,
         })
     }
 }
@@ -40,23 +39,20 @@ struct CountingAdapter {
 }
 
 impl NetworkAdapter for CountingAdapter {
-    fn fetch(
-        &self,
-        _url: &str,
-        _headers: &HashMap<String, String>,
-    ) -> anyhow::Result<NetworkResponse> {
+    fn fetch(&self, _request: &service::model::Request) -> anyhow::Result<NetworkResponse> {
         self.fetch_calls.fetch_add(1, Ordering::SeqCst);
         Ok(NetworkResponse {
             status: 200,
             headers: HashMap::new(),
-            body: r#"{"id":1,"userId":2,"title":"t","body":"b"}"#.to_string(),
+            body: r#"{"id":1,"userId":2,"title":"t","body":"b"}"#.into(),
+            decoded: None,
         })
     }
 }
 
 #[test]
 fn test_event_bus_increment() {
-    let mut bus = event_bus::EventBus::new();
+    let mut bus = event_bus::EventBus::with_decorator(None);
     assert_eq!(bus.clock, 0);
     bus.emit("start", json!({}));
     assert_eq!(bus.clock, 1);
@@ -73,8 +69,11 @@ fn test_lifecycle_record() {
     };
     let events = vec![service::model::Event {
         t: "0".to_string(),
+        wall_time: None,
+        trace: None,
         type_: "start".to_string(),
         data: json!({}),
+        host: None,
     }];
     let rec = metadata::LifecycleRecord::new(
         "svc",
@@ -131,7 +130,9 @@ fn test_header_validation_and_final_state() {
         .as_array()
         .unwrap()
         .iter()
-        .any(|e| e["type"] == "error"));
+        .any(|e| e["type"] == "error"
+            && e["data"]["code"] == "header.unexpected"
+            && e["data"]["params"]["header"] == "x-foo"));
     let meta_val: Value = serde_json::from_str(&meta_json).unwrap();
     assert_eq!(meta_val["state"], "failed");
 }
@@ -168,52 +169,902 @@ fn test_header_validation_skips_network_fetch() {
 }
 
 #[test]
-fn test_adapter_manager_env_wrappers() {
+fn test_run_returns_typed_results_and_classified_errors() {
+    let _guard = env_lock();
+    let input = |url: &str| {
+        let request = service::model::Request {
+            url: url.to_string(),
+            ..Default::default()
+        };
+        Input::new(request, "run-typed")
+    };
+    let result = run(
+        input("https://example.com/posts/1"),
+        Some(Box::new(DummyAdapter)),
+    )
+    .unwrap();
+    assert_eq!(result.output.normalized_post.as_ref().unwrap().id, 1);
+    assert_eq!(result.lifecycle.state, "terminated");
+    assert_eq!(result.error(), None);
+
+    let mut patch = input("https://example.com/posts/1");
+    patch.request.as_mut().unwrap().method = Some("PATCH".to_string());
+    let failed = run(patch, None).unwrap();
+    assert_eq!(failed.lifecycle.state, "failed");
+    assert_eq!(
+        failed.error(),
+        Some(RunError::ContractViolation(
+            "adapter host-fetch does not support method PATCH".to_string()
+        ))
+    );
+
+    // The contract's allowlist is checked before the adapter's capabilities.
+    let denied = run(input("ftp://example.com"), None).unwrap();
+    assert_eq!(
+        denied.error(),
+        Some(RunError::InputInvalid(
+            "scheme ftp is not allowed".to_string()
+        ))
+    );
+
+    let err = run_json("{", None).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<RunError>(),
+        Some(RunError::InputInvalid(_))
+    ));
+}
+
+#[test]
+fn test_unsupported_capability_fails_before_fetch() {
+    let _guard = env_lock();
+    let policy = AdapterPolicy {
+        retries: 3,
+        ..Default::default()
+    };
+    let host_fetch = AdapterRegistry::builtin()
+        .create_async("host-fetch", ExecutionLimits::default())
+        .unwrap();
+    let mgr = adapter_manager::AdapterManager::new(
+        host_fetch,
+        "host-fetch".to_string(),
+        &policy,
+        adapter_manager::AdapterContext::default(),
+    );
+    // The retry wrapper reports the host fetch adapter's capabilities.
+    let capabilities = mgr.capabilities();
+    assert_eq!(capabilities.methods, ["GET", "POST", "PUT", "DELETE"]);
+    assert!(capabilities.schemes.contains(&"uma-fixture".to_string()));
+    assert!(capabilities.tls && capabilities.compression && !capabilities.streaming);
+
+    let fetch_calls = Arc::new(AtomicUsize::new(0));
+    let adapter = CountingAdapter {
+        fetch_calls: Arc::clone(&fetch_calls),
+    };
+    let input = json!({
+        "request": { "url": "https://example.com/posts", "method": "post" },
+        "runId": "run-caps"
+    });
+    let (out_json, meta_json) = run_json(&input.to_string(), Some(Box::new(adapter))).unwrap();
+    let out_val: Value = serde_json::from_str(&out_json).unwrap();
+    let events = out_val["events"].as_array().unwrap();
+    assert_eq!(fetch_calls.load(Ordering::SeqCst), 0);
+    assert_eq!(events.len(), 3);
+    assert_eq!(
+        events[1]["data"],
+        json!({
+            "error": "adapter custom does not support method POST",
+            "code": "adapter.unsupported_capability",
+            "params": { "adapter": "custom", "capability": "method", "requested": "POST" },
+        })
+    );
+    let meta_val: Value = serde_json::from_str(&meta_json).unwrap();
+    assert_eq!(meta_val["state"], "failed");
+}
+
+#[test]
+fn test_method_and_body_reach_the_adapter() {
+    let _guard = env_lock();
+    /// Method and body of every request the adapter received.
+    type Sent = Arc<Mutex<Vec<(String, Option<Vec<u8>>)>>>;
+    struct RecordingAdapter {
+        sent: Sent,
+    }
+
+    impl NetworkAdapter for RecordingAdapter {
+        fn fetch(&self, request: &service::model::Request) -> anyhow::Result<NetworkResponse> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push((request.method(), request.body.clone()));
+            Err(anyhow::anyhow!("unreachable"))
+        }
+
+        fn capabilities(&self) -> service::api::AdapterCapabilities {
+            service::api::AdapterCapabilities {
+                methods: vec!["GET".to_string(), "POST".to_string()],
+                ..Default::default()
+            }
+        }
+    }
+
+    // Retries apply to idempotent methods only, so the failing POST is sent
+    // exactly once.
+    std::env::set_var("UMA_ENABLE_RETRY", "1");
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let adapter = RecordingAdapter {
+        sent: Arc::clone(&sent),
+    };
+    let input = json!({
+        "request": {
+            "url": "https://example.com/posts",
+            "method": "post",
+            "headers": { "content-type": "application/json; charset=utf-8" },
+            "body": { "title": "t" }
+        },
+        "runId": "run-post"
+    });
+    let result = run_json(&input.to_string(), Some(Box::new(adapter)));
+    std::env::remove_var("UMA_ENABLE_RETRY");
+    let (out_json, meta_json) = result.unwrap();
+    let out_val: Value = serde_json::from_str(&out_json).unwrap();
+    let fetch_request = out_val["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|e| e["type"] == "fetch_request")
+        .unwrap();
+    assert_eq!(
+        fetch_request["data"],
+        json!({ "url": "https://example.com/posts", "method": "POST" })
+    );
+    // An inline JSON body is sent serialized; the lifecycle record keeps
+    // only its size.
+    let payload = br#"{"title":"t"}"#;
+    assert_eq!(
+        *sent.lock().unwrap(),
+        [("POST".to_string(), Some(payload.to_vec()))]
+    );
+    let meta_val: Value = serde_json::from_str(&meta_json).unwrap();
+    assert_eq!(meta_val["payloadBytes"], payload.len());
+    assert!(!meta_json.contains("title"));
+}
+
+#[test]
+fn test_content_type_must_be_submittable() {
+    let _guard = env_lock();
+    let fetch_calls = Arc::new(AtomicUsize::new(0));
+    let run = |content_type: &str| {
+        let adapter = CountingAdapter {
+            fetch_calls: Arc::clone(&fetch_calls),
+        };
+        let input = json!({
+            "request": {
+                "url": "https://example.com/posts/1",
+                "headers": { "Content-Type": content_type }
+            },
+            "runId": "run-content-type"
+        });
+        let (out_json, _) = run_json(&input.to_string(), Some(Box::new(adapter))).unwrap();
+        let out_val: Value = serde_json::from_str(&out_json).unwrap();
+        out_val["events"].clone()
+    };
+    for accepted in [
+        "application/json",
+        "Application/JSON; charset=utf-8",
+        "application/merge-patch+json",
+        "text/plain",
+    ] {
+        assert_eq!(run(accepted)[1]["type"], "fetch_request", "{accepted}");
+    }
+    assert_eq!(fetch_calls.load(Ordering::SeqCst), 4);
+    let rejected = run("multipart/form-data; boundary=x");
+    assert_eq!(rejected[1]["type"], "header_unsupported_content_type");
+    assert_eq!(
+        rejected[2]["data"],
+        json!({
+            "error": "unsupported Content-Type multipart/form-data; boundary=x",
+            "code": "header.unsupported_content_type",
+            "params": { "header": "Content-Type", "value": "multipart/form-data; boundary=x" },
+        })
+    );
+    assert_eq!(fetch_calls.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn test_adapter_manager_wrappers_follow_policies() {
+    use adapter_manager::AdapterManager;
+    // The binding impl_name reflects the applied wrappers.
+    let name = |retries, failure_threshold, cache| {
+        let policy = AdapterPolicy {
+            retries,
+            circuit_breaker: CircuitBreakerPolicy {
+                failure_threshold,
+                cooldown_requests: 1,
+            },
+            cache,
+            ..AdapterPolicy::default()
+        };
+        let host_fetch = AdapterRegistry::builtin()
+            .create_async("host-fetch", ExecutionLimits::default())
+            .unwrap();
+        AdapterManager::new(
+            host_fetch,
+            "host-fetch".to_string(),
+            &policy,
+            adapter_manager::AdapterContext::default(),
+        )
+        .binding
+        .impl_name
+    };
+    assert_eq!(name(0, 0, false), "host-fetch");
+    assert_eq!(name(3, 0, false), "retry-host-fetch");
+    assert_eq!(name(0, 0, true), "cache-host-fetch");
+    assert_eq!(name(3, 0, true), "cache-retry-host-fetch");
+    assert_eq!(name(3, 2, true), "cache-circuit-retry-host-fetch");
+}
+
+#[test]
+fn test_contract_bindings_select_a_registered_adapter() {
+    let registry = AdapterRegistry::builtin();
+    assert_eq!(
+        registry.names().collect::<Vec<_>>(),
+        ["host-fetch", "replay"]
+    );
+    assert_eq!(
+        ServiceContract::bundled().bindings.network_fetch(),
+        DEFAULT_ADAPTER
+    );
+
+    let bound_to = |name: &str| {
+        let contract = ServiceContract::from_json(
+            &json!({ "bindings": { "network.fetch": name } }).to_string(),
+        )
+        .unwrap();
+        Runtime::builder()
+            .contract(contract)
+            .register_adapter("stub", |_| {
+                Box::new(ReplayAdapter::new().record("uma-fixture://sample-post", "{}"))
+            })
+            .build()
+    };
+    let run = |runtime: &Runtime| {
+        let input =
+            json!({ "request": { "url": "uma-fixture://sample-post" }, "runId": "run-bound" });
+        let (out_json, meta_json) = runtime.run_json(&input.to_string()).unwrap();
+        let out: Value = serde_json::from_str(&out_json).unwrap();
+        let meta: Value = serde_json::from_str(&meta_json).unwrap();
+        (out, meta)
+    };
+
+    let (out, meta) = run(&bound_to("replay"));
+    assert_eq!(out["normalizedPost"]["id"], 1);
+    assert_eq!(meta["bindings"]["network.fetch"]["impl"], "replay");
+
+    // Hosts register their own implementations under new names.
+    let (out, meta) = run(&bound_to("stub"));
+    assert_eq!(out["events"][3]["data"]["code"], "response.invalid_post");
+    assert_eq!(meta["bindings"]["network.fetch"]["impl"], "stub");
+
+    // An adapter given directly wins over the contract's binding.
+    let contract =
+        ServiceContract::from_json(r#"{ "bindings": { "network.fetch": "stub" } }"#).unwrap();
+    let runtime = Runtime::builder()
+        .contract(contract)
+        .adapter(Box::new(ReplayAdapter::bundled()))
+        .build();
+    let (out, meta) = run(&runtime);
+    assert_eq!(out["normalizedPost"]["id"], 1);
+    assert_eq!(meta["bindings"]["network.fetch"]["impl"], "custom");
+
+    // A name nothing is registered under fails the run before fetching.
+    let runtime = bound_to("mock");
+    let (out, meta) = run(&runtime);
+    assert_eq!(
+        out["events"][1]["data"],
+        json!({
+            "error": "no adapter is registered as mock",
+            "code": "adapter.unknown",
+            "params": { "adapter": "mock" },
+        })
+    );
+    assert_eq!(out["summary"]["totalRequests"], 0);
+    assert_eq!(meta["bindings"]["network.fetch"]["impl"], "mock");
+    let input: Input = serde_json::from_value(
+        json!({ "request": { "url": "uma-fixture://sample-post" }, "runId": "run-bound" }),
+    )
+    .unwrap();
+    assert_eq!(
+        runtime.run(input).unwrap().error(),
+        Some(RunError::ContractViolation(
+            "no adapter is registered as mock".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_circuit_breaker_opens_fails_fast_and_recovers() {
+    struct Scripted(std::cell::RefCell<Vec<u16>>, Arc<AtomicUsize>);
+
+    impl NetworkAdapter for Scripted {
+        fn fetch(&self, _req: &service::model::Request) -> anyhow::Result<NetworkResponse> {
+            self.1.fetch_add(1, Ordering::SeqCst);
+            let status = self.0.borrow_mut().remove(0);
+            if status == 0 {
+                anyhow::bail!("connection refused");
+            }
+            Ok(NetworkResponse {
+                status,
+                headers: Default::default(),
+                body: r#"{"id":1,"userId":2,"title":"t","body":"b"}"#.into(),
+                decoded: None,
+            })
+        }
+    }
+
+    let contract = ServiceContract::from_json(
+        r#"{ "adapter": { "circuitBreaker": { "failureThreshold": 2, "cooldownRequests": 1 } } }"#,
+    )
+    .unwrap();
+    let fetch_calls = Arc::new(AtomicUsize::new(0));
+    // 4xx is not a host failure, so it resets nothing and opens nothing.
+    let statuses = vec![503, 404, 0, 0, 500, 200];
+    let runtime = Runtime::builder()
+        .contract(contract)
+        .adapter(Box::new(Scripted(
+            std::cell::RefCell::new(statuses),
+            Arc::clone(&fetch_calls),
+        )))
+        .build();
+    let run = |url: &str| {
+        let input = json!({ "request": { "url": url }, "runId": "run-circuit" });
+        let (out_json, _) = runtime.run_json(&input.to_string()).unwrap();
+        let out: Value = serde_json::from_str(&out_json).unwrap();
+        let transitions: Vec<Value> = out["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|e| e["type"] == "circuit_state")
+            .map(|e| e["data"].clone())
+            .collect();
+        (out, transitions)
+    };
+    let url = "https://api.example.com/posts/1";
+
+    let (_, transitions) = run(url);
+    assert!(transitions.is_empty());
+    run(url);
+    run(url);
+    let (_, transitions) = run(url);
+    assert_eq!(
+        transitions,
+        [json!({ "host": "api.example.com", "from": "closed", "to": "open" })]
+    );
+    assert_eq!(
+        runtime.circuits().state("API.example.com"),
+        CircuitState::Open
+    );
+
+    // Open: the next request fails fast without reaching the adapter, and
+    // other hosts are unaffected.
+    let before = fetch_calls.load(Ordering::SeqCst);
+    let (out, _) = run(url);
+    assert_eq!(fetch_calls.load(Ordering::SeqCst), before);
+    assert_eq!(out["events"][3]["data"]["code"], "network.circuit_open");
+    assert_eq!(
+        out["events"][3]["data"]["params"],
+        json!({ "host": "api.example.com" })
+    );
+    assert_eq!(runtime.circuits().state("other.test"), CircuitState::Closed);
+
+    // The cooldown is over: a failed trial reopens, a good one closes.
+    let (_, transitions) = run(url);
+    assert_eq!(
+        transitions,
+        [
+            json!({ "host": "api.example.com", "from": "open", "to": "half_open" }),
+            json!({ "host": "api.example.com", "from": "half_open", "to": "open" }),
+        ]
+    );
+    run(url);
+    let (out, transitions) = run(url);
+    assert_eq!(
+        transitions,
+        [
+            json!({ "host": "api.example.com", "from": "open", "to": "half_open" }),
+            json!({ "host": "api.example.com", "from": "half_open", "to": "closed" }),
+        ]
+    );
+    assert_eq!(out["normalizedPost"]["id"], 1);
+    assert_eq!(fetch_calls.load(Ordering::SeqCst), 6);
+}
+
+#[test]
+fn test_builder_reads_wrapper_policies_from_env() {
     let _guard = env_lock();
-    // Test that environment variables cause the adapter manager to wrap
-    // adapters in retry and cache wrappers.  The binding impl_name should
-    // reflect the applied wrappers.
     use std::env;
     // Clear any existing variables to start from a clean state.
     env::remove_var("UMA_ENABLE_RETRY");
     env::remove_var("UMA_ENABLE_CACHE");
-    // No wrappers when variables unset
-    let mgr = adapter_manager::AdapterManager::new(None);
-    assert_eq!(mgr.binding.impl_name.contains("retry"), false);
-    assert_eq!(mgr.binding.impl_name.contains("cache"), false);
-    // Enable retry
+    let runtime = Runtime::builder().with_env().build();
+    assert_eq!((runtime.retry, runtime.cache), (None, None));
     env::set_var("UMA_ENABLE_RETRY", "1");
-    let mgr_retry = adapter_manager::AdapterManager::new(None);
-    assert!(mgr_retry.binding.impl_name.contains("retry"));
-    env::remove_var("UMA_ENABLE_RETRY");
-    // Enable cache
     env::set_var("UMA_ENABLE_CACHE", "1");
-    let mgr_cache = adapter_manager::AdapterManager::new(None);
-    assert!(mgr_cache.binding.impl_name.contains("cache"));
-    // Enable both
-    env::set_var("UMA_ENABLE_RETRY", "1");
-    let mgr_both = adapter_manager::AdapterManager::new(None);
-    assert!(mgr_both.binding.impl_name.contains("retry"));
-    assert!(mgr_both.binding.impl_name.contains("cache"));
+    let runtime = Runtime::builder().with_env().build();
+    assert_eq!(
+        (runtime.retry, runtime.cache),
+        (Some(RetryPolicy::up_to(3)), Some(CachePolicy::InMemory))
+    );
+    // Without `with_env` the environment is ignored.
+    let runtime = Runtime::builder().build();
+    assert_eq!((runtime.retry, runtime.cache), (None, None));
     // Clean up
     env::remove_var("UMA_ENABLE_RETRY");
     env::remove_var("UMA_ENABLE_CACHE");
 }
 
+#[test]
+fn test_builder_configures_runs_in_code() {
+    let _guard = env_lock();
+    let fetch_calls = Arc::new(AtomicUsize::new(0));
+    let seen = Mutex::new(Vec::new());
+    let sink = |event: &service::model::Event| seen.lock().unwrap().push(event.type_.clone());
+    let runtime = Runtime::builder()
+        .adapter(Box::new(CountingAdapter {
+            fetch_calls: Arc::clone(&fetch_calls),
+        }))
+        .cache(CachePolicy::InMemory)
+        .allowed_headers(["Accept", "X-Request-Id"])
+        .sink(&sink)
+        .build();
+    let input = |headers: Value| {
+        json!({
+            "request": { "url": "https://example.com/posts/1", "headers": headers },
+            "runId": "run-builder"
+        })
+        .to_string()
+    };
+
+    let (_, meta_json) = runtime
+        .run_json(&input(json!({ "x-request-id": "r1" })))
+        .unwrap();
+    let meta_val: Value = serde_json::from_str(&meta_json).unwrap();
+    assert_eq!(
+        meta_val["bindings"]["network.fetch"]["impl"],
+        "cache-custom"
+    );
+    assert_eq!(meta_val["state"], "terminated");
+    assert_eq!(
+        *seen.lock().unwrap(),
+        [
+            "start",
+            "fetch_request",
+            "cache_miss",
+            "fetch_response",
+            "normalized",
+            "end"
+        ]
+    );
+
+    // The same runtime runs again with the same adapter; `authorization`
+    // is no longer on the allowlist.
+    let (out_json, _) = runtime
+        .run_json(&input(json!({ "authorization": "t" })))
+        .unwrap();
+    let out_val: Value = serde_json::from_str(&out_json).unwrap();
+    assert_eq!(out_val["events"][1]["type"], "header_unexpected");
+    assert_eq!(out_val["events"][2]["data"]["code"], "header.unexpected");
+    assert_eq!(fetch_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(seen.lock().unwrap().len(), 10);
+}
+
+#[test]
+fn test_contract_adapter_policy_drives_wrappers_and_hosts() {
+    let _guard = env_lock();
+    std::env::remove_var("UMA_ENABLE_RETRY");
+    std::env::remove_var("UMA_ENABLE_CACHE");
+    let contract = ServiceContract::from_json(
+        r#"{ "adapter": { "retries": 2, "cache": true, "allowedHosts": ["Example.com"] } }"#,
+    )
+    .unwrap();
+    assert!(contract.adapter.allows("https://example.com:8443/posts/1"));
+    assert!(contract.adapter.allows("https://user@EXAMPLE.com/posts/1"));
+    assert!(!contract
+        .adapter
+        .allows("https://example.com.evil.test/posts/1"));
+    assert!(AdapterPolicy::default().allows("https://anywhere.test"));
+
+    let fetch_calls = Arc::new(AtomicUsize::new(0));
+    let run = |url: &str, retry: Option<RetryPolicy>| {
+        let mut builder = Runtime::builder()
+            .contract(contract.clone())
+            .adapter(Box::new(CountingAdapter {
+                fetch_calls: Arc::clone(&fetch_calls),
+            }));
+        if let Some(retry) = retry {
+            builder = builder.retry(retry);
+        }
+        let input = json!({ "request": { "url": url }, "runId": "run-policy" });
+        let (out_json, meta_json) = builder.build().run_json(&input.to_string()).unwrap();
+        let out_val: Value = serde_json::from_str(&out_json).unwrap();
+        let meta_val: Value = serde_json::from_str(&meta_json).unwrap();
+        (out_val, meta_val)
+    };
+
+    let (_, meta) = run("https://example.com/posts/1", None);
+    assert_eq!(
+        meta["bindings"]["network.fetch"]["impl"],
+        "cache-retry-custom"
+    );
+    assert_eq!(
+        meta["adapterPolicy"],
+        json!({
+            "retries": 2,
+            "retryOn": [],
+            "retryNonIdempotent": false,
+            "backoff": { "initialMillis": 0, "maxMillis": 0 },
+            "circuitBreaker": { "failureThreshold": 0, "cooldownRequests": 0 },
+            "cache": true,
+            "cacheLimits": { "ttlMillis": null, "maxEntries": null },
+            "allowedHosts": ["Example.com"],
+            "allowedSchemes": [],
+        })
+    );
+
+    // Runtime settings override the contract, and the record says so.
+    let (_, meta) = run("https://example.com/posts/1", Some(RetryPolicy::never()));
+    assert_eq!(meta["bindings"]["network.fetch"]["impl"], "cache-custom");
+    assert_eq!(meta["adapterPolicy"]["retries"], 0);
+    assert_eq!(fetch_calls.load(Ordering::SeqCst), 2);
+
+    let (out, meta) = run("https://other.test/posts/1", None);
+    assert_eq!(
+        out["events"][2]["data"],
+        json!({
+            "error": "host other.test is not allowed",
+            "code": "request.host_not_allowed",
+            "params": { "host": "other.test" },
+        })
+    );
+    assert_eq!(meta["state"], "failed");
+    assert_eq!(fetch_calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_policy_denied_urls_are_never_fetched() {
+    let contract = ServiceContract::from_json(
+        r#"{ "adapter": { "allowedSchemes": ["HTTPS"], "allowedHosts": ["example.com"] } }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        check_url(&contract.adapter, "file:///etc/passwd"),
+        Err(PolicyDenial::Scheme("file".to_string()))
+    );
+    assert_eq!(
+        check_url(&contract.adapter, "https://other.test:8443/posts/1"),
+        Err(PolicyDenial::Host("other.test".to_string()))
+    );
+    assert_eq!(
+        check_url(&contract.adapter, "https://Example.com/posts/1"),
+        Ok(())
+    );
+
+    let fetch_calls = Arc::new(AtomicUsize::new(0));
+    let runtime = Runtime::builder()
+        .contract(contract)
+        .adapter(Box::new(CountingAdapter {
+            fetch_calls: Arc::clone(&fetch_calls),
+        }))
+        .build();
+    let input = |url: &str| {
+        let request = service::model::Request {
+            url: url.to_string(),
+            ..Default::default()
+        };
+        Input::new(request, "run-denied")
+    };
+
+    let result = runtime.run(input("file:///etc/passwd")).unwrap();
+    let types: Vec<&str> = result
+        .output
+        .events
+        .iter()
+        .map(|e| e.type_.as_str())
+        .collect();
+    assert_eq!(types, ["start", "policy_denied", "error", "end"]);
+    assert_eq!(
+        result.output.events[1].data,
+        json!({ "url": "file:///etc/passwd", "rule": "allowedSchemes", "value": "file" })
+    );
+    assert_eq!(
+        result.output.events[2].data["code"],
+        "request.scheme_not_allowed"
+    );
+    assert_eq!(
+        result.error(),
+        Some(RunError::InputInvalid(
+            "scheme file is not allowed".to_string()
+        ))
+    );
+    assert_eq!(result.lifecycle.state, "failed");
+    assert_eq!(result.output.summary.total_requests, 0);
+
+    let result = runtime.run(input("https://example.com/posts/1")).unwrap();
+    assert_eq!(result.error(), None);
+    assert_eq!(fetch_calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_contract_header_policy_records_each_violation() {
+    let policy = HeaderPolicy {
+        allowed: vec!["accept".into()],
+        max_value_length: Some(4),
+        required: vec!["X-Api-Version".into()],
+    };
+    assert!(policy.allows("ACCEPT") && policy.allows("x-api-version"));
+    assert!(!policy.allows("authorization"));
+    let headers: HashMap<String, String> = [("X-Debug", "1"), ("accept", "text/*")]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    assert_eq!(
+        policy.validate(&headers),
+        vec![
+            HeaderViolation::TooLong {
+                header: "accept".into(),
+                length: 6,
+                max: 4
+            },
+            HeaderViolation::Unexpected {
+                header: "X-Debug".into()
+            },
+            HeaderViolation::Missing {
+                header: "X-Api-Version".into()
+            },
+        ]
+    );
+    let unlimited = HeaderPolicy {
+        max_value_length: None,
+        ..policy.clone()
+    };
+    assert_eq!(unlimited.validate(&headers).len(), 2);
+
+    let fetch_calls = Arc::new(AtomicUsize::new(0));
+    let contract = ServiceContract::from_json(
+        r#"{ "headers": { "allowed": ["accept"], "maxValueLength": 4, "required": ["X-Api-Version"] } }"#,
+    )
+    .unwrap();
+    assert_eq!(contract.headers, policy);
+    let runtime = Runtime::builder()
+        .contract(contract)
+        .adapter(Box::new(CountingAdapter {
+            fetch_calls: Arc::clone(&fetch_calls),
+        }))
+        .build();
+    let input = json!({
+        "request": { "url": "https://example.com/posts/1", "headers": { "accept": "text/*" } },
+        "runId": "run-header-policy"
+    });
+    let (out_json, meta_json) = runtime.run_json(&input.to_string()).unwrap();
+    let out_val: Value = serde_json::from_str(&out_json).unwrap();
+    let events: Vec<(&str, &Value)> = out_val["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| (event["type"].as_str().unwrap(), &event["data"]))
+        .collect();
+    assert_eq!(events[1].0, "header_too_long");
+    assert_eq!(
+        events[1].1,
+        &json!({ "header": "accept", "length": 6, "maxLength": 4 })
+    );
+    assert_eq!(events[2].1["code"], "header.too_long");
+    assert_eq!(events[3].0, "header_missing");
+    assert_eq!(events[3].1, &json!({ "header": "X-Api-Version" }));
+    assert_eq!(
+        events[4].1,
+        &json!({
+            "error": "missing header X-Api-Version",
+            "code": "header.missing",
+            "params": { "header": "X-Api-Version" },
+        })
+    );
+    assert_eq!(fetch_calls.load(Ordering::SeqCst), 0);
+    let meta_val: Value = serde_json::from_str(&meta_json).unwrap();
+    assert_eq!(meta_val["state"], "failed");
+
+    // The builder's allowlist replaces the contract's, keeping the rest.
+    let runtime = Runtime::builder()
+        .contract(
+            ServiceContract::from_json(r#"{ "headers": { "required": ["accept"] } }"#).unwrap(),
+        )
+        .allowed_headers(["x-debug"])
+        .adapter(Box::new(DummyAdapter))
+        .build();
+    let input = json!({
+        "request": { "url": "https://example.com/posts/1", "headers": { "X-Debug": "1" } },
+        "runId": "run-header-policy"
+    });
+    let (out_json, _) = runtime.run_json(&input.to_string()).unwrap();
+    let out_val: Value = serde_json::from_str(&out_json).unwrap();
+    assert_eq!(out_val["events"][1]["type"], "header_missing");
+    assert_eq!(out_val["events"][1]["data"]["header"], "accept");
+}
+
+#[test]
+fn test_execution_limits_surface_as_budget_exceeded() {
+    let contract =
+        ServiceContract::from_json(r#"{ "execution": { "maxBodyBytes": 10 } }"#).unwrap();
+    let runtime = Runtime::builder().contract(contract).build();
+    let input = json!({ "request": { "url": "uma-fixture://sample-post" }, "runId": "run-budget" });
+    let (out_json, _) = runtime.run_json(&input.to_string()).unwrap();
+    let out_val: Value = serde_json::from_str(&out_json).unwrap();
+    assert_eq!(
+        out_val["events"][3]["data"],
+        json!({
+            "error": "maxBodyBytes of 10 exceeded",
+            "code": "network.budget_exceeded",
+            "params": { "limit": "maxBodyBytes", "max": 10 },
+        })
+    );
+
+    // A local server that redirects forever, or never answers `/slow`.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut buf = [0u8; 1024];
+            let read = std::io::Read::read(&mut stream, &mut buf).unwrap_or(0);
+            if buf[..read].starts_with(b"GET /slow") {
+                std::thread::spawn(move || {
+                    std::thread::sleep(std::time::Duration::from_secs(2));
+                    drop(stream);
+                });
+            } else {
+                let response =
+                    "HTTP/1.1 302 Found\r\nLocation: /again\r\nContent-Length: 0\r\n\r\n";
+                let _ = std::io::Write::write_all(&mut stream, response.as_bytes());
+            }
+        }
+    });
+    let adapter = adapter_manager::HostFetchAdapter {
+        limits: ExecutionLimits {
+            max_redirects: Some(2),
+            timeout_millis: Some(200),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let budget = |url: String| {
+        let request = service::model::Request {
+            url,
+            ..Default::default()
+        };
+        let err = adapter.fetch(&request).expect_err("fetch should fail");
+        err.downcast_ref::<service::api::BudgetExceeded>()
+            .cloned()
+            .unwrap_or_else(|| panic!("not a budget error: {err:#}"))
+    };
+    assert_eq!(budget(format!("{base}/loop")).limit, "maxRedirects");
+    assert_eq!(
+        budget(format!("{base}/slow")),
+        service::api::BudgetExceeded {
+            limit: "timeoutMillis",
+            max: 200
+        }
+    );
+}
+
+#[test]
+fn test_host_fetch_decompresses_bodies() {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    let post = json!({ "id": 5, "userId": 1, "title": "t", "body": "b".repeat(2000) }).to_string();
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(post.as_bytes()).unwrap();
+    let gzip = gzip.finish().unwrap();
+    let compressed = gzip.len();
+
+    // A local server answering every request with the gzipped post.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/posts/5", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut buf = [0u8; 1024];
+            let _ = std::io::Read::read(&mut stream, &mut buf);
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                gzip.len()
+            );
+            let _ = stream.write_all(head.as_bytes());
+            let _ = stream.write_all(&gzip);
+        }
+    });
+    let run = |limits: ExecutionLimits| {
+        let runtime = Runtime::builder()
+            .adapter(Box::new(adapter_manager::HostFetchAdapter {
+                limits,
+                ..Default::default()
+            }))
+            .build();
+        let input = json!({ "request": { "url": url }, "runId": "run-gzip" });
+        let (out_json, _) = runtime.run_json(&input.to_string()).unwrap();
+        serde_json::from_str::<Value>(&out_json).unwrap()
+    };
+
+    let out = run(ExecutionLimits::default());
+    assert_eq!(out["events"][3]["type"], "decompressed");
+    assert_eq!(
+        out["events"][3]["data"],
+        json!({ "encoding": "gzip", "encodedBytes": compressed, "decodedBytes": post.len() })
+    );
+    assert_eq!(out["normalizedPost"]["body"].as_str().unwrap().len(), 2000);
+
+    // The limit holds for the expanded body too.
+    let out = run(ExecutionLimits {
+        max_body_bytes: Some(1000),
+        ..Default::default()
+    });
+    assert_eq!(out["events"][3]["data"]["code"], "network.budget_exceeded");
+}
+
+#[test]
+fn test_contract_is_checked_against_its_schema() {
+    let Err(ContractError::Schema(diagnostics)) = ServiceContract::from_json(
+        r#"{ "inputMode": "lenient",
+             "adapter": { "retries": -1, "allowedhosts": ["example.com"] } }"#,
+    ) else {
+        panic!("contract should not match its schema");
+    };
+    let found: Vec<(&str, Option<Value>)> = diagnostics
+        .iter()
+        .map(|d| (d.pointer.as_str(), d.found.clone()))
+        .collect();
+    assert_eq!(found.len(), 3, "{diagnostics:?}");
+    assert!(found.contains(&("/inputMode", Some(json!("lenient")))));
+    assert!(found.contains(&("/adapter/retries", Some(json!(-1)))));
+    assert!(found.contains(&("/adapter/allowedhosts", Some(json!(["example.com"])))));
+    let retries = diagnostics
+        .iter()
+        .find(|d| d.pointer == "/adapter/retries")
+        .unwrap();
+    assert_eq!(
+        retries.to_string(),
+        "/adapter/retries: expected minimum 0, found -1"
+    );
+    let typo = diagnostics
+        .iter()
+        .find(|d| d.pointer == "/adapter/allowedhosts")
+        .unwrap();
+    assert!(typo.expected.contains("allowedHosts"), "{typo}");
+
+    let message = ServiceContract::from_json(r#"{ "sla": { "maxTicks": "8" } }"#)
+        .unwrap_err()
+        .to_string();
+    assert_eq!(
+        message,
+        "service contract does not match its schema:\n  /sla/maxTicks: expected type \"integer\", found \"8\""
+    );
+    assert!(matches!(
+        ServiceContract::from_json("{ not json"),
+        Err(ContractError::Parse(_))
+    ));
+}
+
 #[test]
 fn test_parse_error_marks_run_failed() {
+    // Holds the env lock so a concurrent UMA_PRIVACY_MODE test cannot drop
+    // the `error` message asserted below.
+    let _guard = env_lock();
     struct InvalidJsonAdapter;
 
     impl NetworkAdapter for InvalidJsonAdapter {
-        fn fetch(
-            &self,
-            _url: &str,
-            _headers: &HashMap<String, String>,
-        ) -> anyhow::Result<NetworkResponse> {
+        fn fetch(&self, _request: &service::model::Request) -> anyhow::Result<NetworkResponse> {
             Ok(NetworkResponse {
                 status: 200,
                 headers: HashMap::new(),
-                body: "not-json".to_string(),
+                body: "not-json".into(),
+                decoded: None,
             })
         }
     }
@@ -254,7 +1105,8 @@ fn test_fixture_url_is_resolved_without_network() {
     });
     let input_str = serde_json::to_string(&input).unwrap();
 
-    let (out_json, meta_json) = run_json(&input_str, None).expect("fixture-backed run should succeed");
+    let (out_json, meta_json) =
+        run_json(&input_str, None).expect("fixture-backed run should succeed");
 
     let out_val: Value = serde_json::from_str(&out_json).unwrap();
     assert_eq!(out_val["normalizedPost"]["id"], 1);
@@ -270,8 +1122,2645 @@ fn test_fixture_url_is_resolved_without_network() {
 }
 
 #[test]
-fn test_fixture_file_exists_for_validated_lab() {
-    let fixture_path =
-        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../tests/fixtures/sample_post.json");
-    assert!(fixture_path.is_file(), "missing fixture {}", fixture_path.display());
+fn test_replay_adapter_serves_recordings_only() {
+    let _guard = env_lock();
+    std::env::remove_var("UMA_ENABLE_RETRY");
+    std::env::remove_var("UMA_ENABLE_CACHE");
+
+    let run = |url: &str| {
+        let input = json!({
+            "request": { "url": url, "headers": { "accept": "application/json" } },
+            "runId": "run-replay"
+        });
+        let adapter = ReplayAdapter::bundled().record(
+            "uma-fixture://other",
+            r#"{"id":7,"userId":2,"title":"t","body":"b"}"#,
+        );
+        let (out_json, meta_json) = run_json(&input.to_string(), Some(Box::new(adapter))).unwrap();
+        let out: Value = serde_json::from_str(&out_json).unwrap();
+        let meta: Value = serde_json::from_str(&meta_json).unwrap();
+        let types: Vec<String> = out["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["type"].as_str().unwrap().to_string())
+            .collect();
+        (types, meta["state"].as_str().unwrap().to_string())
+    };
+
+    let (types, state) = run("uma-fixture://sample-post");
+    assert_eq!(
+        types,
+        [
+            "start",
+            "fetch_request",
+            "fetch_response",
+            "normalized",
+            "end"
+        ]
+    );
+    assert_eq!(state, "terminated");
+    assert_eq!(run("uma-fixture://other").1, "terminated");
+    // An unrecorded fixture fails in the fetch; another scheme never gets there.
+    let (types, state) = run("uma-fixture://missing");
+    assert!(types.contains(&"fetch_request".to_string()));
+    assert_eq!(state, "failed");
+    let (types, state) = run("https://example.com/posts/1");
+    assert_eq!(types, ["start", "error", "end"]);
+    assert_eq!(state, "failed");
+}
+
+#[test]
+fn test_mock_adapter_routes_sequences_and_checks_headers() {
+    let post = r#"{"id":1,"userId":2,"title":"t","body":"b"}"#;
+    let mock = MockAdapter::new()
+        .fail("GET https://example.com/posts/*", "connection reset")
+        .route(
+            "GET https://example.com/posts/*",
+            MockAdapter::json(503, ""),
+        )
+        .route(
+            "GET https://example.com/posts/*",
+            MockAdapter::json(200, post),
+        )
+        .route(
+            "POST https://example.com/posts",
+            MockAdapter::json(201, post),
+        )
+        .expect_header("Authorization", "Bearer t0k3n");
+    let runtime = Runtime::builder()
+        .adapter(Box::new(mock.clone()))
+        .retry(RetryPolicy::up_to(3))
+        .delay(Rc::new(NoDelay))
+        .build();
+    let run = |request: Value| {
+        let input = json!({ "request": request, "runId": "run-mock" });
+        let (out_json, _) = runtime.run_json(&input.to_string()).unwrap();
+        serde_json::from_str::<Value>(&out_json).unwrap()
+    };
+    let auth = json!({ "authorization": "Bearer t0k3n" });
+
+    // A reset and a 503 before the post, then the last reply repeats.
+    let out = run(json!({ "url": "https://example.com/posts/1", "headers": auth }));
+    assert_eq!(out["summary"]["retries"], 2);
+    assert_eq!(out["normalizedPost"]["id"], 1);
+    let out = run(json!({ "url": "https://example.com/posts/2", "headers": auth }));
+    assert_eq!(out["summary"]["retries"], 0);
+
+    let out = run(json!({
+        "url": "https://example.com/posts",
+        "method": "POST",
+        "headers": auth,
+        "body": "{}"
+    }));
+    assert_eq!(out["events"][2]["data"]["status"], 201);
+    let out = run(json!({ "url": "https://example.com/users/1", "headers": auth }));
+    let error = out["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|event| event["type"] == "error")
+        .unwrap();
+    assert_eq!(
+        error["data"]["error"],
+        "no mock route for GET https://example.com/users/1"
+    );
+
+    assert_eq!(mock.calls("https://example.com/posts/*"), 4);
+    assert_eq!(mock.calls("POST https://example.com/*"), 1);
+    // The unrouted GET was retried too.
+    assert_eq!(mock.requests().len(), 9);
+
+    let missing = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        run(json!({ "url": "https://example.com/posts/1" }))
+    }));
+    assert!(missing.is_err());
+}
+
+#[test]
+fn test_recorded_cassette_replays_the_same_run() {
+    // Answers 503 to the first request, then the post.
+    struct Flaky(AtomicUsize);
+
+    impl NetworkAdapter for Flaky {
+        fn fetch(&self, _req: &service::model::Request) -> anyhow::Result<NetworkResponse> {
+            if self.0.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Ok(NetworkResponse {
+                    status: 503,
+                    headers: HashMap::new(),
+                    body: Vec::new(),
+                    decoded: None,
+                });
+            }
+            Ok(NetworkResponse {
+                status: 200,
+                headers: HashMap::from([("etag".to_string(), "\"v1\"".to_string())]),
+                body: r#"{"id":1,"userId":2,"title":"t","body":"b"}"#.into(),
+                decoded: None,
+            })
+        }
+    }
+
+    let dir = scratch_dir("cassette");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("posts.json");
+    let input = json!({
+        "request": { "url": "https://example.com/posts/1", "headers": { "authorization": "secret" } },
+        "runId": "run-vcr"
+    })
+    .to_string();
+    let run = |adapter: Box<dyn NetworkAdapter>| {
+        let runtime = Runtime::builder()
+            .adapter(adapter)
+            .retry(RetryPolicy::up_to(2))
+            .delay(Rc::new(NoDelay))
+            .build();
+        runtime.run_json(&input).unwrap()
+    };
+
+    let recorder = RecordingAdapter::new(Box::new(Flaky(AtomicUsize::new(0))), &path);
+    let recorded = run(Box::new(recorder));
+    let cassette = Cassette::load(&path).unwrap();
+    let statuses: Vec<u16> = cassette
+        .interactions
+        .iter()
+        .map(|interaction| interaction.response.status)
+        .collect();
+    assert_eq!(statuses, [503, 200]);
+    assert_eq!(cassette.interactions[0].method, "GET");
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("secret"));
+
+    // Replaying serves the 503 and then the post, so the retry happens again.
+    let replayed = run(Box::new(ReplayAdapter::load(&path).unwrap()));
+    assert_eq!(replayed, recorded);
+    let out: Value = serde_json::from_str(&replayed.0).unwrap();
+    assert_eq!(out["summary"]["retries"], 1);
+    assert_eq!(out["normalizedPost"]["id"], 1);
+
+    // Only what was recorded can be replayed.
+    let capabilities = ReplayAdapter::from_cassette(&cassette).capabilities();
+    assert_eq!(capabilities.methods, ["GET"]);
+    assert_eq!(capabilities.schemes, ["https"]);
+
+    // Appending keeps the earlier interactions.
+    let recorder = RecordingAdapter::append(Box::new(Flaky(AtomicUsize::new(1))), &path).unwrap();
+    run(Box::new(recorder));
+    assert_eq!(Cassette::load(&path).unwrap().interactions.len(), 3);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_binary_bodies_pass_through_intact() {
+    // Normalizes a body into a post whose id is its length in bytes.
+    struct ByteCount;
+    impl Normalizer for ByteCount {
+        fn kind(&self) -> RecordKind {
+            RecordKind::Post
+        }
+        fn normalize(&self, body: &[u8]) -> Result<Normalized, NormalizeError> {
+            let id = body.len() as u64;
+            Ok(Normalized {
+                posts: vec![service::model::Post {
+                    id,
+                    user_id: 0,
+                    title: format!("{:02x?}", &body[..4]),
+                    body: String::new(),
+                }],
+                details: json!({ "id": id }),
+                ..Default::default()
+            })
+        }
+    }
+
+    let png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0xff, 0xfe];
+    let image = NetworkResponse {
+        status: 200,
+        headers: HashMap::from([("content-type".to_string(), "image/png".to_string())]),
+        body: png.clone(),
+        decoded: None,
+    };
+    assert!(image.text().is_err());
+    let mock = MockAdapter::new().route("https://example.com/logo.png", image);
+    let dir = scratch_dir("binary");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("logo.json");
+    let recorder = RecordingAdapter::new(Box::new(mock), &path);
+    let request = service::model::Request {
+        url: "https://example.com/logo.png".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(recorder.fetch(&request).unwrap().body, png);
+
+    // The cassette keeps the bytes as base64 and replays them as they were.
+    let cassette = std::fs::read_to_string(&path).unwrap();
+    assert!(
+        cassette.contains(r#""bodyBase64": "iVBORw0KGgr//g==""#),
+        "{cassette}"
+    );
+    let replay = ReplayAdapter::load(&path).unwrap();
+    assert_eq!(replay.fetch(&request).unwrap().body, png);
+
+    // A JSON normalizer reports a body that is not text as a parse error.
+    let run = |runtime: Runtime| {
+        let input = json!({ "request": { "url": "https://example.com/logo.png" }, "runId": "r" });
+        let (out, _) = runtime.run_json(&input.to_string()).unwrap();
+        serde_json::from_str::<Value>(&out).unwrap()
+    };
+    let replay = || Box::new(ReplayAdapter::load(&path).unwrap());
+    let out = run(Runtime::builder().adapter(replay()).build());
+    assert_eq!(out["events"][3]["data"]["code"], "response.parse_error");
+    assert!(out["events"][3]["data"]["params"]["detail"]
+        .as_str()
+        .unwrap()
+        .starts_with("body is not UTF-8"));
+
+    // Other normalizers get the bytes untouched.
+    let out = run(Runtime::builder()
+        .adapter(replay())
+        .register_normalizer("bytes", ByteCount)
+        .normalize_content_type("image/png", "bytes")
+        .build());
+    assert_eq!(out["normalizedPosts"][0]["id"], 10);
+    assert_eq!(out["normalizedPosts"][0]["title"], "[89, 50, 4e, 47]");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_fixture_file_exists_for_validated_lab() {
+    let fixture_path =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../tests/fixtures/sample_post.json");
+    assert!(
+        fixture_path.is_file(),
+        "missing fixture {}",
+        fixture_path.display()
+    );
+}
+
+#[test]
+fn test_bundled_policy_declares_per_host_cap() {
+    let policy = RuntimePolicy::bundled();
+    assert_eq!(policy.concurrency.max_per_host, Some(2));
+
+    let unbounded = RuntimePolicy::from_json(r#"{"name":"p"}"#).unwrap();
+    assert_eq!(unbounded.concurrency.max_per_host, None);
+    assert!(RuntimePolicy::from_json("not json").is_err());
+}
+
+#[test]
+fn test_builder_concurrency_replaces_policy_caps() {
+    let input = json!({
+        "requests": [
+            { "url": "https://example.com/posts/1" },
+            { "url": "https://example.com/posts/2" },
+            { "url": "https://example.com/posts/3" }
+        ],
+        "runId": "run-caps"
+    })
+    .to_string();
+    let queued = |concurrency: ConcurrencyPolicy| {
+        let (out, _) = Runtime::builder()
+            .adapter(Box::new(DummyAdapter))
+            .concurrency(concurrency)
+            .build()
+            .run_json(&input)
+            .unwrap();
+        let out: Value = serde_json::from_str(&out).unwrap();
+        out["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|event| event["type"] == "queued")
+            .count()
+    };
+    // The bundled policy allows two requests per host at a time.
+    assert_eq!(queued(RuntimePolicy::bundled().concurrency), 1);
+    let one_at_a_time = ConcurrencyPolicy {
+        max_per_host: Some(1),
+        max_workers: Some(2),
+    };
+    assert_eq!(queued(one_at_a_time), 2);
+    assert_eq!(queued(ConcurrencyPolicy::default()), 0);
+}
+
+#[test]
+fn test_thread_manager_plans_waves_per_host() {
+    let hosts: Vec<String> = [
+        "https://a.test/1",
+        "https://a.test/2",
+        "https://b.test/1",
+        "https://A.test/3",
+        "https://a.test/4",
+    ]
+    .iter()
+    .map(|url| host_of(url))
+    .collect();
+
+    let schedule = ThreadManager::with_max_per_host(Some(2)).plan(&hosts);
+    assert_eq!(schedule.waves, vec![vec![0, 1, 2], vec![3, 4]]);
+    assert_eq!(
+        schedule.queued,
+        vec![
+            QueuedTask {
+                index: 3,
+                host: "a.test".to_string(),
+                wave: 1
+            },
+            QueuedTask {
+                index: 4,
+                host: "a.test".to_string(),
+                wave: 1
+            },
+        ]
+    );
+
+    let unbounded = ThreadManager::new().plan(&hosts);
+    assert_eq!(unbounded.waves, vec![vec![0, 1, 2, 3, 4]]);
+    assert!(unbounded.queued.is_empty());
+
+    let zero_cap = ThreadManager::with_max_per_host(Some(0)).plan(&hosts[..2]);
+    assert_eq!(zero_cap.waves, vec![vec![0], vec![1]]);
+}
+
+#[test]
+fn test_host_of_strips_scheme_credentials_and_path() {
+    assert_eq!(
+        host_of("https://user:pw@Example.COM:8443/a?b#c"),
+        "example.com:8443"
+    );
+    assert_eq!(host_of("uma-fixture://sample-post"), "sample-post");
+    assert_eq!(host_of("no-scheme/path"), "no-scheme");
+}
+
+#[test]
+fn test_run_json_reports_summary() {
+    let input = json!({
+        "request": { "url": "https://example.com", "headers": {} },
+        "runId": "run-summary"
+    });
+    let input_str = serde_json::to_string(&input).unwrap();
+    let (out_json, _) =
+        run_json(&input_str, Some(Box::new(DummyAdapter))).expect("run_json should succeed");
+    let out_val: Value = serde_json::from_str(&out_json).unwrap();
+    assert_eq!(
+        out_val["summary"],
+        json!({
+            "totalRequests": 1,
+            "successes": 1,
+            "failuresByCode": {},
+            "cacheHits": 0,
+            "retries": 0,
+            "durationTicks": 5,
+            "deduplicated": 0,
+            "sla": { "status": "within", "maxTicks": 8, "maxWallMillis": 2000, "ticks": 5 },
+        })
+    );
+}
+
+#[test]
+fn test_strict_mode_rejects_unexpected_fields() {
+    let run = |input: Value| {
+        let (out_json, meta_json) =
+            run_json(&input.to_string(), Some(Box::new(DummyAdapter))).unwrap();
+        let out: Value = serde_json::from_str(&out_json).unwrap();
+        let meta: Value = serde_json::from_str(&meta_json).unwrap();
+        (out, meta["state"].clone())
+    };
+    let sloppy = json!({
+        "request": { "url": "https://example.com", "headers": { "accept": "x" }, "retries": 3 },
+        "runId": "run-strict",
+        "debug": true
+    });
+
+    // Permissive, the default, ignores the extra fields as before.
+    let (out, state) = run(sloppy.clone());
+    assert_eq!(state, "terminated");
+    assert!(out["normalizedPost"].is_object());
+
+    let mut strict = sloppy;
+    strict["inputMode"] = json!("strict");
+    let (out, state) = run(strict);
+    assert_eq!(state, "failed");
+    assert_eq!(out["summary"]["totalRequests"], 0);
+    let error = &out["events"][1];
+    assert_eq!(error["data"]["code"], "input.validation_error");
+    assert_eq!(
+        error["data"]["params"]["fields"],
+        json!(["debug", "request.retries"])
+    );
+
+    let (_, state) = run(json!({
+        "request": { "url": "https://example.com", "headers": { "x-anything": "ok" } },
+        "runId": "run-strict",
+        "inputMode": "strict"
+    }));
+    // Header names are free-form in the schema; the allowlist still applies.
+    assert_eq!(state, "failed");
+    let (out, state) = run(json!({
+        "request": { "url": "https://example.com", "method": "GET" },
+        "runId": "run-strict",
+        "inputMode": "strict"
+    }));
+    assert_eq!(state, "terminated", "{out}");
+}
+
+#[test]
+fn test_strict_contract_cannot_be_relaxed_by_input() {
+    let contract = ServiceContract::from_json(
+        r#"{"inputMode": "strict", "input": {
+            "type": "object",
+            "properties": { "runId": { "type": "string" } },
+            "additionalProperties": false
+        }}"#,
+    )
+    .unwrap();
+    assert!(contract.strict(service::model::InputMode::Permissive));
+    assert!(!ServiceContract::bundled().strict(service::model::InputMode::Permissive));
+    assert_eq!(
+        contract.unexpected_fields(&json!({ "runId": "r", "extra": { "deep": 1 } })),
+        ["extra"]
+    );
+    assert!(ServiceContract::default()
+        .unexpected_fields(&json!({ "anything": 1 }))
+        .is_empty());
+}
+
+#[test]
+fn test_sla_uses_the_clock_when_one_is_present() {
+    // Each reading advances 2.5 s, so the run spans more than the
+    // contract's 2000 ms even though it stays within its tick budget.
+    struct SteppingClock(std::cell::Cell<u64>);
+
+    impl Clock for SteppingClock {
+        fn now_millis(&self) -> u64 {
+            let now = self.0.get();
+            self.0.set(now + 2500);
+            now
+        }
+    }
+
+    let input = json!({
+        "request": { "url": "https://example.com", "headers": {} },
+        "runId": "run-sla"
+    });
+    let clock = SteppingClock(std::cell::Cell::new(10_000));
+    let (out_json, lifecycle_json) = run_json_with_clock(
+        &input.to_string(),
+        Some(Box::new(DummyAdapter)),
+        Some(&clock),
+    )
+    .unwrap();
+    let out: Value = serde_json::from_str(&out_json).unwrap();
+    assert_eq!(
+        out["summary"]["sla"],
+        json!({
+            "status": "violated",
+            "maxTicks": 8,
+            "maxWallMillis": 2000,
+            "ticks": 5,
+            "wallMillis": 2500,
+        })
+    );
+    let lifecycle: Value = serde_json::from_str(&lifecycle_json).unwrap();
+    assert_eq!(lifecycle["sla"], out["summary"]["sla"]);
+    assert_eq!(ServiceContract::bundled().sla.unwrap().max_ticks, Some(8));
+}
+
+#[test]
+fn test_event_decorator_tags_every_event() {
+    let input = json!({
+        "request": { "url": "https://example.com", "headers": {} },
+        "runId": "run-host"
+    })
+    .to_string();
+    let fleet = |_: &service::model::Event| {
+        json!({ "deploymentId": "d-42", "region": "eu-west-1" })
+            .as_object()
+            .unwrap()
+            .clone()
+    };
+    let (out_json, lifecycle_json) =
+        run_json_with_decorator(&input, Some(Box::new(DummyAdapter)), None, Some(&fleet)).unwrap();
+    let out: Value = serde_json::from_str(&out_json).unwrap();
+    let lifecycle: Value = serde_json::from_str(&lifecycle_json).unwrap();
+    for events in [&out["events"], &lifecycle["events"]] {
+        let events = events.as_array().unwrap();
+        assert_eq!(events.len(), 5);
+        assert!(events
+            .iter()
+            .all(|e| e["host"] == json!({ "deploymentId": "d-42", "region": "eu-west-1" })));
+    }
+    // Events keep their data, and the counters ignore the attributes.
+    assert_eq!(out["events"][0]["data"], json!({ "runId": "run-host" }));
+    assert_eq!(out["summary"]["totalRequests"], 1);
+
+    // Only the events the decorator tags carry `host`.
+    let end_only = |event: &service::model::Event| {
+        let mut attributes = serde_json::Map::new();
+        if event.type_ == "end" {
+            attributes.insert("region".to_string(), json!("eu-west-1"));
+        }
+        attributes
+    };
+    let (out_json, _) =
+        run_json_with_decorator(&input, Some(Box::new(DummyAdapter)), None, Some(&end_only))
+            .unwrap();
+    let out: Value = serde_json::from_str(&out_json).unwrap();
+    assert!(out["events"][0].get("host").is_none());
+    assert_eq!(out["events"][4]["host"]["region"], "eu-west-1");
+
+    let oversized = |_: &service::model::Event| {
+        let mut attributes = serde_json::Map::new();
+        attributes.insert("blob".to_string(), json!("x".repeat(HOST_ATTRIBUTES_LIMIT)));
+        attributes
+    };
+    let err = run_json_with_decorator(&input, Some(Box::new(DummyAdapter)), None, Some(&oversized))
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("`start` event 0"), "{err:#}");
+    assert!(format!("{err:#}").contains("1024-byte limit"), "{err:#}");
+
+    let nested = |_: &service::model::Event| {
+        json!({ "labels": { "team": "growth" } })
+            .as_object()
+            .unwrap()
+            .clone()
+    };
+    let err = run_json_with_decorator(&input, Some(Box::new(DummyAdapter)), None, Some(&nested))
+        .unwrap_err();
+    assert!(
+        format!("{err:#}").contains("`labels` must be a string"),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn test_wrappers_count_retries_and_cache_hits() {
+    struct FlakyAdapter {
+        failures_left: std::cell::Cell<u32>,
+    }
+
+    impl NetworkAdapter for FlakyAdapter {
+        fn fetch(&self, request: &service::model::Request) -> anyhow::Result<NetworkResponse> {
+            if self.failures_left.get() > 0 {
+                self.failures_left.set(self.failures_left.get() - 1);
+                return Err(anyhow::anyhow!("flaky"));
+            }
+            DummyAdapter.fetch(request)
+        }
+    }
+
+    let stats = Rc::new(adapter_manager::AdapterStats::default());
+    let retry = retry_adapter::RetryAdapter::new(
+        Box::new(BlockingAdapter(Box::new(FlakyAdapter {
+            failures_left: std::cell::Cell::new(2),
+        }))),
+        RetryPolicy::up_to(3),
+        Rc::new(NoDelay),
+        Rc::clone(&stats),
+    );
+    let cache = cache_adapter::CacheAdapter::new(
+        Box::new(retry),
+        CacheLimits::default(),
+        Rc::default(),
+        None,
+        Rc::clone(&stats),
+    );
+    let request = service::model::Request {
+        url: "https://example.com".to_string(),
+        ..Default::default()
+    };
+    let fetch = || executor::block_on(cache.fetch(&request)).unwrap();
+    assert_eq!(fetch().status, 200);
+    assert_eq!(fetch().status, 200);
+    assert_eq!(stats.retries.get(), 2);
+    assert_eq!(stats.cache_hits.get(), 1);
+}
+
+#[test]
+fn test_cache_limits_expire_and_evict_entries() {
+    let fetch_calls = Arc::new(AtomicUsize::new(0));
+    let runtime = Runtime::builder()
+        .adapter(Box::new(CountingAdapter {
+            fetch_calls: Arc::clone(&fetch_calls),
+        }))
+        .cache(CachePolicy::InMemory)
+        .cache_limits(CacheLimits {
+            ttl_millis: Some(3),
+            max_entries: Some(2),
+        })
+        .build();
+    let fetch = |url: &str, headers: Value| {
+        let before = fetch_calls.load(Ordering::SeqCst);
+        let input = json!({ "request": { "url": url, "headers": headers }, "runId": "run-cache" });
+        let (out_json, _) = runtime.run_json(&input.to_string()).unwrap();
+        let out: Value = serde_json::from_str(&out_json).unwrap();
+        assert_eq!(out["normalizedPost"]["id"], 1);
+        fetch_calls.load(Ordering::SeqCst) > before
+    };
+    let a = "https://example.com/posts/a";
+    let b = "https://example.com/posts/b";
+    let c = "https://example.com/posts/c";
+
+    // Runs of one runtime share the cache; without a clock every lookup is
+    // one tick, so an entry is fresh for the two lookups after its store.
+    assert!(fetch(a, json!({})));
+    assert!(!fetch(a, json!({})));
+    assert!(!fetch(a, json!({})));
+    assert!(fetch(a, json!({})));
+
+    // Other credentials or representations are other entries.
+    assert!(fetch(a, json!({ "authorization": "Bearer one" })));
+    assert!(!fetch(a, json!({ "Authorization": "Bearer one" })));
+    assert!(fetch(a, json!({ "authorization": "Bearer two" })));
+    assert_eq!(runtime.responses().len(), 2);
+
+    // At capacity, the least recently used entry makes room.
+    assert!(fetch(b, json!({})));
+    assert!(fetch(c, json!({})));
+    assert!(!fetch(b, json!({})));
+    assert!(fetch(c, json!({ "accept": "application/json" })));
+    assert_eq!(runtime.responses().len(), 2);
+    assert!(!fetch(c, json!({ "accept": "application/json" })));
+    assert!(fetch(c, json!({})));
+
+    let request = |headers: &[(&str, &str)]| service::model::Request {
+        url: a.to_string(),
+        headers: headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+        ..Default::default()
+    };
+    let key = cache_key(&request(&[
+        ("Authorization", "Bearer secret"),
+        ("accept", "*/*"),
+    ]));
+    assert!(key.starts_with("https://example.com/posts/a\naccept: */*\nauthorization: #"));
+    assert!(!key.contains("secret"));
+
+    // Each lookup is in the event log, so a run without a network call says
+    // why.
+    let types = |url: &str| -> Vec<Value> {
+        let input = json!({ "request": { "url": url }, "runId": "run-cache" });
+        let (out_json, _) = runtime.run_json(&input.to_string()).unwrap();
+        let out: Value = serde_json::from_str(&out_json).unwrap();
+        out["events"].as_array().unwrap()[1..3].to_vec()
+    };
+    let d = "https://example.com/posts/d";
+    assert_eq!(
+        types(d),
+        [
+            json!({ "t": "1", "type": "fetch_request", "data": { "url": d, "method": "GET" } }),
+            json!({ "t": "2", "type": "cache_miss", "data": { "url": d } }),
+        ]
+    );
+    assert_eq!(
+        types(d)[1],
+        json!({ "t": "2", "type": "cache_hit", "data": { "url": d } })
+    );
+}
+
+#[test]
+fn test_expired_entries_are_revalidated_with_their_etag() {
+    // Serves the post under `etag`, answering 304 when the client has it.
+    struct Versioned {
+        etag: Arc<Mutex<&'static str>>,
+        seen: Arc<Mutex<Vec<Option<String>>>>,
+    }
+
+    impl NetworkAdapter for Versioned {
+        fn fetch(&self, request: &service::model::Request) -> anyhow::Result<NetworkResponse> {
+            let condition = request.headers.get("If-None-Match").cloned();
+            self.seen.lock().unwrap().push(condition.clone());
+            let etag = *self.etag.lock().unwrap();
+            if condition.as_deref() == Some(etag) {
+                return Ok(NetworkResponse {
+                    status: 304,
+                    headers: HashMap::new(),
+                    body: Vec::new(),
+                    decoded: None,
+                });
+            }
+            Ok(NetworkResponse {
+                status: 200,
+                headers: HashMap::from([("ETag".to_string(), etag.to_string())]),
+                body: r#"{"id":1,"userId":2,"title":"t","body":"b"}"#.into(),
+                decoded: None,
+            })
+        }
+    }
+
+    let etag = Arc::new(Mutex::new("\"v1\""));
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let adapter = Versioned {
+        etag: Arc::clone(&etag),
+        seen: Arc::clone(&seen),
+    };
+    let runtime = Runtime::builder()
+        .adapter(Box::new(adapter))
+        .cache(CachePolicy::InMemory)
+        .cache_limits(CacheLimits {
+            ttl_millis: Some(1),
+            max_entries: None,
+        })
+        .retry(RetryPolicy::up_to(2))
+        .delay(Rc::new(NoDelay))
+        .build();
+    let run = || {
+        let input =
+            json!({ "request": { "url": "https://example.com/posts/1" }, "runId": "run-etag" });
+        let (out_json, _) = runtime.run_json(&input.to_string()).unwrap();
+        let out: Value = serde_json::from_str(&out_json).unwrap();
+        let types: Vec<String> = out["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["type"].as_str().unwrap().to_string())
+            .collect();
+        (out, types)
+    };
+
+    let (_, types) = run();
+    assert_eq!(types[2], "cache_miss");
+
+    // Expired, so the next lookup asks whether the entry is still current;
+    // the 304 is neither retried nor passed on.
+    let (out, types) = run();
+    assert_eq!(
+        types,
+        [
+            "start",
+            "fetch_request",
+            "cache_revalidated",
+            "fetch_response",
+            "normalized",
+            "end"
+        ]
+    );
+    assert_eq!(out["events"][3]["data"]["status"], 200);
+    assert_eq!(out["summary"]["cacheHits"], 1);
+
+    // A changed resource replaces the entry and its validator.
+    *etag.lock().unwrap() = "\"v2\"";
+    let (_, types) = run();
+    assert_eq!(types[2], "cache_miss");
+    let (_, types) = run();
+    assert_eq!(types[2], "cache_revalidated");
+    let condition = |etag: &str| Some(etag.to_string());
+    assert_eq!(
+        *seen.lock().unwrap(),
+        [
+            None,
+            condition("\"v1\""),
+            condition("\"v1\""),
+            condition("\"v2\"")
+        ]
+    );
+    assert!(!RetryPolicy::up_to(2).retries_status(304));
+}
+
+#[test]
+fn test_middleware_stack_wraps_each_request_in_order() {
+    // Records the authorization header of each request it serves.
+    struct Echo(Arc<Mutex<Vec<Option<String>>>>);
+
+    impl NetworkAdapter for Echo {
+        fn fetch(&self, request: &service::model::Request) -> anyhow::Result<NetworkResponse> {
+            let auth = request.headers.get("Authorization").cloned();
+            self.0.lock().unwrap().push(auth);
+            Ok(NetworkResponse {
+                status: 200,
+                headers: HashMap::new(),
+                body: r#"{"id":1,"userId":2,"title":"t","body":"b"}"#.into(),
+                decoded: None,
+            })
+        }
+    }
+
+    // Logs its hooks under `name`; `token` adds a bearer token and `answers`
+    // serves the post itself for URLs with that suffix.
+    struct Hook {
+        name: &'static str,
+        log: Rc<std::cell::RefCell<Vec<String>>>,
+        token: Option<&'static str>,
+        answers: Option<&'static str>,
+    }
+
+    impl Middleware for Hook {
+        fn before_fetch(
+            &self,
+            request: &mut service::model::Request,
+        ) -> anyhow::Result<Option<NetworkResponse>> {
+            self.log.borrow_mut().push(format!("before {}", self.name));
+            if let Some(token) = self.token {
+                *request = request.with_header("Authorization", &format!("Bearer {token}"));
+            }
+            if self
+                .answers
+                .is_some_and(|suffix| request.url.ends_with(suffix))
+            {
+                return Ok(Some(NetworkResponse {
+                    status: 200,
+                    headers: HashMap::new(),
+                    body: r#"{"id":9,"userId":2,"title":"canned","body":"b"}"#.into(),
+                    decoded: None,
+                }));
+            }
+            Ok(None)
+        }
+
+        fn after_fetch(
+            &self,
+            _request: &service::model::Request,
+            response: &mut NetworkResponse,
+        ) -> anyhow::Result<()> {
+            self.log
+                .borrow_mut()
+                .push(format!("after {} {}", self.name, response.status));
+            Ok(())
+        }
+    }
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Rc::new(std::cell::RefCell::new(Vec::new()));
+    let hook = |name, token, answers| {
+        Rc::new(Hook {
+            name,
+            log: Rc::clone(&log),
+            token,
+            answers,
+        })
+    };
+    let runtime = Runtime::builder()
+        .adapter(Box::new(Echo(Arc::clone(&seen))))
+        .retry(RetryPolicy::up_to(0))
+        .cache(CachePolicy::Disabled)
+        .middleware(hook("auth", Some("t0k3n"), None))
+        .middleware(hook("stub", None, Some("/posts/9")))
+        .middleware(hook("metrics", None, None))
+        .build();
+    let run = |url: &str| {
+        let input = json!({ "request": { "url": url }, "runId": "run-middleware" });
+        let (out_json, meta_json) = runtime.run_json(&input.to_string()).unwrap();
+        let out: Value = serde_json::from_str(&out_json).unwrap();
+        let meta: Value = serde_json::from_str(&meta_json).unwrap();
+        (out, meta)
+    };
+
+    let (out, meta) = run("https://example.com/posts/1");
+    assert_eq!(out["normalizedPost"]["id"], 1);
+    assert_eq!(
+        meta["bindings"]["network.fetch"]["impl"],
+        "middleware-custom"
+    );
+    assert_eq!(*seen.lock().unwrap(), [Some("Bearer t0k3n".to_string())]);
+    assert_eq!(
+        log.take(),
+        [
+            "before auth",
+            "before stub",
+            "before metrics",
+            "after metrics 200",
+            "after stub 200",
+            "after auth 200"
+        ]
+    );
+
+    // The stub answers without reaching the adapter or the metrics hooks.
+    let (out, _) = run("https://example.com/posts/9");
+    assert_eq!(out["normalizedPost"]["title"], "canned");
+    assert_eq!(seen.lock().unwrap().len(), 1);
+    assert_eq!(
+        log.take(),
+        [
+            "before auth",
+            "before stub",
+            "after stub 200",
+            "after auth 200"
+        ]
+    );
+}
+
+#[test]
+fn test_retry_policy_emits_an_event_per_attempt() {
+    // Answers with `statuses` in order, then 200.
+    struct StatusSequence(std::cell::RefCell<Vec<u16>>);
+
+    impl NetworkAdapter for StatusSequence {
+        fn fetch(&self, request: &service::model::Request) -> anyhow::Result<NetworkResponse> {
+            let mut response = DummyAdapter.fetch(request)?;
+            let mut statuses = self.0.borrow_mut();
+            if !statuses.is_empty() {
+                response.status = statuses.remove(0);
+            }
+            Ok(response)
+        }
+
+        fn capabilities(&self) -> service::api::AdapterCapabilities {
+            service::api::AdapterCapabilities {
+                methods: vec!["GET".to_string(), "POST".to_string()],
+                ..Default::default()
+            }
+        }
+    }
+
+    let run = |statuses: Vec<u16>, policy: RetryPolicy, method: &str| {
+        let runtime = Runtime::builder()
+            .adapter(Box::new(StatusSequence(std::cell::RefCell::new(statuses))))
+            .retry(policy)
+            .delay(Rc::new(NoDelay))
+            .build();
+        let input = json!({
+            "request": { "url": "https://example.com/posts/1", "method": method },
+            "runId": "run-retry"
+        });
+        let (out_json, _) = runtime.run_json(&input.to_string()).unwrap();
+        serde_json::from_str::<Value>(&out_json).unwrap()
+    };
+    let types = |out: &Value| -> Vec<String> {
+        out["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["type"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    // 503 is retryable, 500 is not, so the second response is final.
+    let out = run(vec![503, 500], RetryPolicy::up_to(3).retry_on([503]), "GET");
+    assert_eq!(
+        types(&out),
+        [
+            "start",
+            "fetch_request",
+            "fetch_retry",
+            "fetch_response",
+            "normalized",
+            "end"
+        ]
+    );
+    assert_eq!(
+        out["events"][2]["data"],
+        json!({ "attempt": 2, "previousStatus": 503, "delayMillis": 100 })
+    );
+    assert_eq!(out["events"][3]["data"]["status"], 500);
+    assert_eq!(out["summary"]["retries"], 1);
+
+    // The idempotency guard is part of the policy.
+    let guarded = run(vec![500], RetryPolicy::up_to(1), "POST");
+    assert_eq!(guarded["events"][2]["data"]["status"], 500);
+    assert_eq!(guarded["summary"]["retries"], 0);
+    let unguarded = RetryPolicy {
+        idempotent_only: false,
+        ..RetryPolicy::up_to(1)
+    };
+    let out = run(vec![500], unguarded, "POST");
+    assert_eq!(out["events"][2]["type"], "fetch_retry");
+    assert_eq!(out["events"][3]["data"]["status"], 200);
+
+    let contract = ServiceContract::from_json(
+        r#"{ "adapter": { "retries": 2, "retryOn": [429, 503], "retryNonIdempotent": true } }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        contract.adapter.retry_policy(),
+        RetryPolicy {
+            max_attempts: 3,
+            retryable_statuses: vec![429, 503],
+            idempotent_only: false,
+            backoff: Some(Backoff::default()),
+        }
+    );
+}
+
+#[test]
+fn test_retry_backoff_waits_through_the_delay_provider() {
+    struct AlwaysUnavailable;
+
+    impl NetworkAdapter for AlwaysUnavailable {
+        fn fetch(&self, _req: &service::model::Request) -> anyhow::Result<NetworkResponse> {
+            Ok(NetworkResponse {
+                status: 503,
+                headers: Default::default(),
+                body: Vec::new(),
+                decoded: None,
+            })
+        }
+    }
+
+    let backoff = Backoff {
+        initial_millis: 100,
+        max_millis: 250,
+    };
+    assert_eq!(
+        (1..=5).map(|n| backoff.before(n)).collect::<Vec<_>>(),
+        [100, 100, 200, 250, 250]
+    );
+
+    let contract = ServiceContract::from_json(
+        r#"{ "adapter": { "retries": 3, "backoff": { "initialMillis": 100, "maxMillis": 250 } } }"#,
+    )
+    .unwrap();
+    let delay = Rc::new(LogicalDelay::default());
+    let runtime = Runtime::builder()
+        .contract(contract)
+        .adapter(Box::new(AlwaysUnavailable))
+        .delay(delay.clone())
+        .build();
+    let input =
+        json!({ "request": { "url": "https://example.com/posts/1" }, "runId": "run-backoff" });
+    let (out_json, meta_json) = runtime.run_json(&input.to_string()).unwrap();
+    let out: Value = serde_json::from_str(&out_json).unwrap();
+    let meta: Value = serde_json::from_str(&meta_json).unwrap();
+
+    // The provider is asked for the same delays the event log records.
+    assert_eq!(delay.delays(), [100, 200, 250]);
+    assert_eq!(delay.total_millis(), 550);
+    let logged: Vec<&Value> = out["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|e| e["type"] == "fetch_retry")
+        .map(|e| &e["data"]["delayMillis"])
+        .collect();
+    assert_eq!(logged, [&json!(100), &json!(200), &json!(250)]);
+    assert_eq!(
+        meta["adapterPolicy"]["backoff"],
+        json!({ "initialMillis": 100, "maxMillis": 250 })
+    );
+
+    // A runtime setting replaces the contract's backoff.
+    let delay = Rc::new(LogicalDelay::default());
+    let runtime = Runtime::builder()
+        .adapter(Box::new(AlwaysUnavailable))
+        .retry(RetryPolicy::up_to(2).backoff(Backoff::default()))
+        .delay(delay.clone())
+        .build();
+    runtime.run_json(&input.to_string()).unwrap();
+    assert!(delay.delays().is_empty());
+}
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("uma-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn test_lifecycle_store_round_trips_mixed_codecs() {
+    let dir = scratch_dir("lifecycle-store");
+    let plain = LifecycleStore::open(&dir, Compression::None).unwrap();
+    let first = plain.append("run-1", r#"{"state":"completed"}"#).unwrap();
+    let gzip = LifecycleStore::open(&dir, Compression::Gzip).unwrap();
+    let second = gzip.append("run-2", r#"{"state":"failed"}"#).unwrap();
+
+    assert_eq!(first.offset, 0);
+    assert_eq!(second.offset, first.length);
+    assert_eq!(second.compression, Compression::Gzip);
+    assert_eq!(gzip.index().unwrap(), vec![first, second]);
+
+    // Either handle reads both records regardless of its own codec.
+    assert_eq!(
+        plain.read("run-2").unwrap().as_deref(),
+        Some(r#"{"state":"failed"}"#)
+    );
+    assert_eq!(
+        gzip.read("run-1").unwrap().as_deref(),
+        Some(r#"{"state":"completed"}"#)
+    );
+    assert_eq!(gzip.read("missing").unwrap(), None);
+
+    // Re-appending a run shadows the earlier record.
+    gzip.append("run-1", r#"{"state":"retried"}"#).unwrap();
+    assert_eq!(
+        plain.read("run-1").unwrap().as_deref(),
+        Some(r#"{"state":"retried"}"#)
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_compression_parse() {
+    assert_eq!(Compression::parse("").unwrap(), Compression::None);
+    assert_eq!(Compression::parse("GZIP").unwrap(), Compression::Gzip);
+    assert!(Compression::parse("brotli").is_err());
+    assert_eq!(Compression::parse("zstd").is_ok(), cfg!(feature = "zstd"));
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_lifecycle_store_zstd() {
+    let dir = scratch_dir("lifecycle-store-zstd");
+    let store = LifecycleStore::open(&dir, Compression::Zstd).unwrap();
+    store.append("run-z", r#"{"state":"completed"}"#).unwrap();
+    assert_eq!(
+        store.read("run-z").unwrap().as_deref(),
+        Some(r#"{"state":"completed"}"#)
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_privacy_rules_redact_events() {
+    let policy = RuntimePolicy::bundled().privacy;
+    assert!(!policy.enabled);
+    let mut events = vec![
+        service::model::Event {
+            t: "0".to_string(),
+            wall_time: None,
+            trace: None,
+            type_: "fetch_request".to_string(),
+            data: json!({
+                "url": "https://api.test/users/42/posts/550e8400-e29b-41d4-a716-446655440000?token=abc&debug#top"
+            }),
+            host: None,
+        },
+        service::model::Event {
+            t: "1".to_string(),
+            wall_time: None,
+            trace: None,
+            type_: "error".to_string(),
+            data: json!({
+                "error": "network failure: secret",
+                "code": "network.failure",
+                "params": { "detail": "secret" }
+            }),
+            host: None,
+        },
+    ];
+    policy.redact(&mut events);
+    let url = events[0].data["url"].as_str().unwrap();
+    assert!(url.starts_with("https://api.test/users/h:"));
+    assert!(url.contains("/posts/h:"));
+    assert!(url.contains("?token=h:") && url.ends_with("&debug"));
+    assert!(!url.contains("42") && !url.contains("abc") && !url.contains("#top"));
+    assert!(events[1].data.get("error").is_none());
+    assert_eq!(events[1].data["code"], "network.failure");
+    assert!(events[1].data["params"]["detail"]
+        .as_str()
+        .unwrap()
+        .starts_with("h:"));
+
+    // Hashes are stable for a salt so redacted records can still be joined.
+    let mut again = vec![service::model::Event {
+        t: "0".to_string(),
+        wall_time: None,
+        trace: None,
+        type_: "fetch_request".to_string(),
+        data: json!({ "url": "https://api.test/users/42" }),
+        host: None,
+    }];
+    policy.redact(&mut again);
+    let user = url.split('/').nth(4).unwrap();
+    assert!(again[0].data["url"].as_str().unwrap().ends_with(user));
+
+    let policy = RuntimePolicy::from_json(
+        r#"{"privacy":{"rules":[{"field":"body","action":"truncate","maxChars":4}]}}"#,
+    )
+    .unwrap()
+    .privacy;
+    let mut excerpt = vec![service::model::Event {
+        t: "0".to_string(),
+        wall_time: None,
+        trace: None,
+        type_: "fetch_response".to_string(),
+        data: json!({ "body": "épée et bouclier", "status": 200 }),
+        host: None,
+    }];
+    policy.redact(&mut excerpt);
+    assert_eq!(excerpt[0].data, json!({ "body": "épée…", "status": 200 }));
+}
+
+#[test]
+fn test_privacy_mode_env_redacts_output_and_lifecycle() {
+    let _guard = env_lock();
+    std::env::set_var("UMA_PRIVACY_MODE", "1");
+    let input = json!({
+        "request": { "url": "https://example.com/posts/7?q=secret", "headers": {} },
+        "runId": "run-private"
+    });
+    let result = run_json(&input.to_string(), Some(Box::new(DummyAdapter)));
+    std::env::remove_var("UMA_PRIVACY_MODE");
+    let (out_json, lifecycle_json) = result.unwrap();
+    for json in [&out_json, &lifecycle_json] {
+        assert!(!json.contains("secret") && !json.contains("run-private"));
+        assert!(!json.contains("/posts/7"));
+    }
+    let out: Value = serde_json::from_str(&out_json).unwrap();
+    assert_eq!(out["summary"]["successes"], 1);
+    let lifecycle: Value = serde_json::from_str(&lifecycle_json).unwrap();
+    assert_eq!(lifecycle["redacted"], true);
+}
+
+#[test]
+fn test_privacy_salt_is_secret_and_mode_is_parsed() {
+    let _guard = env_lock();
+    let input = json!({
+        "request": { "url": "https://example.com/posts/7", "headers": {} },
+        "runId": "run-salted"
+    })
+    .to_string();
+    let run_id = |builder: RuntimeBuilder| {
+        let (out, _) = builder
+            .adapter(Box::new(DummyAdapter))
+            .build()
+            .run_json(&input)
+            .unwrap();
+        let out: Value = serde_json::from_str(&out).unwrap();
+        out["events"][0]["data"]["runId"].clone()
+    };
+
+    // `0` and other values that do not switch the mode on leave it off.
+    for mode in ["0", "false", "off", ""] {
+        std::env::set_var("UMA_PRIVACY_MODE", mode);
+        assert_eq!(
+            run_id(Runtime::builder().with_env()),
+            "run-salted",
+            "{mode}"
+        );
+    }
+
+    // The bundled policy carries no salt; one comes from the environment or
+    // the builder, and the same salt hashes a value the same way.
+    assert!(RuntimePolicy::bundled().privacy.salt.is_empty());
+    std::env::set_var("UMA_PRIVACY_MODE", "true");
+    std::env::set_var("UMA_PRIVACY_SALT", "host-secret");
+    let from_env = run_id(Runtime::builder().with_env());
+    std::env::remove_var("UMA_PRIVACY_SALT");
+    std::env::remove_var("UMA_PRIVACY_MODE");
+    assert!(from_env.as_str().unwrap().starts_with("h:"));
+    let privacy = PrivacyPolicy {
+        enabled: true,
+        salt: "host-secret".to_string(),
+        ..RuntimePolicy::bundled().privacy
+    };
+    let from_builder = run_id(Runtime::builder().privacy(privacy.clone()));
+    assert_eq!(from_builder, from_env);
+
+    // Without a salt each runtime picks a random one.
+    let unsalted = PrivacyPolicy {
+        salt: String::new(),
+        ..privacy
+    };
+    let first = run_id(Runtime::builder().privacy(unsalted.clone()));
+    let second = run_id(Runtime::builder().privacy(unsalted));
+    assert!(first.as_str().unwrap().starts_with("h:"));
+    assert_ne!(first, from_env);
+    assert_ne!(first, second);
+}
+
+#[test]
+fn test_async_runs_await_adapter_and_backoff() {
+    // Fails with 503 the first time, then answers after yielding to the
+    // executor.
+    struct SlowAdapter {
+        calls: std::cell::Cell<u32>,
+    }
+
+    impl AsyncNetworkAdapter for SlowAdapter {
+        fn fetch<'a>(&'a self, request: &'a service::model::Request) -> FetchFuture<'a> {
+            Box::pin(async move {
+                self.calls.set(self.calls.get() + 1);
+                if self.calls.get() == 1 {
+                    return Ok(NetworkResponse {
+                        status: 503,
+                        headers: HashMap::new(),
+                        body: Vec::new(),
+                        decoded: None,
+                    });
+                }
+                tokio::task::yield_now().await;
+                DummyAdapter.fetch(request)
+            })
+        }
+    }
+
+    let tokio = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let input = |url: &str| json!({ "request": { "url": url }, "runId": "run-async" }).to_string();
+    let parse = |(out_json, meta_json): (String, String)| {
+        let out: Value = serde_json::from_str(&out_json).unwrap();
+        let meta: Value = serde_json::from_str(&meta_json).unwrap();
+        (out, meta)
+    };
+
+    // The retry backoff waits on a timer instead of blocking the executor.
+    let runtime = Runtime::builder()
+        .async_adapter(Box::new(SlowAdapter {
+            calls: std::cell::Cell::new(0),
+        }))
+        .retry(RetryPolicy::up_to(1).backoff(Backoff {
+            initial_millis: 5,
+            max_millis: 5,
+        }))
+        .delay(Rc::new(SleepDelay))
+        .build();
+    let started = std::time::Instant::now();
+    let (out, meta) = parse(
+        tokio
+            .block_on(runtime.run_json_async(&input("https://example.com/posts/1")))
+            .unwrap(),
+    );
+    assert!(started.elapsed() >= std::time::Duration::from_millis(5));
+    assert_eq!(out["normalizedPost"]["id"], 1);
+    assert_eq!(out["summary"]["retries"], 1);
+    assert_eq!(meta["bindings"]["network.fetch"]["impl"], "retry-custom");
+
+    // The same runtime serves blocking runs too.
+    let (out, _) = parse(
+        runtime
+            .run_json(&input("https://example.com/posts/1"))
+            .unwrap(),
+    );
+    assert_eq!(out["normalizedPost"]["id"], 1);
+
+    // Async runs bind a name's async implementation, blocking runs its
+    // blocking one.
+    let canned = |title: &str| {
+        ReplayAdapter::new().record(
+            "uma-fixture://sample-post",
+            &json!({ "id": 1, "userId": 1, "title": title, "body": "b" }).to_string(),
+        )
+    };
+    let contract =
+        ServiceContract::from_json(r#"{ "bindings": { "network.fetch": "probe" } }"#).unwrap();
+    let runtime = Runtime::builder()
+        .contract(contract)
+        .register_adapter("probe", move |_| Box::new(canned("blocking")))
+        .register_async_adapter("probe", move |_| {
+            Box::new(BlockingAdapter(Box::new(canned("async"))))
+        })
+        .build();
+    let url = "uma-fixture://sample-post";
+    let (out, _) = parse(tokio.block_on(runtime.run_json_async(&input(url))).unwrap());
+    assert_eq!(out["normalizedPost"]["title"], "async");
+    let (out, _) = parse(runtime.run_json(&input(url)).unwrap());
+    assert_eq!(out["normalizedPost"]["title"], "blocking");
+
+    // The default binding fetches with reqwest's async client on async runs.
+    let (out, meta) = parse(tokio.block_on(run_json_async(&input(url), None)).unwrap());
+    assert_eq!(out["normalizedPost"]["id"], 1);
+    assert_eq!(meta["bindings"]["network.fetch"]["impl"], "host-fetch");
+}
+
+#[test]
+fn test_batch_runs_fetch_each_request_in_order() {
+    let post = |id: u64| json!({ "id": id, "userId": 1, "title": "t", "body": "b" }).to_string();
+    let mock = MockAdapter::new()
+        .route(
+            "https://example.com/posts/1",
+            MockAdapter::json(200, &post(1)),
+        )
+        .route(
+            "https://example.com/posts/2",
+            MockAdapter::json(200, "oops"),
+        )
+        .route(
+            "https://example.com/posts/3",
+            MockAdapter::json(200, &post(3)),
+        );
+    let runtime = Runtime::builder().adapter(Box::new(mock.clone())).build();
+    let run = |input: Value| -> Result<(Value, Value)> {
+        let (out_json, meta_json) = runtime.run_json(&input.to_string())?;
+        Ok((
+            serde_json::from_str(&out_json).unwrap(),
+            serde_json::from_str(&meta_json).unwrap(),
+        ))
+    };
+    let requests = |ids: &[u64]| -> Vec<Value> {
+        ids.iter()
+            .map(|id| json!({ "url": format!("https://example.com/posts/{id}") }))
+            .collect()
+    };
+
+    // Every request is fetched and logged in order, the third after the
+    // other two because the policy allows two at a time per host; the one
+    // that fails leaves no post and fails the run.
+    let (out, meta) =
+        run(json!({ "requests": requests(&[1, 2, 3]), "runId": "run-batch" })).unwrap();
+    assert_eq!(out["normalizedPost"], Value::Null);
+    let ids: Vec<&Value> = out["normalizedPosts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|post| &post["id"])
+        .collect();
+    assert_eq!(ids, [1, 3]);
+    let events: Vec<(&str, &Value)> = out["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| (event["type"].as_str().unwrap(), &event["data"]["request"]))
+        .collect();
+    assert_eq!(
+        events,
+        [
+            ("start", &Value::Null),
+            ("queued", &json!(2)),
+            ("fetch_request", &json!(0)),
+            ("fetch_response", &json!(0)),
+            ("normalized", &json!(0)),
+            ("fetch_request", &json!(1)),
+            ("fetch_response", &json!(1)),
+            ("error", &json!(1)),
+            ("fetch_request", &json!(2)),
+            ("fetch_response", &json!(2)),
+            ("normalized", &json!(2)),
+            ("end", &Value::Null),
+        ]
+    );
+    assert_eq!(out["events"][1]["data"]["wave"], 1);
+    assert_eq!(out["events"][7]["data"]["code"], "response.parse_error");
+    assert_eq!(out["summary"]["totalRequests"], 3);
+    assert_eq!(out["summary"]["successes"], 3);
+    assert_eq!(meta["state"], "failed");
+
+    // A request that fails validation stops the whole batch before fetching.
+    let mut bad = requests(&[1, 3]);
+    bad[1]["headers"] = json!({ "x-debug": "1" });
+    bad[1]["retries"] = json!(2);
+    let (out, _) = run(json!({ "requests": bad, "runId": "run-batch" })).unwrap();
+    assert_eq!(
+        out["events"][1]["data"],
+        json!({ "header": "x-debug", "request": 1 })
+    );
+    assert_eq!(out["events"][2]["data"]["code"], "header.unexpected");
+    assert_eq!(out["events"][2]["data"]["request"], 1);
+    assert_eq!(out["normalizedPosts"], json!([]));
+    let (out, _) =
+        run(json!({ "requests": bad, "runId": "run-batch", "inputMode": "strict" })).unwrap();
+    assert_eq!(
+        out["events"][1]["data"]["params"]["fields"],
+        json!(["requests.1.retries"])
+    );
+    assert_eq!(mock.requests().len(), 3);
+
+    // Single-request runs are unchanged: no index, no post list.
+    let (out, _) = run(json!({ "request": requests(&[1])[0], "runId": "run-single" })).unwrap();
+    assert_eq!(out["normalizedPost"]["id"], 1);
+    assert!(out.get("normalizedPosts").is_none());
+    assert!(out["events"][1]["data"].get("request").is_none());
+
+    // The input gives exactly one of `request` and a non-empty `requests`.
+    for (input, message) in [
+        (
+            json!({ "request": requests(&[1])[0], "requests": requests(&[1]), "runId": "r" }),
+            "`request` and `requests` are exclusive",
+        ),
+        (
+            json!({ "requests": [], "runId": "r" }),
+            "`requests` must not be empty",
+        ),
+        (json!({ "runId": "r" }), "missing field `request`"),
+    ] {
+        let err = run(input).unwrap_err();
+        assert!(err.to_string().contains(message), "{err}");
+    }
+}
+
+// A thread-safe adapter whose fetches take longer the earlier the post, so
+// concurrent fetches finish in reverse order.  Counts the fetches in flight.
+#[derive(Clone, Default)]
+struct SlowAdapter {
+    in_flight: Arc<AtomicUsize>,
+    peak: Arc<AtomicUsize>,
+}
+
+impl NetworkAdapter for SlowAdapter {
+    fn fetch(&self, request: &service::model::Request) -> anyhow::Result<NetworkResponse> {
+        let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(now, Ordering::SeqCst);
+        let id: u64 = request.url.rsplit('/').next().unwrap().parse().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(80 - 20 * id));
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        Ok(NetworkResponse {
+            status: 200,
+            headers: HashMap::new(),
+            body: json!({ "id": id, "userId": 1, "title": "t", "body": "b" })
+                .to_string()
+                .into(),
+            decoded: None,
+        })
+    }
+
+    fn thread_safe(&self) -> Option<Arc<dyn NetworkAdapter + Send + Sync>> {
+        Some(Arc::new(self.clone()))
+    }
+}
+
+#[test]
+fn test_batch_fetches_overlap_and_log_in_request_order() {
+    let adapter = SlowAdapter::default();
+    let runtime = Runtime::builder()
+        .adapter(Box::new(adapter.clone()))
+        .build();
+    let input = json!({
+        "requests": [
+            { "url": "https://example.com/posts/0" },
+            { "url": "https://example.org/posts/1" },
+            { "url": "https://example.com/posts/2" },
+            { "url": "https://example.org/posts/3" },
+        ],
+        "runId": "run-pooled",
+    })
+    .to_string();
+    let run = || -> Value { serde_json::from_str(&runtime.run_json(&input).unwrap().0).unwrap() };
+
+    let out = run();
+    assert!(adapter.peak.load(Ordering::SeqCst) >= 2);
+    let ids: Vec<&Value> = out["normalizedPosts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|post| &post["id"])
+        .collect();
+    assert_eq!(ids, [0, 1, 2, 3]);
+    let requests: Vec<&Value> = out["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|event| event["type"] == "fetch_request")
+        .map(|event| &event["data"]["request"])
+        .collect();
+    assert_eq!(requests, [0, 1, 2, 3]);
+    assert_eq!(out["events"], run()["events"]);
+}
+
+#[test]
+fn test_resource_selects_the_normalizer() {
+    let comment =
+        |id: u64| json!({ "id": id, "postId": 1, "name": "n", "email": "e@x.io", "body": "b" });
+    let mock = MockAdapter::new()
+        .route(
+            "https://example.com/posts/1/comments",
+            MockAdapter::json(
+                200,
+                &json!([comment(1), { "id": 2 }, comment(3)]).to_string(),
+            ),
+        )
+        .route(
+            "https://example.com/users/1",
+            MockAdapter::json(
+                200,
+                &json!({ "id": 1, "name": "L", "username": "Bret", "email": "s@april.biz" })
+                    .to_string(),
+            ),
+        )
+        .route(
+            "https://example.com/posts",
+            MockAdapter::json(200, &json!({ "id": 1 }).to_string()),
+        );
+    let runtime = Runtime::builder().adapter(Box::new(mock)).build();
+    let run = |url: &str, resource: &str| -> Value {
+        let input =
+            json!({ "request": { "url": url }, "resource": resource, "runId": "run-resource" });
+        serde_json::from_str(&runtime.run_json(&input.to_string()).unwrap().0).unwrap()
+    };
+
+    // A collection keeps the elements that normalize and counts the rest.
+    let out = run("https://example.com/posts/1/comments", "comments");
+    assert_eq!(out["normalizedPost"], Value::Null);
+    assert!(out.get("normalizedPosts").is_none());
+    let ids: Vec<&Value> = out["normalizedComments"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|comment| &comment["id"])
+        .collect();
+    assert_eq!(ids, [1, 3]);
+    assert_eq!(
+        out["events"][3]["data"],
+        json!({ "resource": "comments", "count": 2, "skipped": 1 })
+    );
+
+    let out = run("https://example.com/users/1", "user");
+    assert_eq!(out["normalizedUsers"][0]["username"], "Bret");
+    assert_eq!(
+        out["events"][3]["data"],
+        json!({ "resource": "user", "id": 1 })
+    );
+
+    // A collection must be an array.
+    let out = run("https://example.com/posts", "posts");
+    assert_eq!(out["normalizedPosts"], json!([]));
+    assert_eq!(
+        out["events"][3]["data"]["code"],
+        "response.invalid_resource"
+    );
+    assert_eq!(out["events"][3]["data"]["params"]["resource"], "posts");
+
+    // Strict mode accepts the field; a resource without a normalizer fails
+    // the run before fetching.
+    let input = json!({
+        "request": { "url": "https://example.com/users/1" },
+        "resource": "user",
+        "runId": "run-resource",
+        "inputMode": "strict",
+    });
+    let (out, _) = runtime.run_json(&input.to_string()).unwrap();
+    assert!(!out.contains("input.validation_error"));
+    let out = run("https://example.com/todos/1", "todos");
+    assert_eq!(out["events"][1]["data"]["code"], "normalizer.unknown");
+    assert_eq!(out["events"][2]["type"], "end");
+}
+
+// Normalizes a plain-text body of `id|title` into a post.
+struct PipeNormalizer;
+
+impl Normalizer for PipeNormalizer {
+    fn kind(&self) -> RecordKind {
+        RecordKind::Post
+    }
+
+    fn normalize(&self, body: &[u8]) -> Result<Normalized, NormalizeError> {
+        let body = service::normalizer::text(body)?;
+        let (id, title) = body.split_once('|').ok_or(NormalizeError::Shape)?;
+        let id: u64 = id
+            .parse()
+            .map_err(|_| NormalizeError::Parse(format!("bad id {id}")))?;
+        Ok(Normalized {
+            posts: vec![service::model::Post {
+                id,
+                user_id: 0,
+                title: title.to_string(),
+                body: String::new(),
+            }],
+            details: json!({ "id": id }),
+            ..Default::default()
+        })
+    }
+}
+
+#[test]
+fn test_normalizers_dispatch_by_content_type_and_are_recorded() {
+    let response = |content_type: &str, body: &str| NetworkResponse {
+        status: 200,
+        headers: HashMap::from([("content-type".to_string(), content_type.to_string())]),
+        body: body.into(),
+        decoded: None,
+    };
+    let feed = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel><title>Posts</title>
+  <item><title>First &amp; best</title><guid>https://example.com/posts/41</guid>
+    <description><![CDATA[<p>Hello</p>]]></description></item>
+  <item><title>No id</title></item>
+</channel></rss>"#;
+    let mock = MockAdapter::new()
+        .route(
+            "https://example.com/feed",
+            response("application/rss+xml; charset=utf-8", feed),
+        )
+        .route(
+            "https://example.com/pipe",
+            response("text/x-pipe", "7|Piped"),
+        )
+        .route("https://example.com/posts/1", {
+            MockAdapter::json(200, r#"{"id":1,"userId":1,"title":"t","body":"b"}"#)
+        });
+    let runtime = Runtime::builder()
+        .adapter(Box::new(mock))
+        .register_normalizer("pipe", PipeNormalizer)
+        .normalize_content_type("text/x-pipe", "pipe")
+        .build();
+    let run = |input: Value| -> (Value, Value) {
+        let (out, meta) = runtime.run_json(&input.to_string()).unwrap();
+        (
+            serde_json::from_str(&out).unwrap(),
+            serde_json::from_str(&meta).unwrap(),
+        )
+    };
+
+    // An RSS feed goes to `rss-item` by its content type.
+    let (out, meta) =
+        run(json!({ "request": { "url": "https://example.com/feed" }, "runId": "r" }));
+    assert_eq!(out["normalizedPost"], Value::Null);
+    assert_eq!(
+        out["normalizedPosts"],
+        json!([{ "id": 41, "user_id": 0, "title": "First & best", "body": "<p>Hello</p>" }])
+    );
+    assert_eq!(
+        out["events"][3]["data"],
+        json!({ "resource": "rss-item", "count": 1, "skipped": 1 })
+    );
+    assert_eq!(
+        meta["bindings"]["normalizer"],
+        json!({ "impl": "rss-item", "selectedBy": "contentType" })
+    );
+
+    // A batch records every normalizer its responses went through.
+    let urls = ["https://example.com/posts/1", "https://example.com/pipe"];
+    let requests: Vec<Value> = urls.iter().map(|url| json!({ "url": url })).collect();
+    let (out, meta) = run(json!({ "requests": requests, "runId": "r" }));
+    let titles: Vec<&Value> = out["normalizedPosts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|post| &post["title"])
+        .collect();
+    assert_eq!(titles, ["t", "Piped"]);
+    assert_eq!(meta["bindings"]["normalizer"]["impl"], "post,pipe");
+
+    // A named resource overrides the content type.
+    let (out, meta) = run(json!({
+        "request": { "url": "https://example.com/feed" },
+        "resource": "pipe",
+        "runId": "r",
+    }));
+    assert_eq!(
+        out["events"][3]["data"]["code"],
+        "response.invalid_resource"
+    );
+    assert_eq!(
+        meta["bindings"]["normalizer"],
+        json!({ "impl": "pipe", "selectedBy": "resource" })
+    );
+
+    // Single JSON posts are unchanged.
+    let (out, meta) =
+        run(json!({ "request": { "url": "https://example.com/posts/1" }, "runId": "r" }));
+    assert_eq!(out["normalizedPost"]["id"], 1);
+    assert!(out.get("normalizedPosts").is_none());
+    assert_eq!(meta["bindings"]["normalizer"]["impl"], "post");
+}
+
+#[test]
+fn test_json_array_stream_returns_elements_as_they_complete() {
+    let body = r#" [ {"id": 1, "title": "a \"quoted\" ]}, bracket", "tags": [1, [2]]},
+        "text \\", 42, -1.5e3, true, null, [] ] "#;
+    let expected: Vec<Value> = serde_json::from_str::<Value>(body)
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .clone();
+    // However the body is split, the elements are the same.
+    for chunk_bytes in [1, 2, 7, body.len()] {
+        let mut stream = JsonArrayStream::new();
+        let mut elements = Vec::new();
+        for chunk in body.as_bytes().chunks(chunk_bytes) {
+            elements.extend(stream.push(chunk).unwrap());
+        }
+        stream.finish().unwrap();
+        assert_eq!(elements, expected, "chunks of {chunk_bytes}");
+    }
+
+    // An element is returned as soon as it closes.
+    let mut stream = JsonArrayStream::new();
+    assert_eq!(
+        stream.push(br#"[{"id": 1}, {"id""#).unwrap(),
+        [json!({ "id": 1 })]
+    );
+    assert_eq!(stream.push(br#": 2}]"#).unwrap(), [json!({ "id": 2 })]);
+
+    let fail = |body: &str| {
+        let mut stream = JsonArrayStream::new();
+        stream
+            .push(body.as_bytes())
+            .and_then(|_| stream.finish())
+            .unwrap_err()
+    };
+    assert_eq!(fail(r#" {"id": 1}"#), ArrayStreamError::NotAnArray);
+    assert_eq!(fail("  "), ArrayStreamError::NotAnArray);
+    for invalid in ["[1,]", "[1 2]", "[1] x", "[{\"id\": 1}", "[tru]"] {
+        assert!(
+            matches!(fail(invalid), ArrayStreamError::Invalid(_)),
+            "{invalid}"
+        );
+    }
+}
+
+#[test]
+fn test_streamed_runs_normalize_list_bodies_by_chunk() {
+    use std::io::Write;
+
+    let post = |id: u64| json!({ "id": id, "userId": 1, "title": format!("t{id}"), "body": "b" });
+    let mut posts: Vec<Value> = (1..=20).map(post).collect();
+    posts.push(json!({ "id": 21 }));
+    let body = json!(posts).to_string();
+
+    // A local server answering with the list, with no length, so the body
+    // is read until the connection closes.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/posts", listener.local_addr().unwrap());
+    let served = body.clone();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut buf = [0u8; 1024];
+            let _ = std::io::Read::read(&mut stream, &mut buf);
+            let head =
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n";
+            let _ = stream.write_all(head.as_bytes());
+            let _ = stream.write_all(served.as_bytes());
+        }
+    });
+    let run = |limits: ExecutionLimits, chunk_bytes: Option<usize>| {
+        let mut runtime = Runtime::builder().adapter(Box::new(adapter_manager::HostFetchAdapter {
+            limits,
+            ..Default::default()
+        }));
+        if let Some(chunk_bytes) = chunk_bytes {
+            runtime = runtime.stream_chunks(chunk_bytes);
+        }
+        let input =
+            json!({ "request": { "url": url }, "resource": "posts", "runId": "run-stream" });
+        let (out_json, _) = runtime.build().run_json(&input.to_string()).unwrap();
+        serde_json::from_str::<Value>(&out_json).unwrap()
+    };
+
+    let buffered = run(ExecutionLimits::default(), None);
+    let streamed = run(ExecutionLimits::default(), Some(256));
+    assert_eq!(streamed["normalizedPosts"], buffered["normalizedPosts"]);
+    assert_eq!(streamed["normalizedPosts"].as_array().unwrap().len(), 20);
+    let events = streamed["events"].as_array().unwrap();
+    let progress: Vec<&Value> = events
+        .iter()
+        .filter(|event| event["type"] == "fetch_progress")
+        .map(|event| &event["data"])
+        .collect();
+    assert_eq!(progress.len(), body.len().div_ceil(256));
+    assert_eq!(progress[0]["chunk"], 1);
+    assert_eq!(progress[0]["bytes"], 256);
+    // Records are counted as their elements close, before the body ends.
+    let counts: Vec<u64> = progress
+        .iter()
+        .map(|data| data["records"].as_u64().unwrap())
+        .collect();
+    assert!(counts[0] > 0 && counts[0] < 20, "{counts:?}");
+    assert_eq!(progress.last().unwrap()["bytes"], body.len());
+    let normalized = events
+        .iter()
+        .find(|event| event["type"] == "normalized")
+        .unwrap();
+    assert_eq!(
+        normalized["data"],
+        json!({ "resource": "posts", "count": 20, "skipped": 1 })
+    );
+    assert!(buffered["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|event| event["type"] != "fetch_progress"));
+
+    // The body limit stops the stream partway.
+    let limited = run(
+        ExecutionLimits {
+            max_body_bytes: Some(600),
+            ..Default::default()
+        },
+        Some(256),
+    );
+    let types: Vec<&str> = limited["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| event["type"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        types,
+        [
+            "start",
+            "fetch_request",
+            "fetch_response",
+            "fetch_progress",
+            "fetch_progress",
+            "error",
+            "end"
+        ]
+    );
+    assert_eq!(
+        limited["events"][5]["data"]["code"],
+        "network.budget_exceeded"
+    );
+}
+
+#[test]
+fn test_host_fetch_config_routes_through_the_proxy() {
+    use std::io::Write;
+
+    // A local proxy that answers every request itself and reports the
+    // request line it was sent.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy = format!("http://{}", listener.local_addr().unwrap());
+    let (seen, received) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut buf = [0u8; 1024];
+            let read = std::io::Read::read(&mut stream, &mut buf).unwrap_or(0);
+            let head = String::from_utf8_lossy(&buf[..read]).to_string();
+            let _ = seen.send(head.lines().next().unwrap_or_default().to_string());
+            let body = json!({ "id": 7, "userId": 1, "title": "proxied", "body": "b" }).to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    let run = |config: HostFetchConfig| {
+        let runtime = Runtime::builder().host_fetch_config(config).build();
+        let input =
+            json!({ "request": { "url": "http://posts.invalid/posts/7" }, "runId": "run-proxy" });
+        let (out_json, _) = runtime.run_json(&input.to_string()).unwrap();
+        serde_json::from_str::<Value>(&out_json).unwrap()
+    };
+
+    let out = run(HostFetchConfig {
+        proxy: Some(proxy),
+        connect_timeout_millis: Some(2000),
+        tls_min_version: Some(TlsVersion::Tls1_2),
+        ..HostFetchConfig::default()
+    });
+    assert_eq!(out["normalizedPost"]["title"], "proxied");
+    // The proxy is sent the absolute URL.
+    assert_eq!(
+        received.recv().unwrap(),
+        "GET http://posts.invalid/posts/7 HTTP/1.1"
+    );
+
+    // A CA file that cannot be read fails the fetch.
+    let out = run(HostFetchConfig {
+        ca_cert_path: Some("/nonexistent/ca.pem".into()),
+        ..HostFetchConfig::default()
+    });
+    assert_eq!(out["events"][3]["data"]["code"], "network.failure");
+    assert!(out["events"][3]["data"]["params"]["detail"]
+        .as_str()
+        .unwrap()
+        .contains("/nonexistent/ca.pem"));
+}
+
+#[test]
+fn test_auth_adapter_injects_refreshes_and_redacts_tokens() {
+    // Accepts only `Bearer fresh`, recording the authorization of every
+    // request, and fails with the header in its error for `Bearer leaky`.
+    struct Guarded(Arc<Mutex<Vec<String>>>);
+
+    impl NetworkAdapter for Guarded {
+        fn fetch(&self, request: &service::model::Request) -> anyhow::Result<NetworkResponse> {
+            let auth = request
+                .headers
+                .get("Authorization")
+                .cloned()
+                .unwrap_or_default();
+            self.0.lock().unwrap().push(auth.clone());
+            if auth == "Bearer leaky" {
+                anyhow::bail!("rejected credentials {auth}");
+            }
+            let status = if auth == "Bearer fresh" { 200 } else { 401 };
+            Ok(NetworkResponse {
+                status,
+                headers: HashMap::new(),
+                body: r#"{"id":1,"userId":2,"title":"t","body":"b"}"#.into(),
+                decoded: None,
+            })
+        }
+    }
+
+    // Hands out `stale` until refreshed.
+    struct Rotating(std::cell::RefCell<&'static str>);
+
+    impl TokenProvider for Rotating {
+        fn token(&self) -> anyhow::Result<String> {
+            Ok(self.0.borrow().to_string())
+        }
+
+        fn refresh(&self) -> anyhow::Result<()> {
+            *self.0.borrow_mut() = "fresh";
+            Ok(())
+        }
+    }
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let run = |provider: Rc<dyn TokenProvider>| {
+        let runtime = Runtime::builder()
+            .adapter(Box::new(Guarded(Arc::clone(&seen))))
+            .auth(provider)
+            .build();
+        let input = json!({
+            "request": { "url": "https://example.com/posts/1", "headers": { "Authorization": "Bearer input" } },
+            "runId": "run-auth",
+        });
+        let (out, meta) = runtime.run_json(&input.to_string()).unwrap();
+        (serde_json::from_str::<Value>(&out).unwrap(), meta)
+    };
+
+    // A 401 refreshes the token and sends the request once more.
+    let (out, meta) = run(Rc::new(Rotating(std::cell::RefCell::new("stale"))));
+    assert_eq!(out["normalizedPost"]["id"], 1);
+    assert_eq!(*seen.lock().unwrap(), ["Bearer stale", "Bearer fresh"]);
+    let types: Vec<&str> = out["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| event["type"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        types,
+        [
+            "start",
+            "fetch_request",
+            "auth_refresh",
+            "fetch_response",
+            "normalized",
+            "end"
+        ]
+    );
+    assert_eq!(
+        out["events"][2]["data"]["url"],
+        "https://example.com/posts/1"
+    );
+    let meta: Value = serde_json::from_str(&meta).unwrap();
+    assert_eq!(meta["bindings"]["network.fetch"]["impl"], "auth-custom");
+
+    // A token that is still rejected is not refreshed again.
+    seen.lock().unwrap().clear();
+    let (out, _) = run(Rc::new(StaticToken("stale".to_string())));
+    assert_eq!(*seen.lock().unwrap(), ["Bearer stale", "Bearer stale"]);
+    assert_eq!(out["events"][3]["data"]["status"], 401);
+
+    // Tokens never reach the events or the lifecycle record.
+    let (out, meta) = run(Rc::new(|| Ok("leaky".to_string())));
+    let detail = out["events"][3]["data"]["params"]["detail"]
+        .as_str()
+        .unwrap();
+    assert_eq!(detail, "rejected credentials Bearer [redacted]");
+    assert!(!out.to_string().contains("leaky") && !meta.contains("leaky"));
+
+    // File tokens are read on every request, trimmed.
+    let path = std::env::temp_dir().join(format!("uma-token-{}", std::process::id()));
+    std::fs::write(&path, "fresh\n").unwrap();
+    assert_eq!(FileToken(path.clone()).token().unwrap(), "fresh");
+    std::fs::write(&path, "  ").unwrap();
+    assert!(FileToken(path.clone()).token().is_err());
+    std::fs::remove_file(&path).unwrap();
+    assert!(FileToken(path).token().is_err());
+}
+
+#[test]
+fn test_canonical_request_ignores_case_order_and_encoding() {
+    let request = |url: &str, headers: &[(&str, &str)]| service::model::Request {
+        url: url.to_string(),
+        headers: headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+        method: Some("post".to_string()),
+        body: Some(br#"{"title":"t"}"#.to_vec()),
+    };
+    let names = ["Host", "content-type", "x-date", "x-missing"];
+    let (canonical, signed) = canonical_request(
+        &request(
+            "https://API.example.com/posts/a%20b/caf%c3%a9?b=2&a=1&a=0&flag#top",
+            &[
+                ("Content-Type", "application/json"),
+                ("X-Date", " 20260101T000000Z "),
+            ],
+        ),
+        &names,
+    );
+    assert_eq!(
+        canonical,
+        "POST\n/posts/a%20b/caf%C3%A9\na=0&a=1&b=2&flag=\n\
+         content-type:application/json\nhost:api.example.com\nx-date:20260101T000000Z\n\n\
+         content-type;host;x-date\n\
+         fd32f01954a82d8192431bf0c8d2fe22d8c10a9d62a1f4aad96228b065e6cc65"
+    );
+    assert_eq!(signed, ["content-type", "host", "x-date"]);
+
+    // Raw characters, reordered parameters and header case sign the same.
+    let (equivalent, _) = canonical_request(
+        &request(
+            "https://api.example.com/posts/a b/café?flag=&a=0&b=2&a=1",
+            &[
+                ("x-date", "20260101T000000Z"),
+                ("content-type", "application/json"),
+            ],
+        ),
+        &names,
+    );
+    assert_eq!(equivalent, canonical);
+
+    // A bare URL signs as the root path with no query and an empty body.
+    let bare = service::model::Request {
+        url: "http://example.com".to_string(),
+        headers: HashMap::new(),
+        method: None,
+        body: None,
+    };
+    let (canonical, _) = canonical_request(&bare, &["host"]);
+    assert!(canonical.starts_with("GET\n/\n\nhost:example.com\n\nhost\ne3b0c442"));
+}
+
+#[test]
+fn test_signing_middleware_signs_each_request() {
+    struct Echo(Arc<Mutex<Vec<Option<String>>>>);
+
+    impl NetworkAdapter for Echo {
+        fn fetch(&self, request: &service::model::Request) -> anyhow::Result<NetworkResponse> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(request.headers.get("X-Signature").cloned());
+            Ok(NetworkResponse {
+                status: 200,
+                headers: HashMap::new(),
+                body: r#"{"id":1,"userId":2,"title":"t","body":"b"}"#.into(),
+                decoded: None,
+            })
+        }
+    }
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let run = |keys: Rc<dyn KeyProvider>| {
+        let runtime = Runtime::builder()
+            .adapter(Box::new(Echo(Arc::clone(&seen))))
+            .middleware(Rc::new(SigningMiddleware::new(keys).sign_headers(["host"])))
+            .build();
+        let input =
+            json!({ "request": { "url": "https://example.com/posts/1" }, "runId": "run-sign" });
+        let (out, _) = runtime.run_json(&input.to_string()).unwrap();
+        serde_json::from_str::<Value>(&out).unwrap()
+    };
+
+    let out = run(Rc::new(SigningKey::new("k1", "secret")));
+    assert_eq!(out["normalizedPost"]["id"], 1);
+    assert_eq!(
+        seen.lock().unwrap().pop().unwrap().unwrap(),
+        "HMAC-SHA256 KeyId=k1, SignedHeaders=host, \
+         Signature=1d555c11f5c6c2c01ba3e79e56d40a10cfb03b3bb413bd0e80e36820b18b1273"
+    );
+    assert_eq!(
+        format!("{:?}", SigningKey::new("k1", "secret")),
+        r#"SigningKey { id: "k1", secret: "[redacted]" }"#
+    );
+
+    // A key that cannot be had fails the fetch before it is sent.
+    let out = run(Rc::new(|| Err(anyhow::anyhow!("the key store is sealed"))));
+    assert!(seen.lock().unwrap().is_empty());
+    assert_eq!(out["events"][3]["data"]["code"], "network.failure");
+    assert_eq!(out["events"][3]["data"]["error"], "the key store is sealed");
+}
+
+#[test]
+fn test_secrets_are_masked_before_events_are_stored() {
+    // Fails with the credentials it was sent, as a careless upstream might.
+    struct Echoing;
+
+    impl NetworkAdapter for Echoing {
+        fn fetch(&self, request: &service::model::Request) -> anyhow::Result<NetworkResponse> {
+            let auth = request
+                .headers
+                .get("Authorization")
+                .cloned()
+                .unwrap_or_default();
+            anyhow::bail!("GET {} was refused\nAuthorization: {auth}", request.url)
+        }
+    }
+
+    let input = json!({
+        "request": {
+            "url": "https://example.com/posts?access_token=abc123&page=2",
+            "headers": { "Authorization": "Bearer abc123", "X-Session": "s-9" }
+        },
+        "runId": "run-secrets"
+    })
+    .to_string();
+    let decorator = |_: &service::model::Event| {
+        json!({ "region": "eu-west-1", "apiKey": "k-1" })
+            .as_object()
+            .unwrap()
+            .clone()
+    };
+    let seen = Mutex::new(Vec::new());
+    let sink = |event: &service::model::Event| seen.lock().unwrap().push(event.clone());
+    let runtime = Runtime::builder()
+        .adapter(Box::new(Echoing))
+        .allowed_headers(["authorization"])
+        .decorator(&decorator)
+        .sink(&sink)
+        .build();
+    let (out, lifecycle) = runtime.run_json(&input).unwrap();
+    assert!(!out.contains("abc123") && !lifecycle.contains("abc123"));
+    assert!(!out.contains("k-1") && !lifecycle.contains("k-1"));
+    let out: Value = serde_json::from_str(&out).unwrap();
+    // The unexpected header is named, never valued.
+    assert_eq!(out["events"][1]["type"], "header_unexpected");
+    assert_eq!(out["events"][1]["data"], json!({ "header": "X-Session" }));
+    assert_eq!(out["events"][2]["data"]["params"]["header"], "X-Session");
+    assert_eq!(out["events"][1]["host"]["apiKey"], "[redacted]");
+    assert_eq!(out["events"][1]["host"]["region"], "eu-west-1");
+    assert_eq!(
+        serde_json::to_value(&*seen.lock().unwrap()).unwrap(),
+        out["events"]
+    );
+
+    // Without it the fetch fails, echoing the token.
+    let input = input.replace(r#","X-Session":"s-9""#, "");
+    let (out, lifecycle) = runtime.run_json(&input).unwrap();
+    assert!(!out.contains("abc123") && !lifecycle.contains("abc123"));
+    let out: Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(
+        out["events"][1]["data"]["url"],
+        "https://example.com/posts?access_token=[redacted]&page=2"
+    );
+    assert_eq!(
+        out["events"][3]["data"]["params"]["detail"],
+        "GET https://example.com/posts?access_token=[redacted]&page=2 was refused\n\
+         Authorization: [redacted]"
+    );
+
+    // Hosts choose the names; a path that ends in one is not a name.
+    let secrets = SecretsPolicy::new(["x-session", "*api?key*"]);
+    let mut data = json!({
+        "header": "X-Session",
+        "value": "s-9",
+        "detail": "reading /run/secrets/api-key: not found; api_key=k-2",
+        "token": "kept"
+    });
+    secrets.mask(&mut data);
+    assert_eq!(
+        data,
+        json!({
+            "header": "X-Session",
+            "value": "[redacted]",
+            "detail": "reading /run/secrets/api-key: not found; api_key=[redacted]",
+            "token": "kept"
+        })
+    );
+    assert!(SecretsPolicy::default().is_sensitive("Proxy-Authorization"));
+    assert!(SecretsPolicy::default().is_sensitive("X-CSRF-Token"));
+    assert!(SecretsPolicy::default().is_sensitive("apiKey"));
+    assert!(!SecretsPolicy::default().is_sensitive("keyId"));
+}
+
+#[test]
+fn test_subscribers_receive_events_as_they_are_emitted() {
+    let _guard = env_lock();
+    // Records which events the subscriber had received when the fetch began.
+    struct Watching(Arc<Mutex<Vec<service::model::Event>>>, Arc<Mutex<usize>>);
+
+    impl NetworkAdapter for Watching {
+        fn fetch(&self, request: &service::model::Request) -> anyhow::Result<NetworkResponse> {
+            *self.1.lock().unwrap() = self.0.lock().unwrap().len();
+            DummyAdapter.fetch(request)
+        }
+    }
+
+    let streamed = Arc::new(Mutex::new(Vec::new()));
+    let seen_at_fetch = Arc::new(Mutex::new(0));
+    let listener = {
+        let streamed = Arc::clone(&streamed);
+        move |event: &service::model::Event| streamed.lock().unwrap().push(event.clone())
+    };
+    let lines = JsonLines::new(Vec::new());
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let input = json!({
+        "request": { "url": "https://example.com/posts/7?q=secret", "headers": {} },
+        "runId": "run-streamed"
+    })
+    .to_string();
+    std::env::set_var("UMA_PRIVACY_MODE", "1");
+    let result = Runtime::builder()
+        .with_env()
+        .adapter(Box::new(Watching(
+            Arc::clone(&streamed),
+            Arc::clone(&seen_at_fetch),
+        )))
+        .subscribe(&listener)
+        .subscribe(&lines)
+        .subscribe(&sender)
+        .build()
+        .run_json(&input);
+    std::env::remove_var("UMA_PRIVACY_MODE");
+    let (out_json, _) = result.unwrap();
+
+    // Only `start` had been emitted when the fetch began.
+    assert_eq!(*seen_at_fetch.lock().unwrap(), 1);
+    let out: Value = serde_json::from_str(&out_json).unwrap();
+    assert_eq!(out["events"].as_array().unwrap().len(), 5);
+    // Every subscriber saw the log of the output, privacy rules applied.
+    assert!(!out_json.contains("run-streamed"));
+    assert_eq!(
+        serde_json::to_value(&*streamed.lock().unwrap()).unwrap(),
+        out["events"]
+    );
+    let lines: Vec<Value> = String::from_utf8(lines.into_inner())
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(Value::Array(lines), out["events"]);
+    drop(sender);
+    let received: Vec<service::model::Event> = receiver.iter().collect();
+    assert_eq!(serde_json::to_value(received).unwrap(), out["events"]);
+}
+
+#[test]
+fn test_event_schemas_flag_payloads_and_fail_strict_runs() {
+    let contract = ServiceContract::from_json(
+        r#"{ "events": {
+            "start": { "required": ["runId"] },
+            "normalized": { "properties": { "id": { "type": "string" } } }
+        } }"#,
+    )
+    .unwrap();
+    let runtime = Runtime::builder()
+        .contract(contract)
+        .adapter(Box::new(DummyAdapter))
+        .build();
+    let input = |mode: &str| -> Input {
+        serde_json::from_value(json!({
+            "request": { "url": "https://example.com/posts/1" },
+            "runId": "run-schemas",
+            "inputMode": mode
+        }))
+        .unwrap()
+    };
+
+    // Permissive runs record the violation and carry on.
+    let result = runtime.run(input("permissive")).unwrap();
+    let types: Vec<&str> = result
+        .output
+        .events
+        .iter()
+        .map(|event| event.type_.as_str())
+        .collect();
+    assert_eq!(
+        types,
+        [
+            "start",
+            "fetch_request",
+            "fetch_response",
+            "normalized",
+            "schema_violation",
+            "end"
+        ]
+    );
+    assert_eq!(
+        result.output.events[4].data,
+        json!({
+            "event": "normalized",
+            "t": "3",
+            "violations": [{ "pointer": "/id", "expected": "type \"string\"" }],
+        })
+    );
+    assert_eq!(result.error(), None);
+    assert!(result.output.normalized_post.is_some());
+
+    // Strict runs fail on it.
+    let result = runtime.run(input("strict")).unwrap();
+    assert_eq!(
+        result.output.events[5].data["code"],
+        "event.schema_violation"
+    );
+    assert_eq!(
+        result.error(),
+        Some(RunError::ContractViolation(
+            "event normalized does not match its schema".to_string()
+        ))
+    );
+    assert_eq!(result.lifecycle.state, "failed");
+
+    // Even the `end` event can fail a run, and the builder's schemas
+    // replace the contract's.
+    let schemas = EventSchemas::new()
+        .schema("end", json!({ "required": ["reason"] }))
+        .unwrap();
+    let runtime = Runtime::builder()
+        .adapter(Box::new(DummyAdapter))
+        .event_schemas(schemas)
+        .build();
+    let result = runtime.run(input("strict")).unwrap();
+    let last = result.output.events.last().unwrap();
+    assert_eq!(
+        (last.type_.as_str(), &last.data["params"]),
+        ("error", &json!({ "event": "end" }))
+    );
+    assert_eq!(result.lifecycle.state, "failed");
+
+    let err = ServiceContract::from_json(r#"{ "events": { "start": { "type": 5 } } }"#)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("schema for `start` events"), "{err}");
+    assert!(EventSchemas::new()
+        .schema("start", json!({ "minimum": "x" }))
+        .is_err());
+}
+
+#[test]
+fn test_time_source_stamps_wall_time_beside_logical_time() {
+    let input = json!({ "request": { "url": "uma-fixture://sample-post" }, "runId": "run-wall" });
+    let ticks = std::cell::Cell::new(1_714_566_600_000);
+    let source = || ticks.replace(ticks.get() + 250);
+    let runtime = Runtime::builder().time_source(&source).build();
+    let (out_json, lifecycle_json) = runtime.run_json(&input.to_string()).unwrap();
+    let out: Value = serde_json::from_str(&out_json).unwrap();
+    let stamps: Vec<(&str, &str)> = out["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| {
+            (
+                event["t"].as_str().unwrap(),
+                event["wallTime"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        stamps,
+        [
+            ("0", "2024-05-01T12:30:00.000Z"),
+            ("1", "2024-05-01T12:30:00.250Z"),
+            ("2", "2024-05-01T12:30:00.500Z"),
+            ("3", "2024-05-01T12:30:00.750Z"),
+            ("4", "2024-05-01T12:30:01.000Z"),
+        ]
+    );
+    let lifecycle: Value = serde_json::from_str(&lifecycle_json).unwrap();
+    assert_eq!(lifecycle["events"], out["events"]);
+
+    // Without a source the log is as before.
+    let (out_json, _) = Runtime::builder()
+        .build()
+        .run_json(&input.to_string())
+        .unwrap();
+    assert!(!out_json.contains("wallTime"));
+}
+
+#[test]
+fn test_trace_context_is_propagated_and_stamped() {
+    // Records the traceparent of every request it receives.
+    struct Traced(Arc<Mutex<Vec<Option<String>>>>);
+
+    impl NetworkAdapter for Traced {
+        fn fetch(&self, request: &service::model::Request) -> anyhow::Result<NetworkResponse> {
+            let traceparent = request.headers.get(TRACEPARENT_HEADER).cloned();
+            self.0.lock().unwrap().push(traceparent);
+            DummyAdapter.fetch(request)
+        }
+    }
+
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let runtime = Runtime::builder()
+        .adapter(Box::new(Traced(Arc::clone(&sent))))
+        .cache(CachePolicy::InMemory)
+        .build();
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+    let input = |span_id: &str, url: &str| {
+        json!({
+            "request": { "url": url },
+            "runId": "run-traced",
+            "traceId": trace_id,
+            "spanId": span_id
+        })
+        .to_string()
+    };
+    let (out_json, lifecycle_json) = runtime
+        .run_json(&input("00f067aa0ba902b7", "https://example.com/posts/1"))
+        .unwrap();
+    assert_eq!(
+        *sent.lock().unwrap(),
+        [Some(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".to_string()
+        )]
+    );
+    let out: Value = serde_json::from_str(&out_json).unwrap();
+    for event in out["events"].as_array().unwrap() {
+        assert_eq!(event["traceId"], trace_id, "{event}");
+        assert_eq!(event["spanId"], "00f067aa0ba902b7", "{event}");
+    }
+    let lifecycle: Value = serde_json::from_str(&lifecycle_json).unwrap();
+    assert_eq!(lifecycle["traceId"], trace_id);
+    assert_eq!(lifecycle["spanId"], "00f067aa0ba902b7");
+
+    // The header is not part of the cache key.
+    let (out_json, _) = runtime
+        .run_json(&input("b7ad6b7169203331", "https://example.com/posts/1"))
+        .unwrap();
+    assert!(out_json.contains("cache_hit"));
+    assert_eq!(sent.lock().unwrap().len(), 1);
+
+    // Runs without a trace send no header and stamp nothing.
+    let plain =
+        json!({ "request": { "url": "https://example.com/posts/2" }, "runId": "run-plain" });
+    let (out_json, lifecycle_json) = runtime.run_json(&plain.to_string()).unwrap();
+    assert_eq!(sent.lock().unwrap()[1], None);
+    assert!(!out_json.contains("traceId") && !lifecycle_json.contains("spanId"));
+
+    for (trace_id, span_id) in [
+        (Some(trace_id), None),
+        (
+            Some("4BF92F3577B34DA6A3CE929D0E0E4736"),
+            Some("00f067aa0ba902b7"),
+        ),
+        (Some(trace_id), Some("0000000000000000")),
+    ] {
+        let input = json!({
+            "request": { "url": "https://example.com/posts/1" },
+            "runId": "run-bad-trace",
+            "traceId": trace_id,
+            "spanId": span_id
+        });
+        let err = runtime.run_json(&input.to_string()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RunError>(),
+            Some(RunError::InputInvalid(_))
+        ));
+    }
+}
+
+#[cfg(feature = "otel")]
+#[test]
+fn test_otel_sink_mirrors_runs_as_spans_and_logs() {
+    use opentelemetry::logs::{LoggerProvider, Severity};
+    use opentelemetry::trace::{Status, TracerProvider};
+    use opentelemetry::Value as OtelValue;
+    use opentelemetry_sdk::logs::{InMemoryLogExporter, SdkLoggerProvider};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+    let spans = InMemorySpanExporter::default();
+    let tracer_provider = SdkTracerProvider::builder()
+        .with_simple_exporter(spans.clone())
+        .build();
+    let logs = InMemoryLogExporter::default();
+    let logger_provider = SdkLoggerProvider::builder()
+        .with_simple_exporter(logs.clone())
+        .build();
+    let sink = OtelSink::new(tracer_provider.tracer("uma-test"))
+        .logger(logger_provider.logger("uma-test"));
+    let mock =
+        MockAdapter::new().route("https://example.com/posts/404", MockAdapter::json(404, ""));
+    let runtime = Runtime::builder()
+        .adapter(Box::new(mock))
+        .subscribe(&sink)
+        .build();
+
+    let (out_json, _) = runtime
+        .run_json(
+            &json!({
+                "request": { "url": "https://example.com/posts/404" },
+                "runId": "run-otel",
+                "traceId": "4bf92f3577b34da6a3ce929d0e0e4736",
+                "spanId": "00f067aa0ba902b7"
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let out: Value = serde_json::from_str(&out_json).unwrap();
+    let types: Vec<&str> = out["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| event["type"].as_str().unwrap())
+        .collect();
+
+    let finished = spans.get_finished_spans().unwrap();
+    assert_eq!(finished.len(), 1);
+    let span = &finished[0];
+    assert_eq!(span.name, RUN_SPAN_NAME);
+    assert_eq!(
+        span.span_context.trace_id().to_string(),
+        "4bf92f3577b34da6a3ce929d0e0e4736"
+    );
+    assert_eq!(span.parent_span_id.to_string(), "00f067aa0ba902b7");
+    assert!(span.parent_span_is_remote);
+    assert_eq!(
+        span.events
+            .events
+            .iter()
+            .map(|event| event.name.as_ref())
+            .collect::<Vec<_>>(),
+        types
+    );
+    assert!(matches!(span.status, Status::Error { .. }));
+    let attribute = |name: &str| {
+        span.attributes
+            .iter()
+            .find(|attribute| attribute.key.as_str() == name)
+            .map(|attribute| attribute.value.clone())
+    };
+    assert_eq!(attribute("uma.run_id"), Some(OtelValue::from("run-otel")));
+    assert_eq!(
+        attribute("uma.lifecycle.state"),
+        Some(OtelValue::from("failed"))
+    );
+    assert_eq!(
+        attribute("uma.lifecycle.bindings.network.fetch.impl"),
+        Some(OtelValue::from("custom"))
+    );
+    assert_eq!(
+        attribute("uma.lifecycle.logicalClock"),
+        Some(OtelValue::from(types.len() as i64))
+    );
+    assert_eq!(attribute("uma.lifecycle.events"), None);
+
+    let emitted = logs.get_emitted_logs().unwrap();
+    assert_eq!(emitted.len(), types.len());
+    for log in &emitted {
+        let context = log.record.trace_context().unwrap();
+        assert_eq!(context.span_id, span.span_context.span_id());
+    }
+    assert!(emitted
+        .iter()
+        .any(|log| log.record.severity_number() == Some(Severity::Error)));
 }
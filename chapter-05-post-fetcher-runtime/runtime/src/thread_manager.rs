@@ -1,12 +1,177 @@
-/// A simple thread manager that schedules tasks in FIFO order.  In this
-/// example there is only one logical task per invocation, so the
-/// implementation is trivial.  In a more complex runtime this would
-/// coordinate multiple tasks and manage cooperative scheduling.
-pub struct ThreadManager;
+use std::cell::OnceCell;
+use std::future::Future;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+
+/// A simple thread manager that schedules tasks in FIFO order.  A
+/// single-request run has one task, which runs on the caller's thread.
+///
+/// When a per-host concurrency cap is configured, [`ThreadManager::plan`]
+/// splits a batch of host-tagged tasks into deterministic waves so no wave
+/// sends more than the cap to any one upstream.  With more than one worker,
+/// [`ThreadManager::spawn`] runs tasks on a pool of that many threads,
+/// started on first use and joined when the manager is dropped.
+#[derive(Debug, Default)]
+pub struct ThreadManager {
+    max_per_host: Option<usize>,
+    workers: usize,
+    pool: OnceCell<WorkerPool>,
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Threads taking jobs from a shared queue until it is closed.
+struct WorkerPool {
+    jobs: Option<Sender<Job>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl std::fmt::Debug for WorkerPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkerPool")
+            .field("threads", &self.threads.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl WorkerPool {
+    fn start(size: usize) -> Self {
+        let (jobs, queue) = channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        let threads = (0..size)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                std::thread::spawn(move || loop {
+                    // Hold the lock only while taking a job.
+                    let job = queue.lock().unwrap().recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                })
+            })
+            .collect();
+        Self {
+            jobs: Some(jobs),
+            threads,
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        drop(self.jobs.take());
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The result of a task given to [`ThreadManager::spawn`], resolved once a
+/// worker has run it.  A panic in the task resumes when the result is
+/// awaited.
+pub struct Spawned<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+struct Slot<T> {
+    result: Option<std::thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+impl<T> Future for Spawned<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut slot = self.slot.lock().unwrap();
+        match slot.result.take() {
+            Some(Ok(value)) => Poll::Ready(value),
+            Some(Err(panic)) => resume_unwind(panic),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A task that had to wait for an earlier wave because its host was at the
+/// concurrency cap.  The runtime records one `queued` event per entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedTask {
+    pub index: usize,
+    pub host: String,
+    pub wave: usize,
+}
+
+/// Execution plan for a batch: task indices grouped into waves, in FIFO
+/// order, plus the tasks that were deferred by the per-host cap.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schedule {
+    pub waves: Vec<Vec<usize>>,
+    pub queued: Vec<QueuedTask>,
+}
 
 impl ThreadManager {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Create a thread manager that caps concurrent tasks per host.  A cap of
+    /// zero is treated as one so every task is eventually scheduled.
+    pub fn with_max_per_host(max_per_host: Option<usize>) -> Self {
+        Self {
+            max_per_host: max_per_host.map(|cap| cap.max(1)),
+            ..Self::default()
+        }
+    }
+
+    /// Run spawned tasks on a pool of `workers` threads.  Zero or one runs
+    /// them on the calling thread as they are spawned.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Whether spawned tasks run on worker threads.
+    pub fn is_pooled(&self) -> bool {
+        self.workers > 1
+    }
+
+    /// Run `task` on a worker thread, or at once on this one without a
+    /// pool.  The tasks of one manager start in the order they were spawned.
+    pub fn spawn<T, F>(&self, task: F) -> Spawned<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let slot = Arc::new(Mutex::new(Slot {
+            result: None,
+            waker: None,
+        }));
+        let run = {
+            let slot = Arc::clone(&slot);
+            move || {
+                let result = catch_unwind(AssertUnwindSafe(task));
+                let mut slot = slot.lock().unwrap();
+                slot.result = Some(result);
+                if let Some(waker) = slot.waker.take() {
+                    waker.wake();
+                }
+            }
+        };
+        if self.is_pooled() {
+            let pool = self.pool.get_or_init(|| WorkerPool::start(self.workers));
+            let jobs = pool.jobs.as_ref().expect("the pool is open until dropped");
+            jobs.send(Box::new(run)).expect("workers outlive the pool");
+        } else {
+            run();
+        }
+        Spawned { slot }
     }
 
     /// Run a closure synchronously on the current thread.  Returns the
@@ -18,4 +183,46 @@ impl ThreadManager {
     {
         f()
     }
+
+    /// Plan the execution of tasks whose target hosts are given in submission
+    /// order.  Tasks are placed in the earliest wave where their host still
+    /// has capacity, so the plan depends only on the input order.
+    pub fn plan(&self, hosts: &[String]) -> Schedule {
+        let mut schedule = Schedule::default();
+        let mut per_wave: Vec<std::collections::HashMap<&str, usize>> = Vec::new();
+        for (index, host) in hosts.iter().enumerate() {
+            let wave = match self.max_per_host {
+                None => 0,
+                Some(cap) => per_wave
+                    .iter()
+                    .position(|counts| counts.get(host.as_str()).copied().unwrap_or(0) < cap)
+                    .unwrap_or(per_wave.len()),
+            };
+            if wave == per_wave.len() {
+                per_wave.push(std::collections::HashMap::new());
+                schedule.waves.push(Vec::new());
+            }
+            *per_wave[wave].entry(host.as_str()).or_insert(0) += 1;
+            schedule.waves[wave].push(index);
+            if wave > 0 {
+                schedule.queued.push(QueuedTask {
+                    index,
+                    host: host.clone(),
+                    wave,
+                });
+            }
+        }
+        schedule
+    }
+}
+
+/// Extract the host component of a URL for per-host scheduling.  Falls back
+/// to the whole string when the URL has no authority section.
+pub fn host_of(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    host.to_ascii_lowercase()
 }
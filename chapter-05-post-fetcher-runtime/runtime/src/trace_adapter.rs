@@ -0,0 +1,48 @@
+//! A wrapper adapter that propagates the run's trace.  [`TraceAdapter`]
+//! sets the `traceparent` header of every request it sends from the
+//! input's [`TraceContext`], replacing any the request set, so the servers
+//! a run reaches can join their spans to the caller's trace.
+//!
+//! The wrapper sits directly around the adapter, inside the authorization
+//! and middleware wrappers, so every retry attempt and cache revalidation
+//! carries the header while cache keys and header validation never see it.
+
+use service::api::{AdapterCapabilities, AsyncNetworkAdapter, FetchFuture, StreamFuture};
+use service::model::{Request, TraceContext};
+
+/// The header the trace is sent in.
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+pub struct TraceAdapter {
+    inner: Box<dyn AsyncNetworkAdapter>,
+    traceparent: String,
+}
+
+impl TraceAdapter {
+    pub fn new(inner: Box<dyn AsyncNetworkAdapter>, trace: &TraceContext) -> Self {
+        Self {
+            inner,
+            traceparent: trace.traceparent(),
+        }
+    }
+
+    fn traced(&self, request: &Request) -> Request {
+        request.with_header(TRACEPARENT_HEADER, &self.traceparent)
+    }
+}
+
+impl AsyncNetworkAdapter for TraceAdapter {
+    fn fetch<'a>(&'a self, request: &'a Request) -> FetchFuture<'a> {
+        let request = self.traced(request);
+        Box::pin(async move { self.inner.fetch(&request).await })
+    }
+
+    fn fetch_stream<'a>(&'a self, request: &'a Request) -> StreamFuture<'a> {
+        let request = self.traced(request);
+        Box::pin(async move { self.inner.fetch_stream(&request).await })
+    }
+
+    fn capabilities(&self) -> AdapterCapabilities {
+        self.inner.capabilities()
+    }
+}
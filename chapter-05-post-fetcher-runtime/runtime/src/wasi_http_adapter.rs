@@ -8,9 +8,9 @@
 #[cfg(target_arch = "wasm32")]
 use anyhow::{anyhow, Result};
 #[cfg(target_arch = "wasm32")]
-use service::api::{NetworkAdapter, NetworkResponse};
+use service::api::{AdapterCapabilities, NetworkAdapter, NetworkResponse};
 #[cfg(target_arch = "wasm32")]
-use std::collections::HashMap;
+use service::model::Request;
 
 /// The WASI HTTP adapter.  This adapter is only compiled on the
 /// `wasm32` architecture.  The current sample leaves outbound HTTP to
@@ -21,9 +21,19 @@ pub struct WasiHttpAdapter;
 
 #[cfg(target_arch = "wasm32")]
 impl NetworkAdapter for WasiHttpAdapter {
-    fn fetch(&self, _url: &str, _headers: &HashMap<String, String>) -> Result<NetworkResponse> {
+    fn fetch(&self, _request: &Request) -> Result<NetworkResponse> {
         Err(anyhow!(
             "wasi-http adapter is not implemented in this example; provide a host adapter instead"
         ))
     }
+
+    /// No methods at all, so the runtime rejects requests up front instead
+    /// of failing inside `fetch`.
+    fn capabilities(&self) -> AdapterCapabilities {
+        AdapterCapabilities {
+            methods: Vec::new(),
+            tls: false,
+            ..AdapterCapabilities::default()
+        }
+    }
 }
@@ -0,0 +1,43 @@
+// @generated by scripts/generate_schema_tests.py from scripts/schema_tests.json.
+// Do not edit: change the schema or the type, then rerun the script.
+
+use jsonschema::{Draft, JSONSchema};
+use serde_json::Value;
+
+/// The schema at `pointer` inside `source`, with the root's definitions in
+/// scope.  Draft 7 ignores keywords next to `$ref`, so the root's own
+/// constraints do not apply.
+fn compile(source: &str, pointer: &str) -> JSONSchema {
+    let mut schema: Value = serde_json::from_str(source).expect("schema is JSON");
+    if !pointer.is_empty() {
+        schema["$ref"] = Value::String(format!("#{pointer}"));
+    }
+    JSONSchema::options()
+        .with_draft(Draft::Draft7)
+        .compile(&schema)
+        .expect("schema compiles")
+}
+
+fn assert_valid(schema: &JSONSchema, document: &Value, what: &str) {
+    if let Err(errors) = schema.validate(document) {
+        let errors: Vec<String> = errors
+            .map(|e| format!("{}: {e}", e.instance_path))
+            .collect();
+        panic!("{what} does not match the schema: {errors:?}\n{document:#}");
+    }
+}
+
+#[test]
+fn lifecycle_metadata_serializes_to_the_schema() {
+    let schema = compile(include_str!("../../contracts/metadata.schema.json"), "");
+    let value = serde_json::to_value({
+        let input = serde_json::json!({
+            "request": { "url": "uma-fixture://sample-post", "headers": {} },
+            "runId": "schema-contract"
+        });
+        let (_, lifecycle) = uma_runtime::run_json(&input.to_string(), None).unwrap();
+        serde_json::from_str::<Value>(&lifecycle).unwrap()
+    })
+    .unwrap();
+    assert_valid(&schema, &value, "lifecycle_metadata");
+}
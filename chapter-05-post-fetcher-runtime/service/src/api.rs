@@ -1,35 +1,318 @@
+use crate::content_encoding::ContentDecoding;
+use crate::model::Request;
 use anyhow::Result;
+use serde::Serialize;
 use serde_json::Value;
+use std::future::Future;
+use std::io::Read;
+use std::pin::Pin;
+use std::sync::Arc;
 
 /// Trait representing a network capability.  The UMA runtime will provide an implementation
 /// of this trait at runtime, either via a `wasi-http` binding or a host‑provided fetch.
 pub trait NetworkAdapter {
-    fn fetch(
-        &self,
-        url: &str,
-        headers: &std::collections::HashMap<String, String>,
-    ) -> Result<NetworkResponse>;
+    /// Send `request`: its [`Request::method`] to its URL with its headers
+    /// and body.  The runtime only calls this after
+    /// [`AdapterCapabilities::check`] accepted the request.
+    fn fetch(&self, request: &Request) -> Result<NetworkResponse>;
+
+    /// Send `request` like [`NetworkAdapter::fetch`], returning once the
+    /// response's head has arrived, with a body read as it arrives.  The
+    /// default fetches the whole response and reads the body from memory;
+    /// adapters that declare [`AdapterCapabilities::streaming`] override it.
+    fn fetch_stream(&self, request: &Request) -> Result<StreamingResponse> {
+        self.fetch(request).map(StreamingResponse::from)
+    }
+
+    /// What this adapter can do, so requests it cannot serve are rejected
+    /// before the fetch.  Wrappers report the capabilities of the adapter
+    /// they wrap.
+    fn capabilities(&self) -> AdapterCapabilities {
+        AdapterCapabilities::default()
+    }
+
+    /// A handle to this adapter that other threads can call, for adapters
+    /// that are `Send` and `Sync`.  Batch runs send the fetches of such an
+    /// adapter from the runtime's worker threads, several at a time; other
+    /// adapters are called on the run's thread, one request after another.
+    fn thread_safe(&self) -> Option<Arc<dyn NetworkAdapter + Send + Sync>> {
+        None
+    }
+}
+
+/// The future an [`AsyncNetworkAdapter`] returns from `fetch`.
+pub type FetchFuture<'a> = Pin<Box<dyn Future<Output = Result<NetworkResponse>> + 'a>>;
+
+/// The future an [`AsyncNetworkAdapter`] returns from `fetch_stream`.
+pub type StreamFuture<'a> = Pin<Box<dyn Future<Output = Result<StreamingResponse>> + 'a>>;
+
+/// A network capability for hosts that run the runtime inside an async
+/// executor, where a blocking fetch would stall the executor's thread.  The
+/// contract is the one of [`NetworkAdapter`]; only `fetch` is asynchronous.
+pub trait AsyncNetworkAdapter {
+    /// Send `request`, resolving to the response once it has arrived.
+    fn fetch<'a>(&'a self, request: &'a Request) -> FetchFuture<'a>;
+
+    /// Send `request`, resolving to the response once its head has arrived;
+    /// see [`NetworkAdapter::fetch_stream`].  The default resolves once the
+    /// whole response has arrived.
+    fn fetch_stream<'a>(&'a self, request: &'a Request) -> StreamFuture<'a> {
+        Box::pin(async move { self.fetch(request).await.map(StreamingResponse::from) })
+    }
+
+    /// What this adapter can do; see [`NetworkAdapter::capabilities`].
+    fn capabilities(&self) -> AdapterCapabilities {
+        AdapterCapabilities::default()
+    }
+
+    /// The thread-safe blocking adapter this one calls, if any; see
+    /// [`NetworkAdapter::thread_safe`].  Async adapters leave concurrency to
+    /// their executor and return `None`.
+    fn thread_safe(&self) -> Option<Arc<dyn NetworkAdapter + Send + Sync>> {
+        None
+    }
+}
+
+/// Features a network adapter supports.  The default describes a plain GET
+/// client that accepts any URL scheme, which is what an adapter that does not
+/// declare its capabilities is assumed to be.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AdapterCapabilities {
+    /// HTTP methods the adapter can send, upper case.
+    pub methods: Vec<String>,
+    /// URL schemes the adapter can fetch; empty means any scheme.
+    pub schemes: Vec<String>,
+    /// Whether response bodies can be consumed incrementally.
+    pub streaming: bool,
+    /// Whether compressed responses are decoded transparently.
+    pub compression: bool,
+    /// Whether per-request timeouts can be configured.
+    pub timeouts: bool,
+    /// Whether TLS (`https`) is available.
+    pub tls: bool,
+}
+
+impl Default for AdapterCapabilities {
+    fn default() -> Self {
+        Self {
+            methods: vec!["GET".to_string()],
+            schemes: Vec::new(),
+            streaming: false,
+            compression: false,
+            timeouts: false,
+            tls: true,
+        }
+    }
+}
+
+/// A request feature an adapter lacks: the capability name (`method`,
+/// `scheme` or `tls`) and the value the request asked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedCapability {
+    pub capability: &'static str,
+    pub requested: String,
+}
+
+impl AdapterCapabilities {
+    /// The first feature of `request` these capabilities cannot serve,
+    /// checking the method, then the URL scheme, then TLS.
+    pub fn check(&self, request: &Request) -> Option<UnsupportedCapability> {
+        let method = request.method();
+        if !self.methods.contains(&method) {
+            return Some(UnsupportedCapability {
+                capability: "method",
+                requested: method,
+            });
+        }
+        let scheme = request
+            .url
+            .split_once(':')
+            .map(|(scheme, _)| scheme.to_ascii_lowercase())?;
+        if !self.schemes.is_empty() && !self.schemes.contains(&scheme) {
+            return Some(UnsupportedCapability {
+                capability: "scheme",
+                requested: scheme,
+            });
+        }
+        if scheme == "https" && !self.tls {
+            return Some(UnsupportedCapability {
+                capability: "tls",
+                requested: scheme,
+            });
+        }
+        None
+    }
+}
+
+/// A fetch stopped because it went over one of the contract's execution
+/// limits.  Adapters return it inside their error so the runtime reports
+/// `network.budget_exceeded` rather than a plain network failure.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{limit} of {max} exceeded")]
+pub struct BudgetExceeded {
+    /// The limit's name in the contract: `maxBodyBytes`, `maxRedirects` or
+    /// `timeoutMillis`.
+    pub limit: &'static str,
+    pub max: u64,
+}
+
+/// Optional wall-clock capability.  The runtime stays deterministic without
+/// one; hosts that provide it get wall-clock SLA measurements.
+pub trait Clock {
+    /// Milliseconds since an arbitrary, fixed origin.
+    fn now_millis(&self) -> u64;
 }
 
-/// Response returned by the network adapter.  The body is returned as a string to
-/// simplify JSON parsing; if the underlying implementation returns bytes, it should
-/// decode them as UTF‑8.
+/// Optional calendar-time capability.  Without one events carry only their
+/// logical `t`; with one each also gets a `wallTime`.  Closures returning
+/// the time are sources, so tests can inject a fixed one and stay
+/// reproducible.
+pub trait TimeSource {
+    /// Milliseconds since the Unix epoch.
+    fn unix_millis(&self) -> u64;
+}
+
+impl<F: Fn() -> u64> TimeSource for F {
+    fn unix_millis(&self) -> u64 {
+        self()
+    }
+}
+
+/// `unix_millis` as an RFC 3339 UTC timestamp with milliseconds, as
+/// `2024-05-01T12:30:00.250Z`.
+pub fn format_wall_time(unix_millis: u64) -> String {
+    let (days, millis) = (unix_millis / 86_400_000, unix_millis % 86_400_000);
+    // Civil date from days since 1970-01-01, after Howard Hinnant's
+    // `civil_from_days`, on an era of 400 years starting on March 1st.
+    let shifted = days + 719_468;
+    let era = shifted / 146_097;
+    let day_of_era = shifted % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1_000 % 60,
+        millis % 1_000
+    )
+}
+
+/// Waits between retry attempts.  The delay asked for is computed from the
+/// retry policy and recorded in the event log, so a provider that does not
+/// actually wait keeps runs deterministic.
+pub trait DelayProvider {
+    fn delay(&self, millis: u64);
+
+    /// Wait without blocking the thread, for async runs.  Defaults to
+    /// calling [`DelayProvider::delay`], which is right for providers that
+    /// return at once; providers that sleep should override it.
+    fn delay_async(&self, millis: u64) -> Pin<Box<dyn Future<Output = ()> + '_>> {
+        self.delay(millis);
+        Box::pin(std::future::ready(()))
+    }
+}
+
+/// Response returned by the network adapter.  The body is kept as the bytes
+/// received, so images or protobuf pass through intact; [`NetworkResponse::text`]
+/// reads it as UTF‑8 for the normalizers that parse text.  Adapters should
+/// undo any `Content-Encoding` with
+/// [`decode_response`](crate::content_encoding::decode_response) and record
+/// that in `decoded`.
+#[derive(Debug, Clone, Default)]
 pub struct NetworkResponse {
     pub status: u16,
     pub headers: std::collections::HashMap<String, String>,
-    pub body: String,
+    pub body: Vec<u8>,
+    pub decoded: Option<ContentDecoding>,
+}
+
+impl NetworkResponse {
+    /// The value of response header `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The body as UTF‑8 text, or an error if it is not valid UTF‑8.  Nothing
+    /// is replaced, so text that reads back is exactly what was received.
+    pub fn text(&self) -> std::result::Result<&str, std::str::Utf8Error> {
+        std::str::from_utf8(&self.body)
+    }
+}
+
+/// A response whose body is read as it arrives, from
+/// [`NetworkAdapter::fetch_stream`].  `decoded` is set when the adapter
+/// decoded the whole body before handing it over.
+pub struct StreamingResponse {
+    pub status: u16,
+    pub headers: std::collections::HashMap<String, String>,
+    pub body: Box<dyn Read>,
+    pub decoded: Option<ContentDecoding>,
+}
+
+impl StreamingResponse {
+    /// The value of response header `name`; see [`NetworkResponse::header`].
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The body in chunks of `chunk_bytes`, the last one possibly shorter.
+    /// Each chunk is filled before it is returned, so how the network
+    /// splits the body does not show.
+    pub fn chunks(self, chunk_bytes: usize) -> impl Iterator<Item = std::io::Result<Vec<u8>>> {
+        let mut body = self.body;
+        let mut done = false;
+        std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            let mut chunk = Vec::with_capacity(chunk_bytes);
+            match (&mut body)
+                .take(chunk_bytes.max(1) as u64)
+                .read_to_end(&mut chunk)
+            {
+                Ok(0) => None,
+                Ok(_) => Some(Ok(chunk)),
+                Err(err) => {
+                    done = true;
+                    Some(Err(err))
+                }
+            }
+        })
+    }
+}
+
+impl From<NetworkResponse> for StreamingResponse {
+    fn from(response: NetworkResponse) -> Self {
+        Self {
+            status: response.status,
+            headers: response.headers,
+            body: Box::new(std::io::Cursor::new(response.body)),
+            decoded: response.decoded,
+        }
+    }
 }
 
-/// Fetch a JSON document from the given URL using the provided network adapter and
-/// parse it into a JSON value.  Returns the status code and the parsed value on success.
-pub fn fetch_json<A: NetworkAdapter>(
-    adapter: &A,
-    url: &str,
-    headers: &std::collections::HashMap<String, String>,
-) -> Result<(u16, Value)> {
-    let resp = adapter.fetch(url, headers)?;
+/// Send `request` with the provided network adapter and parse the response
+/// body into a JSON value.  Returns the status code and the parsed value on success.
+pub fn fetch_json<A: NetworkAdapter>(adapter: &A, request: &Request) -> Result<(u16, Value)> {
+    let resp = adapter.fetch(request)?;
     let status = resp.status;
-    let body = resp.body;
-    let value: Value = serde_json::from_str(&body)?;
+    let value: Value = serde_json::from_str(resp.text()?)?;
     Ok((status, value))
 }
@@ -0,0 +1,97 @@
+//! Decoding of compressed response bodies.  Servers compress a body when the
+//! request's `Accept-Encoding` allows it and name the codings they applied in
+//! `Content-Encoding`; the host adapters undo them with [`decode_response`]
+//! before turning the body into text, so compressed JSON parses like any
+//! other.
+//!
+//! `gzip` (and its alias `x-gzip`), `deflate` and `br` are supported, as is
+//! `identity`, which needs nothing.  Codings are listed in the order they were
+//! applied, so they are undone from the last to the first.  A coding outside
+//! these fails the fetch rather than handing compressed bytes on as text.
+
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::io::Read;
+
+/// What a host adapter did to decode a response body: the `Content-Encoding`
+/// it undid and the body's size before and after.  The runtime records it as
+/// a `decompressed` event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentDecoding {
+    pub encoding: String,
+    pub encoded_bytes: u64,
+    pub decoded_bytes: u64,
+}
+
+/// Decode `body` as the `Content-Encoding` in `headers` says.  When it was
+/// encoded, the header and the now stale `Content-Length` are removed and the
+/// decoding is returned with the decoded body.  Decoding stops one byte past
+/// `max_bytes`, enough for the caller to tell the body is over its limit
+/// without expanding all of it.
+pub fn decode_response(
+    headers: &mut HashMap<String, String>,
+    body: Vec<u8>,
+    max_bytes: Option<u64>,
+) -> Result<(Vec<u8>, Option<ContentDecoding>)> {
+    let Some(encoding) = header(headers, "content-encoding") else {
+        return Ok((body, None));
+    };
+    let codings: Vec<String> = encoding
+        .split(',')
+        .map(|coding| coding.trim().to_ascii_lowercase())
+        .filter(|coding| !coding.is_empty() && coding != "identity")
+        .collect();
+    if codings.is_empty() {
+        return Ok((body, None));
+    }
+    let encoded_bytes = body.len() as u64;
+    let mut decoded = body;
+    for coding in codings.iter().rev() {
+        decoded = decode(coding, &decoded, max_bytes)?;
+    }
+    headers.retain(|key, _| {
+        !key.eq_ignore_ascii_case("content-encoding") && !key.eq_ignore_ascii_case("content-length")
+    });
+    let decoding = ContentDecoding {
+        encoding: codings.join(", "),
+        encoded_bytes,
+        decoded_bytes: decoded.len() as u64,
+    };
+    Ok((decoded, Some(decoding)))
+}
+
+/// Undo one `coding` of `body`.
+fn decode(coding: &str, body: &[u8], max_bytes: Option<u64>) -> Result<Vec<u8>> {
+    let mut reader: Box<dyn Read + '_> = match coding {
+        "gzip" | "x-gzip" => Box::new(flate2::read::MultiGzDecoder::new(body)),
+        // HTTP's deflate is zlib-wrapped, but some servers send the raw
+        // stream; a zlib header tells them apart.
+        "deflate" if has_zlib_header(body) => Box::new(flate2::read::ZlibDecoder::new(body)),
+        "deflate" => Box::new(flate2::read::DeflateDecoder::new(body)),
+        "br" => Box::new(brotli_decompressor::Decompressor::new(body, 4096)),
+        other => bail!("unsupported content-encoding {other}"),
+    };
+    let mut decoded = Vec::new();
+    match max_bytes {
+        Some(max) => reader.take(max.saturating_add(1)).read_to_end(&mut decoded),
+        None => reader.read_to_end(&mut decoded),
+    }
+    .map_err(|err| anyhow!("invalid {coding} body: {err}"))?;
+    Ok(decoded)
+}
+
+/// Whether `body` starts with a zlib header: the deflate method and a check
+/// value that makes the first two bytes a multiple of 31.
+fn has_zlib_header(body: &[u8]) -> bool {
+    match body {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+        _ => false,
+    }
+}
+
+fn header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
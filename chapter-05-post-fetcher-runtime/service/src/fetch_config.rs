@@ -0,0 +1,83 @@
+//! Connection settings for the host fetch adapters, for hosts that reach
+//! the network through a proxy or a TLS-inspecting gateway.  The settings
+//! are plain data, so the service stays free of an HTTP client:
+//! `HostFetchAdapter` in the runtime and the standalone `HostFetch` apply
+//! them to their reqwest clients.
+//!
+//! The defaults change nothing: no proxy, the system's CA certificates,
+//! the TLS library's minimum version and no connect timeout beyond the
+//! contract's `timeoutMillis`.
+
+use anyhow::{anyhow, bail, Result};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// How a host fetch adapter connects.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostFetchConfig {
+    /// The `http://` or `https://` URL of a proxy every request goes
+    /// through.  Credentials in the URL are sent to the proxy.
+    pub proxy: Option<String>,
+    /// A PEM file of CA certificates to trust besides the system's.
+    pub ca_cert_path: Option<PathBuf>,
+    /// The oldest TLS version a connection may use.
+    pub tls_min_version: Option<TlsVersion>,
+    /// How long establishing a connection may take.
+    pub connect_timeout_millis: Option<u64>,
+}
+
+impl HostFetchConfig {
+    /// The contents of the CA certificate file, if one is set.
+    pub fn ca_certificates(&self) -> Result<Option<Vec<u8>>> {
+        self.ca_cert_path
+            .as_ref()
+            .map(|path| {
+                std::fs::read(path).map_err(|err| {
+                    anyhow!("reading CA certificates from {}: {err}", path.display())
+                })
+            })
+            .transpose()
+    }
+}
+
+/// A TLS protocol version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    Tls1_0,
+    Tls1_1,
+    Tls1_2,
+    Tls1_3,
+}
+
+impl TlsVersion {
+    /// The version number, as `1.2`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TlsVersion::Tls1_0 => "1.0",
+            TlsVersion::Tls1_1 => "1.1",
+            TlsVersion::Tls1_2 => "1.2",
+            TlsVersion::Tls1_3 => "1.3",
+        }
+    }
+}
+
+impl FromStr for TlsVersion {
+    type Err = anyhow::Error;
+
+    /// Parse a version number, `1.2`, optionally prefixed by `TLS`, as in
+    /// `TLSv1.2`.
+    fn from_str(value: &str) -> Result<Self> {
+        let lower = value.trim().to_ascii_lowercase();
+        let number = lower
+            .strip_prefix("tlsv")
+            .or_else(|| lower.strip_prefix("tls"))
+            .unwrap_or(&lower);
+        Ok(match number {
+            "1.0" => TlsVersion::Tls1_0,
+            "1.1" => TlsVersion::Tls1_1,
+            "1.2" => TlsVersion::Tls1_2,
+            "1.3" => TlsVersion::Tls1_3,
+            _ => bail!("unknown TLS version {value:?}"),
+        })
+    }
+}
@@ -4,9 +4,13 @@
 //! persistence around these pure functions.
 
 pub mod api;
+pub mod content_encoding;
+pub mod fetch_config;
+pub mod messages;
 pub mod model;
+pub mod normalizer;
 
-use model::Post;
+use model::{Comment, Event, Post, SlaReport, SlaStatus, SlaTarget, Summary, User};
 use serde_json::Value;
 
 /// Normalise a JSONPlaceholder post into a canonical shape.  The input must be
@@ -25,6 +29,81 @@ pub fn normalize_post(json: &Value) -> Option<Post> {
     })
 }
 
+/// Normalise a JSONPlaceholder post collection.  Array elements that are not
+/// posts are left out; anything but an array gives no posts.
+pub fn normalize_posts(json: &Value) -> Vec<Post> {
+    normalize_each(json, normalize_post)
+}
+
+/// Normalise a JSONPlaceholder comment.  The input must be a JSON object with
+/// `id`, `postId`, `name`, `email` and `body` fields.  Returns `None` if any
+/// of them are missing or have the wrong type.
+pub fn normalize_comment(json: &Value) -> Option<Comment> {
+    Some(Comment {
+        id: json.get("id")?.as_u64()?,
+        post_id: json.get("postId")?.as_u64()?,
+        name: json.get("name")?.as_str()?.to_string(),
+        email: json.get("email")?.as_str()?.to_string(),
+        body: json.get("body")?.as_str()?.to_string(),
+    })
+}
+
+/// Normalise a JSONPlaceholder comment collection, like [`normalize_posts`].
+pub fn normalize_comments(json: &Value) -> Vec<Comment> {
+    normalize_each(json, normalize_comment)
+}
+
+/// Normalise a JSONPlaceholder user.  The input must be a JSON object with
+/// `id`, `name`, `username` and `email` fields; other fields are ignored.
+/// Returns `None` if any of them are missing or have the wrong type.
+pub fn normalize_user(json: &Value) -> Option<User> {
+    Some(User {
+        id: json.get("id")?.as_u64()?,
+        name: json.get("name")?.as_str()?.to_string(),
+        username: json.get("username")?.as_str()?.to_string(),
+        email: json.get("email")?.as_str()?.to_string(),
+    })
+}
+
+/// Normalise a JSONPlaceholder user collection, like [`normalize_posts`].
+pub fn normalize_users(json: &Value) -> Vec<User> {
+    normalize_each(json, normalize_user)
+}
+
+fn normalize_each<T>(json: &Value, normalize: fn(&Value) -> Option<T>) -> Vec<T> {
+    json.as_array()
+        .map(|items| items.iter().filter_map(normalize).collect())
+        .unwrap_or_default()
+}
+
+/// Compute a stable content hash for a normalised post.  The canonical form
+/// covers `id`, `userId`, `title` and `body` in that order, each prefixed with
+/// its byte length so field boundaries cannot collide, and is hashed with
+/// 64-bit FNV-1a.  The value is identical across platforms and runs.
+pub fn content_hash(post: &Post) -> u64 {
+    let id = post.id.to_string();
+    let user_id = post.user_id.to_string();
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for field in [id.as_str(), user_id.as_str(), &post.title, &post.body] {
+        let len = field.len().to_string();
+        for byte in len.bytes().chain([b':']).chain(field.bytes()) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// Drop posts whose [`content_hash`] has already been seen, keeping the first
+/// occurrence and the original order.  Used to merge paginated fetches.
+pub fn dedupe_posts(posts: Vec<Post>) -> Vec<Post> {
+    let mut seen = std::collections::HashSet::new();
+    posts
+        .into_iter()
+        .filter(|post| seen.insert(content_hash(post)))
+        .collect()
+}
+
 /// Pure helper to extract an error value for error events.  Returns a string
 /// describing the error given a status code or a parsing failure.
 pub fn error_message(status: Option<u16>, parse_error: Option<&serde_json::Error>) -> String {
@@ -37,5 +116,56 @@ pub fn error_message(status: Option<u16>, parse_error: Option<&serde_json::Error
     }
 }
 
+/// Derive the request statistics of a run from its event log.  Each event
+/// advances the logical clock by one tick, so the duration equals the number
+/// of events.  Cache hits and retries are not visible in the log and are left
+/// at zero for the runtime to fill in from its adapter counters.
+pub fn summarize_events(events: &[Event]) -> Summary {
+    let mut summary = Summary {
+        duration_ticks: events.len() as u64,
+        ..Summary::default()
+    };
+    for event in events {
+        match event.type_.as_str() {
+            "fetch_request" => summary.total_requests += 1,
+            "fetch_response" => {
+                let status = event
+                    .data
+                    .get("status")
+                    .and_then(Value::as_u64)
+                    .unwrap_or(0);
+                if (200..300).contains(&status) {
+                    summary.successes += 1;
+                } else {
+                    *summary
+                        .failures_by_code
+                        .entry(status.to_string())
+                        .or_insert(0) += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    summary
+}
+
+/// Classify a run against an SLA target.  A bound is violated when the
+/// measurement exceeds it; a wall-clock bound with no measurement is skipped.
+pub fn classify_sla(target: SlaTarget, ticks: u64, wall_millis: Option<u64>) -> SlaReport {
+    let over_ticks = target.max_ticks.is_some_and(|max| ticks > max);
+    let over_wall =
+        matches!((target.max_wall_millis, wall_millis), (Some(max), Some(ms)) if ms > max);
+    SlaReport {
+        status: if over_ticks || over_wall {
+            SlaStatus::Violated
+        } else {
+            SlaStatus::Within
+        },
+        target,
+        ticks,
+        wall_millis,
+    }
+}
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,283 @@
+//! Stable error codes and a locale-aware message catalog.  Error events carry
+//! a machine-readable `code` plus the `params` needed to render it, so hosts
+//! can present messages in the reader's locale without parsing the English
+//! text that is kept in the event log for compatibility.
+
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// Locale used when a requested locale or code has no template.
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Machine-readable error codes emitted in `error` events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// A request header outside the allowlist.  Params: `header`.
+    UnexpectedHeader,
+    /// A request header value over the length limit.  Params: `header`.
+    HeaderTooLong,
+    /// A `content-type` the runtime does not submit.  Params: `header`,
+    /// `value`.
+    UnsupportedContentType,
+    /// A header the contract requires is not in the request.  Params:
+    /// `header`.
+    MissingHeader,
+    /// The response body was not valid JSON.  Params: `status`, `detail`.
+    ParseError,
+    /// The response JSON did not have the shape of a post.  Params: `status`.
+    InvalidPost,
+    /// The response JSON did not have the shape of the input's `resource`,
+    /// other than a single post.  Params: `status`, `resource`.
+    InvalidResource,
+    /// The adapter failed before producing a response.  Params: `detail`.
+    NetworkFailure,
+    /// The fetch went over an execution limit of the contract.  Params:
+    /// `limit`, `max`.
+    BudgetExceeded,
+    /// The request's host had failed repeatedly, so its circuit is open and
+    /// the request was not sent.  Params: `host`.
+    CircuitOpen,
+    /// The bound adapter cannot serve the request.  Params: `adapter`,
+    /// `capability`, `requested`.
+    UnsupportedCapability,
+    /// The contract binds the fetch to an adapter name the runtime has not
+    /// registered.  Params: `adapter`.
+    UnknownAdapter,
+    /// The input's `resource` names no registered normalizer.  Params:
+    /// `normalizer`.
+    UnknownNormalizer,
+    /// The request URL's host is not in the contract's `allowedHosts`.
+    /// Params: `host`.
+    HostNotAllowed,
+    /// The request URL's scheme is not in the contract's `allowedSchemes`.
+    /// Params: `scheme`.
+    SchemeNotAllowed,
+    /// Strict mode found input fields the contract does not declare.
+    /// Params: `fields`, their dotted paths.
+    ValidationError,
+    /// Strict mode found an event whose data does not match the schema the
+    /// contract declares for its type.  Params: `event`, the type.
+    SchemaViolation,
+}
+
+impl ErrorCode {
+    /// Every code, in declaration order.
+    pub const ALL: [ErrorCode; 17] = [
+        ErrorCode::UnexpectedHeader,
+        ErrorCode::HeaderTooLong,
+        ErrorCode::UnsupportedContentType,
+        ErrorCode::MissingHeader,
+        ErrorCode::ParseError,
+        ErrorCode::InvalidPost,
+        ErrorCode::InvalidResource,
+        ErrorCode::NetworkFailure,
+        ErrorCode::BudgetExceeded,
+        ErrorCode::CircuitOpen,
+        ErrorCode::UnsupportedCapability,
+        ErrorCode::UnknownAdapter,
+        ErrorCode::UnknownNormalizer,
+        ErrorCode::HostNotAllowed,
+        ErrorCode::SchemeNotAllowed,
+        ErrorCode::ValidationError,
+        ErrorCode::SchemaViolation,
+    ];
+
+    /// The stable string form used in events and catalogs.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedHeader => "header.unexpected",
+            ErrorCode::HeaderTooLong => "header.too_long",
+            ErrorCode::UnsupportedContentType => "header.unsupported_content_type",
+            ErrorCode::MissingHeader => "header.missing",
+            ErrorCode::ParseError => "response.parse_error",
+            ErrorCode::InvalidPost => "response.invalid_post",
+            ErrorCode::InvalidResource => "response.invalid_resource",
+            ErrorCode::NetworkFailure => "network.failure",
+            ErrorCode::BudgetExceeded => "network.budget_exceeded",
+            ErrorCode::CircuitOpen => "network.circuit_open",
+            ErrorCode::UnsupportedCapability => "adapter.unsupported_capability",
+            ErrorCode::UnknownAdapter => "adapter.unknown",
+            ErrorCode::UnknownNormalizer => "normalizer.unknown",
+            ErrorCode::HostNotAllowed => "request.host_not_allowed",
+            ErrorCode::SchemeNotAllowed => "request.scheme_not_allowed",
+            ErrorCode::ValidationError => "input.validation_error",
+            ErrorCode::SchemaViolation => "event.schema_violation",
+        }
+    }
+}
+
+/// Message templates keyed by locale and error code.  Templates reference
+/// params as `{name}`; unknown placeholders are left untouched.
+#[derive(Debug, Clone, Default)]
+pub struct MessageCatalog {
+    templates: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl MessageCatalog {
+    /// An empty catalog.  Formatting falls back to the bare code.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The catalog shipped with the service: English, which matches the text
+    /// recorded in the event log, plus Spanish.
+    pub fn builtin() -> Self {
+        let mut catalog = Self::new();
+        for (code, en, es) in [
+            (
+                ErrorCode::UnexpectedHeader,
+                "unexpected header {header}",
+                "cabecera inesperada {header}",
+            ),
+            (
+                ErrorCode::HeaderTooLong,
+                "header {header} too long",
+                "la cabecera {header} es demasiado larga",
+            ),
+            (
+                ErrorCode::UnsupportedContentType,
+                "unsupported {header} {value}",
+                "{header} no admitido {value}",
+            ),
+            (
+                ErrorCode::MissingHeader,
+                "missing header {header}",
+                "falta la cabecera {header}",
+            ),
+            (
+                ErrorCode::ParseError,
+                "parse error: {detail}",
+                "error de análisis: {detail}",
+            ),
+            (ErrorCode::InvalidPost, "status {status}", "estado {status}"),
+            (
+                ErrorCode::InvalidResource,
+                "status {status}, expected {resource}",
+                "estado {status}, se esperaba {resource}",
+            ),
+            (
+                ErrorCode::NetworkFailure,
+                "{detail}",
+                "fallo de red: {detail}",
+            ),
+            (
+                ErrorCode::BudgetExceeded,
+                "{limit} of {max} exceeded",
+                "se superó {limit} de {max}",
+            ),
+            (
+                ErrorCode::CircuitOpen,
+                "circuit for {host} is open",
+                "el circuito de {host} está abierto",
+            ),
+            (
+                ErrorCode::UnsupportedCapability,
+                "adapter {adapter} does not support {capability} {requested}",
+                "el adaptador {adapter} no admite {capability} {requested}",
+            ),
+            (
+                ErrorCode::UnknownAdapter,
+                "no adapter is registered as {adapter}",
+                "no hay ningún adaptador registrado como {adapter}",
+            ),
+            (
+                ErrorCode::UnknownNormalizer,
+                "no normalizer is registered as {normalizer}",
+                "no hay ningún normalizador registrado como {normalizer}",
+            ),
+            (
+                ErrorCode::HostNotAllowed,
+                "host {host} is not allowed",
+                "el host {host} no está permitido",
+            ),
+            (
+                ErrorCode::SchemeNotAllowed,
+                "scheme {scheme} is not allowed",
+                "el esquema {scheme} no está permitido",
+            ),
+            (
+                ErrorCode::ValidationError,
+                "unexpected input fields {fields}",
+                "campos de entrada inesperados {fields}",
+            ),
+            (
+                ErrorCode::SchemaViolation,
+                "event {event} does not match its schema",
+                "el evento {event} no cumple su esquema",
+            ),
+        ] {
+            catalog.insert("en", code.as_str(), en);
+            catalog.insert("es", code.as_str(), es);
+        }
+        catalog
+    }
+
+    /// Add or replace the template for `code` in `locale`.
+    pub fn insert(&mut self, locale: &str, code: &str, template: &str) {
+        self.templates
+            .entry(locale.to_string())
+            .or_default()
+            .insert(code.to_string(), template.to_string());
+    }
+
+    /// Render `code` for `locale`.  Lookup tries the exact locale, then its
+    /// language (`es-MX` → `es`), then [`DEFAULT_LOCALE`]; if none has a
+    /// template the code itself is returned.
+    pub fn format(&self, locale: &str, code: &str, params: &Value) -> String {
+        let language = locale.split(['-', '_']).next().unwrap_or(locale);
+        let template = [locale, language, DEFAULT_LOCALE]
+            .iter()
+            .find_map(|candidate| self.templates.get(*candidate)?.get(code));
+        match template {
+            Some(template) => render(template, params),
+            None => code.to_string(),
+        }
+    }
+
+    /// Render the `code` and `params` of an `error` event's data for `locale`.
+    pub fn format_event(&self, locale: &str, data: &Value) -> Option<String> {
+        let code = data.get("code")?.as_str()?;
+        let params = data.get("params").cloned().unwrap_or(Value::Null);
+        Some(self.format(locale, code, &params))
+    }
+}
+
+/// Substitute `{name}` placeholders with values from `params`.
+fn render(template: &str, params: &Value) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after
+            .find('}')
+            .and_then(|end| Some((end, params.get(&after[..end])?)));
+        match value {
+            Some((end, Value::String(s))) => {
+                out.push_str(s);
+                rest = &after[end + 1..];
+            }
+            Some((end, other)) => {
+                out.push_str(&other.to_string());
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Build the data of an `error` event: the English message under `error`
+/// plus the stable `code` and the `params` used to render it.
+pub fn error_event_data(code: ErrorCode, params: Value) -> Value {
+    let message = MessageCatalog::builtin().format(DEFAULT_LOCALE, code.as_str(), &params);
+    json!({
+        "error": message,
+        "code": code.as_str(),
+        "params": params,
+    })
+}
@@ -1,23 +1,278 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
 
-/// Represents the JSON structure of the incoming request.
+/// Represents the JSON structure of the incoming request.  A run fetches
+/// either one `request` or, in a batch run, every one of `requests` in order;
+/// the document must give exactly one of the two, and `requests` must not be
+/// empty.
 #[derive(Debug, Deserialize)]
+#[serde(try_from = "InputDocument")]
 pub struct Input {
-    pub request: Request,
-    #[serde(rename = "runId")]
+    /// The request of a single-fetch run; `None` in a batch run.
+    pub request: Option<Request>,
+    /// The requests of a batch run; empty in a single-fetch run.
+    pub requests: Vec<Request>,
     pub run_id: String,
+    /// `strict` asks the runtime to reject fields the contract does not
+    /// declare.  A strict contract stays strict whatever the input says.
+    pub input_mode: InputMode,
+    /// The normalizer every response of the run goes through, by name;
+    /// `None` picks one per response by its content type.
+    pub resource: Option<String>,
+    /// The distributed trace the run belongs to, if the caller has one.
+    pub trace: Option<TraceContext>,
 }
 
-/// HTTP request parameters (currently only URL and optional headers).
-#[derive(Debug, Deserialize)]
+impl Input {
+    /// A permissive single-fetch run of `request`.
+    pub fn new(request: Request, run_id: &str) -> Self {
+        Self {
+            request: Some(request),
+            requests: Vec::new(),
+            run_id: run_id.to_string(),
+            input_mode: InputMode::default(),
+            resource: None,
+            trace: None,
+        }
+    }
+
+    /// A permissive batch run fetching `requests` in order.
+    pub fn batch(requests: Vec<Request>, run_id: &str) -> Self {
+        Self {
+            request: None,
+            requests,
+            run_id: run_id.to_string(),
+            input_mode: InputMode::default(),
+            resource: None,
+            trace: None,
+        }
+    }
+
+    /// The same run, normalizing every response with the normalizer
+    /// registered as `resource`, such as a [`Resource`] name.
+    pub fn with_resource(mut self, resource: &str) -> Self {
+        self.resource = Some(resource.to_string());
+        self
+    }
+
+    /// The same run, as part of the trace `trace`.
+    pub fn with_trace(mut self, trace: TraceContext) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    /// Whether this is a batch run, given `requests` rather than `request`.
+    pub fn is_batch(&self) -> bool {
+        self.request.is_none()
+    }
+
+    /// The requests to fetch, in order.
+    pub fn all_requests(&self) -> impl Iterator<Item = &Request> {
+        self.request.iter().chain(&self.requests)
+    }
+}
+
+/// An input document as written, checked into an [`Input`].
+#[derive(Deserialize)]
+struct InputDocument {
+    request: Option<Request>,
+    requests: Option<Vec<Request>>,
+    #[serde(rename = "runId")]
+    run_id: String,
+    #[serde(rename = "inputMode", default)]
+    input_mode: InputMode,
+    #[serde(default)]
+    resource: Option<String>,
+    #[serde(rename = "traceId", default)]
+    trace_id: Option<String>,
+    #[serde(rename = "spanId", default)]
+    span_id: Option<String>,
+}
+
+impl TryFrom<InputDocument> for Input {
+    type Error = String;
+
+    fn try_from(document: InputDocument) -> Result<Self, Self::Error> {
+        let requests = match (&document.request, document.requests) {
+            (Some(_), Some(_)) => return Err("`request` and `requests` are exclusive".into()),
+            (None, None) => return Err("missing field `request`".into()),
+            (None, Some(requests)) if requests.is_empty() => {
+                return Err("`requests` must not be empty".into())
+            }
+            (_, requests) => requests.unwrap_or_default(),
+        };
+        let trace = match (document.trace_id, document.span_id) {
+            (Some(trace_id), Some(span_id)) => Some(TraceContext::new(&trace_id, &span_id)?),
+            (None, None) => None,
+            _ => return Err("`traceId` and `spanId` must be given together".into()),
+        };
+        Ok(Self {
+            request: document.request,
+            requests,
+            run_id: document.run_id,
+            input_mode: document.input_mode,
+            resource: document.resource,
+            trace,
+        })
+    }
+}
+
+/// A W3C Trace Context position: the trace a run belongs to and the span
+/// that started it.  The runtime sends it on every fetch as a
+/// `traceparent` header and stamps it on every event and the lifecycle
+/// record, where it serializes as `traceId` and `spanId`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TraceContext {
+    #[serde(rename = "traceId")]
+    trace_id: String,
+    #[serde(rename = "spanId")]
+    span_id: String,
+}
+
+impl TraceContext {
+    /// A position from a 32-digit trace id and a 16-digit span id, in
+    /// lowercase hex and not all zeros, as the W3C format requires.
+    pub fn new(trace_id: &str, span_id: &str) -> Result<Self, String> {
+        let valid = |id: &str, digits: usize| {
+            id.len() == digits
+                && id
+                    .bytes()
+                    .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+                && id.bytes().any(|b| b != b'0')
+        };
+        if !valid(trace_id, 32) {
+            return Err(format!(
+                "`traceId` must be 32 lowercase hex digits, not all zero: {trace_id:?}"
+            ));
+        }
+        if !valid(span_id, 16) {
+            return Err(format!(
+                "`spanId` must be 16 lowercase hex digits, not all zero: {span_id:?}"
+            ));
+        }
+        Ok(Self {
+            trace_id: trace_id.to_string(),
+            span_id: span_id.to_string(),
+        })
+    }
+
+    pub fn trace_id(&self) -> &str {
+        &self.trace_id
+    }
+
+    pub fn span_id(&self) -> &str {
+        &self.span_id
+    }
+
+    /// The `traceparent` header value: version `00`, the ids and the
+    /// sampled flag.
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-01", self.trace_id, self.span_id)
+    }
+}
+
+/// How the runtime treats input fields the service contract does not declare.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputMode {
+    /// Undeclared fields are ignored.
+    #[default]
+    Permissive,
+    /// Undeclared fields fail the run with an `input.validation_error`.
+    Strict,
+}
+
+/// A JSONPlaceholder resource a response can hold, each with its own
+/// [`crate::normalizer::ResourceNormalizer`]: one object, or an array of
+/// them for the plural names.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Resource {
+    #[default]
+    Post,
+    Posts,
+    Comment,
+    Comments,
+    User,
+    Users,
+}
+
+impl Resource {
+    /// The name of its normalizer, used in the input and in events.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Resource::Post => "post",
+            Resource::Posts => "posts",
+            Resource::Comment => "comment",
+            Resource::Comments => "comments",
+            Resource::User => "user",
+            Resource::Users => "users",
+        }
+    }
+
+    /// Whether the response is an array of the resource.
+    pub fn is_collection(self) -> bool {
+        matches!(self, Resource::Posts | Resource::Comments | Resource::Users)
+    }
+}
+
+/// HTTP request parameters: URL, optional headers, method and body.  This
+/// is also what the runtime hands to the bound [`crate::api::NetworkAdapter`].
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct Request {
     pub url: String,
     #[serde(default)]
     pub headers: std::collections::HashMap<String, String>,
+    /// HTTP method, `GET` when absent.  Methods the bound adapter does not
+    /// list in its capabilities are rejected before the fetch.
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Request payload bytes.  In the input a string is sent as its UTF-8
+    /// bytes and any other JSON value as its serialized JSON, so a JSON
+    /// payload can be written inline.
+    #[serde(default, deserialize_with = "payload")]
+    pub body: Option<Vec<u8>>,
+}
+
+/// Deserialize a request `body`: `null` is no body, a string is taken as
+/// text and any other value is serialized as JSON.
+fn payload<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(text) => Some(text.into_bytes()),
+        value => Some(value.to_string().into_bytes()),
+    })
+}
+
+impl Request {
+    /// The requested method, upper case, `GET` when none was given.
+    pub fn method(&self) -> String {
+        self.method.as_deref().unwrap_or("GET").to_ascii_uppercase()
+    }
+
+    /// Whether repeating the request has the same effect as sending it once
+    /// (RFC 9110): true for `GET`, `HEAD`, `PUT` and `DELETE`.  Retry and
+    /// cache wrappers only act on these.
+    pub fn is_idempotent(&self) -> bool {
+        matches!(self.method().as_str(), "GET" | "HEAD" | "PUT" | "DELETE")
+    }
+
+    /// A copy of this request with header `name` set to `value`, replacing
+    /// any header of that name whatever its case.  Adapter wrappers use it
+    /// to add headers, such as conditional ones, on the way to the adapter
+    /// they wrap.
+    pub fn with_header(&self, name: &str, value: &str) -> Request {
+        let mut request = self.clone();
+        request
+            .headers
+            .retain(|existing, _| !existing.eq_ignore_ascii_case(name));
+        request.headers.insert(name.to_string(), value.to_string());
+        request
+    }
 }
 
 /// Canonical representation of a Post from JSONPlaceholder.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Post {
     pub id: u64,
     pub user_id: u64,
@@ -25,19 +280,127 @@ pub struct Post {
     pub body: String,
 }
 
+/// Canonical representation of a Comment from JSONPlaceholder.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Comment {
+    pub id: u64,
+    pub post_id: u64,
+    pub name: String,
+    pub email: String,
+    pub body: String,
+}
+
+/// Canonical representation of a User from JSONPlaceholder: the identity
+/// fields, without the nested address and company.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct User {
+    pub id: u64,
+    pub name: String,
+    pub username: String,
+    pub email: String,
+}
+
 /// An event in the deterministic event log.
 #[derive(Debug, Clone, Serialize)]
 pub struct Event {
     pub t: String,
+    /// When the event was emitted, as an RFC 3339 UTC timestamp.  Absent
+    /// unless the host provides a [`crate::api::TimeSource`].
+    #[serde(rename = "wallTime", skip_serializing_if = "Option::is_none")]
+    pub wall_time: Option<String>,
+    /// The run's trace, as `traceId` and `spanId`.  Absent unless the input
+    /// gives one.
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<TraceContext>,
     #[serde(rename = "type")]
     pub type_: String,
     pub data: serde_json::Value,
+    /// Attributes attached by the host's event decorator, such as a
+    /// deployment id or region.  Absent when no decorator is registered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Aggregate statistics for a run so consumers do not have to re-derive them
+/// by scanning the event log.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Summary {
+    /// Number of `fetch_request` events.
+    #[serde(rename = "totalRequests")]
+    pub total_requests: u64,
+    /// Responses with a 2xx status.
+    pub successes: u64,
+    /// Non-2xx responses keyed by status code; `"0"` counts transport errors.
+    #[serde(rename = "failuresByCode")]
+    pub failures_by_code: BTreeMap<String, u64>,
+    /// Responses served from the cache wrapper without reaching the adapter.
+    #[serde(rename = "cacheHits")]
+    pub cache_hits: u64,
+    /// Additional attempts made by the retry wrapper.
+    pub retries: u64,
+    /// Run duration measured in logical clock ticks.
+    #[serde(rename = "durationTicks")]
+    pub duration_ticks: u64,
+    /// Posts dropped by [`crate::dedupe_posts`] because their content hash
+    /// was already seen in the same run.
+    pub deduplicated: u64,
+    /// Classification against the contract's SLA target, when it declares one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sla: Option<SlaReport>,
+}
+
+/// Completion target declared under `sla` in the service contract.  Either
+/// bound may be omitted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SlaTarget {
+    #[serde(rename = "maxTicks", default, skip_serializing_if = "Option::is_none")]
+    pub max_ticks: Option<u64>,
+    #[serde(
+        rename = "maxWallMillis",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_wall_millis: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlaStatus {
+    Within,
+    Violated,
+}
+
+/// How one run measured up against an [`SlaTarget`].  `wall_millis` is only
+/// present when the host supplied a clock; without one the wall-clock bound
+/// cannot be checked and only ticks decide the status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SlaReport {
+    pub status: SlaStatus,
+    #[serde(flatten)]
+    pub target: SlaTarget,
+    pub ticks: u64,
+    #[serde(rename = "wallMillis", skip_serializing_if = "Option::is_none")]
+    pub wall_millis: Option<u64>,
 }
 
-/// Output returned by the service.  Either `normalized_post` or `null`, plus the event log.
+/// Output returned by the service.  Either `normalized_post` or `null`, plus the event log
+/// and a summary of the run.  Batch runs and runs of a post collection list
+/// their posts in `normalized_posts` instead and leave `normalized_post`
+/// empty, and runs of comments or users list them in their own field.
 #[derive(Debug, Serialize)]
 pub struct Output {
     #[serde(rename = "normalizedPost")]
     pub normalized_post: Option<Post>,
+    /// The posts of a batch run's requests in request order, leaving out
+    /// requests that failed.  `None` outside batch runs and runs of `posts`.
+    #[serde(rename = "normalizedPosts", skip_serializing_if = "Option::is_none")]
+    pub normalized_posts: Option<Vec<Post>>,
+    /// The comments of a run of `comment` or `comments`, in request order.
+    #[serde(rename = "normalizedComments", skip_serializing_if = "Option::is_none")]
+    pub normalized_comments: Option<Vec<Comment>>,
+    /// The users of a run of `user` or `users`, in request order.
+    #[serde(rename = "normalizedUsers", skip_serializing_if = "Option::is_none")]
+    pub normalized_users: Option<Vec<User>>,
     pub events: Vec<Event>,
+    pub summary: Summary,
 }
@@ -0,0 +1,370 @@
+//! Normalizers turn a response body into canonical records.  A
+//! [`NormalizerRegistry`] holds them by name: the input's `resource` names
+//! the one every response of a run goes through, and without it each
+//! response is dispatched by its `content-type`, falling back to
+//! [`DEFAULT_NORMALIZER`].
+//!
+//! [`NormalizerRegistry::builtin`] registers one [`ResourceNormalizer`] per
+//! JSONPlaceholder [`Resource`], under the resource's name, and
+//! [`RssItemNormalizer`] as `rss-item`.  JSON bodies go to `post` and RSS
+//! feeds to `rss-item`.
+
+use crate::model::{Comment, Post, Resource, User};
+use crate::{
+    normalize_comment, normalize_comments, normalize_post, normalize_posts, normalize_user,
+    normalize_users,
+};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// The normalizer used when the input names no resource and the response's
+/// content type has no normalizer of its own.
+pub const DEFAULT_NORMALIZER: &str = "post";
+
+/// The kind of record a normalizer produces, which decides the output field
+/// its records are listed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RecordKind {
+    Post,
+    Comment,
+    User,
+}
+
+/// The records normalized from one response, with the data of the
+/// `normalized` event the runtime records for it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Normalized {
+    pub posts: Vec<Post>,
+    pub comments: Vec<Comment>,
+    pub users: Vec<User>,
+    pub details: Value,
+}
+
+impl Normalized {
+    /// Append the records of `other`, keeping these details.
+    pub fn extend(&mut self, other: Normalized) {
+        self.posts.extend(other.posts);
+        self.comments.extend(other.comments);
+        self.users.extend(other.users);
+    }
+
+    /// How many records there are of every kind.
+    pub fn len(&self) -> usize {
+        self.posts.len() + self.comments.len() + self.users.len()
+    }
+
+    /// Whether there are no records.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Why a body could not be normalized.  The runtime reports [`Parse`] as
+/// `response.parse_error` and [`Shape`] as `response.invalid_post` for the
+/// `post` normalizer and `response.invalid_resource` for the others.
+///
+/// [`Parse`]: NormalizeError::Parse
+/// [`Shape`]: NormalizeError::Shape
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum NormalizeError {
+    /// The body is not in the normalizer's format.
+    #[error("parse error: {0}")]
+    Parse(String),
+    /// The body parsed but does not hold the expected records.
+    #[error("the body does not have the expected shape")]
+    Shape,
+}
+
+/// Turns a response body into records of one kind.
+pub trait Normalizer {
+    /// The kind of the records [`Normalizer::normalize`] returns.
+    fn kind(&self) -> RecordKind;
+
+    /// Normalize `body`, the bytes of the response.  Normalizers of text
+    /// formats read it with [`text`].
+    fn normalize(&self, body: &[u8]) -> Result<Normalized, NormalizeError>;
+
+    /// Whether bodies that are JSON arrays can be normalized one element at
+    /// a time with [`Normalizer::normalize_element`], so a streamed body
+    /// need not be held whole.  False by default.
+    fn normalizes_elements(&self) -> bool {
+        false
+    }
+
+    /// The records of one `element` of a JSON array body, none for an
+    /// element that is skipped.  Only called when
+    /// [`Normalizer::normalizes_elements`] is true.
+    fn normalize_element(&self, element: &Value) -> Normalized {
+        let _ = element;
+        Normalized::default()
+    }
+}
+
+/// `body` as UTF-8 text, or a [`NormalizeError::Parse`] if it is not.
+pub fn text(body: &[u8]) -> Result<&str, NormalizeError> {
+    std::str::from_utf8(body)
+        .map_err(|err| NormalizeError::Parse(format!("body is not UTF-8: {err}")))
+}
+
+/// Normalizes the JSON of a JSONPlaceholder resource: one object, or an
+/// array of them for the plural resources, whose elements that are not
+/// records are counted as skipped.
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceNormalizer(pub Resource);
+
+impl Normalizer for ResourceNormalizer {
+    fn kind(&self) -> RecordKind {
+        match self.0 {
+            Resource::Post | Resource::Posts => RecordKind::Post,
+            Resource::Comment | Resource::Comments => RecordKind::Comment,
+            Resource::User | Resource::Users => RecordKind::User,
+        }
+    }
+
+    fn normalize(&self, body: &[u8]) -> Result<Normalized, NormalizeError> {
+        let json: Value = serde_json::from_str(text(body)?)
+            .map_err(|err| NormalizeError::Parse(err.to_string()))?;
+        let resource = self.0;
+        let shape = NormalizeError::Shape;
+        let mut normalized = Normalized::default();
+        match resource {
+            Resource::Post => normalized.posts.push(normalize_post(&json).ok_or(shape)?),
+            Resource::Comment => normalized
+                .comments
+                .push(normalize_comment(&json).ok_or(shape)?),
+            Resource::User => normalized.users.push(normalize_user(&json).ok_or(shape)?),
+            Resource::Posts => normalized.posts = normalize_posts(&json),
+            Resource::Comments => normalized.comments = normalize_comments(&json),
+            Resource::Users => normalized.users = normalize_users(&json),
+        }
+        normalized.details = match resource {
+            // A post keeps the event it had before other resources.
+            Resource::Post => json!({ "id": json["id"] }),
+            Resource::Comment | Resource::User => {
+                json!({ "resource": resource.as_str(), "id": json["id"] })
+            }
+            _ => {
+                let items = json.as_array().ok_or(NormalizeError::Shape)?.len();
+                collection_details(resource.as_str(), normalized.len(), items)
+            }
+        };
+        Ok(normalized)
+    }
+
+    /// The plural resources, whose bodies are arrays.
+    fn normalizes_elements(&self) -> bool {
+        self.0.is_collection()
+    }
+
+    fn normalize_element(&self, element: &Value) -> Normalized {
+        let mut normalized = Normalized::default();
+        match self.0 {
+            Resource::Posts => normalized.posts.extend(normalize_post(element)),
+            Resource::Comments => normalized.comments.extend(normalize_comment(element)),
+            Resource::Users => normalized.users.extend(normalize_user(element)),
+            Resource::Post | Resource::Comment | Resource::User => {}
+        }
+        normalized
+    }
+}
+
+/// Normalizes the `<item>` elements of an RSS 2.0 feed into posts: `title`
+/// becomes the title, `description` the body, and the number that ends the
+/// `guid`, or the `link` without one, the id.  Feeds carry no user id, so
+/// `user_id` is 0.  Items without a title or an id are counted as skipped.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RssItemNormalizer;
+
+impl Normalizer for RssItemNormalizer {
+    fn kind(&self) -> RecordKind {
+        RecordKind::Post
+    }
+
+    fn normalize(&self, body: &[u8]) -> Result<Normalized, NormalizeError> {
+        let body = text(body)?;
+        if !body.trim_start().starts_with('<') {
+            return Err(NormalizeError::Parse("not an XML document".to_string()));
+        }
+        let channel = xml_elements(body, "channel")
+            .next()
+            .ok_or(NormalizeError::Shape)?;
+        let items: Vec<&str> = xml_elements(channel, "item").collect();
+        let posts: Vec<Post> = items.iter().filter_map(|item| rss_post(item)).collect();
+        Ok(Normalized {
+            details: collection_details("rss-item", posts.len(), items.len()),
+            posts,
+            ..Normalized::default()
+        })
+    }
+}
+
+/// The `normalized` event data of a collection of `items` elements of which
+/// `count` were records.
+pub fn collection_details(resource: &str, count: usize, items: usize) -> Value {
+    json!({ "resource": resource, "count": count, "skipped": items - count })
+}
+
+/// The post an RSS `<item>` describes, if it has a title and an id.
+fn rss_post(item: &str) -> Option<Post> {
+    let text = |name: &str| xml_elements(item, name).next().map(xml_text);
+    let id_source = text("guid").or_else(|| text("link"))?;
+    let prefix = id_source.trim_end_matches(|c: char| c.is_ascii_digit());
+    let id = id_source[prefix.len()..].parse().ok()?;
+    Some(Post {
+        id,
+        user_id: 0,
+        title: text("title")?,
+        body: text("description").unwrap_or_default(),
+    })
+}
+
+/// The contents of every `<name>` element in `xml`, outermost first.  Enough
+/// XML for RSS: no namespaces, and elements of one name do not nest.
+fn xml_elements<'a>(xml: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    let open = format!("<{name}");
+    let close = format!("</{name}>");
+    let mut rest = xml;
+    std::iter::from_fn(move || loop {
+        let at = rest.find(&open)?;
+        let after = &rest[at + open.len()..];
+        // `<item` must not match `<items>` or `<itemized>`.
+        if !after.starts_with(['>', ' ', '\t', '\r', '\n', '/']) {
+            rest = after;
+            continue;
+        }
+        let start = after.find('>')?;
+        if after[..start].ends_with('/') {
+            rest = &after[start + 1..];
+            return Some("");
+        }
+        let content = &after[start + 1..];
+        let end = content.find(&close)?;
+        rest = &content[end + close.len()..];
+        return Some(&content[..end]);
+    })
+}
+
+/// The text of element contents: CDATA sections as written and everything
+/// else with the predefined and numeric character references decoded, then
+/// trimmed.
+fn xml_text(content: &str) -> String {
+    let mut text = String::new();
+    let mut rest = content;
+    while let Some(at) = rest.find("<![CDATA[") {
+        text.push_str(&decode_references(&rest[..at]));
+        let cdata = &rest[at + "<![CDATA[".len()..];
+        let end = cdata.find("]]>").unwrap_or(cdata.len());
+        text.push_str(&cdata[..end]);
+        rest = cdata.get(end + "]]>".len()..).unwrap_or_default();
+    }
+    text.push_str(&decode_references(rest));
+    text.trim().to_string()
+}
+
+fn decode_references(text: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = text;
+    while let Some(at) = rest.find('&') {
+        decoded.push_str(&rest[..at]);
+        rest = &rest[at..];
+        let reference = rest.find(';').map(|end| (&rest[1..end], end));
+        let character = reference.and_then(|(name, _)| match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => name
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| name.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        });
+        match (character, reference) {
+            (Some(character), Some((_, end))) => {
+                decoded.push(character);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Normalizers by name, and the names that serve each content type.
+#[derive(Clone, Default)]
+pub struct NormalizerRegistry {
+    normalizers: BTreeMap<String, Rc<dyn Normalizer>>,
+    content_types: BTreeMap<String, String>,
+}
+
+impl NormalizerRegistry {
+    /// An empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The normalizers shipped with the service.
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        for resource in [
+            Resource::Post,
+            Resource::Posts,
+            Resource::Comment,
+            Resource::Comments,
+            Resource::User,
+            Resource::Users,
+        ] {
+            registry.register(resource.as_str(), ResourceNormalizer(resource));
+        }
+        registry.register("rss-item", RssItemNormalizer);
+        registry.register_content_type("application/json", "post");
+        registry.register_content_type("application/rss+xml", "rss-item");
+        registry
+    }
+
+    /// Register `normalizer` under `name`, replacing any normalizer
+    /// registered under it before.
+    pub fn register(&mut self, name: &str, normalizer: impl Normalizer + 'static) {
+        self.normalizers
+            .insert(name.to_string(), Rc::new(normalizer));
+    }
+
+    /// Dispatch bodies of `content_type` to the normalizer named `name`.
+    /// Content types compare without parameters and case-insensitively.
+    pub fn register_content_type(&mut self, content_type: &str, name: &str) {
+        self.content_types
+            .insert(media_type(content_type), name.to_string());
+    }
+
+    /// The registered names, in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.normalizers.keys().map(String::as_str)
+    }
+
+    /// The normalizer registered as `name`.
+    pub fn get(&self, name: &str) -> Option<Rc<dyn Normalizer>> {
+        self.normalizers.get(name).cloned()
+    }
+
+    /// The name of the normalizer for bodies of `content_type`:
+    /// [`DEFAULT_NORMALIZER`] when none is registered for it or there is no
+    /// content type.
+    pub fn name_for_content_type(&self, content_type: Option<&str>) -> &str {
+        content_type
+            .and_then(|content_type| self.content_types.get(&media_type(content_type)))
+            .map_or(DEFAULT_NORMALIZER, String::as_str)
+    }
+}
+
+/// `content_type` lowercased, without parameters.
+fn media_type(content_type: &str) -> String {
+    let media_type = content_type.split(';').next().unwrap_or_default();
+    media_type.trim().to_ascii_lowercase()
+}
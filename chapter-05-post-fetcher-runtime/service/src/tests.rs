@@ -1,6 +1,8 @@
 // Unit tests for the service crate.
 use super::*;
-use crate::api::{fetch_json, NetworkAdapter, NetworkResponse};
+use crate::api::{fetch_json, format_wall_time, NetworkAdapter, NetworkResponse};
+use crate::messages::{error_event_data, ErrorCode, MessageCatalog, DEFAULT_LOCALE};
+use crate::model::Request;
 use anyhow::{anyhow, Result};
 use serde_json::json;
 use std::collections::HashMap;
@@ -10,18 +12,70 @@ struct StubAdapter {
 }
 
 impl NetworkAdapter for StubAdapter {
-    fn fetch(&self, _url: &str, _headers: &HashMap<String, String>) -> Result<NetworkResponse> {
+    fn fetch(&self, _request: &Request) -> Result<NetworkResponse> {
         match &self.response {
             Ok(resp) => Ok(NetworkResponse {
                 status: resp.status,
                 headers: resp.headers.clone(),
                 body: resp.body.clone(),
+                decoded: None,
             }),
             Err(err) => Err(anyhow!(err.to_string())),
         }
     }
 }
 
+#[test]
+fn test_request_body_accepts_text_or_inline_json() {
+    let body = |value: serde_json::Value| -> Option<Vec<u8>> {
+        serde_json::from_value::<Request>(json!({ "url": "https://x", "body": value }))
+            .unwrap()
+            .body
+    };
+    assert_eq!(body(json!("a=1")), Some(b"a=1".to_vec()));
+    assert_eq!(
+        body(json!({ "a": [1, true] })),
+        Some(br#"{"a":[1,true]}"#.to_vec())
+    );
+    assert_eq!(body(json!(null)), None);
+    let absent: Request = serde_json::from_value(json!({ "url": "https://x" })).unwrap();
+    assert_eq!(absent.body, None);
+}
+
+#[test]
+fn test_wrappers_can_set_request_headers_and_read_response_headers() {
+    let request: Request = serde_json::from_value(json!({
+        "url": "https://example.test/data",
+        "headers": { "if-none-match": "\"old\"", "accept": "application/json" }
+    }))
+    .unwrap();
+    let conditional = request.with_header("If-None-Match", "\"v1\"");
+    assert_eq!(
+        conditional.headers,
+        HashMap::from([
+            ("If-None-Match".to_string(), "\"v1\"".to_string()),
+            ("accept".to_string(), "application/json".to_string()),
+        ])
+    );
+    assert_eq!(request.headers["if-none-match"], "\"old\"");
+
+    let response = NetworkResponse {
+        status: 200,
+        headers: HashMap::from([("ETag".to_string(), "\"v1\"".to_string())]),
+        body: Vec::new(),
+        decoded: None,
+    };
+    assert_eq!(response.header("etag"), Some("\"v1\""));
+    assert_eq!(response.header("last-modified"), None);
+}
+
+fn data_request() -> Request {
+    Request {
+        url: "https://example.test/data".to_string(),
+        ..Default::default()
+    }
+}
+
 #[test]
 fn test_normalize_post_success() {
     let input = json!({
@@ -145,11 +199,11 @@ fn test_fetch_json_success() {
         response: Ok(NetworkResponse {
             status: 200,
             headers: HashMap::new(),
-            body: r#"{"ok":true,"count":2}"#.to_string(),
+            body: r#"{"ok":true,"count":2}"#.into(),
+            decoded: None,
         }),
     };
-    let headers = HashMap::new();
-    let (status, value) = fetch_json(&adapter, "https://example.test/data", &headers).unwrap();
+    let (status, value) = fetch_json(&adapter, &data_request()).unwrap();
     assert_eq!(status, 200);
     assert_eq!(value["ok"], true);
     assert_eq!(value["count"], 2);
@@ -160,8 +214,7 @@ fn test_fetch_json_propagates_adapter_error() {
     let adapter = StubAdapter {
         response: Err(anyhow!("network unavailable")),
     };
-    let headers = HashMap::new();
-    let error = fetch_json(&adapter, "https://example.test/data", &headers).unwrap_err();
+    let error = fetch_json(&adapter, &data_request()).unwrap_err();
     assert!(error.to_string().contains("network unavailable"));
 }
 
@@ -171,10 +224,439 @@ fn test_fetch_json_rejects_invalid_json() {
         response: Ok(NetworkResponse {
             status: 200,
             headers: HashMap::new(),
-            body: "not json".to_string(),
+            body: "not json".into(),
+            decoded: None,
         }),
     };
-    let headers = HashMap::new();
-    let error = fetch_json(&adapter, "https://example.test/data", &headers).unwrap_err();
-    assert!(error.to_string().contains("expected ident") || error.to_string().contains("expected value"));
+    let error = fetch_json(&adapter, &data_request()).unwrap_err();
+    assert!(
+        error.to_string().contains("expected ident")
+            || error.to_string().contains("expected value")
+    );
+}
+
+fn event(t: u64, type_: &str, data: Value) -> Event {
+    Event {
+        t: t.to_string(),
+        wall_time: None,
+        trace: None,
+        type_: type_.to_string(),
+        data,
+        host: None,
+    }
+}
+
+#[test]
+fn test_summarize_events_counts_requests_and_failures() {
+    let events = vec![
+        event(0, "start", json!({})),
+        event(1, "fetch_request", json!({ "url": "a" })),
+        event(2, "fetch_response", json!({ "status": 200 })),
+        event(3, "fetch_request", json!({ "url": "b" })),
+        event(4, "fetch_response", json!({ "status": 404 })),
+        event(5, "fetch_request", json!({ "url": "c" })),
+        event(6, "fetch_response", json!({ "status": 0 })),
+        event(7, "fetch_request", json!({ "url": "d" })),
+        event(8, "fetch_response", json!({ "status": 404 })),
+        event(9, "fetch_response", json!({})),
+        event(10, "end", json!({})),
+    ];
+    let summary = summarize_events(&events);
+    assert_eq!(summary.total_requests, 4);
+    assert_eq!(summary.successes, 1);
+    assert_eq!(summary.failures_by_code.get("404"), Some(&2));
+    assert_eq!(summary.failures_by_code.get("0"), Some(&2));
+    assert_eq!(summary.cache_hits, 0);
+    assert_eq!(summary.retries, 0);
+    assert_eq!(summary.duration_ticks, 11);
+}
+
+#[test]
+fn test_summarize_events_serializes_camel_case() {
+    let value = serde_json::to_value(summarize_events(&[])).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "totalRequests": 0,
+            "successes": 0,
+            "failuresByCode": {},
+            "cacheHits": 0,
+            "retries": 0,
+            "durationTicks": 0,
+            "deduplicated": 0,
+        })
+    );
+}
+
+fn post(id: u64, title: &str, body: &str) -> Post {
+    Post {
+        id,
+        user_id: 1,
+        title: title.to_string(),
+        body: body.to_string(),
+    }
+}
+
+#[test]
+fn test_normalize_collections_comments_and_users() {
+    let posts = json!([
+        { "id": 1, "userId": 1, "title": "a", "body": "x" },
+        { "id": 2, "title": "no user" },
+        { "id": 3, "userId": 1, "title": "c", "body": "z" },
+    ]);
+    let ids: Vec<u64> = normalize_posts(&posts).iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![1, 3]);
+    assert!(normalize_posts(&posts[0]).is_empty());
+
+    let comment = json!({ "id": 7, "postId": 1, "name": "n", "email": "e@x.io", "body": "b" });
+    assert_eq!(normalize_comment(&comment).unwrap().post_id, 1);
+    assert!(normalize_comment(&json!({ "id": 7, "postId": "1" })).is_none());
+    assert_eq!(normalize_comments(&json!([comment])).len(), 1);
+
+    let user = json!({
+        "id": 1,
+        "name": "Leanne Graham",
+        "username": "Bret",
+        "email": "Sincere@april.biz",
+        "address": { "city": "Gwenborough" },
+    });
+    let normalized = normalize_user(&user).unwrap();
+    assert_eq!(normalized.username, "Bret");
+    assert_eq!(
+        serde_json::to_value(&normalized).unwrap(),
+        json!({ "id": 1, "name": "Leanne Graham", "username": "Bret", "email": "Sincere@april.biz" })
+    );
+    assert!(normalize_user(&json!({ "id": 1, "name": "x" })).is_none());
+    assert!(normalize_users(&json!({})).is_empty());
+}
+
+#[test]
+fn test_content_hash_is_stable_and_field_sensitive() {
+    let a = post(1, "title", "body");
+    assert_eq!(content_hash(&a), content_hash(&a.clone()));
+    assert_eq!(content_hash(&a), 0x6c9c_641c_6468_1c4e);
+    assert_ne!(content_hash(&a), content_hash(&post(2, "title", "body")));
+    assert_ne!(content_hash(&a), content_hash(&post(1, "titleb", "ody")));
+}
+
+#[test]
+fn test_dedupe_posts_keeps_first_occurrence_in_order() {
+    let posts = vec![
+        post(1, "a", "x"),
+        post(2, "b", "y"),
+        post(1, "a", "x"),
+        post(3, "c", "z"),
+        post(2, "b", "y"),
+    ];
+    let unique = dedupe_posts(posts);
+    let ids: Vec<u64> = unique.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![1, 2, 3]);
+    assert!(dedupe_posts(Vec::new()).is_empty());
+}
+
+#[test]
+fn test_error_codes_are_stable_and_unique() {
+    let codes: Vec<&str> = ErrorCode::ALL.iter().map(|code| code.as_str()).collect();
+    assert_eq!(
+        codes,
+        vec![
+            "header.unexpected",
+            "header.too_long",
+            "header.unsupported_content_type",
+            "header.missing",
+            "response.parse_error",
+            "response.invalid_post",
+            "response.invalid_resource",
+            "network.failure",
+            "network.budget_exceeded",
+            "network.circuit_open",
+            "adapter.unsupported_capability",
+            "adapter.unknown",
+            "normalizer.unknown",
+            "request.host_not_allowed",
+            "request.scheme_not_allowed",
+            "input.validation_error",
+            "event.schema_violation",
+        ]
+    );
+    let catalog = MessageCatalog::builtin();
+    for code in ErrorCode::ALL {
+        assert_ne!(
+            catalog.format("es", code.as_str(), &json!({})),
+            code.as_str()
+        );
+    }
+}
+
+#[test]
+fn test_error_event_data_keeps_english_message() {
+    assert_eq!(
+        error_event_data(ErrorCode::UnexpectedHeader, json!({ "header": "x-foo" })),
+        json!({
+            "error": "unexpected header x-foo",
+            "code": "header.unexpected",
+            "params": { "header": "x-foo" },
+        })
+    );
+    assert_eq!(
+        error_event_data(ErrorCode::InvalidPost, json!({ "status": 200 }))["error"],
+        error_message(Some(200), None)
+    );
+}
+
+#[test]
+fn test_catalog_locale_fallback_chain() {
+    let catalog = MessageCatalog::builtin();
+    let params = json!({ "header": "x-foo" });
+    assert_eq!(
+        catalog.format("es-MX", "header.unexpected", &params),
+        "cabecera inesperada x-foo"
+    );
+    assert_eq!(
+        catalog.format("fr", "header.unexpected", &params),
+        "unexpected header x-foo"
+    );
+    assert_eq!(
+        catalog.format(DEFAULT_LOCALE, "no.such.code", &params),
+        "no.such.code"
+    );
+    assert_eq!(
+        MessageCatalog::new().format("en", "header.unexpected", &params),
+        "header.unexpected"
+    );
+}
+
+#[test]
+fn test_catalog_custom_templates_and_placeholders() {
+    let mut catalog = MessageCatalog::new();
+    catalog.insert(
+        "fr",
+        "response.invalid_post",
+        "statut {status} {missing} {unclosed",
+    );
+    assert_eq!(
+        catalog.format("fr_CA", "response.invalid_post", &json!({ "status": 502 })),
+        "statut 502 {missing} {unclosed"
+    );
+    let data = error_event_data(ErrorCode::NetworkFailure, json!({ "detail": "refused" }));
+    assert_eq!(
+        MessageCatalog::builtin()
+            .format_event("es", &data)
+            .as_deref(),
+        Some("fallo de red: refused")
+    );
+    assert_eq!(
+        catalog
+            .format_event("fr", &json!({ "code": "response.invalid_post" }))
+            .as_deref(),
+        Some("statut {status} {missing} {unclosed")
+    );
+    assert_eq!(
+        catalog.format_event("fr", &json!({ "error": "legacy" })),
+        None
+    );
+}
+
+#[test]
+fn test_classify_sla_checks_each_declared_bound() {
+    use crate::model::{SlaStatus, SlaTarget};
+    let target = SlaTarget {
+        max_ticks: Some(5),
+        max_wall_millis: Some(100),
+    };
+    assert_eq!(classify_sla(target, 5, Some(100)).status, SlaStatus::Within);
+    assert_eq!(
+        classify_sla(target, 6, Some(10)).status,
+        SlaStatus::Violated
+    );
+    assert_eq!(
+        classify_sla(target, 1, Some(101)).status,
+        SlaStatus::Violated
+    );
+    // Without a clock the wall bound cannot be checked.
+    let report = classify_sla(target, 5, None);
+    assert_eq!(report.status, SlaStatus::Within);
+    assert_eq!(
+        serde_json::to_value(report).unwrap(),
+        json!({ "status": "within", "maxTicks": 5, "maxWallMillis": 100, "ticks": 5 })
+    );
+    assert_eq!(
+        classify_sla(SlaTarget::default(), 99, Some(99)).status,
+        SlaStatus::Within
+    );
+}
+
+#[test]
+fn test_capabilities_check_method_scheme_and_tls() {
+    use crate::api::{AdapterCapabilities, UnsupportedCapability};
+    use crate::model::Request;
+    let request = |url: &str, method: Option<&str>| -> Request {
+        serde_json::from_value(json!({ "url": url, "method": method })).unwrap()
+    };
+    let unsupported = |capability, requested: &str| {
+        Some(UnsupportedCapability {
+            capability,
+            requested: requested.to_string(),
+        })
+    };
+    let plain = AdapterCapabilities::default();
+    assert_eq!(
+        plain.check(&request("https://example.com/posts/1", None)),
+        None
+    );
+    assert_eq!(
+        plain.check(&request("ftp://example.com", Some("get"))),
+        None
+    );
+    assert_eq!(
+        plain.check(&request("https://example.com", Some("post"))),
+        unsupported("method", "POST")
+    );
+    let http_only = AdapterCapabilities {
+        schemes: vec!["http".to_string(), "https".to_string()],
+        tls: false,
+        ..AdapterCapabilities::default()
+    };
+    assert_eq!(
+        http_only.check(&request("ftp://example.com", None)),
+        unsupported("scheme", "ftp")
+    );
+    assert_eq!(
+        http_only.check(&request("HTTPS://example.com", None)),
+        unsupported("tls", "https")
+    );
+    assert_eq!(http_only.check(&request("http://example.com", None)), None);
+}
+
+#[test]
+fn test_decode_response_undoes_content_encodings() {
+    use crate::content_encoding::{decode_response, ContentDecoding};
+    use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+    use std::io::Write;
+
+    let text = br#"{"id":1,"userId":1,"title":"t","body":"b"}"#;
+    let compress = |mut encoder: Box<dyn Write>| {
+        encoder.write_all(text).unwrap();
+        drop(encoder);
+    };
+    let mut gzip = Vec::new();
+    compress(Box::new(GzEncoder::new(&mut gzip, Compression::default())));
+    let mut zlib = Vec::new();
+    compress(Box::new(ZlibEncoder::new(
+        &mut zlib,
+        Compression::default(),
+    )));
+    let mut raw = Vec::new();
+    compress(Box::new(DeflateEncoder::new(
+        &mut raw,
+        Compression::default(),
+    )));
+    let headers = |encoding: &str| {
+        HashMap::from([
+            ("Content-Encoding".to_string(), encoding.to_string()),
+            ("content-length".to_string(), "99".to_string()),
+            ("content-type".to_string(), "application/json".to_string()),
+        ])
+    };
+
+    for (encoding, body) in [("gzip", &gzip), ("deflate", &zlib), ("deflate", &raw)] {
+        let mut found = headers(encoding);
+        let (decoded, decoding) = decode_response(&mut found, body.clone(), None).unwrap();
+        assert_eq!(decoded, text, "{encoding}");
+        assert_eq!(
+            decoding,
+            Some(ContentDecoding {
+                encoding: encoding.to_string(),
+                encoded_bytes: body.len() as u64,
+                decoded_bytes: text.len() as u64,
+            })
+        );
+        assert_eq!(found.keys().collect::<Vec<_>>(), ["content-type"]);
+    }
+
+    // One uncompressed brotli meta-block holding "hello", then the last one.
+    let brotli = [&[0x40, 0x00, 0x10][..], b"hello", &[0x03]].concat();
+    let (decoded, _) = decode_response(&mut headers("br"), brotli, None).unwrap();
+    assert_eq!(decoded, b"hello");
+
+    // Codings are undone last to first; identity needs nothing.
+    let mut twice = Vec::new();
+    let mut encoder = GzEncoder::new(&mut twice, Compression::default());
+    encoder.write_all(&zlib).unwrap();
+    drop(encoder);
+    let (decoded, decoding) =
+        decode_response(&mut headers("deflate, identity, GZIP"), twice, None).unwrap();
+    assert_eq!(decoded, text);
+    assert_eq!(decoding.unwrap().encoding, "deflate, gzip");
+    let mut plain = headers("identity");
+    let (decoded, decoding) = decode_response(&mut plain, text.to_vec(), None).unwrap();
+    assert_eq!((decoded.as_slice(), decoding), (&text[..], None));
+    assert_eq!(plain.len(), 3);
+
+    // Decoding stops one byte past the limit.
+    let (decoded, _) = decode_response(&mut headers("gzip"), gzip.clone(), Some(10)).unwrap();
+    assert_eq!(decoded.len(), 11);
+
+    let err = decode_response(&mut headers("compress"), gzip, None).unwrap_err();
+    assert_eq!(err.to_string(), "unsupported content-encoding compress");
+    let err = decode_response(&mut headers("gzip"), text.to_vec(), None).unwrap_err();
+    assert!(err.to_string().starts_with("invalid gzip body:"), "{err}");
+}
+
+#[test]
+fn test_host_fetch_config_parses_tls_versions_and_reads_certificates() {
+    use crate::fetch_config::{HostFetchConfig, TlsVersion};
+
+    for (value, version) in [
+        ("1.2", TlsVersion::Tls1_2),
+        ("TLSv1.3", TlsVersion::Tls1_3),
+        ("tls1.0", TlsVersion::Tls1_0),
+        (" 1.1 ", TlsVersion::Tls1_1),
+    ] {
+        assert_eq!(value.parse::<TlsVersion>().unwrap(), version, "{value}");
+    }
+    assert!("1.4".parse::<TlsVersion>().is_err());
+    assert!("".parse::<TlsVersion>().is_err());
+    assert_eq!(TlsVersion::Tls1_2.as_str(), "1.2");
+    assert!(TlsVersion::Tls1_3 > TlsVersion::Tls1_2);
+
+    assert_eq!(HostFetchConfig::default().ca_certificates().unwrap(), None);
+    let path = std::env::temp_dir().join(format!("uma-ca-{}.pem", std::process::id()));
+    std::fs::write(&path, "-----BEGIN CERTIFICATE-----\n").unwrap();
+    let config = HostFetchConfig {
+        ca_cert_path: Some(path.clone()),
+        ..HostFetchConfig::default()
+    };
+    assert_eq!(
+        config.ca_certificates().unwrap().as_deref(),
+        Some(&b"-----BEGIN CERTIFICATE-----\n"[..])
+    );
+    std::fs::remove_file(&path).unwrap();
+    let err = config.ca_certificates().unwrap_err();
+    assert!(
+        err.to_string().contains("reading CA certificates from"),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_wall_time_is_rfc3339_utc() {
+    assert_eq!(format_wall_time(0), "1970-01-01T00:00:00.000Z");
+    assert_eq!(
+        format_wall_time(951_782_400_000),
+        "2000-02-29T00:00:00.000Z"
+    );
+    assert_eq!(
+        format_wall_time(1_714_566_600_250),
+        "2024-05-01T12:30:00.250Z"
+    );
+    assert_eq!(
+        format_wall_time(4_107_542_399_000),
+        "2100-02-28T23:59:59.000Z"
+    );
+    assert_eq!(
+        format_wall_time(253_402_300_799_999),
+        "9999-12-31T23:59:59.999Z"
+    );
 }
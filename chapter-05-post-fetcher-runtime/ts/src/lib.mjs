@@ -1,4 +1,5 @@
 import process from "node:process";
+import { createHash, createHmac } from "node:crypto";
 import fs from "node:fs/promises";
 import path from "node:path";
 import { fileURLToPath } from "node:url";
@@ -28,111 +29,1529 @@ function normalizePost(json) {
   };
 }
 
-function errorMessage(status, parseError) {
-  if (parseError) {
-    return `parse error: ${parseError.message}`;
+// Mirrors `normalize_comment`.
+function normalizeComment(json) {
+  if (
+    typeof json !== "object" ||
+    json === null ||
+    typeof json.id !== "number" ||
+    typeof json.postId !== "number" ||
+    typeof json.name !== "string" ||
+    typeof json.email !== "string" ||
+    typeof json.body !== "string"
+  ) {
+    return null;
   }
-  if (typeof status === "number") {
-    return `status ${status}`;
+  return { id: json.id, post_id: json.postId, name: json.name, email: json.email, body: json.body };
+}
+
+// Mirrors `normalize_user`.
+function normalizeUser(json) {
+  if (
+    typeof json !== "object" ||
+    json === null ||
+    typeof json.id !== "number" ||
+    typeof json.name !== "string" ||
+    typeof json.username !== "string" ||
+    typeof json.email !== "string"
+  ) {
+    return null;
   }
-  return "unknown error";
+  return { id: json.id, name: json.name, username: json.username, email: json.email };
 }
 
-class EventBus {
+// Mirrors `normalizer::text`: a response body, a string or the bytes the
+// adapter received, as UTF-8 text.  Bytes that are not UTF-8 are an error.
+export function bodyText(body) {
+  if (typeof body === "string") {
+    return body;
+  }
+  try {
+    return new TextDecoder("utf-8", { fatal: true }).decode(body);
+  } catch (error) {
+    throw new Error(`body is not UTF-8: ${error.message}`);
+  }
+}
+
+// Thrown by normalizers, as `NormalizeError::Shape`, when the body parsed
+// but does not hold the expected records.  Other errors are parse errors.
+export class ShapeError extends Error {}
+
+// Mirrors `ResourceNormalizer`: the record normalizer of each resource, its
+// kind, and whether it is an array.
+const RESOURCES = {
+  post: { normalize: normalizePost, kind: "post", collection: false },
+  posts: { normalize: normalizePost, kind: "post", collection: true },
+  comment: { normalize: normalizeComment, kind: "comment", collection: false },
+  comments: { normalize: normalizeComment, kind: "comment", collection: true },
+  user: { normalize: normalizeUser, kind: "user", collection: false },
+  users: { normalize: normalizeUser, kind: "user", collection: true },
+};
+
+// The `normalized` event data of a collection of `items` elements of which
+// `count` were records.
+function collectionDetails(resource, count, items) {
+  return { resource, count, skipped: items - count };
+}
+
+function resourceNormalizer(resource) {
+  const { normalize, kind, collection } = RESOURCES[resource];
+  return {
+    kind,
+    normalize(body) {
+      const json = JSON.parse(bodyText(body));
+      if (collection) {
+        if (!Array.isArray(json)) {
+          throw new ShapeError();
+        }
+        const records = json.map(normalize).filter(Boolean);
+        return { records, details: collectionDetails(resource, records.length, json.length) };
+      }
+      const record = normalize(json);
+      if (!record) {
+        throw new ShapeError();
+      }
+      return {
+        records: [record],
+        details: resource === "post" ? { id: record.id } : { resource, id: record.id },
+      };
+    },
+    // Mirrors `Normalizer::normalizes_elements` and `normalize_element`.
+    normalizesElements: collection,
+    normalizeElement(element) {
+      const record = normalize(element);
+      return record ? [record] : [];
+    },
+  };
+}
+
+// Mirrors `ArrayStreamError::NotAnArray`.
+export class NotAnArray extends Error {}
+
+// Mirrors `JsonArrayStream`: a JSON array parser fed a body in byte chunks,
+// returning each element once its closing byte has been read.
+export class JsonArrayStream {
   constructor() {
+    this.state = "start";
+    this.mayClose = false;
+    this.element = [];
+    this.depth = 0;
+    this.inString = false;
+    this.escaped = false;
+  }
+
+  inArray() {
+    return this.state !== "start";
+  }
+
+  push(chunk) {
+    const elements = [];
+    for (const byte of chunk) {
+      if (this.state === "element") {
+        const element = this.elementByte(byte);
+        if (element !== undefined) {
+          elements.push(element);
+          if (this.state !== "separator") {
+            // The byte that ended a number or literal is a separator too.
+            this.state = "separator";
+            this.separatorByte(byte);
+          }
+        }
+        continue;
+      }
+      if (isWhitespace(byte)) {
+        continue;
+      }
+      const char = String.fromCharCode(byte);
+      if (this.state === "start") {
+        if (char !== "[") {
+          throw new NotAnArray();
+        }
+        this.state = "value";
+        this.mayClose = true;
+      } else if (this.state === "value") {
+        if (char === "]" && this.mayClose) {
+          this.state = "end";
+        } else if (char === "]" || char === ",") {
+          throw new Error(`expected a value, found \`${char}\``);
+        } else {
+          this.startElement(byte);
+        }
+      } else if (this.state === "separator") {
+        this.separatorByte(byte);
+      } else {
+        throw new Error("trailing characters after the array");
+      }
+    }
+    return elements;
+  }
+
+  finish() {
+    if (this.state === "start") {
+      throw new NotAnArray();
+    }
+    if (this.state !== "end") {
+      throw new Error("the body ended inside the array");
+    }
+  }
+
+  startElement(byte) {
+    this.state = "element";
+    this.element = [byte];
+    this.depth = byte === 0x7b || byte === 0x5b ? 1 : 0;
+    this.inString = byte === 0x22;
+    this.escaped = false;
+  }
+
+  elementByte(byte) {
+    if (this.depth === 0 && !this.inString && this.element[0] !== 0x22) {
+      if (isWhitespace(byte) || byte === 0x2c || byte === 0x5d) {
+        return this.complete();
+      }
+      this.element.push(byte);
+      return undefined;
+    }
+    this.element.push(byte);
+    if (this.inString) {
+      if (this.escaped) {
+        this.escaped = false;
+      } else if (byte === 0x5c) {
+        this.escaped = true;
+      } else if (byte === 0x22) {
+        this.inString = false;
+      }
+    } else if (byte === 0x22) {
+      this.inString = true;
+    } else if (byte === 0x7b || byte === 0x5b) {
+      this.depth += 1;
+    } else if ((byte === 0x7d || byte === 0x5d) && this.depth > 0) {
+      this.depth -= 1;
+    }
+    if (this.depth === 0 && !this.inString) {
+      const element = this.complete();
+      this.state = "separator";
+      return element;
+    }
+    return undefined;
+  }
+
+  separatorByte(byte) {
+    const char = String.fromCharCode(byte);
+    if (char === ",") {
+      this.state = "value";
+      this.mayClose = false;
+    } else if (char === "]") {
+      this.state = "end";
+    } else if (!isWhitespace(byte)) {
+      throw new Error(`expected \`,\` or \`]\`, found \`${char}\``);
+    }
+  }
+
+  complete() {
+    const element = this.element;
+    this.element = [];
+    return JSON.parse(bodyText(Uint8Array.from(element)));
+  }
+}
+
+function isWhitespace(byte) {
+  return byte === 0x20 || byte === 0x09 || byte === 0x0a || byte === 0x0d || byte === 0x0c;
+}
+
+// Mirrors `xml_elements`: the contents of every `<name>` element in `xml`.
+function xmlElements(xml, name) {
+  const elements = [];
+  const open = `<${name}`;
+  const close = `</${name}>`;
+  let rest = xml;
+  for (;;) {
+    const at = rest.indexOf(open);
+    if (at < 0) {
+      return elements;
+    }
+    const after = rest.slice(at + open.length);
+    if (!/^[>\s/]/.test(after)) {
+      rest = after;
+      continue;
+    }
+    const start = after.indexOf(">");
+    if (start < 0) {
+      return elements;
+    }
+    if (after.slice(0, start).endsWith("/")) {
+      elements.push("");
+      rest = after.slice(start + 1);
+      continue;
+    }
+    const content = after.slice(start + 1);
+    const end = content.indexOf(close);
+    if (end < 0) {
+      return elements;
+    }
+    elements.push(content.slice(0, end));
+    rest = content.slice(end + close.length);
+  }
+}
+
+const XML_ENTITIES = { amp: "&", lt: "<", gt: ">", quot: '"', apos: "'" };
+
+function decodeReferences(text) {
+  return text.replace(/&([^&;]*);/g, (reference, name) => {
+    if (Object.hasOwn(XML_ENTITIES, name)) {
+      return XML_ENTITIES[name];
+    }
+    const code = name.startsWith("#x")
+      ? Number.parseInt(name.slice(2), 16)
+      : name.startsWith("#")
+        ? Number.parseInt(name.slice(1), 10)
+        : Number.NaN;
+    return Number.isNaN(code) || code > 0x10ffff ? reference : String.fromCodePoint(code);
+  });
+}
+
+// Mirrors `xml_text`: CDATA sections as written, everything else decoded.
+function xmlText(content) {
+  let text = "";
+  let rest = content;
+  for (let at = rest.indexOf("<![CDATA["); at >= 0; at = rest.indexOf("<![CDATA[")) {
+    text += decodeReferences(rest.slice(0, at));
+    const cdata = rest.slice(at + "<![CDATA[".length);
+    const end = cdata.indexOf("]]>");
+    text += end < 0 ? cdata : cdata.slice(0, end);
+    rest = end < 0 ? "" : cdata.slice(end + "]]>".length);
+  }
+  return (text + decodeReferences(rest)).trim();
+}
+
+// Mirrors `rss_post`: the post an RSS `<item>` describes, if it has a title
+// and an id.
+function rssPost(item) {
+  const text = (name) => {
+    const [element] = xmlElements(item, name);
+    return element === undefined ? null : xmlText(element);
+  };
+  const digits = /\d+$/.exec(text("guid") ?? text("link") ?? "");
+  const title = text("title");
+  if (!digits || title === null) {
+    return null;
+  }
+  return { id: Number(digits[0]), user_id: 0, title, body: text("description") ?? "" };
+}
+
+// Mirrors `RssItemNormalizer`.
+const rssItemNormalizer = {
+  kind: "post",
+  normalize(bytes) {
+    const body = bodyText(bytes);
+    if (!body.trimStart().startsWith("<")) {
+      throw new Error("not an XML document");
+    }
+    const [channel] = xmlElements(body, "channel");
+    if (channel === undefined) {
+      throw new ShapeError();
+    }
+    const items = xmlElements(channel, "item");
+    const records = items.map(rssPost).filter(Boolean);
+    return { records, details: collectionDetails("rss-item", records.length, items.length) };
+  },
+};
+
+// Mirrors `NormalizerRegistry::builtin`.  A normalizer is an object with a
+// `kind` (`post`, `comment` or `user`) and a `normalize(body)` returning the
+// records and the `normalized` event data.  `body` is what the adapter
+// returned, a string or bytes; `bodyText` reads either as text.
+export const BUILTIN_NORMALIZERS = {
+  ...Object.fromEntries(Object.keys(RESOURCES).map((resource) => [resource, resourceNormalizer(resource)])),
+  "rss-item": rssItemNormalizer,
+};
+
+export const BUILTIN_CONTENT_TYPES = {
+  "application/json": "post",
+  "application/rss+xml": "rss-item",
+};
+
+// Mirrors `DEFAULT_NORMALIZER`.
+const DEFAULT_NORMALIZER = "post";
+
+// Mirrors `NormalizerRegistry::name_for_content_type`.
+function normalizerForContentType(contentTypes, contentType) {
+  const mediaType = (contentType ?? "").split(";")[0].trim().toLowerCase();
+  return Object.hasOwn(contentTypes, mediaType) ? contentTypes[mediaType] : DEFAULT_NORMALIZER;
+}
+
+const ERROR_TEMPLATES = {
+  "header.unexpected": "unexpected header {header}",
+  "header.too_long": "header {header} too long",
+  "header.unsupported_content_type": "unsupported {header} {value}",
+  "header.missing": "missing header {header}",
+  "response.parse_error": "parse error: {detail}",
+  "response.invalid_post": "status {status}",
+  "response.invalid_resource": "status {status}, expected {resource}",
+  "network.failure": "{detail}",
+  "network.budget_exceeded": "{limit} of {max} exceeded",
+  "network.circuit_open": "circuit for {host} is open",
+  "adapter.unsupported_capability": "adapter {adapter} does not support {capability} {requested}",
+  "adapter.unknown": "no adapter is registered as {adapter}",
+  "normalizer.unknown": "no normalizer is registered as {normalizer}",
+  "request.host_not_allowed": "host {host} is not allowed",
+  "request.scheme_not_allowed": "scheme {scheme} is not allowed",
+  "input.validation_error": "unexpected input fields {fields}",
+  "event.schema_violation": "event {event} does not match its schema",
+};
+
+function errorEventData(code, params) {
+  const error = ERROR_TEMPLATES[code].replace(/\{(\w+)\}/g, (match, name) =>
+    name in params ? (typeof params[name] === "string" ? params[name] : JSON.stringify(params[name])) : match,
+  );
+  return { error, code, params };
+}
+
+// Mirrors `glob`: `*` matches any run of characters and `?` any one.
+function globMatch(pattern, name) {
+  const source = [...pattern]
+    .map((char) => (char === "*" ? ".*" : char === "?" ? "." : char.replace(/[.+^${}()|[\]\\]/g, "\\$&")))
+    .join("");
+  return new RegExp(`^${source}$`, "is").test(name);
+}
+
+export const DEFAULT_SENSITIVE_KEYS = [
+  "authorization",
+  "proxy-authorization",
+  "cookie",
+  "set-cookie",
+  "*token*",
+  "*secret*",
+  "*password*",
+  "*api*key*",
+];
+
+// Mirrors `SecretsPolicy`: `sensitiveKeys` are the name patterns whose
+// values are masked.
+class Secrets {
+  constructor(sensitiveKeys) {
+    this.sensitiveKeys = sensitiveKeys;
+  }
+
+  isSensitive(name) {
+    return this.sensitiveKeys.some((pattern) => globMatch(pattern, name));
+  }
+
+  mask(value) {
+    if (this.sensitiveKeys.length === 0) {
+      return value;
+    }
+    if (typeof value === "string") {
+      return this.maskText(value);
+    }
+    if (Array.isArray(value)) {
+      return value.map((item) => this.mask(item));
+    }
+    if (value && typeof value === "object") {
+      const namesSecret = typeof value.header === "string" && this.isSensitive(value.header);
+      return Object.fromEntries(
+        Object.entries(value).map(([key, item]) => [
+          key,
+          item !== null && (this.isSensitive(key) || (namesSecret && key === "value")) ? REDACTED : this.mask(item),
+        ]),
+      );
+    }
+    return value;
+  }
+
+  // Mirrors `mask_text`: values of sensitive `name=value` pairs and
+  // `Name: value` lines.
+  maskText(text) {
+    let masked = "";
+    let copied = 0;
+    for (let separator = text.search(/[=:]/); separator !== -1; ) {
+      let start = separator;
+      while (start > copied && /[A-Za-z0-9\-_.]/.test(text[start - 1])) {
+        start -= 1;
+      }
+      const before = text[start - 1];
+      const equals = text[separator] === "=";
+      const bounded = before === undefined || /[\s"'{(\[,;]/.test(before) || (equals && /[?&]/.test(before));
+      let next = separator + 1;
+      if (start < separator && bounded && this.isSensitive(text.slice(start, separator))) {
+        const valueStart = equals ? separator + 1 : separator + 1 + text.slice(separator + 1).match(/^ */)[0].length;
+        const end = text.slice(valueStart).search(equals ? /[&#;,"' \t\r\n]/ : /[;,"'\r\n]/);
+        const valueEnd = end === -1 ? text.length : valueStart + end;
+        if (valueEnd > valueStart) {
+          masked += `${text.slice(copied, valueStart)}${REDACTED}`;
+          copied = valueEnd;
+          next = valueEnd;
+        }
+      }
+      const offset = text.slice(next).search(/[=:]/);
+      separator = offset === -1 ? -1 : next + offset;
+    }
+    return copied === 0 ? text : masked + text.slice(copied);
+  }
+}
+
+class EventBus {
+  constructor(secrets = new Secrets([]), timeSource = null, trace = null) {
     this.clock = 0;
     this.events = [];
+    this.secrets = secrets;
+    this.timeSource = timeSource;
+    this.trace = trace;
+    this.listeners = [];
+  }
+
+  // Mirrors `EventBus::subscribe`: `listener` is called with every event
+  // emitted from now on, as it is stored.
+  subscribe(listener) {
+    this.listeners.push(listener);
   }
 
   emit(type, data) {
-    this.events.push({
+    // Mirrors `format_wall_time`: `toISOString` is the same RFC 3339 form.
+    const event = {
       t: String(this.clock),
+      ...(this.timeSource ? { wallTime: new Date(this.timeSource()).toISOString() } : {}),
+      ...this.trace,
       type,
-      data,
-    });
+      data: this.secrets.mask(data),
+    };
+    for (const listener of this.listeners) {
+      listener(event);
+    }
+    this.events.push(event);
     this.clock += 1;
   }
 }
 
+function summarizeEvents(events) {
+  const summary = {
+    totalRequests: 0,
+    successes: 0,
+    failuresByCode: {},
+    cacheHits: 0,
+    retries: 0,
+    durationTicks: events.length,
+    deduplicated: 0,
+  };
+  for (const event of events) {
+    if (event.type === "fetch_request") {
+      summary.totalRequests += 1;
+    } else if (event.type === "fetch_response") {
+      const status = typeof event.data.status === "number" ? event.data.status : 0;
+      if (status >= 200 && status < 300) {
+        summary.successes += 1;
+      } else {
+        const code = String(status);
+        summary.failuresByCode[code] = (summary.failuresByCode[code] ?? 0) + 1;
+      }
+    }
+  }
+  return summary;
+}
+
+async function loadContract() {
+  const contractPath = path.resolve(__dirname, "../../contracts/service.contract.json");
+  return JSON.parse(await fs.readFile(contractPath, "utf8"));
+}
+
+async function loadRuntimePolicy() {
+  const policyPath = path.resolve(__dirname, "../../contracts/policy.runtime.json");
+  return JSON.parse(await fs.readFile(policyPath, "utf8"));
+}
+
+// Mirrors `ServiceContract::unexpected_fields`: dotted paths, sorted by key.
+function unexpectedFields(schema, value, prefix = "", root = schema) {
+  if (typeof schema?.$ref === "string" && schema.$ref.startsWith("#/")) {
+    schema = schema.$ref
+      .slice(2)
+      .split("/")
+      .reduce((target, key) => target?.[key.replaceAll("~1", "/").replaceAll("~0", "~")], root);
+  }
+  if (schema?.items && Array.isArray(value)) {
+    return value.flatMap((item, index) => unexpectedFields(schema.items, item, `${prefix}.${index}`, root));
+  }
+  if (!schema?.properties || typeof value !== "object" || value === null || Array.isArray(value)) {
+    return [];
+  }
+  const open = schema.additionalProperties !== undefined && schema.additionalProperties !== false;
+  const fields = [];
+  for (const key of Object.keys(value).sort()) {
+    const fieldPath = prefix ? `${prefix}.${key}` : key;
+    if (Object.hasOwn(schema.properties, key)) {
+      fields.push(...unexpectedFields(schema.properties[key], value[key], fieldPath, root));
+    } else if (!open) {
+      fields.push(fieldPath);
+    }
+  }
+  return fields;
+}
+
+// The reference implementation has no clock capability, so only the tick
+// bound is checked and `wallMillis` is never reported.
+function classifySla(target, ticks) {
+  const report = { status: "within" };
+  if (target.maxTicks !== undefined) {
+    report.maxTicks = target.maxTicks;
+    if (ticks > target.maxTicks) {
+      report.status = "violated";
+    }
+  }
+  if (target.maxWallMillis !== undefined) {
+    report.maxWallMillis = target.maxWallMillis;
+  }
+  report.ticks = ticks;
+  return report;
+}
+
+// Assumed for adapters that do not declare capabilities: plain GET, any scheme.
+const DEFAULT_CAPABILITIES = {
+  methods: ["GET"],
+  schemes: [],
+  streaming: false,
+  compression: false,
+  timeouts: false,
+  tls: true,
+};
+
+function adapterCapabilities(adapter) {
+  return typeof adapter.capabilities === "function" ? adapter.capabilities() : DEFAULT_CAPABILITIES;
+}
+
+// Mirrors `Request::method`: upper case, `GET` when absent.
+function requestMethod(request) {
+  return (request.method ?? "GET").toUpperCase();
+}
+
+// Mirrors the `body` deserializer: a string is sent as its UTF-8 bytes, any
+// other JSON value as its serialized JSON.
+function requestBody(request) {
+  if (request.body === undefined || request.body === null) {
+    return undefined;
+  }
+  const text = typeof request.body === "string" ? request.body : JSON.stringify(request.body);
+  return Buffer.from(text, "utf8");
+}
+
+// Mirrors `submittable_content_type`: JSON (`application/json` or a `+json`
+// suffix type) or `text/plain`, with any parameters.
+function submittableContentType(value) {
+  const [type, subtype] = String(value).split(";")[0].trim().toLowerCase().split("/");
+  if (type === "application") {
+    return subtype === "json" || (subtype ?? "").endsWith("+json");
+  }
+  return type === "text" && subtype === "plain";
+}
+
+// Mirrors `HeaderPolicy` defaults and `HeaderPolicy::validate`: each header's
+// violations, in name order, then each missing required header, as
+// `[eventType, eventData, errorCode, errorParams]`.
+const DEFAULT_HEADER_POLICY = {
+  allowed: ["accept", "content-type", "authorization"],
+  maxValueLength: 1024,
+  required: [],
+};
+
+function headerViolations(policy, headers) {
+  const { allowed, maxValueLength, required } = { ...DEFAULT_HEADER_POLICY, ...policy };
+  const allows = (name) => [...allowed, ...required].some((known) => known.toLowerCase() === name.toLowerCase());
+  const key = (name) => [name.toLowerCase(), name];
+  const names = Object.keys(headers).sort((a, b) => {
+    const [left, right] = [key(a), key(b)];
+    return left[0] < right[0] ? -1 : left[0] > right[0] ? 1 : left[1] < right[1] ? -1 : left[1] > right[1] ? 1 : 0;
+  });
+  const violations = [];
+  for (const header of names) {
+    const value = String(headers[header]);
+    if (!allows(header)) {
+      violations.push(["header_unexpected", { header }, "header.unexpected", { header }]);
+    }
+    if (header.toLowerCase() === "content-type" && !submittableContentType(value)) {
+      const data = { header, value };
+      violations.push(["header_unsupported_content_type", data, "header.unsupported_content_type", data]);
+    }
+    const length = Buffer.byteLength(value);
+    if (maxValueLength !== null && length > maxValueLength) {
+      violations.push([
+        "header_too_long",
+        { header, length, maxLength: maxValueLength },
+        "header.too_long",
+        { header },
+      ]);
+    }
+  }
+  for (const header of required) {
+    if (!names.some((name) => name.toLowerCase() === header.toLowerCase())) {
+      violations.push(["header_missing", { header }, "header.missing", { header }]);
+    }
+  }
+  return violations;
+}
+
+// Mirrors `Request::is_idempotent`.
+function isIdempotent(request) {
+  return ["GET", "HEAD", "PUT", "DELETE"].includes(requestMethod(request));
+}
+
+// Mirrors `AdapterCapabilities::check`: method, then scheme, then TLS.
+export function checkCapabilities(capabilities, request) {
+  const method = requestMethod(request);
+  if (!capabilities.methods.includes(method)) {
+    return { capability: "method", requested: method };
+  }
+  const colon = request.url.indexOf(":");
+  if (colon < 0) {
+    return null;
+  }
+  const scheme = request.url.slice(0, colon).toLowerCase();
+  if (capabilities.schemes.length > 0 && !capabilities.schemes.includes(scheme)) {
+    return { capability: "scheme", requested: scheme };
+  }
+  if (scheme === "https" && !capabilities.tls) {
+    return { capability: "tls", requested: scheme };
+  }
+  return null;
+}
+
+// Mirrors `SleepDelay`: the default delay provider really waits.
+const sleep = (millis) => new Promise((resolve) => setTimeout(resolve, millis));
+
+// Mirrors `Backoff::before`.
+function backoffBefore(backoff, attempt) {
+  const { initialMillis = 0, maxMillis = 0 } = backoff ?? {};
+  return Math.min(initialMillis * 2 ** Math.max(attempt - 2, 0), maxMillis);
+}
+
+export const REDACTED = "[redacted]";
+
+// Mirrors `AuthAdapter`: `provider` has a `token()` method, and optionally
+// `refresh()` and a `scheme` other than "Bearer".  A 401 refreshes the token
+// and sends the request once more.
+export class AuthAdapter {
+  constructor(inner, provider, stats) {
+    this.inner = inner;
+    this.provider = provider;
+    this.stats = stats;
+  }
+
+  async authorize(request) {
+    const token = await this.provider.token();
+    const headers = Object.fromEntries(
+      Object.entries(request.headers ?? {}).filter(([key]) => key.toLowerCase() !== "authorization"),
+    );
+    headers.Authorization = `${this.provider.scheme ?? "Bearer"} ${token}`;
+    if (!this.stats.tokens.includes(token)) {
+      this.stats.tokens.push(token);
+    }
+    return { ...request, headers };
+  }
+
+  async fetch(request) {
+    const response = await this.inner.fetch(await this.authorize(request));
+    if (response.status !== 401) {
+      return response;
+    }
+    this.stats.authLog.push(request.url);
+    await this.provider.refresh?.();
+    return this.inner.fetch(await this.authorize(request));
+  }
+
+  capabilities() {
+    return adapterCapabilities(this.inner);
+  }
+}
+
+export const TRACEPARENT_HEADER = "traceparent";
+
+// Mirrors `TraceAdapter`: every request carries the run's trace as a
+// `traceparent` header.
+export class TraceAdapter {
+  constructor(inner, trace) {
+    this.inner = inner;
+    this.traceparent = `00-${trace.traceId}-${trace.spanId}-01`;
+  }
+
+  fetch(request) {
+    return this.inner.fetch(withHeader(request, TRACEPARENT_HEADER, this.traceparent));
+  }
+
+  capabilities() {
+    return adapterCapabilities(this.inner);
+  }
+}
+
+// Mirrors the trace checks of `Input`'s deserialization and
+// `TraceContext::new`: the input's `traceId` and `spanId`, or null.
+function traceContext({ traceId, spanId }) {
+  if (traceId === undefined && spanId === undefined) {
+    return null;
+  }
+  if (traceId === undefined || spanId === undefined) {
+    throw new Error("`traceId` and `spanId` must be given together");
+  }
+  const valid = (id, digits) => new RegExp(`^[0-9a-f]{${digits}}$`).test(id) && /[^0]/.test(id);
+  if (!valid(traceId, 32)) {
+    throw new Error(`\`traceId\` must be 32 lowercase hex digits, not all zero: ${JSON.stringify(traceId)}`);
+  }
+  if (!valid(spanId, 16)) {
+    throw new Error(`\`spanId\` must be 16 lowercase hex digits, not all zero: ${JSON.stringify(spanId)}`);
+  }
+  return { traceId, spanId };
+}
+
+// Mirrors `redact_tokens`.
+function redactTokens(value, tokens) {
+  if (typeof value === "string") {
+    return tokens.reduce((text, token) => text.replaceAll(token, REDACTED), value);
+  }
+  if (Array.isArray(value)) {
+    return value.map((item) => redactTokens(item, tokens));
+  }
+  if (value && typeof value === "object") {
+    return Object.fromEntries(Object.entries(value).map(([key, item]) => [key, redactTokens(item, tokens)]));
+  }
+  return value;
+}
+
+export const SIGNATURE_HEADER = "X-Signature";
+
+// Mirrors `uri_encode`.
+function uriEncode(bytes) {
+  let encoded = "";
+  for (const byte of bytes) {
+    const char = String.fromCharCode(byte);
+    encoded += /[A-Za-z0-9\-._~]/.test(char) ? char : `%${byte.toString(16).toUpperCase().padStart(2, "0")}`;
+  }
+  return encoded;
+}
+
+// Mirrors `percent_decode`.
+function percentDecode(text) {
+  const bytes = Buffer.from(text, "utf8");
+  const decoded = [];
+  for (let index = 0; index < bytes.length; ) {
+    const digits = bytes.subarray(index + 1, index + 3).toString("latin1");
+    if (bytes[index] === 0x25 && /^[0-9A-Fa-f]{2}$/.test(digits)) {
+      decoded.push(parseInt(digits, 16));
+      index += 3;
+    } else {
+      decoded.push(bytes[index]);
+      index += 1;
+    }
+  }
+  return Buffer.from(decoded);
+}
+
+// Mirrors `canonical_request`: the canonical form of `request` signing the
+// headers in `names`, and the sorted names of those it has.
+export function canonicalRequest(request, names) {
+  const method = requestMethod(request).toUpperCase();
+  let rest = request.url.includes("://") ? request.url.slice(request.url.indexOf("://") + 3) : request.url;
+  rest = rest.split("#")[0];
+  const queryStart = rest.indexOf("?");
+  const query = queryStart === -1 ? "" : rest.slice(queryStart + 1);
+  rest = queryStart === -1 ? rest : rest.slice(0, queryStart);
+  const path = rest.includes("/") ? rest.slice(rest.indexOf("/")) : "";
+  const canonicalPath = path === "" ? "/" : path.split("/").map((segment) => uriEncode(percentDecode(segment))).join("/");
+
+  const params = query
+    .split("&")
+    .filter((param) => param !== "")
+    .map((param) => {
+      const split = param.indexOf("=");
+      const [name, value] = split === -1 ? [param, ""] : [param.slice(0, split), param.slice(split + 1)];
+      return [uriEncode(percentDecode(name)), uriEncode(percentDecode(value))];
+    })
+    .sort(([a, x], [b, y]) => (a === b ? (x < y ? -1 : x > y ? 1 : 0) : a < b ? -1 : 1));
+  const canonicalQuery = params.map(([name, value]) => `${name}=${value}`).join("&");
+
+  let headers = "";
+  const signed = [...new Set(names.map((name) => name.toLowerCase()))].sort().filter((name) => {
+    const values = Object.entries(request.headers ?? {})
+      .filter(([key]) => key.toLowerCase() === name)
+      .map(([, value]) => String(value).split(/\s+/).filter(Boolean).join(" "));
+    if (values.length === 0 && name === "host") {
+      values.push(hostOf(request.url));
+    }
+    if (values.length === 0) {
+      return false;
+    }
+    headers += `${name}:${values.sort().join(",")}\n`;
+    return true;
+  });
+
+  const bodyHash = createHash("sha256")
+    .update(request.body ?? Buffer.alloc(0))
+    .digest("hex");
+  const canonical = [method, canonicalPath, canonicalQuery, headers, signed.join(";"), bodyHash].join("\n");
+  return { canonical, signed };
+}
+
+// Mirrors `SigningMiddleware`: `keys` is a `{ id, secret }` key or a
+// function returning one for each request.
+export class SigningMiddleware {
+  constructor(keys, { signedHeaders = ["host", "content-type"], header = SIGNATURE_HEADER } = {}) {
+    this.keys = keys;
+    this.signedHeaders = signedHeaders;
+    this.header = header;
+  }
+
+  async sign(request) {
+    const names = this.signedHeaders.filter((name) => name.toLowerCase() !== this.header.toLowerCase());
+    const { canonical, signed } = canonicalRequest(request, names);
+    const key = typeof this.keys === "function" ? await this.keys() : this.keys;
+    const signature = createHmac("sha256", key.secret).update(canonical).digest("hex");
+    return `HMAC-SHA256 KeyId=${key.id}, SignedHeaders=${signed.join(";")}, Signature=${signature}`;
+  }
+
+  async beforeFetch(request) {
+    const signature = await this.sign(request);
+    for (const key of Object.keys(request.headers)) {
+      if (key.toLowerCase() === this.header.toLowerCase()) {
+        delete request.headers[key];
+      }
+    }
+    request.headers[this.header] = signature;
+  }
+}
+
+// Mirrors `MiddlewareAdapter`: each middleware may define `beforeFetch(request)`,
+// which can change the request in place or return a response that
+// short-circuits the stack, and `afterFetch(request, response)`, which can
+// change the response in place.
+class MiddlewareAdapter {
+  constructor(inner, stack) {
+    this.inner = inner;
+    this.stack = stack;
+  }
+
+  async fetch(request) {
+    const current = { ...request, headers: { ...request.headers } };
+    let ran = 0;
+    let response;
+    for (const middleware of this.stack) {
+      ran += 1;
+      response = await middleware.beforeFetch?.(current);
+      if (response) {
+        break;
+      }
+    }
+    response ??= await this.inner.fetch(current);
+    for (const middleware of this.stack.slice(0, ran).reverse()) {
+      await middleware.afterFetch?.(current, response);
+    }
+    return response;
+  }
+
+  capabilities() {
+    return adapterCapabilities(this.inner);
+  }
+}
+
+// Mirrors `RetryAdapter`: `policy` holds the adapter policy's `retries`
+// (attempts after the first), `retryOn`, `retryNonIdempotent` and
+// `backoff`; `delay` waits out the backoff.
 class RetryAdapter {
-  constructor(inner, retries = 3) {
+  constructor(inner, policy, delay = sleep, stats = { cacheHits: 0, retries: 0, retryLog: [] }) {
     this.inner = inner;
-    this.retries = retries;
+    this.policy = policy;
+    this.delay = delay;
+    this.stats = stats;
   }
 
-  async fetch(url, headers) {
-    let lastError;
-    for (let attempt = 0; attempt < this.retries; attempt += 1) {
+  retriesStatus(status) {
+    const retryOn = this.policy.retryOn ?? [];
+    return (
+      (status < 200 || status >= 300) && status !== 304 && (retryOn.length === 0 || retryOn.includes(status))
+    );
+  }
+
+  async fetch(request) {
+    // A failed POST may still have been applied, so by default only
+    // idempotent requests are retried.
+    if (!this.policy.retryNonIdempotent && !isIdempotent(request)) {
+      return this.inner.fetch(request);
+    }
+    for (let attempt = 1; ; attempt += 1) {
+      let previousStatus;
       try {
-        return await this.inner.fetch(url, headers);
+        const response = await this.inner.fetch(request);
+        if (!this.retriesStatus(response.status) || attempt > this.policy.retries) {
+          return response;
+        }
+        previousStatus = response.status;
       } catch (error) {
-        lastError = error;
+        if (attempt > this.policy.retries) {
+          throw error;
+        }
+        previousStatus = 0;
+      }
+      const delayMillis = backoffBefore(this.policy.backoff, attempt + 1);
+      if (delayMillis > 0) {
+        await this.delay(delayMillis);
       }
+      this.stats.retries += 1;
+      this.stats.retryLog.push({ attempt: attempt + 1, previousStatus, delayMillis });
     }
+  }
 
-    throw lastError ?? new Error("retry adapter exhausted without an error");
+  capabilities() {
+    return adapterCapabilities(this.inner);
   }
 }
 
+// Mirrors `CircuitOpen`: a request failed fast because its host's circuit
+// is open.
+export class CircuitOpen extends Error {
+  constructor(host) {
+    super(`circuit for ${host} is open`);
+    this.host = host;
+  }
+}
+
+// Mirrors `CircuitBreakerAdapter`: `circuits` maps each host to its
+// `{ state, failures, rejected }` and outlasts single runs.
+class CircuitBreakerAdapter {
+  constructor(inner, policy, circuits, stats) {
+    this.inner = inner;
+    this.policy = policy;
+    this.circuits = circuits;
+    this.stats = stats;
+  }
+
+  transition(host, circuit, to) {
+    this.stats.circuitLog.push({ host, from: circuit.state, to });
+    circuit.state = to;
+  }
+
+  async fetch(request) {
+    const host = hostOf(request.url);
+    if (!this.circuits.has(host)) {
+      this.circuits.set(host, { state: "closed", failures: 0, rejected: 0 });
+    }
+    const circuit = this.circuits.get(host);
+    if (circuit.state === "open") {
+      if (circuit.rejected < this.policy.cooldownRequests) {
+        circuit.rejected += 1;
+        throw new CircuitOpen(host);
+      }
+      this.transition(host, circuit, "half_open");
+    }
+    let response;
+    try {
+      response = await this.inner.fetch(request);
+    } catch (error) {
+      this.recordFailure(host, circuit);
+      throw error;
+    }
+    // A 4xx is the caller's problem, not the host's.
+    if (response.status >= 500) {
+      this.recordFailure(host, circuit);
+    } else {
+      circuit.failures = 0;
+      if (circuit.state === "half_open") {
+        this.transition(host, circuit, "closed");
+      }
+    }
+    return response;
+  }
+
+  recordFailure(host, circuit) {
+    circuit.failures += 1;
+    if (circuit.state === "half_open" || circuit.failures >= this.policy.failureThreshold) {
+      circuit.rejected = 0;
+      this.transition(host, circuit, "open");
+    }
+  }
+
+  capabilities() {
+    return adapterCapabilities(this.inner);
+  }
+}
+
+const CREDENTIAL_HEADERS = ["authorization", "cookie", "proxy-authorization"];
+
+function fnv1a(value) {
+  let hash = 0xcbf29ce484222325n;
+  for (const byte of new TextEncoder().encode(value)) {
+    hash = ((hash ^ BigInt(byte)) * 0x100000001b3n) & 0xffffffffffffffffn;
+  }
+  return hash.toString(16).padStart(16, "0");
+}
+
+// Mirrors `cache_key`: the URL plus the headers sorted by name, with
+// credential values hashed.
+export function cacheKey(request) {
+  const headers = Object.entries(request.headers ?? {})
+    .map(([name, value]) => {
+      const lower = name.toLowerCase();
+      return [lower, CREDENTIAL_HEADERS.includes(lower) ? `#${fnv1a(value)}` : value];
+    })
+    .sort(([a, av], [b, bv]) => (a < b ? -1 : a > b ? 1 : av < bv ? -1 : av > bv ? 1 : 0));
+  return [request.url, ...headers.map(([name, value]) => `${name}: ${value}`)].join("\n");
+}
+
+// Mirrors `ResponseCache`.  The reference implementation has no clock, so
+// entry ages are always counted in lookups.
+export class ResponseCache {
+  constructor() {
+    this.entries = new Map();
+    this.ticks = 0;
+    this.uses = 0;
+  }
+
+  get size() {
+    return this.entries.size;
+  }
+}
+
+// Mirrors `NetworkResponse::header`.
+function responseHeader(response, name) {
+  const entry = Object.entries(response.headers ?? {}).find(([key]) => key.toLowerCase() === name);
+  return entry?.[1];
+}
+
+// Mirrors `Request::with_header`.
+function withHeader(request, name, value) {
+  const headers = Object.fromEntries(
+    Object.entries(request.headers ?? {}).filter(([key]) => key.toLowerCase() !== name.toLowerCase()),
+  );
+  return { ...request, headers: { ...headers, [name]: value } };
+}
+
+// Mirrors `CacheAdapter::conditional`: `request` made conditional on the
+// validators of `cached`, or null when it has none.
+function conditionalRequest(request, cached) {
+  const etag = responseHeader(cached, "etag");
+  if (etag !== undefined) {
+    return withHeader(request, "If-None-Match", etag);
+  }
+  const modified = responseHeader(cached, "last-modified");
+  return modified === undefined ? null : withHeader(request, "If-Modified-Since", modified);
+}
+
+// Mirrors `CacheAdapter`: `limits` holds the adapter policy's `cacheLimits`.
 class CacheAdapter {
-  constructor(inner) {
+  constructor(inner, limits, cache, stats = { cacheHits: 0, retries: 0, cacheLog: [] }) {
     this.inner = inner;
-    this.cache = new Map();
+    this.limits = limits;
+    this.cache = cache;
+    this.stats = stats;
   }
 
-  async fetch(url, headers) {
-    const cacheKey = JSON.stringify({ url, headers });
-    if (this.cache.has(cacheKey)) {
-      return this.cache.get(cacheKey);
+  async fetch(request) {
+    // Only GET responses are cached; other methods change server state.
+    if (requestMethod(request) !== "GET") {
+      return this.inner.fetch(request);
+    }
+    const key = cacheKey(request);
+    const now = (this.cache.ticks += 1);
+    const entry = this.cache.entries.get(key);
+    let conditional = null;
+    if (entry) {
+      const ttl = this.limits.ttlMillis;
+      if (ttl === null || now - entry.storedAt < ttl) {
+        entry.lastUsed = this.cache.uses += 1;
+        this.log(request, "cache_hit");
+        return entry.response;
+      }
+      conditional = conditionalRequest(request, entry.response);
+      if (!conditional) {
+        this.cache.entries.delete(key);
+      }
     }
 
-    const response = await this.inner.fetch(url, headers);
-    this.cache.set(cacheKey, response);
+    let response;
+    try {
+      response = await this.inner.fetch(conditional ?? request);
+    } catch (error) {
+      // A failed revalidation keeps the expired entry for the next lookup.
+      this.log(request, "cache_miss");
+      throw error;
+    }
+    if (conditional && response.status === 304 && this.cache.entries.has(key)) {
+      const revalidated = this.cache.entries.get(key);
+      revalidated.storedAt = now;
+      revalidated.lastUsed = this.cache.uses += 1;
+      this.log(request, "cache_revalidated");
+      return revalidated.response;
+    }
+    this.log(request, "cache_miss");
+    this.cache.entries.delete(key);
+    const max = this.limits.maxEntries;
+    if (max === 0) {
+      return response;
+    }
+    while (max !== null && this.cache.entries.size >= max) {
+      let oldest = null;
+      for (const [candidate, { lastUsed }] of this.cache.entries) {
+        if (oldest === null || lastUsed < this.cache.entries.get(oldest).lastUsed) {
+          oldest = candidate;
+        }
+      }
+      this.cache.entries.delete(oldest);
+    }
+    // Entries are served as they were decoded, so hits decode nothing.
+    const { decoded, ...stored } = response;
+    this.cache.entries.set(key, { response: stored, storedAt: now, lastUsed: (this.cache.uses += 1) });
     return response;
   }
+
+  log(request, type) {
+    if (type !== "cache_miss") {
+      this.stats.cacheHits += 1;
+    }
+    this.stats.cacheLog.push({ type, url: request.url });
+  }
+
+  capabilities() {
+    return adapterCapabilities(this.inner);
+  }
+}
+
+// Mirrors `BudgetExceeded`: a fetch over one of the contract's execution limits.
+export class BudgetExceeded extends Error {
+  constructor(limit, max) {
+    super(`${limit} of ${max} exceeded`);
+    this.limit = limit;
+    this.max = max;
+  }
 }
 
-class HostFetchAdapter {
-  async fetch(url, headers) {
-    if (url === "uma-fixture://sample-post") {
+// Mirrors `HostFetchAdapter`, enforcing the contract's execution limits.
+export class HostFetchAdapter {
+  constructor(limits = {}) {
+    this.limits = limits;
+  }
+
+  async fetch(request) {
+    let method = requestMethod(request);
+    if (method === "GET" && request.url === "uma-fixture://sample-post") {
       const fixturePath = path.resolve(__dirname, "../../tests/fixtures/sample_post.json");
+      const body = await fs.readFile(fixturePath, "utf8");
+      this.checkBodyLength(Buffer.byteLength(body));
       return {
         status: 200,
         headers: {
           "content-type": "application/json",
         },
-        body: await fs.readFile(fixturePath, "utf8"),
+        body,
       };
     }
 
-    const response = await fetch(url, {
-      method: "GET",
-      headers,
-    });
+    const { maxRedirects, timeoutMillis } = this.limits;
+    const signal = timeoutMillis === undefined ? undefined : AbortSignal.timeout(timeoutMillis);
+    let url = request.url;
+    let body = request.body;
+    let redirects = 0;
+    try {
+      for (;;) {
+        const response = await fetch(url, {
+          method,
+          headers: request.headers ?? {},
+          body,
+          redirect: maxRedirects === undefined ? "follow" : "manual",
+          signal,
+        });
+        if (maxRedirects !== undefined && response.status >= 300 && response.status < 400) {
+          const location = response.headers.get("location");
+          if (location) {
+            if (redirects === maxRedirects) {
+              throw new BudgetExceeded("maxRedirects", maxRedirects);
+            }
+            redirects += 1;
+            url = new URL(location, url).href;
+            // Like reqwest, a 303, or a 301/302 after a POST, continues as a GET.
+            if (response.status === 303 || ((response.status === 301 || response.status === 302) && method === "POST")) {
+              method = "GET";
+              body = undefined;
+            }
+            continue;
+          }
+        }
+        // Node's fetch has already undone any Content-Encoding without
+        // saying how large the body was, so no decoding is recorded.
+        const headers = Object.fromEntries(response.headers.entries());
+        if (headers["content-encoding"] !== undefined) {
+          delete headers["content-encoding"];
+          delete headers["content-length"];
+        }
+        return {
+          status: response.status,
+          headers,
+          body: await this.readBody(response),
+        };
+      }
+    } catch (error) {
+      if (error.name === "TimeoutError") {
+        throw new BudgetExceeded("timeoutMillis", timeoutMillis);
+      }
+      throw error;
+    }
+  }
+
+  // Reads at most one chunk past `maxBodyBytes`, enough to tell the body is
+  // over it, and returns the bytes as received.
+  async readBody(response) {
+    const chunks = [];
+    let length = 0;
+    for await (const chunk of response.body ?? []) {
+      chunks.push(chunk);
+      length += chunk.length;
+      this.checkBodyLength(length);
+    }
+    return Buffer.concat(chunks);
+  }
+
+  checkBodyLength(length) {
+    const max = this.limits.maxBodyBytes;
+    if (max !== undefined && length > max) {
+      throw new BudgetExceeded("maxBodyBytes", max);
+    }
+  }
+
+  capabilities() {
+    return {
+      ...DEFAULT_CAPABILITIES,
+      methods: ["GET", "POST", "PUT", "DELETE"],
+      schemes: ["http", "https", "uma-fixture"],
+      compression: true,
+      timeouts: true,
+    };
+  }
+}
+
+// Mirrors `ReplayAdapter`: serves the interactions of a parsed `cassette`,
+// or the recorded sample post without one, and never touches the network.
+export class ReplayAdapter {
+  constructor(cassette = null) {
+    this.interactions = cassette?.interactions ?? null;
+    this.played = new Map();
+  }
+
+  static async load(file) {
+    return new ReplayAdapter(JSON.parse(await fs.readFile(file, "utf8")));
+  }
+
+  async fetch(request) {
+    if (this.interactions === null) {
+      if (request.url !== "uma-fixture://sample-post") {
+        throw new Error(`no recorded response for ${request.url}`);
+      }
+      const body = await fs.readFile(path.resolve(__dirname, "../../tests/fixtures/sample_post.json"), "utf8");
+      return { status: 200, headers: { "content-type": "application/json" }, body };
+    }
+    const method = requestMethod(request);
+    const responses = this.interactions
+      .filter((interaction) => interaction.method.toUpperCase() === method && interaction.url === request.url)
+      .map((interaction) => interaction.response);
+    if (responses.length === 0) {
+      throw new Error(`no recorded response for ${request.url}`);
+    }
+    const key = `${method} ${request.url}`;
+    const count = this.played.get(key) ?? 0;
+    this.played.set(key, count + 1);
+    const { status, headers = {}, body = "", bodyBase64 } = responses[Math.min(count, responses.length - 1)];
+    return { status, headers: { ...headers }, body: bodyBase64 === undefined ? body : Buffer.from(bodyBase64, "base64") };
+  }
+
+  capabilities() {
+    if (this.interactions === null) {
+      return { ...DEFAULT_CAPABILITIES, schemes: ["uma-fixture"] };
+    }
+    const distinct = (values) => [...new Set(values)].sort();
     return {
-      status: response.status,
-      headers: Object.fromEntries(response.headers.entries()),
-      body: await response.text(),
+      ...DEFAULT_CAPABILITIES,
+      methods: distinct(this.interactions.map((interaction) => interaction.method.toUpperCase())),
+      schemes: distinct(this.interactions.map((interaction) => interaction.url.split("://")[0].toLowerCase())),
     };
   }
 }
 
-function createAdapterManager(adapter) {
-  let active = adapter ?? new HostFetchAdapter();
-  let implName = adapter ? "custom" : "host-fetch";
+// Mirrors `RecordedResponse`: a UTF-8 body as text, any other as base64.
+function recordedBody(body) {
+  try {
+    return { body: bodyText(body) };
+  } catch {
+    return { body: "", bodyBase64: Buffer.from(body).toString("base64") };
+  }
+}
+
+// Mirrors `RecordingAdapter`: appends what `inner` answers to the cassette
+// at `file`, rewriting it after every response.
+export class RecordingAdapter {
+  constructor(inner, file, cassette = { interactions: [] }) {
+    this.inner = inner;
+    this.file = file;
+    this.cassette = cassette;
+  }
+
+  static async append(inner, file) {
+    const text = await fs.readFile(file, "utf8").catch((error) => {
+      if (error.code === "ENOENT") {
+        return null;
+      }
+      throw error;
+    });
+    return new RecordingAdapter(inner, file, text === null ? undefined : JSON.parse(text));
+  }
+
+  async fetch(request) {
+    const response = await this.inner.fetch(request);
+    const headers = Object.fromEntries(Object.entries(response.headers ?? {}).sort(([a], [b]) => (a < b ? -1 : 1)));
+    this.cassette.interactions.push({
+      method: requestMethod(request),
+      url: request.url,
+      response: { status: response.status, headers, ...recordedBody(response.body ?? "") },
+    });
+    await fs.writeFile(this.file, `${JSON.stringify(this.cassette, null, 2)}\n`);
+    return response;
+  }
+
+  capabilities() {
+    return adapterCapabilities(this.inner);
+  }
+}
+
+// Mirrors `AdapterRegistry::builtin` on native hosts: factories taking the
+// contract's execution limits, by name.
+export const BUILTIN_ADAPTERS = {
+  "host-fetch": (limits) => new HostFetchAdapter(limits),
+  replay: () => new ReplayAdapter(),
+};
 
-  if (process.env.UMA_ENABLE_RETRY) {
-    active = new RetryAdapter(active, 3);
+// Mirrors `Unbound`: stands in for an adapter name nothing is registered
+// under.  Runs bound to it fail before fetching.
+const UNBOUND = {
+  async fetch() {
+    throw new Error("no adapter is bound");
+  },
+  capabilities() {
+    return { ...DEFAULT_CAPABILITIES, methods: [] };
+  },
+};
+
+// Mirrors `Runtime::bind_adapter`: the adapter for one run and its binding
+// name, with `unknown` set when the contract binds a name not in `adapters`.
+function bindAdapter(adapter, adapters, contract) {
+  if (adapter) {
+    return { adapter, implName: "custom", unknown: false };
+  }
+  const implName = contract.bindings?.["network.fetch"] ?? "host-fetch";
+  const factory = adapters[implName];
+  return factory
+    ? { adapter: factory(contract.execution ?? {}), implName, unknown: false }
+    : { adapter: UNBOUND, implName, unknown: true };
+}
+
+// Mirrors `AdapterPolicy::with_overrides` for the settings `with_env` reads.
+function resolveAdapterPolicy(contract) {
+  const section = contract.adapter ?? {};
+  const retry = process.env.UMA_ENABLE_RETRY
+    ? { retries: 3, retryOn: [], retryNonIdempotent: false }
+    : {
+        retries: section.retries ?? 0,
+        retryOn: section.retryOn ?? [],
+        retryNonIdempotent: section.retryNonIdempotent ?? false,
+      };
+  return {
+    ...retry,
+    backoff: { initialMillis: section.backoff?.initialMillis ?? 0, maxMillis: section.backoff?.maxMillis ?? 0 },
+    circuitBreaker: {
+      failureThreshold: section.circuitBreaker?.failureThreshold ?? 0,
+      cooldownRequests: section.circuitBreaker?.cooldownRequests ?? 0,
+    },
+    cache: process.env.UMA_ENABLE_CACHE ? true : (section.cache ?? false),
+    cacheLimits: {
+      ttlMillis: section.cacheLimits?.ttlMillis ?? null,
+      maxEntries: section.cacheLimits?.maxEntries ?? null,
+    },
+    allowedHosts: section.allowedHosts ?? [],
+    allowedSchemes: section.allowedSchemes ?? [],
+  };
+}
+
+// Mirrors `host_of`.
+function hostOf(url) {
+  const rest = url.includes("://") ? url.slice(url.indexOf("://") + 3) : url;
+  const authority = rest.split(/[/?#]/)[0];
+  return authority.slice(authority.lastIndexOf("@") + 1).toLowerCase();
+}
+
+// Mirrors `ThreadManager::plan`: request indices grouped into waves that send
+// at most `maxPerHost` requests to one host, and the requests pushed past the
+// first wave.
+function planWaves(hosts, maxPerHost) {
+  const cap = maxPerHost == null ? null : Math.max(maxPerHost, 1);
+  const waves = [];
+  const counts = [];
+  const queued = [];
+  hosts.forEach((host, index) => {
+    let wave = cap == null ? 0 : counts.findIndex((perHost) => (perHost.get(host) ?? 0) < cap);
+    if (wave === -1) {
+      wave = counts.length;
+    }
+    if (wave === counts.length) {
+      counts.push(new Map());
+      waves.push([]);
+    }
+    counts[wave].set(host, (counts[wave].get(host) ?? 0) + 1);
+    waves[wave].push(index);
+    if (wave > 0) {
+      queued.push({ index, host, wave });
+    }
+  });
+  return { waves, queued };
+}
+
+// Mirrors `check_url`: null when allowed, otherwise the denied rule and value.
+export function checkUrl(policy, url) {
+  const allowed = (list, value) => list.length === 0 || list.some((entry) => entry.toLowerCase() === value);
+  const scheme = url.includes("://") ? url.slice(0, url.indexOf("://")).toLowerCase() : "";
+  if (!allowed(policy.allowedSchemes, scheme)) {
+    return { rule: "allowedSchemes", value: scheme };
+  }
+  const host = hostOf(url).replace(/:\d*$/, "");
+  if (!allowed(policy.allowedHosts, host)) {
+    return { rule: "allowedHosts", value: host };
+  }
+  return null;
+}
+
+function createAdapterManager({ adapter, implName }, policy, delay, circuits, responses, middleware, auth, trace) {
+  let active = adapter;
+  const stats = { cacheHits: 0, retries: 0, cacheLog: [], retryLog: [], circuitLog: [], authLog: [], tokens: [] };
+
+  if (trace) {
+    active = new TraceAdapter(active, trace);
+  }
+
+  if (auth) {
+    active = new AuthAdapter(active, auth, stats);
+    implName = `auth-${implName}`;
+  }
+
+  if (middleware.length > 0) {
+    active = new MiddlewareAdapter(active, middleware);
+    implName = `middleware-${implName}`;
+  }
+
+  if (policy.retries > 0) {
+    active = new RetryAdapter(active, policy, delay, stats);
     implName = `retry-${implName}`;
   }
 
-  if (process.env.UMA_ENABLE_CACHE) {
-    active = new CacheAdapter(active);
+  if (policy.circuitBreaker.failureThreshold > 0) {
+    active = new CircuitBreakerAdapter(active, policy.circuitBreaker, circuits, stats);
+    implName = `circuit-${implName}`;
+  }
+
+  if (policy.cache) {
+    active = new CacheAdapter(active, policy.cacheLimits, responses, stats);
     implName = `cache-${implName}`;
   }
 
   return {
     adapter: active,
+    stats,
     binding: {
       impl: implName,
       host: "native",
@@ -140,76 +1559,366 @@ function createAdapterManager(adapter) {
   };
 }
 
-export async function runJson(inputJson, adapter) {
+// Mirrors `fetch_resource`: fetch `request` and normalize the response with
+// the normalizer named `resource`, or the one for its content type.  Returns
+// the records, or null after the error that prevented them, the name and
+// kind of the normalizer used, and the events of the fetch, each passed
+// through `tag`, for the run to log in request order.
+// Mirrors `read_body`.  Adapters here return whole bodies, so a streamed
+// run splits the body into `streamChunkBytes` chunks after the fetch, which
+// gives the same events and records as the Rust runtime's streaming path.
+function readBody(response, name, normalizer, streamChunkBytes, progress) {
+  if (streamChunkBytes === null) {
+    return normalizer.normalize(response.body);
+  }
+  const body = Buffer.from(response.body);
+  let parser = normalizer.normalizesElements ? new JsonArrayStream() : null;
+  const records = [];
+  let items = 0;
+  for (let offset = 0, chunk = 1; offset < body.length; offset += streamChunkBytes, chunk += 1) {
+    const bytes = body.subarray(offset, offset + streamChunkBytes);
+    if (parser) {
+      try {
+        for (const element of parser.push(bytes)) {
+          items += 1;
+          records.push(...normalizer.normalizeElement(element));
+        }
+      } catch (error) {
+        if (!(error instanceof NotAnArray)) {
+          throw error;
+        }
+        parser = null;
+      }
+    }
+    const data = { chunk, bytes: offset + bytes.length };
+    if (parser?.inArray()) {
+      data.records = records.length;
+    }
+    progress(data);
+  }
+  if (parser?.inArray()) {
+    parser.finish();
+    return { records, details: collectionDetails(name, records.length, items) };
+  }
+  return normalizer.normalize(response.body);
+}
+
+async function fetchResource(adapterManager, request, registry, resource, streamChunkBytes, tag) {
+  const log = [];
+  const eventBus = { emit: (type, data) => log.push([type, data]) };
+  eventBus.emit("fetch_request", tag({ url: request.url, method: requestMethod(request) }));
+  // The cache lookup, token refreshes, attempts the retry wrapper made
+  // after the first, then circuit state changes, in order.
+  const emitAdapterLog = () => {
+    for (const { type, url } of adapterManager.stats.cacheLog.splice(0)) {
+      eventBus.emit(type, tag({ url }));
+    }
+    for (const url of adapterManager.stats.authLog.splice(0)) {
+      eventBus.emit("auth_refresh", tag({ url }));
+    }
+    for (const retry of adapterManager.stats.retryLog.splice(0)) {
+      eventBus.emit("fetch_retry", tag(retry));
+    }
+    for (const transition of adapterManager.stats.circuitLog.splice(0)) {
+      eventBus.emit("circuit_state", tag(transition));
+    }
+  };
+
+  let response;
+  try {
+    response = await adapterManager.adapter.fetch(request);
+  } catch (error) {
+    emitAdapterLog();
+    eventBus.emit("fetch_response", tag({ status: 0 }));
+    eventBus.emit(
+      "error",
+      tag(
+        error instanceof BudgetExceeded
+          ? errorEventData("network.budget_exceeded", { limit: error.limit, max: error.max })
+          : error instanceof CircuitOpen
+            ? errorEventData("network.circuit_open", { host: error.host })
+            : errorEventData("network.failure", { detail: error.message }),
+      ),
+    );
+    return { records: null, normalizer: null, log };
+  }
+  emitAdapterLog();
+  eventBus.emit("fetch_response", tag({ status: response.status }));
+  // Mirrors `ContentDecoding`: a body the adapter decompressed.
+  if (response.decoded) {
+    const { encoding, encodedBytes, decodedBytes } = response.decoded;
+    eventBus.emit("decompressed", tag({ encoding, encodedBytes, decodedBytes }));
+  }
+  const name = resource ?? normalizerForContentType(registry.contentTypes, responseHeader(response, "content-type"));
+  const normalizer = Object.hasOwn(registry.normalizers, name) ? registry.normalizers[name] : null;
+  if (!normalizer) {
+    eventBus.emit("error", tag(errorEventData("normalizer.unknown", { normalizer: name })));
+    return { records: null, normalizer: null, log };
+  }
+  const used = [name, normalizer.kind];
+  try {
+    const progress = (data) => eventBus.emit("fetch_progress", tag(data));
+    const { records, details } = readBody(response, name, normalizer, streamChunkBytes, progress);
+    eventBus.emit("normalized", tag(details));
+    return { records, normalizer: used, log };
+  } catch (error) {
+    eventBus.emit(
+      "error",
+      tag(
+        !(error instanceof ShapeError)
+          ? errorEventData("response.parse_error", { status: response.status, detail: error.message })
+          : name === DEFAULT_NORMALIZER
+            ? errorEventData("response.invalid_post", { status: response.status })
+            : errorEventData("response.invalid_resource", { status: response.status, resource: name }),
+      ),
+    );
+    return { records: null, normalizer: used, log };
+  }
+}
+
+// `contract` and `delay` replace the bundled contract and the sleeping delay
+// provider, as `RuntimeBuilder::contract` and `RuntimeBuilder::delay` do,
+// `adapters` adds factories to `BUILTIN_ADAPTERS` as
+// `RuntimeBuilder::register_adapter` does, `middleware` is the stack
+// `RuntimeBuilder::middleware` builds, and `normalizers` and `contentTypes`
+// add to `BUILTIN_NORMALIZERS` and `BUILTIN_CONTENT_TYPES` as
+// `RuntimeBuilder::register_normalizer` and
+// `RuntimeBuilder::normalize_content_type` do.
+// `streamChunks` streams response bodies as `RuntimeBuilder::stream_chunks`
+// does, `auth` authorizes every request as `RuntimeBuilder::auth` does,
+// and `sensitiveKeys` replaces the runtime policy's patterns as
+// `RuntimeBuilder::sensitive_keys` does.  `circuits` and `responses` carry
+// circuit breaker and cache state between runs, as a `Runtime` does; pass
+// the same ones to every run that should share them.
+export async function runJson(
+  inputJson,
+  adapter,
+  {
+    contract,
+    delay = sleep,
+    circuits = new Map(),
+    responses = new ResponseCache(),
+    middleware = [],
+    adapters = {},
+    normalizers = {},
+    contentTypes = {},
+    streamChunks = null,
+    auth = null,
+    sensitiveKeys = null,
+    subscribers = [],
+    timeSource = null,
+  } = {},
+) {
   const input = JSON.parse(inputJson);
-  const eventBus = new EventBus();
+  // Mirrors the checks of `Input`'s deserialization.
+  if (input.request !== undefined && input.requests !== undefined) {
+    throw new Error("`request` and `requests` are exclusive");
+  }
+  if (input.request === undefined && input.requests === undefined) {
+    throw new Error("missing field `request`");
+  }
+  if (input.request === undefined && input.requests.length === 0) {
+    throw new Error("`requests` must not be empty");
+  }
+  const trace = traceContext(input);
+  const registry = {
+    normalizers: { ...BUILTIN_NORMALIZERS, ...normalizers },
+    contentTypes: {
+      ...BUILTIN_CONTENT_TYPES,
+      ...Object.fromEntries(
+        Object.entries(contentTypes).map(([contentType, name]) => [
+          contentType.split(";")[0].trim().toLowerCase(),
+          name,
+        ]),
+      ),
+    },
+  };
+  const resource = input.resource ?? null;
+  contract ??= await loadContract();
+  const runtimePolicy = await loadRuntimePolicy();
+  const eventBus = new EventBus(
+    new Secrets(sensitiveKeys ?? runtimePolicy.secrets?.sensitiveKeys ?? DEFAULT_SENSITIVE_KEYS),
+    timeSource,
+    trace,
+  );
+  for (const listener of subscribers) {
+    eventBus.subscribe(listener);
+  }
   eventBus.emit("start", { runId: input.runId });
 
-  const allowedHeaders = new Set(["accept", "content-type", "authorization"]);
-  let headerValidationFailed = false;
+  let validationFailed = false;
+  if (contract.inputMode === "strict" || input.inputMode === "strict") {
+    const fields = unexpectedFields(contract.input, input);
+    if (fields.length > 0) {
+      eventBus.emit("error", errorEventData("input.validation_error", { fields }));
+      validationFailed = true;
+    }
+  }
+
+  // Mirrors the batch handling of `execute`: every event about one of a
+  // batch's requests names it by its index.
+  const batch = input.request === undefined;
+  const requests = batch ? input.requests : [input.request];
+  const tag = (index, data) => (batch ? { ...data, request: index } : data);
 
-  for (const [key, value] of Object.entries(input.request.headers ?? {})) {
-    const lower = key.toLowerCase();
-    if (!allowedHeaders.has(lower)) {
-      eventBus.emit("error", { error: `unexpected header ${key}` });
-      headerValidationFailed = true;
+  requests.forEach((request, index) => {
+    for (const [eventType, data, code, params] of headerViolations(contract.headers, request.headers ?? {})) {
+      eventBus.emit(eventType, tag(index, data));
+      eventBus.emit("error", tag(index, errorEventData(code, params)));
+      validationFailed = true;
     }
-    if (String(value).length > 1024) {
-      eventBus.emit("error", { error: `header ${key} too long` });
-      headerValidationFailed = true;
+  });
+
+  // Mirrors the normalizer check of `execute`: with a resource named, its
+  // normalizer is the run's only one.
+  const normalizersUsed = [];
+  if (resource !== null) {
+    if (Object.hasOwn(registry.normalizers, resource)) {
+      normalizersUsed.push([resource, registry.normalizers[resource].kind]);
+    } else {
+      eventBus.emit("error", errorEventData("normalizer.unknown", { normalizer: resource }));
+      validationFailed = true;
     }
   }
 
-  let normalizedPost = null;
+  const bodies = requests.map(requestBody);
   let finalState = "terminated";
-  const adapterManager = createAdapterManager(adapter);
-
-  if (!headerValidationFailed) {
-    eventBus.emit("fetch_request", { url: input.request.url });
-
-    try {
-      const response = await adapterManager.adapter.fetch(input.request.url, input.request.headers ?? {});
-      eventBus.emit("fetch_response", { status: response.status });
+  const adapterPolicy = resolveAdapterPolicy(contract);
+  const bound = bindAdapter(adapter, { ...BUILTIN_ADAPTERS, ...adapters }, contract);
+  // One wrapper chain per request, sharing the circuit and cache state.
+  const adapterManagers = requests.map(() =>
+    createAdapterManager(bound, adapterPolicy, delay, circuits, responses, middleware, auth, trace),
+  );
+  const adapterManager = adapterManagers[0];
+  if (bound.unknown) {
+    eventBus.emit("error", errorEventData("adapter.unknown", { adapter: bound.implName }));
+    validationFailed = true;
+  } else {
+    requests.forEach((request, index) => {
+      const denial = checkUrl(adapterPolicy, request.url);
+      const missing = denial ? null : checkCapabilities(adapterCapabilities(adapterManager.adapter), request);
+      if (denial) {
+        eventBus.emit("policy_denied", tag(index, { url: request.url, ...denial }));
+        eventBus.emit(
+          "error",
+          tag(
+            index,
+            denial.rule === "allowedSchemes"
+              ? errorEventData("request.scheme_not_allowed", { scheme: denial.value })
+              : errorEventData("request.host_not_allowed", { host: denial.value }),
+          ),
+        );
+        validationFailed = true;
+      } else if (missing) {
+        eventBus.emit(
+          "error",
+          tag(
+            index,
+            errorEventData("adapter.unsupported_capability", { adapter: adapterManager.binding.impl, ...missing }),
+          ),
+        );
+        validationFailed = true;
+      }
+    });
+  }
 
-      try {
-        const parsed = JSON.parse(response.body);
-        normalizedPost = normalizePost(parsed);
-        if (normalizedPost) {
-          eventBus.emit("normalized", { id: normalizedPost.id });
-        } else {
-          eventBus.emit("error", { error: errorMessage(response.status, null) });
-        }
-      } catch (error) {
-        eventBus.emit("error", { error: errorMessage(response.status, error) });
+  const records = { post: [], comment: [], user: [] };
+  if (!validationFailed) {
+    // Mirrors the waves of `execute`: the requests of a wave are fetched
+    // together, and each request's events are logged in request order.
+    const schedule = planWaves(
+      requests.map((request) => hostOf(request.url)),
+      runtimePolicy.concurrency?.maxPerHost,
+    );
+    for (const { index, host, wave } of schedule.queued) {
+      eventBus.emit("queued", tag(index, { host, wave }));
+    }
+    const fetched = [];
+    for (const wave of schedule.waves) {
+      const results = await Promise.all(
+        wave.map((index) =>
+          fetchResource(
+            adapterManagers[index],
+            { ...requests[index], body: bodies[index] },
+            registry,
+            resource,
+            streamChunks === null ? null : Math.max(1, streamChunks),
+            (data) => tag(index, data),
+          ),
+        ),
+      );
+      wave.forEach((index, position) => {
+        fetched[index] = results[position];
+      });
+    }
+    // Mirrors `execute`: tokens are redacted before the events reach a
+    // subscriber.
+    const tokens = adapterManagers.flatMap((manager) => manager.stats.tokens).filter(Boolean);
+    for (const { records: fetchedRecords, normalizer, log } of fetched) {
+      for (const [type, data] of log) {
+        eventBus.emit(type, tokens.length > 0 ? redactTokens(data, tokens) : data);
+      }
+      if (normalizer) {
+        records[normalizer[1]].push(...(fetchedRecords ?? []));
+      }
+      if (normalizer && !normalizersUsed.some(([name]) => name === normalizer[0])) {
+        normalizersUsed.push(normalizer);
       }
-    } catch (error) {
-      eventBus.emit("fetch_response", { status: 0 });
-      eventBus.emit("error", { error: error.message });
     }
   }
+  // Mirrors the output fields of `execute`: a single post keeps its own
+  // field.  Batches list their posts, and every other normalizer lists its
+  // records in the field of their kind.
+  const singlePost = !batch && normalizersUsed.every(([name]) => name === DEFAULT_NORMALIZER);
+  const kinds = new Set(normalizersUsed.map(([, kind]) => kind));
+  const normalizedPost = singlePost ? (records.post.at(-1) ?? null) : null;
+  const listsPosts = kinds.has("post") || (batch && kinds.size === 0);
 
   if (eventBus.events.some((event) => event.type === "error")) {
     finalState = "failed";
-    normalizedPost = normalizedPost ?? null;
   }
 
   eventBus.emit("end", {});
 
+  const summary = {
+    ...summarizeEvents(eventBus.events),
+    cacheHits: adapterManagers.reduce((total, manager) => total + manager.stats.cacheHits, 0),
+    retries: adapterManagers.reduce((total, manager) => total + manager.stats.retries, 0),
+  };
+  if (contract.sla) {
+    summary.sla = classifySla(contract.sla, summary.durationTicks);
+  }
+
   return {
     output: {
       normalizedPost,
+      ...(listsPosts && !singlePost ? { normalizedPosts: records.post } : {}),
+      ...(kinds.has("comment") ? { normalizedComments: records.comment } : {}),
+      ...(kinds.has("user") ? { normalizedUsers: records.user } : {}),
       events: eventBus.events,
+      summary,
     },
     lifecycle: {
       service: "uma-post-fetcher.service",
-      service_version: "1.0.0",
-      policy_ref: "default.runtime.policy",
+      serviceVersion: "1.0.0",
+      policyRef: "default.runtime.policy",
       bindings: {
         "network.fetch": adapterManager.binding,
+        // Mirrors `NormalizerBinding::new`.
+        normalizer: {
+          impl: normalizersUsed.map(([name]) => name).join(",") || DEFAULT_NORMALIZER,
+          selectedBy: resource === null ? "contentType" : "resource",
+        },
       },
       events: eventBus.events,
       state: finalState,
       logicalClock: eventBus.clock,
+      ...(summary.sla ? { sla: summary.sla } : {}),
+      ...(bodies.some(Boolean)
+        ? { payloadBytes: bodies.reduce((total, body) => total + (body?.length ?? 0), 0) }
+        : {}),
+      adapterPolicy,
+      ...trace,
     },
   };
 }
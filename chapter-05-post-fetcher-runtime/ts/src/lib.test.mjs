@@ -1,6 +1,27 @@
 import test from "node:test";
 import assert from "node:assert/strict";
-import { runJson, summarizeRun } from "./lib.mjs";
+import http from "node:http";
+import { mkdtemp, readFile } from "node:fs/promises";
+import os from "node:os";
+import zlib from "node:zlib";
+import path from "node:path";
+import {
+  BudgetExceeded,
+  HostFetchAdapter,
+  JsonArrayStream,
+  NotAnArray,
+  RecordingAdapter,
+  ReplayAdapter,
+  ResponseCache,
+  ShapeError,
+  SigningMiddleware,
+  bodyText,
+  cacheKey,
+  canonicalRequest,
+  checkUrl,
+  runJson,
+  summarizeRun,
+} from "./lib.mjs";
 
 test("normalizes a successful fetch deterministically", async () => {
   const report = await runJson(
@@ -26,6 +47,17 @@ test("normalizes a successful fetch deterministically", async () => {
     normalizedPost: { id: 1, user_id: 2, title: "t", body: "b" },
     eventTypes: ["start", "fetch_request", "fetch_response", "normalized", "end"],
   });
+  assert.deepEqual(report.output.summary, {
+    totalRequests: 1,
+    successes: 1,
+    failuresByCode: {},
+    cacheHits: 0,
+    retries: 0,
+    durationTicks: 5,
+    deduplicated: 0,
+    sla: { status: "within", maxTicks: 8, maxWallMillis: 2000, ticks: 5 },
+  });
+  assert.deepEqual(report.lifecycle.sla, report.output.summary.sla);
 });
 
 test("header validation fails before fetch", async () => {
@@ -46,7 +78,456 @@ test("header validation fails before fetch", async () => {
   assert.equal(called, false);
   assert.equal(report.output.normalizedPost, null);
   assert.equal(report.lifecycle.state, "failed");
-  assert.deepEqual(report.output.events.map((event) => event.type), ["start", "error", "end"]);
+  assert.deepEqual(report.output.events.map((event) => event.type), ["start", "header_unexpected", "error", "end"]);
+  assert.deepEqual(report.output.events[1].data, { header: "x-foo" });
+  assert.deepEqual(report.output.events[2].data, {
+    error: "unexpected header x-foo",
+    code: "header.unexpected",
+    params: { header: "x-foo" },
+  });
+});
+
+test("the contract header policy records each violation", async () => {
+  const contract = JSON.parse(await readFile(new URL("../../contracts/service.contract.json", import.meta.url), "utf8"));
+  const report = await runJson(
+    JSON.stringify({
+      request: { url: "https://example.com/posts/1", headers: { "X-Debug": "1", accept: "text/*" } },
+      runId: "ts-header-policy",
+    }),
+    undefined,
+    { contract: { ...contract, headers: { allowed: ["accept"], maxValueLength: 4, required: ["X-Api-Version"] } } },
+  );
+
+  assert.equal(report.lifecycle.state, "failed");
+  assert.deepEqual(
+    report.output.events.map((event) => [event.type, event.data.header ?? event.data.code]),
+    [
+      ["start", undefined],
+      ["header_too_long", "accept"],
+      ["error", "header.too_long"],
+      ["header_unexpected", "X-Debug"],
+      ["error", "header.unexpected"],
+      ["header_missing", "X-Api-Version"],
+      ["error", "header.missing"],
+      ["end", undefined],
+    ],
+  );
+  assert.deepEqual(report.output.events[1].data, { header: "accept", length: 6, maxLength: 4 });
+  assert.equal(report.output.events[6].data.error, "missing header X-Api-Version");
+});
+
+test("unsupported adapter capabilities fail before fetch", async () => {
+  let called = false;
+  const report = await runJson(
+    JSON.stringify({
+      request: { url: "http://example.com/posts/1" },
+      runId: "ts-caps",
+    }),
+    {
+      async fetch() {
+        called = true;
+        throw new Error("should not run");
+      },
+      capabilities() {
+        return { methods: ["GET"], schemes: ["https"], streaming: false, compression: false, timeouts: false, tls: true };
+      },
+    },
+  );
+
+  assert.equal(called, false);
+  assert.equal(report.lifecycle.state, "failed");
+  assert.deepEqual(report.output.events[1].data, {
+    error: "adapter custom does not support scheme http",
+    code: "adapter.unsupported_capability",
+    params: { adapter: "custom", capability: "scheme", requested: "http" },
+  });
+});
+
+test("method and body reach the adapter and the fetch_request event", async () => {
+  const sent = [];
+  const report = await runJson(
+    JSON.stringify({
+      request: {
+        url: "https://example.com/posts",
+        method: "post",
+        headers: { "content-type": "application/json" },
+        body: { title: "t" },
+      },
+      runId: "ts-post",
+    }),
+    {
+      async fetch(request) {
+        sent.push([request.method, request.body.toString("utf8")]);
+        return { status: 201, headers: {}, body: '{"id":1,"userId":2,"title":"t","body":"b"}' };
+      },
+      capabilities() {
+        return { methods: ["GET", "POST"], schemes: [], streaming: false, compression: false, timeouts: false, tls: true };
+      },
+    },
+  );
+
+  assert.deepEqual(sent, [["post", '{"title":"t"}']]);
+  assert.deepEqual(report.output.events[1].data, { url: "https://example.com/posts", method: "POST" });
+  assert.equal(report.lifecycle.payloadBytes, 13);
+});
+
+test("unsupported content types fail before fetch", async () => {
+  let called = false;
+  const report = await runJson(
+    JSON.stringify({
+      request: { url: "https://example.com/posts/1", headers: { "Content-Type": "multipart/form-data" } },
+      runId: "ts-content-type",
+    }),
+    {
+      async fetch() {
+        called = true;
+        throw new Error("should not run");
+      },
+    },
+  );
+
+  assert.equal(called, false);
+  assert.equal(report.output.events[1].type, "header_unsupported_content_type");
+  assert.deepEqual(report.output.events[2].data, {
+    error: "unsupported Content-Type multipart/form-data",
+    code: "header.unsupported_content_type",
+    params: { header: "Content-Type", value: "multipart/form-data" },
+  });
+});
+
+test("the resolved adapter policy is recorded and denied URLs are never fetched", async () => {
+  const report = await runJson(
+    JSON.stringify({ request: { url: "https://example.com/posts/1" }, runId: "ts-policy" }),
+    {
+      async fetch() {
+        return { status: 200, headers: {}, body: '{"id":1,"userId":2,"title":"t","body":"b"}' };
+      },
+    },
+  );
+  assert.deepEqual(report.lifecycle.adapterPolicy, {
+    retries: 0,
+    retryOn: [],
+    retryNonIdempotent: false,
+    backoff: { initialMillis: 100, maxMillis: 2000 },
+    circuitBreaker: { failureThreshold: 0, cooldownRequests: 10 },
+    cache: false,
+    cacheLimits: { ttlMillis: 60000, maxEntries: 256 },
+    allowedHosts: [],
+    allowedSchemes: ["https", "http", "uma-fixture"],
+  });
+
+  const policy = { retries: 0, cache: false, allowedHosts: ["Example.com"], allowedSchemes: ["HTTPS"] };
+  assert.equal(checkUrl(policy, "https://user@EXAMPLE.com:8443/posts/1"), null);
+  assert.deepEqual(checkUrl(policy, "https://example.com.evil.test/"), {
+    rule: "allowedHosts",
+    value: "example.com.evil.test",
+  });
+  assert.deepEqual(checkUrl(policy, "file:///etc/passwd"), { rule: "allowedSchemes", value: "file" });
+
+  let called = false;
+  const denied = await runJson(JSON.stringify({ request: { url: "file:///etc/passwd" }, runId: "ts-denied" }), {
+    async fetch() {
+      called = true;
+      return { status: 200, headers: {}, body: "{}" };
+    },
+  });
+  assert.equal(called, false);
+  assert.deepEqual(
+    denied.output.events.map((event) => event.type),
+    ["start", "policy_denied", "error", "end"],
+  );
+  assert.deepEqual(denied.output.events[1].data, { url: "file:///etc/passwd", rule: "allowedSchemes", value: "file" });
+  assert.equal(denied.output.events[2].data.code, "request.scheme_not_allowed");
+  assert.equal(denied.lifecycle.state, "failed");
+});
+
+test("strict mode lists unexpected input fields", async () => {
+  let called = false;
+  const report = await runJson(
+    JSON.stringify({
+      request: { url: "https://example.com", headers: {}, retries: 3 },
+      runId: "ts-strict",
+      inputMode: "strict",
+      debug: true,
+    }),
+    {
+      async fetch() {
+        called = true;
+        return { status: 200, headers: {}, body: "{}" };
+      },
+    },
+  );
+
+  assert.equal(called, false);
+  assert.equal(report.lifecycle.state, "failed");
+  assert.deepEqual(report.output.events[1].data, {
+    error: 'unexpected input fields ["debug","request.retries"]',
+    code: "input.validation_error",
+    params: { fields: ["debug", "request.retries"] },
+  });
+});
+
+test("batch runs fetch each request in order", async () => {
+  const fetched = [];
+  const adapter = {
+    async fetch(request) {
+      fetched.push(request.url);
+      const id = Number(request.url.split("/").pop());
+      const body = id === 2 ? "oops" : JSON.stringify({ id, userId: 1, title: "t", body: "b" });
+      return { status: 200, headers: {}, body };
+    },
+  };
+  const requests = (ids) => ids.map((id) => ({ url: `https://example.com/posts/${id}` }));
+
+  const report = await runJson(JSON.stringify({ requests: requests([1, 2, 3]), runId: "ts-batch" }), adapter);
+  assert.equal(report.output.normalizedPost, null);
+  assert.deepEqual(
+    report.output.normalizedPosts.map((post) => post.id),
+    [1, 3],
+  );
+  assert.deepEqual(
+    report.output.events.map((event) => [event.type, event.data.request]),
+    [
+      ["start", undefined],
+      ["queued", 2],
+      ["fetch_request", 0],
+      ["fetch_response", 0],
+      ["normalized", 0],
+      ["fetch_request", 1],
+      ["fetch_response", 1],
+      ["error", 1],
+      ["fetch_request", 2],
+      ["fetch_response", 2],
+      ["normalized", 2],
+      ["end", undefined],
+    ],
+  );
+  assert.equal(report.output.summary.totalRequests, 3);
+  assert.equal(report.lifecycle.state, "failed");
+
+  const bad = requests([1, 3]);
+  bad[1].retries = 2;
+  const strict = await runJson(JSON.stringify({ requests: bad, runId: "ts-batch", inputMode: "strict" }), adapter);
+  assert.deepEqual(strict.output.events[1].data.params.fields, ["requests.1.retries"]);
+  assert.deepEqual(strict.output.normalizedPosts, []);
+  assert.equal(fetched.length, 3);
+
+  await assert.rejects(runJson(JSON.stringify({ requests: [], runId: "ts-batch" }), adapter), /must not be empty/);
+});
+
+test("batch fetches overlap and log in request order", async () => {
+  let inFlight = 0;
+  let peak = 0;
+  const adapter = {
+    async fetch(request) {
+      inFlight += 1;
+      peak = Math.max(peak, inFlight);
+      const id = Number(request.url.split("/").pop());
+      await new Promise((resolve) => setTimeout(resolve, 80 - 20 * id));
+      inFlight -= 1;
+      return { status: 200, headers: {}, body: JSON.stringify({ id, userId: 1, title: "t", body: "b" }) };
+    },
+  };
+  const input = JSON.stringify({
+    requests: ["com", "org", "com", "org"].map((tld, id) => ({ url: `https://example.${tld}/posts/${id}` })),
+    runId: "ts-pooled",
+  });
+
+  const report = await runJson(input, adapter);
+  assert.ok(peak >= 2);
+  assert.deepEqual(
+    report.output.normalizedPosts.map((post) => post.id),
+    [0, 1, 2, 3],
+  );
+  assert.deepEqual(
+    report.output.events.filter((event) => event.type === "fetch_request").map((event) => event.data.request),
+    [0, 1, 2, 3],
+  );
+  assert.deepEqual((await runJson(input, adapter)).output.events, report.output.events);
+});
+
+test("resource selects the normalizer", async () => {
+  const comment = (id) => ({ id, postId: 1, name: "n", email: "e@x.io", body: "b" });
+  const bodies = {
+    "https://example.com/posts/1/comments": [comment(1), { id: 2 }, comment(3)],
+    "https://example.com/users/1": { id: 1, name: "L", username: "Bret", email: "s@april.biz" },
+    "https://example.com/posts": { id: 1 },
+  };
+  const adapter = {
+    async fetch(request) {
+      return { status: 200, headers: {}, body: JSON.stringify(bodies[request.url]) };
+    },
+  };
+  const run = (url, resource) =>
+    runJson(JSON.stringify({ request: { url }, resource, runId: "ts-resource" }), adapter);
+
+  const comments = await run("https://example.com/posts/1/comments", "comments");
+  assert.equal(comments.output.normalizedPost, null);
+  assert.deepEqual(
+    comments.output.normalizedComments.map((record) => record.id),
+    [1, 3],
+  );
+  assert.deepEqual(comments.output.events[3].data, { resource: "comments", count: 2, skipped: 1 });
+
+  const user = await run("https://example.com/users/1", "user");
+  assert.equal(user.output.normalizedUsers[0].username, "Bret");
+  assert.deepEqual(user.output.events[3].data, { resource: "user", id: 1 });
+
+  const posts = await run("https://example.com/posts", "posts");
+  assert.deepEqual(posts.output.normalizedPosts, []);
+  assert.equal(posts.output.events[3].data.code, "response.invalid_resource");
+
+  const unknown = await run("https://example.com", "todos");
+  assert.equal(unknown.lifecycle.state, "failed");
+  assert.deepEqual(unknown.output.events[1].data.params, { normalizer: "todos" });
+  assert.equal(unknown.output.events[2].type, "end");
+});
+
+test("json array stream returns elements as they complete", () => {
+  const body = ` [ {"id": 1, "title": "a \\"quoted\\" ]}, bracket", "tags": [1, [2]]},
+    "text \\\\", 42, -1.5e3, true, null, [] ] `;
+  const bytes = Buffer.from(body);
+  for (const size of [1, 2, 7, bytes.length]) {
+    const stream = new JsonArrayStream();
+    const elements = [];
+    for (let offset = 0; offset < bytes.length; offset += size) {
+      elements.push(...stream.push(bytes.subarray(offset, offset + size)));
+    }
+    stream.finish();
+    assert.deepEqual(elements, JSON.parse(body), `chunks of ${size}`);
+  }
+  const fail = (text) => {
+    const stream = new JsonArrayStream();
+    try {
+      stream.push(Buffer.from(text));
+      stream.finish();
+    } catch (error) {
+      return error;
+    }
+    return null;
+  };
+  assert.ok(fail(' {"id": 1}') instanceof NotAnArray);
+  for (const invalid of ["[1,]", "[1 2]", "[1] x", '[{"id": 1}', "[tru]"]) {
+    const error = fail(invalid);
+    assert.ok(error && !(error instanceof NotAnArray), invalid);
+  }
+});
+
+test("streamed runs normalize list bodies by chunk", async () => {
+  const post = (id) => ({ id, userId: 1, title: `t${id}`, body: "b" });
+  const body = JSON.stringify([...Array.from({ length: 20 }, (_, index) => post(index + 1)), { id: 21 }]);
+  const adapter = {
+    async fetch() {
+      return { status: 200, headers: { "content-type": "application/json" }, body };
+    },
+  };
+  const input = JSON.stringify({ request: { url: "https://example.com/posts" }, resource: "posts", runId: "ts-s" });
+  const buffered = await runJson(input, adapter);
+  const streamed = await runJson(input, adapter, { streamChunks: 256 });
+  assert.deepEqual(streamed.output.normalizedPosts, buffered.output.normalizedPosts);
+  assert.equal(streamed.output.normalizedPosts.length, 20);
+  const progress = streamed.output.events.filter((event) => event.type === "fetch_progress").map((event) => event.data);
+  assert.equal(progress.length, Math.ceil(body.length / 256));
+  assert.deepEqual(progress.at(-1).bytes, body.length);
+  assert.ok(progress[0].records > 0 && progress[0].records < 20);
+  assert.deepEqual(streamed.output.events.find((event) => event.type === "normalized").data, {
+    resource: "posts",
+    count: 20,
+    skipped: 1,
+  });
+  assert.ok(buffered.output.events.every((event) => event.type !== "fetch_progress"));
+});
+
+test("normalizers dispatch by content type and are recorded", async () => {
+  const feed = `<?xml version="1.0"?>
+<rss version="2.0"><channel><title>Posts</title>
+  <item><title>First &amp; best</title><guid>https://example.com/posts/41</guid>
+    <description><![CDATA[<p>Hello</p>]]></description></item>
+  <item><title>No id</title></item>
+</channel></rss>`;
+  const responses = {
+    "https://example.com/feed": ["application/rss+xml; charset=utf-8", feed],
+    "https://example.com/pipe": ["text/x-pipe", "7|Piped"],
+    "https://example.com/posts/1": ["application/json", '{"id":1,"userId":1,"title":"t","body":"b"}'],
+  };
+  const adapter = {
+    async fetch(request) {
+      const [contentType, body] = responses[request.url];
+      return { status: 200, headers: { "content-type": contentType }, body };
+    },
+  };
+  const pipe = {
+    kind: "post",
+    normalize(body) {
+      const [id, title] = body.split("|");
+      if (title === undefined) {
+        throw new ShapeError();
+      }
+      return { records: [{ id: Number(id), user_id: 0, title, body: "" }], details: { id: Number(id) } };
+    },
+  };
+  const run = (input) =>
+    runJson(JSON.stringify({ ...input, runId: "ts-normalizers" }), adapter, {
+      normalizers: { pipe },
+      contentTypes: { "text/x-pipe": "pipe" },
+    });
+
+  const rss = await run({ request: { url: "https://example.com/feed" } });
+  assert.equal(rss.output.normalizedPost, null);
+  assert.deepEqual(rss.output.normalizedPosts, [{ id: 41, user_id: 0, title: "First & best", body: "<p>Hello</p>" }]);
+  assert.deepEqual(rss.output.events[3].data, { resource: "rss-item", count: 1, skipped: 1 });
+  assert.deepEqual(rss.lifecycle.bindings.normalizer, { impl: "rss-item", selectedBy: "contentType" });
+
+  const batch = await run({ requests: [{ url: "https://example.com/posts/1" }, { url: "https://example.com/pipe" }] });
+  assert.deepEqual(
+    batch.output.normalizedPosts.map((post) => post.title),
+    ["t", "Piped"],
+  );
+  assert.equal(batch.lifecycle.bindings.normalizer.impl, "post,pipe");
+
+  const named = await run({ request: { url: "https://example.com/feed" }, resource: "pipe" });
+  assert.equal(named.output.events[3].data.code, "response.invalid_resource");
+  assert.deepEqual(named.lifecycle.bindings.normalizer, { impl: "pipe", selectedBy: "resource" });
+
+  const single = await run({ request: { url: "https://example.com/posts/1" } });
+  assert.equal(single.output.normalizedPost.id, 1);
+  assert.equal(single.output.normalizedPosts, undefined);
+  assert.equal(single.lifecycle.bindings.normalizer.impl, "post");
+});
+
+test("decompressed bodies are recorded", async () => {
+  const post = JSON.stringify({ id: 5, userId: 1, title: "t", body: "b".repeat(2000) });
+  const gzip = zlib.gzipSync(post);
+  const server = http.createServer((request, response) => {
+    response.writeHead(200, { "content-type": "application/json", "content-encoding": "gzip" }).end(gzip);
+  });
+  await new Promise((resolve) => server.listen(0, "127.0.0.1", resolve));
+  try {
+    const response = await new HostFetchAdapter().fetch({ url: `http://127.0.0.1:${server.address().port}/posts/5` });
+    assert.equal(bodyText(response.body), post);
+    assert.equal(response.headers["content-encoding"], undefined);
+  } finally {
+    server.closeAllConnections();
+    server.close();
+  }
+
+  const report = await runJson(JSON.stringify({ request: { url: "https://example.com/posts/5" }, runId: "ts-gzip" }), {
+    async fetch() {
+      return {
+        status: 200,
+        headers: {},
+        body: post,
+        decoded: { encoding: "gzip", encodedBytes: gzip.length, decodedBytes: post.length },
+      };
+    },
+  });
+  assert.equal(report.output.events[3].type, "decompressed");
+  assert.deepEqual(report.output.events[3].data, {
+    encoding: "gzip",
+    encodedBytes: gzip.length,
+    decodedBytes: post.length,
+  });
+  assert.equal(report.output.normalizedPost.id, 5);
 });
 
 test("parse errors are explicit", async () => {
@@ -97,6 +578,433 @@ test("wrapper binding order is cache then retry then base", async () => {
   }
 });
 
+test("each retry is recorded as a fetch_retry event", async () => {
+  process.env.UMA_ENABLE_RETRY = "1";
+  const statuses = [503, 0];
+  const delays = [];
+  try {
+    const report = await runJson(
+      JSON.stringify({ request: { url: "https://example.com" }, runId: "ts-retry" }),
+      {
+        async fetch() {
+          const status = statuses.shift() ?? 200;
+          if (status === 0) {
+            throw new Error("connection reset");
+          }
+          return { status, headers: {}, body: JSON.stringify({ id: 1, userId: 2, title: "t", body: "b" }) };
+        },
+      },
+      { delay: async (millis) => delays.push(millis) },
+    );
+    assert.deepEqual(
+      report.output.events.map((event) => event.type),
+      ["start", "fetch_request", "fetch_retry", "fetch_retry", "fetch_response", "normalized", "end"],
+    );
+    assert.deepEqual(report.output.events[2].data, { attempt: 2, previousStatus: 503, delayMillis: 100 });
+    assert.deepEqual(report.output.events[3].data, { attempt: 3, previousStatus: 0, delayMillis: 200 });
+    assert.deepEqual(delays, [100, 200]);
+    assert.equal(report.output.summary.retries, 2);
+  } finally {
+    delete process.env.UMA_ENABLE_RETRY;
+  }
+});
+
+test("an open circuit fails fast until a trial request succeeds", async () => {
+  const contract = JSON.parse(await readFile(new URL("../../contracts/service.contract.json", import.meta.url), "utf8"));
+  contract.adapter.circuitBreaker = { failureThreshold: 2, cooldownRequests: 1 };
+  const circuits = new Map();
+  const statuses = [503, 0, 200];
+  let calls = 0;
+  const adapter = {
+    async fetch() {
+      calls += 1;
+      const status = statuses.shift();
+      if (status === 0) {
+        throw new Error("connection refused");
+      }
+      return { status, headers: {}, body: JSON.stringify({ id: 1, userId: 2, title: "t", body: "b" }) };
+    },
+  };
+  const run = () =>
+    runJson(JSON.stringify({ request: { url: "https://api.example.com/posts/1" }, runId: "ts-circuit" }), adapter, {
+      contract,
+      circuits,
+    });
+  const transitions = (report) =>
+    report.output.events.filter((event) => event.type === "circuit_state").map((event) => event.data);
+
+  await run();
+  assert.deepEqual(transitions(await run()), [{ host: "api.example.com", from: "closed", to: "open" }]);
+
+  const failedFast = await run();
+  assert.equal(calls, 2);
+  assert.equal(failedFast.output.events[3].data.code, "network.circuit_open");
+  assert.deepEqual(failedFast.output.events[3].data.params, { host: "api.example.com" });
+
+  const recovered = await run();
+  assert.deepEqual(transitions(recovered), [
+    { host: "api.example.com", from: "open", to: "half_open" },
+    { host: "api.example.com", from: "half_open", to: "closed" },
+  ]);
+  assert.equal(recovered.lifecycle.bindings["network.fetch"].impl, "circuit-custom");
+});
+
+test("cached responses expire, are evicted and vary by headers", async () => {
+  const contract = JSON.parse(await readFile(new URL("../../contracts/service.contract.json", import.meta.url), "utf8"));
+  contract.adapter.cache = true;
+  contract.adapter.cacheLimits = { ttlMillis: 3, maxEntries: 2 };
+  const responses = new ResponseCache();
+  let calls = 0;
+  const adapter = {
+    async fetch() {
+      calls += 1;
+      return { status: 200, headers: {}, body: JSON.stringify({ id: 1, userId: 2, title: "t", body: "b" }) };
+    },
+  };
+  const fetched = async (url, headers = {}) => {
+    const before = calls;
+    await runJson(JSON.stringify({ request: { url, headers }, runId: "ts-cache" }), adapter, { contract, responses });
+    return calls > before;
+  };
+  const a = "https://example.com/posts/a";
+  const b = "https://example.com/posts/b";
+  const c = "https://example.com/posts/c";
+
+  assert.deepEqual([await fetched(a), await fetched(a), await fetched(a), await fetched(a)], [true, false, false, true]);
+  assert.equal(await fetched(a, { authorization: "Bearer one" }), true);
+  assert.equal(await fetched(a, { Authorization: "Bearer one" }), false);
+  assert.equal(await fetched(a, { authorization: "Bearer two" }), true);
+  assert.equal(responses.size, 2);
+
+  assert.equal(await fetched(b), true);
+  assert.equal(await fetched(c), true);
+  assert.equal(await fetched(b), false);
+  assert.equal(await fetched(c, { accept: "application/json" }), true);
+  assert.equal(responses.size, 2);
+  assert.equal(await fetched(c, { accept: "application/json" }), false);
+  assert.equal(await fetched(c), true);
+
+  const key = cacheKey({ url: a, headers: { Authorization: "Bearer secret", accept: "*/*" } });
+  assert.match(key, /^https:\/\/example\.com\/posts\/a\naccept: \*\/\*\nauthorization: #[0-9a-f]{16}$/);
+  assert.ok(!key.includes("secret"));
+
+  const d = "https://example.com/posts/d";
+  const lookups = async () => {
+    const report = await runJson(JSON.stringify({ request: { url: d }, runId: "ts-cache" }), adapter, {
+      contract,
+      responses,
+    });
+    return report.output.events[2];
+  };
+  assert.deepEqual(await lookups(), { t: "2", type: "cache_miss", data: { url: d } });
+  assert.deepEqual(await lookups(), { t: "2", type: "cache_hit", data: { url: d } });
+});
+
+test("expired entries are revalidated with their ETag", async () => {
+  const contract = JSON.parse(await readFile(new URL("../../contracts/service.contract.json", import.meta.url), "utf8"));
+  contract.adapter.cache = true;
+  contract.adapter.cacheLimits = { ttlMillis: 1, maxEntries: null };
+  contract.adapter.retries = 2;
+  const responses = new ResponseCache();
+  let etag = '"v1"';
+  const seen = [];
+  const adapter = {
+    async fetch(request) {
+      const condition = request.headers?.["If-None-Match"];
+      seen.push(condition ?? null);
+      if (condition === etag) {
+        return { status: 304, headers: {}, body: "" };
+      }
+      return { status: 200, headers: { ETag: etag }, body: JSON.stringify({ id: 1, userId: 2, title: "t", body: "b" }) };
+    },
+  };
+  const run = () =>
+    runJson(JSON.stringify({ request: { url: "https://example.com/posts/1" }, runId: "ts-etag" }), adapter, {
+      contract,
+      responses,
+      delay: async () => {},
+    });
+
+  assert.equal((await run()).output.events[2].type, "cache_miss");
+  const revalidated = await run();
+  assert.deepEqual(
+    revalidated.output.events.map((event) => event.type),
+    ["start", "fetch_request", "cache_revalidated", "fetch_response", "normalized", "end"],
+  );
+  assert.equal(revalidated.output.events[3].data.status, 200);
+  assert.equal(revalidated.output.summary.cacheHits, 1);
+
+  etag = '"v2"';
+  assert.equal((await run()).output.events[2].type, "cache_miss");
+  assert.equal((await run()).output.events[2].type, "cache_revalidated");
+  assert.deepEqual(seen, [null, '"v1"', '"v1"', '"v2"']);
+});
+
+test("the middleware stack wraps each request in order", async () => {
+  const seen = [];
+  const adapter = {
+    async fetch(request) {
+      seen.push(request.headers.Authorization ?? null);
+      return { status: 200, headers: {}, body: JSON.stringify({ id: 1, userId: 2, title: "t", body: "b" }) };
+    },
+  };
+  const log = [];
+  const hook = (name, { token, answers } = {}) => ({
+    beforeFetch(request) {
+      log.push(`before ${name}`);
+      if (token) {
+        request.headers.Authorization = `Bearer ${token}`;
+      }
+      if (answers && request.url.endsWith(answers)) {
+        return { status: 200, headers: {}, body: JSON.stringify({ id: 9, userId: 2, title: "canned", body: "b" }) };
+      }
+    },
+    afterFetch(request, response) {
+      log.push(`after ${name} ${response.status}`);
+    },
+  });
+  const middleware = [hook("auth", { token: "t0k3n" }), hook("stub", { answers: "/posts/9" }), hook("metrics")];
+  const run = (url) => runJson(JSON.stringify({ request: { url }, runId: "ts-middleware" }), adapter, { middleware });
+
+  const first = await run("https://example.com/posts/1");
+  assert.equal(first.output.normalizedPost.id, 1);
+  assert.equal(first.lifecycle.bindings["network.fetch"].impl, "middleware-custom");
+  assert.deepEqual(seen, ["Bearer t0k3n"]);
+  assert.deepEqual(log.splice(0), [
+    "before auth",
+    "before stub",
+    "before metrics",
+    "after metrics 200",
+    "after stub 200",
+    "after auth 200",
+  ]);
+
+  const stubbed = await run("https://example.com/posts/9");
+  assert.equal(stubbed.output.normalizedPost.title, "canned");
+  assert.equal(seen.length, 1);
+  assert.deepEqual(log, ["before auth", "before stub", "after stub 200", "after auth 200"]);
+});
+
+test("the auth provider refreshes on 401 and its tokens are redacted", async () => {
+  const seen = [];
+  const adapter = {
+    async fetch(request) {
+      seen.push(request.headers.Authorization);
+      if (request.headers.Authorization === "Bearer leaky") {
+        throw new Error("rejected credentials Bearer leaky");
+      }
+      const status = request.headers.Authorization === "Bearer fresh" ? 200 : 401;
+      return { status, headers: {}, body: JSON.stringify({ id: 1, userId: 2, title: "t", body: "b" }) };
+    },
+  };
+  const run = (auth) =>
+    runJson(
+      JSON.stringify({
+        request: { url: "https://example.com/posts/1", headers: { Authorization: "Bearer input" } },
+        runId: "ts-auth",
+      }),
+      adapter,
+      { auth },
+    );
+
+  let current = "stale";
+  const rotated = await run({ token: () => current, refresh: () => (current = "fresh") });
+  assert.equal(rotated.output.normalizedPost.id, 1);
+  assert.deepEqual(seen.splice(0), ["Bearer stale", "Bearer fresh"]);
+  assert.deepEqual(
+    rotated.output.events.map((event) => event.type),
+    ["start", "fetch_request", "auth_refresh", "fetch_response", "normalized", "end"],
+  );
+  assert.equal(rotated.output.events[2].data.url, "https://example.com/posts/1");
+  assert.equal(rotated.lifecycle.bindings["network.fetch"].impl, "auth-custom");
+
+  const leaked = await run({ token: () => "leaky" });
+  assert.equal(leaked.output.events[3].data.params.detail, "rejected credentials Bearer [redacted]");
+  assert.ok(!JSON.stringify(leaked).includes("leaky"));
+});
+
+test("signing canonicalizes requests as the Rust runtime does", async () => {
+  const request = (url, headers) => ({ url, headers, method: "post", body: Buffer.from('{"title":"t"}') });
+  const names = ["Host", "content-type", "x-date", "x-missing"];
+  const { canonical, signed } = canonicalRequest(
+    request("https://API.example.com/posts/a%20b/caf%c3%a9?b=2&a=1&a=0&flag#top", {
+      "Content-Type": "application/json",
+      "X-Date": " 20260101T000000Z ",
+    }),
+    names,
+  );
+  assert.equal(
+    canonical,
+    "POST\n/posts/a%20b/caf%C3%A9\na=0&a=1&b=2&flag=\n" +
+      "content-type:application/json\nhost:api.example.com\nx-date:20260101T000000Z\n\n" +
+      "content-type;host;x-date\n" +
+      "fd32f01954a82d8192431bf0c8d2fe22d8c10a9d62a1f4aad96228b065e6cc65",
+  );
+  assert.deepEqual(signed, ["content-type", "host", "x-date"]);
+  const equivalent = canonicalRequest(
+    request("https://api.example.com/posts/a b/café?flag=&a=0&b=2&a=1", {
+      "x-date": "20260101T000000Z",
+      "content-type": "application/json",
+    }),
+    names,
+  );
+  assert.equal(equivalent.canonical, canonical);
+
+  const seen = [];
+  const adapter = {
+    async fetch(request) {
+      seen.push(request.headers["X-Signature"]);
+      return { status: 200, headers: {}, body: JSON.stringify({ id: 1, userId: 2, title: "t", body: "b" }) };
+    },
+  };
+  const middleware = [new SigningMiddleware({ id: "k1", secret: "secret" }, { signedHeaders: ["host"] })];
+  const report = await runJson(
+    JSON.stringify({ request: { url: "https://example.com/posts/1" }, runId: "ts-sign" }),
+    adapter,
+    { middleware },
+  );
+  assert.equal(report.output.normalizedPost.id, 1);
+  assert.deepEqual(seen, [
+    "HMAC-SHA256 KeyId=k1, SignedHeaders=host, " +
+      "Signature=1d555c11f5c6c2c01ba3e79e56d40a10cfb03b3bb413bd0e80e36820b18b1273",
+  ]);
+});
+
+test("secrets are masked as events are emitted", async () => {
+  const adapter = {
+    async fetch(request) {
+      throw new Error(`GET ${request.url} was refused\nAuthorization: ${request.headers.Authorization}`);
+    },
+  };
+  const input = JSON.stringify({
+    request: {
+      url: "https://example.com/posts?access_token=abc123&page=2",
+      headers: { Authorization: "Bearer abc123" },
+    },
+    runId: "ts-secrets",
+  });
+  const report = await runJson(input, adapter);
+  assert.ok(!JSON.stringify(report).includes("abc123"));
+  assert.equal(report.output.events[1].data.url, "https://example.com/posts?access_token=[redacted]&page=2");
+  assert.equal(
+    report.output.events[3].data.params.detail,
+    "GET https://example.com/posts?access_token=[redacted]&page=2 was refused\nAuthorization: [redacted]",
+  );
+
+  const custom = await runJson(input, adapter, { sensitiveKeys: ["x-session"] });
+  assert.equal(custom.output.events[1].data.url, "https://example.com/posts?access_token=abc123&page=2");
+});
+
+test("contract bindings select a registered adapter", async () => {
+  const contract = JSON.parse(await readFile(new URL("../../contracts/service.contract.json", import.meta.url), "utf8"));
+  const run = (name, options = {}) =>
+    runJson(JSON.stringify({ request: { url: "uma-fixture://sample-post" }, runId: "ts-bound" }), options.adapter, {
+      contract: { ...contract, bindings: { "network.fetch": name } },
+      adapters: {
+        stub: () => ({
+          async fetch() {
+            return { status: 200, headers: {}, body: "{}" };
+          },
+        }),
+      },
+    });
+
+  const replay = await run("replay");
+  assert.equal(replay.output.normalizedPost.id, 1);
+  assert.equal(replay.lifecycle.bindings["network.fetch"].impl, "replay");
+
+  const stub = await run("stub");
+  assert.equal(stub.output.events[3].data.code, "response.invalid_post");
+  assert.equal(stub.lifecycle.bindings["network.fetch"].impl, "stub");
+
+  const custom = await run("stub", { adapter: new ReplayAdapter() });
+  assert.equal(custom.output.normalizedPost.id, 1);
+  assert.equal(custom.lifecycle.bindings["network.fetch"].impl, "custom");
+
+  const unknown = await run("mock");
+  assert.deepEqual(unknown.output.events[1].data, {
+    error: "no adapter is registered as mock",
+    code: "adapter.unknown",
+    params: { adapter: "mock" },
+  });
+  assert.equal(unknown.output.summary.totalRequests, 0);
+  assert.equal(unknown.lifecycle.bindings["network.fetch"].impl, "mock");
+});
+
+test("a recorded cassette replays the same run", async () => {
+  const contract = JSON.parse(await readFile(new URL("../../contracts/service.contract.json", import.meta.url), "utf8"));
+  contract.adapter.retries = 2;
+  let calls = 0;
+  const flaky = {
+    async fetch() {
+      calls += 1;
+      return calls === 1
+        ? { status: 503, headers: {}, body: "" }
+        : { status: 200, headers: { etag: '"v1"' }, body: JSON.stringify({ id: 1, userId: 2, title: "t", body: "b" }) };
+    },
+  };
+  const file = path.join(await mkdtemp(path.join(os.tmpdir(), "uma-cassette-")), "posts.json");
+  const input = JSON.stringify({
+    request: { url: "https://example.com/posts/1", headers: { authorization: "secret" } },
+    runId: "ts-vcr",
+  });
+  const run = (adapter) => runJson(input, adapter, { contract, delay: async () => {} });
+
+  const recorded = await run(new RecordingAdapter(flaky, file));
+  const cassette = JSON.parse(await readFile(file, "utf8"));
+  assert.deepEqual(
+    cassette.interactions.map(({ method, response }) => [method, response.status]),
+    [
+      ["GET", 503],
+      ["GET", 200],
+    ],
+  );
+  assert.ok(!(await readFile(file, "utf8")).includes("secret"));
+
+  const replayed = await run(await ReplayAdapter.load(file));
+  assert.deepEqual(replayed, recorded);
+  assert.equal(replayed.output.summary.retries, 1);
+  assert.deepEqual(new ReplayAdapter(cassette).capabilities().schemes, ["https"]);
+
+  await run(await RecordingAdapter.append(flaky, file));
+  assert.equal(JSON.parse(await readFile(file, "utf8")).interactions.length, 3);
+});
+
+test("binary bodies pass through intact", async () => {
+  const png = Buffer.from([0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0xff, 0xfe]);
+  const file = path.join(await mkdtemp(path.join(os.tmpdir(), "uma-binary-")), "logo.json");
+  const recorder = new RecordingAdapter(
+    {
+      async fetch() {
+        return { status: 200, headers: { "content-type": "image/png" }, body: png };
+      },
+    },
+    file,
+  );
+  const request = { url: "https://example.com/logo.png" };
+  assert.deepEqual((await recorder.fetch(request)).body, png);
+  assert.match(await readFile(file, "utf8"), /"bodyBase64": "iVBORw0KGgr\/\/g=="/);
+  assert.throws(() => bodyText(png), /^Error: body is not UTF-8/);
+
+  const run = async (options) =>
+    runJson(JSON.stringify({ request, runId: "ts-binary" }), await ReplayAdapter.load(file), options);
+  const parsed = await run();
+  assert.equal(parsed.output.events[3].data.code, "response.parse_error");
+  assert.match(parsed.output.events[3].data.params.detail, /^body is not UTF-8/);
+
+  const bytes = {
+    kind: "post",
+    normalize(body) {
+      const id = body.length;
+      const title = `[${[...body.subarray(0, 4)].map((byte) => byte.toString(16).padStart(2, "0")).join(", ")}]`;
+      return { records: [{ id, user_id: 0, title, body: "" }], details: { id } };
+    },
+  };
+  const counted = await run({ normalizers: { bytes }, contentTypes: { "image/png": "bytes" } });
+  assert.equal(counted.output.normalizedPosts[0].id, 10);
+  assert.equal(counted.output.normalizedPosts[0].title, "[89, 50, 4e, 47]");
+});
+
 test("fixture URL is resolved hermetically", async () => {
   const report = await runJson(
     JSON.stringify({
@@ -113,3 +1021,101 @@ test("fixture URL is resolved hermetically", async () => {
     ["start", "fetch_request", "fetch_response", "normalized", "end"],
   );
 });
+
+test("execution limits surface as budget_exceeded", async () => {
+  await assert.rejects(
+    new HostFetchAdapter({ maxBodyBytes: 10 }).fetch({ url: "uma-fixture://sample-post" }),
+    { limit: "maxBodyBytes", max: 10 },
+  );
+
+  // A local server that redirects forever, or never answers `/slow`.
+  const server = http.createServer((request, response) => {
+    if (request.url !== "/slow") {
+      response.writeHead(302, { location: "/again" }).end();
+    }
+  });
+  await new Promise((resolve) => server.listen(0, "127.0.0.1", resolve));
+  const base = `http://127.0.0.1:${server.address().port}`;
+  try {
+    const adapter = new HostFetchAdapter({ maxRedirects: 2, timeoutMillis: 200 });
+    await assert.rejects(adapter.fetch({ url: `${base}/loop` }), { limit: "maxRedirects", max: 2 });
+    await assert.rejects(adapter.fetch({ url: `${base}/slow` }), { limit: "timeoutMillis", max: 200 });
+  } finally {
+    server.closeAllConnections();
+    server.close();
+  }
+
+  const report = await runJson(JSON.stringify({ request: { url: "https://example.com" }, runId: "ts-budget" }), {
+    async fetch() {
+      throw new BudgetExceeded("timeoutMillis", 10000);
+    },
+  });
+  assert.deepEqual(report.output.events[3].data, {
+    error: "timeoutMillis of 10000 exceeded",
+    code: "network.budget_exceeded",
+    params: { limit: "timeoutMillis", max: 10000 },
+  });
+});
+
+test("subscribers receive events as they are emitted", async () => {
+  const streamed = [];
+  let seenAtFetch = null;
+  const report = await runJson(
+    JSON.stringify({ request: { url: "https://example.com/posts/1" }, runId: "ts-streamed" }),
+    {
+      async fetch() {
+        seenAtFetch = streamed.map((event) => event.type);
+        return { status: 200, headers: {}, body: '{"id":1,"userId":2,"title":"t","body":"b"}' };
+      },
+    },
+    { subscribers: [(event) => streamed.push(event)] },
+  );
+
+  assert.deepEqual(seenAtFetch, ["start"]);
+  assert.deepEqual(streamed, report.output.events);
+});
+
+test("a time source stamps wallTime beside the logical clock", async () => {
+  let now = 1714566600000;
+  const input = JSON.stringify({ request: { url: "uma-fixture://sample-post" }, runId: "ts-wall" });
+  const report = await runJson(input, undefined, { timeSource: () => (now += 250) - 250 });
+
+  assert.deepEqual(
+    report.output.events.map((event) => [event.t, event.wallTime]),
+    [
+      ["0", "2024-05-01T12:30:00.000Z"],
+      ["1", "2024-05-01T12:30:00.250Z"],
+      ["2", "2024-05-01T12:30:00.500Z"],
+      ["3", "2024-05-01T12:30:00.750Z"],
+      ["4", "2024-05-01T12:30:01.000Z"],
+    ],
+  );
+  const plain = await runJson(input);
+  assert.equal(JSON.stringify(plain.output).includes("wallTime"), false);
+});
+
+test("a trace context is sent as traceparent and stamped on events and the lifecycle", async () => {
+  const seen = [];
+  const adapter = {
+    async fetch(request) {
+      seen.push(request.headers.traceparent);
+      return { status: 200, headers: {}, body: JSON.stringify({ id: 1, userId: 2, title: "t", body: "b" }) };
+    },
+  };
+  const traceId = "4bf92f3577b34da6a3ce929d0e0e4736";
+  const spanId = "00f067aa0ba902b7";
+  const input = (fields) =>
+    JSON.stringify({ request: { url: "https://example.com/posts/1" }, runId: "ts-trace", ...fields });
+  const report = await runJson(input({ traceId, spanId }), adapter);
+
+  assert.deepEqual(seen, [`00-${traceId}-${spanId}-01`]);
+  assert.equal(report.lifecycle.bindings["network.fetch"].impl, "custom");
+  assert.ok(report.output.events.every((event) => event.traceId === traceId && event.spanId === spanId));
+  assert.equal(report.lifecycle.traceId, traceId);
+  assert.equal(report.lifecycle.spanId, spanId);
+
+  const plain = await runJson(input({}), adapter);
+  assert.equal(JSON.stringify(plain).includes("traceId"), false);
+  await assert.rejects(runJson(input({ traceId }), adapter), /must be given together/);
+  await assert.rejects(runJson(input({ traceId: "0".repeat(32), spanId }), adapter), /`traceId` must be 32/);
+});
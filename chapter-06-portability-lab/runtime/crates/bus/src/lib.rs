@@ -1,9 +1,36 @@
 use anyhow::{Context, Result};
 use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
 use tracing::info;
 
+pub mod spool;
+
+pub use spool::{pending, recover, Spool};
+
 pub struct JsonlBus;
 
+/// Write-ahead spool used by [`publish_validated`] when enabled.
+static SPOOL: Mutex<Option<Spool>> = Mutex::new(None);
+
+/// Route every published event through a write-ahead spool at `path`.
+/// Call [`recover`] on the same path first to re-emit events a crashed run
+/// never printed.
+pub fn enable_spool(path: impl AsRef<Path>) -> Result<()> {
+    let spool = Spool::open(path)?;
+    *SPOOL.lock().unwrap_or_else(|e| e.into_inner()) = Some(spool);
+    Ok(())
+}
+
+/// Truncate and close the active spool on clean exit.  No-op when the spool
+/// is not enabled.
+pub fn finish_spool() -> Result<()> {
+    match SPOOL.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        Some(spool) => spool.finish(),
+        None => Ok(()),
+    }
+}
+
 fn schema_for<'a>(c: &'a contract::Contract, event: &str) -> Result<&'a serde_json::Value> {
     c.events
         .iter()
@@ -28,14 +55,29 @@ pub fn publish_validated<T: Serialize>(
     let json = serde_json::to_value(payload)?;
     validate_payload(c, event, &json)?;
     let line = format_event(event, &payload)?;
+    let mut spool = SPOOL.lock().unwrap_or_else(|e| e.into_inner());
+    let seq = match spool.as_mut() {
+        Some(spool) => Some(spool.record(&line)?),
+        None => None,
+    };
     // Distinguish events from logs in stdout
     println!("{}", line);
+    if let (Some(spool), Some(seq)) = (spool.as_mut(), seq) {
+        spool.ack(seq)?;
+    }
     info!(target: "uma.bus", event = event, "published");
     Ok(())
 }
 
+/// Check `json` against the contract schema for `event`, as
+/// [`publish_validated`] does before printing.  On wasm32 only the event's
+/// presence in the contract is checked.
 #[cfg(not(target_arch = "wasm32"))]
-fn validate_payload(c: &contract::Contract, event: &str, json: &serde_json::Value) -> Result<()> {
+pub fn validate_payload(
+    c: &contract::Contract,
+    event: &str,
+    json: &serde_json::Value,
+) -> Result<()> {
     let schema_val = schema_for(c, event)?.clone();
     if !jsonschema::is_valid(&schema_val, json) {
         return Err(anyhow::anyhow!("payload failed schema validation"));
@@ -44,7 +86,11 @@ fn validate_payload(c: &contract::Contract, event: &str, json: &serde_json::Valu
 }
 
 #[cfg(target_arch = "wasm32")]
-fn validate_payload(c: &contract::Contract, event: &str, _json: &serde_json::Value) -> Result<()> {
+pub fn validate_payload(
+    c: &contract::Contract,
+    event: &str,
+    _json: &serde_json::Value,
+) -> Result<()> {
     let _ = schema_for(c, event)?;
     Ok(())
 }
@@ -0,0 +1,191 @@
+//! Write-ahead spool for bus events.
+//!
+//! Every event line is appended to the spool and fsynced *before* it is
+//! printed, and an acknowledgement is appended once the print succeeded.
+//! [`recover`] re-emits every unacknowledged event, so no event is lost, but
+//! delivery is at-least-once: a crash after an event was printed and before
+//! its acknowledgement reached the spool re-emits an event consumers already
+//! saw.  Consumers that must not act twice should de-duplicate on the event.
+//! A clean shutdown truncates the spool.
+//!
+//! Spool records are JSON lines: `{"seq":1,"line":"..."}` for an event and
+//! `{"ack":1}` once it has been printed.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Record {
+    Event { seq: u64, line: String },
+    Ack { ack: u64 },
+}
+
+/// An open spool file.  Sequence numbers continue after any records
+/// already in the file.
+pub struct Spool {
+    path: PathBuf,
+    file: File,
+    next_seq: u64,
+}
+
+impl Spool {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let next_seq = read_records(&path)?
+            .iter()
+            .map(|r| match r {
+                Record::Event { seq, .. } => *seq,
+                Record::Ack { ack } => *ack,
+            })
+            .max()
+            .map_or(1, |seq| seq + 1);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("opening bus spool {}", path.display()))?;
+        Ok(Self {
+            path,
+            file,
+            next_seq,
+        })
+    }
+
+    /// Durably record an event line before it is emitted.
+    pub fn record(&mut self, line: &str) -> Result<u64> {
+        let seq = self.next_seq;
+        self.append(&Record::Event {
+            seq,
+            line: line.to_string(),
+        })?;
+        self.next_seq += 1;
+        Ok(seq)
+    }
+
+    /// Mark a recorded event as emitted.
+    pub fn ack(&mut self, seq: u64) -> Result<()> {
+        self.append(&Record::Ack { ack: seq })
+    }
+
+    /// Truncate the spool after a clean run.
+    pub fn finish(self) -> Result<()> {
+        self.file.set_len(0)?;
+        self.file.sync_all()?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn append(&mut self, record: &Record) -> Result<()> {
+        writeln!(self.file, "{}", serde_json::to_string(record)?)?;
+        self.file.flush()?;
+        self.file.sync_all()?;
+        Ok(())
+    }
+}
+
+fn read_records(path: &Path) -> Result<Vec<Record>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut records = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        // A torn final write from a crash is simply not a record.
+        if let Ok(record) = serde_json::from_str(&line) {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+/// Event lines in `path` that were recorded but never acknowledged, in the
+/// order they were recorded.
+pub fn pending(path: impl AsRef<Path>) -> Result<Vec<String>> {
+    let mut unacked = BTreeMap::new();
+    for record in read_records(path.as_ref())? {
+        match record {
+            Record::Event { seq, line } => {
+                unacked.insert(seq, line);
+            }
+            Record::Ack { ack } => {
+                unacked.remove(&ack);
+            }
+        }
+    }
+    Ok(unacked.into_values().collect())
+}
+
+/// Re-emit unacknowledged events from a previous run to `out`, then
+/// truncate the spool.  Returns the number of events re-emitted, which may
+/// include events printed just before the crash.
+pub fn recover_to(path: impl AsRef<Path>, out: &mut impl Write) -> Result<usize> {
+    let path = path.as_ref();
+    let lines = pending(path)?;
+    for line in &lines {
+        writeln!(out, "{}", line)?;
+    }
+    out.flush()?;
+    if path.exists() {
+        let file = OpenOptions::new().write(true).open(path)?;
+        file.set_len(0)?;
+        file.sync_all()?;
+    }
+    Ok(lines.len())
+}
+
+/// [`recover_to`] standard output, where the bus prints events.
+pub fn recover(path: impl AsRef<Path>) -> Result<usize> {
+    recover_to(path, &mut std::io::stdout().lock())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spool_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("bus-{}-{}.spool", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn unacked_events_are_recovered_once() {
+        let path = spool_path("recover");
+        let mut spool = Spool::open(&path).unwrap();
+        let first = spool.record("{\"event\":\"a\"}").unwrap();
+        spool.ack(first).unwrap();
+        spool.record("{\"event\":\"b\"}").unwrap();
+        // Simulate a crash: drop without finishing, plus a torn write.
+        drop(spool);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"seq\":3,\"li").unwrap();
+
+        assert_eq!(pending(&path).unwrap(), vec!["{\"event\":\"b\"}"]);
+        let mut out = Vec::new();
+        assert_eq!(recover_to(&path, &mut out).unwrap(), 1);
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"event\":\"b\"}\n");
+        assert!(pending(&path).unwrap().is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn finish_truncates_and_sequence_continues() {
+        let path = spool_path("finish");
+        let mut spool = Spool::open(&path).unwrap();
+        assert_eq!(spool.record("x").unwrap(), 1);
+        drop(spool);
+        let mut spool = Spool::open(&path).unwrap();
+        assert_eq!(spool.record("y").unwrap(), 2);
+        spool.finish().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
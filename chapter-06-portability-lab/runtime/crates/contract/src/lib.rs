@@ -1,6 +1,7 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::path::Path;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ServiceInfo {
@@ -8,10 +9,17 @@ pub struct ServiceInfo {
     pub version: String,
 }
 
+/// Input format names a capability may declare under `formats`.
+pub const IMAGE_FORMATS: [&str; 4] = ["pgm-ascii", "pgm-binary", "ppm", "png"];
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Capability {
     pub name: String,
     pub description: String,
+    /// Input formats the capability accepts, from [`IMAGE_FORMATS`].  Empty
+    /// means the capability does not restrict its input format.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub formats: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -31,7 +39,7 @@ pub struct Contract {
     pub capabilities: Vec<Capability>,
     pub events: Vec<EventDef>,
     pub execution: ExecutionConstraints,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
     pub parameters: serde_json::Value,
 }
 
@@ -41,9 +49,132 @@ impl Contract {
         let c: Contract = serde_json::from_str(&data)?;
         Ok(c)
     }
+
+    /// Load a base contract and apply overlay files in order, so later
+    /// overlays take precedence over earlier ones and over the base.  The
+    /// merged document is validated as a whole; an overlay may therefore
+    /// be partial, but the result must still be a complete contract.
+    pub fn load_with_overlays(base: &str, overlays: &[&str]) -> Result<Self> {
+        let mut merged = read_json(base)?;
+        for overlay in overlays {
+            merge_overlay(&mut merged, &read_json(overlay)?);
+        }
+        let c: Contract =
+            serde_json::from_value(merged).context("merged contract is incomplete")?;
+        c.validate()?;
+        Ok(c)
+    }
+
+    /// Load the base contract plus the overlay for `env`, if any.  Overlays
+    /// live next to the base contract as `overlays/<env>.json`.
+    pub fn load_for_env(base: &str, env: Option<&str>) -> Result<Self> {
+        match env {
+            None => Self::load_with_overlays(base, &[]),
+            Some(env) => {
+                let overlay = Path::new(base)
+                    .parent()
+                    .unwrap_or_else(|| Path::new("."))
+                    .join("overlays")
+                    .join(format!("{env}.json"));
+                if !overlay.exists() {
+                    bail!(
+                        "no contract overlay for environment '{env}' at {}",
+                        overlay.display()
+                    );
+                }
+                Self::load_with_overlays(
+                    base,
+                    &[overlay.to_str().context("overlay path is not UTF-8")?],
+                )
+            }
+        }
+    }
+
+    /// Checks that cannot be expressed by deserialization alone.  Run after
+    /// overlays are merged, since an overlay may change any of these.
+    pub fn validate(&self) -> Result<()> {
+        let mut names = std::collections::BTreeSet::new();
+        for event in &self.events {
+            if !names.insert(event.name.as_str()) {
+                bail!("event '{}' is declared more than once", event.name);
+            }
+        }
+        for capability in &self.capabilities {
+            for format in &capability.formats {
+                if !IMAGE_FORMATS.contains(&format.as_str()) {
+                    bail!(
+                        "capability '{}' declares unknown format '{format}'",
+                        capability.name
+                    );
+                }
+            }
+        }
+        let Some(gates) = self.execution.constraints.as_object() else {
+            bail!("execution.constraints must be an object");
+        };
+        for (name, gate) in gates {
+            match gate["compatibility"].as_str() {
+                Some("universal" | "target-specific" | "disabled") => {}
+                _ => bail!("capability gate '{name}' has an invalid compatibility"),
+            }
+        }
+        if let Some(tagging) = self.parameters().and_then(|p| p.get("tagging")) {
+            let threshold = |key: &str| -> Result<Option<f64>> {
+                match tagging.get(key) {
+                    None => Ok(None),
+                    Some(v) => match v.as_f64() {
+                        Some(t) if (0.0..=1.0).contains(&t) => Ok(Some(t)),
+                        _ => bail!("parameters.tagging.{key} must be a number in [0, 1]"),
+                    },
+                }
+            };
+            let dark = threshold("avg_dark_threshold")?;
+            let bright = threshold("avg_bright_threshold")?;
+            if let (Some(dark), Some(bright)) = (dark, bright) {
+                if dark > bright {
+                    bail!("parameters.tagging.avg_dark_threshold exceeds avg_bright_threshold");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_json(path: &str) -> Result<serde_json::Value> {
+    let data = fs::read_to_string(path).with_context(|| format!("reading {path}"))?;
+    serde_json::from_str(&data).with_context(|| format!("parsing {path}"))
+}
+
+/// Merge `overlay` into `base` with JSON merge-patch semantics: objects are
+/// merged key by key, `null` removes a key, and any other value (including
+/// arrays) replaces the base value outright.
+pub fn merge_overlay(base: &mut serde_json::Value, overlay: &serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                if value.is_null() {
+                    base.remove(key);
+                } else {
+                    merge_overlay(
+                        base.entry(key.clone()).or_insert(serde_json::Value::Null),
+                        value,
+                    );
+                }
+            }
+        }
+        (base, overlay @ serde_json::Value::Object(_)) => {
+            *base = serde_json::Value::Object(Default::default());
+            merge_overlay(base, overlay);
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
 }
 
 impl Contract {
+    pub fn capability(&self, name: &str) -> Option<&Capability> {
+        self.capabilities.iter().find(|c| c.name == name)
+    }
+
     pub fn parameters(&self) -> Option<&serde_json::Value> {
         if self.parameters.is_null() {
             None
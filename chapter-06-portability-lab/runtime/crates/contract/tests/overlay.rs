@@ -0,0 +1,83 @@
+use serde_json::json;
+
+const BASE: &str = "../../../CONTRACT.json";
+
+fn scratch_file(name: &str, value: serde_json::Value) -> String {
+    let path = std::env::temp_dir().join(format!("contract-{}-{}.json", name, std::process::id()));
+    std::fs::write(&path, value.to_string()).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn env_overlays_change_parameters_and_gates() {
+    let base = contract::Contract::load_for_env(BASE, None).unwrap();
+    assert_eq!(base.parameters["tagging"]["avg_dark_threshold"], 0.4);
+
+    let dev = contract::Contract::load_for_env(BASE, Some("dev")).unwrap();
+    assert_eq!(dev.parameters["tagging"]["avg_dark_threshold"], 0.3);
+    assert_eq!(dev.service.name, base.service.name);
+    assert_eq!(dev.events.len(), base.events.len());
+
+    let prod = contract::Contract::load_for_env(BASE, Some("prod")).unwrap();
+    let gate = &prod.execution.constraints["native-gpu"];
+    assert_eq!(gate["compatibility"], "disabled");
+    // Keys the overlay does not mention survive the merge.
+    assert_eq!(
+        gate["scope"],
+        base.execution.constraints["native-gpu"]["scope"]
+    );
+
+    assert!(contract::Contract::load_for_env(BASE, Some("staging")).is_err());
+}
+
+#[test]
+fn later_overlays_take_precedence() {
+    let first = scratch_file(
+        "first",
+        json!({ "parameters": { "tagging": { "avg_dark_threshold": 0.1, "avg_bright_threshold": 0.9 } } }),
+    );
+    let second = scratch_file(
+        "second",
+        json!({ "parameters": { "tagging": { "avg_bright_threshold": 0.5 } } }),
+    );
+    let c = contract::Contract::load_with_overlays(BASE, &[&first, &second]).unwrap();
+    assert_eq!(c.parameters["tagging"]["avg_dark_threshold"], 0.1);
+    assert_eq!(c.parameters["tagging"]["avg_bright_threshold"], 0.5);
+}
+
+#[test]
+fn merged_contract_is_validated() {
+    let inverted = scratch_file(
+        "inverted",
+        json!({ "parameters": { "tagging": { "avg_dark_threshold": 0.8 } } }),
+    );
+    let err = contract::Contract::load_with_overlays(BASE, &[&inverted]).unwrap_err();
+    assert!(err.to_string().contains("exceeds"));
+
+    let removed = scratch_file("removed", json!({ "events": null }));
+    assert!(contract::Contract::load_with_overlays(BASE, &[&removed]).is_err());
+
+    let bad_gate = scratch_file(
+        "bad-gate",
+        json!({ "execution": { "constraints": { "portable": { "compatibility": "sometimes" } } } }),
+    );
+    assert!(contract::Contract::load_with_overlays(BASE, &[&bad_gate]).is_err());
+
+    // Arrays replace, so the overlay restates every capability.
+    let bad_format = scratch_file(
+        "bad-format",
+        json!({ "capabilities": [{ "name": "analyze.image", "description": "d", "formats": ["gif"] }] }),
+    );
+    let err = contract::Contract::load_with_overlays(BASE, &[&bad_format]).unwrap_err();
+    assert!(err.to_string().contains("unknown format 'gif'"));
+}
+
+#[test]
+fn merge_overlay_follows_merge_patch_rules() {
+    let mut base = json!({ "a": { "b": 1, "c": [1, 2] }, "d": 1 });
+    contract::merge_overlay(
+        &mut base,
+        &json!({ "a": { "c": [3], "e": { "f": null } }, "d": null }),
+    );
+    assert_eq!(base, json!({ "a": { "b": 1, "c": [3], "e": {} } }));
+}
@@ -0,0 +1,100 @@
+// @generated by scripts/generate_schema_tests.py from scripts/schema_tests.json.
+// Do not edit: change the schema or the type, then rerun the script.
+
+use jsonschema::{Draft, JSONSchema};
+use serde_json::Value;
+
+/// The schema at `pointer` inside `source`, with the root's definitions in
+/// scope.  Draft 7 ignores keywords next to `$ref`, so the root's own
+/// constraints do not apply.
+fn compile(source: &str, pointer: &str) -> JSONSchema {
+    let mut schema: Value = serde_json::from_str(source).expect("schema is JSON");
+    if !pointer.is_empty() {
+        schema["$ref"] = Value::String(format!("#{pointer}"));
+    }
+    JSONSchema::options()
+        .with_draft(Draft::Draft7)
+        .compile(&schema)
+        .expect("schema compiles")
+}
+
+fn assert_valid(schema: &JSONSchema, document: &Value, what: &str) {
+    if let Err(errors) = schema.validate(document) {
+        let errors: Vec<String> = errors
+            .map(|e| format!("{}: {e}", e.instance_path))
+            .collect();
+        panic!("{what} does not match the schema: {errors:?}\n{document:#}");
+    }
+}
+
+#[test]
+fn contract_minimal_sample_round_trips() {
+    let schema = compile(
+        include_str!("../../../../schemas/uma-contract.schema.json"),
+        "",
+    );
+    let sample: Value = serde_json::from_str(
+        r#"{
+  "capabilities": [],
+  "events": [],
+  "execution": {
+    "constraints": {}
+  },
+  "service": {
+    "name": "example",
+    "version": "example"
+  }
+}"#,
+    )
+    .unwrap();
+    assert_valid(&schema, &sample, "the minimal sample");
+    let typed: contract::Contract = serde_json::from_value(sample.clone())
+        .expect("the minimal sample deserializes into contract::Contract");
+    let back = serde_json::to_value(&typed).unwrap();
+    assert_valid(&schema, &back, "contract::Contract serialized");
+}
+
+#[test]
+fn contract_full_sample_round_trips() {
+    let schema = compile(
+        include_str!("../../../../schemas/uma-contract.schema.json"),
+        "",
+    );
+    let sample: Value = serde_json::from_str(
+        r#"{
+  "capabilities": [
+    {
+      "description": "example",
+      "formats": [
+        "png"
+      ],
+      "name": "example"
+    }
+  ],
+  "events": [
+    {
+      "name": "example",
+      "schema": {}
+    }
+  ],
+  "execution": {
+    "constraints": {}
+  },
+  "parameters": {},
+  "service": {
+    "name": "example",
+    "version": "example"
+  }
+}"#,
+    )
+    .unwrap();
+    assert_valid(&schema, &sample, "the full sample");
+    let typed: contract::Contract = serde_json::from_value(sample.clone())
+        .expect("the full sample deserializes into contract::Contract");
+    let back = serde_json::to_value(&typed).unwrap();
+    assert_valid(&schema, &back, "contract::Contract serialized");
+    assert_eq!(
+        back, sample,
+        "fields were lost or changed in the round trip"
+    );
+}
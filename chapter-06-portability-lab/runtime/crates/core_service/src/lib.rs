@@ -1,7 +1,11 @@
 use anyhow::{Context, Result};
 use serde::Serialize;
+use serde_json::json;
+use std::fmt;
 use std::fs;
 
+pub mod serve;
+
 fn thresholds_from_contract(c: &contract::Contract) -> (f32, f32) {
     let mut dark = 0.4f32;
     let mut bright = 0.6f32;
@@ -32,9 +36,183 @@ pub struct AnalysisResult {
     pub metrics: ImageMetrics,
 }
 
-/// Parse a simple ASCII PGM (P2) and return pixel values
+/// Input formats recognised by [`detect_format`].  Names match
+/// `contract::IMAGE_FORMATS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    PgmAscii,
+    PgmBinary,
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ImageFormat::PgmAscii => "pgm-ascii",
+            ImageFormat::PgmBinary => "pgm-binary",
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+/// Formats this service can decode.  The contract may narrow the set but
+/// cannot extend it.
+pub const DECODABLE_FORMATS: [ImageFormat; 1] = [ImageFormat::PgmAscii];
+
+/// Identify an image by its magic bytes.
+pub fn detect_format(bytes: &[u8]) -> Option<ImageFormat> {
+    match bytes {
+        [b'P', b'2', ..] => Some(ImageFormat::PgmAscii),
+        [b'P', b'5', ..] => Some(ImageFormat::PgmBinary),
+        [b'P', b'3' | b'6', ..] => Some(ImageFormat::Ppm),
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => Some(ImageFormat::Png),
+        _ => None,
+    }
+}
+
+/// Formats `analyze.image` accepts under `contract`: those it declares,
+/// limited to what this service decodes.  A contract that declares no
+/// formats accepts everything decodable.
+pub fn supported_formats(contract: &contract::Contract) -> Vec<ImageFormat> {
+    let declared = contract
+        .capability("analyze.image")
+        .map(|c| c.formats.as_slice())
+        .unwrap_or_default();
+    DECODABLE_FORMATS
+        .into_iter()
+        .filter(|f| declared.is_empty() || declared.iter().any(|d| d == f.as_str()))
+        .collect()
+}
+
+/// An input refused by [`negotiate_format`]: what was detected (`None` when
+/// the format is not recognised) and what would have been accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatUnsupported {
+    pub detected: Option<ImageFormat>,
+    pub supported: Vec<ImageFormat>,
+}
+
+impl FormatUnsupported {
+    /// The `format.unsupported` event payload.
+    pub fn payload(&self, service_name: &str, path: &str) -> serde_json::Value {
+        serde_json::json!({
+            "service": service_name,
+            "path": path,
+            "format": self.detected.map_or("unknown", ImageFormat::as_str),
+            "supported": self.supported.iter().map(|f| f.as_str()).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Detect the format of `bytes` and check it against [`supported_formats`].
+pub fn negotiate_format(
+    bytes: &[u8],
+    contract: &contract::Contract,
+) -> std::result::Result<ImageFormat, FormatUnsupported> {
+    let supported = supported_formats(contract);
+    match detect_format(bytes) {
+        Some(format) if supported.contains(&format) => Ok(format),
+        detected => Err(FormatUnsupported {
+            detected,
+            supported,
+        }),
+    }
+}
+
+/// Why [`analyze_image`] did not analyze an input.  Every variant but
+/// [`AnalyzeError::Publish`] has its own status event, published before the
+/// error is returned, so runners and their consumers can tell the failures
+/// apart without parsing messages.
+#[derive(Debug)]
+pub enum AnalyzeError {
+    /// The input could not be read.  Published as `image.unreadable`.
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    /// The input format is not accepted under the contract.  Published as
+    /// `format.unsupported`.
+    UnsupportedFormat(FormatUnsupported),
+    /// The input has an accepted format but does not decode.  Published as
+    /// `image.corrupt`.
+    CorruptImage { detail: String },
+    /// A payload does not match its event's schema, or the contract does not
+    /// declare the event.  Published as `schema.violation` when the contract
+    /// declares that event.
+    SchemaViolation { event: String, detail: String },
+    /// The bus could not record a validated event, e.g. the spool failed.
+    /// Nothing is published.
+    Publish(anyhow::Error),
+}
+
+impl AnalyzeError {
+    /// The status event reporting this error and its payload, or `None` for
+    /// [`AnalyzeError::Publish`].
+    pub fn status_event(
+        &self,
+        service_name: &str,
+        path: &str,
+    ) -> Option<(&'static str, serde_json::Value)> {
+        let event = match self {
+            AnalyzeError::Io { source, .. } => (
+                "image.unreadable",
+                json!({ "service": service_name, "path": path, "reason": source.to_string() }),
+            ),
+            AnalyzeError::UnsupportedFormat(unsupported) => (
+                "format.unsupported",
+                unsupported.payload(service_name, path),
+            ),
+            AnalyzeError::CorruptImage { detail } => (
+                "image.corrupt",
+                json!({ "service": service_name, "path": path, "detail": detail }),
+            ),
+            AnalyzeError::SchemaViolation { event, detail } => (
+                "schema.violation",
+                json!({ "service": service_name, "event": event, "detail": detail }),
+            ),
+            AnalyzeError::Publish(_) => return None,
+        };
+        Some(event)
+    }
+}
+
+impl fmt::Display for AnalyzeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnalyzeError::Io { path, source } => write!(f, "open {path}: {source}"),
+            AnalyzeError::UnsupportedFormat(unsupported) => write!(
+                f,
+                "unsupported format {}",
+                unsupported.detected.map_or("unknown", ImageFormat::as_str)
+            ),
+            AnalyzeError::CorruptImage { detail } => write!(f, "corrupt image: {detail}"),
+            AnalyzeError::SchemaViolation { event, detail } => {
+                write!(f, "event '{event}' violates the contract: {detail}")
+            }
+            AnalyzeError::Publish(err) => write!(f, "publishing failed: {err:#}"),
+        }
+    }
+}
+
+impl std::error::Error for AnalyzeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AnalyzeError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a simple ASCII PGM (P2) file and return pixel values
 pub(crate) fn load_pgm_ascii(path: &str) -> Result<(usize, usize, Vec<u16>, u16)> {
     let contents = fs::read_to_string(path).with_context(|| format!("open {}", path))?;
+    parse_pgm_ascii(&contents)
+}
+
+/// Parse the text of an ASCII PGM (P2) and return pixel values
+pub(crate) fn parse_pgm_ascii(contents: &str) -> Result<(usize, usize, Vec<u16>, u16)> {
     let mut lines = contents.lines();
 
     // magic
@@ -73,7 +251,21 @@ pub(crate) fn load_pgm_ascii(path: &str) -> Result<(usize, usize, Vec<u16>, u16)
 }
 
 pub fn analyze_image_data(path: &str, contract: &contract::Contract) -> Result<AnalysisResult> {
-    let (w, h, px, maxval) = load_pgm_ascii(path)?;
+    let pixels = load_pgm_ascii(path)?;
+    Ok(analyze_pixels(pixels, contract))
+}
+
+/// Analyze an image already in memory.  `bytes` must be in a format
+/// [`negotiate_format`] accepted.
+pub fn analyze_bytes(bytes: &[u8], contract: &contract::Contract) -> Result<AnalysisResult> {
+    let contents = std::str::from_utf8(bytes).context("ASCII PGM is not valid UTF-8")?;
+    Ok(analyze_pixels(parse_pgm_ascii(contents)?, contract))
+}
+
+fn analyze_pixels(
+    (w, h, px, maxval): (usize, usize, Vec<u16>, u16),
+    contract: &contract::Contract,
+) -> AnalysisResult {
     let sum: u64 = px.iter().map(|&v| v as u64).sum();
     let avg = sum as f32 / (px.len() as f32);
     let avg_norm = if maxval > 0 { avg / maxval as f32 } else { 0.0 };
@@ -107,20 +299,75 @@ pub fn analyze_image_data(path: &str, contract: &contract::Contract) -> Result<A
         avg: avg_norm,
         contrast,
     };
-    Ok(AnalysisResult { tags, metrics })
+    AnalysisResult { tags, metrics }
+}
+
+/// Analyze the image at `path` and publish the outcome: `image.analyzed` on
+/// success, otherwise the status event of the [`AnalyzeError`] returned.  The
+/// input format is checked against [`supported_formats`] first, so an
+/// unsupported image is reported as such rather than as a corrupt one.
+pub fn analyze_image(
+    path: &str,
+    service_name: &str,
+    contract: &contract::Contract,
+) -> std::result::Result<AnalysisResult, AnalyzeError> {
+    let outcome = analyze_file(path, contract);
+    let (event, payload) = match &outcome {
+        Ok(result) => (
+            "image.analyzed",
+            json!({
+                "service": service_name,
+                "path": path,
+                "tags": result.tags,
+                "metrics": result.metrics,
+            }),
+        ),
+        Err(err) => err
+            .status_event(service_name, path)
+            .expect("analysis errors have a status event"),
+    };
+    match publish(contract, event, &payload) {
+        Ok(()) => outcome,
+        Err(violation @ AnalyzeError::SchemaViolation { .. }) => {
+            if let Some((event, payload)) = violation.status_event(service_name, path) {
+                // Best effort: a contract without `schema.violation` still
+                // gets the error back.
+                let _ = publish(contract, event, &payload);
+            }
+            Err(violation)
+        }
+        Err(err) => Err(err),
+    }
 }
 
-pub fn analyze_image(path: &str, service_name: &str, contract: &contract::Contract) -> Result<()> {
-    let result = analyze_image_data(path, contract)?;
-
-    let payload = serde_json::json!({
-        "service": service_name,
-        "path": path,
-        "tags": result.tags,
-        "metrics": result.metrics,
-    });
-    bus::publish_validated(contract, "image.analyzed", &payload)?;
-    Ok(())
+fn analyze_file(
+    path: &str,
+    contract: &contract::Contract,
+) -> std::result::Result<AnalysisResult, AnalyzeError> {
+    let bytes = fs::read(path).map_err(|source| AnalyzeError::Io {
+        path: path.to_string(),
+        source,
+    })?;
+    negotiate_format(&bytes, contract).map_err(AnalyzeError::UnsupportedFormat)?;
+    analyze_bytes(&bytes, contract).map_err(|err| AnalyzeError::CorruptImage {
+        detail: format!("{err:#}"),
+    })
+}
+
+/// Validate and publish one event, keeping schema failures apart from bus
+/// failures.
+fn publish(
+    contract: &contract::Contract,
+    event: &str,
+    payload: &serde_json::Value,
+) -> std::result::Result<(), AnalyzeError> {
+    bus::validate_payload(contract, event, payload).map_err(|err| {
+        AnalyzeError::SchemaViolation {
+            event: event.to_string(),
+            detail: format!("{err:#}"),
+        }
+    })?;
+    bus::publish_validated(contract, event, payload).map_err(AnalyzeError::Publish)
 }
 
 #[cfg(test)]
@@ -317,7 +564,104 @@ mod tests {
         let path = write_temp_pgm(pgm);
         let contract = contract::Contract::load_from("../../../CONTRACT.json").unwrap();
 
-        analyze_image(path.to_str().unwrap(), "core-service", &contract).unwrap();
+        let result = analyze_image(path.to_str().unwrap(), "core-service", &contract).unwrap();
+        assert_eq!(result.tags, vec!["high_contrast".to_string()]);
+    }
+
+    #[test]
+    fn detects_formats_by_magic_bytes() {
+        assert_eq!(detect_format(b"P2\n2 2\n"), Some(ImageFormat::PgmAscii));
+        assert_eq!(detect_format(b"P5\n2 2\n"), Some(ImageFormat::PgmBinary));
+        assert_eq!(detect_format(b"P3\n"), Some(ImageFormat::Ppm));
+        assert_eq!(detect_format(b"P6\n"), Some(ImageFormat::Ppm));
+        assert_eq!(
+            detect_format(b"\x89PNG\r\n\x1a\n\0\0"),
+            Some(ImageFormat::Png)
+        );
+        assert_eq!(detect_format(b"GIF89a"), None);
+        assert_eq!(detect_format(b""), None);
+    }
+
+    #[test]
+    fn contract_formats_narrow_but_never_extend_decoding() {
+        let mut contract = contract::Contract::load_from("../../../CONTRACT.json").unwrap();
+        assert_eq!(supported_formats(&contract), vec![ImageFormat::PgmAscii]);
+
+        contract.capabilities[0].formats = vec!["png".to_string()];
+        assert!(supported_formats(&contract).is_empty());
+
+        contract.capabilities[0].formats.clear();
+        assert_eq!(supported_formats(&contract), DECODABLE_FORMATS.to_vec());
+    }
+
+    #[test]
+    fn unsupported_formats_publish_a_status_event() {
+        let path = write_temp_pgm("P5\n2 2\n255\n\0\0\0\0");
+        let contract = contract::Contract::load_from("../../../CONTRACT.json").unwrap();
+        let err = analyze_image(path.to_str().unwrap(), "core-service", &contract).unwrap_err();
+        assert!(matches!(
+            &err,
+            AnalyzeError::UnsupportedFormat(FormatUnsupported {
+                detected: Some(ImageFormat::PgmBinary),
+                ..
+            })
+        ));
+        assert_eq!(
+            err.status_event("svc", "p").unwrap().0,
+            "format.unsupported"
+        );
+
+        // The status event is validated like any other.
+        let mut contract = contract.clone();
+        contract.events.retain(|e| e.name != "format.unsupported");
+        let err = analyze_image(path.to_str().unwrap(), "core-service", &contract).unwrap_err();
+        assert!(
+            matches!(&err, AnalyzeError::SchemaViolation { event, .. } if event == "format.unsupported")
+        );
+        assert!(err.to_string().contains("schema not found"));
+    }
+
+    #[test]
+    fn each_failure_has_its_own_status_event() {
+        let contract = contract::Contract::load_from("../../../CONTRACT.json").unwrap();
+        let corrupt = write_temp_pgm("P2\n2 2\n255\n0 255 255\n");
+        let err = analyze_image(corrupt.to_str().unwrap(), "core-service", &contract).unwrap_err();
+        assert!(
+            matches!(&err, AnalyzeError::CorruptImage { detail } if detail.contains("pixel count mismatch"))
+        );
+
+        let errors = [
+            AnalyzeError::Io {
+                path: "p".to_string(),
+                source: std::io::Error::from(std::io::ErrorKind::NotFound),
+            },
+            AnalyzeError::UnsupportedFormat(FormatUnsupported {
+                detected: None,
+                supported: DECODABLE_FORMATS.to_vec(),
+            }),
+            err,
+            AnalyzeError::SchemaViolation {
+                event: "image.analyzed".to_string(),
+                detail: "payload failed schema validation".to_string(),
+            },
+        ];
+        let mut names = Vec::new();
+        for err in &errors {
+            let (event, payload) = err.status_event("core-service", "p").unwrap();
+            bus::validate_payload(&contract, event, &payload).unwrap();
+            names.push(event);
+        }
+        assert_eq!(
+            names,
+            [
+                "image.unreadable",
+                "format.unsupported",
+                "image.corrupt",
+                "schema.violation"
+            ]
+        );
+        let publish = AnalyzeError::Publish(anyhow::anyhow!("spool full"));
+        assert!(publish.status_event("core-service", "p").is_none());
     }
 
     #[test]
@@ -328,6 +672,9 @@ mod tests {
         contract.events.clear();
 
         let err = analyze_image(path.to_str().unwrap(), "core-service", &contract).unwrap_err();
+        assert!(
+            matches!(&err, AnalyzeError::SchemaViolation { event, .. } if event == "image.analyzed")
+        );
         assert!(err.to_string().contains("schema not found"));
     }
 
@@ -335,6 +682,10 @@ mod tests {
     fn analyze_image_propagates_analysis_errors() {
         let contract = contract::Contract::load_from("../../../CONTRACT.json").unwrap();
         let err = analyze_image("/definitely/missing/file.pgm", "core-service", &contract).unwrap_err();
+        assert!(matches!(
+            &err,
+            AnalyzeError::Io { source, .. } if source.kind() == std::io::ErrorKind::NotFound
+        ));
         assert!(err.to_string().contains("open /definitely/missing/file.pgm"));
     }
 }
@@ -0,0 +1,173 @@
+//! Long-running service mode.  Each input line is one analysis request and
+//! each output line the matching result envelope, so an orchestrator can keep
+//! one warm analyzer instance for many images instead of instantiating the
+//! module per image.
+//!
+//! Requests are JSON objects with an optional `id`, echoed back unchanged,
+//! and either a `path` to read or `data` holding the image as base64:
+//!
+//! ```text
+//! {"id":1,"path":"../sample-data/sample.pgm"}
+//! {"id":2,"data":"UDIKMiAyCjEwCjAgMTAgMTAgMAo="}
+//! ```
+//!
+//! Results carry the same `event`/`payload` pair the bus prints, validated
+//! against the contract: `image.analyzed` or `format.unsupported`.  A request
+//! that cannot be served gets `{"id":..,"error":".."}` instead, and the
+//! service keeps going.
+
+use crate::{analyze_bytes, negotiate_format};
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+
+/// `path` reported in payloads for requests that sent inline `data`.
+pub const INLINE_PATH: &str = "<inline>";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    path: Option<String>,
+    data: Option<String>,
+}
+
+/// Serve requests from `input` until end of input, writing one envelope per
+/// request to `output` and flushing after each.  Blank lines are ignored.
+/// Only I/O errors on `input` or `output` end the loop.
+pub fn serve(
+    input: impl BufRead,
+    mut output: impl Write,
+    service_name: &str,
+    contract: &contract::Contract,
+) -> Result<()> {
+    for line in input.lines() {
+        let line = line.context("reading request")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let envelope = handle(&line, service_name, contract);
+        writeln!(output, "{envelope}")?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+/// The envelope answering one request line.
+pub fn handle(line: &str, service_name: &str, contract: &contract::Contract) -> Value {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => return json!({ "id": Value::Null, "error": format!("invalid request: {err}") }),
+    };
+    match respond(&request, service_name, contract) {
+        Ok((event, payload)) => json!({ "id": request.id, "event": event, "payload": payload }),
+        Err(err) => json!({ "id": request.id, "error": format!("{err:#}") }),
+    }
+}
+
+fn respond(
+    request: &Request,
+    service_name: &str,
+    contract: &contract::Contract,
+) -> Result<(&'static str, Value)> {
+    let (bytes, path) = match (&request.path, &request.data) {
+        (Some(path), None) => {
+            let bytes = std::fs::read(path).with_context(|| format!("open {}", path))?;
+            (bytes, path.as_str())
+        }
+        (None, Some(data)) => {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(data)
+                .context("data is not valid base64")?;
+            (bytes, INLINE_PATH)
+        }
+        _ => bail!("request needs exactly one of `path` or `data`"),
+    };
+    let (event, payload) = match negotiate_format(&bytes, contract) {
+        Err(unsupported) => (
+            "format.unsupported",
+            unsupported.payload(service_name, path),
+        ),
+        Ok(_) => {
+            let result = analyze_bytes(&bytes, contract)?;
+            let payload = json!({
+                "service": service_name,
+                "path": path,
+                "tags": result.tags,
+                "metrics": result.metrics,
+            });
+            ("image.analyzed", payload)
+        }
+    };
+    bus::validate_payload(contract, event, &payload)?;
+    Ok((event, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contract() -> contract::Contract {
+        contract::Contract::load_from("../../../CONTRACT.json").unwrap()
+    }
+
+    #[test]
+    fn answers_each_request_line_in_order() {
+        let input = [
+            r#"{"id":1,"path":"../../../sample-data/sample.pgm"}"#,
+            "",
+            r#"{"id":"two","data":"UDIKMiAyCjEwCjAgMTAgMTAgMAo="}"#,
+            r#"{"id":3,"data":"UDUK"}"#,
+        ]
+        .join("\n");
+        let mut output = Vec::new();
+        serve(input.as_bytes(), &mut output, "svc", &contract()).unwrap();
+
+        let lines: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["id"], 1);
+        assert_eq!(lines[0]["event"], "image.analyzed");
+        assert_eq!(lines[0]["payload"]["tags"], json!(["high_contrast"]));
+        assert_eq!(lines[1]["id"], "two");
+        assert_eq!(lines[1]["payload"]["path"], INLINE_PATH);
+        assert_eq!(lines[1]["payload"]["metrics"]["width"], 2);
+        assert_eq!(lines[2]["event"], "format.unsupported");
+        assert_eq!(lines[2]["payload"]["format"], "pgm-binary");
+    }
+
+    #[test]
+    fn bad_requests_get_an_error_envelope() {
+        let contract = contract();
+        let error = |line: &str| handle(line, "svc", &contract)["error"].clone();
+        assert!(error("not json")
+            .as_str()
+            .unwrap()
+            .starts_with("invalid request"));
+        assert!(error(r#"{"id":1}"#)
+            .as_str()
+            .unwrap()
+            .contains("exactly one"));
+        assert!(error(r#"{"data":"%%%"}"#)
+            .as_str()
+            .unwrap()
+            .contains("base64"));
+        assert!(error(r#"{"path":"/definitely/missing.pgm"}"#)
+            .as_str()
+            .unwrap()
+            .contains("open /definitely/missing.pgm"));
+        // P2 magic with a broken body passes negotiation but fails decoding.
+        let envelope = handle(r#"{"id":9,"data":"UDIKMiAyCjEwCjEK"}"#, "svc", &contract);
+        assert_eq!(envelope["id"], 9);
+        assert!(envelope["error"]
+            .as_str()
+            .unwrap()
+            .contains("pixel count mismatch"));
+    }
+}
@@ -37,9 +37,19 @@ fn main() -> Result<()> {
     } else {
         repo_root.join("CONTRACT.json")
     };
-    let contract = contract::Contract::load_from(contract_path.to_str().unwrap())?;
+    // UMA_ENV selects an overlay such as `overlays/dev.json` on top of the base contract.
+    let env = std::env::var("UMA_ENV").ok();
+    let contract =
+        contract::Contract::load_for_env(contract_path.to_str().unwrap(), env.as_deref())?;
     let svc = format!("{}:{}", contract.service.name, contract.service.version);
 
+    // Optional write-ahead spool: re-emit events a crashed run never printed.
+    let spool = std::env::var("UMA_BUS_SPOOL").ok();
+    if let Some(spool) = &spool {
+        bus::recover(spool)?;
+        bus::enable_spool(spool)?;
+    }
+
     let args: Vec<String> = std::env::args().collect();
     let path = args.get(1).cloned().unwrap_or_else(|| {
         let relative = PathBuf::from("../sample-data/sample.pgm");
@@ -53,7 +63,17 @@ fn main() -> Result<()> {
         }
     });
 
-    core_service::analyze_image(&path, &svc, &contract)?;
+    match core_service::analyze_image(&path, &svc, &contract) {
+        Ok(_) => {}
+        Err(core_service::AnalyzeError::Publish(err)) => return Err(err),
+        Err(err) => {
+            // The error's status event has been published; report failure by
+            // exit code.
+            eprintln!("{err}");
+            bus::finish_spool()?;
+            std::process::exit(1);
+        }
+    }
 
     // Enforce capability gate by contract scope
     let allow_gpu =
@@ -79,5 +99,6 @@ fn main() -> Result<()> {
         bus::publish_validated(&contract, "gpu.telemetry.reported", &err)?;
     }
 
+    bus::finish_spool()?;
     Ok(())
 }
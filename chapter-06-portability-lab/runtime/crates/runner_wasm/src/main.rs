@@ -8,10 +8,26 @@ fn main() -> Result<()> {
     } else {
         repo_root.join("CONTRACT.json")
     };
-    let contract = contract::Contract::load_from(contract_path.to_str().unwrap())?;
+    // UMA_ENV selects an overlay such as `overlays/dev.json` on top of the base contract.
+    let env = std::env::var("UMA_ENV").ok();
+    let contract =
+        contract::Contract::load_for_env(contract_path.to_str().unwrap(), env.as_deref())?;
     let svc = format!("{}:{}", contract.service.name, contract.service.version);
 
     let args: Vec<String> = std::env::args().collect();
+    // `--serve`: answer one analysis request per stdin line until EOF.
+    if args.get(1).map(String::as_str) == Some("--serve") {
+        let stdin = std::io::stdin();
+        return core_service::serve::serve(stdin.lock(), std::io::stdout().lock(), &svc, &contract);
+    }
+
+    // Optional write-ahead spool: re-emit events a crashed run never printed.
+    let spool = std::env::var("UMA_BUS_SPOOL").ok();
+    if let Some(spool) = &spool {
+        bus::recover(spool)?;
+        bus::enable_spool(spool)?;
+    }
+
     let path = args.get(1).cloned().unwrap_or_else(|| {
         let relative = PathBuf::from("../sample-data/sample.pgm");
         if relative.exists() {
@@ -24,6 +40,17 @@ fn main() -> Result<()> {
         }
     });
 
-    core_service::analyze_image(&path, &svc, &contract)?;
+    match core_service::analyze_image(&path, &svc, &contract) {
+        Ok(_) => {}
+        Err(core_service::AnalyzeError::Publish(err)) => return Err(err),
+        Err(err) => {
+            // The error's status event has been published; report failure by
+            // exit code.
+            eprintln!("{err}");
+            bus::finish_spool()?;
+            std::process::exit(1);
+        }
+    }
+    bus::finish_spool()?;
     Ok(())
 }
@@ -56,13 +56,13 @@ require_cmd wasmtime
 printf "not a pgm" > "$TMP_DIR/bad.pgm"
 
 pushd "$ROOT_DIR/runtime" >/dev/null
-echo "Corrupted image run: expect native validation failure"
+echo "Corrupted image run: expect format.unsupported and a non-zero exit"
 if cargo run --locked -p runner_native -- "$TMP_DIR/bad.pgm"; then
   echo "Expected native runner to reject malformed PGM input." >&2
   exit 1
 fi
 
-echo "WASM runner without repo preopen: expect access denial or file-open failure"
+echo "WASM runner without repo preopen: expect access denial or image.unreadable"
 rustup target add wasm32-wasip1 >/dev/null 2>&1 || true
 cargo build --locked -p runner_wasm --target wasm32-wasip1 >/dev/null
 if wasmtime run target/wasm32-wasip1/debug/runner_wasm.wasm "$TMP_DIR/bad.pgm"; then
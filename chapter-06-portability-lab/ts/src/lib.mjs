@@ -14,6 +14,33 @@ export async function loadContract(rootDir) {
   return JSON.parse(source);
 }
 
+// Formats the Rust core decodes; the contract may narrow this set.
+const DECODABLE_FORMATS = ["pgm-ascii"];
+
+export function detectFormat(bytes) {
+  const magic = String.fromCharCode(...bytes.subarray(0, 2));
+  if (magic === "P2") {
+    return "pgm-ascii";
+  }
+  if (magic === "P5") {
+    return "pgm-binary";
+  }
+  if (magic === "P3" || magic === "P6") {
+    return "ppm";
+  }
+  const png = [0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a];
+  if (bytes.length >= png.length && png.every((byte, i) => bytes[i] === byte)) {
+    return "png";
+  }
+  return null;
+}
+
+export function supportedFormats(contract) {
+  const capability = contract.capabilities?.find((c) => c.name === "analyze.image");
+  const declared = capability?.formats ?? [];
+  return DECODABLE_FORMATS.filter((format) => declared.length === 0 || declared.includes(format));
+}
+
 export function parsePgmAscii(source) {
   const lines = source
     .split(/\r?\n/)
@@ -81,8 +108,37 @@ export function analyzeImageData(pgmSource, contract) {
 
 export async function renderEvent(rootDir, imagePath, serviceName = "uma.image-analyzer:1.0.0") {
   const contract = await loadContract(rootDir);
-  const pgmSource = await readFile(imagePath, "utf8");
-  const result = analyzeImageData(pgmSource, contract);
+  let bytes;
+  try {
+    bytes = await readFile(imagePath);
+  } catch (error) {
+    return {
+      event: "image.unreadable",
+      payload: { service: serviceName, path: imagePath, reason: error.message },
+    };
+  }
+  const supported = supportedFormats(contract);
+  const format = detectFormat(bytes);
+  if (!supported.includes(format)) {
+    return {
+      event: "format.unsupported",
+      payload: {
+        service: serviceName,
+        path: imagePath,
+        format: format ?? "unknown",
+        supported,
+      },
+    };
+  }
+  let result;
+  try {
+    result = analyzeImageData(bytes.toString("utf8"), contract);
+  } catch (error) {
+    return {
+      event: "image.corrupt",
+      payload: { service: serviceName, path: imagePath, detail: error.message },
+    };
+  }
 
   return {
     event: "image.analyzed",
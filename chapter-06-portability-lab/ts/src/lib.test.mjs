@@ -3,7 +3,7 @@ import assert from "node:assert/strict";
 import path from "node:path";
 import { mkdtemp, writeFile } from "node:fs/promises";
 import os from "node:os";
-import { analyzeImageData, parsePgmAscii, renderEvent } from "./lib.mjs";
+import { analyzeImageData, detectFormat, parsePgmAscii, renderEvent } from "./lib.mjs";
 
 const rootDir = path.resolve(path.dirname(new URL(import.meta.url).pathname), "..", "..");
 
@@ -35,9 +35,28 @@ test("contract thresholds change tags", () => {
   assert.deepEqual(result.tags, ["mostly_dark"]);
 });
 
-test("pixel count mismatch is rejected", async () => {
+test("pixel count mismatch produces an image.corrupt event", async () => {
   const tmpDir = await mkdtemp(path.join(os.tmpdir(), "uma-ch6-ts-"));
   const imagePath = path.join(tmpDir, "bad.pgm");
   await writeFile(imagePath, "P2\n2 2\n255\n1 2\n", "utf8");
-  await assert.rejects(renderEvent(rootDir, imagePath), /pixel count mismatch/);
+  const event = await renderEvent(rootDir, imagePath);
+  assert.deepEqual(event, {
+    event: "image.corrupt",
+    payload: { service: "uma.image-analyzer:1.0.0", path: imagePath, detail: "pixel count mismatch" },
+  });
+  const missing = await renderEvent(rootDir, path.join(tmpDir, "missing.pgm"));
+  assert.equal(missing.event, "image.unreadable");
+});
+
+test("unsupported formats produce a format.unsupported event", async () => {
+  assert.equal(detectFormat(Buffer.from("P6\n")), "ppm");
+  assert.equal(detectFormat(Buffer.from([0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a])), "png");
+  const tmpDir = await mkdtemp(path.join(os.tmpdir(), "uma-ch6-ts-"));
+  const imagePath = path.join(tmpDir, "binary.pgm");
+  await writeFile(imagePath, Buffer.from("P5\n2 2\n255\n\0\0\0\0", "latin1"));
+  const event = await renderEvent(rootDir, imagePath);
+  assert.deepEqual(event, {
+    event: "format.unsupported",
+    payload: { service: "uma.image-analyzer:1.0.0", path: imagePath, format: "pgm-binary", supported: ["pgm-ascii"] },
+  });
 });
@@ -22,6 +22,9 @@ async function main() {
   const rootDir = path.resolve(path.dirname(new URL(import.meta.url).pathname), "..", "..");
   const event = await renderEvent(rootDir, path.resolve(imagePath));
   console.log(JSON.stringify(event));
+  if (event.event !== "image.analyzed") {
+    process.exit(1);
+  }
 }
 
 await main();
@@ -0,0 +1,350 @@
+//! Event provenance: every envelope carries a `umasignature` made with a key
+//! the orchestrator issued to the service named in its `source`, and the
+//! orchestrator checks that signature before routing the event.  A module
+//! holding one service's key cannot produce events that verify as another
+//! service's, so a compromised or misconfigured module cannot spoof them.
+//!
+//! Keys are HMAC-SHA256 keys derived from a single orchestrator secret, so
+//! only the orchestrator needs to hold the secret and every service key can
+//! be re-derived for verification.  The orchestrator uses [`Keyring`]; a
+//! module seals its output with [`seal`], using the key it was issued in
+//! [`KEY_ENV`].
+
+use hmac::{Hmac, Mac};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Prefix of `umasignature`, naming the algorithm.
+const SCHEME: &str = "hmac-sha256:";
+
+/// The environment variable through which a module receives its signing
+/// key, hex encoded.
+pub const KEY_ENV: &str = "UMA_SIGNING_KEY";
+
+static EVENT_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+/// The orchestrator's secret, from which each service's signing key is
+/// derived.
+pub struct Keyring {
+    secret: Vec<u8>,
+}
+
+impl Keyring {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            secret: secret.to_vec(),
+        }
+    }
+
+    /// A keyring from `UMA_SIGNING_SECRET`, or a fresh secret for this run
+    /// when it is unset.  Events only need to verify within the run that
+    /// wrote them, so a per-run secret is enough unless another process
+    /// verifies the logs.
+    pub fn from_env() -> Self {
+        match std::env::var("UMA_SIGNING_SECRET") {
+            Ok(secret) if !secret.is_empty() => Self::new(secret.as_bytes()),
+            _ => {
+                let nanos = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos();
+                let seed = format!("{nanos}:{}", std::process::id());
+                Self::new(&Sha256::digest(seed.as_bytes()))
+            }
+        }
+    }
+
+    /// The signer holding `service_id`'s key, to be handed to that service
+    /// only.
+    pub fn signer(&self, service_id: &str) -> Signer {
+        Signer {
+            service_id: service_id.to_string(),
+            key: self.key_for(service_id),
+        }
+    }
+
+    /// Check that `envelope` was signed with the key of the service named in
+    /// its `source`.
+    pub fn verify(&self, envelope: &Map<String, Value>) -> Result<(), String> {
+        let source = envelope
+            .get("source")
+            .and_then(Value::as_str)
+            .ok_or("envelope has no source")?;
+        let signature = envelope
+            .get("umasignature")
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("unsigned event from {source}"))?;
+        let tag = signature
+            .strip_prefix(SCHEME)
+            .and_then(decode_hex)
+            .ok_or_else(|| format!("malformed signature on event from {source}"))?;
+        let mut mac = HmacSha256::new_from_slice(&self.key_for(source))
+            .expect("HMAC accepts keys of any length");
+        mac.update(&signing_input(envelope));
+        mac.verify_slice(&tag)
+            .map_err(|_| format!("signature does not match source {source}"))
+    }
+
+    fn key_for(&self, service_id: &str) -> Vec<u8> {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(b"uma.service:");
+        mac.update(service_id.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+}
+
+/// A service's signing key.
+pub struct Signer {
+    service_id: String,
+    key: Vec<u8>,
+}
+
+impl Signer {
+    /// The key issued to `service_id` through [`KEY_ENV`], or `None` when
+    /// the module was run without one.
+    pub fn from_env(service_id: &str) -> Result<Option<Self>, String> {
+        let Ok(hex) = std::env::var(KEY_ENV) else {
+            return Ok(None);
+        };
+        let key = decode_hex(&hex).ok_or_else(|| format!("{KEY_ENV} is not hex"))?;
+        Ok(Some(Self {
+            service_id: service_id.to_string(),
+            key,
+        }))
+    }
+
+    pub fn service_id(&self) -> &str {
+        &self.service_id
+    }
+
+    /// The key, hex encoded, as a module expects it in [`KEY_ENV`].
+    pub fn key_hex(&self) -> String {
+        encode_hex(&self.key)
+    }
+
+    /// A signed envelope for `data`, with this service as its `source`.
+    pub fn envelope(&self, event_type: &str, data: Value) -> Map<String, Value> {
+        self.envelope_at(event_type, data, &timestamp())
+    }
+
+    /// Like [`Signer::envelope`], with an explicit envelope `time`.
+    pub fn envelope_at(&self, event_type: &str, data: Value, time: &str) -> Map<String, Value> {
+        let mut envelope = Map::new();
+        envelope.insert("specversion".into(), Value::String("1.0".into()));
+        envelope.insert("id".into(), Value::String(next_event_id()));
+        envelope.insert("source".into(), Value::String(self.service_id.clone()));
+        envelope.insert("type".into(), Value::String(event_type.to_string()));
+        envelope.insert("time".into(), Value::String(time.to_string()));
+        envelope.insert(
+            "datacontenttype".into(),
+            Value::String("application/json".into()),
+        );
+        envelope.insert("data".into(), data);
+        envelope.insert(
+            "umaserviceid".into(),
+            Value::String(self.service_id.clone()),
+        );
+        self.sign(&mut envelope);
+        envelope
+    }
+
+    /// Set `umasignature` on a complete envelope.
+    pub fn sign(&self, envelope: &mut Map<String, Value>) {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(&signing_input(envelope));
+        let tag = encode_hex(&mac.finalize().into_bytes());
+        envelope.insert(
+            "umasignature".into(),
+            Value::String(format!("{SCHEME}{tag}")),
+        );
+    }
+}
+
+/// `data` in an envelope signed with the key issued to `service_id` through
+/// [`KEY_ENV`], or `data` itself when the module was run without one.  Fails
+/// when the key is malformed; a module reports that on stderr and exits
+/// non-zero, so the orchestrator treats it as a failed run.
+pub fn seal(service_id: &str, event_type: &str, data: Value) -> Result<Value, String> {
+    Ok(match Signer::from_env(service_id)? {
+        Some(signer) => Value::Object(signer.envelope(event_type, data)),
+        None => data,
+    })
+}
+
+/// Seconds since the epoch with a `Z` suffix, the envelope `time` format.
+pub fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    format!("{secs}Z")
+}
+
+fn next_event_id() -> String {
+    let count = EVENT_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!("evt-{now}-{count}")
+}
+
+/// The signed fields, one per line: `id`, `source`, `type`, `time` and
+/// `data` as canonical JSON.
+fn signing_input(envelope: &Map<String, Value>) -> Vec<u8> {
+    let field = |name: &str| {
+        envelope
+            .get(name)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+    };
+    let mut data = String::new();
+    if let Some(value) = envelope.get("data") {
+        canonical_json(value, &mut data);
+    }
+    format!(
+        "{}\n{}\n{}\n{}\n{data}",
+        field("id"),
+        field("source"),
+        field("type"),
+        field("time")
+    )
+    .into_bytes()
+}
+
+/// Compact JSON with object keys sorted and integral floats written as
+/// integers, as the JavaScript runner writes them, so an envelope signed on
+/// one side verifies on the other.
+fn canonical_json(value: &Value, out: &mut String) {
+    match value {
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                canonical_json(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(fields) => {
+            let mut names: Vec<&String> = fields.keys().collect();
+            names.sort();
+            out.push('{');
+            for (i, name) in names.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(name.clone()).to_string());
+                out.push(':');
+                canonical_json(&fields[name], out);
+            }
+            out.push('}');
+        }
+        Value::Number(number) => match number.as_f64() {
+            Some(float) if number.is_f64() && float.fract() == 0.0 && float.abs() < 1e21 => {
+                out.push_str(&format!("{:.0}", float + 0.0));
+            }
+            _ => out.push_str(&number.to_string()),
+        },
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn envelope(source: &str) -> Map<String, Value> {
+        json!({
+            "id": "evt-1",
+            "source": source,
+            "type": "image.analyzed.v1",
+            "time": "1700000000Z",
+            "data": { "id": "img-001", "tags": ["even"] },
+        })
+        .as_object()
+        .unwrap()
+        .clone()
+    }
+
+    #[test]
+    fn events_verify_only_under_their_own_source() {
+        let keyring = Keyring::new(b"test-secret");
+        let mut genuine = envelope("image.tagger");
+        keyring.signer("image.tagger").sign(&mut genuine);
+        assert_eq!(keyring.verify(&genuine), Ok(()));
+
+        // edge.cache's key cannot vouch for an event claiming image.tagger.
+        let mut spoofed = envelope("image.tagger");
+        keyring.signer("edge.cache").sign(&mut spoofed);
+        assert_eq!(
+            keyring.verify(&spoofed),
+            Err("signature does not match source image.tagger".to_string())
+        );
+
+        let mut tampered = genuine.clone();
+        tampered["data"]["tags"] = json!(["odd"]);
+        assert!(keyring.verify(&tampered).is_err());
+
+        let other_run = Keyring::new(b"another-secret");
+        assert!(other_run.verify(&genuine).is_err());
+        assert_eq!(
+            keyring.verify(&envelope("image.tagger")),
+            Err("unsigned event from image.tagger".to_string())
+        );
+    }
+
+    #[test]
+    fn modules_seal_envelopes_with_the_issued_key() {
+        let keyring = Keyring::new(b"test-secret");
+        let issued = keyring.signer("edge.cache").key_hex();
+        let module = Signer {
+            service_id: "edge.cache".into(),
+            key: decode_hex(&issued).unwrap(),
+        };
+        let sealed = module.envelope("cache.persisted.v1", json!({ "status": "passed" }));
+        assert_eq!(sealed["source"], "edge.cache");
+        assert_eq!(sealed["umaserviceid"], "edge.cache");
+        assert_eq!(keyring.verify(&sealed), Ok(()));
+
+        // The same key cannot sign as another service.
+        let impostor = Signer {
+            service_id: "image.tagger".into(),
+            key: decode_hex(&issued).unwrap(),
+        };
+        let forged = impostor.envelope("image.analyzed.v1", json!({ "id": "img-001" }));
+        assert!(keyring.verify(&forged).is_err());
+    }
+
+    #[test]
+    fn canonical_json_matches_the_javascript_runner() {
+        let mut out = String::new();
+        canonical_json(
+            &json!({ "b": [1.0, 0.5, -0.0, 3], "a": { "z": null, "y": "q\"" } }),
+            &mut out,
+        );
+        assert_eq!(out, r#"{"a":{"y":"q\"","z":null},"b":[1,0.5,0,3]}"#);
+    }
+}
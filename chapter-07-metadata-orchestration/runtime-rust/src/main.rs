@@ -1,14 +1,17 @@
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
 use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::time::Instant;
+use uma_provenance::{Keyring, Signer, KEY_ENV};
+use uma_schema_registry::{Registry, SchemaClient};
 
-static EVENT_COUNTER: AtomicU64 = AtomicU64::new(1);
+mod manifest;
+use manifest::Manifest;
 
 #[derive(Debug, Deserialize)]
 struct Contract {
@@ -18,6 +21,8 @@ struct Contract {
     events: ContractEvents,
     #[serde(default)]
     constraints: ContractConstraints,
+    #[serde(default)]
+    parameters: Value,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -31,7 +36,6 @@ struct ContractEvents {
 #[derive(Debug, Deserialize)]
 struct EventSpec {
     name: String,
-    #[allow(dead_code)]
     schema: String,
 }
 
@@ -69,12 +73,23 @@ struct PolicyCondition {
 struct TaggerInput {
     id: String,
     bytes: Vec<u8>,
+    config: TaggerConfig,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct TaggerConfig {
+    confidence_threshold: f64,
+    debug: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
 struct ImageAnalyzed {
     id: String,
     tags: Vec<String>,
+    #[serde(default)]
+    confidence: BTreeMap<String, f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    raw_scores: Option<BTreeMap<String, f64>>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -106,12 +121,34 @@ fn run() -> Result<(), Box<dyn Error>> {
     let policy_digest = sha256_file(root.join("contracts/schemas/policy.standard.v1.json"))?;
     println!("[info] policy.digest {policy_digest}");
 
-    let tagger = load_contract(root.join("contracts/image.tagger.contract.yaml"))?;
-    let logger = load_contract(root.join("contracts/telemetry.logger.contract.yaml"))?;
-    let edge_cache = load_contract(root.join("contracts/edge.cache.contract.yaml"))?;
-    let evaluator = load_contract(root.join("contracts/ai.model.evaluator.contract.yaml"))?;
+    let manifest_path = std::env::var("PIPELINE_MANIFEST")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| root.join("contracts/pipeline.manifest.yaml"));
+    let manifest = Manifest::load(&manifest_path)?;
+    let plan = manifest.plan(&root)?;
+    println!(
+        "[info] plan.loaded {} {} deliveries={}",
+        manifest.name,
+        manifest.version,
+        plan.deliveries.len()
+    );
+    let tagger = plan.contract("image.tagger")?;
+    let logger = plan.contract("telemetry.logger")?;
+    let edge_cache = plan.contract("edge.cache")?;
+    let evaluator = plan.contract("ai.model.evaluator")?;
 
-    let policy_check = enforce_policy(&root, &evaluator)?;
+    let registry_dir = std::env::var("SCHEMA_REGISTRY_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| root.join("registry"));
+    let mut schemas = SchemaClient::new(Registry::open(&registry_dir));
+    prefetch_schemas(&mut schemas, &[tagger, logger, edge_cache, evaluator])?;
+    println!(
+        "[info] schema.cached {} from {}",
+        schemas.cached(),
+        registry_dir.display()
+    );
+
+    let policy_check = enforce_policy(&root, evaluator)?;
     let fail_mode = std::env::var("POLICY_FAIL_MODE").unwrap_or_else(|_| "closed".to_string());
     if let Some(reason) = policy_check {
         if fail_mode == "closed" {
@@ -121,70 +158,305 @@ fn run() -> Result<(), Box<dyn Error>> {
         println!("[warn] policy.violation {reason} continuing due to fail-open");
     }
 
-    print_binding(&tagger, &logger);
-    print_binding(&tagger, &edge_cache);
-    print_binding(&tagger, &evaluator);
+    // Each WASM module is issued only its own key and signs its own events.
+    // The logger and evaluator are not invoked here: the runner computes
+    // their results in-process and publishes them under its own key, like
+    // the events it originates.
+    let keyring = Keyring::from_env();
+    let runner = keyring.signer("cloud-runner");
+    let tagger_signer = keyring.signer(&tagger.name);
+    let edge_cache_signer = keyring.signer(&edge_cache.name);
+
+    for (publisher, subscriber) in plan.bindings() {
+        print_binding(publisher, subscriber);
+    }
 
+    let debug = std::env::var("TAGGER_DEBUG").is_ok_and(|v| v == "1");
     let input = TaggerInput {
         id: "img-001".to_string(),
         bytes: (0..8).collect(),
+        config: tagger_config(tagger, debug),
     };
 
-    let tagger_output: ImageAnalyzed = run_wasmtime(
-        root.join("services/image.tagger/target/wasm32-wasip1/release/image_tagger.wasm"),
-        &input,
+    let analyzed = verify_envelope(
+        &keyring,
+        run_wasmtime(
+            plan.module(&tagger.name)?.to_path_buf(),
+            &input,
+            &tagger_signer,
+        )?,
     )?;
+    let tagger_output: ImageAnalyzed = serde_json::from_value(analyzed.data().clone())?;
 
     validate_image_analyzed(&tagger_output)?;
-    println!("[info] validation.passed event_schema=image.analyzed.v1");
-    write_event_envelope(
-        "image.analyzed.v1",
+    check_required(
+        schemas.fetch_event("image.analyzed.v1")?,
         &serde_json::to_value(&tagger_output)?,
-        &tagger.name,
-        &tagger.version,
     )?;
+    println!("[info] validation.passed event_schema=image.analyzed.v1");
+    let analyzed_at = analyzed.time().to_string();
+    let analyzed_id = analyzed.id().to_string();
+    write_event_envelope(analyzed, &tagger.version)?;
 
-    let telemetry = validate_telemetry(&tagger_output);
-    println!(
-        "[info] telemetry.{} {}",
-        if telemetry.status == "passed" { "ok" } else { "error" },
-        serde_json::to_string(&telemetry)?
-    );
-    write_event_envelope(
-        "telemetry.validation.v1",
-        &serde_json::to_value(&telemetry)?,
-        &logger.name,
-        &logger.version,
-    )?;
+    if plan.delivers("image.analyzed.v1", &logger.name) {
+        let telemetry = validate_telemetry(&tagger_output);
+        println!(
+            "[info] telemetry.{} {}",
+            if telemetry.status == "passed" { "ok" } else { "error" },
+            serde_json::to_string(&telemetry)?
+        );
+        let envelope =
+            runner.envelope("telemetry.validation.v1", serde_json::to_value(&telemetry)?);
+        write_event_envelope(verify_envelope(&keyring, envelope)?, &logger.version)?;
+    } else {
+        print_skip("image.analyzed.v1", logger);
+    }
 
-    let cache_output: ValidationStatus = run_wasmtime(
-        root.join("services/edge.cache/target/wasm32-wasip1/release/edge_cache.wasm"),
-        &tagger_output,
-    )?;
-    println!(
-        "[info] cache.{} {}",
-        if cache_output.status == "passed" { "ok" } else { "error" },
-        serde_json::to_string(&cache_output)?
-    );
-    write_event_envelope(
-        "cache.persisted.v1",
-        &serde_json::to_value(&cache_output)?,
-        &edge_cache.name,
-        &edge_cache.version,
-    )?;
+    let max_attempts = std::env::var("MAX_EVENT_ATTEMPTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(plan.max_attempts);
+    let mut dispatcher =
+        Dispatcher::new(max_attempts, PathBuf::from("logs/dlq")).with_limit(plan.max_dispatches);
 
-    let evaluator_output = evaluate(&tagger_output);
-    println!("[info] evaluator.ok {}", serde_json::to_string(&evaluator_output)?);
-    write_event_envelope(
-        "inference.completed.v1",
-        &serde_json::to_value(&evaluator_output)?,
-        &evaluator.name,
-        &evaluator.version,
-    )?;
+    if plan.delivers("image.analyzed.v1", &edge_cache.name) {
+        let edge_cache_wasm = plan.module(&edge_cache.name)?.to_path_buf();
+        let mut cache_input = serde_json::to_value(&tagger_output)?;
+        cache_input["time"] = Value::String(analyzed_at);
+        let cache_output = dispatcher.dispatch(
+            &analyzed_id,
+            "image.analyzed.v1",
+            &edge_cache.name,
+            &cache_input,
+            || {
+                // An unsealed or forged status is a failed delivery, so it is
+                // retried and dead-lettered like any other.
+                let out = verify_envelope(
+                    &keyring,
+                    run_wasmtime(edge_cache_wasm.clone(), &cache_input, &edge_cache_signer)?,
+                )?;
+                let data = out.data();
+                match (
+                    data.get("status"),
+                    data.get("reason").and_then(Value::as_str),
+                ) {
+                    (Some(status), Some(reason)) if status == "failed" => Err(reason.into()),
+                    _ => Ok(out),
+                }
+            },
+        )?;
+        if let Some(cache_output) = cache_output {
+            let status: ValidationStatus = serde_json::from_value(cache_output.data().clone())?;
+            println!(
+                "[info] cache.{} {}",
+                if status.status == "passed" { "ok" } else { "error" },
+                serde_json::to_string(&status)?
+            );
+            write_event_envelope(cache_output, &edge_cache.version)?;
+        }
+
+        // Read back through the cache's query mode to show it serves lookups too.
+        let query = json!({ "type": "cache.query.v1", "id": tagger_output.id });
+        let query_envelope =
+            verify_envelope(&keyring, runner.envelope("cache.query.v1", query.clone()))?;
+        let query_id = query_envelope.id().to_string();
+        write_event_envelope(query_envelope, "1.0.0")?;
+        let query_output = dispatcher.dispatch(
+            &query_id,
+            "cache.query.v1",
+            &edge_cache.name,
+            &query,
+            || {
+                verify_envelope(
+                    &keyring,
+                    run_wasmtime(edge_cache_wasm.clone(), &query, &edge_cache_signer)?,
+                )
+            },
+        )?;
+        if let Some(query_output) = query_output {
+            let result = query_output.data();
+            println!(
+                "[info] cache.query.{} {}",
+                if result["status"] == "passed" { "ok" } else { "error" },
+                serde_json::to_string(result)?
+            );
+            write_event_envelope(query_output, &edge_cache.version)?;
+        }
+    } else {
+        print_skip("image.analyzed.v1", edge_cache);
+    }
+
+    if plan.delivers("image.analyzed.v1", &evaluator.name) {
+        let evaluator_output = evaluate(&tagger_output);
+        println!(
+            "[info] evaluator.ok {}",
+            serde_json::to_string(&evaluator_output)?
+        );
+        let envelope = runner.envelope(
+            "inference.completed.v1",
+            serde_json::to_value(&evaluator_output)?,
+        );
+        write_event_envelope(verify_envelope(&keyring, envelope)?, &evaluator.version)?;
+    } else {
+        print_skip("image.analyzed.v1", evaluator);
+    }
+
+    let summary = dispatcher.summary();
+    println!("[info] summary {}", serde_json::to_string(&summary)?);
+    let envelope = runner.envelope("orchestration.summary.v1", serde_json::to_value(summary)?);
+    write_event_envelope(verify_envelope(&keyring, envelope)?, "1.0.0")?;
 
     Ok(())
 }
 
+/// Delivery attempts per event and subscriber before the event is treated as
+/// poison and parked in the dead-letter queue.
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Counts reported in the final `orchestration.summary.v1` event.
+#[derive(Debug, Default, Serialize, PartialEq)]
+struct DispatchSummary {
+    dispatched: u32,
+    retries: u32,
+    poison: u32,
+    /// `<event id>:<subscriber>` for each poisoned delivery, in order.
+    poisoned: Vec<String>,
+}
+
+/// Delivers events to subscribers with bounded retries.  An event that keeps
+/// failing is written to the DLQ with `poison: true` and the pipeline moves on.
+struct Dispatcher {
+    max_attempts: u32,
+    /// Deliveries allowed in the run, from the manifest's limits policy.
+    max_dispatches: Option<u32>,
+    dlq_dir: PathBuf,
+    attempts: BTreeMap<String, u32>,
+    summary: DispatchSummary,
+}
+
+impl Dispatcher {
+    fn new(max_attempts: u32, dlq_dir: PathBuf) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            max_dispatches: None,
+            dlq_dir,
+            attempts: BTreeMap::new(),
+            summary: DispatchSummary::default(),
+        }
+    }
+
+    fn with_limit(mut self, max_dispatches: Option<u32>) -> Self {
+        self.max_dispatches = max_dispatches;
+        self
+    }
+
+    /// Deliver `data` to `subscriber`, retrying up to the attempt limit.
+    /// Returns `None` when the event was poisoned.  Only DLQ write failures
+    /// and exceeding the dispatch limit abort the run.
+    fn dispatch<T>(
+        &mut self,
+        event_id: &str,
+        event_type: &str,
+        subscriber: &str,
+        data: &Value,
+        mut deliver: impl FnMut() -> Result<T, Box<dyn Error>>,
+    ) -> Result<Option<T>, Box<dyn Error>> {
+        let key = format!("{event_id}:{subscriber}");
+        if let Some(limit) = self.max_dispatches {
+            if self.summary.dispatched >= limit {
+                return Err(format!("dispatch.limit {limit} deliveries reached at {key}").into());
+            }
+        }
+        self.summary.dispatched += 1;
+        loop {
+            let attempt = {
+                let count = self.attempts.entry(key.clone()).or_insert(0);
+                *count += 1;
+                *count
+            };
+            match deliver() {
+                Ok(out) => return Ok(Some(out)),
+                Err(err) if attempt < self.max_attempts => {
+                    self.summary.retries += 1;
+                    println!("[warn] dispatch.retry {key} attempt={attempt} {err}");
+                }
+                Err(err) => {
+                    let error = err.to_string();
+                    self.dead_letter(event_id, event_type, subscriber, data, attempt, &error)?;
+                    println!("[warn] dlq.poison {key} attempts={attempt} {err}");
+                    self.summary.poison += 1;
+                    self.summary.poisoned.push(key);
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    fn dead_letter(
+        &self,
+        event_id: &str,
+        event_type: &str,
+        subscriber: &str,
+        data: &Value,
+        attempts: u32,
+        error: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&self.dlq_dir)?;
+        let record = json!({
+            "eventId": event_id,
+            "type": event_type,
+            "subscriber": subscriber,
+            "attempts": attempts,
+            "error": error,
+            "poison": true,
+            "data": data,
+        });
+        let path = self.dlq_dir.join(format!("{event_id}-{subscriber}.json"));
+        fs::write(path, serde_json::to_string_pretty(&record)?)?;
+        Ok(())
+    }
+
+    fn summary(&self) -> &DispatchSummary {
+        &self.summary
+    }
+}
+
+/// An envelope whose signature checked out against the key of the service
+/// named in its `source`.  Only verified envelopes are routed or recorded.
+struct Verified(Map<String, Value>);
+
+impl Verified {
+    fn field(&self, name: &str) -> &str {
+        self.0.get(name).and_then(Value::as_str).unwrap_or_default()
+    }
+
+    fn id(&self) -> &str {
+        self.field("id")
+    }
+
+    fn time(&self) -> &str {
+        self.field("time")
+    }
+
+    fn data(&self) -> &Value {
+        self.0.get("data").unwrap_or(&Value::Null)
+    }
+}
+
+/// Check the signature of an envelope a service produced, so an event is only
+/// routed or recorded if the service it names in `source` signed it.
+fn verify_envelope(
+    keyring: &Keyring,
+    envelope: Map<String, Value>,
+) -> Result<Verified, Box<dyn Error>> {
+    let envelope = Verified(envelope);
+    keyring
+        .verify(&envelope.0)
+        .map_err(|reason| format!("provenance.rejected {} {reason}", envelope.id()))?;
+    println!("[info] provenance.verified {}", envelope.field("type"));
+    Ok(envelope)
+}
+
 fn ensure_log_dirs() -> Result<(), Box<dyn Error>> {
     fs::create_dir_all("logs/events")?;
     Ok(())
@@ -215,11 +487,59 @@ fn sha256_file(path: PathBuf) -> Result<String, Box<dyn Error>> {
     Ok(digest.iter().map(|b| format!("{b:02x}")).collect())
 }
 
+/// Fetch the schema of every event the contracts emit, so a missing or
+/// unregistered schema stops the run before any service executes.  A
+/// contract names its schema by file; the file stem is the registry key.
+fn prefetch_schemas(
+    client: &mut SchemaClient,
+    contracts: &[&Contract],
+) -> Result<(), Box<dyn Error>> {
+    for contract in contracts {
+        for emit in &contract.events.emits {
+            let event_type = Path::new(&emit.schema)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or_else(|| format!("{}: bad schema path {}", contract.name, emit.schema))?;
+            client
+                .fetch_event(event_type)
+                .map_err(|err| format!("{} emits {}: {err}", contract.name, emit.name))?;
+        }
+    }
+    Ok(())
+}
+
+/// Fields the registry schema marks as `required` must be present.
+fn check_required(schema: &Value, event: &Value) -> Result<(), Box<dyn Error>> {
+    let missing: Vec<&str> = schema["required"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .filter(|field| event.get(field).is_none())
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("validation.failed missing required {}", missing.join(", ")).into())
+    }
+}
+
 fn load_contract(path: PathBuf) -> Result<Contract, Box<dyn Error>> {
     let raw = fs::read_to_string(path)?;
     Ok(serde_yaml::from_str(&raw)?)
 }
 
+/// Tagger configuration from the contract's `parameters`.  `TAGGER_DEBUG=1`
+/// forces `raw_scores` on regardless of the contract.
+fn tagger_config(contract: &Contract, debug_override: bool) -> TaggerConfig {
+    TaggerConfig {
+        confidence_threshold: contract.parameters["confidence_threshold"]
+            .as_f64()
+            .unwrap_or(0.5),
+        debug: debug_override || contract.parameters["debug"].as_bool().unwrap_or(false),
+    }
+}
+
 fn match_pattern(pattern: &str, event_name: &str) -> bool {
     if let Some(prefix) = pattern.strip_suffix(".*") {
         event_name.starts_with(prefix)
@@ -253,6 +573,11 @@ fn print_binding(publisher: &Contract, subscriber: &Contract) {
     }
 }
 
+/// A subscriber the contracts would bind but the manifest does not wire.
+fn print_skip(event: &str, subscriber: &Contract) {
+    println!("[info] plan.skip {event} → {}", subscriber.name);
+}
+
 fn enforce_policy(root: &Path, evaluator: &Contract) -> Result<Option<String>, Box<dyn Error>> {
     let raw = fs::read_to_string(root.join("contracts/policies/org.telemetry.standard.json"))?;
     let policy: PolicyFile = serde_json::from_str(&raw)?;
@@ -274,7 +599,14 @@ fn enforce_policy(root: &Path, evaluator: &Contract) -> Result<Option<String>, B
     Ok(None)
 }
 
-fn run_wasmtime<TInput, TOutput>(wasm_path: PathBuf, input: &TInput) -> Result<TOutput, Box<dyn Error>>
+/// Run a module, issuing it `signer`'s key in `UMA_SIGNING_KEY` so it signs
+/// its own output.  The key is forwarded from the child's environment rather
+/// than passed on the command line.
+fn run_wasmtime<TInput, TOutput>(
+    wasm_path: PathBuf,
+    input: &TInput,
+    signer: &Signer,
+) -> Result<TOutput, Box<dyn Error>>
 where
     TInput: Serialize,
     TOutput: for<'de> Deserialize<'de>,
@@ -282,7 +614,14 @@ where
     let start = Instant::now();
     let input_json = serde_json::to_vec(input)?;
     let output = Command::new("wasmtime")
-        .args(["run", "--dir=.", wasm_path.to_string_lossy().as_ref()])
+        .args([
+            "run",
+            "--dir=.",
+            "--env",
+            KEY_ENV,
+            wasm_path.to_string_lossy().as_ref(),
+        ])
+        .env(KEY_ENV, signer.key_hex())
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .spawn()
@@ -318,6 +657,13 @@ fn validate_image_analyzed(event: &ImageAnalyzed) -> Result<(), Box<dyn Error>>
     if event.tags.is_empty() || event.tags.iter().any(|tag| tag.trim().is_empty()) {
         return Err("validation.failed tags must be a non-empty string array".into());
     }
+    if event
+        .confidence
+        .values()
+        .any(|score| !(0.0..=1.0).contains(score))
+    {
+        return Err("validation.failed confidence scores must be within [0, 1]".into());
+    }
     Ok(())
 }
 
@@ -364,26 +710,23 @@ fn log_telemetry(payload: Value) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Record a verified envelope in `logs/events`, named by its id, adding the
+/// runtime's own attributes, which the signature does not cover.
 fn write_event_envelope(
-    event_type: &str,
-    data: &Value,
-    service_id: &str,
+    envelope: Verified,
     contract_version: &str,
 ) -> Result<PathBuf, Box<dyn Error>> {
     ensure_log_dirs()?;
-    let id = next_event_id();
-    let mut envelope = Map::new();
-    envelope.insert("specversion".into(), Value::String("1.0".into()));
-    envelope.insert("id".into(), Value::String(id.clone()));
-    envelope.insert("source".into(), Value::String(service_id.to_string()));
-    envelope.insert("type".into(), Value::String(event_type.to_string()));
-    envelope.insert("time".into(), Value::String(iso_timestamp()));
-    envelope.insert(
-        "datacontenttype".into(),
-        Value::String("application/json".into()),
-    );
-    envelope.insert("data".into(), data.clone());
-    envelope.insert("umaserviceid".into(), Value::String(service_id.to_string()));
+    let id = envelope.id().to_string();
+    if id.is_empty()
+        || id.starts_with('.')
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(format!("event id {id:?} is not a valid file name").into());
+    }
+    let mut envelope = envelope.0;
     envelope.insert(
         "umacontractversion".into(),
         Value::String(contract_version.to_string()),
@@ -393,30 +736,17 @@ fn write_event_envelope(
     envelope.insert("reasonCode".into(), Value::String("OK".into()));
 
     let path = Path::new("logs/events").join(format!("{id}.json"));
-    fs::write(&path, serde_json::to_string_pretty(&Value::Object(envelope))?)?;
+    fs::write(
+        &path,
+        serde_json::to_string_pretty(&Value::Object(envelope))?,
+    )?;
     Ok(path)
 }
 
-fn next_event_id() -> String {
-    let count = EVENT_COUNTER.fetch_add(1, Ordering::Relaxed);
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_nanos();
-    format!("evt-{now}-{count}")
-}
-
-fn iso_timestamp() -> String {
-    let secs = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-    format!("{secs}Z")
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
 
     #[test]
     fn wildcard_binding_matches_image_events() {
@@ -431,6 +761,7 @@ mod tests {
                 subscribes: Vec::new(),
             },
             constraints: ContractConstraints::default(),
+            parameters: Value::Null,
         };
         let subscriber = Contract {
             name: "telemetry.logger".into(),
@@ -442,6 +773,7 @@ mod tests {
                 }],
             },
             constraints: ContractConstraints::default(),
+            parameters: Value::Null,
         };
 
         assert_eq!(bindings(&publisher, &subscriber), vec!["image.analyzed.v1"]);
@@ -461,6 +793,7 @@ mod tests {
         let invalid = ImageAnalyzed {
             id: "img-001".into(),
             tags: vec![],
+            ..Default::default()
         };
         assert!(validate_image_analyzed(&invalid).is_err());
     }
@@ -476,6 +809,7 @@ mod tests {
         let invalid = ImageAnalyzed {
             id: "".into(),
             tags: vec!["even".into()],
+            ..Default::default()
         };
         let result = validate_telemetry(&invalid);
         assert_eq!(result.status, "failed");
@@ -487,6 +821,7 @@ mod tests {
         let event = ImageAnalyzed {
             id: "img-001".into(),
             tags: vec!["odd".into(), "low-entropy".into()],
+            ..Default::default()
         };
         let output = evaluate(&event);
         assert_eq!(output.score, 0.3);
@@ -505,17 +840,42 @@ mod tests {
         let previous = std::env::current_dir().unwrap();
         std::env::set_current_dir(&tmp).unwrap();
 
-        let path = write_event_envelope(
-            "image.analyzed.v1",
-            &json!({"id":"img-001","tags":["even"]}),
-            "image.tagger",
-            "1.1.0",
-        )
-        .unwrap();
-        let written: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let keyring = Keyring::new(b"test-secret");
+        let module = keyring.signer("image.tagger");
+        let envelope =
+            module.envelope("image.analyzed.v1", json!({"id":"img-001","tags":["even"]}));
+        let verified = verify_envelope(&keyring, envelope)
+            .and_then(|envelope| write_event_envelope(envelope, "1.1.0"));
+
+        // A module may only speak for the service whose key it holds.
+        let mut spoofed = keyring
+            .signer("edge.cache")
+            .envelope("image.analyzed.v1", json!({"id":"img-001","tags":["odd"]}));
+        spoofed.insert("source".into(), json!("image.tagger"));
+        let spoofed_id = spoofed["id"].as_str().unwrap().to_string();
+        let rejected = verify_envelope(&keyring, spoofed).map(|_| ()).unwrap_err();
+        let recorded = fs::read_dir("logs/events").unwrap().count();
+
+        let mut unsafe_id = module.envelope("image.analyzed.v1", json!({}));
+        unsafe_id.insert("id".into(), json!("../escape"));
+        module.sign(&mut unsafe_id);
+        let unsafe_write =
+            write_event_envelope(verify_envelope(&keyring, unsafe_id).unwrap(), "1.1.0");
 
         std::env::set_current_dir(previous).unwrap();
 
+        let path = verified.unwrap();
+        let written: Value =
+            serde_json::from_str(&fs::read_to_string(tmp.join(&path)).unwrap()).unwrap();
+        assert_eq!(
+            rejected.to_string(),
+            format!(
+                "provenance.rejected {spoofed_id} signature does not match source image.tagger"
+            )
+        );
+        assert_eq!(recorded, 1);
+        assert!(unsafe_write.is_err());
+        assert!(written["umasignature"].as_str().unwrap().starts_with("hmac-sha256:"));
         assert_eq!(written.get("type").and_then(Value::as_str), Some("image.analyzed.v1"));
         assert_eq!(written.get("umaserviceid").and_then(Value::as_str), Some("image.tagger"));
         assert_eq!(written.get("umacontractversion").and_then(Value::as_str), Some("1.1.0"));
@@ -524,4 +884,211 @@ mod tests {
             Some("img-001")
         );
     }
+
+    #[test]
+    fn tagger_config_comes_from_contract_parameters() {
+        let root = project_root().unwrap();
+        let tagger = load_contract(root.join("contracts/image.tagger.contract.yaml")).unwrap();
+        assert_eq!(
+            tagger_config(&tagger, false),
+            TaggerConfig {
+                confidence_threshold: 0.5,
+                debug: false
+            }
+        );
+        assert!(tagger_config(&tagger, true).debug);
+    }
+
+    #[test]
+    fn check_required_uses_the_registry_schema() {
+        let schema = json!({"required": ["id", "tags"]});
+        assert!(check_required(&schema, &json!({"id": "img-001", "tags": []})).is_ok());
+        let err = check_required(&schema, &json!({"tags": []})).unwrap_err();
+        assert_eq!(err.to_string(), "validation.failed missing required id");
+    }
+
+    #[test]
+    fn image_analyzed_validation_rejects_out_of_range_confidence() {
+        let invalid = ImageAnalyzed {
+            id: "img-001".into(),
+            tags: vec!["even".into()],
+            confidence: BTreeMap::from([("even".to_string(), 1.5)]),
+            ..Default::default()
+        };
+        assert!(validate_image_analyzed(&invalid).is_err());
+    }
+
+    #[test]
+    fn pipeline_manifest_materializes_the_plan() {
+        let root = project_root().unwrap();
+        let manifest = Manifest::load(&root.join("contracts/pipeline.manifest.yaml")).unwrap();
+        let plan = manifest.plan(&root).unwrap();
+        assert_eq!((plan.max_attempts, plan.max_dispatches), (3, Some(16)));
+        let bindings: Vec<(&str, &str)> = plan
+            .bindings()
+            .into_iter()
+            .map(|(p, s)| (p.name.as_str(), s.name.as_str()))
+            .collect();
+        assert_eq!(
+            bindings,
+            [
+                ("image.tagger", "telemetry.logger"),
+                ("image.tagger", "edge.cache"),
+                ("image.tagger", "ai.model.evaluator"),
+            ]
+        );
+        assert!(plan.delivers("image.analyzed.v1", "edge.cache"));
+        assert!(!plan.delivers("cache.query.v1", "edge.cache"));
+        let module = plan.module("edge.cache").unwrap();
+        assert!(module.ends_with("edge_cache.wasm"));
+        assert!(plan.module("telemetry.logger").is_err());
+    }
+
+    #[test]
+    fn pipeline_manifest_reports_every_wiring_problem() {
+        let root = project_root().unwrap();
+        let manifest: Manifest = serde_yaml::from_str(
+            "name: broken
+version: 1.0.0
+services:
+  - name: image.tagger
+    version: 1.0.0
+    contract: contracts/image.tagger.contract.yaml
+  - name: edge.cache
+    version: 1.0.0
+    contract: contracts/edge.cache.contract.yaml
+wiring:
+  - event: image.analyzed.v1
+    from: image.tagger
+    to: [edge.cache, edge.cache, image.tagger, billing]
+  - event: cache.persisted.v1
+    from: image.tagger
+    to: [edge.cache]
+policies:
+  retry:
+    maxAttempts: 0
+",
+        )
+        .unwrap();
+        let err = manifest.plan(&root).unwrap_err().to_string();
+        assert_eq!(
+            err.lines().skip(1).collect::<Vec<_>>(),
+            [
+                "  - policies.retry.maxAttempts must be at least 1",
+                "  - service image.tagger pins version 1.0.0 but its contract is at 1.1.0",
+                "  - wiring image.analyzed.v1: image.tagger → edge.cache is declared more than once",
+                "  - wiring image.analyzed.v1: image.tagger does not subscribe to it",
+                "  - wiring image.analyzed.v1: unknown subscriber billing",
+                "  - wiring cache.persisted.v1: image.tagger does not emit it",
+                "  - wiring cache.persisted.v1: edge.cache does not subscribe to it",
+            ]
+        );
+        let unknown_field = "name: x\nversion: 1.0.0\nservices: []\nwiring: []\nextra: 1\n";
+        assert!(serde_yaml::from_str::<Manifest>(unknown_field).is_err());
+    }
+
+    #[test]
+    fn pipeline_manifest_is_validated_against_its_schema() {
+        let document: Value = serde_yaml::from_str(
+            "name: broken
+version: '1.0'
+services: []
+wiring:
+  - event: image.analyzed.v1
+    from: image.tagger
+    to: []
+policies:
+  retry:
+    maxAttempts: 0
+",
+        )
+        .unwrap();
+        // Serde alone accepts every one of these.
+        assert!(serde_json::from_value::<Manifest>(document.clone()).is_ok());
+        let err = Manifest::from_value(document).unwrap_err();
+        let mut problems: Vec<&str> = err
+            .lines()
+            .skip(1)
+            .map(|line| line.split(':').next().unwrap())
+            .collect();
+        problems.sort();
+        assert_eq!(
+            problems,
+            [
+                "  - /policies/retry/maxAttempts",
+                "  - /services",
+                "  - /version",
+                "  - /wiring/0/to",
+            ]
+        );
+        let root = project_root().unwrap();
+        assert!(Manifest::load(&root.join("contracts/pipeline.manifest.yaml")).is_ok());
+    }
+
+    #[test]
+    fn dispatcher_stops_at_the_dispatch_limit() {
+        let mut dispatcher = Dispatcher::new(3, std::env::temp_dir()).with_limit(Some(1));
+        let data = json!({});
+        let first = dispatcher.dispatch("evt-1", "image.analyzed.v1", "edge.cache", &data, || Ok(()));
+        assert_eq!(first.unwrap(), Some(()));
+        let err = dispatcher
+            .dispatch("evt-2", "image.analyzed.v1", "edge.cache", &data, || Ok(()))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "dispatch.limit 1 deliveries reached at evt-2:edge.cache");
+        assert_eq!(dispatcher.summary().dispatched, 1);
+    }
+
+    #[test]
+    fn dispatcher_retries_then_poisons_and_continues() {
+        let dlq = std::env::temp_dir().join(format!(
+            "chapter7-dlq-{}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let mut dispatcher = Dispatcher::new(3, dlq.clone());
+        let data = json!({"id":"img-001"});
+
+        let mut failures_left = 1;
+        let delivered = dispatcher
+            .dispatch("evt-1", "image.analyzed.v1", "edge.cache", &data, || {
+                if failures_left > 0 {
+                    failures_left -= 1;
+                    return Err("transient".into());
+                }
+                Ok("stored")
+            })
+            .unwrap();
+        assert_eq!(delivered, Some("stored"));
+
+        let mut calls = 0;
+        let poisoned = dispatcher
+            .dispatch("evt-2", "image.analyzed.v1", "edge.cache", &data, || {
+                calls += 1;
+                Err::<(), _>("always fails".into())
+            })
+            .unwrap();
+        assert_eq!(poisoned, None);
+        assert_eq!(calls, 3);
+
+        assert_eq!(
+            dispatcher.summary(),
+            &DispatchSummary {
+                dispatched: 2,
+                retries: 3,
+                poison: 1,
+                poisoned: vec!["evt-2:edge.cache".into()],
+            }
+        );
+        let record: Value = serde_json::from_str(
+            &fs::read_to_string(dlq.join("evt-2-edge.cache.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(record["poison"], true);
+        assert_eq!(record["attempts"], 3);
+        assert_eq!(record["error"], "always fails");
+        assert_eq!(record["data"], data);
+        fs::remove_dir_all(dlq).unwrap();
+    }
 }
@@ -0,0 +1,298 @@
+//! Pipeline manifest: the services, event wiring and delivery policies of a
+//! run, declared in `contracts/pipeline.manifest.yaml`.  Loading validates
+//! the document against `contracts/schemas/pipeline.manifest.v1.json`;
+//! planning checks the manifest against the service contracts it names and
+//! materializes an
+//! [`ExecutionPlan`], so a wiring mistake stops the run before any service
+//! executes instead of surfacing as a missing step.
+
+use crate::{load_contract, match_pattern, Contract, DEFAULT_MAX_ATTEMPTS};
+use jsonschema::{Draft, JSONSchema};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub name: String,
+    pub version: String,
+    pub services: Vec<ServiceEntry>,
+    pub wiring: Vec<Wire>,
+    #[serde(default)]
+    pub policies: Policies,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceEntry {
+    pub name: String,
+    pub version: String,
+    pub contract: String,
+    #[serde(default)]
+    pub module: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Wire {
+    pub event: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policies {
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(default)]
+    pub limits: Limits,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct RetryPolicy {
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+        }
+    }
+}
+
+fn default_max_attempts() -> u32 {
+    DEFAULT_MAX_ATTEMPTS
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct Limits {
+    #[serde(default)]
+    pub max_dispatches: Option<u32>,
+}
+
+/// The manifest schema, bundled so loading never depends on the working
+/// directory.
+const MANIFEST_SCHEMA: &str = include_str!("../../contracts/schemas/pipeline.manifest.v1.json");
+
+/// One event delivery the plan allows.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Delivery {
+    pub event: String,
+    pub publisher: String,
+    pub subscriber: String,
+}
+
+#[derive(Debug)]
+pub struct PlannedService {
+    pub contract: Contract,
+    pub module: Option<PathBuf>,
+}
+
+/// The manifest resolved against its contracts.
+#[derive(Debug)]
+pub struct ExecutionPlan {
+    services: BTreeMap<String, PlannedService>,
+    /// Deliveries in manifest order.
+    pub deliveries: Vec<Delivery>,
+    pub max_attempts: u32,
+    pub max_dispatches: Option<u32>,
+}
+
+impl Manifest {
+    /// Read a manifest written as YAML or, for a `.json` path, JSON, and
+    /// validate it against the manifest schema.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let raw =
+            fs::read_to_string(path).map_err(|err| format!("reading {}: {err}", path.display()))?;
+        let parsed = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&raw).map_err(|err| err.to_string())
+        } else {
+            serde_yaml::from_str(&raw).map_err(|err| err.to_string())
+        };
+        parsed
+            .and_then(Self::from_value)
+            .map_err(|err| format!("{}: {err}", path.display()).into())
+    }
+
+    /// A manifest from a parsed document, which must match the manifest
+    /// schema.  Every violation is reported, not just the first.
+    pub fn from_value(document: Value) -> Result<Self, String> {
+        let schema: Value = serde_json::from_str(MANIFEST_SCHEMA).map_err(|err| err.to_string())?;
+        let compiled = JSONSchema::options()
+            .with_draft(Draft::Draft7)
+            .compile(&schema)
+            .map_err(|err| format!("pipeline.manifest.v1 schema: {err}"))?;
+        if let Err(errors) = compiled.validate(&document) {
+            let problems: Vec<String> = errors
+                .map(|err| {
+                    let at = err.instance_path.to_string();
+                    format!("  - {}: {err}", if at.is_empty() { "/" } else { &at })
+                })
+                .collect();
+            return Err(format!(
+                "does not match pipeline.manifest.v1:\n{}",
+                problems.join("\n")
+            ));
+        }
+        serde_json::from_value(document).map_err(|err| err.to_string())
+    }
+
+    /// Load every contract the manifest names, relative to `root`, and check
+    /// the wiring against them.  Every problem is reported, not just the
+    /// first.
+    pub fn plan(&self, root: &Path) -> Result<ExecutionPlan, Box<dyn Error>> {
+        let mut problems = Vec::new();
+        if self.policies.retry.max_attempts == 0 {
+            problems.push("policies.retry.maxAttempts must be at least 1".to_string());
+        }
+        if self.policies.limits.max_dispatches == Some(0) {
+            problems.push("policies.limits.maxDispatches must be at least 1".to_string());
+        }
+
+        let mut services = BTreeMap::new();
+        for entry in &self.services {
+            if services.contains_key(&entry.name) {
+                problems.push(format!("service {} is declared more than once", entry.name));
+                continue;
+            }
+            let contract = match load_contract(root.join(&entry.contract)) {
+                Ok(contract) => contract,
+                Err(err) => {
+                    problems.push(format!("service {}: {}: {err}", entry.name, entry.contract));
+                    continue;
+                }
+            };
+            if contract.name != entry.name {
+                problems.push(format!(
+                    "service {}: contract {} is for {}",
+                    entry.name, entry.contract, contract.name
+                ));
+            }
+            if contract.version != entry.version {
+                problems.push(format!(
+                    "service {} pins version {} but its contract is at {}",
+                    entry.name, entry.version, contract.version
+                ));
+            }
+            let module = entry.module.as_ref().map(|module| root.join(module));
+            services.insert(entry.name.clone(), PlannedService { contract, module });
+        }
+
+        let mut deliveries = Vec::new();
+        let mut seen = BTreeSet::new();
+        for wire in &self.wiring {
+            let Some(publisher) = services.get(&wire.from) else {
+                problems.push(format!(
+                    "wiring {}: unknown publisher {}",
+                    wire.event, wire.from
+                ));
+                continue;
+            };
+            if !publisher
+                .contract
+                .events
+                .emits
+                .iter()
+                .any(|e| e.name == wire.event)
+            {
+                problems.push(format!(
+                    "wiring {}: {} does not emit it",
+                    wire.event, wire.from
+                ));
+            }
+            for to in &wire.to {
+                let Some(subscriber) = services.get(to) else {
+                    problems.push(format!("wiring {}: unknown subscriber {to}", wire.event));
+                    continue;
+                };
+                let subscribed = subscriber
+                    .contract
+                    .events
+                    .subscribes
+                    .iter()
+                    .any(|s| match_pattern(&s.pattern, &wire.event));
+                if !subscribed {
+                    problems.push(format!(
+                        "wiring {}: {to} does not subscribe to it",
+                        wire.event
+                    ));
+                }
+                let delivery = Delivery {
+                    event: wire.event.clone(),
+                    publisher: wire.from.clone(),
+                    subscriber: to.clone(),
+                };
+                if !seen.insert(delivery.clone()) {
+                    problems.push(format!(
+                        "wiring {}: {} → {to} is declared more than once",
+                        wire.event, wire.from
+                    ));
+                    continue;
+                }
+                deliveries.push(delivery);
+            }
+        }
+
+        if !problems.is_empty() {
+            return Err(format!(
+                "pipeline manifest {} is invalid:\n  - {}",
+                self.name,
+                problems.join("\n  - ")
+            )
+            .into());
+        }
+        Ok(ExecutionPlan {
+            services,
+            deliveries,
+            max_attempts: self.policies.retry.max_attempts,
+            max_dispatches: self.policies.limits.max_dispatches,
+        })
+    }
+}
+
+impl ExecutionPlan {
+    pub fn contract(&self, service: &str) -> Result<&Contract, Box<dyn Error>> {
+        self.services
+            .get(service)
+            .map(|s| &s.contract)
+            .ok_or_else(|| format!("service {service} is not in the pipeline manifest").into())
+    }
+
+    pub fn module(&self, service: &str) -> Result<&Path, Box<dyn Error>> {
+        self.services
+            .get(service)
+            .and_then(|s| s.module.as_deref())
+            .ok_or_else(|| format!("service {service} declares no module in the manifest").into())
+    }
+
+    /// Whether the plan wires `event` to `subscriber`.
+    pub fn delivers(&self, event: &str, subscriber: &str) -> bool {
+        self.deliveries
+            .iter()
+            .any(|d| d.event == event && d.subscriber == subscriber)
+    }
+
+    /// Distinct publisher/subscriber pairs in manifest order.
+    pub fn bindings(&self) -> Vec<(&Contract, &Contract)> {
+        let mut pairs: Vec<(&str, &str)> = Vec::new();
+        for delivery in &self.deliveries {
+            let pair = (delivery.publisher.as_str(), delivery.subscriber.as_str());
+            if !pairs.contains(&pair) {
+                pairs.push(pair);
+            }
+        }
+        pairs
+            .into_iter()
+            .map(|(p, s)| (&self.services[p].contract, &self.services[s].contract))
+            .collect()
+    }
+}
@@ -2,6 +2,8 @@ import fs from "node:fs";
 import path from "node:path";
 import yaml from "js-yaml";
 
+export { canonicalJson, serviceKey, signEnvelope, verifyEnvelope } from "./provenance.mjs";
+
 export function loadYaml(filePath) {
   return yaml.load(fs.readFileSync(filePath, "utf-8"));
 }
@@ -55,6 +57,7 @@ export function summarizeRunnerOutput(output) {
   const bindingLines = lines
     .filter((line) => line.startsWith("[info] binding.created "))
     .sort();
+  const provenanceLines = lines.filter((line) => line.startsWith("[info] provenance.verified "));
 
   const parseJsonSuffix = (prefix) => {
     const line = lines.find((entry) => entry.startsWith(prefix));
@@ -67,11 +70,13 @@ export function summarizeRunnerOutput(output) {
   const telemetryJson = parseJsonSuffix("[info] telemetry.ok ");
   const cacheJson = parseJsonSuffix("[info] cache.ok ");
   const evaluatorJson = parseJsonSuffix("[info] evaluator.ok ");
+  const summaryJson = parseJsonSuffix("[info] summary ");
   const policyLine = lines.find((line) => line.startsWith("[warn] policy.violation "));
   const validationLine = lines.find((line) => line.startsWith("[info] validation.passed "));
 
   return {
     bindings: bindingLines,
+    provenance: provenanceLines,
     policyLine: policyLine || null,
     validationLine: validationLine || null,
     telemetry: telemetryJson
@@ -94,6 +99,14 @@ export function summarizeRunnerOutput(output) {
           score: evaluatorJson.score,
         }
       : null,
+    // Poisoned keys embed runner-specific event ids, so compare counts only.
+    summary: summaryJson
+      ? {
+          dispatched: summaryJson.dispatched,
+          retries: summaryJson.retries,
+          poison: summaryJson.poison,
+        }
+      : null,
   };
 }
 
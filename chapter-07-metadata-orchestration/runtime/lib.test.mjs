@@ -2,7 +2,16 @@ import test from "node:test";
 import assert from "node:assert/strict";
 import path from "node:path";
 import fs from "node:fs";
-import { bindContracts, findPolicyViolation, loadYaml, matchPattern, summarizeRunnerOutput } from "./lib.mjs";
+import {
+  bindContracts,
+  findPolicyViolation,
+  loadYaml,
+  matchPattern,
+  serviceKey,
+  signEnvelope,
+  summarizeRunnerOutput,
+  verifyEnvelope,
+} from "./lib.mjs";
 
 const rootDir = path.resolve(path.dirname(new URL(import.meta.url).pathname), "..");
 
@@ -40,9 +49,11 @@ test("runner output summary captures the parity-critical fields", () => {
     [info] binding.created image.analyzed.v1 → telemetry.logger
     [info] binding.created image.analyzed.v1 → edge.cache
     [info] validation.passed event_schema=image.analyzed.v1
+    [info] provenance.verified image.analyzed.v1
     [info] telemetry.ok {"source":"telemetry.logger","event":"image.analyzed.v1","status":"passed"}
     [info] cache.ok {"source":"edge.cache","event":"image.analyzed.v1","status":"passed"}
     [info] evaluator.ok {"id":"img-001","score":0.7}
+    [info] summary {"dispatched":2,"retries":1,"poison":1,"poisoned":["evt-9:edge.cache"]}
   `);
 
   assert.deepEqual(summary.bindings, [
@@ -51,7 +62,28 @@ test("runner output summary captures the parity-critical fields", () => {
   ]);
   assert.match(summary.policyLine, /policy\.deny/);
   assert.match(summary.validationLine, /validation\.passed/);
+  assert.deepEqual(summary.provenance, ["[info] provenance.verified image.analyzed.v1"]);
   assert.equal(summary.telemetry.status, "passed");
   assert.equal(summary.cache.status, "passed");
   assert.equal(summary.evaluator.score, 0.7);
+  assert.deepEqual(summary.summary, { dispatched: 2, retries: 1, poison: 1 });
+});
+
+test("envelopes verify only under the service that signed them", () => {
+  const secret = "test-secret";
+  const envelope = {
+    id: "evt-1",
+    source: "image.tagger",
+    type: "image.analyzed.v1",
+    time: "1700000000Z",
+    data: { tags: ["even"], id: "img-001" },
+  };
+  const genuine = signEnvelope(serviceKey(secret, "image.tagger"), envelope);
+  assert.match(genuine.umasignature, /^hmac-sha256:[0-9a-f]{64}$/);
+  assert.equal(verifyEnvelope(secret, genuine), null);
+
+  const spoofed = signEnvelope(serviceKey(secret, "edge.cache"), envelope);
+  assert.equal(verifyEnvelope(secret, spoofed), "signature does not match source image.tagger");
+  assert.notEqual(verifyEnvelope(secret, { ...genuine, data: { id: "img-001", tags: ["odd"] } }), null);
+  assert.equal(verifyEnvelope(secret, envelope), "unsigned event from image.tagger");
 });
@@ -0,0 +1,19 @@
+// Types for provenance.mjs, imported by the TypeScript services.
+
+export interface Envelope<T = unknown> {
+  specversion: string;
+  id: string;
+  source: string;
+  type: string;
+  time: string;
+  datacontenttype: string;
+  data: T;
+  umaserviceid: string;
+  umasignature: string;
+}
+
+export function canonicalJson(value: unknown): string;
+export function serviceKey(secret: string | Buffer, serviceId: string): Buffer;
+export function signEnvelope<T extends object>(key: Buffer, envelope: T): T & { umasignature: string };
+export function signedEnvelope<T>(key: Buffer, source: string, type: string, data: T): Envelope<T>;
+export function verifyEnvelope(secret: string | Buffer, envelope: Record<string, unknown>): string | null;
@@ -0,0 +1,72 @@
+// Event provenance shared by the JavaScript runner and the TypeScript services,
+// the counterpart of the Rust `provenance` crate.
+import crypto from "node:crypto";
+
+const SIGNATURE_SCHEME = "hmac-sha256:";
+
+// JSON with object keys sorted, matching serde_json's default map order.
+export function canonicalJson(value) {
+  if (Array.isArray(value)) {
+    return `[${value.map(canonicalJson).join(",")}]`;
+  }
+  if (value && typeof value === "object") {
+    const entries = Object.keys(value)
+      .sort()
+      .map((key) => `${JSON.stringify(key)}:${canonicalJson(value[key])}`);
+    return `{${entries.join(",")}}`;
+  }
+  return JSON.stringify(value);
+}
+
+// The signing key the orchestrator issues to one service.
+export function serviceKey(secret, serviceId) {
+  return crypto.createHmac("sha256", secret).update(`uma.service:${serviceId}`).digest();
+}
+
+function signingInput(envelope) {
+  const field = (name) => (typeof envelope[name] === "string" ? envelope[name] : "");
+  const data = envelope.data === undefined ? "" : canonicalJson(envelope.data);
+  return [field("id"), field("source"), field("type"), field("time"), data].join("\n");
+}
+
+export function signEnvelope(key, envelope) {
+  const tag = crypto.createHmac("sha256", key).update(signingInput(envelope)).digest("hex");
+  return { ...envelope, umasignature: `${SIGNATURE_SCHEME}${tag}` };
+}
+
+// A signed envelope for `data`, with `source` as the emitting service.
+export function signedEnvelope(key, source, type, data) {
+  return signEnvelope(key, {
+    specversion: "1.0",
+    id: crypto.randomUUID(),
+    source,
+    type,
+    time: new Date().toISOString(),
+    datacontenttype: "application/json",
+    data,
+    umaserviceid: source,
+  });
+}
+
+// Returns null when the envelope was signed with the key of its `source`,
+// otherwise the reason it is rejected.
+export function verifyEnvelope(secret, envelope) {
+  const source = envelope.source;
+  if (typeof source !== "string") {
+    return "envelope has no source";
+  }
+  const signature = envelope.umasignature;
+  if (typeof signature !== "string") {
+    return `unsigned event from ${source}`;
+  }
+  const hex = signature.startsWith(SIGNATURE_SCHEME) ? signature.slice(SIGNATURE_SCHEME.length) : "";
+  const tag = Buffer.from(hex, "hex");
+  if (hex.length === 0 || tag.length * 2 !== hex.length) {
+    return `malformed signature on event from ${source}`;
+  }
+  const expected = crypto.createHmac("sha256", serviceKey(secret, source)).update(signingInput(envelope)).digest();
+  if (tag.length !== expected.length || !crypto.timingSafeEqual(tag, expected)) {
+    return `signature does not match source ${source}`;
+  }
+  return null;
+}
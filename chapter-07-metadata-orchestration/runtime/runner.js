@@ -5,6 +5,7 @@ import path from "node:path";
 import Ajv from "ajv";
 import yaml from "js-yaml";
 import crypto from "node:crypto";
+import { serviceKey, signedEnvelope, verifyEnvelope } from "./provenance.mjs";
 
 const ajv = new Ajv({ allErrors: true });
 
@@ -22,14 +23,47 @@ function bind(pub, sub) {
   for (const e of emits) for (const s of subs) if (matchPattern(s.pattern, e.name)) bindings.push({ event: e.name, schema: e.schema });
   return bindings;
 }
-function runWasmtime(wasmPath, input) {
+// The module is issued its service's key in UMA_SIGNING_KEY and signs its own
+// output; the key is forwarded from the environment, not the command line.
+function runWasmtime(wasmPath, input, serviceId) {
   const t0 = Date.now();
-  const out = execFileSync("wasmtime", ["run", "--dir=.", wasmPath], { input: JSON.stringify(input) });
+  const env = { ...process.env, UMA_SIGNING_KEY: serviceKey(signingSecret, serviceId).toString("hex") };
+  const out = execFileSync("wasmtime", ["run", "--dir=.", "--env", "UMA_SIGNING_KEY", wasmPath], { input: JSON.stringify(input), env });
   const ms = Date.now() - t0;
   logTelemetry({ metric: "uma.qos.latency.ms", value: ms });
   return JSON.parse(out.toString("utf-8"));
 }
-function uuid() { return crypto.randomUUID(); }
+
+// Bounded per-event retries: after maxAttempts failures the event goes to the DLQ
+// with poison: true and the pipeline continues.
+const maxAttempts = Math.max(1, Number(process.env.MAX_EVENT_ATTEMPTS) || 3);
+const attempts = new Map();
+const dispatchSummary = { dispatched: 0, retries: 0, poison: 0, poisoned: [] };
+function dispatch(eventId, eventType, subscriber, data, deliver) {
+  const key = `${eventId}:${subscriber}`;
+  dispatchSummary.dispatched += 1;
+  for (;;) {
+    const attempt = (attempts.get(key) || 0) + 1;
+    attempts.set(key, attempt);
+    try {
+      return deliver();
+    } catch (err) {
+      const message = err instanceof Error ? err.message : String(err);
+      if (attempt < maxAttempts) {
+        dispatchSummary.retries += 1;
+        console.log(`[warn] dispatch.retry ${key} attempt=${attempt} ${message}`);
+        continue;
+      }
+      fs.mkdirSync(path.join("logs", "dlq"), { recursive: true });
+      const record = { eventId, type: eventType, subscriber, attempts: attempt, error: message, poison: true, data };
+      fs.writeFileSync(path.join("logs", "dlq", `${eventId}-${subscriber}.json`), JSON.stringify(record, null, 2));
+      console.log(`[warn] dlq.poison ${key} attempts=${attempt} ${message}`);
+      dispatchSummary.poison += 1;
+      dispatchSummary.poisoned.push(key);
+      return null;
+    }
+  }
+}
 
 function otlpExport(metricName, value) {
   const endpoint = process.env.OTLP_ENDPOINT;
@@ -43,25 +77,37 @@ function logTelemetry(obj) {
   ensureLogDirs();
   fs.appendFileSync(path.join("logs", "telemetry.jsonl"), JSON.stringify(obj) + "\n");
 }
-function writeEventEnvelope(evtType, data, serviceId, contractVersion) {
+// Each service signs its own envelopes with the key issued to it, and every
+// envelope is verified before it is routed or recorded.
+const signingSecret = process.env.UMA_SIGNING_SECRET || crypto.randomBytes(32);
+function runnerEnvelope(evtType, data) {
+  return signedEnvelope(serviceKey(signingSecret, "cloud-runner"), "cloud-runner", evtType, data);
+}
+// Throws on a bad signature, so inside dispatch() it counts as a failed delivery.
+function verified(envelope) {
+  const reason = verifyEnvelope(signingSecret, envelope);
+  if (reason) throw new Error(`provenance.rejected ${envelope.id} ${reason}`);
+  console.log("[info] provenance.verified", envelope.type);
+  return envelope;
+}
+function verifyBeforeRouting(envelope) {
+  try { return verified(envelope); }
+  catch (err) { console.error(`[error] ${err.message}`); process.exit(1); }
+}
+function writeEventEnvelope(envelope, contractVersion) {
   ensureLogDirs();
-  const envelope = {
-    specversion: "1.0",
-    id: uuid(),
-    source: serviceId,
-    type: evtType,
-    time: new Date().toISOString(),
-    datacontenttype: "application/json",
-    data,
-    "umaserviceid": serviceId,
+  if (typeof envelope.id !== "string" || !/^[A-Za-z0-9_-][A-Za-z0-9._-]*$/.test(envelope.id)) {
+    console.error(`[error] event id ${JSON.stringify(envelope.id)} is not a valid file name`); process.exit(1);
+  }
+  const recorded = {
+    ...envelope,
     "umacontractversion": contractVersion,
     "umaruntimeid": "cloud-runner",
     "phase": "normal",
     "reasonCode": "OK"
   };
-  const p = path.join("logs", "events", `${envelope.id}.json`);
-  fs.writeFileSync(p, JSON.stringify(envelope, null, 2));
-  return envelope;
+  fs.writeFileSync(path.join("logs", "events", `${envelope.id}.json`), JSON.stringify(recorded, null, 2));
+  return recorded;
 }
 function verifyPolicy() {
   const policyPath = path.join("contracts", "schemas", "policy.standard.v1.json");
@@ -115,8 +161,14 @@ else console.log(`[info] binding.created ${bindingsTE.map(b=>b.event).join(", ")
 
 // Execute publisher via WASI
 const wasmPath = path.join("services", "image.tagger", "target", "wasm32-wasip1", "release", "image_tagger.wasm");
-const input = { id: "img-001", bytes: Array.from({length: 8}, (_,i)=>i) };
-const published = runWasmtime(wasmPath, input);
+const params = tagger.parameters || {};
+const config = {
+  confidence_threshold: params.confidence_threshold ?? 0.5,
+  debug: process.env.TAGGER_DEBUG === "1" || params.debug === true,
+};
+const input = { id: "img-001", bytes: Array.from({length: 8}, (_,i)=>i), config };
+const analyzedEnvelope = verifyBeforeRouting(runWasmtime(wasmPath, input, tagger.name));
+const published = analyzedEnvelope.data;
 
 // Validate with schema
 const schemaPath = path.join("contracts", "schemas", "image.analyzed.v1.json");
@@ -125,23 +177,45 @@ const ok = ajv.validate(schema, published);
 if (ok) console.log("[info] validation.passed event_schema=image.analyzed.v1");
 else { console.error("[error] validation.failed", ajv.errorsText()); process.exit(2); }
 
-writeEventEnvelope("image.analyzed.v1", published, "image.tagger", tagger.version);
+writeEventEnvelope(analyzedEnvelope, tagger.version);
+const analyzedAt = analyzedEnvelope.time;
 
-// Dispatch to telemetry.logger
+// Dispatch to telemetry.logger, which signs its status with its own key
 const loggerMod = await import(path.resolve("services/telemetry.logger/dist/index.js"));
-const validateFn = loggerMod.createValidator(schemaPath);
-const tval = validateFn(published);
+const validateFn = loggerMod.createValidator(schemaPath, serviceKey(signingSecret, logger.name));
+const telemetryEnvelope = verifyBeforeRouting(validateFn(published));
+const tval = telemetryEnvelope.data;
 console.log("[info] telemetry." + (tval.status === "passed" ? "ok" : "error"), JSON.stringify(tval));
-writeEventEnvelope("telemetry.validation.v1", tval, "telemetry.logger", logger.version);
+writeEventEnvelope(telemetryEnvelope, logger.version);
 
 // Dispatch to edge.cache via WASI
 const edgeWasm = path.join("services", "edge.cache", "target", "wasm32-wasip1", "release", "edge_cache.wasm");
-const cacheOut = runWasmtime(edgeWasm, published);
-console.log("[info] cache." + (cacheOut.status === "passed" ? "ok" : "error"), JSON.stringify(cacheOut));
-writeEventEnvelope("cache.persisted.v1", cacheOut, "edge.cache", edgeCache.version);
+const cacheInput = { ...published, time: analyzedAt };
+const cacheOut = dispatch(analyzedEnvelope.id, "image.analyzed.v1", edgeCache.name, cacheInput, () => {
+  const out = verified(runWasmtime(edgeWasm, cacheInput, edgeCache.name));
+  if (out.data && out.data.status === "failed" && out.data.reason) throw new Error(out.data.reason);
+  return out;
+});
+if (cacheOut) {
+  console.log("[info] cache." + (cacheOut.data.status === "passed" ? "ok" : "error"), JSON.stringify(cacheOut.data));
+  writeEventEnvelope(cacheOut, edgeCache.version);
+}
 
-// Dispatch to ai.model.evaluator
+// Read back through the cache's query mode to show it serves lookups too.
+const query = { type: "cache.query.v1", id: published.id };
+const queryEnvelope = verifyBeforeRouting(runnerEnvelope("cache.query.v1", query));
+writeEventEnvelope(queryEnvelope, "1.0.0");
+const queryOut = dispatch(queryEnvelope.id, "cache.query.v1", edgeCache.name, query, () => verified(runWasmtime(edgeWasm, query, edgeCache.name)));
+if (queryOut) {
+  console.log("[info] cache.query." + (queryOut.data.status === "passed" ? "ok" : "error"), JSON.stringify(queryOut.data));
+  writeEventEnvelope(queryOut, edgeCache.version);
+}
+
+// Dispatch to ai.model.evaluator, which signs its result with its own key
 const evaluatorMod = await import(path.resolve("services/ai.model.evaluator/dist/index.js"));
-const evalOut = evaluatorMod.evaluate(published);
-console.log("[info] evaluator.ok", JSON.stringify(evalOut));
-writeEventEnvelope("inference.completed.v1", evalOut, "ai.model.evaluator", evaluator.version);
+const evalEnvelope = verifyBeforeRouting(evaluatorMod.evaluate(published, serviceKey(signingSecret, evaluator.name)));
+console.log("[info] evaluator.ok", JSON.stringify(evalEnvelope.data));
+writeEventEnvelope(evalEnvelope, evaluator.version);
+
+console.log("[info] summary", JSON.stringify(dispatchSummary));
+writeEventEnvelope(verifyBeforeRouting(runnerEnvelope("orchestration.summary.v1", dispatchSummary)), "1.0.0");
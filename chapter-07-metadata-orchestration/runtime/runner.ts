@@ -1,3 +1,4 @@
+import crypto from "node:crypto";
 import fs from "node:fs";
 import { execFileSync } from "node:child_process";
 import path from "node:path";
@@ -19,6 +20,7 @@ type Contract = {
   };
   constraints?: { placement?: string[] };
   policies?: { requires?: string[] };
+  parameters?: { confidence_threshold?: number; debug?: boolean };
   orchestration?: any;
 };
 
@@ -45,6 +47,37 @@ function runWasmtime(wasmPath: string, input: any): any {
   return JSON.parse(out.toString("utf-8"));
 }
 
+const maxAttempts = Math.max(1, Number(process.env.MAX_EVENT_ATTEMPTS) || 3);
+const attempts = new Map<string, number>();
+const dispatchSummary = { dispatched: 0, retries: 0, poison: 0, poisoned: [] as string[] };
+
+// Deliver with bounded retries; exhausted events go to logs/dlq with poison: true.
+function dispatch<T>(eventId: string, eventType: string, subscriber: string, data: any, deliver: () => T): T | null {
+  const key = `${eventId}:${subscriber}`;
+  dispatchSummary.dispatched += 1;
+  for (;;) {
+    const attempt = (attempts.get(key) ?? 0) + 1;
+    attempts.set(key, attempt);
+    try {
+      return deliver();
+    } catch (err) {
+      const message = err instanceof Error ? err.message : String(err);
+      if (attempt < maxAttempts) {
+        dispatchSummary.retries += 1;
+        console.log(`[warn] dispatch.retry ${key} attempt=${attempt} ${message}`);
+        continue;
+      }
+      fs.mkdirSync(path.join("logs", "dlq"), { recursive: true });
+      const record = { eventId, type: eventType, subscriber, attempts: attempt, error: message, poison: true, data };
+      fs.writeFileSync(path.join("logs", "dlq", `${eventId}-${subscriber}.json`), JSON.stringify(record, null, 2));
+      console.log(`[warn] dlq.poison ${key} attempts=${attempt} ${message}`);
+      dispatchSummary.poison += 1;
+      dispatchSummary.poisoned.push(key);
+      return null;
+    }
+  }
+}
+
 async function main() {
   ensureLogDirs();
   const contractsDir = path.join(process.cwd(), "contracts");
@@ -68,7 +101,12 @@ async function main() {
 
   // Execute publisher via WASI
   const wasmPath = path.join("services", "image.tagger", "target", "wasm32-wasip1", "release", "image_tagger.wasm");
-  const input = { id: "img-001", bytes: Array.from({length: 8}, (_,i)=>i) };
+  const params = tagger.parameters || {};
+  const config = {
+    confidence_threshold: params.confidence_threshold ?? 0.5,
+    debug: process.env.TAGGER_DEBUG === "1" || params.debug === true,
+  };
+  const input = { id: "img-001", bytes: Array.from({length: 8}, (_,i)=>i), config };
   const published = runWasmtime(wasmPath, input);
 
   // Validate with schema
@@ -89,8 +127,20 @@ async function main() {
 
   // Dispatch to edge.cache via WASI
   const edgeWasm = path.join("services", "edge.cache", "target", "wasm32-wasip1", "release", "edge_cache.wasm");
-  const cacheOut = runWasmtime(edgeWasm, published);
-  console.log("[info] cache." + (cacheOut.status === "passed" ? "ok" : "error"), JSON.stringify(cacheOut));
+  const cacheInput = { ...published, time: new Date().toISOString() };
+  const cacheOut = dispatch(crypto.randomUUID(), "image.analyzed.v1", edgeCache.name, cacheInput, () => {
+    const out = runWasmtime(edgeWasm, cacheInput);
+    if (out.status === "failed" && out.reason) throw new Error(out.reason);
+    return out;
+  });
+  if (cacheOut) console.log("[info] cache." + (cacheOut.status === "passed" ? "ok" : "error"), JSON.stringify(cacheOut));
+
+  // Read back through the cache's query mode
+  const query = { type: "cache.query.v1", id: published.id };
+  const queryOut = dispatch(crypto.randomUUID(), "cache.query.v1", edgeCache.name, query, () => runWasmtime(edgeWasm, query));
+  if (queryOut) console.log("[info] cache.query." + (queryOut.status === "passed" ? "ok" : "error"), JSON.stringify(queryOut));
+
+  console.log("[info] summary", JSON.stringify(dispatchSummary));
 }
 
 main().catch(e => { console.error(e); process.exit(1); });
@@ -0,0 +1,503 @@
+//! File-backed event schema registry.
+//!
+//! Schemas are registered by name and version and stored as
+//! `<root>/<name>/v<version>.json`.  That layout is the whole protocol: the
+//! `schema-registry` binary writes it, and [`SchemaClient`] reads it, so
+//! services fetch schemas at startup instead of embedding them.
+//!
+//! Registration is where evolution is checked.  A version, once registered,
+//! is immutable, versions only move forward, and a new version must be
+//! backward compatible with the latest one: a consumer reading with the new
+//! schema must still accept every event written against the old one.
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum RegistryError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    InvalidName(String),
+    InvalidSchema {
+        path: PathBuf,
+        message: String,
+    },
+    NotFound {
+        name: String,
+        version: u32,
+    },
+    /// The version is already registered with different content.
+    VersionExists {
+        name: String,
+        version: u32,
+    },
+    /// The version is older than the latest registered one.
+    StaleVersion {
+        name: String,
+        version: u32,
+        latest: u32,
+    },
+    Incompatible {
+        name: String,
+        version: u32,
+        against: u32,
+        problems: Vec<String>,
+    },
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            RegistryError::InvalidName(name) => write!(
+                f,
+                "invalid schema name '{name}': use lowercase letters, digits, '.', '_' and '-'"
+            ),
+            RegistryError::InvalidSchema { path, message } => {
+                write!(f, "{}: {message}", path.display())
+            }
+            RegistryError::NotFound { name, version } => {
+                write!(f, "schema {name} v{version} is not registered")
+            }
+            RegistryError::VersionExists { name, version } => write!(
+                f,
+                "schema {name} v{version} is already registered with different content"
+            ),
+            RegistryError::StaleVersion {
+                name,
+                version,
+                latest,
+            } => write!(f, "schema {name} v{version} is older than v{latest}"),
+            RegistryError::Incompatible {
+                name,
+                version,
+                against,
+                problems,
+            } => write!(
+                f,
+                "schema {name} v{version} is not backward compatible with v{against}: {}",
+                problems.join("; ")
+            ),
+        }
+    }
+}
+
+impl Error for RegistryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RegistryError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Outcome of [`Registry::register`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Registration {
+    Created,
+    /// The same content was already registered under this version.
+    Unchanged,
+}
+
+/// The registry store rooted at a directory.
+#[derive(Debug, Clone)]
+pub struct Registry {
+    root: PathBuf,
+}
+
+impl Registry {
+    pub fn open(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Registered schema names, sorted.
+    pub fn names(&self) -> Result<Vec<String>, RegistryError> {
+        let mut names = Vec::new();
+        for entry in read_dir(&self.root)? {
+            if entry.path().is_dir() {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Registered versions of `name`, ascending.  Empty if none.
+    pub fn versions(&self, name: &str) -> Result<Vec<u32>, RegistryError> {
+        check_name(name)?;
+        let mut versions = Vec::new();
+        for entry in read_dir(&self.root.join(name))? {
+            let file = entry.file_name().to_string_lossy().into_owned();
+            let version = file
+                .strip_prefix('v')
+                .and_then(|rest| rest.strip_suffix(".json"))
+                .and_then(|v| v.parse::<u32>().ok());
+            versions.extend(version);
+        }
+        versions.sort_unstable();
+        Ok(versions)
+    }
+
+    pub fn get(&self, name: &str, version: u32) -> Result<Value, RegistryError> {
+        check_name(name)?;
+        let path = self.path(name, version);
+        match fs::read_to_string(&path) {
+            Ok(raw) => parse_schema(&path, &raw),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Err(RegistryError::NotFound {
+                name: name.to_string(),
+                version,
+            }),
+            Err(source) => Err(RegistryError::Io { path, source }),
+        }
+    }
+
+    /// Register `schema` as `name` v`version`.  Fails if the version exists
+    /// with other content, is older than the latest, or is not backward
+    /// compatible with the latest; nothing is written in that case.
+    pub fn register(
+        &self,
+        name: &str,
+        version: u32,
+        schema: &Value,
+    ) -> Result<Registration, RegistryError> {
+        let path = self.path(name, version);
+        if !schema.is_object() {
+            return Err(RegistryError::InvalidSchema {
+                path,
+                message: "a schema must be a JSON object".to_string(),
+            });
+        }
+        let versions = self.versions(name)?;
+        if versions.contains(&version) {
+            return match self.get(name, version)? == *schema {
+                true => Ok(Registration::Unchanged),
+                false => Err(RegistryError::VersionExists {
+                    name: name.to_string(),
+                    version,
+                }),
+            };
+        }
+        if let Some(&latest) = versions.last() {
+            if version < latest {
+                return Err(RegistryError::StaleVersion {
+                    name: name.to_string(),
+                    version,
+                    latest,
+                });
+            }
+            let problems = backward_incompatibilities(&self.get(name, latest)?, schema);
+            if !problems.is_empty() {
+                return Err(RegistryError::Incompatible {
+                    name: name.to_string(),
+                    version,
+                    against: latest,
+                    problems,
+                });
+            }
+        }
+        let dir = self.root.join(name);
+        fs::create_dir_all(&dir).map_err(|source| RegistryError::Io { path: dir, source })?;
+        let pretty = serde_json::to_string_pretty(schema).expect("a Value always serializes");
+        fs::write(&path, pretty + "\n").map_err(|source| RegistryError::Io { path, source })?;
+        Ok(Registration::Created)
+    }
+
+    fn path(&self, name: &str, version: u32) -> PathBuf {
+        self.root.join(name).join(format!("v{version}.json"))
+    }
+}
+
+/// Fetches schemas from a [`Registry`] and keeps them for the life of the
+/// process, so each schema is read once however often it is used.
+#[derive(Debug)]
+pub struct SchemaClient {
+    registry: Registry,
+    cache: BTreeMap<(String, u32), Value>,
+}
+
+impl SchemaClient {
+    pub fn new(registry: Registry) -> Self {
+        Self {
+            registry,
+            cache: BTreeMap::new(),
+        }
+    }
+
+    pub fn fetch(&mut self, name: &str, version: u32) -> Result<&Value, RegistryError> {
+        let key = (name.to_string(), version);
+        if !self.cache.contains_key(&key) {
+            let schema = self.registry.get(name, version)?;
+            self.cache.insert(key.clone(), schema);
+        }
+        Ok(&self.cache[&key])
+    }
+
+    /// Fetch the schema for a versioned event type such as
+    /// `image.analyzed.v1`.
+    pub fn fetch_event(&mut self, event_type: &str) -> Result<&Value, RegistryError> {
+        let (name, version) = split_event_type(event_type)
+            .ok_or_else(|| RegistryError::InvalidName(event_type.to_string()))?;
+        self.fetch(name, version)
+    }
+
+    /// Schemas fetched so far.
+    pub fn cached(&self) -> usize {
+        self.cache.len()
+    }
+}
+
+/// Split `image.analyzed.v1` into `("image.analyzed", 1)`.
+pub fn split_event_type(event_type: &str) -> Option<(&str, u32)> {
+    let (name, version) = event_type.rsplit_once(".v")?;
+    Some((name, version.parse().ok()?))
+}
+
+/// Ways in which `new` rejects events that `old` accepted, each prefixed
+/// with the JSON path it applies to.  Empty means `new` is backward
+/// compatible.  Covers the keywords the chapter's schemas use: `type`,
+/// `required`, `properties`, `additionalProperties`, `items`, `enum`,
+/// `minimum` and `maximum`.
+pub fn backward_incompatibilities(old: &Value, new: &Value) -> Vec<String> {
+    let mut problems = Vec::new();
+    compare("$", old, new, &mut problems);
+    problems
+}
+
+fn compare(path: &str, old: &Value, new: &Value, out: &mut Vec<String>) {
+    let (old_types, new_types) = (types(old), types(new));
+    if !new_types.is_empty() && old_types.iter().any(|t| !new_types.contains(t)) {
+        out.push(format!(
+            "{path}: type changed from {} to {}",
+            old_types.join("|"),
+            new_types.join("|")
+        ));
+    }
+    let old_required = strings(&old["required"]);
+    for field in strings(&new["required"]) {
+        if !old_required.contains(&field) {
+            out.push(format!("{path}: `{field}` is newly required"));
+        }
+    }
+    let closed = new["additionalProperties"] == Value::Bool(false);
+    if closed && old["additionalProperties"] != Value::Bool(false) {
+        out.push(format!(
+            "{path}: additional properties are no longer allowed"
+        ));
+    }
+    if let Some(old_properties) = old["properties"].as_object() {
+        for (field, old_field) in old_properties {
+            match new["properties"].get(field) {
+                Some(new_field) => compare(&format!("{path}.{field}"), old_field, new_field, out),
+                None if closed => out.push(format!("{path}: `{field}` was removed")),
+                None => {}
+            }
+        }
+    }
+    for key in ["items", "additionalProperties"] {
+        if old[key].is_object() && new[key].is_object() {
+            let suffix = if key == "items" { "[]" } else { ".*" };
+            compare(&format!("{path}{suffix}"), &old[key], &new[key], out);
+        }
+    }
+    if let Some(allowed) = new["enum"].as_array() {
+        match old["enum"].as_array() {
+            Some(before) => {
+                for value in before.iter().filter(|v| !allowed.contains(v)) {
+                    out.push(format!("{path}: enum value {value} was removed"));
+                }
+            }
+            None => out.push(format!("{path}: values are newly restricted to an enum")),
+        }
+    }
+    let bound = |schema: &Value, key: &str| schema[key].as_f64();
+    match (bound(old, "minimum"), bound(new, "minimum")) {
+        (Some(before), Some(after)) if after > before => {
+            out.push(format!("{path}: minimum raised from {before} to {after}"))
+        }
+        (None, Some(after)) => out.push(format!("{path}: minimum {after} was added")),
+        _ => {}
+    }
+    match (bound(old, "maximum"), bound(new, "maximum")) {
+        (Some(before), Some(after)) if after < before => {
+            out.push(format!("{path}: maximum lowered from {before} to {after}"))
+        }
+        (None, Some(after)) => out.push(format!("{path}: maximum {after} was added")),
+        _ => {}
+    }
+}
+
+fn types(schema: &Value) -> Vec<String> {
+    match &schema["type"] {
+        Value::String(t) => vec![t.clone()],
+        other => strings(other),
+    }
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn check_name(name: &str) -> Result<(), RegistryError> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-'));
+    match valid {
+        true => Ok(()),
+        false => Err(RegistryError::InvalidName(name.to_string())),
+    }
+}
+
+/// Entries of `dir`; a missing directory has none.
+fn read_dir(dir: &Path) -> Result<Vec<fs::DirEntry>, RegistryError> {
+    let io_err = |source| RegistryError::Io {
+        path: dir.to_path_buf(),
+        source,
+    };
+    match fs::read_dir(dir) {
+        Ok(entries) => entries.collect::<Result<_, _>>().map_err(io_err),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(io_err(err)),
+    }
+}
+
+fn parse_schema(path: &Path, raw: &str) -> Result<Value, RegistryError> {
+    serde_json::from_str(raw).map_err(|err| RegistryError::InvalidSchema {
+        path: path.to_path_buf(),
+        message: err.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_registry(label: &str) -> Registry {
+        Registry::open(std::env::temp_dir().join(format!(
+            "chapter7-registry-{label}-{}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        )))
+    }
+
+    fn analyzed() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "tags"],
+            "properties": {
+                "id": {"type": "string"},
+                "tags": {"type": "array", "items": {"type": "string"}},
+                "confidence": {"type": "object", "additionalProperties": {"type": "number", "minimum": 0, "maximum": 1}}
+            }
+        })
+    }
+
+    #[test]
+    fn register_is_immutable_and_forward_only() {
+        let registry = temp_registry("versions");
+        let schema = analyzed();
+        assert_eq!(
+            registry.register("image.analyzed", 1, &schema).unwrap(),
+            Registration::Created
+        );
+        assert_eq!(
+            registry.register("image.analyzed", 1, &schema).unwrap(),
+            Registration::Unchanged
+        );
+        let mut edited = schema.clone();
+        edited["title"] = json!("image.analyzed.v1");
+        assert!(matches!(
+            registry.register("image.analyzed", 1, &edited),
+            Err(RegistryError::VersionExists { .. })
+        ));
+        registry.register("image.analyzed", 3, &edited).unwrap();
+        assert!(matches!(
+            registry.register("image.analyzed", 2, &edited),
+            Err(RegistryError::StaleVersion { latest: 3, .. })
+        ));
+        assert_eq!(registry.versions("image.analyzed").unwrap(), [1, 3]);
+        assert_eq!(registry.names().unwrap(), ["image.analyzed"]);
+        assert!(matches!(
+            registry.register("../escape", 1, &schema),
+            Err(RegistryError::InvalidName(_))
+        ));
+        fs::remove_dir_all(registry.root()).unwrap();
+    }
+
+    #[test]
+    fn rejects_backward_incompatible_versions() {
+        let registry = temp_registry("compat");
+        registry.register("image.analyzed", 1, &analyzed()).unwrap();
+
+        let mut breaking = analyzed();
+        breaking["required"] = json!(["id", "tags", "confidence"]);
+        breaking["properties"]["id"]["type"] = json!("integer");
+        breaking["properties"]["confidence"]["additionalProperties"]["maximum"] = json!(0.9);
+        let err = registry
+            .register("image.analyzed", 2, &breaking)
+            .unwrap_err();
+        let RegistryError::Incompatible { problems, .. } = &err else {
+            panic!("expected an incompatibility, got {err}");
+        };
+        assert_eq!(
+            problems,
+            &[
+                "$: `confidence` is newly required",
+                "$.confidence.*: maximum lowered from 1 to 0.9",
+                "$.id: type changed from string to integer",
+            ]
+        );
+        assert_eq!(registry.versions("image.analyzed").unwrap(), [1]);
+
+        let mut additive = analyzed();
+        additive["properties"]["source"] = json!({"type": "string"});
+        additive["properties"]["id"]["type"] = json!(["string", "null"]);
+        registry.register("image.analyzed", 2, &additive).unwrap();
+        fs::remove_dir_all(registry.root()).unwrap();
+    }
+
+    #[test]
+    fn client_fetches_once_and_caches() {
+        let registry = temp_registry("client");
+        registry.register("image.analyzed", 1, &analyzed()).unwrap();
+        let mut client = SchemaClient::new(registry.clone());
+        assert_eq!(
+            client.fetch_event("image.analyzed.v1").unwrap(),
+            &analyzed()
+        );
+
+        // Served from the cache even after the store goes away.
+        fs::remove_dir_all(registry.root()).unwrap();
+        assert!(client.fetch("image.analyzed", 1).is_ok());
+        assert_eq!(client.cached(), 1);
+        assert!(matches!(
+            client.fetch_event("image.analyzed.v2"),
+            Err(RegistryError::NotFound { version: 2, .. })
+        ));
+        assert!(client.fetch_event("image.analyzed").is_err());
+    }
+}
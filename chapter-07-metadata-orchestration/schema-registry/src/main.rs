@@ -0,0 +1,110 @@
+use serde_json::Value;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use uma_schema_registry::{split_event_type, Registration, Registry};
+
+const USAGE: &str = "Usage: schema-registry [--root DIR] <command>
+
+Commands:
+  register <name> <version> <file>  register one schema version
+  import <dir>                      register every <name>.v<N>.json in dir
+  get <name> <version>              print a registered schema
+  list                              print every name and its versions
+
+DIR defaults to $SCHEMA_REGISTRY_DIR, then ./registry.";
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("[error] {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "--help" || a == "-h") {
+        eprintln!("{USAGE}");
+        return Ok(());
+    }
+    let root = match args.iter().position(|a| a == "--root") {
+        Some(at) if at + 1 < args.len() => {
+            let root = args.remove(at + 1);
+            args.remove(at);
+            root
+        }
+        Some(_) => return Err("--root needs a directory".into()),
+        None => std::env::var("SCHEMA_REGISTRY_DIR").unwrap_or_else(|_| "registry".to_string()),
+    };
+    let registry = Registry::open(root);
+
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["register", name, version, file] => {
+            register(&registry, name, version.parse()?, Path::new(file))
+        }
+        ["import", dir] => import(&registry, Path::new(dir)),
+        ["get", name, version] => {
+            let schema = registry.get(name, version.parse()?)?;
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            Ok(())
+        }
+        ["list"] => {
+            for name in registry.names()? {
+                let versions: Vec<String> = registry
+                    .versions(&name)?
+                    .iter()
+                    .map(|v| format!("v{v}"))
+                    .collect();
+                println!("{name} {}", versions.join(" "));
+            }
+            Ok(())
+        }
+        _ => Err(USAGE.into()),
+    }
+}
+
+fn register(
+    registry: &Registry,
+    name: &str,
+    version: u32,
+    file: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let schema: Value = serde_json::from_str(&fs::read_to_string(file)?)
+        .map_err(|err| format!("{}: {err}", file.display()))?;
+    let outcome = match registry.register(name, version, &schema)? {
+        Registration::Created => "registered",
+        Registration::Unchanged => "unchanged",
+    };
+    println!("[info] schema.{outcome} {name} v{version}");
+    Ok(())
+}
+
+/// Register every versioned schema file in `dir`, lowest version first so
+/// each one is checked against its predecessor.
+fn import(registry: &Registry, dir: &Path) -> Result<(), Box<dyn Error>> {
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let stem = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_string();
+        if let Some((name, version)) = split_event_type(&stem) {
+            found.push((name.to_string(), version, path));
+        }
+    }
+    found.sort();
+    for (name, version, path) in found {
+        register(registry, &name, version, &path)?;
+    }
+    Ok(())
+}
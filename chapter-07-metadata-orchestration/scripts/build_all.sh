@@ -17,6 +17,11 @@ pushd services/edge.cache >/dev/null
   ./build.sh
 popd >/dev/null
 
+echo "Building schema registry (Rust)"
+pushd schema-registry >/dev/null
+  cargo build --locked
+popd >/dev/null
+
 echo "Building cloud runner (Rust)"
 pushd runtime-rust >/dev/null
   cargo build --locked
@@ -45,5 +45,8 @@ if [ ! -f runtime-rust/Cargo.toml ]; then
   echo "Rust cloud runner is missing."
   exit 1
 fi
+# The runner fetches event schemas from the file-backed registry at startup.
+# Seed it from contracts/schemas; re-registering an unchanged schema is a no-op.
+cargo run --locked --quiet --manifest-path schema-registry/Cargo.toml -- import contracts/schemas >/dev/null
 # The quick-start path is fail-open so readers can see the full orchestration flow.
 POLICY_FAIL_MODE="${POLICY_FAIL_MODE:-open}" cargo run --locked --quiet --manifest-path runtime-rust/Cargo.toml
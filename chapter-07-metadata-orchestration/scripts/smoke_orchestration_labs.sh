@@ -6,6 +6,8 @@ if [ ! -d node_modules ]; then
 fi
 
 ./scripts/validate_contracts.sh
+cargo test --locked --manifest-path schema-registry/Cargo.toml
+cargo test --locked --manifest-path provenance/Cargo.toml
 cargo test --locked --manifest-path runtime-rust/Cargo.toml
 npm test
 ./scripts/list_labs.sh
@@ -5,8 +5,13 @@ test -f contracts/image.tagger.contract.yaml
 test -f contracts/telemetry.logger.contract.yaml
 test -f contracts/edge.cache.contract.yaml
 test -f contracts/ai.workflow.orchestrator.contract.yaml
+test -f contracts/pipeline.manifest.yaml
 jq . contracts/schemas/image.analyzed.v1.json >/dev/null
 jq . contracts/schemas/telemetry.validation.v1.json >/dev/null
 jq . contracts/schemas/uma.cloudevents.ext.json >/dev/null
 jq . contracts/schemas/policy.standard.v1.json >/dev/null
+jq . contracts/schemas/cache.query.v1.json >/dev/null
+jq . contracts/schemas/cache.query.result.v1.json >/dev/null
+jq . contracts/schemas/orchestration.summary.v1.json >/dev/null
+jq . contracts/schemas/pipeline.manifest.v1.json >/dev/null
 echo "OK"
@@ -1,5 +1,10 @@
-export function evaluate(evt: { id: string; tags: string[] }) {
+import { signedEnvelope } from "../../../runtime/provenance.mjs";
+
+// Given the signing key the orchestrator issued, the result is returned as a
+// signed inference.completed.v1 envelope instead.
+export function evaluate(evt: { id: string; tags: string[] }, signingKey?: Buffer) {
   // simple rule based scoring
   const score = evt.tags.includes("even") ? 0.7 : 0.3;
-  return { id: evt.id, score };
+  const result = { id: evt.id, score };
+  return signingKey ? signedEnvelope(signingKey, "ai.model.evaluator", "inference.completed.v1", result) : result;
 }
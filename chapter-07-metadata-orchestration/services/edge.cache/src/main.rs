@@ -1,11 +1,55 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::io::{Read, Write};
+use uma_provenance::seal;
 
 #[derive(Deserialize, Serialize)]
-pub struct ImageAnalyzed { pub id: String, pub tags: Vec<String> }
+pub struct ImageAnalyzed {
+    pub id: String,
+    pub tags: Vec<String>,
+    // Kept so cache readers can filter on confidence; raw_scores are debug-only and not cached.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")] pub confidence: BTreeMap<String, f64>,
+    /// Envelope `time` of the analyzed event, used by time-range queries.
+    #[serde(default, skip_serializing_if = "Option::is_none")] pub time: Option<String>,
+}
+
+/// `cache.query.v1`: every given filter must match.  `since`/`until` are inclusive
+/// envelope timestamps; entries without a time never match a time range.
+#[derive(Deserialize)]
+pub struct Query {
+    #[serde(default)] pub id: Option<String>,
+    #[serde(default)] pub tag: Option<String>,
+    #[serde(default)] pub since: Option<String>,
+    #[serde(default)] pub until: Option<String>,
+}
+
+/// The service reads one event per run, dispatched on its `type`: a query when it is
+/// `cache.query.v1`, an `image.analyzed.v1` payload to persist when it is absent.
+pub enum Request { Query(Query), Write(ImageAnalyzed) }
+
+impl Request {
+    /// Parse a request; the error names the event it was taken for and why it failed.
+    pub fn parse(buf: &str) -> Result<Request, (String, String)> {
+        let value: serde_json::Value = serde_json::from_str(buf)
+            .map_err(|e| ("image.analyzed.v1".to_string(), format!("invalid JSON: {e}")))?;
+        match value.get("type").cloned() {
+            None => serde_json::from_value(value).map(Request::Write)
+                .map_err(|e| ("image.analyzed.v1".to_string(), format!("invalid image.analyzed.v1: {e}"))),
+            Some(serde_json::Value::String(kind)) if kind == "cache.query.v1" => serde_json::from_value(value).map(Request::Query)
+                .map_err(|e| (kind.clone(), format!("invalid cache.query.v1: {e}"))),
+            Some(kind) => {
+                let kind = kind.as_str().map_or_else(|| kind.to_string(), str::to_string);
+                Err((kind.clone(), format!("unsupported event type '{kind}'")))
+            }
+        }
+    }
+}
 
 #[derive(Serialize)]
-pub struct Status { pub source: String, pub event: String, pub status: String, pub reason: Option<String> }
+pub struct Status {
+    pub source: String, pub event: String, pub status: String, pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub entries: Option<Vec<ImageAnalyzed>>,
+}
 
 fn persist(evt: &ImageAnalyzed) -> std::io::Result<()> {
     // simple deterministic KV file path for demo
@@ -13,14 +57,170 @@ fn persist(evt: &ImageAnalyzed) -> std::io::Result<()> {
     std::fs::write(path, serde_json::to_string(evt).unwrap())
 }
 
+/// Scan the cache files in the working directory, sorted by id.
+fn load_all() -> std::io::Result<Vec<ImageAnalyzed>> {
+    let mut entries = Vec::new();
+    for dirent in std::fs::read_dir(".")? {
+        let name = dirent?.file_name().to_string_lossy().into_owned();
+        if name.starts_with("cache-") && name.ends_with(".json") {
+            // Skip files that are not cache entries rather than failing the query.
+            if let Ok(entry) = serde_json::from_str::<ImageAnalyzed>(&std::fs::read_to_string(&name)?) {
+                entries.push(entry);
+            }
+        }
+    }
+    entries.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(entries)
+}
+
+fn query(q: &Query) -> Result<Vec<ImageAnalyzed>, String> {
+    filter(q, load_all().map_err(|e| e.to_string())?)
+}
+
+/// The entries that match every filter of `q`, in order.
+fn filter(q: &Query, entries: Vec<ImageAnalyzed>) -> Result<Vec<ImageAnalyzed>, String> {
+    let since = q.since.as_deref().map(parse_time).transpose()?;
+    let until = q.until.as_deref().map(parse_time).transpose()?;
+    Ok(entries.into_iter().filter(|e| {
+        if q.id.as_ref().is_some_and(|id| *id != e.id) { return false; }
+        if q.tag.as_ref().is_some_and(|tag| !e.tags.contains(tag)) { return false; }
+        if since.is_none() && until.is_none() { return true; }
+        match e.time.as_deref().and_then(|t| parse_time(t).ok()) {
+            Some(t) => since.is_none_or(|s| t >= s) && until.is_none_or(|u| t <= u),
+            None => false,
+        }
+    }).collect())
+}
+
+/// Parse an envelope timestamp into Unix seconds.  Accepts both forms the runners
+/// write: `<seconds>Z` (Rust) and RFC 3339 UTC such as `2025-01-02T03:04:05.678Z` (TypeScript).
+/// Fields out of range, such as month 13 or hour 24, are rejected.
+fn parse_time(t: &str) -> Result<i64, String> {
+    let bad = || format!("invalid timestamp '{t}'");
+    let body = t.strip_suffix('Z').ok_or_else(bad)?;
+    if let Ok(secs) = body.parse::<i64>() { return Ok(secs); }
+    let (date, time) = body.split_once('T').ok_or_else(bad)?;
+    let num = |s: &str, len: usize| if s.len() == len && s.bytes().all(|b| b.is_ascii_digit()) {
+        Ok(s.parse::<i64>().unwrap_or_default())
+    } else { Err(bad()) };
+    let (time, fraction) = time.split_once('.').unwrap_or((time, "0"));
+    if fraction.is_empty() || !fraction.bytes().all(|b| b.is_ascii_digit()) { return Err(bad()); }
+    let d: Vec<&str> = date.split('-').collect();
+    let h: Vec<&str> = time.split(':').collect();
+    if d.len() != 3 || h.len() != 3 { return Err(bad()); }
+    let (y, m, day) = (num(d[0], 4)?, num(d[1], 2)?, num(d[2], 2)?);
+    let (hour, minute, second) = (num(h[0], 2)?, num(h[1], 2)?, num(h[2], 2)?);
+    let leap = y % 4 == 0 && (y % 100 != 0 || y % 400 == 0);
+    let month_days = match m { 2 if leap => 29, 2 => 28, 4 | 6 | 9 | 11 => 30, _ => 31 };
+    if !(1..=12).contains(&m) || !(1..=month_days).contains(&day) || hour > 23 || minute > 59 || second > 59 {
+        return Err(bad());
+    }
+    // Days since the Unix epoch for a proleptic Gregorian date (Howard Hinnant's algorithm).
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day - 1;
+    let days = era * 146_097 + yoe * 365 + yoe / 4 - yoe / 100 + doy - 719_468;
+    Ok(days * 86_400 + hour * 3_600 + minute * 60 + second)
+}
+
+fn status(event: &str, res: Result<Option<Vec<ImageAnalyzed>>, String>) -> Status {
+    let (status, reason, entries) = match res {
+        Ok(entries) => ("passed", None, entries),
+        Err(e) => ("failed", Some(e), None),
+    };
+    Status { source: "edge.cache".into(), event: event.into(), status: status.into(), reason, entries }
+}
+
+/// Given a key in `UMA_SIGNING_KEY`, the status goes out in an envelope signed with it:
+/// `cache.query.result.v1` for a query and `cache.persisted.v1` otherwise.
 fn main() {
     let mut buf = String::new();
-    std::io::stdin().read_to_string(&mut buf).unwrap();
-    let evt: ImageAnalyzed = serde_json::from_str(&buf).unwrap();
-    let res = persist(&evt);
-    let out = match res {
-        Ok(_) => Status { source: "edge.cache".into(), event: "image.analyzed.v1".into(), status: "passed".into(), reason: None },
-        Err(e) => Status { source: "edge.cache".into(), event: "image.analyzed.v1".into(), status: "failed".into(), reason: Some(e.to_string()) }
+    let out = match std::io::stdin().read_to_string(&mut buf).map_err(|e| e.to_string()) {
+        Err(e) => status("image.analyzed.v1", Err(e)),
+        Ok(_) => match Request::parse(&buf) {
+            Ok(Request::Query(q)) => status("cache.query.v1", query(&q).map(Some)),
+            Ok(Request::Write(evt)) => status("image.analyzed.v1", persist(&evt).map(|_| None).map_err(|e| e.to_string())),
+            Err((event, reason)) => status(&event, Err(reason)),
+        },
     };
+    let emitted = if out.event == "cache.query.v1" { "cache.query.result.v1" } else { "cache.persisted.v1" };
+    let out = serde_json::to_value(&out).unwrap();
+    let out = seal("edge.cache", emitted, out).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1)
+    });
     std::io::stdout().write_all(serde_json::to_string(&out).unwrap().as_bytes()).unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, tags: &[&str], time: Option<&str>) -> ImageAnalyzed {
+        ImageAnalyzed {
+            id: id.into(), tags: tags.iter().map(|t| t.to_string()).collect(),
+            confidence: BTreeMap::new(), time: time.map(str::to_string),
+        }
+    }
+
+    fn ids(q: &str) -> Result<Vec<String>, String> {
+        let entries = vec![
+            entry("img-001", &["cat", "indoor"], Some("2025-01-02T03:04:05.678Z")),
+            entry("img-002", &["dog"], Some("1735787045Z")),
+            entry("img-003", &["cat"], None),
+        ];
+        let Ok(Request::Query(q)) = Request::parse(q) else { panic!("not a query: {q}") };
+        Ok(filter(&q, entries)?.into_iter().map(|e| e.id).collect())
+    }
+
+    #[test]
+    fn parses_both_timestamp_forms() {
+        assert_eq!(parse_time("2025-01-02T03:04:05.678Z"), Ok(1_735_787_045));
+        assert_eq!(parse_time("2025-01-02T03:04:05Z"), Ok(1_735_787_045));
+        assert_eq!(parse_time("1735787045Z"), Ok(1_735_787_045));
+        assert_eq!(parse_time("1970-01-01T00:00:00Z"), Ok(0));
+        assert_eq!(parse_time("2024-02-29T23:59:59Z"), Ok(1_709_251_199));
+    }
+
+    #[test]
+    fn rejects_out_of_range_and_malformed_timestamps() {
+        for t in [
+            "2025-13-01T00:00:00Z", "2025-00-01T00:00:00Z", "2025-01-32T00:00:00Z",
+            "2025-02-29T00:00:00Z", "2025-04-31T00:00:00Z", "2025-01-02T99:00:00Z",
+            "2025-01-02T24:00:00Z", "2025-01-02T03:60:00Z", "2025-01-02T03:04:60Z",
+            "2025-01-02T03:04:05", "2025-1-2T03:04:05Z", "2025-01-02T03:04:05.Z",
+            "2025-01-02 03:04:05Z", "yesterday",
+        ] {
+            assert!(parse_time(t).is_err(), "{t}");
+        }
+    }
+
+    #[test]
+    fn filters_by_id_tag_and_time_range() {
+        let q = |body: &str| ids(&format!(r#"{{"type":"cache.query.v1"{body}}}"#));
+        assert_eq!(q(""), Ok(vec!["img-001".into(), "img-002".into(), "img-003".into()]));
+        assert_eq!(q(r#","id":"img-002""#), Ok(vec!["img-002".into()]));
+        assert_eq!(q(r#","tag":"cat""#), Ok(vec!["img-001".into(), "img-003".into()]));
+        assert_eq!(q(r#","id":"img-002","tag":"cat""#), Ok(vec![]));
+        // Bounds are inclusive and entries without a time never match a range.
+        assert_eq!(q(r#","since":"2025-01-02T03:04:05Z""#), Ok(vec!["img-001".into(), "img-002".into()]));
+        assert_eq!(q(r#","until":"1735787044Z""#), Ok(vec![]));
+        assert_eq!(q(r#","tag":"cat","until":"2025-01-02T03:04:05Z""#), Ok(vec!["img-001".into()]));
+        assert!(q(r#","since":"2025-13-01T00:00:00Z""#).is_err());
+    }
+
+    #[test]
+    fn dispatches_on_type_and_reports_bad_input() {
+        assert!(matches!(Request::parse(r#"{"id":"img-001","tags":["cat"]}"#), Ok(Request::Write(_))));
+        assert!(matches!(Request::parse(r#"{"type":"cache.query.v1","id":"img-001"}"#), Ok(Request::Query(_))));
+        let err = |buf: &str| Request::parse(buf).err().unwrap();
+        assert_eq!(err(r#"{"type":"cache.qeury.v1","id":"img-001"}"#),
+            ("cache.qeury.v1".to_string(), "unsupported event type 'cache.qeury.v1'".to_string()));
+        assert_eq!(err(r#"{"type":"cache.query.v1","id":7}"#).0, "cache.query.v1");
+        assert_eq!(err("not json").0, "image.analyzed.v1");
+        assert_eq!(err(r#"{"id":"img-001"}"#).0, "image.analyzed.v1");
+        let out = status("cache.query.v1", Err("bad".into()));
+        assert_eq!((out.status.as_str(), out.reason.as_deref()), ("failed", Some("bad")));
+    }
+}
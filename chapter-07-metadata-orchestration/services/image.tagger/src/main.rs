@@ -1,25 +1,102 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::io::{Read, Write};
+use uma_provenance::seal;
 
 #[derive(Deserialize)]
-pub struct Input { pub id: String, pub bytes: Vec<u8> }
+pub struct Input { pub id: String, pub bytes: Vec<u8>, #[serde(default)] pub config: Config }
+
+/// Tagging configuration, supplied by the runner from the tagger contract's `parameters`.
+#[derive(Deserialize)]
+pub struct Config {
+    /// Tags scoring below this confidence are dropped from the output.
+    #[serde(default = "default_threshold")] pub confidence_threshold: f64,
+    /// When set, every candidate score is echoed in `raw_scores`, including dropped tags.
+    #[serde(default)] pub debug: bool,
+}
+
+fn default_threshold() -> f64 { 0.5 }
+
+impl Default for Config {
+    fn default() -> Self { Config { confidence_threshold: default_threshold(), debug: false } }
+}
 
 #[derive(Serialize)]
-pub struct Output { pub id: String, pub tags: Vec<String> }
+pub struct Output {
+    pub id: String,
+    pub tags: Vec<String>,
+    pub confidence: BTreeMap<String, f64>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub raw_scores: Option<BTreeMap<String, f64>>,
+}
+
+/// Score every candidate tag in [0, 1].  Parity is near-certain; entropy is
+/// the share of the 256 byte values that never occur.
+fn score(bytes: &[u8]) -> Vec<(&'static str, f64)> {
+    let sum: u64 = bytes.iter().map(|b| *b as u64).sum();
+    let (even, odd) = if sum.is_multiple_of(2) { (0.9, 0.1) } else { (0.1, 0.9) };
+    let mut seen = [false; 256];
+    for b in bytes { seen[*b as usize] = true; }
+    let distinct = seen.iter().filter(|s| **s).count() as f64;
+    vec![("even", even), ("odd", odd), ("low-entropy", 1.0 - distinct / 256.0)]
+}
 
 pub fn analyze(input: Input) -> Output {
-    let sum: u64 = input.bytes.iter().map(|b| *b as u64).sum();
-    let tags = if sum % 2 == 0 { vec!["even".to_string(), "low-entropy".to_string()] }
-               else { vec!["odd".to_string(), "low-entropy".to_string()] };
-    Output { id: input.id, tags }
+    let scores = score(&input.bytes);
+    let kept: Vec<_> = scores.iter().filter(|(_, s)| *s >= input.config.confidence_threshold).collect();
+    let tags = kept.iter().map(|(t, _)| t.to_string()).collect();
+    let confidence = kept.iter().map(|(t, s)| (t.to_string(), *s)).collect();
+    let raw_scores = input.config.debug.then(|| scores.iter().map(|(t, s)| (t.to_string(), *s)).collect());
+    Output { id: input.id, tags, confidence, raw_scores }
 }
 
-// WASI entry via stdin/stdout so we can run with wasmtime
+// WASI entry via stdin/stdout so we can run with wasmtime.  Given a key in
+// UMA_SIGNING_KEY, the output is an image.analyzed.v1 envelope signed with it.
 fn main() {
     let mut buf = String::new();
     std::io::stdin().read_to_string(&mut buf).unwrap();
     let input: Input = serde_json::from_str(&buf).unwrap();
-    let out = analyze(input);
+    let out = serde_json::to_value(analyze(input)).unwrap();
+    let out = seal("image.tagger", "image.analyzed.v1", out).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1)
+    });
     let json = serde_json::to_string(&out).unwrap();
     std::io::stdout().write_all(json.as_bytes()).unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(threshold: f64, debug: bool) -> Output {
+        let config = Config { confidence_threshold: threshold, debug };
+        analyze(Input { id: "img-001".into(), bytes: (0..8).collect(), config })
+    }
+
+    #[test]
+    fn small_inputs_score_as_low_entropy() {
+        // 8 distinct byte values out of 256: 1 - 8/256.
+        let scores: BTreeMap<_, _> = score(&(0..8).collect::<Vec<u8>>()).into_iter().collect();
+        assert_eq!(scores["low-entropy"], 0.96875);
+        assert_eq!((scores["even"], scores["odd"]), (0.9, 0.1));
+        assert_eq!(score(&[])[2], ("low-entropy", 1.0));
+        assert_eq!(run(0.5, false).tags, ["even", "low-entropy"]);
+    }
+
+    #[test]
+    fn threshold_is_inclusive() {
+        let out = run(0.96875, false);
+        assert_eq!(out.tags, ["low-entropy"]);
+        assert_eq!(out.confidence.keys().collect::<Vec<_>>(), ["low-entropy"]);
+        assert_eq!(run(0.9, false).tags, ["even", "low-entropy"]);
+        assert!(run(0.97, false).tags.is_empty());
+    }
+
+    #[test]
+    fn raw_scores_include_dropped_tags_only_in_debug() {
+        assert!(run(0.5, false).raw_scores.is_none());
+        let raw = run(0.5, true).raw_scores.unwrap();
+        assert_eq!(raw.get("odd"), Some(&0.1));
+        assert_eq!(raw.len(), 3);
+    }
+}
@@ -1,15 +1,19 @@
 import Ajv from "ajv";
 import * as fs from "node:fs";
+import { signedEnvelope } from "../../../runtime/provenance.mjs";
 
 const ajv = new Ajv({ allErrors: true });
 
-export function createValidator(schemaPath: string) {
+// Given the signing key the orchestrator issued, each status is returned as a
+// signed telemetry.validation.v1 envelope instead.
+export function createValidator(schemaPath: string, signingKey?: Buffer) {
   const schema = JSON.parse(fs.readFileSync(schemaPath, "utf-8"));
   const validate = ajv.compile(schema);
   return (evt: any) => {
     const ok = validate(evt);
-    return ok
+    const status = ok
       ? { source: "telemetry.logger", event: "image.analyzed.v1", status: "passed" }
       : { source: "telemetry.logger", event: "image.analyzed.v1", status: "failed", reason: ajv.errorsText(validate.errors || []) };
+    return signingKey ? signedEnvelope(signingKey, "telemetry.logger", "telemetry.validation.v1", status) : status;
   };
 }
@@ -0,0 +1,50 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+# Build the Chapter 4 evaluator and the Chapter 5 runtime as WASI modules,
+# then run `uma parity` to check that each produces byte-identical output
+# natively and under wasmtime.
+
+ROOT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")/.." && pwd)"
+
+add_local_wasmtime_to_path() {
+  local candidate
+
+  for candidate in "$ROOT_DIR"/.bin/wasmtime-*; do
+    if [[ -x "$candidate/wasmtime" ]]; then
+      export PATH="$candidate:$PATH"
+      return
+    fi
+
+    if [[ -x "$candidate/bin/wasmtime" ]]; then
+      export PATH="$candidate/bin:$PATH"
+      return
+    fi
+  done
+}
+
+add_local_wasmtime_to_path
+
+require_cmd() {
+  if ! command -v "$1" >/dev/null 2>&1; then
+    echo "Missing required command: $1" >&2
+    exit 1
+  fi
+}
+
+require_cmd cargo
+require_cmd rustup
+require_cmd wasmtime
+
+rustup target add wasm32-wasip1 >/dev/null
+
+echo "== Building WASI modules =="
+pushd "$ROOT_DIR/chapter-04-feature-flag-evaluator" >/dev/null
+cargo build --quiet --release --target wasm32-wasip1 -p ff_eval_wasi_app
+popd >/dev/null
+pushd "$ROOT_DIR/chapter-05-post-fetcher-runtime" >/dev/null
+cargo build --quiet --release --target wasm32-wasip1 -p uma_runtime
+popd >/dev/null
+
+echo "== Native vs wasm parity =="
+cargo run --quiet --manifest-path "$ROOT_DIR/uma-cli/Cargo.toml" -- parity
@@ -0,0 +1,258 @@
+#!/usr/bin/env python3
+"""Generate serde contract tests from the examples' JSON schemas.
+
+Each case in `scripts/schema_tests.json` pairs a schema (or the sub-schema at a
+JSON pointer inside it) with the Rust type it describes.  For every case this
+script derives sample documents from the schema and writes an integration test
+into the crate that owns the type:
+
+- a type that deserializes gets a minimal sample (required properties only)
+  and a full sample (every property).  Both must deserialize and re-serialize
+  to a document the schema accepts, and the full sample must survive the
+  round trip unchanged, so a field the type renamed, dropped or retyped fails
+  the test;
+- a serialize-only type lists the Rust statements of a block producing a
+  value, and the serialized value must satisfy the schema.
+
+The tests embed the samples but read the schemas at compile time, so editing a
+schema without regenerating still runs the new schema against the old
+samples.  Run `--check` in CI to fail when the generated files are stale.
+"""
+
+from __future__ import annotations
+
+import argparse
+import json
+import shutil
+import subprocess
+import sys
+from pathlib import Path
+
+
+ROOT = Path(__file__).resolve().parent.parent
+CONFIG_PATH = ROOT / "scripts" / "schema_tests.json"
+TIMESTAMP = "2025-01-01T00:00:00Z"
+
+
+class SampleError(Exception):
+    pass
+
+
+def resolve(node: dict, root: dict) -> dict:
+    while "$ref" in node:
+        ref = node["$ref"]
+        if not ref.startswith("#"):
+            raise SampleError(f"only local $ref is supported, got {ref}")
+        node = pointer_get(root, ref[1:])
+    return node
+
+
+def pointer_get(document: dict, pointer: str):
+    node = document
+    for token in filter(None, pointer.split("/")):
+        token = token.replace("~1", "/").replace("~0", "~")
+        node = node[int(token)] if isinstance(node, list) else node[token]
+    return node
+
+
+def sample(node: dict, root: dict, full: bool, path: str, skip=()):
+    """A document `node` accepts.  The full sample takes every property and
+    the last enum value, since the first is conventionally the default and
+    defaults are often omitted on serialization."""
+    node = resolve(node, root)
+    if "examples" in node:
+        return node["examples"][0]
+    if "const" in node:
+        return node["const"]
+    if "enum" in node:
+        values = [v for v in node["enum"] if v is not None]
+        return values[-1] if full else values[0]
+    for key in ("anyOf", "oneOf"):
+        if key in node:
+            branches = [b for b in node[key] if resolve(b, root).get("type") != "null"]
+            return sample(branches[0], root, full, path)
+    if "allOf" in node:
+        raise SampleError(f"{path}: allOf is not supported; add `examples`")
+
+    kind = node.get("type")
+    if isinstance(kind, list):
+        kind = next(k for k in kind if k != "null")
+    if kind is None:
+        kind = "object" if "properties" in node else None
+
+    if kind == "object":
+        properties = node.get("properties", {})
+        required = node.get("required", [])
+        names = [n for n in properties if full or n in required]
+        out = {}
+        for name in names:
+            if name in skip:
+                continue
+            if name == "$schema":
+                continue
+            out[name] = sample(properties[name], root, full, f"{path}/{name}")
+        extra = node.get("additionalProperties")
+        if full and not properties and isinstance(extra, dict):
+            out["example"] = sample(extra, root, full, f"{path}/example")
+        return out
+    if kind == "array":
+        count = max(node.get("minItems", 0), 1 if full else 0)
+        items = node.get("items", {})
+        return [sample(items, root, full, f"{path}/{i}") for i in range(count)]
+    if kind == "string":
+        if node.get("format") == "date-time":
+            return TIMESTAMP
+        if "pattern" in node:
+            raise SampleError(f"{path}: cannot derive a string for a pattern; add `examples`")
+        return "example"
+    if kind == "integer":
+        return node.get("minimum", 1)
+    if kind == "number":
+        low = node.get("minimum", 0)
+        high = node.get("maximum", low + 1)
+        return (low + high) / 2
+    if kind == "boolean":
+        return True
+    if kind is None:
+        return {}
+    raise SampleError(f"{path}: unsupported type {kind}")
+
+
+def rust_string(value) -> str:
+    text = json.dumps(value, indent=2, sort_keys=True)
+    return 'r#"' + text + '"#' if '"#' not in text else 'r##"' + text + '"##'
+
+
+HEADER = """\
+// @generated by scripts/generate_schema_tests.py from scripts/schema_tests.json.
+// Do not edit: change the schema or the type, then rerun the script.
+"""
+
+HELPERS = """
+use jsonschema::{Draft, JSONSchema};
+use serde_json::Value;
+
+/// The schema at `pointer` inside `source`, with the root's definitions in
+/// scope.  Draft 7 ignores keywords next to `$ref`, so the root's own
+/// constraints do not apply.
+fn compile(source: &str, pointer: &str) -> JSONSchema {
+    let mut schema: Value = serde_json::from_str(source).expect("schema is JSON");
+    if !pointer.is_empty() {
+        schema["$ref"] = Value::String(format!("#{pointer}"));
+    }
+    JSONSchema::options()
+        .with_draft(Draft::Draft7)
+        .compile(&schema)
+        .expect("schema compiles")
+}
+
+fn assert_valid(schema: &JSONSchema, document: &Value, what: &str) {
+    if let Err(errors) = schema.validate(document) {
+        let errors: Vec<String> = errors.map(|e| format!("{}: {e}", e.instance_path)).collect();
+        panic!("{what} does not match the schema: {errors:?}\\n{document:#}");
+    }
+}
+"""
+
+
+def render_case(case: dict, crate_dir: Path) -> str:
+    schema_path = (crate_dir / case["schema"]).resolve()
+    root = json.loads(schema_path.read_text())
+    pointer = case.get("pointer", "")
+    node = pointer_get(root, pointer)
+    include = Path(case["schema"]).as_posix()
+    # Integration tests live in `tests/`, one level below the crate root.
+    include = "../" + include
+    name = case["name"]
+    lines = []
+
+    if "type" in case:
+        rust_type = case["type"]
+        skip = case.get("skip", [])
+        for label, full in (("minimal", False), ("full", True)):
+            document = sample(node, root, full, f"{case['schema']}#{pointer}", skip)
+            lines.append(f"""
+#[test]
+fn {name}_{label}_sample_round_trips() {{
+    let schema = compile(include_str!("{include}"), "{pointer}");
+    let sample: Value = serde_json::from_str({rust_string(document)}).unwrap();
+    assert_valid(&schema, &sample, "the {label} sample");
+    let typed: {rust_type} = serde_json::from_value(sample.clone())
+        .expect("the {label} sample deserializes into {rust_type}");
+    let back = serde_json::to_value(&typed).unwrap();
+    assert_valid(&schema, &back, "{rust_type} serialized");""")
+            if full:
+                lines.append("    assert_eq!(back, sample, \"fields were lost or changed in the round trip\");")
+            lines.append("}")
+    else:
+        body = "\n".join(case["value"])
+        lines.append(f"""
+#[test]
+fn {name}_serializes_to_the_schema() {{
+    let schema = compile(include_str!("{include}"), "{pointer}");
+    let value = serde_json::to_value({{
+{body}
+    }})
+    .unwrap();
+    assert_valid(&schema, &value, "{name}");
+}}""")
+    return "\n".join(lines)
+
+
+def render(target: dict) -> str:
+    crate_dir = ROOT / target["crate"]
+    parts = [HEADER]
+    if "cfg" in target:
+        parts.append(f"#![cfg({target['cfg']})]")
+    parts.append(HELPERS)
+    for case in target["cases"]:
+        parts.append(render_case(case, crate_dir))
+    return rustfmt("\n".join(parts).rstrip() + "\n")
+
+
+def rustfmt(source: str) -> str:
+    """Format like `cargo fmt` would, so the crates stay fmt-clean."""
+    if shutil.which("rustfmt") is None:
+        return source
+    result = subprocess.run(
+        ["rustfmt", "--edition", "2021", "--emit", "stdout"],
+        input=source,
+        capture_output=True,
+        text=True,
+        check=True,
+    )
+    return result.stdout
+
+
+def main() -> int:
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("--check", action="store_true", help="fail if a generated file is stale")
+    args = parser.parse_args()
+
+    stale = []
+    for target in json.loads(CONFIG_PATH.read_text()):
+        output = ROOT / target["crate"] / target["output"]
+        try:
+            text = render(target)
+        except SampleError as err:
+            print(f"{target['crate']}: {err}", file=sys.stderr)
+            return 1
+        if output.exists() and output.read_text() == text:
+            continue
+        if args.check:
+            stale.append(output.relative_to(ROOT))
+            continue
+        output.parent.mkdir(parents=True, exist_ok=True)
+        output.write_text(text)
+        print(f"wrote {output.relative_to(ROOT)}")
+
+    if stale:
+        for path in stale:
+            print(f"stale: {path}", file=sys.stderr)
+        print("Run scripts/generate_schema_tests.py and commit the result.", file=sys.stderr)
+        return 1
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -0,0 +1,363 @@
+//! `uma`: one entry point for the runnable chapter examples.
+//!
+//! Every subcommand calls the library API of the chapter crate that owns the
+//! behavior, so the output matches what that chapter's own binary prints.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::PathBuf;
+use uma_runtime::{Compression, LifecycleStore};
+
+mod parity;
+
+fn usage_text() -> String {
+    [
+        "Usage:",
+        "  uma flags eval [input.json]                        (Chapter 4, reads stdin without a file)",
+        "  uma fetch run [input.json]                         (Chapter 5, reads stdin without a file)",
+        "  uma image analyze [image.pgm] [--contract PATH] [--env ENV]   (Chapter 6)",
+        "  uma contract validate [CONTRACT.json] [--env ENV]  (Chapter 6)",
+        "  uma replay <run-id> [--dir DIR]                    (Chapter 5 lifecycle store)",
+        "  uma replay --spool PATH                            (Chapter 6 bus spool)",
+        "  uma parity [--wasmtime BIN]                        (Chapters 4 and 5, native vs wasm)",
+    ]
+    .join("\n")
+}
+
+/// Positional arguments and `--name value` options of one subcommand.
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>, known: &[&str]) -> Result<Self> {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if known.contains(&name) => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--{name} needs a value"))?;
+                    options.insert(name.to_string(), value);
+                }
+                Some(name) => bail!("unknown option --{name}\n{}", usage_text()),
+                None => positional.push(arg),
+            }
+        }
+        Ok(Self {
+            positional,
+            options,
+        })
+    }
+
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+}
+
+/// Root of the repository checkout, used to find chapter assets.
+fn repo_root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..")
+}
+
+fn chapter6_path(relative: &str) -> String {
+    repo_root()
+        .join("chapter-06-portability-lab")
+        .join(relative)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Read the named file, or standard input when no file (or `-`) is given.
+fn read_input(path: Option<&String>) -> Result<String> {
+    match path.map(String::as_str) {
+        None | Some("-") => {
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input)?;
+            Ok(input)
+        }
+        Some(path) => std::fs::read_to_string(path).with_context(|| format!("reading {path}")),
+    }
+}
+
+fn flags_eval(args: Args) -> Result<String> {
+    let input = read_input(args.positional.first())?;
+    let output = ff_eval_wasi_app::evaluate_document(&input)?;
+    Ok(output.to_string())
+}
+
+fn fetch_run(args: Args) -> Result<String> {
+    let input = read_input(args.positional.first())?;
+    let (output_json, lifecycle_json) = uma_runtime::run_json(&input, None)?;
+    // Persist like the Chapter 5 runtime so `uma replay` can read the run back.
+    if let Ok(dir) = std::env::var("UMA_LIFECYCLE_DIR") {
+        let compression = match std::env::var("UMA_LIFECYCLE_COMPRESSION") {
+            Ok(name) => Compression::parse(&name)?,
+            Err(_) => Compression::None,
+        };
+        let input: serde_json::Value = serde_json::from_str(&input)?;
+        let run_id = input["runId"].as_str().unwrap_or_default();
+        LifecycleStore::open(dir, compression)?.append(run_id, &lifecycle_json)?;
+    }
+    let output: serde_json::Value = serde_json::from_str(&output_json)?;
+    let lifecycle: serde_json::Value = serde_json::from_str(&lifecycle_json)?;
+    Ok(serde_json::to_string_pretty(&json!({
+        "output": output,
+        "lifecycle": lifecycle,
+    }))?)
+}
+
+fn load_contract(args: &Args, path: Option<&String>) -> Result<contract::Contract> {
+    let path = path
+        .cloned()
+        .unwrap_or_else(|| chapter6_path("CONTRACT.json"));
+    let env = args
+        .option("env")
+        .map(str::to_string)
+        .or_else(|| std::env::var("UMA_ENV").ok());
+    contract::Contract::load_for_env(&path, env.as_deref())
+}
+
+fn image_analyze(args: Args) -> Result<String> {
+    let contract = load_contract(&args, args.options.get("contract"))?;
+    let path = args
+        .positional
+        .first()
+        .cloned()
+        .unwrap_or_else(|| chapter6_path("sample-data/sample.pgm"));
+    let result = core_service::analyze_image_data(&path, &contract)?;
+    Ok(serde_json::to_string_pretty(&json!({
+        "service": format!("{}:{}", contract.service.name, contract.service.version),
+        "path": path,
+        "tags": result.tags,
+        "metrics": result.metrics,
+    }))?)
+}
+
+fn contract_validate(args: Args) -> Result<String> {
+    let contract = load_contract(&args, args.positional.first())?;
+    contract.validate()?;
+    Ok(format!(
+        "Validated {}:{}",
+        contract.service.name, contract.service.version
+    ))
+}
+
+fn replay(args: Args) -> Result<String> {
+    if let Some(spool) = args.option("spool") {
+        let mut out = Vec::new();
+        let count = bus::spool::recover_to(spool, &mut out)?;
+        let lines = String::from_utf8(out)?;
+        return Ok(format!("{lines}replayed {count} event(s) from {spool}"));
+    }
+    let run_id = args
+        .positional
+        .first()
+        .ok_or_else(|| anyhow!(usage_text()))?;
+    let dir = match args.option("dir") {
+        Some(dir) => dir.to_string(),
+        None => std::env::var("UMA_LIFECYCLE_DIR")
+            .context("pass --dir or set UMA_LIFECYCLE_DIR to the lifecycle store")?,
+    };
+    // Reading never writes, so the codec here is irrelevant: each entry
+    // records its own.
+    let store = LifecycleStore::open(dir, Compression::None)?;
+    let record = store
+        .read(run_id)?
+        .ok_or_else(|| anyhow!("no lifecycle record for run `{run_id}`"))?;
+    let record: serde_json::Value = serde_json::from_str(&record)?;
+    Ok(serde_json::to_string_pretty(&record)?)
+}
+
+fn parity(args: Args) -> Result<String> {
+    let root = repo_root();
+    let wasmtime = match args.option("wasmtime") {
+        Some(bin) => bin.to_string(),
+        None => std::env::var("WASMTIME").unwrap_or_else(|_| "wasmtime".to_string()),
+    };
+    parity::require_modules(&root)?;
+    let cases = parity::cases(&root)?;
+    parity::check(&cases, |case| parity::run_wasm(&root, &wasmtime, case))
+}
+
+fn run<I>(args: I) -> Result<String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let command = args.next().ok_or_else(|| anyhow!(usage_text()))?;
+    let command = match command.as_str() {
+        "replay" | "parity" => command,
+        _ => format!("{command} {}", args.next().unwrap_or_default()),
+    };
+    match command.as_str() {
+        "flags eval" => flags_eval(Args::parse(args, &[])?),
+        "fetch run" => fetch_run(Args::parse(args, &[])?),
+        "image analyze" => image_analyze(Args::parse(args, &["contract", "env"])?),
+        "contract validate" => contract_validate(Args::parse(args, &["env"])?),
+        "replay" => replay(Args::parse(args, &["dir", "spool"])?),
+        "parity" => parity(Args::parse(args, &["wasmtime"])?),
+        _ => Err(anyhow!(usage_text())),
+    }
+}
+
+fn main() {
+    match run(std::env::args().skip(1)) {
+        Ok(output) => {
+            if !output.is_empty() {
+                println!("{output}");
+            }
+        }
+        Err(err) => {
+            eprintln!("{err:#}");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("uma-cli-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn flags_eval_matches_the_wasi_app() {
+        let vector = repo_root().join("chapter-04-feature-flag-evaluator/tests/vectors/t1.json");
+        let output = run(args(&["flags", "eval", vector.to_str().unwrap()])).unwrap();
+        let output: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(output["key"], "paywall");
+        assert_eq!(output["enabled"], true);
+        assert_eq!(output["matchedRule"], 0);
+    }
+
+    #[test]
+    fn fetch_run_then_replay_reads_the_lifecycle_back() {
+        let dir = scratch_dir("replay");
+        let input = dir.join("input.json");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            &input,
+            r#"{"request":{"url":"uma-fixture://sample-post","headers":{}},"runId":"run-cli"}"#,
+        )
+        .unwrap();
+        let output = run(args(&["fetch", "run", input.to_str().unwrap()])).unwrap();
+        let output: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(output["output"]["normalizedPost"]["id"], 1);
+
+        let lifecycle = serde_json::to_string(&output["lifecycle"]).unwrap();
+        let store_dir = dir.join("store");
+        LifecycleStore::open(&store_dir, Compression::Gzip)
+            .unwrap()
+            .append("run-cli", &lifecycle)
+            .unwrap();
+        let replayed = run(args(&[
+            "replay",
+            "run-cli",
+            "--dir",
+            store_dir.to_str().unwrap(),
+        ]));
+        let replayed: serde_json::Value = serde_json::from_str(&replayed.unwrap()).unwrap();
+        assert_eq!(replayed, output["lifecycle"]);
+        assert!(run(args(&[
+            "replay",
+            "nope",
+            "--dir",
+            store_dir.to_str().unwrap()
+        ]))
+        .is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn replay_spool_reemits_unacknowledged_events() {
+        let dir = scratch_dir("spool");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bus.spool");
+        let mut spool = bus::Spool::open(&path).unwrap();
+        spool.record("{\"event\":\"image.analyzed\"}").unwrap();
+        drop(spool);
+        let output = run(args(&["replay", "--spool", path.to_str().unwrap()])).unwrap();
+        assert!(output.starts_with("{\"event\":\"image.analyzed\"}\n"));
+        assert!(output.ends_with(&format!("replayed 1 event(s) from {}", path.display())));
+        assert!(bus::pending(&path).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn image_analyze_and_contract_validate_use_chapter_six_defaults() {
+        let output = run(args(&["image", "analyze"])).unwrap();
+        let output: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert!(output["tags"]
+            .as_array()
+            .is_some_and(|tags| !tags.is_empty()));
+        assert!(output["metrics"]["width"].as_u64().is_some());
+
+        let validated = run(args(&["contract", "validate", "--env", "prod"])).unwrap();
+        assert!(validated.starts_with("Validated "));
+    }
+
+    #[test]
+    fn parity_cases_are_deterministic_natively() {
+        let cases = parity::cases(&repo_root()).unwrap();
+        assert!(cases.iter().any(|c| c.target == parity::Target::Flags));
+        assert!(cases.iter().any(|c| c.target == parity::Target::Fetch));
+        // A second native run stands in for the wasm module.
+        let report = parity::check(&cases, |case| case.native()).unwrap();
+        assert!(report.ends_with(&format!(
+            "{} case(s) identical on native and wasm",
+            cases.len()
+        )));
+        assert!(report.contains("ok   fetch golden: identical"));
+    }
+
+    #[test]
+    fn parity_reports_the_first_differing_line() {
+        let cases = parity::cases(&repo_root()).unwrap();
+        let golden: Vec<_> = cases.into_iter().filter(|c| c.name == "golden").collect();
+        let err = parity::check(&golden, |case| {
+            Ok(case.native()?.replace("\"t\": \"4\"", "\"t\": \"5\""))
+        })
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("FAIL fetch golden: outputs differ at line"));
+        assert!(err.contains(r#"wasm "        \"t\": \"5\",""#));
+        assert!(err.ends_with("1 of 1 case(s) differ"));
+
+        let err = parity::check(&golden, |_| anyhow::bail!("exited with 1"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("native succeeded, wasm failed: exited with 1"));
+    }
+
+    #[test]
+    fn parity_reports_a_missing_module() {
+        let err = parity::require_modules(&scratch_dir("parity"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("wasm module not found"));
+        assert!(err.contains("-p ff_eval_wasi_app"));
+    }
+
+    #[test]
+    fn unknown_commands_and_options_print_usage() {
+        let err = run(args(&["flags", "explain"])).unwrap_err().to_string();
+        assert!(err.contains("uma replay"));
+        let err = run(args(&["image", "analyze", "--bogus", "x"]))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown option --bogus"));
+    }
+}
@@ -0,0 +1,231 @@
+//! Native vs wasm parity harness.
+//!
+//! Runs the Chapter 4 flag evaluator and the Chapter 5 post fetcher on the
+//! same inputs twice: in process through their libraries, and as WASI
+//! modules under `wasmtime`.  Each case passes only when both sides print the
+//! same bytes, event log included, or both fail.  The post fetcher runs on
+//! its replay adapter so neither side touches the network.  The one field
+//! allowed to differ is the adapter binding's `host`, which names the target
+//! (`native` or `wasm32`); the native document is written with the wasm
+//! value before comparing.
+
+use anyhow::{bail, Context, Result};
+use serde_json::json;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use uma_runtime::ReplayAdapter;
+
+/// Post fetcher inputs: the golden path, fail-fast header validation, a
+/// fixture with no recording, and a scheme the replay adapter rejects.
+const FETCH_INPUTS: [(&str, &str); 4] = [
+    (
+        "golden",
+        r#"{"request":{"url":"uma-fixture://sample-post","headers":{"accept":"application/json"}},"runId":"parity-001"}"#,
+    ),
+    (
+        "header-validation",
+        r#"{"request":{"url":"uma-fixture://sample-post","headers":{"x-foo":"bar"}},"runId":"parity-002"}"#,
+    ),
+    (
+        "missing-recording",
+        r#"{"request":{"url":"uma-fixture://missing","headers":{}},"runId":"parity-003"}"#,
+    ),
+    (
+        "unsupported-scheme",
+        r#"{"request":{"url":"https://example.com/posts/1","headers":{}},"runId":"parity-004"}"#,
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Flags,
+    Fetch,
+}
+
+impl Target {
+    fn name(self) -> &'static str {
+        match self {
+            Target::Flags => "flags",
+            Target::Fetch => "fetch",
+        }
+    }
+
+    /// The release wasm module, relative to the repository root, and the
+    /// command that builds it.
+    fn module(self) -> (&'static str, &'static str) {
+        match self {
+            Target::Flags => (
+                "chapter-04-feature-flag-evaluator/target/wasm32-wasip1/release/ff_eval_wasi_app.wasm",
+                "cargo build --release --target wasm32-wasip1 -p ff_eval_wasi_app",
+            ),
+            Target::Fetch => (
+                "chapter-05-post-fetcher-runtime/target/wasm32-wasip1/release/uma_runtime.wasm",
+                "cargo build --release --target wasm32-wasip1 -p uma_runtime",
+            ),
+        }
+    }
+
+    /// Environment the wasm module runs with.
+    fn env(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Target::Flags => &[],
+            Target::Fetch => &[("UMA_ADAPTER", "replay")],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Case {
+    pub target: Target,
+    pub name: String,
+    pub input: String,
+}
+
+impl Case {
+    /// What the wasm module should print for this input, computed in
+    /// process.  An error means the module should exit non-zero.
+    pub fn native(&self) -> Result<String> {
+        match self.target {
+            Target::Flags => Ok(ff_eval_wasi_app::evaluate_document(&self.input)?.to_string()),
+            Target::Fetch => {
+                let adapter = Box::new(ReplayAdapter::bundled());
+                let (output_json, lifecycle_json) =
+                    uma_runtime::run_json(&self.input, Some(adapter))?;
+                let output: serde_json::Value = serde_json::from_str(&output_json)?;
+                let mut lifecycle: serde_json::Value = serde_json::from_str(&lifecycle_json)?;
+                lifecycle["bindings"]["network.fetch"]["host"] = json!("wasm32");
+                // Same document and layout as the runtime binary prints.
+                let document = json!({ "output": output, "lifecycle": lifecycle });
+                Ok(format!("{}\n", serde_json::to_string_pretty(&document)?))
+            }
+        }
+    }
+}
+
+/// Every case: the Chapter 4 test vectors and lab inputs, then the post
+/// fetcher inputs above.
+pub fn cases(root: &Path) -> Result<Vec<Case>> {
+    let chapter4 = root.join("chapter-04-feature-flag-evaluator");
+    let mut cases = Vec::new();
+    for dir in ["tests/vectors", "labs/inputs"] {
+        let dir = chapter4.join(dir);
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+            .with_context(|| format!("reading {}", dir.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<_>>()?;
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+        paths.sort();
+        for path in paths {
+            cases.push(Case {
+                target: Target::Flags,
+                name: path.file_name().unwrap().to_string_lossy().into_owned(),
+                input: std::fs::read_to_string(&path)
+                    .with_context(|| format!("reading {}", path.display()))?,
+            });
+        }
+    }
+    cases.extend(FETCH_INPUTS.iter().map(|(name, input)| Case {
+        target: Target::Fetch,
+        name: name.to_string(),
+        input: input.to_string(),
+    }));
+    Ok(cases)
+}
+
+/// Fail unless both wasm modules have been built.
+pub fn require_modules(root: &Path) -> Result<()> {
+    for target in [Target::Flags, Target::Fetch] {
+        let (module, build) = target.module();
+        let module = root.join(module);
+        if !module.is_file() {
+            bail!(
+                "wasm module not found at {}; build it in its chapter with: {build}",
+                module.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Run `case` under the `wasmtime` CLI at `wasmtime`, feeding the input on
+/// stdin.  A non-zero exit becomes an error carrying stderr.
+pub fn run_wasm(root: &Path, wasmtime: &str, case: &Case) -> Result<String> {
+    let module = root.join(case.target.module().0);
+    let mut command = Command::new(wasmtime);
+    for (key, value) in case.target.env() {
+        command.arg("--env").arg(format!("{key}={value}"));
+    }
+    let mut child = command
+        .arg(&module)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("starting {wasmtime}; install wasmtime or pass --wasmtime"))?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(case.input.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Compare every case on both sides and report one line per case.  Fails
+/// with the report when any case differs.
+pub fn check(cases: &[Case], run_wasm: impl Fn(&Case) -> Result<String>) -> Result<String> {
+    let mut report = Vec::new();
+    let mut mismatches = 0;
+    for case in cases {
+        let label = format!("{} {}", case.target.name(), case.name);
+        let (ok, verdict) = match (case.native(), run_wasm(case)) {
+            (Ok(native), Ok(wasm)) if native == wasm => (true, "identical".to_string()),
+            (Ok(native), Ok(wasm)) => (false, first_difference(&native, &wasm)),
+            (Err(_), Err(_)) => (true, "both rejected the input".to_string()),
+            (Ok(_), Err(err)) => (false, format!("native succeeded, wasm failed: {err:#}")),
+            (Err(err), Ok(_)) => (false, format!("wasm succeeded, native failed: {err:#}")),
+        };
+        if !ok {
+            mismatches += 1;
+        }
+        report.push(format!(
+            "{} {label}: {verdict}",
+            if ok { "ok  " } else { "FAIL" }
+        ));
+    }
+    let report = report.join("\n");
+    if mismatches > 0 {
+        bail!("{report}\n{mismatches} of {} case(s) differ", cases.len());
+    }
+    Ok(format!(
+        "{report}\n{} case(s) identical on native and wasm",
+        cases.len()
+    ))
+}
+
+/// Describe the first line where the two outputs part ways.
+fn first_difference(native: &str, wasm: &str) -> String {
+    let mut native_lines = native.split('\n');
+    let mut wasm_lines = wasm.split('\n');
+    for line in 1.. {
+        match (native_lines.next(), wasm_lines.next()) {
+            (Some(left), Some(right)) if left == right => continue,
+            (left, right) => {
+                return format!(
+                    "outputs differ at line {line}: native {:?}, wasm {:?}",
+                    left.unwrap_or("<end>"),
+                    right.unwrap_or("<end>")
+                )
+            }
+        }
+    }
+    unreachable!("the line counter is unbounded")
+}